@@ -0,0 +1,37 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! CBOR encoding and decoding of gossip messages.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use link_bench::gossip::{encode, haves, Gossip};
+
+const MESSAGES: usize = 1_000;
+
+fn codec(c: &mut Criterion) {
+    let msgs = haves(MESSAGES);
+    let encoded = encode(&msgs);
+
+    let mut group = c.benchmark_group("gossip");
+    group.throughput(Throughput::Elements(MESSAGES as u64));
+    group.bench_function("encode", |b| {
+        b.iter(|| {
+            for msg in &msgs {
+                minicbor::to_vec(msg).unwrap();
+            }
+        })
+    });
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            for bytes in &encoded {
+                minicbor::decode::<Gossip>(bytes).unwrap();
+            }
+        })
+    });
+    group.finish()
+}
+
+criterion_group!(benches, codec);
+criterion_main!(benches);
@@ -0,0 +1,53 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Verifying the `rad/signed_refs` of many remotes, as done when loading the
+//! tracking graph of a project.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use librad::git::refs::{Signed, Unverified};
+use link_bench::sigrefs::signed;
+
+fn verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sigrefs");
+    for remotes in [10, 100] {
+        let sigrefs = signed(remotes, 100);
+        group.bench_with_input(
+            BenchmarkId::new("from_json", remotes),
+            &sigrefs,
+            |b, sigrefs| {
+                b.iter(|| {
+                    for (peer, json) in sigrefs {
+                        Signed::from_json(json, peer).unwrap();
+                    }
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("verify_batch", remotes),
+            &sigrefs,
+            |b, sigrefs| {
+                b.iter_batched(
+                    || {
+                        sigrefs
+                            .iter()
+                            .map(|(peer, json)| {
+                                let unknown: Signed<Unverified> =
+                                    serde_json::from_slice(json).unwrap();
+                                (unknown, *peer)
+                            })
+                            .collect::<Vec<_>>()
+                    },
+                    |unknown| Signed::verify_batch(unknown).unwrap(),
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish()
+}
+
+criterion_group!(benches, verify);
+criterion_main!(benches);
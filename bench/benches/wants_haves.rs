@@ -0,0 +1,31 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Computing wants and haves from the refs advertised by a peer, against a
+//! large number of remote tracking refs.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use link_bench::wants_haves::fixture;
+use link_replication::BuildWantsHaves;
+
+fn add(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wants_haves");
+    for (remotes, branches) in [(10, 100), (50, 1_000)] {
+        let fix = fixture(remotes, branches);
+        let n = fix.refs.len();
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(BenchmarkId::new("add", n), &fix, |b, fix| {
+            b.iter(|| {
+                let mut bld = BuildWantsHaves::default();
+                bld.add(&fix.db, &fix.refs).unwrap();
+                bld.build().unwrap()
+            })
+        });
+    }
+    group.finish()
+}
+
+criterion_group!(benches, add);
+criterion_main!(benches);
@@ -0,0 +1,254 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Fixtures for the benchmarks in `benches/`.
+//!
+//! The fixtures are sized after what a seed serving popular projects sees:
+//! tens of thousands of remote tracking refs, hundreds of tracked remotes
+//! with their `rad/signed_refs`, and a steady stream of gossip.
+//!
+//! Run with `cargo bench -p link-bench`, or `cargo bench -p link-bench --
+//! --save-baseline <name>` before and `--baseline <name>` after a change to
+//! compare.
+
+pub mod gossip {
+    use std::{iter, net::SocketAddr};
+
+    use librad::{
+        git::Urn,
+        git_ext,
+        net::protocol::{
+            broadcast::Message,
+            gossip::{Payload, Rev},
+            PeerAdvertisement,
+            PeerInfo,
+        },
+        PeerId,
+        SecretKey,
+    };
+
+    pub type Gossip = Message<SocketAddr, Payload>;
+
+    /// `n` `Have` messages, each from a different origin and about a
+    /// different URN.
+    pub fn haves(n: usize) -> Vec<Gossip> {
+        (0..n)
+            .map(|i| {
+                let origin = PeerInfo {
+                    peer_id: PeerId::from(SecretKey::new()),
+                    advertised_info: PeerAdvertisement::new(
+                        ([10, 0, (i / 256) as u8, (i % 256) as u8], 8776).into(),
+                    ),
+                    seen_addrs: iter::empty().into(),
+                };
+                let payload = Payload {
+                    urn: Urn::new(git_ext::Oid::from(
+                        git2::Oid::hash_object(git2::ObjectType::Blob, &i.to_be_bytes()).unwrap(),
+                    ))
+                    .with_path(librad::reflike!("refs/heads/main")),
+                    rev: Some(Rev::Git(
+                        git2::Oid::hash_object(git2::ObjectType::Commit, &i.to_le_bytes()).unwrap(),
+                    )),
+                    origin: Some(PeerId::from(SecretKey::new())),
+                };
+                Message::have(origin, payload)
+            })
+            .collect()
+    }
+
+    pub fn encode(msgs: &[Gossip]) -> Vec<Vec<u8>> {
+        msgs.iter()
+            .map(|msg| minicbor::to_vec(msg).unwrap())
+            .collect()
+    }
+}
+
+pub mod sigrefs {
+    use std::collections::BTreeMap;
+
+    use librad::{
+        git::refs::{Refs, Remotes},
+        git_ext,
+        PeerId,
+        SecretKey,
+    };
+
+    fn oid(s: String) -> git_ext::Oid {
+        git2::Oid::hash_object(git2::ObjectType::Commit, s.as_bytes())
+            .unwrap()
+            .into()
+    }
+
+    /// The JSON-encoded `rad/signed_refs` of `remotes` peers, each with
+    /// `branches` heads and tags, along with the signing peer.
+    pub fn signed(remotes: usize, branches: usize) -> Vec<(PeerId, Vec<u8>)> {
+        (0..remotes)
+            .map(|r| {
+                let key = SecretKey::new();
+                let category = |prefix: &str| {
+                    (0..branches)
+                        .map(|b| {
+                            let name = format!("{}-{}", prefix, b);
+                            let tip = oid(format!("{}/{}", r, name));
+                            (name, tip)
+                        })
+                        .collect::<BTreeMap<_, _>>()
+                };
+                let categorised_refs = [
+                    ("heads".to_owned(), category("branch")),
+                    ("tags".to_owned(), category("v")),
+                    (
+                        "rad".to_owned(),
+                        [("id".to_owned(), oid(format!("{}/rad/id", r)))]
+                            .into_iter()
+                            .collect(),
+                    ),
+                    ("notes".to_owned(), BTreeMap::new()),
+                    ("cobs".to_owned(), BTreeMap::new()),
+                ]
+                .into_iter()
+                .collect();
+                let signed = Refs {
+                    categorised_refs,
+                    remotes: Remotes::new(),
+                }
+                .sign(&key)
+                .unwrap();
+                (PeerId::from(key), serde_json::to_vec(&signed).unwrap())
+            })
+            .collect()
+    }
+}
+
+pub mod wants_haves {
+    use std::{
+        collections::{HashMap, HashSet},
+        convert::Infallible,
+        path::Path,
+    };
+
+    use librad::{PeerId, SecretKey};
+    use link_replication::{
+        odb::Object,
+        oid,
+        refs::{self, parsed::Identity, Qualified},
+        Applied,
+        FilteredRef,
+        ObjectId,
+        Odb,
+        Refdb,
+        Update,
+    };
+
+    /// In-memory refdb and odb, which knows about objects but not their
+    /// contents or ancestry.
+    #[derive(Default)]
+    pub struct Db {
+        refs: HashMap<Qualified<'static>, ObjectId>,
+        objects: HashSet<ObjectId>,
+    }
+
+    impl Refdb for Db {
+        type Oid = ObjectId;
+
+        type FindError = Infallible;
+        type TxError = Infallible;
+        type ReloadError = Infallible;
+
+        fn refname_to_id<'a, Q>(&self, refname: Q) -> Result<Option<Self::Oid>, Self::FindError>
+        where
+            Q: AsRef<Qualified<'a>>,
+        {
+            Ok(self.refs.get(refname.as_ref()).copied())
+        }
+
+        /// Benchmarks only negotiate wants and haves, so updates are never
+        /// applied: all of them are reported as rejected.
+        fn update<'a, I>(&mut self, updates: I) -> Result<Applied<'a>, Self::TxError>
+        where
+            I: IntoIterator<Item = Update<'a>>,
+        {
+            Ok(Applied {
+                rejected: updates.into_iter().collect(),
+                updated: vec![],
+            })
+        }
+
+        fn reload(&mut self) -> Result<(), Self::ReloadError> {
+            Ok(())
+        }
+    }
+
+    impl Odb for Db {
+        type LookupError = Infallible;
+        type RevwalkError = Infallible;
+        type AddPackError = Infallible;
+
+        fn contains(&self, oid: impl AsRef<oid>) -> bool {
+            self.objects.contains(&oid.as_ref().to_owned())
+        }
+
+        fn lookup<'a>(
+            &self,
+            _: impl AsRef<oid>,
+            _: &'a mut Vec<u8>,
+        ) -> Result<Option<Object<'a>>, Self::LookupError> {
+            Ok(None)
+        }
+
+        fn is_in_ancestry_path(
+            &self,
+            _: impl Into<ObjectId>,
+            _: impl Into<ObjectId>,
+        ) -> Result<bool, Self::RevwalkError> {
+            Ok(false)
+        }
+
+        fn add_pack(&self, _: impl AsRef<Path>) -> Result<(), Self::AddPackError> {
+            Ok(())
+        }
+    }
+
+    pub struct Fixture {
+        pub db: Db,
+        pub refs: Vec<FilteredRef<()>>,
+    }
+
+    fn object_id(n: usize) -> ObjectId {
+        ObjectId::from_hex(format!("{:040x}", n).as_bytes()).unwrap()
+    }
+
+    /// `remotes * branches` advertised refs, of which a third is up-to-date,
+    /// a third has moved on, and a third is not yet tracked.
+    pub fn fixture(remotes: usize, branches: usize) -> Fixture {
+        let mut db = Db::default();
+        let mut refs = Vec::with_capacity(remotes * branches);
+        for r in 0..remotes {
+            let remote_id = PeerId::from(SecretKey::new());
+            for b in 0..branches {
+                let n = r * branches + b;
+                let name = format!("refs/heads/branch-{}", b);
+                let parsed = refs::parse::<Identity>(name.as_str().into()).unwrap();
+                let filtered = FilteredRef::new(object_id(2 * n), &remote_id, parsed);
+                let tracking = filtered.to_remote_tracking();
+                match n % 3 {
+                    0 => {
+                        db.refs
+                            .insert((*tracking).clone().into_owned(), filtered.tip);
+                        db.objects.insert(filtered.tip);
+                    },
+                    1 => {
+                        let old = object_id(2 * n + 1);
+                        db.refs.insert((*tracking).clone().into_owned(), old);
+                        db.objects.insert(old);
+                    },
+                    _ => {},
+                }
+                refs.push(filtered);
+            }
+        }
+        Fixture { db, refs }
+    }
+}
@@ -3,11 +3,10 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use linkd_lib::node::run;
+use linkd_lib::node::start;
 
-#[tokio::main]
-async fn main() {
-    if let Err(e) = run().await {
+fn main() {
+    if let Err(e) = start() {
         eprintln!("linkd failed: {:?}", e);
     }
 }
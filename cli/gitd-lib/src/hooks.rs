@@ -13,7 +13,7 @@ use librad::git::{
     Urn,
 };
 use link_async::Spawner;
-use linkd_lib::api::client::Reply;
+use lnk_clib::rpc::client::Reply;
 
 #[derive(Clone)]
 pub(crate) struct Hooks {
@@ -64,7 +64,7 @@ pub(crate) enum Error<E: std::error::Error + Send + 'static> {
     LinkdConnect(#[source] std::io::Error),
     #[error("linkd rpc transport failed: {0}")]
     LinkdTransport(
-        #[source] linkd_lib::api::client::ReplyError<linkd_lib::api::io::SocketTransportError>,
+        #[source] lnk_clib::rpc::client::ReplyError<lnk_clib::rpc::io::SocketTransportError>,
     ),
     #[error("the linkd node reported an error: {0}")]
     Linkd(String),
@@ -146,10 +146,10 @@ impl Hooks {
             report(&mut reporter, "announcing new refs".into()).await?;
             tracing::trace!(?rpc_socket_path, "attempting to send announcement");
             let conn =
-                linkd_lib::api::client::Connection::connect(LINKD_CLIENT_NAME, rpc_socket_path)
+                lnk_clib::rpc::client::Connection::connect(LINKD_CLIENT_NAME, rpc_socket_path)
                     .await
                     .map_err(|e| Error::LinkdConnect(e))?;
-            let cmd = linkd_lib::api::client::Command::announce(urn.clone(), at.into());
+            let cmd = lnk_clib::rpc::client::Command::announce(urn.clone(), at.into());
             let mut replies = cmd
                 .execute_with_reply(conn)
                 .await
@@ -0,0 +1,144 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{fs, io, num::NonZeroU32, path::PathBuf, time::Duration};
+
+use clap::Parser;
+use librad::{
+    net::protocol::{membership, Quota},
+    rate_limit,
+};
+use thiserror::Error;
+
+use crate::{links, sim};
+
+/// Simulate the propagation of announcements through a network of peers, to
+/// help pick membership and rate limiting parameters.
+#[derive(Debug, Parser)]
+#[clap(name = "lnk-gossip-sim")]
+pub struct Args {
+    /// Number of peers in the network.
+    #[clap(long, default_value_t = 100)]
+    pub peers: usize,
+
+    /// Ratio of peers tracking the announced URN.
+    #[clap(long, default_value_t = 0.1)]
+    pub tracking: f64,
+
+    /// Number of announcements to make.
+    #[clap(long, default_value_t = 10)]
+    pub announcements: usize,
+
+    /// Seconds between consecutive announcements.
+    #[clap(long, default_value_t = 10)]
+    pub interval: u64,
+
+    /// Milliseconds it takes to fetch announced updates, in addition to the
+    /// round-trip time to the provider.
+    #[clap(long, default_value_t = 2000)]
+    pub fetch_ms: u64,
+
+    /// Maximum number of active connections, ie. the fanout.
+    #[clap(long, default_value_t = 5)]
+    pub max_active: usize,
+
+    /// Maximum number of passive connections.
+    #[clap(long, default_value_t = 30)]
+    pub max_passive: usize,
+
+    /// Number of hops a join is propagated.
+    #[clap(long, default_value_t = 6)]
+    pub active_random_walk_length: usize,
+
+    /// Number of hops after which a join is added to the passive view.
+    #[clap(long, default_value_t = 3)]
+    pub passive_random_walk_length: usize,
+
+    /// Fetches per minute each peer performs per announcing peer.
+    #[clap(long, default_value = "1")]
+    pub fetch_quota: NonZeroU32,
+
+    /// Burst of fetches each peer performs per announcing peer.
+    #[clap(long, default_value = "5")]
+    pub fetch_quota_burst: NonZeroU32,
+
+    /// File containing measured link samples, one per line, of the form
+    /// `<rtt in ms>[,<loss ratio>]`. Each link between two peers is drawn
+    /// from the samples.
+    #[clap(long)]
+    pub links: Option<PathBuf>,
+
+    /// Round-trip time in milliseconds of all links, if no samples are given.
+    #[clap(long, default_value_t = 100)]
+    pub rtt_ms: u64,
+
+    /// Ratio of lost messages on all links, if no samples are given.
+    #[clap(long, default_value_t = 0.0)]
+    pub loss: f64,
+
+    /// Seed of all randomness. Simulations with the same seed and parameters
+    /// yield the same results.
+    #[clap(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Print the report as JSON.
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("tracking ratio must be in the range [0, 1]")]
+    Tracking,
+
+    #[error("loss ratio must be in the range [0, 1]")]
+    Loss,
+
+    #[error("invalid link samples: {0}")]
+    Links(#[from] links::error::Parse),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Args {
+    pub fn into_config(self) -> Result<sim::Config, Error> {
+        if !(0.0..=1.0).contains(&self.tracking) {
+            return Err(Error::Tracking);
+        }
+        if !(0.0..=1.0).contains(&self.loss) {
+            return Err(Error::Loss);
+        }
+
+        let links = match self.links {
+            Some(path) => links::parse(&fs::read_to_string(path)?)?,
+            None => vec![links::Link {
+                rtt: Duration::from_millis(self.rtt_ms),
+                loss: self.loss,
+            }],
+        };
+        let mut quota = Quota::default();
+        quota.gossip.fetches_per_peer_and_urn =
+            rate_limit::Quota::per_minute(self.fetch_quota).allow_burst(self.fetch_quota_burst);
+
+        Ok(sim::Config {
+            peers: self.peers,
+            membership: membership::Params {
+                max_active: self.max_active,
+                max_passive: self.max_passive,
+                active_random_walk_length: self.active_random_walk_length,
+                passive_random_walk_length: self.passive_random_walk_length,
+                ..membership::Params::default()
+            },
+            quota,
+            tracking: self.tracking,
+            announcements: self.announcements,
+            interval: Duration::from_secs(self.interval),
+            fetch: Duration::from_millis(self.fetch_ms),
+            links,
+            seed: self.seed,
+        })
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! In-memory simulation of gossip propagation.
+//!
+//! Given membership parameters, rate limits and the characteristics of the
+//! links between peers, [`sim::run`] estimates how long announcements take
+//! to reach the peers interested in them, and how many are lost along the
+//! way. This helps operators pick [`librad::net::protocol::Quota`] and
+//! [`librad::net::protocol::membership::Params`] (notably the fanout, ie.
+//! the size of the active view) for their deployment.
+//!
+//! The overlay is formed by the membership state machine of the protocol
+//! proper (see [`overlay`]), while the propagation of announcements is
+//! modelled after the broadcast protocol (see [`sim`]). All randomness is
+//! seeded, so results are reproducible.
+
+pub mod args;
+pub mod links;
+pub mod overlay;
+pub mod sim;
+
+use clap::Parser as _;
+
+pub fn main() {
+    let args = args::Args::parse();
+    let json = args.json;
+    let config = match args.into_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("lnk-gossip-sim: {}", e);
+            std::process::exit(2)
+        },
+    };
+
+    let report = sim::run(&config);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("report is serialisable")
+        );
+    } else {
+        println!("{}", report);
+    }
+}
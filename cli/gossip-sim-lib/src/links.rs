@@ -0,0 +1,97 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Network characteristics of the links between simulated peers.
+//!
+//! Links are drawn from a set of measured samples, such as the smoothed
+//! round-trip times and loss ratios a node keeps for its connected peers (see
+//! [`librad::net::protocol::latency`]).
+
+use std::time::Duration;
+
+use rand::{seq::SliceRandom as _, Rng};
+
+pub mod error {
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    #[error("line {line}: {reason}")]
+    pub struct Parse {
+        pub line: usize,
+        pub reason: String,
+    }
+}
+
+/// A link between two peers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Link {
+    /// Round-trip time of the link.
+    pub rtt: Duration,
+    /// Ratio of lost messages, in the range `[0, 1]`.
+    pub loss: f64,
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Self {
+            rtt: Duration::from_millis(100),
+            loss: 0.0,
+        }
+    }
+}
+
+impl Link {
+    /// The time it takes a message to traverse the link.
+    pub fn delay(&self) -> Duration {
+        self.rtt / 2
+    }
+}
+
+/// Parse link samples, one per line, of the form `<rtt in ms>[,<loss>]`.
+///
+/// Empty lines and lines starting with `#` are ignored.
+pub fn parse(s: &str) -> Result<Vec<Link>, error::Parse> {
+    let mut links = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |reason: &str| error::Parse {
+            line: i + 1,
+            reason: reason.to_owned(),
+        };
+
+        let mut fields = line.split(',').map(str::trim);
+        let rtt = fields
+            .next()
+            .and_then(|rtt| rtt.parse::<f64>().ok())
+            .filter(|rtt| rtt.is_finite() && *rtt >= 0.0)
+            .ok_or_else(|| err("invalid round-trip time"))?;
+        let loss = match fields.next() {
+            None => 0.0,
+            Some(loss) => loss
+                .parse::<f64>()
+                .ok()
+                .filter(|loss| (0.0..=1.0).contains(loss))
+                .ok_or_else(|| err("invalid loss ratio"))?,
+        };
+        if fields.next().is_some() {
+            return Err(err("trailing fields"));
+        }
+
+        links.push(Link {
+            rtt: Duration::from_secs_f64(rtt / 1000.0),
+            loss,
+        });
+    }
+
+    Ok(links)
+}
+
+/// Draw a link from `samples`, or the default [`Link`] if there are none.
+pub fn sample<R: Rng>(samples: &[Link], rng: &mut R) -> Link {
+    samples.choose(rng).copied().unwrap_or_default()
+}
@@ -0,0 +1,233 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! The overlay formed by the membership protocol.
+//!
+//! Every simulated peer runs the [`Hpv`] state machine of the real protocol,
+//! seeded deterministically. Peers join one after the other via a randomly
+//! chosen peer which already joined, and the resulting membership messages
+//! are exchanged until the network settles. Messages are delivered in order
+//! and instantly, as the shape of the overlay does not depend on timing.
+//!
+//! Afterwards, [`PROMOTION_ROUNDS`] rounds of promotions are performed, in
+//! which peers with vacancies in their active view connect to peers from
+//! their passive view, as they would periodically. Shuffles are not
+//! simulated.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    iter,
+};
+
+use librad::{
+    net::protocol::{
+        membership::{Hpv, Message, Params, Tick},
+        PeerAdvertisement,
+        PeerInfo,
+    },
+    PeerId,
+    SecretKey,
+};
+use rand::{rngs::StdRng, seq::SliceRandom as _, Rng as _, SeedableRng as _};
+
+/// Upper bound on the number of membership events processed per joining peer
+/// or promotion round, in case the network does not settle.
+const MAX_EVENTS: usize = 10_000;
+
+/// The number of promotion rounds after all peers joined.
+pub const PROMOTION_ROUNDS: usize = 3;
+
+enum Event {
+    Deliver {
+        from: usize,
+        to: usize,
+        message: Message<usize>,
+    },
+    Connect {
+        from: usize,
+        to: PeerInfo<usize>,
+    },
+    Lost {
+        at: usize,
+        peer: usize,
+    },
+}
+
+struct Network<'a> {
+    ids: &'a [PeerId],
+    index: &'a BTreeMap<PeerId, usize>,
+    hpvs: &'a [Hpv<StdRng, usize>],
+    events: VecDeque<Event>,
+}
+
+impl Network<'_> {
+    /// Process events until there are none left.
+    fn settle(&mut self) {
+        let mut processed = 0;
+        while let Some(event) = self.events.pop_front() {
+            processed += 1;
+            if processed > MAX_EVENTS {
+                self.events.clear();
+                break;
+            }
+
+            let (at, ticks) = match event {
+                Event::Deliver { from, to, message } => {
+                    match self.hpvs[to].apply(self.ids[from], from, message) {
+                        Ok(tnt) => (to, tnt.ticks),
+                        Err(_) => continue,
+                    }
+                },
+                Event::Connect { from, to } => {
+                    let to_idx = self.index[&to.peer_id];
+                    if to_idx == from {
+                        continue;
+                    }
+                    let tnt = self.hpvs[from].connection_established(to.into());
+                    self.events.push_back(Event::Deliver {
+                        from,
+                        to: to_idx,
+                        message: self.hpvs[from].hello(PeerAdvertisement::new(from)),
+                    });
+                    (from, tnt.ticks)
+                },
+                Event::Lost { at, peer } => {
+                    (at, self.hpvs[at].connection_lost(self.ids[peer]).ticks)
+                },
+            };
+
+            for tick in ticks {
+                self.interpret(at, tick)
+            }
+        }
+    }
+
+    fn interpret(&mut self, at: usize, tick: Tick<usize>) {
+        let index = self.index;
+        match tick {
+            Tick::All {
+                recipients,
+                message,
+            } => self
+                .events
+                .extend(recipients.into_iter().map(|to| Event::Deliver {
+                    from: at,
+                    to: index[&to],
+                    message: message.clone(),
+                })),
+            Tick::Reply { to, message } => self.events.push_back(Event::Deliver {
+                from: at,
+                to: index[&to],
+                message,
+            }),
+            Tick::Try { recipient, message } => self.events.push_back(Event::Deliver {
+                from: at,
+                to: index[&recipient.peer_id],
+                message,
+            }),
+            Tick::Connect { to } => self.events.push_back(Event::Connect { from: at, to }),
+            Tick::Forget { peer } => self.events.push_back(Event::Lost {
+                at: index[&peer],
+                peer: at,
+            }),
+        }
+    }
+}
+
+/// The active views of all peers, by index.
+#[derive(Clone, Debug)]
+pub struct Overlay {
+    active: Vec<Vec<usize>>,
+}
+
+impl Overlay {
+    /// Form the overlay of `peers` peers, using membership `params`.
+    pub fn build(peers: usize, params: &Params, seed: u64) -> Self {
+        // `Hpv` sets up timers for its periodic tasks, which we discard
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .expect("failed to build runtime");
+        let _enter = rt.enter();
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let ids = (0..peers)
+            .map(|_| PeerId::from(SecretKey::from_seed(rng.gen())))
+            .collect::<Vec<_>>();
+        let index = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect::<BTreeMap<_, _>>();
+        let hpvs = ids
+            .iter()
+            .map(|id| {
+                let (hpv, _periodic) =
+                    Hpv::new(*id, StdRng::seed_from_u64(rng.gen()), params.clone());
+                hpv
+            })
+            .collect::<Vec<_>>();
+        let info = |i: usize| PeerInfo {
+            peer_id: ids[i],
+            advertised_info: PeerAdvertisement::new(i),
+            seen_addrs: iter::once(i).into(),
+        };
+
+        let mut net = Network {
+            ids: &ids,
+            index: &index,
+            hpvs: &hpvs,
+            events: VecDeque::new(),
+        };
+        for joining in 1..peers {
+            net.events.push_back(Event::Connect {
+                from: joining,
+                to: info(rng.gen_range(0..joining)),
+            });
+            net.settle();
+        }
+        for _ in 0..PROMOTION_ROUNDS {
+            for (peer, hpv) in hpvs.iter().enumerate() {
+                let vacant = params.max_active.saturating_sub(hpv.active().len());
+                let mut candidates = hpv.passive_info();
+                candidates.shuffle(&mut rng);
+                net.events.extend(
+                    candidates
+                        .into_iter()
+                        .take(vacant)
+                        .map(|to| Event::Connect { from: peer, to }),
+                );
+            }
+            net.settle();
+        }
+
+        let active = hpvs
+            .iter()
+            .map(|hpv| hpv.active().iter().map(|id| index[id]).collect())
+            .collect();
+
+        Self { active }
+    }
+
+    /// The number of peers.
+    pub fn len(&self) -> usize {
+        self.active.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// The active view of `peer`, ie. the peers it sends gossip to.
+    pub fn active(&self, peer: usize) -> &[usize] {
+        &self.active[peer]
+    }
+
+    /// Whether `peer` has `other` in its active view, and so accepts gossip
+    /// from it.
+    pub fn is_active(&self, peer: usize, other: usize) -> bool {
+        self.active[peer].contains(&other)
+    }
+}
@@ -0,0 +1,488 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Simulation of announcement propagation.
+//!
+//! A number of announcements of the same URN are made by random peers
+//! tracking it, and propagated through the [`Overlay`] the way the broadcast
+//! protocol does:
+//!
+//! * A peer which sees an announcement for the first time relays it to its
+//!   active view, except for the peer it received it from.
+//! * Peers tracking the URN fetch from the announcing peer first, and only
+//!   relay once the fetch succeeded, announcing themselves as the provider.
+//!   Fetches are subject to the [`GossipQuota`] per announcing peer: when it is
+//!   exceeded, the announcement is dropped.
+//! * Peers not tracking the URN relay the announcement right away, leaving the
+//!   provider unchanged.
+//! * Peers only accept announcements from peers in their active view.
+//!
+//! Messages take the [`Link::delay`] of the link they are sent over, and are
+//! lost with its [`Link::loss`] ratio. Retransmission requests are not
+//! simulated.
+//!
+//! [`GossipQuota`]: librad::net::protocol::Quota::gossip
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BTreeSet, BinaryHeap},
+    fmt,
+    time::Duration,
+};
+
+use librad::net::protocol::{membership, Quota};
+use rand::{rngs::StdRng, seq::index, Rng as _, SeedableRng as _};
+use serde::Serialize;
+
+use crate::{
+    links::{self, Link},
+    overlay::Overlay,
+};
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Number of peers in the network.
+    pub peers: usize,
+    /// Membership parameters of all peers. [`membership::Params::max_active`]
+    /// determines the fanout of announcements.
+    pub membership: membership::Params,
+    /// Rate limits of all peers.
+    pub quota: Quota,
+    /// Ratio of peers tracking the announced URN, in the range `[0, 1]`.
+    pub tracking: f64,
+    /// Number of announcements to make.
+    pub announcements: usize,
+    /// Time between consecutive announcements.
+    pub interval: Duration,
+    /// Time it takes to fetch announced updates.
+    pub fetch: Duration,
+    /// Link samples, see [`links::parse`]. If empty, all links are
+    /// [`Link::default`].
+    pub links: Vec<Link>,
+    /// Seed of all randomness, so that simulations can be repeated.
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            peers: 100,
+            membership: membership::Params::default(),
+            quota: Quota::default(),
+            tracking: 0.1,
+            announcements: 10,
+            interval: Duration::from_secs(10),
+            fetch: Duration::from_secs(2),
+            links: vec![],
+            seed: 0,
+        }
+    }
+}
+
+/// Minimum, mean and maximum of a number of values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Spread {
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+}
+
+impl Spread {
+    fn of(values: impl IntoIterator<Item = f64>) -> Self {
+        let (mut min, mut max, mut sum, mut n) = (f64::MAX, f64::MIN, 0.0, 0usize);
+        for v in values {
+            min = min.min(v);
+            max = max.max(v);
+            sum += v;
+            n += 1;
+        }
+        if n == 0 {
+            Self::default()
+        } else {
+            Self {
+                min,
+                mean: sum / n as f64,
+                max,
+            }
+        }
+    }
+}
+
+/// Percentiles of propagation times, in milliseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Percentiles {
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl Percentiles {
+    fn of(mut times: Vec<Duration>) -> Self {
+        if times.is_empty() {
+            return Self::default();
+        }
+        times.sort();
+        let at = |p: usize| times[(times.len() - 1) * p / 100].as_millis() as u64;
+        Self {
+            p50: at(50),
+            p90: at(90),
+            p99: at(99),
+            max: at(100),
+        }
+    }
+}
+
+/// The outcome of a simulation.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Report {
+    /// Number of peers in the network.
+    pub peers: usize,
+    /// Number of peers tracking the announced URN.
+    pub tracking: usize,
+    /// Sizes of the active views, ie. the fanout of each peer.
+    pub fanout: Spread,
+    /// Number of announcements made.
+    pub announcements: usize,
+    /// Ratio of tracking peers which fetched an announced update, excluding
+    /// the announcing peer, averaged over all announcements.
+    pub coverage: f64,
+    /// Number of announcements which reached all tracking peers.
+    pub complete: usize,
+    /// Time from announcement until a tracking peer fetched the update.
+    pub propagation_ms: Percentiles,
+    /// Number of messages sent.
+    pub messages: u64,
+    /// Number of messages received by peers which had already seen them.
+    pub redundant: u64,
+    /// Number of messages lost on the way.
+    pub lost: u64,
+    /// Number of messages rejected because the sender was not in the active
+    /// view of the recipient.
+    pub unsolicited: u64,
+    /// Number of fetches skipped because of the gossip quota.
+    pub rate_limited: u64,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "peers:         {} ({} tracking)",
+            self.peers, self.tracking
+        )?;
+        writeln!(
+            f,
+            "fanout:        min {:.0}, mean {:.1}, max {:.0}",
+            self.fanout.min, self.fanout.mean, self.fanout.max
+        )?;
+        writeln!(
+            f,
+            "coverage:      {:.1}% ({} of {} announcements complete)",
+            self.coverage * 100.0,
+            self.complete,
+            self.announcements
+        )?;
+        writeln!(
+            f,
+            "propagation:   p50 {}ms, p90 {}ms, p99 {}ms, max {}ms",
+            self.propagation_ms.p50,
+            self.propagation_ms.p90,
+            self.propagation_ms.p99,
+            self.propagation_ms.max
+        )?;
+        writeln!(
+            f,
+            "messages:      {} sent, {} redundant, {} lost, {} unsolicited",
+            self.messages, self.redundant, self.lost, self.unsolicited
+        )?;
+        write!(f, "rate limited:  {} fetches", self.rate_limited)
+    }
+}
+
+#[derive(Debug)]
+enum Event {
+    Announce {
+        ann: usize,
+        at: usize,
+    },
+    Have {
+        ann: usize,
+        from: usize,
+        to: usize,
+        provider: usize,
+    },
+    Fetched {
+        ann: usize,
+        at: usize,
+        from: usize,
+    },
+}
+
+struct Scheduled {
+    at: Duration,
+    seq: u64,
+    event: Event,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.at, self.seq).cmp(&(other.at, other.seq))
+    }
+}
+
+/// Token bucket equivalent to the rate limiter configured by a
+/// [`librad::rate_limit::Quota`].
+struct Bucket {
+    tokens: f64,
+    updated: Duration,
+}
+
+impl Bucket {
+    fn check(&mut self, now: Duration, burst: f64, replenish: Duration) -> bool {
+        let elapsed = now.saturating_sub(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed / replenish.as_secs_f64()).min(burst);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+struct Sim<'a> {
+    config: &'a Config,
+    overlay: &'a Overlay,
+    rng: StdRng,
+    links: BTreeMap<(usize, usize), Link>,
+    queue: BinaryHeap<Reverse<Scheduled>>,
+    seq: u64,
+    now: Duration,
+    /// Peers which have seen an announcement, by announcement.
+    seen: Vec<BTreeSet<usize>>,
+    /// Fetch quota of each peer, by announcing peer.
+    buckets: BTreeMap<(usize, usize), Bucket>,
+    announced: Vec<Duration>,
+    fetched: Vec<BTreeMap<usize, Duration>>,
+    messages: u64,
+    redundant: u64,
+    lost: u64,
+    unsolicited: u64,
+    rate_limited: u64,
+}
+
+impl<'a> Sim<'a> {
+    fn schedule(&mut self, after: Duration, event: Event) {
+        self.seq += 1;
+        self.queue.push(Reverse(Scheduled {
+            at: self.now + after,
+            seq: self.seq,
+            event,
+        }))
+    }
+
+    fn link(&mut self, a: usize, b: usize) -> Link {
+        let key = (a.min(b), a.max(b));
+        if let Some(link) = self.links.get(&key) {
+            return *link;
+        }
+        let link = links::sample(&self.config.links, &mut self.rng);
+        self.links.insert(key, link);
+        link
+    }
+
+    fn relay(&mut self, ann: usize, at: usize, provider: usize, exclude: Option<usize>) {
+        for to in self.overlay.active(at).to_vec() {
+            if Some(to) == exclude {
+                continue;
+            }
+            self.messages += 1;
+            let link = self.link(at, to);
+            if self.rng.gen_bool(link.loss) {
+                self.lost += 1;
+            } else {
+                self.schedule(
+                    link.delay(),
+                    Event::Have {
+                        ann,
+                        from: at,
+                        to,
+                        provider,
+                    },
+                )
+            }
+        }
+    }
+
+    fn run(&mut self, tracking: &BTreeSet<usize>) {
+        let burst = self
+            .config
+            .quota
+            .gossip
+            .fetches_per_peer_and_urn
+            .burst_size()
+            .get() as f64;
+        let replenish = self
+            .config
+            .quota
+            .gossip
+            .fetches_per_peer_and_urn
+            .replenish_interval();
+
+        while let Some(Reverse(Scheduled { at, event, .. })) = self.queue.pop() {
+            self.now = at;
+            match event {
+                Event::Announce { ann, at } => {
+                    self.announced[ann] = self.now;
+                    self.seen[ann].insert(at);
+                    self.relay(ann, at, at, None);
+                },
+
+                Event::Have {
+                    ann,
+                    from,
+                    to,
+                    provider,
+                } => {
+                    if !self.overlay.is_active(to, from) {
+                        self.unsolicited += 1;
+                        continue;
+                    }
+                    if !self.seen[ann].insert(to) {
+                        self.redundant += 1;
+                        continue;
+                    }
+
+                    if tracking.contains(&to) {
+                        let now = self.now;
+                        let admitted = self
+                            .buckets
+                            .entry((to, provider))
+                            .or_insert(Bucket {
+                                tokens: burst,
+                                updated: now,
+                            })
+                            .check(now, burst, replenish);
+                        if admitted {
+                            let fetch = self.config.fetch + self.link(to, provider).rtt;
+                            self.schedule(fetch, Event::Fetched { ann, at: to, from });
+                        } else {
+                            self.rate_limited += 1;
+                        }
+                    } else {
+                        self.relay(ann, to, provider, Some(from));
+                    }
+                },
+
+                Event::Fetched { ann, at, from } => {
+                    let elapsed = self.now - self.announced[ann];
+                    self.fetched[ann].insert(at, elapsed);
+                    self.relay(ann, at, at, Some(from));
+                },
+            }
+        }
+    }
+}
+
+/// Run a simulation with the given `config`.
+pub fn run(config: &Config) -> Report {
+    let overlay = Overlay::build(config.peers, &config.membership, config.seed);
+    run_on(config, &overlay)
+}
+
+/// Run a simulation with the given `config` on an existing `overlay`, eg. to
+/// compare quotas on the same network.
+pub fn run_on(config: &Config, overlay: &Overlay) -> Report {
+    let peers = overlay.len();
+    let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(1));
+    let tracking = if peers == 0 {
+        BTreeSet::new()
+    } else {
+        let n = ((peers as f64 * config.tracking.clamp(0.0, 1.0)).ceil() as usize).max(1);
+        index::sample(&mut rng, peers, n).into_iter().collect()
+    };
+    let announcers = tracking.iter().copied().collect::<Vec<_>>();
+    let announcements = if announcers.is_empty() {
+        0
+    } else {
+        config.announcements
+    };
+
+    let mut sim = Sim {
+        config,
+        overlay,
+        rng,
+        links: BTreeMap::new(),
+        queue: BinaryHeap::new(),
+        seq: 0,
+        now: Duration::ZERO,
+        seen: vec![BTreeSet::new(); announcements],
+        buckets: BTreeMap::new(),
+        announced: vec![Duration::ZERO; announcements],
+        fetched: vec![BTreeMap::new(); announcements],
+        messages: 0,
+        redundant: 0,
+        lost: 0,
+        unsolicited: 0,
+        rate_limited: 0,
+    };
+    for ann in 0..announcements {
+        let at = announcers[sim.rng.gen_range(0..announcers.len())];
+        sim.schedule(config.interval * ann as u32, Event::Announce { ann, at });
+    }
+    sim.run(&tracking);
+
+    // The announcing peer does not need to fetch
+    let others = tracking.len().saturating_sub(1);
+    let coverage = if others == 0 || announcements == 0 {
+        1.0
+    } else {
+        sim.fetched
+            .iter()
+            .map(|fetched| fetched.len() as f64 / others as f64)
+            .sum::<f64>()
+            / announcements as f64
+    };
+
+    Report {
+        peers,
+        tracking: tracking.len(),
+        fanout: Spread::of((0..peers).map(|i| overlay.active(i).len() as f64)),
+        announcements,
+        coverage,
+        complete: sim
+            .fetched
+            .iter()
+            .filter(|fetched| fetched.len() >= others)
+            .count(),
+        propagation_ms: Percentiles::of(
+            sim.fetched
+                .iter()
+                .flat_map(|fetched| fetched.values().copied())
+                .collect(),
+        ),
+        messages: sim.messages,
+        redundant: sim.redundant,
+        lost: sim.lost,
+        unsolicited: sim.unsolicited,
+        rate_limited: sim.rate_limited,
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use gossip_sim_lib::links::{parse, Link};
+use pretty_assertions::assert_eq;
+
+#[test]
+fn parse_samples() {
+    let links = parse("# rtt,loss\n120,0.01\n\n  80 \n").unwrap();
+    assert_eq!(
+        links,
+        vec![
+            Link {
+                rtt: Duration::from_millis(120),
+                loss: 0.01,
+            },
+            Link {
+                rtt: Duration::from_millis(80),
+                loss: 0.0,
+            }
+        ]
+    )
+}
+
+#[test]
+fn parse_rejects_invalid() {
+    assert_eq!(parse("100\n-1").unwrap_err().line, 2);
+    assert_eq!(parse("100,2").unwrap_err().line, 1);
+    assert_eq!(parse("100,0.1,3").unwrap_err().line, 1);
+}
@@ -0,0 +1,92 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{num::NonZeroU32, time::Duration};
+
+use gossip_sim_lib::{
+    links::Link,
+    overlay::Overlay,
+    sim::{self, Config},
+};
+use librad::{net::protocol::Quota, rate_limit};
+
+fn config() -> Config {
+    Config {
+        peers: 50,
+        tracking: 0.2,
+        announcements: 5,
+        ..Config::default()
+    }
+}
+
+fn fetch_quota(per_minute: u32, burst: u32) -> Quota {
+    let mut quota = Quota::default();
+    quota.gossip.fetches_per_peer_and_urn =
+        rate_limit::Quota::per_minute(NonZeroU32::new(per_minute).unwrap())
+            .allow_burst(NonZeroU32::new(burst).unwrap());
+    quota
+}
+
+#[test]
+fn deterministic() {
+    let config = config();
+    assert_eq!(sim::run(&config), sim::run(&config))
+}
+
+#[test]
+fn overlay_respects_view_size() {
+    let config = config();
+    let overlay = Overlay::build(config.peers, &config.membership, config.seed);
+
+    assert_eq!(overlay.len(), config.peers);
+    assert!(
+        (0..overlay.len()).all(|peer| overlay.active(peer).len() <= config.membership.max_active)
+    );
+    assert!((0..overlay.len()).any(|peer| !overlay.active(peer).is_empty()));
+}
+
+#[test]
+fn lossless() {
+    let config = config();
+    let report = sim::run(&config);
+    assert_eq!(report.tracking, 10);
+    assert_eq!(report.lost, 0);
+    assert!(report.coverage > 0.0);
+    assert!(report.propagation_ms.p50 >= config.fetch.as_millis() as u64);
+}
+
+#[test]
+fn quota_limits_fetches() {
+    let generous = Config {
+        announcements: 20,
+        interval: Duration::from_secs(1),
+        quota: fetch_quota(1000, 1000),
+        ..config()
+    };
+    let overlay = Overlay::build(generous.peers, &generous.membership, generous.seed);
+    let unlimited = sim::run_on(&generous, &overlay);
+    assert_eq!(unlimited.rate_limited, 0);
+
+    let strict = Config {
+        quota: fetch_quota(1, 1),
+        ..generous.clone()
+    };
+    let limited = sim::run_on(&strict, &overlay);
+    assert!(limited.rate_limited > 0);
+    assert!(limited.coverage <= unlimited.coverage);
+}
+
+#[test]
+fn lossy_links_lose_messages() {
+    let config = Config {
+        links: vec![Link {
+            rtt: Duration::from_millis(50),
+            loss: 0.5,
+        }],
+        ..config()
+    };
+    let report = sim::run(&config);
+    assert!(report.lost > 0);
+}
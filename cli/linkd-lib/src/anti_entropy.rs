@@ -0,0 +1,145 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Periodic reconciliation with connected peers.
+//!
+//! Gossip is best-effort: updates announced while the node was offline or
+//! partitioned from the announcing peers are never seen. To close such gaps,
+//! this routine periodically picks a random connected peer, asks it for the
+//! tips of its `rad/signed_refs` of the URNs we track it for (via the
+//! inventory protocol), and replicates the URNs for which our copy is out of
+//! date. Default tracking entries are applied the same way as for gossip.
+//!
+//! Rounds are spaced by a jittered interval, so nodes started at the same time
+//! don't synchronise, and the number of URNs replicated per round is capped.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+use rand::{seq::SliceRandom as _, Rng as _};
+use tracing::{debug, info, instrument, warn};
+
+use librad::{
+    git::{
+        storage::ReadOnlyStorage as _,
+        tracking,
+        types::{Namespace, Reference},
+        Urn,
+    },
+    git_ext::Oid,
+    net::{peer::Peer, protocol::RequestPullGuard},
+    PeerId,
+    Signer,
+};
+
+use crate::replication::Pool;
+
+/// The maximum deviation from [`Config::interval`], as a fraction of it.
+const JITTER: f64 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// The average time between two rounds.
+    pub interval: Duration,
+    /// The maximum number of URNs to replicate per round.
+    pub max_fetches: usize,
+}
+
+#[instrument(name = "anti-entropy subroutine", skip(peer, pool))]
+pub async fn routine<S, G>(peer: Peer<S, G>, pool: Pool, config: Config) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    info!("starting anti-entropy rounds");
+    loop {
+        tokio::time::sleep(jittered(config.interval)).await;
+        if let Err(e) = round(&peer, &pool, config.max_fetches).await {
+            warn!(err = %e, "anti-entropy round failed");
+        }
+    }
+}
+
+fn jittered(interval: Duration) -> Duration {
+    interval.mul_f64(rand::thread_rng().gen_range(1.0 - JITTER..=1.0 + JITTER))
+}
+
+async fn round<S, G>(peer: &Peer<S, G>, pool: &Pool, max_fetches: usize) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let remote = match peer.connected_peers().await.choose(&mut rand::thread_rng()) {
+        Some(remote) => *remote,
+        None => {
+            debug!("no connected peers");
+            return Ok(());
+        },
+    };
+
+    let ours = peer
+        .using_read_only(move |storage| tracked_signed_refs(storage, remote))
+        .await??;
+    if ours.is_empty() {
+        debug!(%remote, "no URNs tracked for peer");
+        return Ok(());
+    }
+
+    let shared = peer
+        .inventory((remote, vec![]), ours.keys().cloned())
+        .await?;
+    let stale = shared
+        .urns
+        .into_iter()
+        .filter(|urn| match shared.signed_refs.get(urn) {
+            Some(theirs) => ours.get(urn) != Some(&Some(*theirs)),
+            None => false,
+        })
+        .take(max_fetches)
+        .collect::<Vec<_>>();
+    debug!(%remote, tracked = ours.len(), stale = stale.len(), "reconciled");
+
+    for urn in stale {
+        match pool.replicate(peer, (remote, vec![]), urn.clone()).await {
+            Ok(_) => info!(%remote, %urn, "replicated missed update"),
+            Err(e) => warn!(%remote, %urn, err = %e, "failed to replicate missed update"),
+        }
+    }
+
+    Ok(())
+}
+
+/// The URNs `remote` is tracked for, along with our copy of its
+/// `rad/signed_refs`, if any.
+///
+/// Like for gossip, a URN is tracked for `remote` if there is an entry for
+/// `remote`, or if the default entry is the only one.
+fn tracked_signed_refs(
+    storage: &librad::git::storage::ReadOnly,
+    remote: PeerId,
+) -> anyhow::Result<BTreeMap<Urn, Option<Oid>>> {
+    let mut urns = BTreeSet::new();
+    for tracked in tracking::tracked(storage, None)? {
+        urns.insert(tracked?.urn().clone());
+    }
+
+    let mut ours = BTreeMap::new();
+    for urn in urns {
+        if !(tracking::is_tracked(storage, &urn, Some(remote))?
+            || tracking::default_only(storage, &urn)?)
+        {
+            continue;
+        }
+        let signed_refs = Reference::rad_signed_refs(Namespace::from(&urn), Some(remote));
+        let tip = storage
+            .reference(&signed_refs)?
+            .and_then(|r| r.target())
+            .map(Oid::from);
+        ours.insert(urn, tip);
+    }
+    Ok(ours)
+}
@@ -5,6 +5,7 @@
 
 use std::{sync::Arc, time::Duration};
 
+use futures::stream::{self, StreamExt as _};
 use tracing::instrument;
 
 use librad::{
@@ -13,22 +14,40 @@ use librad::{
 };
 use link_async::Spawner;
 
+use crate::{replication::Pool, standby::Role};
+
+pub use lnk_clib::rpc::{
+    announce,
+    client,
+    connections,
+    io,
+    messages,
+    pinned,
+    project_stats,
+    replication,
+    request_pull,
+    standby,
+    usage,
+    wire_types,
+};
+pub use remote::Remote;
 pub use sockets::Sockets;
 
-pub mod announce;
-pub mod client;
-pub mod io;
-pub mod messages;
-pub mod request_pull;
+pub mod remote;
 mod rpc;
 pub mod sockets;
-pub mod wire_types;
 
-#[instrument(name = "api subroutine", skip(spawner, peer, sockets))]
+#[instrument(
+    name = "api subroutine",
+    skip(spawner, peer, pool, role, sockets, remote)
+)]
 pub async fn routine<'a, S, G>(
     spawner: Arc<Spawner>,
     peer: Peer<S, G>,
+    pool: Pool,
+    role: Role,
     sockets: &'a Sockets,
+    remote: Option<&'a Remote>,
     linger_timeout: Option<Duration>,
     announce_wait_time: Duration,
 ) -> ()
@@ -36,7 +55,22 @@ where
     S: Signer + Clone,
     G: RequestPullGuard,
 {
-    let tasks = Box::pin(rpc::tasks(spawner, peer, sockets.rpc(), announce_wait_time));
+    let local = rpc::tasks(
+        spawner.clone(),
+        peer.clone(),
+        pool.clone(),
+        role.clone(),
+        sockets.rpc(),
+        announce_wait_time,
+    );
+    let tasks = match remote {
+        None => local.boxed(),
+        Some(remote) => stream::select(
+            local,
+            remote::tasks(spawner, peer, pool, role, remote, announce_wait_time),
+        )
+        .boxed(),
+    };
     if let Some(timeout) = linger_timeout {
         link_async::tasks::run_until_idle(tasks, timeout).await
     } else {
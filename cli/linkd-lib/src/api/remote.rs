@@ -0,0 +1,365 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Remote control of the node over TCP.
+//!
+//! In addition to the Unix domain socket, the RPC API can be exposed on a TLS
+//! protected TCP port, so that headless seeds can be managed without SSH port
+//! forwarding. Since anyone who can reach the port can attempt to connect,
+//! every connection must authenticate before sending requests:
+//!
+//! 1. The client establishes a TLS session. If the node was configured with a
+//!    client CA, a client certificate signed by that CA authenticates the
+//!    client (mTLS).
+//! 2. The client sends an [`Authenticate`] message, optionally carrying a
+//!    bearer token.
+//! 3. The node replies with [`Authenticated::Granted`] and the [`Access`] level
+//!    of the connection, or with [`Authenticated::Denied`] and closes the
+//!    connection.
+//!
+//! Afterwards, the connection speaks the same protocol as the Unix domain
+//! socket, except that requests exceeding the [`Access`] level of the
+//! connection are answered with an error. Connections over the Unix domain
+//! socket are always granted [`Access::Admin`].
+
+use std::{fmt, fs::File, io::BufReader, net::SocketAddr, path::Path, sync::Arc, time::Duration};
+
+use futures::stream::StreamExt as _;
+use rustls::{
+    internal::pemfile,
+    AllowAnyAnonymousOrAuthenticatedClient,
+    NoClientAuth,
+    RootCertStore,
+    Session as _,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+
+use librad::{
+    net::{peer::Peer, protocol::RequestPullGuard},
+    Signer,
+};
+use link_async::{incoming::TcpListenerExt as _, Spawner};
+
+use super::{io, rpc};
+use crate::{replication::Pool, standby::Role};
+
+pub use lnk_clib::rpc::remote::{authenticate, login, Access, Authenticate, Authenticated};
+
+/// Time a client has to complete the TLS handshake and authenticate.
+const AUTHENTICATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub mod error {
+    use std::{io, path::PathBuf};
+
+    use thiserror::Error;
+
+    pub use lnk_clib::rpc::remote::error::Authenticate;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Tokens {
+        #[error("line {line}: expected `<access> <token>`")]
+        Malformed { line: usize },
+
+        #[error("line {line}: {reason}")]
+        Access { line: usize, reason: String },
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Tls {
+        #[error("no certificates found in {0}")]
+        NoCertificates(PathBuf),
+
+        #[error("no PKCS#8 or RSA private key found in {0}")]
+        NoPrivateKey(PathBuf),
+
+        #[error("invalid client CA certificate in {0}")]
+        InvalidClientCa(PathBuf),
+
+        #[error("invalid CA certificate in {0}")]
+        InvalidCa(PathBuf),
+
+        #[error(transparent)]
+        Rustls(#[from] rustls::TLSError),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+/// Bearer tokens and the [`Access`] they grant.
+#[derive(Clone, Default)]
+pub struct Tokens(Vec<(String, Access)>);
+
+impl fmt::Debug for Tokens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|(_, access)| access))
+            .finish()
+    }
+}
+
+impl Tokens {
+    /// Read tokens from the file at `path`, see [`Tokens::parse`].
+    pub fn load(path: &Path) -> Result<Self, error::Tokens> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parse tokens, one `<access> <token>` pair per line.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn parse(s: &str) -> Result<Self, error::Tokens> {
+        let mut tokens = Self::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (access, token) = line
+                .split_once(char::is_whitespace)
+                .map(|(access, token)| (access, token.trim()))
+                .filter(|(_, token)| !token.is_empty())
+                .ok_or(error::Tokens::Malformed { line: i + 1 })?;
+            let access = access.parse().map_err(|reason| error::Tokens::Access {
+                line: i + 1,
+                reason,
+            })?;
+            tokens.insert(token.to_owned(), access);
+        }
+
+        Ok(tokens)
+    }
+
+    pub fn insert(&mut self, token: String, access: Access) {
+        self.0.retain(|(t, _)| t != &token);
+        self.0.push((token, access))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The [`Access`] granted by `token`, if it is known.
+    ///
+    /// All known tokens are compared in constant time, so the time taken does
+    /// not reveal how much of a token was guessed correctly.
+    pub fn access(&self, token: &str) -> Option<Access> {
+        self.0.iter().fold(None, |found, (known, access)| {
+            if constant_time_eq(known.as_bytes(), token.as_bytes()) {
+                Some(*access)
+            } else {
+                found
+            }
+        })
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Build the TLS configuration of the remote control port from PEM files.
+///
+/// If `client_ca` is given, clients may authenticate using a certificate
+/// signed by it. Clients without certificate are still accepted, and must
+/// present a bearer token instead.
+pub fn tls_config(
+    cert: &Path,
+    key: &Path,
+    client_ca: Option<&Path>,
+) -> Result<rustls::ServerConfig, error::Tls> {
+    let mut cfg = match client_ca {
+        None => rustls::ServerConfig::new(NoClientAuth::new()),
+        Some(ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(ca)? {
+                roots
+                    .add(&cert)
+                    .map_err(|_| error::Tls::InvalidClientCa(ca.to_path_buf()))?;
+            }
+            rustls::ServerConfig::new(AllowAnyAnonymousOrAuthenticatedClient::new(roots))
+        },
+    };
+    cfg.set_single_cert(read_certs(cert)?, read_key(key)?)?;
+
+    Ok(cfg)
+}
+
+/// Build the TLS configuration for connecting to the remote control port of
+/// another node, trusting the CA certificates in `ca`.
+///
+/// If `identity` is given, the certificate chain and private key at these
+/// paths are presented to the node as client certificate.
+pub fn client_tls_config(
+    ca: &Path,
+    identity: Option<(&Path, &Path)>,
+) -> Result<rustls::ClientConfig, error::Tls> {
+    let mut cfg = rustls::ClientConfig::new();
+    for cert in read_certs(ca)? {
+        cfg.root_store
+            .add(&cert)
+            .map_err(|_| error::Tls::InvalidCa(ca.to_path_buf()))?;
+    }
+    if let Some((cert, key)) = identity {
+        cfg.set_single_client_cert(read_certs(cert)?, read_key(key)?)?;
+    }
+
+    Ok(cfg)
+}
+
+fn read_certs(path: &Path) -> Result<Vec<rustls::Certificate>, error::Tls> {
+    let mut reader = BufReader::new(File::open(path)?);
+    match pemfile::certs(&mut reader) {
+        Ok(certs) if !certs.is_empty() => Ok(certs),
+        _ => Err(error::Tls::NoCertificates(path.to_path_buf())),
+    }
+}
+
+fn read_key(path: &Path) -> Result<rustls::PrivateKey, error::Tls> {
+    let no_key = || error::Tls::NoPrivateKey(path.to_path_buf());
+    let pkcs8 = pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(path)?))
+        .map_err(|_| no_key())?;
+    if let Some(key) = pkcs8.into_iter().next() {
+        return Ok(key);
+    }
+    pemfile::rsa_private_keys(&mut BufReader::new(File::open(path)?))
+        .map_err(|_| no_key())?
+        .into_iter()
+        .next()
+        .ok_or_else(no_key)
+}
+
+/// Settings of the remote control port.
+pub struct Config {
+    pub listen: SocketAddr,
+    pub tls: Arc<rustls::ServerConfig>,
+    pub tokens: Tokens,
+    /// The [`Access`] granted to clients presenting a valid client
+    /// certificate.
+    pub client_cert_access: Access,
+}
+
+/// The remote control port.
+pub struct Remote {
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    tokens: Arc<Tokens>,
+    client_cert_access: Access,
+}
+
+impl Remote {
+    pub async fn bind(cfg: Config) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(cfg.listen).await?;
+        tracing::info!(addr = %listener.local_addr()?, "remote control listening");
+        Ok(Self {
+            listener,
+            acceptor: TlsAcceptor::from(cfg.tls),
+            tokens: Arc::new(cfg.tokens),
+            client_cert_access: cfg.client_cert_access,
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+pub(super) fn tasks<'a, S, G>(
+    spawner: Arc<Spawner>,
+    peer: Peer<S, G>,
+    pool: Pool,
+    role: Role,
+    remote: &'a Remote,
+    announce_wait_time: Duration,
+) -> impl futures::stream::Stream<Item = link_async::Task<()>> + Send + 'a
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    remote
+        .listener
+        .incoming()
+        .map(move |stream| match stream {
+            Ok(stream) => {
+                tracing::debug!("new remote connection");
+                Some(spawner.spawn(connection(
+                    spawner.clone(),
+                    peer.clone(),
+                    pool.clone(),
+                    role.clone(),
+                    stream,
+                    remote.acceptor.clone(),
+                    remote.tokens.clone(),
+                    remote.client_cert_access,
+                    announce_wait_time,
+                )))
+            },
+            Err(e) => {
+                tracing::error!(err=?e, "error accepting remote connection");
+                None
+            },
+        })
+        .take_while(|e| futures::future::ready(e.is_some()))
+        .filter_map(futures::future::ready)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn connection<S, G>(
+    spawner: Arc<Spawner>,
+    peer: Peer<S, G>,
+    pool: Pool,
+    role: Role,
+    stream: TcpStream,
+    acceptor: TlsAcceptor,
+    tokens: Arc<Tokens>,
+    client_cert_access: Access,
+    announce_wait_time: Duration,
+) where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let remote_addr = stream.peer_addr().ok();
+    let authenticated = link_async::timeout(AUTHENTICATION_TIMEOUT, async {
+        let mut tls = acceptor.accept(stream).await?;
+        let cert_access = tls
+            .get_ref()
+            .1
+            .get_peer_certificates()
+            .filter(|certs| !certs.is_empty())
+            .map(|_| client_cert_access);
+        let access = authenticate(&mut tls, |token| {
+            let token_access = token.and_then(|token| tokens.access(token));
+            cert_access.max(token_access)
+        })
+        .await?;
+        Ok::<_, error::Authenticate>((tls, access))
+    })
+    .await
+    .map_err(|_| error::Authenticate::Timeout)
+    .and_then(|res| res);
+
+    match authenticated {
+        Ok((tls, access)) => {
+            tracing::info!(?remote_addr, %access, "remote client authenticated");
+            rpc::rpc(
+                spawner,
+                peer,
+                pool,
+                role,
+                io::SocketTransport::from_stream(tls),
+                access,
+                remote_addr.map_or_else(|| "remote".to_owned(), |addr| addr.to_string()),
+                announce_wait_time,
+            )
+            .await
+        },
+        Err(e) => tracing::warn!(?remote_addr, err = %e, "rejecting remote client"),
+    }
+}
@@ -7,28 +7,39 @@ use futures::{future::FutureExt, stream::FuturesUnordered};
 use std::{marker::PhantomData, panic, sync::Arc, time::Duration};
 
 use futures::stream::StreamExt;
-use tokio::{
-    net::{UnixListener, UnixStream},
-    sync::mpsc::{channel, Sender},
-};
+use tokio::sync::mpsc::{channel, Sender};
 
 use librad::{
+    git::storage::{audit, reflog},
     net::{peer::Peer, protocol::RequestPullGuard},
     Signer,
 };
-use link_async::{incoming::UnixListenerExt, Spawner};
+#[cfg(unix)]
+use link_async::incoming::UnixListenerExt as _;
+use link_async::Spawner;
 
 use super::{
     announce,
+    connections,
     io::{self, SocketTransportError, Transport},
     messages,
+    pinned,
+    project_stats,
+    remote::Access,
+    replication,
     request_pull,
+    sockets,
+    standby,
+    usage,
 };
+use crate::{replication::Pool, standby::Role};
 
 pub fn tasks<S, G>(
     spawner: Arc<Spawner>,
     peer: Peer<S, G>,
-    socket: &UnixListener,
+    pool: Pool,
+    role: Role,
+    socket: &sockets::Listener,
     announce_wait_time: Duration,
 ) -> impl futures::stream::Stream<Item = link_async::Task<()>> + Send + '_
 where
@@ -43,7 +54,11 @@ where
                 Some(spawner.spawn(rpc(
                     spawner.clone(),
                     peer.clone(),
-                    stream,
+                    pool.clone(),
+                    role.clone(),
+                    sockets::Transport::from(stream),
+                    Access::Admin,
+                    "rpc socket".to_owned(),
                     announce_wait_time,
                 )))
             },
@@ -58,17 +73,27 @@ where
 
 const MAX_IN_FLIGHT_REQUESTS: usize = 20;
 
-async fn rpc<S, G>(
+/// Serve requests received over `transport`, permitting only those allowed
+/// by `access`.
+///
+/// Requests other than [`Access::Read`] ones are recorded in the audit log,
+/// along with the `origin` of the connection.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn rpc<S, G, T>(
     spawner: Arc<Spawner>,
     peer: Peer<S, G>,
-    stream: UnixStream,
+    pool: Pool,
+    role: Role,
+    mut transport: T,
+    access: Access,
+    origin: String,
     announce_wait_time: Duration,
 ) where
     S: Signer + Clone,
     G: RequestPullGuard,
+    T: Transport<Error = SocketTransportError> + Send,
 {
     let mut running_handlers = FuturesUnordered::new();
-    let mut transport: io::SocketTransport = stream.into();
     // TODO: What should the buffer size be here?
     let (sx, mut rx) = channel(10);
 
@@ -82,7 +107,25 @@ async fn rpc<S, G>(
         futures::select! {
             next = next.fuse() => {
                 match next {
+                    Ok(Some(next)) if !access.permits(&next.payload) => {
+                        let required = Access::required(&next.payload);
+                        tracing::warn!(%access, %required, "refusing unauthorized request");
+                        let mut listener = Listener::<()>::denied(next.mode, sx.clone());
+                        listener.ack().await;
+                        running_handlers.push(spawner.spawn(async move {
+                            listener
+                                .error(format!("request requires `{required}` access, connection has `{access}`"))
+                                .await
+                        }));
+                    },
                     Ok(Some(next)) => {
+                        if Access::required(&next.payload) > Access::Read {
+                            running_handlers.push(spawner.spawn(audit(
+                                peer.clone(),
+                                &next,
+                                origin.clone(),
+                            )));
+                        }
                         let handler = {
                             let peer = peer.clone();
                             spawner.spawn(match next.payload {
@@ -98,7 +141,67 @@ async fn rpc<S, G>(
                                     tracing::info!(?p, "dispatching request");
                                     listener.ack().await;
                                     listener.handle(peer, p).boxed()
-                                }
+                                },
+                                messages::RequestPayload::ReplicationTasks(p) => {
+                                    let mut listener = Listener::replication_tasks(next.mode, sx.clone());
+                                    tracing::info!(?p, "dispatching request");
+                                    listener.ack().await;
+                                    listener.handle(peer, pool.clone()).boxed()
+                                },
+                                messages::RequestPayload::CancelReplication(p) => {
+                                    let mut listener = Listener::cancel_replication(next.mode, sx.clone());
+                                    tracing::info!(?p, "dispatching request");
+                                    listener.ack().await;
+                                    listener.handle(pool.clone(), p).boxed()
+                                },
+                                messages::RequestPayload::ReplicationDryRun(p) => {
+                                    let mut listener = Listener::replication_dry_run(next.mode, sx.clone());
+                                    tracing::info!(?p, "dispatching request");
+                                    listener.ack().await;
+                                    listener.handle(peer, p).boxed()
+                                },
+                                messages::RequestPayload::PinnedPeers(p) => {
+                                    let mut listener = Listener::pinned_peers(next.mode, sx.clone());
+                                    tracing::info!(?p, "dispatching request");
+                                    listener.ack().await;
+                                    listener.handle(peer).boxed()
+                                },
+                                messages::RequestPayload::ProjectStats(p) => {
+                                    let mut listener = Listener::project_stats(next.mode, sx.clone());
+                                    tracing::info!(?p, "dispatching request");
+                                    listener.ack().await;
+                                    listener.handle(peer, p).boxed()
+                                },
+                                messages::RequestPayload::StandbyNamespaces(p) => {
+                                    let mut listener = Listener::standby_namespaces(next.mode, sx.clone());
+                                    tracing::info!(?p, "dispatching request");
+                                    listener.ack().await;
+                                    listener.handle(peer, p).boxed()
+                                },
+                                messages::RequestPayload::Promote(p) => {
+                                    let mut listener = Listener::promote(next.mode, sx.clone());
+                                    tracing::info!(?p, "dispatching request");
+                                    listener.ack().await;
+                                    listener.handle(role.clone()).boxed()
+                                },
+                                messages::RequestPayload::Connections(p) => {
+                                    let mut listener = Listener::connections(next.mode, sx.clone());
+                                    tracing::info!(?p, "dispatching request");
+                                    listener.ack().await;
+                                    listener.handle(peer).boxed()
+                                },
+                                messages::RequestPayload::Disconnect(p) => {
+                                    let mut listener = Listener::disconnect(next.mode, sx.clone());
+                                    tracing::info!(?p, "dispatching request");
+                                    listener.ack().await;
+                                    listener.handle(peer, p).boxed()
+                                },
+                                messages::RequestPayload::Usage(p) => {
+                                    let mut listener = Listener::usage(next.mode, sx.clone());
+                                    tracing::info!(?p, "dispatching request");
+                                    listener.ack().await;
+                                    listener.handle(spawner.clone(), peer, p).boxed()
+                                },
                             })
                         };
                         running_handlers.push(handler);
@@ -148,6 +251,29 @@ async fn rpc<S, G>(
     }
 }
 
+fn audit<S, G>(
+    peer: Peer<S, G>,
+    request: &messages::Request,
+    origin: String,
+) -> impl futures::Future<Output = ()> + Send + 'static
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let event = audit::Event::Command {
+        command: format!("{:?}", request.payload),
+        origin: format!("{} ({})", origin, <&str>::from(&request.user_agent)),
+    };
+    async move {
+        if let Err(e) = peer
+            .using_storage(move |storage| storage.audit(event))
+            .await
+        {
+            tracing::error!(err = %e, "failed to record command in audit log");
+        }
+    }
+}
+
 fn handle_task_complete(task_result: Result<(), link_async::JoinError>) {
     match task_result {
         Ok(_) => (),
@@ -183,6 +309,16 @@ impl From<messages::RequestMode> for ListenerInterest {
 }
 
 impl<P> Listener<P> {
+    /// The request id as recorded in reflog messages, see
+    /// [`reflog::with_request`].
+    fn request(&self) -> String {
+        self.request_id
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
     async fn ack(&mut self) {
         self.send(messages::ResponsePayload::Ack).await
     }
@@ -229,6 +365,20 @@ impl<P> Listener<P> {
     }
 }
 
+impl Listener<()> {
+    fn denied(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+}
+
 impl Listener<announce::Response> {
     fn announce(
         mode: messages::RequestMode,
@@ -337,3 +487,330 @@ impl Listener<request_pull::Response> {
         }
     }
 }
+
+impl Listener<replication::tasks::Response> {
+    fn replication_tasks(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[tracing::instrument(skip(self, peer, pool))]
+    async fn handle<S, G>(mut self, peer: Peer<S, G>, pool: Pool)
+    where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        let tasks = pool.tasks(&peer).await;
+        self.success(replication::tasks::Response(tasks).into())
+            .await
+    }
+}
+
+impl Listener<replication::cancel::Response> {
+    fn cancel_replication(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[tracing::instrument(skip(self, pool))]
+    async fn handle(
+        mut self,
+        pool: Pool,
+        replication::cancel::Request { task }: replication::cancel::Request,
+    ) {
+        if pool.cancel(task) {
+            self.success(replication::cancel::Response.into()).await
+        } else {
+            self.error(format!("no replication task `{task}`")).await
+        }
+    }
+}
+
+impl Listener<replication::dry_run::Response> {
+    fn replication_dry_run(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[tracing::instrument(skip(self, peer))]
+    async fn handle<S, G>(
+        mut self,
+        peer: Peer<S, G>,
+        replication::dry_run::Request {
+            urn,
+            peer: remote,
+            addrs,
+        }: replication::dry_run::Request,
+    ) where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        match peer.replicate_dry_run((remote, addrs), urn.clone()).await {
+            Ok(dry_run) => {
+                self.success(replication::dry_run::Response::from(dry_run).into())
+                    .await
+            },
+            Err(err) => {
+                tracing::warn!(err = %err, "replication dry-run failed");
+                self.error(format!(
+                    "unable to determine what would be replicated from `{remote}` for `{urn}`: {err}"
+                ))
+                .await
+            },
+        }
+    }
+}
+
+impl Listener<pinned::Response> {
+    fn pinned_peers(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[tracing::instrument(skip(self, peer))]
+    async fn handle<S, G>(mut self, peer: Peer<S, G>)
+    where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        self.success(pinned::Response::from(peer.pinned().await).into())
+            .await
+    }
+}
+
+impl Listener<project_stats::Response> {
+    fn project_stats(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[tracing::instrument(skip(self, peer))]
+    async fn handle<S, G>(
+        mut self,
+        peer: Peer<S, G>,
+        project_stats::Request { urn }: project_stats::Request,
+    ) where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        let res = {
+            let urn = urn.clone();
+            peer.using_read_only(move |storage| storage.stats(&urn))
+                .await
+        };
+        match res {
+            Ok(Ok(stats)) => {
+                self.success(project_stats::Response(stats.map(Into::into)).into())
+                    .await
+            },
+            Ok(Err(err)) => {
+                tracing::error!(err = %err, "failed to read project stats");
+                self.error(format!("unable to read stats of `{urn}`")).await
+            },
+            Err(err) => {
+                tracing::error!(err = %err, "failed to access storage");
+                self.error(format!("unable to read stats of `{urn}`")).await
+            },
+        }
+    }
+}
+
+impl Listener<standby::namespaces::Response> {
+    fn standby_namespaces(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[tracing::instrument(skip(self, peer))]
+    async fn handle<S, G>(mut self, peer: Peer<S, G>, request: standby::namespaces::Request)
+    where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        let id = self.request();
+        match peer
+            .using_storage(move |storage| {
+                reflog::with_request(id, || crate::standby::namespaces(storage, &request))
+            })
+            .await
+        {
+            Ok(Ok(response)) => self.success(response.into()).await,
+            Ok(Err(err)) => {
+                tracing::error!(err = %err, "failed to list namespaces");
+                self.error(format!("unable to list namespaces: {err}"))
+                    .await
+            },
+            Err(err) => {
+                tracing::error!(err = %err, "failed to access storage");
+                self.error("unable to list namespaces".to_owned()).await
+            },
+        }
+    }
+}
+
+impl Listener<standby::promote::Response> {
+    fn promote(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[tracing::instrument(skip(self, role))]
+    async fn handle(mut self, role: Role) {
+        if role.promote() {
+            tracing::info!("promoted to primary");
+            self.success(standby::promote::Response.into()).await
+        } else {
+            self.error("node is not a standby".to_owned()).await
+        }
+    }
+}
+
+impl Listener<connections::list::Response> {
+    fn connections(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[tracing::instrument(skip(self, peer))]
+    async fn handle<S, G>(mut self, peer: Peer<S, G>)
+    where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        let conns = peer.connections().await.into_iter().map(Into::into);
+        self.success(connections::list::Response(conns.collect()).into())
+            .await
+    }
+}
+
+impl Listener<connections::disconnect::Response> {
+    fn disconnect(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[tracing::instrument(skip(self, peer))]
+    async fn handle<S, G>(
+        mut self,
+        peer: Peer<S, G>,
+        connections::disconnect::Request { peer: remote }: connections::disconnect::Request,
+    ) where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        if peer.disconnect(remote).await {
+            tracing::info!(peer = %remote, "disconnected");
+            self.success(connections::disconnect::Response.into()).await
+        } else {
+            self.error(format!("not connected to `{remote}`")).await
+        }
+    }
+}
+
+impl Listener<usage::Response> {
+    fn usage(
+        mode: messages::RequestMode,
+        send: Sender<messages::Response<messages::SomeSuccess>>,
+    ) -> Self {
+        Self {
+            request_id: Default::default(),
+            send,
+            interest: mode.into(),
+            _marker: PhantomData,
+        }
+    }
+
+    #[tracing::instrument(skip(self, spawner, peer))]
+    async fn handle<S, G>(
+        mut self,
+        spawner: Arc<Spawner>,
+        peer: Peer<S, G>,
+        usage::Request { from, to, format }: usage::Request,
+    ) where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        let store = peer.usage().clone();
+        let res = spawner
+            .blocking(move || {
+                let days = store.days(from.as_deref(), to.as_deref())?;
+                let mut out = Vec::new();
+                librad::net::protocol::usage::export(&days, format.into(), &mut out)?;
+                Ok::<_, librad::net::protocol::usage::error::Read>(out)
+            })
+            .await;
+        match res {
+            Ok(out) => {
+                self.success(usage::Response(String::from_utf8_lossy(&out).into_owned()).into())
+                    .await
+            },
+            Err(err) => {
+                tracing::error!(err = %err, "failed to export usage");
+                self.error(format!("unable to export usage: {err}")).await
+            },
+        }
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use futures::stream::{self, Stream};
+use tokio::{
+    net::windows::named_pipe::{NamedPipeServer, ServerOptions},
+    sync::Mutex,
+};
+
+use librad::{profile::Profile, PeerId};
+
+use super::Error;
+use crate::api::io as api_io;
+
+/// The [`api_io::Transport`] of a connection accepted by a [`Listener`].
+pub type Transport = api_io::StreamTransport<NamedPipeServer>;
+
+/// Sockets the RPC and events APIs will listen on
+pub struct Sockets {
+    rpc: Listener,
+    events: Listener,
+}
+
+impl Sockets {
+    /// The socket applications will connect to RPC over
+    pub fn rpc(&self) -> &Listener {
+        &self.rpc
+    }
+
+    /// The socket applications will consume events from
+    pub fn events(&self) -> &Listener {
+        &self.events
+    }
+
+    /// Perform any cleanup necessary once you're finished with the sockets
+    ///
+    /// Named pipes are removed by the system once all handles to them are
+    /// closed, so this doesn't do anything.
+    pub fn cleanup(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    pub async fn load(
+        _spawner: Arc<link_async::Spawner>,
+        profile: &Profile,
+        peer_id: PeerId,
+    ) -> Result<Sockets, Error> {
+        Ok(Sockets {
+            rpc: Listener::bind(profile.paths().rpc_socket(&peer_id))?,
+            events: Listener::bind(profile.paths().events_socket(&peer_id))?,
+        })
+    }
+}
+
+/// A named pipe accepting connections.
+///
+/// A named pipe server instance serves exactly one client, so a fresh instance
+/// is created whenever a client connects.
+pub struct Listener {
+    name: PathBuf,
+    next: Mutex<NamedPipeServer>,
+}
+
+impl Listener {
+    /// Create the named pipe `name`, failing if it already exists (ie. another
+    /// node is running).
+    pub fn bind(name: PathBuf) -> io::Result<Self> {
+        let first = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&name)?;
+        Ok(Self {
+            name,
+            next: Mutex::new(first),
+        })
+    }
+
+    pub fn name(&self) -> &Path {
+        &self.name
+    }
+
+    /// Wait for a client to connect.
+    pub async fn accept(&self) -> io::Result<NamedPipeServer> {
+        let mut next = self.next.lock().await;
+        next.connect().await?;
+        let fresh = ServerOptions::new().create(&self.name)?;
+        Ok(std::mem::replace(&mut *next, fresh))
+    }
+
+    /// The stream of connecting clients, which ends after the first error.
+    pub fn incoming(&self) -> impl Stream<Item = io::Result<NamedPipeServer>> + Send + '_ {
+        stream::unfold(Some(self), |this| async move {
+            let this = this?;
+            match this.accept().await {
+                Ok(pipe) => Some((Ok(pipe), Some(this))),
+                Err(e) => {
+                    tracing::error!(err=?e, "error accepting named pipe connection");
+                    Some((Err(e), None))
+                },
+            }
+        })
+    }
+}
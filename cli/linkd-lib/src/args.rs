@@ -5,15 +5,21 @@
 
 // TODO(xla): Expose discovery args.
 // TODO(xla): Expose storage args.
-// TODO(xla): Expose logging args.
 
-use std::{fmt, net::SocketAddr, path::PathBuf, str::FromStr, time::Duration};
+use std::{
+    fmt,
+    net::SocketAddr,
+    num::{NonZeroU32, NonZeroU64},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
 
 use clap::Parser;
 
 use librad::{
     git::Urn,
-    net::Network,
+    net::{dial, Network},
     profile::{LnkHome, ProfileId},
     PeerId,
 };
@@ -34,6 +40,22 @@ pub struct Args {
     #[clap(long = "bootstrap", name = "bootstrap")]
     pub bootstraps: Vec<Seed<String>>,
 
+    /// Usage: `--pin <peer1>@<addr1>[,<label1>] --pin
+    /// <peer2>@<addr2>[,<label2>]`
+    ///
+    /// Peers to always stay connected to, eg. the seeds of an organisation.
+    /// Pinned peers are kept in the active membership view regardless of
+    /// its maximum size, and are redialled with backoff when the connection
+    /// is lost.
+    #[clap(long = "pin", name = "pin")]
+    pub pinned: Vec<Seed<String>>,
+
+    /// Path to a JSON-encoded rotation of the key of this node, signed by it.
+    /// The rotation is served to peers asking for it, so that seed operators
+    /// can announce a new key before switching to it.
+    #[clap(long)]
+    pub rotation: Option<PathBuf>,
+
     /// Identifier of the profile the daemon will run for. This value determines
     /// which monorepo (if existing) on disk will be the backing storage.
     #[clap(long)]
@@ -68,17 +90,63 @@ pub struct Args {
     #[clap(long)]
     pub tmp_root: bool,
 
+    /// Check the environment the node would run in, print a report, and
+    /// exit.
+    #[clap(long)]
+    pub doctor: bool,
+
     #[clap(flatten)]
     pub tracking: TrackingArgs,
 
+    #[cfg(feature = "autotrack")]
+    #[clap(flatten)]
+    pub autotrack: AutoTrackArgs,
+
     #[clap(flatten)]
     pub request_pull: RequestPullStorage,
 
+    #[clap(flatten)]
+    pub replication: ReplicationArgs,
+
+    #[clap(flatten)]
+    pub executor: ExecutorArgs,
+
+    #[clap(flatten)]
+    pub anti_entropy: AntiEntropyArgs,
+
+    #[clap(flatten)]
+    pub remote_control: RemoteControlArgs,
+
+    #[clap(flatten)]
+    pub standby: StandbyArgs,
+
+    #[clap(flatten)]
+    pub tracing: TracingArgs,
+
+    #[cfg(feature = "mirror")]
+    #[clap(flatten)]
+    pub mirror: MirrorArgs,
+
+    #[cfg(feature = "notify")]
+    #[clap(flatten)]
+    pub notify: NotifyArgs,
+
+    #[cfg(feature = "http")]
+    #[clap(flatten)]
+    pub http: HttpArgs,
+
     /// The number of milliseconds to wait after losing all connections before
     /// shutting down the node. If not specified the node will never
     /// shutdown.
     #[clap(long)]
     pub linger_timeout: Option<LingerTimeout>,
+
+    /// The number of milliseconds to wait for further updates after the
+    /// signed refs of a project were changed by another process (eg. by a
+    /// `git push`), before announcing them. If not specified, such changes
+    /// are not announced automatically.
+    #[clap(long)]
+    pub announce_debounce: Option<AnnounceDebounce>,
 }
 
 #[derive(Debug, Eq, PartialEq, Parser)]
@@ -87,6 +155,9 @@ pub enum Signer {
     Key,
     /// Connect to ssh-agent for delegated signing.
     SshAgent,
+    /// Unlock the key storage of the profile with the passphrase from the
+    /// keychain of the operating system, prompting for it if it is not found.
+    Keychain,
 }
 
 impl Default for Signer {
@@ -100,6 +171,7 @@ impl fmt::Display for Signer {
         let ty = match self {
             Self::Key => "key",
             Self::SshAgent => "ssh-agent",
+            Self::Keychain => "keychain",
         };
 
         write!(f, "{}", ty)
@@ -113,6 +185,7 @@ impl FromStr for Signer {
         match input {
             "key" => Ok(Self::Key),
             "ssh-agent" => Ok(Self::SshAgent),
+            "keychain" => Ok(Self::Keychain),
             _ => Err(format!("unsupported signer `{}`", input)),
         }
     }
@@ -281,6 +354,81 @@ pub struct ProtocolArgs {
         parse(try_from_str = parse_protocol_network))
     ]
     pub network: Network,
+
+    /// Maximum number of bytes per second used for replication (in each
+    /// direction), leaving the remaining bandwidth to protocol messages. If
+    /// not provided, replication is not limited.
+    #[clap(long = "protocol-bulk-bandwidth", name = "protocol-bulk-bandwidth")]
+    pub bulk_bandwidth: Option<NonZeroU32>,
+
+    /// Batch gossip sent to peers which support it. Only enable this if the
+    /// peers you connect to run a version which understands the capability.
+    #[clap(long = "protocol-gossip-batch", name = "protocol-gossip-batch")]
+    pub gossip_batch: bool,
+
+    /// Compress gossip and membership messages sent to peers which support
+    /// it. Only enable this if the peers you connect to run a version which
+    /// understands the capability.
+    #[clap(
+        long = "protocol-gossip-compression",
+        name = "protocol-gossip-compression"
+    )]
+    pub gossip_compression: bool,
+
+    /// Declare the locally stored URNs to neighbours which support it, so
+    /// they don't forward announcements about other URNs. Only enable this if
+    /// the peers you connect to run a version which understands the
+    /// capability.
+    #[clap(long = "protocol-gossip-interest", name = "protocol-gossip-interest")]
+    pub gossip_interest: bool,
+
+    /// Tell neighbours which support it when announcements can't be fetched
+    /// because too many fetches are in progress, and stop asking neighbours
+    /// which said so for retransmissions for a while. Only enable this if the
+    /// peers you connect to run a version which understands the capability.
+    #[clap(long = "protocol-gossip-backoff", name = "protocol-gossip-backoff")]
+    pub gossip_backoff: bool,
+
+    /// Run as a private peer: connect to the bootstrap peers, but refuse
+    /// inbound connections and don't advertise the listen address, so that
+    /// it is not passed on to other peers.
+    #[clap(long = "protocol-private", name = "protocol-private")]
+    pub private: bool,
+
+    /// Which IP address families to dial: `any` (the default) dials all
+    /// addresses of a peer concurrently, `prefer-ipv4` and `prefer-ipv6` dial
+    /// the other family only if none of the preferred addresses can be
+    /// reached, `ipv4-only` and `ipv6-only` never dial the other family.
+    #[clap(
+        long = "protocol-address-family",
+        name = "protocol-address-family",
+        default_value_t
+    )]
+    pub address_family: dial::Family,
+
+    /// Seconds to wait for the addresses of bootstrap and pinned peers to
+    /// resolve. If not provided, 10 seconds are used.
+    #[clap(long = "protocol-dns-timeout", name = "protocol-dns-timeout")]
+    pub dns_timeout: Option<u64>,
+
+    /// How to resolve the addresses of bootstrap and pinned peers: `system`
+    /// (the default) uses the resolver of the operating system, `trust-dns`
+    /// reads `/etc/resolv.conf` and queries the name servers directly, if
+    /// compiled with support for it.
+    #[clap(
+        long = "protocol-resolver",
+        name = "protocol-resolver",
+        default_value_t
+    )]
+    pub resolver: dial::Resolver,
+
+    /// Days to keep the statistics of the data served to other peers for,
+    /// including the current one. If not provided, 90 days are kept.
+    #[clap(
+        long = "protocol-usage-retention-days",
+        name = "protocol-usage-retention-days"
+    )]
+    pub usage_retention_days: Option<u32>,
     // TODO(xla): Expose protocol args (membership, replication, etc.).
 }
 
@@ -345,6 +493,62 @@ pub struct TrackingArgs {
     pub pairs: Vec<tracking::Pair>,
 }
 
+/// Settings for tracking contributors automatically, see
+/// `linkd_lib::autotrack`.
+#[cfg(feature = "autotrack")]
+#[derive(Debug, Clone, PartialEq, Eq, Parser)]
+pub struct AutoTrackArgs {
+    /// Track the authors of issues and patches, and the peers referenced by
+    /// delegates, of the projects the local peer is a delegate of.
+    #[clap(long = "auto-track")]
+    pub enabled: bool,
+
+    /// Usage: `--auto-track-urn <urn1> --auto-track-urn <urn2>`
+    ///
+    /// Track contributors of the given projects, too. Implies
+    /// `--auto-track`.
+    #[clap(long = "auto-track-urn", name = "auto-track-urn")]
+    pub urns: Vec<Urn>,
+
+    /// Usage: `--auto-track-ignore <peer1> --auto-track-ignore <peer2>`
+    ///
+    /// Never track the given peers automatically.
+    #[clap(long = "auto-track-ignore", name = "auto-track-ignore")]
+    pub ignore: Vec<PeerId>,
+
+    /// The tracking configuration of automatically created entries, as
+    /// canonical JSON. For example, to replicate only the collaborative
+    /// objects of contributors:
+    /// `{"cobs":{"*":{"pattern":"*","policy":"allow"}},"data":false}`.
+    ///
+    /// Defaults to replicating all data and collaborative objects.
+    #[clap(long = "auto-track-config", name = "auto-track-config")]
+    pub config: Option<librad::git::tracking::Config>,
+
+    /// Maximum number of tracked peers of a project, beyond which no more
+    /// are tracked automatically.
+    #[clap(long = "auto-track-max-per-urn", default_value = "64")]
+    pub max_per_urn: usize,
+
+    /// Maximum number of peers to track automatically per hour.
+    #[clap(long = "auto-track-max-per-hour", default_value = "16")]
+    pub max_per_hour: usize,
+}
+
+#[cfg(feature = "autotrack")]
+impl Default for AutoTrackArgs {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            urns: vec![],
+            ignore: vec![],
+            config: None,
+            max_per_urn: 64,
+            max_per_hour: 16,
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Parser)]
 pub enum TrackingMode {
     Everything,
@@ -384,6 +588,27 @@ impl FromStr for LingerTimeout {
     }
 }
 
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct AnnounceDebounce(Duration);
+
+impl From<&AnnounceDebounce> for Duration {
+    fn from(d: &AnnounceDebounce) -> Self {
+        d.0
+    }
+}
+
+impl FromStr for AnnounceDebounce {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let integer: Result<u64, _> = s.parse();
+        match integer {
+            Ok(i) => Ok(AnnounceDebounce(Duration::from_millis(i))),
+            Err(_) => Err("expected a positive integer"),
+        }
+    }
+}
+
 /// Settings for the request-pull storage.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Parser)]
 pub struct RequestPullStorage {
@@ -399,3 +624,386 @@ impl Default for RequestPullStorage {
         }
     }
 }
+
+/// Settings for the replication worker pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Parser)]
+pub struct ReplicationArgs {
+    /// Maximum number of replication jobs to run concurrently.
+    #[clap(long = "replication-workers", default_value_t = num_cpus::get_physical())]
+    pub workers: usize,
+
+    /// Maximum number of replication jobs for the same URN to run
+    /// concurrently. Further jobs for that URN wait, while jobs for other URNs
+    /// take turns with it.
+    #[clap(long = "replication-workers-per-urn", default_value_t = 2)]
+    pub workers_per_urn: usize,
+
+    /// Check replicated branches against the commit signature policies of
+    /// projects, and report violations.
+    #[clap(long = "verify-commit-signatures")]
+    pub verify_signatures: bool,
+
+    /// Maximum number of fetches triggered by gossip to run concurrently.
+    #[clap(long = "replication-gossip-fetches", default_value_t = 16)]
+    pub gossip_fetches: usize,
+
+    /// Maximum number of fetches triggered by gossip waiting to be run.
+    /// Announcements beyond that are not fetched, but relayed to other peers.
+    #[clap(long = "replication-gossip-queue", default_value_t = 64)]
+    pub gossip_queue: usize,
+}
+
+impl Default for ReplicationArgs {
+    fn default() -> Self {
+        Self {
+            workers: num_cpus::get_physical(),
+            workers_per_urn: 2,
+            verify_signatures: false,
+            gossip_fetches: 16,
+            gossip_queue: 64,
+        }
+    }
+}
+
+/// Settings for the threads running the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Parser)]
+pub struct ExecutorArgs {
+    /// Number of threads running async tasks. Only honoured if the runtime is
+    /// created by `linkd_lib::node::start`. Defaults to one per core.
+    #[clap(long = "worker-threads")]
+    pub worker_threads: Option<usize>,
+
+    /// Number of threads dedicated to blocking operations, such as storage
+    /// access and signature verification. If not specified, blocking
+    /// operations run on a shared pool, which grows on demand.
+    #[clap(long = "blocking-threads")]
+    pub blocking_threads: Option<usize>,
+
+    /// Maximum number of blocking operations waiting for a thread of the
+    /// dedicated pool.
+    #[clap(long = "blocking-queue", default_value = "256")]
+    pub blocking_queue: usize,
+}
+
+impl Default for ExecutorArgs {
+    fn default() -> Self {
+        Self {
+            worker_threads: None,
+            blocking_threads: None,
+            blocking_queue: 256,
+        }
+    }
+}
+
+/// Settings for periodically reconciling tracked projects with connected
+/// peers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Parser)]
+pub struct AntiEntropyArgs {
+    /// The average number of seconds between two anti-entropy rounds, in each
+    /// of which the `rad/signed_refs` of the projects tracked for a random
+    /// connected peer are compared with the peer's, and fetched if they
+    /// differ. If not specified, no rounds are run.
+    #[clap(long = "anti-entropy-interval", name = "anti-entropy-interval")]
+    pub interval_secs: Option<u64>,
+
+    /// Maximum number of projects to fetch per anti-entropy round.
+    #[clap(long = "anti-entropy-max-fetches", default_value = "8")]
+    pub max_fetches: usize,
+}
+
+impl Default for AntiEntropyArgs {
+    fn default() -> Self {
+        Self {
+            interval_secs: None,
+            max_fetches: 8,
+        }
+    }
+}
+
+/// Settings for running as warm standby of another node.
+#[derive(Debug, Clone, PartialEq, Eq, Parser)]
+pub struct StandbyArgs {
+    /// Usage: `--standby-of <peer>@<addr>`
+    ///
+    /// Continuously mirror all namespaces of the given primary node, until
+    /// promoted via the control socket. Requires `--standby-control` and
+    /// `--standby-control-ca`.
+    #[clap(long = "standby-of", name = "standby-of")]
+    pub primary: Option<Seed<String>>,
+
+    /// Address of the remote control port of the primary, as `<host>:<port>`.
+    #[clap(long = "standby-control", name = "standby-control")]
+    pub control: Option<String>,
+
+    /// PEM file containing the CA certificates to verify the certificate of
+    /// the primary's remote control port against.
+    #[clap(long = "standby-control-ca", name = "standby-control-ca")]
+    pub control_ca: Option<PathBuf>,
+
+    /// The name the certificate of the primary's remote control port must be
+    /// valid for. Defaults to the host of `--standby-control`.
+    #[clap(long = "standby-control-name", name = "standby-control-name")]
+    pub control_name: Option<String>,
+
+    /// PEM file containing the client certificate chain to present to the
+    /// primary.
+    #[clap(
+        long = "standby-control-cert",
+        name = "standby-control-cert",
+        requires = "standby-control-key"
+    )]
+    pub control_cert: Option<PathBuf>,
+
+    /// PEM file containing the private key of `--standby-control-cert`.
+    #[clap(
+        long = "standby-control-key",
+        name = "standby-control-key",
+        requires = "standby-control-cert"
+    )]
+    pub control_key: Option<PathBuf>,
+
+    /// File containing the bearer token to present to the primary. The token
+    /// requires `read` access.
+    #[clap(long = "standby-control-token", name = "standby-control-token")]
+    pub control_token: Option<PathBuf>,
+
+    /// The number of seconds between two rounds of mirroring.
+    #[clap(long = "standby-interval", default_value = "60")]
+    pub interval_secs: u64,
+}
+
+impl Default for StandbyArgs {
+    fn default() -> Self {
+        Self {
+            primary: None,
+            control: None,
+            control_ca: None,
+            control_name: None,
+            control_cert: None,
+            control_key: None,
+            control_token: None,
+            interval_secs: 60,
+        }
+    }
+}
+
+/// Settings for controlling the node remotely over TLS.
+#[derive(Debug, Eq, PartialEq, Parser)]
+pub struct RemoteControlArgs {
+    /// Address to listen on for remote control connections, in addition to
+    /// the RPC socket. If not provided, the node can only be controlled
+    /// locally.
+    #[clap(long = "remote-control-listen", name = "remote-control-listen")]
+    pub listen: Option<SocketAddr>,
+
+    /// PEM file containing the certificate chain presented to remote control
+    /// clients.
+    #[clap(
+        long = "remote-control-cert",
+        name = "remote-control-cert",
+        requires = "remote-control-key"
+    )]
+    pub cert: Option<PathBuf>,
+
+    /// PEM file containing the private key of `--remote-control-cert`.
+    #[clap(
+        long = "remote-control-key",
+        name = "remote-control-key",
+        requires = "remote-control-cert"
+    )]
+    pub key: Option<PathBuf>,
+
+    /// File containing the bearer tokens accepted from remote control
+    /// clients, one `<access> <token>` pair per line. Access is one of
+    /// `read`, `operate` or `admin`.
+    #[clap(long = "remote-control-tokens", name = "remote-control-tokens")]
+    pub tokens: Option<PathBuf>,
+
+    /// PEM file containing the CA certificates to verify remote control client
+    /// certificates against. If provided, clients presenting a valid
+    /// certificate are authenticated without a token.
+    #[clap(long = "remote-control-client-ca", name = "remote-control-client-ca")]
+    pub client_ca: Option<PathBuf>,
+
+    /// Access granted to clients authenticated by certificate.
+    #[clap(
+        long = "remote-control-client-cert-access",
+        name = "remote-control-client-cert-access",
+        default_value = "admin"
+    )]
+    pub client_cert_access: crate::api::remote::Access,
+}
+
+impl Default for RemoteControlArgs {
+    fn default() -> Self {
+        Self {
+            listen: None,
+            cert: None,
+            key: None,
+            tokens: None,
+            client_ca: None,
+            client_cert_access: crate::api::remote::Access::Admin,
+        }
+    }
+}
+
+/// Settings for logging and trace export.
+#[derive(Debug, Clone, PartialEq, Eq, Parser)]
+pub struct TracingArgs {
+    /// Only record one in every N spans of the gossip protocol. Under load,
+    /// gossip spans are created for every message sent or received.
+    #[clap(long = "tracing-sample-gossip", default_value = "1")]
+    pub sample_gossip: NonZeroU64,
+
+    /// Endpoint of an OpenTelemetry collector to export spans to via
+    /// OTLP/gRPC, eg. `http://localhost:4317`. Requires linkd to be built with
+    /// the `otlp` feature.
+    #[clap(long = "otlp-endpoint", name = "otlp-endpoint")]
+    pub otlp_endpoint: Option<String>,
+
+    /// The `service.name` reported to the OpenTelemetry collector.
+    #[clap(long = "otlp-service-name", default_value = "linkd")]
+    pub otlp_service_name: String,
+}
+
+impl Default for TracingArgs {
+    fn default() -> Self {
+        Self {
+            sample_gossip: NonZeroU64::new(1).unwrap(),
+            otlp_endpoint: None,
+            otlp_service_name: "linkd".to_string(),
+        }
+    }
+}
+
+/// Settings for mirroring projects to conventional git remotes.
+#[cfg(feature = "mirror")]
+#[derive(Debug, Clone, PartialEq, Eq, Parser)]
+pub struct MirrorArgs {
+    /// File listing the projects to mirror, one `<urn> <url> [<option>...]`
+    /// per line. See `linkd_lib::mirror::Config::parse` for the options.
+    #[clap(long = "mirror-config", name = "mirror-config")]
+    pub config: Option<PathBuf>,
+
+    /// The number of seconds between mirroring all configured projects,
+    /// regardless of them being updated.
+    #[clap(long = "mirror-interval", default_value = "300")]
+    pub interval_secs: u64,
+}
+
+#[cfg(feature = "mirror")]
+impl Default for MirrorArgs {
+    fn default() -> Self {
+        Self {
+            config: None,
+            interval_secs: 300,
+        }
+    }
+}
+
+/// Settings for email notifications about collaborative objects.
+#[cfg(feature = "notify")]
+#[derive(Debug, Clone, PartialEq, Eq, Parser)]
+pub struct NotifyArgs {
+    /// Address of the SMTP server to send notifications via, as
+    /// `<host>:<port>`. If not provided, no notifications are sent.
+    #[clap(long = "notify-smtp", name = "notify-smtp")]
+    pub smtp: Option<String>,
+
+    /// PEM file containing the CA certificates to verify the certificate of
+    /// the SMTP server against. If provided, the connection uses implicit
+    /// TLS.
+    #[clap(long = "notify-smtp-tls-ca", name = "notify-smtp-tls-ca")]
+    pub smtp_tls_ca: Option<PathBuf>,
+
+    /// User name to authenticate to the SMTP server as.
+    #[clap(
+        long = "notify-smtp-user",
+        name = "notify-smtp-user",
+        requires = "notify-smtp-password"
+    )]
+    pub smtp_user: Option<String>,
+
+    /// Password to authenticate to the SMTP server with.
+    #[clap(
+        long = "notify-smtp-password",
+        name = "notify-smtp-password",
+        env = "LNK_SMTP_PASSWORD",
+        hide_env_values = true
+    )]
+    pub smtp_password: Option<String>,
+
+    /// Sender of the notifications, eg. `linkd <linkd@example.com>`.
+    #[clap(long = "notify-from", name = "notify-from")]
+    pub from: Option<String>,
+
+    /// Usage: `--notify-to <addr1> --notify-to <addr2>`
+    ///
+    /// Recipients of the notifications.
+    #[clap(long = "notify-to", name = "notify-to")]
+    pub to: Vec<String>,
+
+    /// Usage: `--notify-urn <urn1> --notify-urn <urn2>`
+    ///
+    /// Projects to send notifications about.
+    #[clap(long = "notify-urn", name = "notify-urn")]
+    pub urns: Vec<Urn>,
+
+    /// Usage: `--notify-type <typename1> --notify-type <typename2>`
+    ///
+    /// Types of collaborative objects to send notifications about. Defaults
+    /// to issues and patches.
+    #[clap(long = "notify-type", name = "notify-type")]
+    pub typenames: Vec<String>,
+
+    /// Usage: `--notify-mention <handle1> --notify-mention <handle2>`
+    ///
+    /// Handles to send notifications about mentions of, and review requests
+    /// for. The local peer id is always included.
+    #[clap(long = "notify-mention", name = "notify-mention")]
+    pub handles: Vec<String>,
+
+    /// Directory containing the templates of the notifications, see
+    /// `linkd_lib::notify::Templates::load`.
+    #[clap(long = "notify-templates", name = "notify-templates")]
+    pub templates: Option<PathBuf>,
+
+    /// Maximum number of notifications to send per hour. Notifications
+    /// exceeding the limit are dropped.
+    #[clap(long = "notify-max-per-hour", default_value = "60")]
+    pub max_per_hour: usize,
+}
+
+#[cfg(feature = "notify")]
+impl Default for NotifyArgs {
+    fn default() -> Self {
+        Self {
+            smtp: None,
+            smtp_tls_ca: None,
+            smtp_user: None,
+            smtp_password: None,
+            from: None,
+            to: vec![],
+            urns: vec![],
+            typenames: vec![],
+            handles: vec![],
+            templates: None,
+            max_per_hour: 60,
+        }
+    }
+}
+
+/// Settings for the read-only HTTP API.
+#[cfg(feature = "http")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Parser)]
+pub struct HttpArgs {
+    /// Address to serve the read-only HTTP API on. If not provided, the API
+    /// is not served.
+    #[clap(long = "http-listen", name = "http-listen")]
+    pub listen: Option<SocketAddr>,
+
+    /// Origin allowed to make cross-origin requests to the HTTP API, eg.
+    /// `https://app.radicle.xyz`, or `*` for any.
+    #[clap(long = "http-cors-origin", name = "http-cors-origin")]
+    pub cors_origin: Option<String>,
+}
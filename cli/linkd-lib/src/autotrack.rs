@@ -0,0 +1,265 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Automatic tracking of contributors.
+//!
+//! Maintainers only replicate the forks of the peers they track, so the
+//! contributions of a new peer go unnoticed until it is tracked by hand. If
+//! enabled, the node instead tracks a peer for a project it maintains -- ie.
+//! one the local peer is a delegate of, or one of [`Config::urns`] -- when
+//! the peer:
+//!
+//! * is the author of an issue or patch of the project, or of a comment on one,
+//!   see [`authors`], or
+//! * is referenced by a delegate, ie. listed as a remote in the
+//!   `rad/signed_refs` of a delegate.
+//!
+//! Projects are checked whenever they are replicated or updated via gossip.
+//! New tracking entries are created with [`Config::tracking`], and existing
+//! entries are never modified. At most [`Config::max_per_urn`] peers are
+//! tracked per project, including the ones tracked by other means, and at
+//! most [`Config::max_per_hour`] entries are created in total: candidates
+//! exceeding the limits are skipped, and a warning is logged. The forks of
+//! newly tracked peers are fetched the next time the project is replicated.
+
+use std::{
+    collections::BTreeSet,
+    str::FromStr as _,
+    time::{Duration, Instant},
+};
+
+use futures::StreamExt as _;
+use tracing::{debug, info, instrument, warn};
+
+use librad::{
+    collaborative_objects::TypeName,
+    git::{identities, refs::Refs, storage::Storage, tracking, Urn},
+    identities::SomeIdentity,
+    net::{
+        peer::{event::upstream::Gossip, Peer, ProtocolEvent},
+        protocol::{broadcast::PutResult, gossip::Payload, RequestPullGuard},
+    },
+    PeerId,
+    Signer,
+};
+
+use crate::{cob, rate_limit::RateLimit};
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The projects to track contributors of, in addition to the ones the
+    /// local peer is a delegate of.
+    pub urns: BTreeSet<Urn>,
+    /// Peers never to track automatically.
+    pub ignore: BTreeSet<PeerId>,
+    /// The configuration of the tracking entries created.
+    pub tracking: tracking::Config,
+    /// The number of tracked peers of a project beyond which no more are
+    /// tracked automatically.
+    pub max_per_urn: usize,
+    pub max_per_hour: usize,
+}
+
+/// The peers a project references, which are not tracked yet.
+struct Candidates {
+    /// The number of peers already tracked.
+    tracked: usize,
+    peers: BTreeSet<PeerId>,
+}
+
+/// The authors of a collaborative object, as realised to JSON.
+///
+/// These are the peer ids given as the `author` of the object, and of the
+/// elements of its `comments`. Values which are not peer ids are ignored.
+pub fn authors(doc: &serde_json::Value) -> BTreeSet<PeerId> {
+    let author = |value: &serde_json::Value| {
+        value
+            .get("author")
+            .and_then(|author| author.as_str())
+            .and_then(|author| PeerId::from_str(author).ok())
+    };
+    author(doc)
+        .into_iter()
+        .chain(
+            doc.get("comments")
+                .and_then(|comments| comments.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(author),
+        )
+        .collect()
+}
+
+#[instrument(name = "autotrack subroutine", skip(peer, config))]
+pub async fn routine<S, G>(peer: Peer<S, G>, config: Config) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    info!(
+        projects = config.urns.len(),
+        "tracking contributors automatically"
+    );
+
+    let mut limit = RateLimit::new(config.max_per_hour, HOUR);
+    for urn in &config.urns {
+        scan(&peer, &config, &mut limit, urn).await;
+    }
+
+    let events = peer.subscribe();
+    futures::pin_mut!(events);
+    while let Some(event) = events.next().await {
+        let urn = match event {
+            Ok(ProtocolEvent::Replicated(replicated)) => replicated.urn,
+            Ok(ProtocolEvent::Gossip(gossip)) => {
+                let Gossip::Put {
+                    payload: Payload { urn, .. },
+                    result,
+                    ..
+                } = *gossip;
+                if !matches!(result, PutResult::Applied(_)) {
+                    continue;
+                }
+                urn
+            },
+            Ok(_) => continue,
+            Err(e) => {
+                warn!(err = %e, "event error");
+                continue;
+            },
+        };
+        scan(&peer, &config, &mut limit, &urn.with_path(None)).await;
+    }
+
+    Ok(())
+}
+
+/// Track the [`Candidates`] of `urn`, within the limits.
+async fn scan<S, G>(peer: &Peer<S, G>, config: &Config, limit: &mut RateLimit, urn: &Urn)
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let candidates = peer
+        .using_storage({
+            let urn = urn.clone();
+            let watched = config.urns.contains(&urn);
+            move |storage| candidates(storage, &urn, watched)
+        })
+        .await;
+    let Candidates { mut tracked, peers } = match candidates {
+        Ok(Ok(Some(candidates))) => candidates,
+        Ok(Ok(None)) => return,
+        Ok(Err(e)) => {
+            warn!(err = %e, %urn, "failed to determine contributors");
+            return;
+        },
+        Err(e) => {
+            warn!(err = %e, "failed to access storage");
+            return;
+        },
+    };
+
+    for contributor in peers.difference(&config.ignore) {
+        if tracked >= config.max_per_urn {
+            warn!(%urn, peer = %contributor, "tracking limit of project reached, skipping");
+            break;
+        }
+        if !limit.allow(Instant::now()) {
+            warn!(%urn, peer = %contributor, "tracking rate limit exceeded, skipping");
+            break;
+        }
+        let created = peer
+            .using_storage({
+                let urn = urn.clone();
+                let contributor = *contributor;
+                let config = config.tracking.clone();
+                move |storage| {
+                    tracking::track(
+                        storage,
+                        &urn,
+                        Some(contributor),
+                        config,
+                        tracking::policy::Track::MustNotExist,
+                    )
+                }
+            })
+            .await;
+        match created {
+            Ok(Ok(Ok(_))) => {
+                info!(%urn, peer = %contributor, "tracked contributor");
+                tracked += 1;
+            },
+            Ok(Ok(Err(e))) => debug!(err = %e, %urn, peer = %contributor, "already tracked"),
+            Ok(Err(e)) => warn!(err = %e, %urn, peer = %contributor, "failed to track"),
+            Err(e) => warn!(err = %e, "failed to access storage"),
+        }
+    }
+}
+
+/// Determine the [`Candidates`] of `urn`, if it is a project the local peer
+/// is a delegate of, or `watched`.
+fn candidates(storage: &Storage, urn: &Urn, watched: bool) -> anyhow::Result<Option<Candidates>> {
+    let local = storage.peer_id();
+    if !matches!(
+        identities::any::get(storage, urn)?,
+        Some(SomeIdentity::Project(_))
+    ) {
+        return Ok(None);
+    }
+    let project = match identities::project::verify(storage, urn)? {
+        None => return Ok(None),
+        Some(project) => project,
+    };
+    let delegates = project
+        .delegations()
+        .iter()
+        .direct()
+        .copied()
+        .chain(
+            project
+                .delegations()
+                .iter()
+                .indirect()
+                .flat_map(|person| person.delegations().iter().copied()),
+        )
+        .map(PeerId::from)
+        .collect::<BTreeSet<_>>();
+    if !watched && !delegates.contains(local) {
+        return Ok(None);
+    }
+
+    let mut peers = BTreeSet::new();
+    for delegate in &delegates {
+        let peer = (delegate != local).then(|| *delegate);
+        if let Some(refs) = Refs::load(storage, urn, peer)? {
+            peers.extend(refs.remotes.flatten().copied());
+        }
+    }
+    let cobs = storage.collaborative_objects(None);
+    for typename in [cob::ISSUE, cob::PATCH] {
+        let typename = TypeName::from_str(typename)
+            .map_err(|_| anyhow::anyhow!("invalid typename `{}`", typename))?;
+        for object in cobs.list(urn, &typename)? {
+            peers.extend(authors(&cob::realize(object.history())?));
+        }
+    }
+
+    let tracked =
+        tracking::tracked_peers(storage, Some(urn))?.collect::<Result<BTreeSet<_>, _>>()?;
+    peers.remove(local);
+    let peers = peers
+        .difference(&tracked)
+        .filter(|peer| !delegates.contains(peer))
+        .copied()
+        .collect();
+
+    Ok(Some(Candidates {
+        tracked: tracked.len(),
+        peers,
+    }))
+}
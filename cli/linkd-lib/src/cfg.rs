@@ -7,6 +7,8 @@ use std::{
     convert::TryFrom,
     io,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs as _},
+    path::Path,
+    sync::Arc,
     time::Duration,
 };
 
@@ -30,7 +32,7 @@ use librad::{
 };
 use lnk_clib::keys;
 
-use crate::{args, request_pull, tracking::Tracker};
+use crate::{anti_entropy, api::remote, args, request_pull, rotation, standby, tracking::Tracker};
 
 use lnk_clib::seed::{self, store::FileStore, Seeds};
 
@@ -52,6 +54,9 @@ pub enum Error {
     #[error(transparent)]
     Io(#[from] io::Error),
 
+    #[error(transparent)]
+    Encryption(#[from] storage::encryption::Error),
+
     #[error(transparent)]
     Init(#[from] storage::error::Init),
 
@@ -64,20 +69,70 @@ pub enum Error {
     #[error("no seed nodes could be resolved")]
     NoSeeds,
 
+    #[error("remote control requires a TLS certificate and key")]
+    RemoteControlTls,
+
+    #[error("standby requires the address of the primary's remote control port and its CA")]
+    StandbyControl,
+
+    #[error("could not resolve `{0}`")]
+    Resolve(String),
+
+    #[error(transparent)]
+    ResolvePrimary(#[from] seed::error::Resolve),
+
+    #[error(transparent)]
+    RemoteControlTokens(#[from] remote::error::Tokens),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 
+    #[cfg(feature = "mirror")]
+    #[error(transparent)]
+    Mirror(#[from] crate::mirror::error::Config),
+
+    #[cfg(feature = "notify")]
+    #[error("notifications require a sender and at least one recipient")]
+    NotifyAddresses,
+
+    #[cfg(feature = "notify")]
+    #[error("SMTP credentials require TLS")]
+    NotifyCleartextCredentials,
+
+    #[cfg(feature = "notify")]
+    #[error("{0}")]
+    NotifyTypename(String),
+
+    #[cfg(feature = "notify")]
+    #[error(transparent)]
+    NotifyTemplate(#[from] crate::notify::error::Template),
+
+    #[error(transparent)]
+    PeerConfig(#[from] net::peer::error::Config),
+
     #[error(transparent)]
     Profile(#[from] librad::profile::Error),
 
+    #[error("malformed key rotation")]
+    Rotation(#[source] serde_json::Error),
+
     #[error(transparent)]
     SecretKey(#[from] IntoSecretKeyError),
 
     #[error(transparent)]
     Seed(#[from] seed::error::Load),
 
+    #[error(transparent)]
+    SeedPins(#[from] seed::pin::error::Pin),
+
+    #[error(transparent)]
+    SeedRotations(#[from] seed::pin::error::Rotation),
+
     #[error(transparent)]
     Timeout(#[from] Elapsed),
+
+    #[error(transparent)]
+    Tls(#[from] remote::error::Tls),
 }
 
 pub enum RunMode {
@@ -96,6 +151,21 @@ pub struct Cfg<Disco, Signer, Auth> {
     pub metrics: Option<Metrics>,
     pub peer: PeerConfig<Signer, Auth>,
     pub tracker: Option<Tracker>,
+    #[cfg(feature = "autotrack")]
+    pub autotrack: Option<crate::autotrack::Config>,
+    pub replication_workers: usize,
+    pub replication_workers_per_urn: usize,
+    pub remote_control: Option<remote::Config>,
+    pub announce_debounce: Option<Duration>,
+    pub anti_entropy: Option<anti_entropy::Config>,
+    pub rotations: Option<rotation::Config>,
+    pub standby: Option<standby::Config>,
+    #[cfg(feature = "mirror")]
+    pub mirror: Option<crate::mirror::Config>,
+    #[cfg(feature = "notify")]
+    pub notify: Option<crate::notify::Config>,
+    #[cfg(feature = "http")]
+    pub http: Option<crate::http::Config>,
     pub run_mode: RunMode,
     pub profile: Profile,
 }
@@ -103,8 +173,9 @@ pub struct Cfg<Disco, Signer, Auth> {
 impl Cfg<discovery::Static, BoxedSigner, request_pull::State> {
     pub async fn from_args(args: &args::Args) -> Result<Self, Error> {
         let membership = membership::Params::default();
+        let dial = dial(&args.protocol);
         let seeds = if !args.bootstraps.is_empty() {
-            let (seeds, failures) = Seeds::resolve(args.bootstraps.iter()).await;
+            let (seeds, failures) = Seeds::resolve_with(args.bootstraps.iter(), &dial).await;
             for fail in failures {
                 tracing::warn!("failed to load bootstrap seed: {}", fail);
             }
@@ -116,10 +187,28 @@ impl Cfg<discovery::Static, BoxedSigner, request_pull::State> {
             seeds
         } else {
             let store = FileStore::<String>::new(paths::seeds()?)?;
-            let (seeds, failures) = Seeds::load(&store, membership.max_active).await?;
+            let mut pins = seed::Pins::load(paths::seed_pins()?)?
+                .with_rotations(seed::pin::load_rotations(paths::seed_rotations()?)?);
+            let (seeds, failures) = Seeds::load_with(&store, membership.max_active, &dial, |seed| {
+                let (seed, checked) = pins.check(seed)?;
+                match checked {
+                    seed::pin::Checked::FirstUse => {
+                        tracing::info!(peer = %seed.peer, addr = %seed.addrs, "pinned seed on first use")
+                    },
+                    seed::pin::Checked::Rotated { from, to } => {
+                        tracing::info!(%from, %to, addr = %seed.addrs, "seed key was rotated")
+                    },
+                    seed::pin::Checked::Known => {},
+                }
+                Ok(seed)
+            })
+            .await?;
 
             for fail in &failures {
-                tracing::warn!("failed to load configured seed: {}", fail)
+                match fail {
+                    seed::error::Load::Pin(e) => tracing::error!("refusing configured seed: {}", e),
+                    _ => tracing::warn!("failed to load configured seed: {}", fail),
+                }
             }
 
             if seeds.is_empty() && !failures.is_empty() {
@@ -128,18 +217,49 @@ impl Cfg<discovery::Static, BoxedSigner, request_pull::State> {
 
             seeds
         };
+        let rotations = if seeds.is_empty() {
+            None
+        } else {
+            Some(rotation::Config {
+                seeds: seeds
+                    .0
+                    .iter()
+                    .map(|seed| (seed.peer, seed.addrs.clone()))
+                    .collect(),
+                path: paths::seed_rotations()?,
+                interval: rotation::DEFAULT_INTERVAL,
+            })
+        };
         let disco = discovery::Static::try_from(seeds)?;
+        let pinned = {
+            let (pinned, failures) = Seeds::resolve_with(args.pinned.iter(), &dial).await;
+            for fail in failures {
+                tracing::warn!("failed to resolve pinned peer: {}", fail);
+            }
+            net::protocol::pinned::Config {
+                peers: pinned
+                    .0
+                    .into_iter()
+                    .map(|seed| (seed.peer, seed.addrs))
+                    .collect(),
+                ..Default::default()
+            }
+        };
         let profile = Profile::try_from(args)?;
         let signer = construct_signer(args, &profile).await?;
 
+        // Unlock the storage if it is encrypted at rest, using a key derived
+        // from the profile key.
+        if storage::encryption::status(profile.paths())? == storage::encryption::Status::Locked {
+            tracing::info!("unlocking encrypted storage");
+            let key = storage::encryption::derive_key(&signer)?;
+            storage::encryption::unlock(profile.paths(), &key)?;
+        }
+
         // Ensure the storage is accessible for the created profile and signer.
         storage::Storage::init(profile.paths(), signer.clone())?;
 
-        let listen_addr = match args.protocol.listen {
-            args::ProtocolListen::Any => *ANY,
-            args::ProtocolListen::Localhost => *LOCALHOST,
-            args::ProtocolListen::Provided { addr } => addr,
-        };
+        let listen_addr = listen_addr(&args.protocol.listen);
 
         let metrics = match args.metrics.provider {
             Some(args::MetricsProvider::Graphite) => Some(Metrics::Graphite(
@@ -166,6 +286,8 @@ impl Cfg<discovery::Static, BoxedSigner, request_pull::State> {
             ),
         });
 
+        let remote_control = remote_control(&args.remote_control)?;
+
         let storage_lock = storage::pool::Initialised::no();
         let request_pull = request_pull::State::new(
             storage::Pool::new(
@@ -179,30 +301,283 @@ impl Cfg<discovery::Static, BoxedSigner, request_pull::State> {
             tracker.clone(),
         );
 
+        // Built via the `Builder`, so that contradicting options are rejected
+        // before the peer is started
+        let peer = net::peer::Peer::builder(signer, profile.paths().clone())
+            .listen_addr(listen_addr)
+            .membership(membership)
+            .network(args.protocol.network.clone())
+            .replication(net::replication::Config {
+                verify_signatures: args.replication.verify_signatures,
+                ..Default::default()
+            })
+            .request_pull(request_pull)
+            .bandwidth(net::quic::shaping::Config {
+                bulk_ceiling: args.protocol.bulk_bandwidth,
+            })
+            .dial(dial)
+            .gossip_batch(net::protocol::batch::Config {
+                enabled: args.protocol.gossip_batch,
+                ..Default::default()
+            })
+            .pinned(pinned)
+            .compression(net::protocol::compress::Config {
+                enabled: args.protocol.gossip_compression,
+                ..Default::default()
+            })
+            .interest(net::protocol::interest::Config {
+                enabled: args.protocol.gossip_interest,
+            })
+            .backoff(net::protocol::backoff::Config {
+                enabled: args.protocol.gossip_backoff,
+                ..Default::default()
+            })
+            .usage(usage(&args.protocol))
+            .private(args.protocol.private)
+            .rotation(args.rotation.as_deref().map(load_rotation).transpose()?)
+            .storage(net::peer::config::Storage {
+                protocol: net::peer::config::ProtocolStorage {
+                    admission: net::peer::storage::admission::Config {
+                        max_running: args.replication.gossip_fetches,
+                        max_queued: args.replication.gossip_queue,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .config()?;
+
         Ok(Self {
             disco,
             metrics,
-            peer: PeerConfig {
-                signer,
-                protocol: net::protocol::Config {
-                    paths: profile.paths().clone(),
-                    listen_addr,
-                    advertised_addrs: None,
-                    membership,
-                    network: args.protocol.network.clone(),
-                    replication: Default::default(),
-                    rate_limits: Default::default(),
-                    request_pull,
-                },
-                storage: Default::default(),
-            },
+            peer,
             tracker,
+            #[cfg(feature = "autotrack")]
+            autotrack: autotrack(&args.autotrack),
+            replication_workers: args.replication.workers,
+            replication_workers_per_urn: args.replication.workers_per_urn,
+            remote_control,
+            announce_debounce: args.announce_debounce.as_ref().map(Duration::from),
+            anti_entropy: anti_entropy(&args.anti_entropy),
+            rotations,
+            standby: standby(&args.standby).await?,
+            #[cfg(feature = "mirror")]
+            mirror: mirror(&args.mirror)?,
+            #[cfg(feature = "notify")]
+            notify: notify(&args.notify)?,
+            #[cfg(feature = "http")]
+            http: args.http.listen.map(|listen| crate::http::Config {
+                listen,
+                cors_origin: args.http.cors_origin.clone(),
+            }),
             profile,
             run_mode,
         })
     }
 }
 
+/// Load the rotation of the local key to serve, see [`args::Args::rotation`].
+fn load_rotation(path: &Path) -> Result<net::protocol::rotation::Rotation, Error> {
+    serde_json::from_slice(&std::fs::read(path)?).map_err(Error::Rotation)
+}
+
+pub(crate) fn listen_addr(listen: &args::ProtocolListen) -> SocketAddr {
+    match listen {
+        args::ProtocolListen::Any => *ANY,
+        args::ProtocolListen::Localhost => *LOCALHOST,
+        args::ProtocolListen::Provided { addr } => *addr,
+    }
+}
+
+pub(crate) fn dial(args: &args::ProtocolArgs) -> net::dial::Config {
+    let mut config = net::dial::Config {
+        family: args.address_family,
+        resolver: args.resolver,
+        ..Default::default()
+    };
+    if let Some(secs) = args.dns_timeout {
+        config.dns_timeout = Duration::from_secs(secs);
+    }
+    config
+}
+
+pub(crate) fn usage(args: &args::ProtocolArgs) -> net::protocol::usage::Config {
+    let mut config = net::protocol::usage::Config::default();
+    if let Some(days) = args.usage_retention_days {
+        config.retention_days = days;
+    }
+    config
+}
+
+pub fn executor(args: &args::ExecutorArgs) -> link_async::spawn::Config {
+    link_async::spawn::Config {
+        worker_threads: args.worker_threads,
+        blocking_threads: args.blocking_threads,
+        blocking_queue: args.blocking_queue,
+        ..Default::default()
+    }
+}
+
+fn anti_entropy(args: &args::AntiEntropyArgs) -> Option<anti_entropy::Config> {
+    args.interval_secs.map(|secs| anti_entropy::Config {
+        interval: Duration::from_secs(secs),
+        max_fetches: args.max_fetches,
+    })
+}
+
+async fn standby(args: &args::StandbyArgs) -> Result<Option<standby::Config>, Error> {
+    let primary = match &args.primary {
+        None => return Ok(None),
+        Some(primary) => primary,
+    };
+    let (control, ca) = args
+        .control
+        .as_deref()
+        .zip(args.control_ca.as_deref())
+        .ok_or(Error::StandbyControl)?;
+    let primary = primary.resolve().await?;
+    let addr = control
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| Error::Resolve(control.to_owned()))?;
+    let server_name = match &args.control_name {
+        Some(name) => name.clone(),
+        None => control
+            .rsplit_once(':')
+            .map_or(control, |(host, _)| host)
+            .to_owned(),
+    };
+    let identity = args
+        .control_cert
+        .as_deref()
+        .zip(args.control_key.as_deref());
+    let token = match &args.control_token {
+        Some(path) => Some(std::fs::read_to_string(path)?.trim().to_owned()),
+        None => None,
+    };
+
+    Ok(Some(standby::Config {
+        primary: primary.peer,
+        addrs: primary.addrs,
+        control: standby::Control {
+            addr,
+            server_name,
+            tls: Arc::new(remote::client_tls_config(ca, identity)?),
+            token,
+        },
+        interval: Duration::from_secs(args.interval_secs),
+    }))
+}
+
+#[cfg(feature = "mirror")]
+fn mirror(args: &args::MirrorArgs) -> Result<Option<crate::mirror::Config>, Error> {
+    args.config
+        .as_deref()
+        .map(|path| {
+            crate::mirror::Config::load(path, Duration::from_secs(args.interval_secs))
+                .map_err(Error::from)
+        })
+        .transpose()
+}
+
+#[cfg(feature = "autotrack")]
+fn autotrack(args: &args::AutoTrackArgs) -> Option<crate::autotrack::Config> {
+    (args.enabled || !args.urns.is_empty()).then(|| crate::autotrack::Config {
+        urns: args
+            .urns
+            .iter()
+            .map(|urn| urn.clone().with_path(None))
+            .collect(),
+        ignore: args.ignore.iter().copied().collect(),
+        tracking: args.config.clone().unwrap_or_default(),
+        max_per_urn: args.max_per_urn,
+        max_per_hour: args.max_per_hour,
+    })
+}
+
+#[cfg(feature = "notify")]
+fn notify(args: &args::NotifyArgs) -> Result<Option<crate::notify::Config>, Error> {
+    let addr = match &args.smtp {
+        None => return Ok(None),
+        Some(addr) => addr.clone(),
+    };
+    let from = match &args.from {
+        Some(from) if !args.to.is_empty() => from.clone(),
+        _ => return Err(Error::NotifyAddresses),
+    };
+    if std::iter::once(&from)
+        .chain(&args.to)
+        .any(|addr| addr.contains(|c: char| c == '\r' || c == '\n'))
+    {
+        return Err(Error::NotifyAddresses);
+    }
+    let server_name = addr
+        .rsplit_once(':')
+        .map_or(&*addr, |(host, _)| host)
+        .to_owned();
+    let tls = args
+        .smtp_tls_ca
+        .as_deref()
+        .map(|ca| remote::client_tls_config(ca, None).map(Arc::new))
+        .transpose()?;
+    let credentials = args.smtp_user.clone().zip(args.smtp_password.clone());
+    if credentials.is_some() && tls.is_none() {
+        return Err(Error::NotifyCleartextCredentials);
+    }
+    let templates = match &args.templates {
+        Some(dir) => crate::notify::Templates::load(dir)?,
+        None => crate::notify::Templates::default(),
+    };
+
+    Ok(Some(crate::notify::Config {
+        smtp: crate::notify::Smtp {
+            addr,
+            server_name,
+            tls,
+            credentials,
+            from,
+            to: args.to.clone(),
+        },
+        urns: args.urns.iter().cloned().collect(),
+        typenames: crate::notify::typenames(&args.typenames).map_err(Error::NotifyTypename)?,
+        handles: args
+            .handles
+            .iter()
+            .map(|h| h.trim_start_matches('@').to_owned())
+            .collect(),
+        templates,
+        max_per_hour: args.max_per_hour,
+    }))
+}
+
+fn remote_control(args: &args::RemoteControlArgs) -> Result<Option<remote::Config>, Error> {
+    let listen = match args.listen {
+        None => return Ok(None),
+        Some(listen) => listen,
+    };
+    let (cert, key) = args
+        .cert
+        .as_deref()
+        .zip(args.key.as_deref())
+        .ok_or(Error::RemoteControlTls)?;
+    let tls = remote::tls_config(cert, key, args.client_ca.as_deref())?;
+    let tokens = match &args.tokens {
+        Some(path) => remote::Tokens::load(path)?,
+        None => remote::Tokens::default(),
+    };
+    if tokens.is_empty() && args.client_ca.is_none() {
+        warn!("remote control enabled without tokens or client CA, no client will be able to authenticate");
+    }
+
+    Ok(Some(remote::Config {
+        listen,
+        tls: Arc::new(tls),
+        tokens,
+        client_cert_access: args.client_cert_access,
+    }))
+}
+
 pub enum Metrics {
     Graphite(SocketAddr),
 }
@@ -222,8 +597,16 @@ impl TryFrom<&args::Args> for Profile {
     }
 }
 
-async fn construct_signer(args: &args::Args, profile: &Profile) -> anyhow::Result<BoxedSigner> {
+pub(crate) async fn construct_signer(
+    args: &args::Args,
+    profile: &Profile,
+) -> anyhow::Result<BoxedSigner> {
     match args.signer {
+        #[cfg(windows)]
+        args::Signer::SshAgent => {
+            bail!("the ssh-agent signer is not supported on Windows, use `--signer key`")
+        },
+        #[cfg(unix)]
         args::Signer::SshAgent => {
             tokio::task::spawn_blocking({
                 let profile = profile.clone();
@@ -232,6 +615,18 @@ async fn construct_signer(args: &args::Args, profile: &Profile) -> anyhow::Resul
             })
             .await?
         },
+        #[cfg(feature = "keychain")]
+        args::Signer::Keychain => {
+            tokio::task::spawn_blocking({
+                let profile = profile.clone();
+                move || keys::keychain::os::signer(&profile).map_err(anyhow::Error::from)
+            })
+            .await?
+        },
+        #[cfg(not(feature = "keychain"))]
+        args::Signer::Keychain => {
+            bail!("the keychain signer requires building with the `keychain` feature")
+        },
         args::Signer::Key => {
             let bytes = match args.key.source {
                 args::KeySource::Ephemeral => {
@@ -0,0 +1,22 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::collaborative_objects::History;
+
+/// The well-known types of collaborative objects.
+pub const ISSUE: &str = "xyz.radicle.issue";
+pub const PATCH: &str = "xyz.radicle.patch";
+
+/// Realise the state of a collaborative object as JSON.
+pub fn realize(history: &History) -> anyhow::Result<serde_json::Value> {
+    match history {
+        History::Automerge(bytes) => {
+            let backend = automerge::Backend::load(bytes.to_vec())?;
+            let mut frontend = automerge::Frontend::new();
+            frontend.apply_patch(backend.get_patch()?)?;
+            Ok(frontend.state().to_json())
+        },
+    }
+}
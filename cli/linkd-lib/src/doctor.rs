@@ -0,0 +1,406 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Self-test of the environment the node runs in.
+//!
+//! [`run`] checks the basics most problems with running a node come down to,
+//! and produces a [`Report`] with hints on how to fix what's broken. It is
+//! exposed on the command line as `linkd --doctor`.
+
+use std::{
+    convert::TryFrom as _,
+    fmt,
+    io,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{net, time::timeout};
+
+use librad::{
+    git::storage::ReadOnly,
+    net::protocol::membership,
+    paths,
+    profile::Profile,
+    Signer as _,
+};
+use lnk_clib::seed::{store::FileStore, Seeds};
+
+use crate::{args::Args, cfg};
+
+/// The server queried to determine clock skew.
+const NTP_SERVER: &str = "pool.ntp.org:123";
+/// Time to wait for responses from the network.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(3);
+/// Clock skew above which a warning is issued.
+const MAX_SKEW: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ok => " ok ",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+        })
+    }
+}
+
+/// The outcome of a single check.
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub message: String,
+    /// What to do about a failed check.
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    /// The worst [`Status`] of all checks.
+    pub fn status(&self) -> Status {
+        self.checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(Status::Ok)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "[{}] {}: {}", check.status, check.name, check.message)?;
+            if let Some(hint) = &check.hint {
+                writeln!(f, "       {}", hint)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run all checks for the node configured by `args`.
+pub async fn run(args: &Args) -> Report {
+    let mut checks = Vec::new();
+
+    match Profile::try_from(args) {
+        Err(e) => checks.push(Check::fail(
+            "profile",
+            e.to_string(),
+            "check `--lnk-home` and `--profile-id`, or create a profile via `lnk profile create`",
+        )),
+        Ok(profile) => {
+            checks.push(keys(args, &profile).await);
+            checks.push(storage(&profile));
+        },
+    }
+    checks.push(listen(args));
+    checks.extend(seeds(args).await);
+    checks.push(clock().await);
+    checks.push(fd_limit());
+
+    Report { checks }
+}
+
+async fn keys(args: &Args, profile: &Profile) -> Check {
+    const NAME: &str = "keys";
+
+    let signer = match cfg::construct_signer(args, profile).await {
+        Ok(signer) => signer,
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("{:#}", e),
+                "add the key to the ssh-agent via `lnk profile ssh add`, or use `--signer key`",
+            )
+        },
+    };
+    match signer.sign(b"linkd doctor").await {
+        Ok(_) => Check::ok(NAME, format!("signing as {}", signer.peer_id())),
+        Err(e) => Check::fail(
+            NAME,
+            format!("signing failed: {}", e),
+            "check that the ssh-agent is running and `SSH_AUTH_SOCK` is set",
+        ),
+    }
+}
+
+fn storage(profile: &Profile) -> Check {
+    const NAME: &str = "storage";
+
+    let storage = match ReadOnly::open(profile.paths()) {
+        Ok(storage) => storage,
+        Err(e) => {
+            return Check::warn(
+                NAME,
+                format!(
+                    "could not open storage at {}: {}",
+                    profile.paths().git_dir().display(),
+                    e
+                ),
+                "the storage is created on first start; if it exists, check its permissions",
+            )
+        },
+    };
+    match storage.dangling_refs() {
+        Ok(dangling) if dangling.is_empty() => {
+            Check::ok(NAME, format!("{} is consistent", storage.path().display()))
+        },
+        Ok(dangling) => Check::fail(
+            NAME,
+            format!(
+                "{} references point to missing objects, eg. {}",
+                dangling.len(),
+                dangling[0]
+            ),
+            "run `git fsck` in the storage directory, and re-replicate the affected projects",
+        ),
+        Err(e) => Check::fail(
+            NAME,
+            format!("reading references failed: {}", e),
+            "run `git fsck` in the storage directory",
+        ),
+    }
+}
+
+fn listen(args: &Args) -> Check {
+    const NAME: &str = "listen";
+
+    let addr = cfg::listen_addr(&args.protocol.listen);
+    match UdpSocket::bind(addr) {
+        Ok(_) => Check::ok(NAME, format!("can bind to {}", addr)),
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => Check::fail(
+            NAME,
+            format!("{} is already in use", addr),
+            "check whether another linkd is running, or choose a different `--protocol-listen`",
+        ),
+        Err(e) => Check::fail(
+            NAME,
+            format!("can't bind to {}: {}", addr, e),
+            "choose a different `--protocol-listen`",
+        ),
+    }
+}
+
+/// Probe the UDP ports of the configured seeds.
+///
+/// UDP is connectionless, so this can only detect seeds which actively
+/// refuse datagrams (ie. nothing is listening on the port), or networks which
+/// are unreachable. Silence is taken as a success, as QUIC endpoints drop
+/// packets they don't understand.
+async fn seeds(args: &Args) -> Vec<Check> {
+    const NAME: &str = "seeds";
+
+    let dial = cfg::dial(&args.protocol);
+    let (seeds, failures): (Seeds, Vec<String>) = if !args.bootstraps.is_empty() {
+        let (seeds, failures) = Seeds::resolve_with(args.bootstraps.iter(), &dial).await;
+        (seeds, failures.iter().map(ToString::to_string).collect())
+    } else {
+        let store = paths::seeds()
+            .map_err(|e| e.to_string())
+            .and_then(|path| FileStore::<String>::new(path).map_err(|e| e.to_string()));
+        let loaded = match store {
+            Ok(store) => {
+                Seeds::load_with(&store, membership::Params::default().max_active, &dial, Ok)
+                    .await
+                    .map_err(|e| e.to_string())
+            },
+            Err(e) => Err(e),
+        };
+        match loaded {
+            Ok((seeds, failures)) => (seeds, failures.iter().map(ToString::to_string).collect()),
+            Err(e) => {
+                return vec![Check::fail(
+                    NAME,
+                    format!("could not load seeds: {}", e),
+                    "check the seeds file, or provide seeds via `--bootstrap`",
+                )]
+            },
+        }
+    };
+
+    let mut checks = failures
+        .into_iter()
+        .map(|e| {
+            Check::warn(
+                NAME,
+                e,
+                "check the seed's address and your DNS configuration",
+            )
+        })
+        .collect::<Vec<_>>();
+    if seeds.is_empty() && checks.is_empty() {
+        checks.push(Check::warn(
+            NAME,
+            "no seeds configured",
+            "the node can only connect to peers which connect to it; add seeds via `--bootstrap`",
+        ));
+    }
+    for seed in seeds.0 {
+        for addr in seed.addrs {
+            let check = match probe(addr).await {
+                Ok(()) => Check::ok(NAME, format!("{} at {} did not refuse", seed.peer, addr)),
+                Err(e) => Check::fail(
+                    NAME,
+                    format!("{} at {}: {}", seed.peer, addr, e),
+                    "check the seed's address, and whether a firewall blocks outgoing UDP",
+                ),
+            };
+            checks.push(check);
+        }
+    }
+    checks
+}
+
+async fn probe(addr: SocketAddr) -> io::Result<()> {
+    let bind: SocketAddr = if addr.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = net::UdpSocket::bind(bind).await?;
+    socket.connect(addr).await?;
+    socket.send(&[0; 32]).await?;
+    // An ICMP "port unreachable" surfaces as an error on the next receive
+    match timeout(NETWORK_TIMEOUT, socket.recv(&mut [0; 32])).await {
+        Ok(Err(e)) => Err(e),
+        Ok(Ok(_)) | Err(_) => Ok(()),
+    }
+}
+
+async fn clock() -> Check {
+    const NAME: &str = "clock";
+
+    match timeout(NETWORK_TIMEOUT, sntp_offset(NTP_SERVER)).await {
+        Ok(Ok(offset)) if offset.abs() < MAX_SKEW.as_secs_f64() => Check::ok(
+            NAME,
+            format!("skew against {} is {:.3}s", NTP_SERVER, offset),
+        ),
+        Ok(Ok(offset)) => Check::warn(
+            NAME,
+            format!("skew against {} is {:.3}s", NTP_SERVER, offset),
+            "enable time synchronisation (eg. NTP) on this machine",
+        ),
+        Ok(Err(e)) => Check::warn(
+            NAME,
+            format!("could not query {}: {}", NTP_SERVER, e),
+            "make sure the system clock is synchronised",
+        ),
+        Err(_) => Check::warn(
+            NAME,
+            format!("{} did not respond", NTP_SERVER),
+            "make sure the system clock is synchronised",
+        ),
+    }
+}
+
+/// Query the offset of the local clock in seconds against an (S)NTP server.
+async fn sntp_offset(server: &str) -> io::Result<f64> {
+    /// Seconds between the NTP epoch (1900) and the UNIX epoch.
+    const NTP_EPOCH: f64 = 2_208_988_800.0;
+
+    fn now() -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+    }
+
+    let socket = net::UdpSocket::bind(SocketAddr::from(([0, 0, 0, 0], 0))).await?;
+    socket.connect(server).await?;
+
+    // LI = 0, VN = 3, Mode = 3 (client)
+    let mut packet = [0u8; 48];
+    packet[0] = 0x1b;
+    let sent = now();
+    socket.send(&packet).await?;
+    let n = socket.recv(&mut packet).await?;
+    let received = now();
+    if n < 48 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "short NTP response",
+        ));
+    }
+
+    let transmit = {
+        let secs = u32::from_be_bytes([packet[40], packet[41], packet[42], packet[43]]);
+        let frac = u32::from_be_bytes([packet[44], packet[45], packet[46], packet[47]]);
+        f64::from(secs) - NTP_EPOCH + f64::from(frac) / f64::from(u32::MAX)
+    };
+    Ok(transmit - (sent + received) / 2.0)
+}
+
+#[cfg(unix)]
+fn fd_limit() -> Check {
+    use nix::sys::resource::{getrlimit, Resource};
+
+    const NAME: &str = "fd limit";
+    /// Recommended minimum limit of open file descriptors.
+    const MIN_NOFILE: u64 = 4096;
+
+    match getrlimit(Resource::RLIMIT_NOFILE) {
+        Ok((Some(soft), _)) if (soft as u64) < MIN_NOFILE => Check::warn(
+            NAME,
+            format!("open files are limited to {}", soft),
+            format!(
+                "raise the limit to at least {}, eg. via `ulimit -n` or `LimitNOFILE=` in the systemd unit",
+                MIN_NOFILE
+            ),
+        ),
+        Ok((Some(soft), _)) => Check::ok(NAME, format!("open files are limited to {}", soft)),
+        Ok((None, _)) => Check::ok(NAME, "open files are not limited"),
+        Err(e) => Check::warn(
+            NAME,
+            format!("could not determine limit: {}", e),
+            format!("make sure at least {} files can be opened", MIN_NOFILE),
+        ),
+    }
+}
+
+#[cfg(windows)]
+fn fd_limit() -> Check {
+    Check::ok("fd limit", "not applicable on Windows")
+}
@@ -0,0 +1,513 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Read-only HTTP API to the replicated data.
+//!
+//! Web frontends need to read the projects, identities and collaborative
+//! objects held by a seed, which otherwise requires running an indexer next
+//! to it. This routine serves them as JSON, straight from the monorepo:
+//!
+//! * `GET /v1/projects` and `GET /v1/persons`: the identities of either kind
+//! * `GET /v1/{projects,persons}/<urn>`: a single identity
+//! * `GET /v1/{projects,persons}/<urn>/refs`: the references of its namespace
+//! * `GET /v1/projects/<urn>/issues[/<id>]`: the issues of a project
+//! * `GET /v1/projects/<urn>/patches[/<id>]`: the patches of a project
+//! * `GET /v1/projects/<urn>/cobs/<typename>[/<id>]`: the collaborative objects
+//!   of any type
+//!
+//! Lists are ordered by URN or object id, paginated with the `after` and
+//! `per_page` query parameters, and returned as a [`Page`]: the `next` field of
+//! a page is the `after` value of the following page. Identities are listed
+//! from the namespace [`index`], so that only the identities on the requested
+//! page are loaded. Every response carries an `ETag`, which clients can pass
+//! as `If-None-Match` to get a `304 Not Modified` instead of the same body
+//! again.
+
+use std::{
+    collections::BTreeSet,
+    convert::Infallible,
+    net::SocketAddr,
+    ops::Bound,
+    str::FromStr as _,
+    sync::Arc,
+};
+
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body,
+    Method,
+    Request,
+    Response,
+    Server,
+    StatusCode,
+    Uri,
+};
+use serde_json::json;
+use tracing::{info, instrument, warn};
+
+use librad::{
+    collaborative_objects::{ObjectId, RefsStorage as _, TypeName},
+    git::{
+        identities::{self, SomeIdentity},
+        storage::index::{self, Filter},
+        Urn,
+    },
+    net::{peer::Peer, protocol::RequestPullGuard},
+    Signer,
+};
+
+use crate::cob;
+
+/// Number of items per page, unless the `per_page` parameter is given.
+pub const DEFAULT_PER_PAGE: usize = 30;
+/// Upper bound of the `per_page` parameter.
+pub const MAX_PER_PAGE: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub listen: SocketAddr,
+    /// Value of the `Access-Control-Allow-Origin` header, if cross-origin
+    /// requests are allowed.
+    pub cors_origin: Option<String>,
+}
+
+/// A page of a list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Page<T> {
+    pub per_page: usize,
+    pub items: Vec<T>,
+    /// If there are more items, the `after` parameter to obtain the next page.
+    pub next: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Take the first `per_page` of `items`, which start after the cursor of
+    /// the requested page. `cursor` is the `after` parameter to continue
+    /// after an item.
+    ///
+    /// `items` are consumed lazily, at most one item beyond the page is taken.
+    pub fn of<I, F>(items: I, per_page: usize, cursor: F) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        F: Fn(&T) -> String,
+    {
+        let mut items = items.into_iter();
+        let page = items.by_ref().take(per_page).collect::<Vec<_>>();
+        let next = match items.next() {
+            Some(_) => page.last().map(cursor),
+            None => None,
+        };
+        Self {
+            per_page,
+            items: page,
+            next,
+        }
+    }
+}
+
+impl Page<serde_json::Value> {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "per_page": self.per_page,
+            "items": self.items,
+            "next": self.next,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Person,
+    Project,
+}
+
+impl Kind {
+    fn of(collection: &str) -> Self {
+        match collection {
+            "projects" => Self::Project,
+            _ => Self::Person,
+        }
+    }
+}
+
+enum Failure {
+    BadRequest(String),
+    NotFound,
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for Failure {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+
+#[instrument(name = "http subroutine", skip(peer, config))]
+pub async fn routine<S, G>(peer: Peer<S, G>, config: Config) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    info!(listen = %config.listen, "serving HTTP API");
+
+    let listen = config.listen;
+    let config = Arc::new(config);
+    let make = make_service_fn(move |_| {
+        let peer = peer.clone();
+        let config = config.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                handle(peer.clone(), config.clone(), req)
+            }))
+        }
+    });
+    Server::try_bind(&listen)?.serve(make).await?;
+
+    Ok(())
+}
+
+async fn handle<S, G>(
+    peer: Peer<S, G>,
+    config: Arc<Config>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let mut res = if req.method() == Method::GET || req.method() == Method::HEAD {
+        match route(&peer, req.uri()).await {
+            Ok(value) => respond(&req, &value),
+            Err(Failure::BadRequest(reason)) => failure(StatusCode::BAD_REQUEST, &reason),
+            Err(Failure::NotFound) => failure(StatusCode::NOT_FOUND, "not found"),
+            Err(Failure::Internal(e)) => {
+                warn!(err = %e, path = %req.uri().path(), "failed to serve request");
+                failure(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+            },
+        }
+    } else {
+        failure(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+    };
+
+    if req.method() == Method::HEAD {
+        *res.body_mut() = Body::empty();
+    }
+    if let Some(origin) = config
+        .cors_origin
+        .as_deref()
+        .and_then(|o| header::HeaderValue::from_str(o).ok())
+    {
+        res.headers_mut()
+            .insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    }
+    Ok(res)
+}
+
+async fn route<S, G>(peer: &Peer<S, G>, uri: &Uri) -> Result<serde_json::Value, Failure>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let (after, per_page) = pagination(uri.query().unwrap_or(""))?;
+    let segments = uri.path().trim_matches('/').split('/').collect::<Vec<_>>();
+
+    match segments.as_slice() {
+        ["v1", collection @ ("projects" | "persons")] => {
+            let after = after.as_deref().map(parse_urn).transpose()?;
+            Ok(list_identities(peer, Kind::of(collection), after, per_page)
+                .await?
+                .to_json())
+        },
+        ["v1", collection @ ("projects" | "persons"), urn] => {
+            get_identity(peer, Kind::of(collection), parse_urn(urn)?).await
+        },
+        ["v1", "projects" | "persons", urn, "refs"] => refs(peer, parse_urn(urn)?).await,
+        ["v1", "projects", urn, rest @ ..] => {
+            let urn = parse_urn(urn)?;
+            let (typename, id) = match rest {
+                ["issues", id @ ..] => (cob::ISSUE, id),
+                ["patches", id @ ..] => (cob::PATCH, id),
+                ["cobs", typename, id @ ..] => (*typename, id),
+                _ => return Err(Failure::NotFound),
+            };
+            let typename = TypeName::from_str(typename)
+                .map_err(|_| Failure::BadRequest(format!("invalid typename `{}`", typename)))?;
+            match id {
+                [] => {
+                    let after = after.as_deref().map(parse_object_id).transpose()?;
+                    Ok(list_objects(peer, urn, typename, after, per_page)
+                        .await?
+                        .to_json())
+                },
+                [id] => get_object(peer, urn, typename, parse_object_id(id)?).await,
+                _ => Err(Failure::NotFound),
+            }
+        },
+        _ => Err(Failure::NotFound),
+    }
+}
+
+/// Parse the `after` and `per_page` query parameters.
+fn pagination(query: &str) -> Result<(Option<String>, usize), Failure> {
+    let mut after = None;
+    let mut per_page = DEFAULT_PER_PAGE;
+    for param in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        match key {
+            "after" => after = Some(value.to_owned()),
+            "per_page" => {
+                per_page = value
+                    .parse::<usize>()
+                    .map_err(|_| Failure::BadRequest(format!("invalid `{}`", key)))?
+                    .clamp(1, MAX_PER_PAGE)
+            },
+            _ => {},
+        }
+    }
+    Ok((after, per_page))
+}
+
+fn parse_urn(urn: &str) -> Result<Urn, Failure> {
+    Urn::from_str(urn).map_err(|_| Failure::BadRequest(format!("invalid urn `{}`", urn)))
+}
+
+fn parse_object_id(id: &str) -> Result<ObjectId, Failure> {
+    ObjectId::from_str(id).map_err(|_| Failure::BadRequest(format!("invalid object id `{}`", id)))
+}
+
+fn identity_json(identity: SomeIdentity) -> anyhow::Result<Option<(Kind, serde_json::Value)>> {
+    let (kind, urn, mut value) = match identity {
+        SomeIdentity::Person(person) => {
+            (Kind::Person, person.urn(), serde_json::to_value(&person)?)
+        },
+        SomeIdentity::Project(project) => (
+            Kind::Project,
+            project.urn(),
+            serde_json::to_value(&project)?,
+        ),
+        _ => return Ok(None),
+    };
+    if let Some(obj) = value.as_object_mut() {
+        obj.insert("urn".to_owned(), json!(urn.to_string()));
+    }
+    Ok(Some((kind, value)))
+}
+
+async fn using_storage<S, G, F, T>(peer: &Peer<S, G>, f: F) -> Result<T, Failure>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+    F: FnOnce(&librad::git::storage::Storage) -> anyhow::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    Ok(peer
+        .using_storage(f)
+        .await
+        .map_err(|e| Failure::Internal(e.into()))??)
+}
+
+async fn list_identities<S, G>(
+    peer: &Peer<S, G>,
+    kind: Kind,
+    after: Option<Urn>,
+    per_page: usize,
+) -> Result<Page<serde_json::Value>, Failure>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    using_storage(peer, move |storage| {
+        let filter = Filter {
+            has_identity: true,
+            ..Filter::default()
+        };
+        // Namespaces of the other kind are skipped, so the index may need to
+        // be read more than once to fill the page
+        let mut items = Vec::new();
+        let mut cursor = after;
+        while items.len() <= per_page {
+            let entries = index::list(storage, &filter, cursor.as_ref(), per_page + 1)?;
+            for entry in entries.entries {
+                let identity = match identities::any::get(storage, &entry.urn)? {
+                    None => continue,
+                    Some(identity) => identity,
+                };
+                if let Some((k, value)) = identity_json(identity)? {
+                    if k == kind {
+                        items.push((entry.urn, value));
+                    }
+                }
+                if items.len() > per_page {
+                    break;
+                }
+            }
+            match entries.next {
+                None => break,
+                next => cursor = next,
+            }
+        }
+        let page = Page::of(items, per_page, |(urn, _)| urn.to_string());
+        Ok(Page {
+            per_page,
+            items: page.items.into_iter().map(|(_, value)| value).collect(),
+            next: page.next,
+        })
+    })
+    .await
+}
+
+async fn get_identity<S, G>(
+    peer: &Peer<S, G>,
+    kind: Kind,
+    urn: Urn,
+) -> Result<serde_json::Value, Failure>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let identity = using_storage(peer, move |storage| {
+        match identities::any::get(storage, &urn)? {
+            None => Ok(None),
+            Some(identity) => identity_json(identity),
+        }
+    })
+    .await?;
+    match identity {
+        Some((k, value)) if k == kind => Ok(value),
+        _ => Err(Failure::NotFound),
+    }
+}
+
+async fn refs<S, G>(peer: &Peer<S, G>, urn: Urn) -> Result<serde_json::Value, Failure>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let refs = using_storage(peer, move |storage| {
+        let snapshot = storage.read_only().snapshot(&urn)?;
+        Ok(snapshot
+            .iter()
+            .map(|(name, oid)| (name.to_string(), json!(oid.to_string())))
+            .collect::<serde_json::Map<_, _>>())
+    })
+    .await?;
+    if refs.is_empty() {
+        Err(Failure::NotFound)
+    } else {
+        Ok(json!({ "refs": refs }))
+    }
+}
+
+fn object_json(
+    object: &librad::collaborative_objects::CollaborativeObject,
+) -> anyhow::Result<serde_json::Value> {
+    Ok(json!({
+        "id": object.id().to_string(),
+        "typename": object.typename().to_string(),
+        "doc": cob::realize(object.history())?,
+    }))
+}
+
+async fn list_objects<S, G>(
+    peer: &Peer<S, G>,
+    urn: Urn,
+    typename: TypeName,
+    after: Option<ObjectId>,
+    per_page: usize,
+) -> Result<Page<serde_json::Value>, Failure>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    using_storage(peer, move |storage| {
+        let cobs = storage.collaborative_objects(None);
+        let ids = cobs
+            .type_references(&urn, &typename)?
+            .keys()
+            .copied()
+            .collect::<BTreeSet<_>>();
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        // Only the objects on the page, and the first one beyond it, are
+        // evaluated
+        let mut items = Vec::new();
+        for id in ids.range((start, Bound::Unbounded)) {
+            if let Some(object) = cobs.retrieve(&urn, &typename, id)? {
+                items.push((*id, object_json(&object)?));
+            }
+            if items.len() > per_page {
+                break;
+            }
+        }
+        let page = Page::of(items, per_page, |(id, _)| id.to_string());
+        Ok(Page {
+            per_page,
+            items: page.items.into_iter().map(|(_, value)| value).collect(),
+            next: page.next,
+        })
+    })
+    .await
+}
+
+async fn get_object<S, G>(
+    peer: &Peer<S, G>,
+    urn: Urn,
+    typename: TypeName,
+    id: ObjectId,
+) -> Result<serde_json::Value, Failure>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    using_storage(peer, move |storage| {
+        storage
+            .collaborative_objects(None)
+            .retrieve(&urn, &typename, &id)?
+            .as_ref()
+            .map(object_json)
+            .transpose()
+    })
+    .await?
+    .ok_or(Failure::NotFound)
+}
+
+/// Respond with `value`, or `304 Not Modified` if the client already has it.
+fn respond(req: &Request<Body>, value: &serde_json::Value) -> Response<Body> {
+    let body = value.to_string();
+    // The SHA-1 of the body as a git blob, which is stable across releases
+    let etag = match git2::Oid::hash_object(git2::ObjectType::Blob, body.as_bytes()) {
+        Ok(oid) => format!("\"{}\"", oid),
+        Err(_) => format!("\"{}\"", body.len()),
+    };
+    let fresh = req
+        .headers()
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        });
+
+    let builder = Response::builder().header(header::ETAG, &etag);
+    if fresh {
+        builder.status(StatusCode::NOT_MODIFIED).body(Body::empty())
+    } else {
+        builder
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+    }
+    .expect("response is well-formed")
+}
+
+fn failure(status: StatusCode, reason: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "error": reason }).to_string()))
+        .expect("response is well-formed")
+}
@@ -3,15 +3,32 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+pub mod anti_entropy;
 pub mod args;
+#[cfg(feature = "autotrack")]
+pub mod autotrack;
 
 mod cfg;
+#[cfg(any(feature = "autotrack", feature = "http", feature = "notify"))]
+mod cob;
 
 pub mod api;
+pub mod doctor;
+#[cfg(feature = "http")]
+pub mod http;
 mod logging;
 mod metrics;
+#[cfg(feature = "mirror")]
+pub mod mirror;
 pub mod node;
+#[cfg(feature = "notify")]
+pub mod notify;
 mod protocol;
+pub mod rate_limit;
+pub mod replication;
 pub mod request_pull;
+pub mod rotation;
 mod signals;
+pub mod standby;
 pub mod tracking;
+mod watch;
@@ -5,9 +5,10 @@
 
 use std::env;
 
-use log::{log_enabled, Level};
-use tracing::subscriber::set_global_default as set_subscriber;
-use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use link_tracing::{sampling::Rule, Format, Guard};
+use tracing_subscriber::{filter::LevelFilter, EnvFilter, Layer, Registry};
+
+use crate::args::TracingArgs;
 
 /// Initialise logging / tracing
 ///
@@ -18,32 +19,49 @@ use tracing_subscriber::{EnvFilter, FmtSubscriber};
 /// * "compact": [`tracing_subscriber::fmt::format::Compact`]
 /// * "json": [`tracing_subscriber::fmt::format::Json`]
 ///
-/// If the variable is not set, or set to any other value, the
-/// [`tracing_subscriber::fmt::format::Full`] format is used.
-pub fn init() {
-    if env_logger::builder().try_init().is_ok() {
-        let mut builder = FmtSubscriber::builder()
-            .with_env_filter(
-                EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("debug")),
-            )
-            .with_test_writer();
-        if log_enabled!(target: "librad", Level::Trace) {
-            builder = builder.with_thread_ids(true);
-        } else if env::var("TRACING_FMT").is_err() {
-            let default_format = if env::var("CI").is_ok() {
-                "compact"
-            } else {
-                "pretty"
-            };
-            env::set_var("TRACING_FMT", default_format);
-        }
+/// If the variable is not set, the "compact" format is used on CI, and
+/// "pretty" otherwise, unless `RUST_LOG` enables the trace level, in which case
+/// the [`tracing_subscriber::fmt::format::Full`] format is used.
+///
+/// Records of the `log` crate are forwarded to the same subscriber.
+///
+/// The returned [`Guard`] must be held until the node shuts down, so pending
+/// spans are exported. If logging was already initialised, `None` is returned.
+pub fn init(args: &TracingArgs) -> anyhow::Result<Option<Guard>> {
+    let thread_ids = EnvFilter::try_from_default_env()
+        .ok()
+        .and_then(|filter| <EnvFilter as Layer<Registry>>::max_level_hint(&filter))
+        == Some(LevelFilter::TRACE);
+    let format = match Format::from_env() {
+        Some(format) => format,
+        None if thread_ids => Format::Full,
+        None if env::var("CI").is_ok() => Format::Compact,
+        None => Format::Pretty,
+    };
+    let config = link_tracing::Config {
+        format,
+        filter: env::var("RUST_LOG").is_err().then(|| "debug".to_owned()),
+        thread_ids,
+        sampling: Rule::gossip(args.sample_gossip),
+        #[cfg(feature = "otlp")]
+        otlp: args
+            .otlp_endpoint
+            .clone()
+            .map(|endpoint| link_tracing::otlp::Config {
+                endpoint,
+                service_name: args.otlp_service_name.clone(),
+            }),
+    };
+    let guard = match link_tracing::init(config) {
+        Ok(guard) => guard,
+        Err(link_tracing::Error::AlreadyInitialised(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
 
-        match env::var("TRACING_FMT").ok().as_deref() {
-            Some("pretty") => set_subscriber(builder.pretty().finish()),
-            Some("compact") => set_subscriber(builder.compact().finish()),
-            Some("json") => set_subscriber(builder.json().flatten_event(true).finish()),
-            _ => set_subscriber(builder.finish()),
-        }
-        .expect("setting tracing subscriber failed")
+    #[cfg(not(feature = "otlp"))]
+    if args.otlp_endpoint.is_some() {
+        tracing::warn!("linkd was built without the `otlp` feature, not exporting spans");
     }
+
+    Ok(Some(guard))
 }
@@ -20,6 +20,19 @@ const CONNECTIONS_TOTAL: &str = "connections_total";
 const CONNECTED_PEERS: &str = "connected_peers";
 const MEMBERSHIP_ACTIVE: &str = "membership_active";
 const MEMBERSHIP_PASSIVE: &str = "membership_passive";
+const QUIC_RTT_MS: &str = "quic_rtt_ms";
+const QUIC_CWND: &str = "quic_cwnd";
+const QUIC_CONGESTION_EVENTS: &str = "quic_congestion_events";
+const QUIC_SENT_PACKETS: &str = "quic_sent_packets";
+const QUIC_LOST_PACKETS: &str = "quic_lost_packets";
+const GOSSIP_SENT_PLAIN_BYTES: &str = "gossip_compression_sent_plain_bytes";
+const GOSSIP_SENT_COMPRESSED_BYTES: &str = "gossip_compression_sent_compressed_bytes";
+const GOSSIP_RECV_COMPRESSED_BYTES: &str = "gossip_compression_recv_compressed_bytes";
+const GOSSIP_RECV_PLAIN_BYTES: &str = "gossip_compression_recv_plain_bytes";
+const EXECUTOR_SPAWNED: &str = "executor_spawned";
+const EXECUTOR_BLOCKING: &str = "executor_blocking";
+const EXECUTOR_BLOCKING_QUEUED: &str = "executor_blocking_queued";
+const EXECUTOR_BLOCKING_SATURATED: &str = "executor_blocking_saturated";
 
 #[instrument(name = "graphite subroutine", skip(peer))]
 pub async fn routine<S, G>(peer: Peer<S, G>, graphite_addr: SocketAddr) -> anyhow::Result<()>
@@ -41,15 +54,46 @@ where
         let stats = time::timeout(Duration::from_secs(5), peer.stats()).await?;
         let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
 
+        let compression = stats.compression;
+        let executor = peer.executor_stats();
         for (metric, value) in &[
             (CONNECTED_PEERS, stats.connected_peers.len()),
             (CONNECTIONS_TOTAL, stats.connections_total),
             (MEMBERSHIP_ACTIVE, stats.membership_active),
             (MEMBERSHIP_PASSIVE, stats.membership_passive),
+            (GOSSIP_SENT_PLAIN_BYTES, compression.sent_plain as usize),
+            (
+                GOSSIP_SENT_COMPRESSED_BYTES,
+                compression.sent_compressed as usize,
+            ),
+            (
+                GOSSIP_RECV_COMPRESSED_BYTES,
+                compression.recv_compressed as usize,
+            ),
+            (GOSSIP_RECV_PLAIN_BYTES, compression.recv_plain as usize),
+            (EXECUTOR_SPAWNED, executor.spawned),
+            (EXECUTOR_BLOCKING, executor.blocking),
+            (EXECUTOR_BLOCKING_QUEUED, executor.blocking_queued),
+            (EXECUTOR_BLOCKING_SATURATED, executor.saturated as usize),
         ] {
             sock.send(line(peer_id.clone(), metric, *value as f32, now).as_bytes())
                 .await?;
         }
+
+        let conns = time::timeout(Duration::from_secs(5), peer.connection_stats()).await?;
+        for (remote, stats) in conns {
+            let tags = format!("{};remote={}", peer_id, remote);
+            for (metric, value) in &[
+                (QUIC_RTT_MS, stats.rtt.as_secs_f32() * 1000.0),
+                (QUIC_CWND, stats.cwnd as f32),
+                (QUIC_CONGESTION_EVENTS, stats.congestion_events as f32),
+                (QUIC_SENT_PACKETS, stats.sent_packets as f32),
+                (QUIC_LOST_PACKETS, stats.lost_packets as f32),
+            ] {
+                sock.send(line(tags.clone(), metric, *value, now).as_bytes())
+                    .await?;
+            }
+        }
     }
 }
 
@@ -0,0 +1,531 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Mirroring of projects to conventional git remotes.
+//!
+//! For each configured [`Mirror`], the branches of a peer's view of the
+//! project are pushed to a git remote (eg. on GitHub or GitLab) whenever the
+//! project is updated via gossip, and periodically. Optionally, branches
+//! updated on the remote are pulled back into the local peer's view of the
+//! project, and the local peer's `rad/signed_refs` are updated accordingly.
+//!
+//! Pushing and fetching is delegated to the `git` executable, so the
+//! transports and credential helpers configured for the user running the
+//! node apply. Fetched branches are written to the project via
+//! [`Storage::transaction`], see [`pull`].
+
+use std::{
+    convert::TryFrom as _,
+    fmt,
+    io,
+    path::{Path, PathBuf},
+    process::{self, Output},
+    str::FromStr,
+    time::Duration,
+};
+
+use futures::StreamExt as _;
+use thiserror::Error;
+use tokio::process::Command;
+use tracing::{debug, error, info, instrument, warn};
+
+use librad::{
+    git::{
+        refs::Refs,
+        storage::{self, txn::Previous, ReadOnlyStorage as _, Storage},
+        types::Namespace,
+        Urn,
+    },
+    net::{
+        peer::{event::upstream::Gossip, Peer, ProtocolEvent},
+        protocol::{broadcast::PutResult, gossip::Payload, RequestPullGuard},
+    },
+    PeerId,
+    Signer,
+};
+use radicle_git_ext::RefLike;
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Config {
+        #[error("line {line}: expected `<urn> <url> [<option>...]`")]
+        Malformed { line: usize },
+
+        #[error("line {line}: {reason}")]
+        Option { line: usize, reason: String },
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("`git {args}` failed: {stderr}")]
+    Git { args: String, stderr: String },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Refs(#[from] librad::git::refs::stored::Error),
+
+    #[error(transparent)]
+    Storage(#[from] storage::Error),
+
+    #[error(transparent)]
+    Transaction(#[from] storage::txn::error::Commit),
+
+    #[error(transparent)]
+    RefName(#[from] radicle_git_ext::name::Error),
+
+    #[error("protocol is not running")]
+    Stopped,
+}
+
+/// How to handle branches which have diverged between the project and the
+/// remote.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// Overwrite the remote branch.
+    Force,
+    /// Leave the remote branch as it is, and log a warning.
+    FastForward,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self::FastForward
+    }
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "force" => Ok(Self::Force),
+            "ff-only" => Ok(Self::FastForward),
+            _ => Err(format!("unknown policy `{}`", s)),
+        }
+    }
+}
+
+/// Mapping of a branch of the project to a branch on the remote.
+///
+/// Both sides may contain a single `*` to map many branches at once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Branch {
+    pub src: String,
+    pub dst: String,
+}
+
+impl Default for Branch {
+    fn default() -> Self {
+        Self {
+            src: "*".to_owned(),
+            dst: "*".to_owned(),
+        }
+    }
+}
+
+impl FromStr for Branch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((src, dst)) if !src.is_empty() && !dst.is_empty() => Ok(Self {
+                src: src.to_owned(),
+                dst: dst.to_owned(),
+            }),
+            None if !s.is_empty() => Ok(Self {
+                src: s.to_owned(),
+                dst: s.to_owned(),
+            }),
+            _ => Err(format!("malformed branch mapping `{}`", s)),
+        }
+    }
+}
+
+/// A project to mirror.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mirror {
+    pub urn: Urn,
+    /// The git remote URL, in any form `git` understands.
+    pub url: String,
+    /// The peer whose branches are pushed. If `None`, the branches of the
+    /// local peer are pushed.
+    pub peer: Option<PeerId>,
+    /// If empty, all branches are mirrored under their own name.
+    pub branches: Vec<Branch>,
+    pub policy: Policy,
+    /// Fetch branches updated on the remote into the local peer's view of the
+    /// project. Only fast-forwards are fetched, and only if `peer` is `None`.
+    pub pull: bool,
+}
+
+impl Mirror {
+    fn branches(&self) -> Vec<Branch> {
+        if self.branches.is_empty() {
+            vec![Branch::default()]
+        } else {
+            self.branches.clone()
+        }
+    }
+
+    fn heads(&self) -> String {
+        let ns = Namespace::from(&self.urn);
+        match self.peer {
+            None => format!("refs/namespaces/{}/refs/heads/", ns),
+            Some(peer) => format!("refs/namespaces/{}/refs/remotes/{}/heads/", ns, peer),
+        }
+    }
+
+    fn push_specs(&self) -> Vec<String> {
+        let force = if self.policy == Policy::Force {
+            "+"
+        } else {
+            ""
+        };
+        let heads = self.heads();
+        self.branches()
+            .iter()
+            .map(|b| format!("{}{}{}:refs/heads/{}", force, heads, b.src, b.dst))
+            .collect()
+    }
+
+    fn fetch_specs(&self, scratch: &str) -> Vec<String> {
+        self.branches()
+            .iter()
+            .map(|b| format!("+refs/heads/{}:{}{}", b.dst, scratch, b.src))
+            .collect()
+    }
+}
+
+impl fmt::Display for Mirror {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.urn, self.url)
+    }
+}
+
+/// The configured mirrors.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    pub mirrors: Vec<Mirror>,
+    /// Interval at which all mirrors are synced, regardless of updates.
+    pub interval: Duration,
+}
+
+impl Config {
+    /// Read the mirrors from the file at `path`, see [`Config::parse`].
+    pub fn load(path: &Path, interval: Duration) -> Result<Self, error::Config> {
+        Self::parse(&std::fs::read_to_string(path)?, interval)
+    }
+
+    /// Parse mirrors, one `<urn> <url> [<option>...]` per line.
+    ///
+    /// Options are:
+    ///
+    /// * `peer=<peer id>`: push the branches of the given peer, instead of the
+    ///   local peer's
+    /// * `branch=<src>[:<dst>]`: mirror the branch `src` as `dst`, may be
+    ///   repeated
+    /// * `policy=force|ff-only`: see [`Policy`]
+    /// * `pull`: see [`Mirror::pull`]
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn parse(s: &str, interval: Duration) -> Result<Self, error::Config> {
+        let mut mirrors = Vec::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let (urn, url) = words
+                .next()
+                .zip(words.next())
+                .ok_or(error::Config::Malformed { line: i + 1 })?;
+            let option = |reason: String| error::Config::Option {
+                line: i + 1,
+                reason,
+            };
+            let mut mirror = Mirror {
+                urn: urn.parse().map_err(|e| option(format!("{}", e)))?,
+                url: url.to_owned(),
+                peer: None,
+                branches: Vec::new(),
+                policy: Policy::default(),
+                pull: false,
+            };
+            for word in words {
+                match word.split_once('=') {
+                    Some(("peer", peer)) => {
+                        mirror.peer = Some(peer.parse().map_err(|e| option(format!("{}", e)))?)
+                    },
+                    Some(("branch", branch)) => {
+                        mirror.branches.push(branch.parse().map_err(option)?)
+                    },
+                    Some(("policy", policy)) => mirror.policy = policy.parse().map_err(option)?,
+                    None if word == "pull" => mirror.pull = true,
+                    _ => return Err(option(format!("unknown option `{}`", word))),
+                }
+            }
+            if mirror.pull && mirror.peer.is_some() {
+                return Err(option(
+                    "`pull` requires mirroring the local peer".to_owned(),
+                ));
+            }
+            mirrors.push(mirror);
+        }
+
+        Ok(Self { mirrors, interval })
+    }
+}
+
+#[instrument(name = "mirror subroutine", skip(peer, config))]
+pub async fn routine<S, G>(peer: Peer<S, G>, git_dir: PathBuf, config: Config) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    info!(mirrors = config.mirrors.len(), "mirroring projects");
+
+    for mirror in &config.mirrors {
+        sync(&peer, &git_dir, mirror).await;
+    }
+
+    let events = peer.subscribe();
+    futures::pin_mut!(events);
+    let mut interval = tokio::time::interval(config.interval);
+    interval.tick().await;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                for mirror in &config.mirrors {
+                    sync(&peer, &git_dir, mirror).await;
+                }
+            },
+            event = events.next() => match event {
+                None => break,
+                Some(Ok(ProtocolEvent::Gossip(gossip))) => {
+                    let Gossip::Put { payload: Payload { urn, .. }, result, .. } = *gossip;
+                    if !matches!(result, PutResult::Applied(_)) {
+                        continue;
+                    }
+                    for mirror in config.mirrors.iter().filter(|m| m.urn == urn) {
+                        sync(&peer, &git_dir, mirror).await;
+                    }
+                },
+                Some(Ok(_)) => {},
+                Some(Err(e)) => warn!(err = %e, "event error"),
+            },
+        }
+    }
+
+    Ok(())
+}
+
+async fn sync<S, G>(peer: &Peer<S, G>, git_dir: &Path, mirror: &Mirror)
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    if mirror.pull {
+        let pulled = peer
+            .using_storage({
+                let mirror = mirror.clone();
+                move |storage| pull(storage, &mirror)
+            })
+            .await
+            .map_err(|_| Error::Stopped)
+            .and_then(|res| res);
+        match pulled {
+            Ok(branches) => debug!(%mirror, ?branches, "pulled"),
+            Err(e) => warn!(%mirror, err = %e, "failed to pull from mirror"),
+        }
+    }
+    match push(git_dir, mirror).await {
+        Ok(diverged) if diverged.is_empty() => debug!(%mirror, "pushed"),
+        Ok(diverged) => warn!(
+            %mirror,
+            ?diverged,
+            "some branches have diverged and were not pushed"
+        ),
+        Err(e) => error!(%mirror, err = %e, "failed to push to mirror"),
+    }
+}
+
+/// Push the branches of `mirror` from the monorepo at `git_dir`.
+///
+/// Returns the remote branches which were not updated, because they have
+/// diverged and the policy is [`Policy::FastForward`].
+pub async fn push(git_dir: &Path, mirror: &Mirror) -> Result<Vec<String>, Error> {
+    let mut args = vec![
+        "push".to_owned(),
+        "--porcelain".to_owned(),
+        mirror.url.clone(),
+    ];
+    args.extend(mirror.push_specs());
+    let out = Command::new("git")
+        .arg("--git-dir")
+        .arg(git_dir)
+        .args(&args)
+        .kill_on_drop(true)
+        .output()
+        .await?;
+    // Ref updates are reported on stdout, while stderr only carries progress
+    // and hints
+    let rejected = rejected(&String::from_utf8_lossy(&out.stdout));
+    let diverged = rejected
+        .iter()
+        .filter(|(_, summary)| summary.starts_with("[rejected]"))
+        .map(|(dst, _)| dst.clone())
+        .collect::<Vec<_>>();
+    if out.status.success() || (!rejected.is_empty() && diverged.len() == rejected.len()) {
+        Ok(diverged)
+    } else {
+        Err(failed(&args, &out))
+    }
+}
+
+/// The refs `git push --porcelain` reports as rejected, along with the
+/// summary of the rejection.
+///
+/// Each ref is reported on a line `<flag>\t<src>:<dst>\t<summary>`, where the
+/// flag of rejected refs is `!`.
+fn rejected(porcelain: &str) -> Vec<(String, String)> {
+    porcelain
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some("!"), Some(refs), Some(summary)) => {
+                    let dst = refs.rsplit(':').next().unwrap_or(refs);
+                    Some((dst.to_owned(), summary.to_owned()))
+                },
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Pull the branches of `mirror` which were fast-forwarded on the remote into
+/// the local peer's view of the project, and update its `rad/signed_refs`.
+///
+/// The remote branches are fetched into scratch refs outside of any
+/// namespace first. The local branches are then updated in a single
+/// [`librad::git::storage::Transaction`], so that the namespace lock, leases
+/// and audit log apply, and a concurrent update of a branch is not
+/// overwritten.
+///
+/// Returns the branches which were updated. This is blocking, and must be
+/// run on the storage's thread pool.
+pub fn pull(storage: &Storage, mirror: &Mirror) -> Result<Vec<String>, Error> {
+    let git_dir = storage.path();
+    let scratch = format!("refs/mirrors/{}/", Namespace::from(&mirror.urn));
+    let mut args = vec![
+        "fetch".to_owned(),
+        "--no-tags".to_owned(),
+        mirror.url.clone(),
+    ];
+    args.extend(mirror.fetch_specs(&scratch));
+    git_blocking(git_dir, &args)?;
+
+    let fetched = git_blocking(
+        git_dir,
+        &[
+            "for-each-ref".to_owned(),
+            "--format=%(objectname) %(refname)".to_owned(),
+            scratch.clone(),
+        ],
+    )?;
+    let fetched = String::from_utf8_lossy(&fetched.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (oid, name) = line.split_once(' ')?;
+            let branch = name.strip_prefix(&scratch)?.to_owned();
+            Some((branch, git2::Oid::from_str(oid).ok()?, name.to_owned()))
+        })
+        .collect::<Vec<_>>();
+
+    let updated = update_branches(storage, mirror, &fetched);
+    for (_, _, scratch_ref) in &fetched {
+        let args = [
+            "update-ref".to_owned(),
+            "-d".to_owned(),
+            scratch_ref.clone(),
+        ];
+        if let Err(e) = git_blocking(git_dir, &args) {
+            warn!(%mirror, err = %e, "failed to delete scratch ref");
+        }
+    }
+    let updated = updated?;
+    if !updated.is_empty() {
+        Refs::update(storage, &mirror.urn)?;
+    }
+    Ok(updated)
+}
+
+fn update_branches(
+    storage: &Storage,
+    mirror: &Mirror,
+    fetched: &[(String, git2::Oid, String)],
+) -> Result<Vec<String>, Error> {
+    let namespace = Namespace::from(&mirror.urn);
+    let mut txn = storage.transaction(&mirror.urn, format!("pull from mirror {}", mirror.url));
+    let mut updated = Vec::new();
+    for (branch, new, _) in fetched {
+        let local = RefLike::try_from(format!("refs/heads/{}", branch))?;
+        let qualified = RefLike::try_from(format!("refs/namespaces/{}/{}", namespace, local))?;
+        let previous = match storage.reference(&qualified)?.and_then(|r| r.target()) {
+            None => Previous::MustNotExist,
+            Some(old) if old == *new => continue,
+            Some(old) => {
+                let ff = process::Command::new("git")
+                    .arg("--git-dir")
+                    .arg(storage.path())
+                    .args(&["merge-base", "--is-ancestor"])
+                    .arg(old.to_string())
+                    .arg(new.to_string())
+                    .status()?;
+                if !ff.success() {
+                    warn!(%mirror, %branch, "branch has diverged and was not pulled");
+                    continue;
+                }
+                Previous::MustBe(old.into())
+            },
+        };
+        txn = txn.write(local, *new, previous);
+        updated.push(branch.clone());
+    }
+    txn.commit()?;
+    Ok(updated)
+}
+
+fn git_blocking(git_dir: &Path, args: &[String]) -> Result<Output, Error> {
+    let out = process::Command::new("git")
+        .arg("--git-dir")
+        .arg(git_dir)
+        .args(args)
+        .output()?;
+    if out.status.success() {
+        Ok(out)
+    } else {
+        Err(failed(args, &out))
+    }
+}
+
+fn failed(args: &[String], out: &Output) -> Error {
+    Error::Git {
+        args: args.join(" "),
+        stderr: String::from_utf8_lossy(&out.stderr).into_owned(),
+    }
+}
@@ -3,10 +3,19 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{panic, sync::Arc, time::Duration};
+use std::{
+    panic,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use clap::Parser as _;
-use futures::{future::FutureExt as _, stream::FuturesUnordered, StreamExt};
+use futures::{
+    future::{self, FutureExt as _, TryFutureExt as _},
+    stream::FuturesUnordered,
+    StreamExt,
+};
+use link_async::supervisor::Restart;
 use tokio::sync::mpsc;
 use tracing::info;
 
@@ -16,48 +25,181 @@ use librad::{
 };
 
 use crate::{
+    anti_entropy,
     api,
     args::Args,
     cfg::{self, Cfg, RunMode},
+    doctor,
     logging,
     metrics::graphite,
     protocol,
+    replication,
     request_pull,
+    rotation,
     signals,
+    standby,
     tracking,
+    watch,
 };
 
+#[cfg(feature = "autotrack")]
+use crate::autotrack;
+#[cfg(feature = "http")]
+use crate::http;
+#[cfg(feature = "mirror")]
+use crate::mirror;
+#[cfg(feature = "notify")]
+use crate::notify;
+
 /// The amount of time to wait for connections before making any announcements
 static ANNOUNCE_WAIT_TIME: Duration = Duration::from_secs(5);
 
+/// The amount of time to wait for storage operations to complete on shutdown
+static STORAGE_DRAIN_TIME: Duration = Duration::from_secs(10);
+
+/// Create a runtime according to the executor settings given on the command
+/// line, and [`run`] the node on it.
+pub fn start() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let rt = cfg::executor(&args.executor).runtime()?;
+    rt.block_on(run_with(args))
+}
+
+/// Run the node on the ambient runtime.
 pub async fn run() -> anyhow::Result<()> {
-    logging::init();
+    run_with(Args::parse()).await
+}
 
-    let spawner = Arc::new(link_async::Spawner::from_current().unwrap());
+async fn run_with(args: Args) -> anyhow::Result<()> {
+    let _tracing = logging::init(&args.tracing)?;
+
+    if args.doctor {
+        let report = doctor::run(&args).await;
+        print!("{}", report);
+        return match report.status() {
+            doctor::Status::Fail => Err(anyhow::anyhow!("some checks failed")),
+            doctor::Status::Ok | doctor::Status::Warn => Ok(()),
+        };
+    }
+
+    let spawner = Arc::new(link_async::Spawner::with_config(
+        tokio::runtime::Handle::current(),
+        cfg::executor(&args.executor),
+    ));
 
-    let args = Args::parse();
     let cfg: Cfg<discovery::Static, BoxedSigner, request_pull::State> = cfg(&args).await?;
 
     let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
     let mut signals_task = spawner.spawn(signals::routine(shutdown_tx)).fuse();
 
     let mut coalesced = FuturesUnordered::new();
-    let peer = Peer::new(cfg.peer)?;
+    let peer = Peer::with_spawner(cfg.peer, spawner.clone())?;
     let peer_task = spawner
         .spawn(protocol::routine(peer.clone(), cfg.disco, shutdown_rx))
         .fuse();
     coalesced.push(peer_task);
 
+    let pool = replication::Pool::new(cfg.replication_workers, cfg.replication_workers_per_urn);
+    let role = match cfg.standby {
+        Some(_) => standby::Role::standby(),
+        None => standby::Role::primary(),
+    };
+
+    // Subsystems which can be restarted independently of the protocol
+    let mut subsystems = spawner.supervisor();
+
     if let Some(cfg::Metrics::Graphite(addr)) = cfg.metrics {
-        let graphite_task = spawner.spawn(graphite::routine(peer.clone(), addr)).fuse();
-        coalesced.push(graphite_task);
+        let peer = peer.clone();
+        subsystems = subsystems.child("graphite", Restart::Permanent, move || {
+            graphite::routine(peer.clone(), addr)
+        });
     }
 
     if let Some(tracker) = cfg.tracker {
-        let tracking_task = spawner
-            .spawn(tracking::routine(peer.clone(), tracker))
+        let spawner = spawner.clone();
+        let peer = peer.clone();
+        let pool = pool.clone();
+        subsystems = subsystems.child("tracking", Restart::Permanent, move || {
+            tracking::routine(spawner.clone(), peer.clone(), tracker.clone(), pool.clone())
+        });
+    }
+
+    #[cfg(feature = "autotrack")]
+    if let Some(config) = cfg.autotrack {
+        let peer = peer.clone();
+        subsystems = subsystems.child("autotrack", Restart::Permanent, move || {
+            autotrack::routine(peer.clone(), config.clone())
+        });
+    }
+
+    if let Some(debounce) = cfg.announce_debounce {
+        let peer = peer.clone();
+        subsystems = subsystems.child("watch", Restart::Permanent, move || {
+            watch::routine(peer.clone(), debounce)
+        });
+    }
+
+    if let Some(config) = cfg.anti_entropy {
+        let peer = peer.clone();
+        let pool = pool.clone();
+        subsystems = subsystems.child("anti-entropy", Restart::Permanent, move || {
+            anti_entropy::routine(peer.clone(), pool.clone(), config)
+        });
+    }
+
+    if let Some(config) = cfg.rotations {
+        let peer = peer.clone();
+        subsystems = subsystems.child("rotation", Restart::Permanent, move || {
+            rotation::routine(peer.clone(), config.clone())
+        });
+    }
+
+    if let Some(config) = cfg.standby {
+        let peer = peer.clone();
+        let pool = pool.clone();
+        let role = role.clone();
+        subsystems = subsystems.child("standby", Restart::Transient, move || {
+            standby::routine(peer.clone(), pool.clone(), role.clone(), config.clone())
+        });
+    }
+
+    #[cfg(feature = "mirror")]
+    if let Some(mirrors) = cfg.mirror {
+        let peer = peer.clone();
+        let git_dir = cfg.profile.paths().git_dir().to_path_buf();
+        subsystems = subsystems.child("mirror", Restart::Transient, move || {
+            mirror::routine(peer.clone(), git_dir.clone(), mirrors.clone())
+        });
+    }
+
+    #[cfg(feature = "notify")]
+    if let Some(config) = cfg.notify {
+        let peer = peer.clone();
+        subsystems = subsystems.child("notify", Restart::Permanent, move || {
+            notify::routine(peer.clone(), config.clone())
+        });
+    }
+
+    #[cfg(feature = "http")]
+    if let Some(config) = cfg.http {
+        let peer = peer.clone();
+        subsystems = subsystems.child("http", Restart::Permanent, move || {
+            http::routine(peer.clone(), config.clone())
+        });
+    }
+
+    if !subsystems.is_empty() {
+        // Subsystems which completed, eg. the standby routine after promotion,
+        // don't stop the node. Only a failure escalated by the supervisor does.
+        let subsystems_task = spawner
+            .spawn(
+                subsystems
+                    .run()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|()| future::pending()),
+            )
             .fuse();
-        coalesced.push(tracking_task);
+        coalesced.push(subsystems_task);
     }
 
     let timeout = match cfg.run_mode {
@@ -65,10 +207,17 @@ pub async fn run() -> anyhow::Result<()> {
         RunMode::Immortal => None,
     };
     let sockets = api::Sockets::load(spawner.clone(), &cfg.profile, peer.peer_id()).await?;
+    let remote = match cfg.remote_control {
+        Some(remote) => Some(api::Remote::bind(remote).await?),
+        None => None,
+    };
     let api_routine = api::routine(
         spawner.clone(),
         peer.clone(),
+        pool,
+        role,
         &sockets,
+        remote.as_ref(),
         timeout,
         ANNOUNCE_WAIT_TIME,
     )
@@ -92,6 +241,14 @@ pub async fn run() -> anyhow::Result<()> {
         }
     }
 
+    info!("waiting for storage operations to complete");
+    if let Err(e) = peer
+        .close_storage(Instant::now() + STORAGE_DRAIN_TIME)
+        .await
+    {
+        tracing::warn!(err = %e, "shutting down with storage operations in flight");
+    }
+
     if let Err(e) = sockets.cleanup() {
         tracing::error!(err=?e, "error cleaning up sockets");
     }
@@ -99,16 +256,8 @@ pub async fn run() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[cfg(unix)]
 async fn cfg(
     args: &Args,
 ) -> anyhow::Result<Cfg<discovery::Static, BoxedSigner, request_pull::State>> {
     Ok(Cfg::from_args(args).await?)
 }
-
-#[cfg(windows)]
-async fn cfg(
-    args: &Args,
-) -> anyhow::Result<Cfg<discovery::Static, BoxedSigner, request_pull::State>> {
-    unimplemented!("Windows is not supported, contributions are welcome :)")
-}
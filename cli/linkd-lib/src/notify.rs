@@ -0,0 +1,634 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Email notifications for collaborative objects.
+//!
+//! Maintainers may want to follow what happens in the issue tracker and the
+//! patches of a project without running a UI. For each configured project,
+//! the collaborative objects of the watched types (by default issues and
+//! patches) are compared to what was seen before whenever the project is
+//! replicated or updated via gossip, and every change is turned into an email
+//! sent via SMTP:
+//!
+//! * [`Kind::Opened`] for objects not seen before,
+//! * [`Kind::Mention`] when one of the configured handles is newly mentioned
+//!   anywhere in the object, as `@handle`,
+//! * [`Kind::ReviewRequest`] when one of the configured handles, or the local
+//!   peer id, is newly listed in the `reviewers` of the object,
+//! * [`Kind::Updated`] for any other change.
+//!
+//! The objects present when the node starts are recorded, but not notified
+//! about. Emails are rendered from [`Templates`], and at most
+//! [`Config::max_per_hour`] are sent: the ones exceeding the limit are
+//! dropped, and a warning is logged.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io,
+    path::Path,
+    str::FromStr as _,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::StreamExt as _;
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncRead, AsyncWrite, AsyncWriteExt as _, BufStream},
+    net::TcpStream,
+};
+use tokio_rustls::{webpki::DNSNameRef, TlsConnector};
+use tracing::{debug, info, instrument, warn};
+
+use librad::{
+    collaborative_objects::{ObjectId, TypeName},
+    git::Urn,
+    net::{
+        peer::{event::upstream::Gossip, Peer, ProtocolEvent},
+        protocol::{broadcast::PutResult, gossip::Payload, RequestPullGuard},
+    },
+    Signer,
+};
+
+use crate::cob;
+pub use crate::rate_limit::RateLimit;
+
+/// The types of collaborative objects watched if none are configured.
+pub const DEFAULT_TYPENAMES: [&str; 2] = [cob::ISSUE, cob::PATCH];
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+pub mod error {
+    use std::{io, path::PathBuf};
+
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Template {
+        #[error("template `{0}` must start with a `Subject:` line")]
+        Subject(PathBuf),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Smtp {
+        #[error("invalid SMTP server name `{0}`")]
+        ServerName(String),
+
+        #[error("unexpected SMTP reply to `{command}`: {reply}")]
+        Reply { command: String, reply: String },
+
+        #[error("SMTP connection closed unexpectedly")]
+        Eof,
+
+        #[error("refusing to send SMTP credentials without TLS")]
+        CleartextCredentials,
+
+        #[error("SMTP command contains a line break")]
+        LineBreak,
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+/// The SMTP server to submit emails to.
+#[derive(Clone)]
+pub struct Smtp {
+    /// The address of the server, as `<host>:<port>`.
+    pub addr: String,
+    /// The name the certificate of the server must be valid for, if `tls` is
+    /// set.
+    pub server_name: String,
+    /// Connect using implicit TLS (ie. SMTPS, usually on port 465), rather
+    /// than in the clear.
+    pub tls: Option<Arc<rustls::ClientConfig>>,
+    /// User name and password to authenticate with, using `AUTH PLAIN`.
+    /// Requires `tls`, so the password is not sent in the clear.
+    pub credentials: Option<(String, String)>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// What happened to a collaborative object.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Opened,
+    Updated,
+    /// The given handle was mentioned.
+    Mention(String),
+    /// The given handle was asked for review.
+    ReviewRequest(String),
+}
+
+/// A subject line and a body, with placeholders of the form `{name}`.
+///
+/// The placeholders are `{urn}`, `{typename}`, `{id}`, `{title}` and
+/// `{handle}`. The latter is empty unless the notification is a
+/// [`Kind::Mention`] or [`Kind::ReviewRequest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    pub subject: String,
+    pub body: String,
+}
+
+impl Template {
+    /// Parse a template, whose first line is `Subject: <subject>`, followed
+    /// by the body.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (subject, body) = s.split_once('\n').unwrap_or((s, ""));
+        let subject = subject.trim_end().strip_prefix("Subject:")?.trim();
+        Some(Self {
+            subject: subject.to_owned(),
+            body: body.to_owned(),
+        })
+    }
+
+    fn load(path: &Path) -> Result<Self, error::Template> {
+        Self::parse(&fs::read_to_string(path)?)
+            .ok_or_else(|| error::Template::Subject(path.to_path_buf()))
+    }
+
+    /// Render the subject and body of the notification `n`.
+    pub fn render(&self, n: &Notification) -> (String, String) {
+        let vars = [
+            ("{urn}", n.urn.to_string()),
+            ("{typename}", n.typename.to_string()),
+            ("{id}", n.id.to_string()),
+            ("{title}", n.title.clone()),
+            (
+                "{handle}",
+                match &n.kind {
+                    Kind::Mention(handle) | Kind::ReviewRequest(handle) => handle.clone(),
+                    Kind::Opened | Kind::Updated => String::new(),
+                },
+            ),
+        ];
+        let render = |s: &str| {
+            vars.iter()
+                .fold(s.to_owned(), |s, (var, value)| s.replace(var, value))
+        };
+        (render(&self.subject), render(&self.body))
+    }
+}
+
+/// The [`Template`] for each [`Kind`] of notification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Templates {
+    pub opened: Template,
+    pub updated: Template,
+    pub mention: Template,
+    pub review: Template,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        let template = |subject: &str, body: &str| Template {
+            subject: subject.to_owned(),
+            body: body.to_owned(),
+        };
+        Self {
+            opened: template(
+                "[{typename}] {title}",
+                "A new {typename} was opened in {urn}:\n\n  {title}\n\nId: {id}\n",
+            ),
+            updated: template(
+                "Re: [{typename}] {title}",
+                "The {typename} {id} in {urn} was updated:\n\n  {title}\n",
+            ),
+            mention: template(
+                "Re: [{typename}] {title}",
+                "{handle} was mentioned in the {typename} {id} in {urn}:\n\n  {title}\n",
+            ),
+            review: template(
+                "Review requested: {title}",
+                "{handle} was asked to review the {typename} {id} in {urn}:\n\n  {title}\n",
+            ),
+        }
+    }
+}
+
+impl Templates {
+    /// Load the templates from the files `opened.txt`, `updated.txt`,
+    /// `mention.txt` and `review.txt` in `dir`, see [`Template::parse`].
+    ///
+    /// Missing files are replaced by the defaults.
+    pub fn load(dir: &Path) -> Result<Self, error::Template> {
+        let mut templates = Self::default();
+        for (name, template) in [
+            ("opened.txt", &mut templates.opened),
+            ("updated.txt", &mut templates.updated),
+            ("mention.txt", &mut templates.mention),
+            ("review.txt", &mut templates.review),
+        ] {
+            match Template::load(&dir.join(name)) {
+                Ok(loaded) => *template = loaded,
+                Err(error::Template::Io(e)) if e.kind() == io::ErrorKind::NotFound => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(templates)
+    }
+
+    fn get(&self, kind: &Kind) -> &Template {
+        match kind {
+            Kind::Opened => &self.opened,
+            Kind::Updated => &self.updated,
+            Kind::Mention(_) => &self.mention,
+            Kind::ReviewRequest(_) => &self.review,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Config {
+    pub smtp: Smtp,
+    /// The projects to notify about.
+    pub urns: BTreeSet<Urn>,
+    /// The types of collaborative objects to notify about.
+    pub typenames: Vec<TypeName>,
+    /// The handles to notify mentions of, and review requests for, without
+    /// the leading `@`.
+    pub handles: Vec<String>,
+    pub templates: Templates,
+    pub max_per_hour: usize,
+}
+
+/// A change to a collaborative object.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Notification {
+    pub kind: Kind,
+    pub urn: Urn,
+    pub typename: TypeName,
+    pub id: ObjectId,
+    /// The `title` of the object, or its id if it has none.
+    pub title: String,
+}
+
+/// Determine what changed between the `previous` and `current` state of a
+/// collaborative object, as realised to JSON.
+///
+/// `handles` are matched as `@handle` anywhere in the object, and as is in
+/// its `reviewers`.
+pub fn changes(
+    previous: Option<&serde_json::Value>,
+    current: &serde_json::Value,
+    handles: &[String],
+) -> Vec<Kind> {
+    if previous == Some(current) {
+        return vec![];
+    }
+    let (mentioned, reviewers) = match previous {
+        Some(previous) => (mentions(previous, handles), reviewers(previous, handles)),
+        None => (BTreeSet::new(), BTreeSet::new()),
+    };
+
+    let mut kinds = Vec::new();
+    if previous.is_none() {
+        kinds.push(Kind::Opened);
+    }
+    kinds.extend(
+        mentions(current, handles)
+            .difference(&mentioned)
+            .cloned()
+            .map(Kind::Mention),
+    );
+    kinds.extend(
+        reviewers(current, handles)
+            .difference(&reviewers)
+            .cloned()
+            .map(Kind::ReviewRequest),
+    );
+    if kinds.is_empty() {
+        kinds.push(Kind::Updated);
+    }
+    kinds
+}
+
+fn mentions(doc: &serde_json::Value, handles: &[String]) -> BTreeSet<String> {
+    let mut found = BTreeSet::new();
+    let mut stack = vec![doc];
+    while let Some(value) = stack.pop() {
+        match value {
+            serde_json::Value::String(s) => {
+                found.extend(handles.iter().filter(|h| mentioned(s, h)).cloned())
+            },
+            serde_json::Value::Array(values) => stack.extend(values),
+            serde_json::Value::Object(values) => stack.extend(values.values()),
+            _ => {},
+        }
+    }
+    found
+}
+
+/// Whether `s` contains `@handle`, not followed by more of a handle.
+fn mentioned(s: &str, handle: &str) -> bool {
+    let mention = format!("@{}", handle);
+    s.match_indices(&mention).any(|(i, _)| {
+        !s[i + mention.len()..]
+            .chars()
+            .next()
+            .map_or(false, |c| c.is_alphanumeric() || c == '-' || c == '_')
+    })
+}
+
+fn reviewers(doc: &serde_json::Value, handles: &[String]) -> BTreeSet<String> {
+    doc.get("reviewers")
+        .and_then(|r| r.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|r| r.as_str())
+        .map(|r| r.trim_start_matches('@'))
+        .filter(|r| handles.iter().any(|h| h == r))
+        .map(|r| r.to_owned())
+        .collect()
+}
+
+type Seen = HashMap<(Urn, ObjectId), serde_json::Value>;
+
+#[instrument(name = "notify subroutine", skip(peer, config))]
+pub async fn routine<S, G>(peer: Peer<S, G>, config: Config) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    info!(
+        projects = config.urns.len(),
+        "notifying about collaborative objects"
+    );
+
+    let mut handles = config.handles.clone();
+    handles.push(peer.peer_id().to_string());
+
+    let mut seen = Seen::new();
+    for urn in &config.urns {
+        scan(&peer, &config.typenames, &handles, &mut seen, urn).await;
+    }
+
+    let mut limit = RateLimit::new(config.max_per_hour, HOUR);
+    let events = peer.subscribe();
+    futures::pin_mut!(events);
+    while let Some(event) = events.next().await {
+        let urn = match event {
+            Ok(ProtocolEvent::Replicated(replicated)) => replicated.urn,
+            Ok(ProtocolEvent::Gossip(gossip)) => {
+                let Gossip::Put {
+                    payload: Payload { urn, .. },
+                    result,
+                    ..
+                } = *gossip;
+                if !matches!(result, PutResult::Applied(_)) {
+                    continue;
+                }
+                urn
+            },
+            Ok(_) => continue,
+            Err(e) => {
+                warn!(err = %e, "event error");
+                continue;
+            },
+        };
+        let urn = urn.with_path(None);
+        if !config.urns.contains(&urn) {
+            continue;
+        }
+
+        for n in scan(&peer, &config.typenames, &handles, &mut seen, &urn).await {
+            if !limit.allow(Instant::now()) {
+                warn!(urn = %n.urn, id = %n.id, "notification rate limit exceeded, dropping");
+                continue;
+            }
+            let (subject, body) = config.templates.get(&n.kind).render(&n);
+            match send(&config.smtp, &subject, &body).await {
+                Ok(()) => debug!(urn = %n.urn, id = %n.id, kind = ?n.kind, "notified"),
+                Err(e) => warn!(err = %e, urn = %n.urn, id = %n.id, "failed to send notification"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Realise the watched objects of `urn`, and return the changes since the
+/// last scan. The first scan of a project only records its objects.
+async fn scan<S, G>(
+    peer: &Peer<S, G>,
+    typenames: &[TypeName],
+    handles: &[String],
+    seen: &mut Seen,
+    urn: &Urn,
+) -> Vec<Notification>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let objects = peer
+        .using_storage({
+            let urn = urn.clone();
+            let typenames = typenames.to_vec();
+            move |storage| -> anyhow::Result<Vec<(TypeName, ObjectId, serde_json::Value)>> {
+                let cobs = storage.collaborative_objects(None);
+                let mut objects = Vec::new();
+                for typename in typenames {
+                    for object in cobs.list(&urn, &typename)? {
+                        objects.push((
+                            typename.clone(),
+                            *object.id(),
+                            cob::realize(object.history())?,
+                        ));
+                    }
+                }
+                Ok(objects)
+            }
+        })
+        .await;
+    let objects = match objects {
+        Ok(Ok(objects)) => objects,
+        Ok(Err(e)) => {
+            warn!(err = %e, %urn, "failed to load collaborative objects");
+            return vec![];
+        },
+        Err(e) => {
+            warn!(err = %e, "failed to access storage");
+            return vec![];
+        },
+    };
+
+    let first = !seen.keys().any(|(seen, _)| seen == urn);
+    let mut notifications = Vec::new();
+    for (typename, id, doc) in objects {
+        let key = (urn.clone(), id);
+        if !first {
+            let title = doc
+                .get("title")
+                .and_then(|t| t.as_str())
+                .map_or_else(|| id.to_string(), |t| t.to_owned());
+            for kind in changes(seen.get(&key), &doc, handles) {
+                notifications.push(Notification {
+                    kind,
+                    urn: urn.clone(),
+                    typename: typename.clone(),
+                    id,
+                    title: title.clone(),
+                })
+            }
+        }
+        seen.insert(key, doc);
+    }
+    notifications
+}
+
+/// Parse the typenames to watch, falling back to [`DEFAULT_TYPENAMES`].
+pub fn typenames(names: &[String]) -> Result<Vec<TypeName>, String> {
+    let names = if names.is_empty() {
+        DEFAULT_TYPENAMES.iter().map(|n| n.to_string()).collect()
+    } else {
+        names.to_vec()
+    };
+    names
+        .iter()
+        .map(|n| TypeName::from_str(n).map_err(|_| format!("invalid typename `{}`", n)))
+        .collect()
+}
+
+async fn send(smtp: &Smtp, subject: &str, body: &str) -> Result<(), error::Smtp> {
+    if smtp.credentials.is_some() && smtp.tls.is_none() {
+        return Err(error::Smtp::CleartextCredentials);
+    }
+    let message = message(smtp, subject, body);
+    let tcp = TcpStream::connect(&smtp.addr).await?;
+    match &smtp.tls {
+        None => session(smtp, BufStream::new(tcp), &message).await,
+        Some(tls) => {
+            let name = DNSNameRef::try_from_ascii_str(&smtp.server_name)
+                .map_err(|_| error::Smtp::ServerName(smtp.server_name.clone()))?;
+            let tls = TlsConnector::from(tls.clone()).connect(name, tcp).await?;
+            session(smtp, BufStream::new(tls), &message).await
+        },
+    }
+}
+
+/// Format the email, with CRLF line endings and dot-stuffing applied, ready
+/// to be sent as the `DATA` of an SMTP transaction.
+///
+/// The subject may contain the titles of objects created by anyone, so line
+/// breaks are removed from all header values.
+pub fn message(smtp: &Smtp, subject: &str, body: &str) -> String {
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+        header(&smtp.from),
+        header(&smtp.to.join(", ")),
+        header(subject)
+    );
+    for line in body.lines() {
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push_str(".\r\n");
+    message
+}
+
+/// A header value with line breaks replaced by spaces, so it cannot start
+/// another header, and encoded as per RFC 2047 unless it is ASCII.
+fn header(value: &str) -> String {
+    let value = value.replace(|c: char| c == '\r' || c == '\n', " ");
+    if value.is_ascii() {
+        value
+    } else {
+        format!("=?utf-8?B?{}?=", base64::encode(value))
+    }
+}
+
+async fn session<T>(smtp: &Smtp, mut stream: BufStream<T>, message: &str) -> Result<(), error::Smtp>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    reply(&mut stream, "connect", 2).await?;
+    let domain = smtp
+        .from
+        .rsplit_once('@')
+        .map_or("localhost", |(_, domain)| domain.trim_end_matches('>'));
+    command(&mut stream, &format!("EHLO {}", domain), 2).await?;
+    if let Some((user, password)) = &smtp.credentials {
+        let auth = base64::encode(format!("\0{}\0{}", user, password));
+        command(&mut stream, &format!("AUTH PLAIN {}", auth), 2).await?;
+    }
+    command(
+        &mut stream,
+        &format!("MAIL FROM:<{}>", address(&smtp.from)),
+        2,
+    )
+    .await?;
+    for to in &smtp.to {
+        command(&mut stream, &format!("RCPT TO:<{}>", address(to)), 2).await?;
+    }
+    command(&mut stream, "DATA", 3).await?;
+    stream.write_all(message.as_bytes()).await?;
+    stream.flush().await?;
+    reply(&mut stream, "DATA", 2).await?;
+    command(&mut stream, "QUIT", 2).await?;
+    Ok(())
+}
+
+/// The bare address of a mailbox, eg. `a@b.c` for `A <a@b.c>`.
+fn address(mailbox: &str) -> &str {
+    match mailbox.rsplit_once('<') {
+        Some((_, addr)) => addr.trim_end_matches('>'),
+        None => mailbox.trim(),
+    }
+}
+
+async fn command<T>(stream: &mut BufStream<T>, command: &str, class: u8) -> Result<(), error::Smtp>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    if command.contains(|c: char| c == '\r' || c == '\n') {
+        return Err(error::Smtp::LineBreak);
+    }
+    stream.write_all(command.as_bytes()).await?;
+    stream.write_all(b"\r\n").await?;
+    stream.flush().await?;
+    let command = if command.starts_with("AUTH") {
+        "AUTH PLAIN"
+    } else {
+        command
+    };
+    reply(stream, command, class).await
+}
+
+/// Read a (possibly multiline) reply, and check that its code is of the
+/// given `class`, eg. `2` for `250`.
+async fn reply<T>(stream: &mut BufStream<T>, command: &str, class: u8) -> Result<(), error::Smtp>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut reply = String::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Err(error::Smtp::Eof);
+        }
+        reply.push_str(&line);
+        if line.as_bytes().get(3) != Some(&b'-') {
+            break;
+        }
+    }
+    if reply.as_bytes().first() == Some(&(b'0' + class)) {
+        Ok(())
+    } else {
+        Err(error::Smtp::Reply {
+            command: command.to_owned(),
+            reply: reply.trim_end().to_owned(),
+        })
+    }
+}
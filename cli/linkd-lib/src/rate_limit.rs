@@ -0,0 +1,44 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Sliding window rate limit.
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    max: usize,
+    window: Duration,
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimit {
+    pub fn new(max: usize, window: Duration) -> Self {
+        Self {
+            max,
+            window,
+            sent: VecDeque::with_capacity(max),
+        }
+    }
+
+    /// Whether another action, eg. sending an email, may be taken at `now`.
+    /// If so, it is counted against the limit.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        while let Some(oldest) = self.sent.front() {
+            if now.saturating_duration_since(*oldest) < self.window {
+                break;
+            }
+            self.sent.pop_front();
+        }
+        if self.sent.len() < self.max {
+            self.sent.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,411 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Replication worker pool.
+//!
+//! Replication jobs are run by a bounded number of workers. Every job is
+//! registered under a [`TaskId`] for as long as it is queued or running, so
+//! that it can be inspected and cancelled via the control socket.
+//!
+//! Queued jobs are started by the [`Scheduler`], which takes turns between
+//! namespaces, and limits the number of jobs running for any one namespace.
+//! This way, a namespace with a lot of (or very slow) jobs can't occupy all
+//! workers, and with them all storage connections, while jobs for other
+//! namespaces are waiting.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use futures::{
+    future::{AbortHandle, Abortable},
+    Future,
+    TryFutureExt as _,
+};
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+use librad::{
+    git::Urn,
+    net::{
+        peer::{error, failover, Peer},
+        protocol::RequestPullGuard,
+        quic,
+        replication,
+    },
+    PeerId,
+    Signer,
+};
+
+pub use lnk_clib::rpc::replication::{Phase, Progress, TaskId, TaskInfo};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("replication task {0} was cancelled")]
+    Cancelled(TaskId),
+
+    #[error(transparent)]
+    Replicate(#[from] error::Replicate),
+
+    #[error(transparent)]
+    ReplicateAny(#[from] error::ReplicateAny),
+}
+
+/// Round-robin scheduling of jobs across namespaces.
+///
+/// Jobs are queued per namespace, in the order they were submitted. Whenever
+/// a worker is available, the next job is taken from the namespace whose turn
+/// it is, skipping namespaces which already have `per_urn` jobs running.
+#[derive(Debug)]
+pub struct Scheduler {
+    workers: usize,
+    per_urn: usize,
+    running: usize,
+    in_flight: HashMap<Urn, usize>,
+    queued: HashMap<Urn, VecDeque<TaskId>>,
+    /// The namespaces with queued jobs, in the order of their turns.
+    turns: VecDeque<Urn>,
+}
+
+impl Scheduler {
+    /// Create a scheduler running at most `workers` jobs concurrently, of
+    /// which at most `per_urn` are for the same namespace.
+    pub fn new(workers: usize, per_urn: usize) -> Self {
+        Self {
+            workers: workers.max(1),
+            per_urn: per_urn.max(1),
+            running: 0,
+            in_flight: HashMap::new(),
+            queued: HashMap::new(),
+            turns: VecDeque::new(),
+        }
+    }
+
+    /// Queue the job `id` for `urn`.
+    pub fn enqueue(&mut self, id: TaskId, urn: &Urn) {
+        let queue = self.queued.entry(urn.clone()).or_default();
+        if queue.is_empty() {
+            self.turns.push_back(urn.clone());
+        }
+        queue.push_back(id)
+    }
+
+    /// Remove the queued job `id` for `urn`.
+    pub fn dequeue(&mut self, id: TaskId, urn: &Urn) {
+        if let Some(queue) = self.queued.get_mut(urn) {
+            queue.retain(|queued| *queued != id);
+            if queue.is_empty() {
+                self.queued.remove(urn);
+                self.turns.retain(|turn| turn != urn);
+            }
+        }
+    }
+
+    /// Take the next job to run, if a worker is available and any job may
+    /// run.
+    ///
+    /// The job counts as running until [`Scheduler::finish`] is called.
+    pub fn next(&mut self) -> Option<(TaskId, Urn)> {
+        if self.running >= self.workers {
+            return None;
+        }
+        for _ in 0..self.turns.len() {
+            let urn = self.turns.pop_front()?;
+            if self.is_throttled(&urn) {
+                self.turns.push_back(urn);
+                continue;
+            }
+
+            let queue = self.queued.get_mut(&urn)?;
+            let id = queue.pop_front()?;
+            if queue.is_empty() {
+                self.queued.remove(&urn);
+            } else {
+                self.turns.push_back(urn.clone());
+            }
+            self.running += 1;
+            *self.in_flight.entry(urn.clone()).or_default() += 1;
+            return Some((id, urn));
+        }
+
+        None
+    }
+
+    /// Release the worker of a job for `urn` taken from [`Scheduler::next`].
+    pub fn finish(&mut self, urn: &Urn) {
+        self.running = self.running.saturating_sub(1);
+        if let Some(n) = self.in_flight.get_mut(urn) {
+            *n -= 1;
+            if *n == 0 {
+                self.in_flight.remove(urn);
+            }
+        }
+    }
+
+    /// The number of jobs running.
+    pub fn running(&self) -> usize {
+        self.running
+    }
+
+    /// Whether `urn` has the maximum number of jobs running.
+    pub fn is_throttled(&self, urn: &Urn) -> bool {
+        self.in_flight.get(urn).copied().unwrap_or(0) >= self.per_urn
+    }
+}
+
+struct Entry {
+    urn: Urn,
+    peer: PeerId,
+    phase: Phase,
+    submitted: Instant,
+    /// The bytes received on the connection to `peer` when the job started.
+    received: Option<u64>,
+    abort: AbortHandle,
+    /// Signalled when the job may start.
+    start: Option<oneshot::Sender<()>>,
+}
+
+struct Tasks {
+    next: u64,
+    active: BTreeMap<TaskId, Entry>,
+    scheduler: Scheduler,
+}
+
+impl Tasks {
+    /// Start as many queued jobs as the [`Scheduler`] permits.
+    fn dispatch(&mut self) {
+        while let Some((id, _)) = self.scheduler.next() {
+            if let Some(entry) = self.active.get_mut(&id) {
+                entry.phase = Phase::Fetching;
+                if let Some(start) = entry.start.take() {
+                    // The job deregisters itself if it is gone
+                    start.send(()).ok();
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Pool {
+    tasks: Arc<Mutex<Tasks>>,
+}
+
+impl Pool {
+    /// Create a pool running at most `workers` replication jobs concurrently,
+    /// of which at most `per_urn` are for the same namespace.
+    pub fn new(workers: usize, per_urn: usize) -> Self {
+        Self {
+            tasks: Arc::new(Mutex::new(Tasks {
+                next: 0,
+                active: BTreeMap::new(),
+                scheduler: Scheduler::new(workers, per_urn),
+            })),
+        }
+    }
+
+    /// Replicate `urn` from the peer `from`, waiting for a worker to become
+    /// available first.
+    ///
+    /// The job can be cancelled via [`Pool::cancel`] while it is queued or
+    /// running, in which case [`Error::Cancelled`] is returned.
+    pub async fn replicate<S, G>(
+        &self,
+        peer: &Peer<S, G>,
+        from: (PeerId, Vec<SocketAddr>),
+        urn: Urn,
+    ) -> Result<replication::Success, Error>
+    where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        let remote = from.0;
+        let job = peer.replicate(from, urn.clone(), None).err_into();
+        self.run(peer, urn, remote, job).await
+    }
+
+    /// Replicate `urn` from the first of `providers` it succeeds from, see
+    /// [`Peer::replicate_any`], waiting for a worker to become available
+    /// first.
+    ///
+    /// The job is listed under the first provider, and can be cancelled like
+    /// the jobs of [`Pool::replicate`].
+    pub async fn replicate_any<S, G>(
+        &self,
+        peer: &Peer<S, G>,
+        providers: Vec<(PeerId, Vec<SocketAddr>)>,
+        urn: Urn,
+    ) -> Result<failover::Outcome, Error>
+    where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        let remote = match providers.first() {
+            Some((remote, _)) => *remote,
+            None => return Err(error::ReplicateAny::NoProviders.into()),
+        };
+        let strategy = failover::Strategy::FirstSuccess;
+        let job = peer
+            .replicate_any(providers, urn.clone(), None, strategy)
+            .err_into();
+        self.run(peer, urn, remote, job).await
+    }
+
+    /// Register `job` for `urn` from `remote`, and run it once the
+    /// [`Scheduler`] permits.
+    async fn run<S, G, F, T>(
+        &self,
+        peer: &Peer<S, G>,
+        urn: Urn,
+        remote: PeerId,
+        job: F,
+    ) -> Result<T, Error>
+    where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+        F: Future<Output = Result<T, Error>>,
+    {
+        let (abort, registration) = AbortHandle::new_pair();
+        let (start, started) = oneshot::channel();
+        let id = {
+            let mut tasks = self.tasks.lock().unwrap();
+            let id = TaskId::from(tasks.next);
+            tasks.next += 1;
+            tasks.active.insert(
+                id,
+                Entry {
+                    urn: urn.clone(),
+                    peer: remote,
+                    phase: Phase::Queued,
+                    submitted: Instant::now(),
+                    received: None,
+                    abort,
+                    start: Some(start),
+                },
+            );
+            tasks.scheduler.enqueue(id, &urn);
+            tasks.dispatch();
+            id
+        };
+        let _guard = Deregister {
+            id,
+            tasks: self.tasks.clone(),
+        };
+
+        let job = async {
+            started
+                .await
+                .expect("jobs are started before they are deregistered");
+            tracing::debug!(task = %id, %urn, peer = %remote, "starting replication");
+            let received = received_bytes(&peer.connection_stats().await, &remote);
+            if let Some(entry) = self.tasks.lock().unwrap().active.get_mut(&id) {
+                entry.received = Some(received);
+            }
+            job.await
+        };
+
+        match Abortable::new(job, registration).await {
+            Ok(res) => res,
+            Err(_) => Err(Error::Cancelled(id)),
+        }
+    }
+
+    /// List the jobs which are currently queued or running, ordered by
+    /// [`TaskId`].
+    pub async fn tasks<S, G>(&self, peer: &Peer<S, G>) -> Vec<TaskInfo>
+    where
+        S: Signer + Clone,
+        G: RequestPullGuard,
+    {
+        let stats = peer.connection_stats().await;
+        let tasks = self.tasks.lock().unwrap();
+        tasks
+            .active
+            .iter()
+            .map(|(id, entry)| TaskInfo {
+                id: *id,
+                urn: entry.urn.clone(),
+                peer: entry.peer,
+                phase: match entry.phase {
+                    Phase::Queued if tasks.scheduler.is_throttled(&entry.urn) => Phase::Throttled,
+                    phase => phase,
+                },
+                elapsed_secs: entry.submitted.elapsed().as_secs(),
+                progress: progress(peer, &stats, entry),
+            })
+            .collect()
+    }
+
+    /// Cancel the job `id`.
+    ///
+    /// Returns `false` if no such job is queued or running.
+    pub fn cancel(&self, id: TaskId) -> bool {
+        match self.tasks.lock().unwrap().active.get(&id) {
+            None => false,
+            Some(entry) => {
+                tracing::info!(task = %id, urn = %entry.urn, "cancelling replication");
+                entry.abort.abort();
+                true
+            },
+        }
+    }
+}
+
+/// The progress of the job `entry`, as reported by the replication backend or
+/// else counted on the connection to the remote peer.
+fn progress<S, G>(
+    peer: &Peer<S, G>,
+    stats: &HashMap<PeerId, quic::ConnectionStats>,
+    entry: &Entry,
+) -> Option<Progress>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let received = entry.received?;
+    let progress = match peer.replication_progress(&entry.urn, &entry.peer) {
+        Some(transfer) => Progress {
+            received_bytes: transfer.received_bytes,
+            total_objects: transfer.total_objects.map(|n| n as u64),
+            indexed_objects: transfer.indexed_objects.map(|n| n as u64),
+        },
+        None => Progress {
+            received_bytes: received_bytes(stats, &entry.peer).saturating_sub(received),
+            total_objects: None,
+            indexed_objects: None,
+        },
+    };
+    Some(progress)
+}
+
+fn received_bytes(stats: &HashMap<PeerId, quic::ConnectionStats>, peer: &PeerId) -> u64 {
+    stats.get(peer).map_or(0, |stats| stats.recv_bytes)
+}
+
+/// Removes a job from the task list once it is done, regardless of how, and
+/// starts the next queued job in its place.
+struct Deregister {
+    id: TaskId,
+    tasks: Arc<Mutex<Tasks>>,
+}
+
+impl Drop for Deregister {
+    fn drop(&mut self) {
+        if let Ok(mut tasks) = self.tasks.lock() {
+            if let Some(entry) = tasks.active.remove(&self.id) {
+                match entry.phase {
+                    Phase::Fetching => tasks.scheduler.finish(&entry.urn),
+                    Phase::Queued | Phase::Throttled => {
+                        tasks.scheduler.dequeue(self.id, &entry.urn)
+                    },
+                }
+                tasks.dispatch();
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Collection of the key rotations announced by seeds.
+//!
+//! Seeds announce a rotation of their key before switching to the new one,
+//! see [`librad::net::protocol::rotation`]. This routine periodically asks
+//! the seeds we were started with for their rotation, and stores the ones
+//! signed by the key we know the seed as in the rotations file (see
+//! [`lnk_clib::seed::pin`]). The pinned seed is followed to its new key the
+//! next time the seeds are loaded, without anyone editing the configuration.
+
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+use tracing::{debug, info, instrument, warn};
+
+use librad::{
+    net::{
+        peer::Peer,
+        protocol::{rotation::Rotation, RequestPullGuard},
+    },
+    PeerId,
+    Signer,
+};
+use lnk_clib::seed::pin;
+
+/// How often the seeds are asked for their rotations.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The seeds to ask, and their addresses.
+    pub seeds: Vec<(PeerId, Vec<SocketAddr>)>,
+    /// The rotations file, see [`librad::paths::seed_rotations`].
+    pub path: PathBuf,
+    pub interval: Duration,
+}
+
+#[instrument(name = "rotation subroutine", skip(peer, config))]
+pub async fn routine<S, G>(peer: Peer<S, G>, config: Config) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    loop {
+        for (seed, addrs) in &config.seeds {
+            match fetch(&peer, *seed, addrs.clone()).await {
+                Ok(None) => debug!(%seed, "seed announced no rotation"),
+                Ok(Some(rotation)) => {
+                    let path = config.path.clone();
+                    let new = rotation.new;
+                    let stored =
+                        tokio::task::spawn_blocking(move || pin::store_rotation(path, rotation))
+                            .await?;
+                    match stored {
+                        Ok(true) => info!(%seed, %new, "stored announced seed rotation"),
+                        Ok(false) => {},
+                        Err(e) => warn!(%seed, err = %e, "failed to store seed rotation"),
+                    }
+                },
+                Err(e) => debug!(%seed, err = %e, "failed to ask seed for its rotation"),
+            }
+        }
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+/// Ask `seed` for its rotation, returning it only if it is signed by `seed`.
+async fn fetch<S, G>(
+    peer: &Peer<S, G>,
+    seed: PeerId,
+    addrs: Vec<SocketAddr>,
+) -> anyhow::Result<Option<Rotation>>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let rotation = peer.interrogate((seed, addrs)).await?.rotation().await?;
+    match rotation {
+        Some(rotation) if rotation.old != seed => {
+            warn!(%seed, old = %rotation.old, "seed announced a rotation of another key");
+            Ok(None)
+        },
+        Some(rotation) => {
+            rotation.verify()?;
+            Ok(Some(rotation))
+        },
+        None => Ok(None),
+    }
+}
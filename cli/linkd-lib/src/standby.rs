@@ -0,0 +1,410 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Warm standby of a seed node.
+//!
+//! A standby continuously mirrors the namespaces of a primary node, so that it
+//! can take over if the primary fails, without copying live git data between
+//! the machines. It follows the primary over two channels:
+//!
+//! * the remote control port of the primary (see [`crate::api::remote`]), over
+//!   which it pages through the namespaces written to since the last round,
+//!   along with their tracking configuration
+//! * the Link protocol, over which it replicates these namespaces from the
+//!   primary
+//!
+//! The tracking configuration of the primary is applied locally before
+//! replicating, so the standby ends up with the same remotes, including their
+//! collaborative objects. The primary itself is tracked in addition.
+//!
+//! A standby is promoted by sending it a
+//! [`crate::api::client::Command::promote`] request via its control socket,
+//! upon which it stops following the primary. The standby keeps its own peer
+//! id, so clients need to be pointed to it after promotion. Namespaces
+//! removed from the primary are not removed from the standby.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom as _,
+    fmt,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::net::TcpStream;
+use tokio_rustls::{client::TlsStream, webpki::DNSNameRef, TlsConnector};
+use tracing::{debug, info, instrument, warn};
+
+use librad::{
+    canonical::Canonical as _,
+    git::{
+        storage::{index, Storage},
+        tracking,
+    },
+    net::{peer::Peer, protocol::RequestPullGuard},
+    PeerId,
+    Signer,
+};
+use link_async::CancellationToken;
+
+use crate::{
+    api::{
+        client::{Command, Connection, Reply},
+        io::StreamTransport,
+        standby::namespaces,
+    },
+    replication::Pool,
+};
+
+const USER_AGENT: &str = "linkd-standby";
+
+/// The number of namespaces requested per page.
+const PAGE_SIZE: u32 = 256;
+
+type Transport = StreamTransport<TlsStream<TcpStream>>;
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use crate::api::remote;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Control {
+        #[error("invalid server name `{0}`")]
+        ServerName(String),
+
+        #[error(transparent)]
+        Authenticate(#[from] remote::error::Authenticate),
+
+        #[error("failed to exchange messages with the primary: {0}")]
+        Transport(String),
+
+        #[error("the primary responded with an error: {0}")]
+        Primary(String),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+/// The role of the node, shared between the standby routine and the control
+/// socket.
+#[derive(Clone, Default)]
+pub struct Role {
+    following: Option<CancellationToken>,
+}
+
+impl Role {
+    pub fn primary() -> Self {
+        Self::default()
+    }
+
+    pub fn standby() -> Self {
+        Self {
+            following: Some(CancellationToken::new()),
+        }
+    }
+
+    /// Whether the node currently follows a primary.
+    pub fn is_standby(&self) -> bool {
+        self.following
+            .as_ref()
+            .map_or(false, |token| !token.is_cancelled())
+    }
+
+    /// Stop following the primary.
+    ///
+    /// Returns `false` if the node was not a standby.
+    pub fn promote(&self) -> bool {
+        match &self.following {
+            Some(token) if !token.is_cancelled() => {
+                token.cancel();
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+/// How to reach the remote control port of the primary.
+#[derive(Clone)]
+pub struct Control {
+    pub addr: SocketAddr,
+    /// The name the certificate of the primary must be valid for.
+    pub server_name: String,
+    pub tls: Arc<rustls::ClientConfig>,
+    /// Bearer token, requires at least `read` access.
+    pub token: Option<String>,
+}
+
+impl fmt::Debug for Control {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Control")
+            .field("addr", &self.addr)
+            .field("server_name", &self.server_name)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub primary: PeerId,
+    /// Addresses to reach the primary over the Link protocol.
+    pub addrs: Vec<SocketAddr>,
+    pub control: Control,
+    /// Time between two rounds.
+    pub interval: Duration,
+}
+
+#[instrument(name = "standby subroutine", skip(peer, pool, role))]
+pub async fn routine<S, G>(
+    peer: Peer<S, G>,
+    pool: Pool,
+    role: Role,
+    config: Config,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let following = match role.following {
+        Some(token) if !token.is_cancelled() => token,
+        _ => return Ok(()),
+    };
+    info!(primary = %config.primary, "following primary");
+    if following
+        .run_until_cancelled(follow(&peer, &pool, &config))
+        .await
+        .is_err()
+    {
+        info!(primary = %config.primary, "promoted, no longer following primary");
+    }
+    Ok(())
+}
+
+async fn follow<S, G>(peer: &Peer<S, G>, pool: &Pool, config: &Config)
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let mut since = None;
+    loop {
+        match round(peer, pool, config, since).await {
+            // Carry the primary's clock forward, ours may differ
+            Ok((0, as_of)) => since = Some(as_of),
+            Ok((failed, _)) => {
+                warn!(failed, "some namespaces could not be replicated, retrying")
+            },
+            Err(e) => warn!(err = %e, "standby round failed"),
+        }
+        link_async::sleep(config.interval).await;
+    }
+}
+
+/// Mirror the namespaces written to on the primary `since` the given time.
+///
+/// Returns the number of namespaces which failed to replicate, and the time
+/// on the primary the listing started at.
+async fn round<S, G>(
+    peer: &Peer<S, G>,
+    pool: &Pool,
+    config: &Config,
+    since: Option<u64>,
+) -> anyhow::Result<(usize, u64)>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let (updated, as_of) = list(connect(&config.control).await?, since).await?;
+    debug!(updated = updated.len(), "listed namespaces of primary");
+
+    let mut failed = 0;
+    for namespace in updated {
+        let urn = namespace.urn.clone();
+        let primary = config.primary;
+        let tracked = peer
+            .using_storage(move |storage| mirror_tracking(storage, primary, &namespace))
+            .await?;
+        if let Err(e) = tracked {
+            warn!(%urn, err = %e, "failed to mirror tracking configuration");
+            failed += 1;
+            continue;
+        }
+        match pool
+            .replicate(peer, (config.primary, config.addrs.clone()), urn.clone())
+            .await
+        {
+            Ok(_) => debug!(%urn, "replicated from primary"),
+            Err(e) => {
+                warn!(%urn, err = %e, "failed to replicate from primary");
+                failed += 1;
+            },
+        }
+    }
+
+    Ok((failed, as_of))
+}
+
+async fn connect(control: &Control) -> Result<Connection<Transport>, error::Control> {
+    let name = DNSNameRef::try_from_ascii_str(&control.server_name)
+        .map_err(|_| error::Control::ServerName(control.server_name.clone()))?;
+    let tcp = TcpStream::connect(control.addr).await?;
+    let tls = TlsConnector::from(control.tls.clone())
+        .connect(name, tcp)
+        .await?;
+    let (conn, access) = Connection::connect_remote(USER_AGENT, tls, control.token.clone()).await?;
+    debug!(%access, "connected to primary");
+    Ok(conn)
+}
+
+/// List the namespaces of the primary written to since `updated_since`.
+///
+/// Also returns the [`namespaces::Response::as_of`] of the first page.
+async fn list(
+    mut conn: Connection<Transport>,
+    updated_since: Option<u64>,
+) -> Result<(Vec<namespaces::Namespace>, u64), error::Control> {
+    let mut all = Vec::new();
+    let mut first = None;
+    let mut after = None;
+    loop {
+        let mut replies = Command::standby_namespaces(updated_since, after.take(), PAGE_SIZE)
+            .execute_with_reply(conn)
+            .await
+            .map_err(|e| error::Control::Transport(e.to_string()))?;
+        let page = loop {
+            match replies.next().await {
+                Ok(Reply::Progress { replies: next, .. }) => replies = next,
+                Ok(Reply::Success {
+                    conn: next,
+                    payload,
+                }) => {
+                    conn = next;
+                    break payload;
+                },
+                Ok(Reply::Error { msg, .. }) => return Err(error::Control::Primary(msg)),
+                Err((_, e)) => return Err(error::Control::Transport(e.to_string())),
+            }
+        };
+        let as_of = *first.get_or_insert(page.as_of);
+        all.extend(page.namespaces);
+        match page.next {
+            Some(next) => after = Some(next),
+            None => return Ok((all, as_of)),
+        }
+    }
+}
+
+/// Make the tracking entries of `namespace` match the ones on the primary.
+fn mirror_tracking(
+    storage: &Storage,
+    primary: PeerId,
+    namespace: &namespaces::Namespace,
+) -> anyhow::Result<()> {
+    let urn = &namespace.urn;
+    let local = storage.peer_id();
+    let mut wanted = BTreeMap::new();
+    for tracked in &namespace.tracked {
+        if tracked.peer == Some(*local) {
+            continue;
+        }
+        wanted.insert(
+            tracked.peer,
+            tracking::Config::try_from(tracked.config.as_slice())?,
+        );
+    }
+
+    for (peer, config) in wanted.iter() {
+        if let Err(e) = tracking::track(
+            storage,
+            urn,
+            *peer,
+            config.clone(),
+            tracking::policy::Track::Any,
+        )? {
+            debug!(%urn, err = %e, "tracking policy error");
+        }
+    }
+    if !wanted.contains_key(&Some(primary)) {
+        // Already tracking the primary is fine, whatever its configuration.
+        tracking::track(
+            storage,
+            urn,
+            Some(primary),
+            tracking::Config::default(),
+            tracking::policy::Track::MustNotExist,
+        )?
+        .ok();
+    }
+
+    let untracked = tracking::tracked_peers(storage, Some(urn))?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|peer| *peer != primary && !wanted.contains_key(&Some(*peer)));
+    for peer in untracked {
+        tracking::untrack(
+            storage,
+            urn,
+            peer,
+            tracking::UntrackArgs {
+                policy: tracking::policy::Untrack::Any,
+                prune: false,
+            },
+        )?
+        .ok();
+    }
+
+    Ok(())
+}
+
+/// Serve a [`namespaces::Request`] from `storage`.
+pub(crate) fn namespaces(
+    storage: &Storage,
+    request: &namespaces::Request,
+) -> anyhow::Result<namespaces::Response> {
+    let as_of = now_secs();
+    let filter = index::Filter {
+        updated_since: request
+            .updated_since
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+        ..index::Filter::default()
+    };
+    let limit = request.limit.min(namespaces::MAX_LIMIT) as usize;
+    let page = index::list(storage, &filter, request.after.as_ref(), limit)?;
+
+    let mut out = Vec::with_capacity(page.entries.len());
+    for entry in page.entries {
+        let mut tracked = Vec::new();
+        for t in tracking::tracked(storage, Some(&entry.urn))? {
+            let t = t?;
+            tracked.push(namespaces::Tracked {
+                peer: t.peer_id(),
+                config: t.config().canonical_form()?,
+            });
+        }
+        out.push(namespaces::Namespace {
+            urn: entry.urn,
+            updated: entry.updated,
+            tracked,
+        });
+    }
+
+    Ok(namespaces::Response {
+        namespaces: out,
+        next: page.next,
+        as_of,
+    })
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -3,9 +3,10 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{collections::BTreeSet, convert::Infallible, str::FromStr};
+use std::{collections::BTreeSet, convert::Infallible, net::SocketAddr, str::FromStr, sync::Arc};
 
 use futures::{pin_mut, StreamExt as _};
+use link_tracing::{Context, InContext as _};
 use radicle_git_ext::FromMultihashError;
 use thiserror::Error;
 use tracing::{error, info, instrument, trace};
@@ -24,6 +25,13 @@ use librad::{
     PeerId,
     Signer,
 };
+use link_async::Spawner;
+
+use crate::replication::Pool;
+
+/// The maximum number of connected peers to fall back to if replicating from
+/// the announcing peer fails.
+const MAX_FALLBACK_PROVIDERS: usize = 3;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Tracker {
@@ -215,8 +223,13 @@ impl Tracker {
     }
 }
 
-#[instrument(name = "tracking subroutine", skip(peer, tracker))]
-pub async fn routine<S, G>(peer: Peer<S, G>, tracker: Tracker) -> anyhow::Result<()>
+#[instrument(name = "tracking subroutine", skip(spawner, peer, tracker, pool))]
+pub async fn routine<S, G>(
+    spawner: Arc<Spawner>,
+    peer: Peer<S, G>,
+    tracker: Tracker,
+    pool: Pool,
+) -> anyhow::Result<()>
 where
     S: Signer + Clone,
     G: RequestPullGuard,
@@ -242,46 +255,64 @@ where
                     continue;
                 }
 
-                let go = async {
-                    let updated = peer
-                    .using_storage({
-                        let urn = urn.clone();
-                        move |storage| -> anyhow::Result<bool> {
-                            match tracking::track(
-                                storage,
-                                &urn,
-                                Some(peer_id),
-                                tracking::Config::default(),
-                                tracking::policy::Track::MustNotExist,
-                            )? {
-                                Ok(reference) => {
-                                    trace!(name=%reference.name, target=%reference.target, "created tracking entry");
-                                    Ok(true)
-                                },
-                                Err(err) => {
-                                    trace!(err = %err, "tracking policy error");
-                                    Ok(false)
+                let go = {
+                    let peer = peer.clone();
+                    let pool = pool.clone();
+                    let urn = urn.clone();
+                    async move {
+                        let updated = peer
+                        .using_storage({
+                            let urn = urn.clone();
+                            move |storage| -> anyhow::Result<bool> {
+                                match tracking::track(
+                                    storage,
+                                    &urn,
+                                    Some(peer_id),
+                                    tracking::Config::default(),
+                                    tracking::policy::Track::MustNotExist,
+                                )? {
+                                    Ok(reference) => {
+                                        trace!(name=%reference.name, target=%reference.target, "created tracking entry");
+                                        Ok(true)
+                                    },
+                                    Err(err) => {
+                                        trace!(err = %err, "tracking policy error");
+                                        Ok(false)
+                                    }
                                 }
                             }
+                        })
+                        .await??;
+
+                        // Skip explicit replication if the peer is already tracked.
+                        if updated {
+                            let addr_hints = seen_addrs.iter().copied().collect::<Vec<_>>();
+                            let providers = providers(&peer, (peer_id, addr_hints)).await;
+                            pool.replicate_any(&peer, providers, urn).await?;
                         }
-                    })
-                    .await??;
-
-                    // Skip explicit replication if the peer is already tracked.
-                    if updated {
-                        let addr_hints = seen_addrs.iter().copied().collect::<Vec<_>>();
-                        peer.replicate((peer_id, addr_hints), urn.clone(), None)
-                            .await?;
-                    }
 
-                    Ok::<_, anyhow::Error>(updated)
+                        Ok::<_, anyhow::Error>(updated)
+                    }
                 };
 
-                match go.await {
-                    Ok(true) => info!("tracked project {} from {}", urn, peer_id),
-                    Ok(false) => info!("already tracked {} from {}", urn, peer_id),
-                    Err(err) => error!(?err, "tracking failed for {} from {}", urn, peer_id),
-                }
+                let cx = Context::new()
+                    .with_peer(peer.peer_id())
+                    .with_remote(peer_id)
+                    .with_urn(&urn);
+                spawner
+                    .spawn(
+                        async move {
+                            match go.await {
+                                Ok(true) => info!("tracked project {} from {}", urn, peer_id),
+                                Ok(false) => info!("already tracked {} from {}", urn, peer_id),
+                                Err(err) => {
+                                    error!(?err, "tracking failed for {} from {}", urn, peer_id)
+                                },
+                            }
+                        }
+                        .in_context(&cx, "track"),
+                    )
+                    .detach();
             },
 
             Ok(_) => {},
@@ -293,3 +324,32 @@ where
 
     Ok(())
 }
+
+/// The announcing peer `from`, followed by the most reliable connected peers
+/// as fallbacks.
+///
+/// Announcements are relayed, so `from` may not be reachable from here, while
+/// the peers which relayed the announcement are likely to have the update as
+/// well.
+async fn providers<S, G>(
+    peer: &Peer<S, G>,
+    from: (PeerId, Vec<SocketAddr>),
+) -> Vec<(PeerId, Vec<SocketAddr>)>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    let connected = peer
+        .connected_peers()
+        .await
+        .into_iter()
+        .filter(|connected| *connected != from.0);
+    let latency = peer.latency().await;
+    let fallbacks = peer
+        .reputations()
+        .rank_with_latency(&latency, connected, |connected| *connected)
+        .into_iter()
+        .take(MAX_FALLBACK_PROVIDERS)
+        .map(|connected| (connected, vec![]));
+    std::iter::once(from).chain(fallbacks).collect()
+}
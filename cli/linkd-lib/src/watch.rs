@@ -0,0 +1,90 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Announcement of changes made to the monorepo by other processes.
+//!
+//! Tools like `git-remote-rad` or `lnk-gitd` update the monorepo directly, and
+//! may or may not ask the node to announce the result. This routine watches
+//! the `rad/signed_refs` of the local peer, and announces every new state.
+
+use std::{collections::HashMap, thread, time::Duration};
+
+use tokio::sync::mpsc;
+use tracing::{debug, info, instrument, warn};
+
+use librad::{
+    git::{
+        storage::ReadOnlyStorage as _,
+        types::{Namespace, Reference},
+        Urn,
+    },
+    git_ext::Oid,
+    net::{
+        peer::Peer,
+        protocol::{gossip, RequestPullGuard},
+    },
+    PeerId,
+    Signer,
+};
+
+#[instrument(name = "watch subroutine", skip(peer))]
+pub async fn routine<S, G>(peer: Peer<S, G>, debounce: Duration) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    info!("watching for out-of-band updates");
+
+    let (_watcher, events) = peer
+        .using_storage(move |storage| storage.watch().signed_refs(debounce))
+        .await??;
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    thread::spawn(move || {
+        for urn in events {
+            if tx.send(urn).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut announced = HashMap::<Urn, Oid>::new();
+    while let Some(urn) = rx.recv().await {
+        let oid = peer
+            .using_read_only({
+                let urn = urn.clone();
+                move |storage| {
+                    let signed_refs: Reference<_, PeerId, _> =
+                        Reference::rad_signed_refs(Namespace::from(&urn), None);
+                    storage.reference_oid(&signed_refs)
+                }
+            })
+            .await?;
+        let oid = match oid {
+            Ok(oid) => oid,
+            Err(e) => {
+                warn!(err = %e, %urn, "failed to read updated signed refs");
+                continue;
+            },
+        };
+        if announced.get(&urn) == Some(&oid) {
+            debug!(%urn, %oid, "already announced");
+            continue;
+        }
+
+        info!(%urn, %oid, "announcing out-of-band update");
+        let payload = gossip::Payload {
+            urn: urn.clone(),
+            rev: Some(oid.into()),
+            origin: Some(peer.peer_id()),
+        };
+        if peer.announce(payload).is_err() {
+            warn!(%urn, "failed to announce, protocol is not running");
+        } else {
+            announced.insert(urn, oid);
+        }
+    }
+
+    Ok(())
+}
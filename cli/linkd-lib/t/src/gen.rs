@@ -8,7 +8,20 @@ use std::{fmt::Debug, net::SocketAddr};
 use librad_test::gen::protocol::gen_request_pull_success;
 use link_crypto_test::gen::gen_peer_id;
 use link_identities_test::gen::urn::{gen_oid, gen_urn};
-use linkd_lib::api::{announce, messages, request_pull};
+use linkd_lib::{
+    api::{
+        announce,
+        connections,
+        messages,
+        pinned,
+        project_stats,
+        replication,
+        request_pull,
+        standby,
+        usage,
+    },
+    replication::{Phase, Progress, TaskId, TaskInfo},
+};
 use proptest::{collection, prelude::*};
 use test_helpers::gen::std_net::gen_socket_addr;
 
@@ -42,15 +55,193 @@ pub fn request_pull(addrs: Vec<SocketAddr>) -> impl Strategy<Value = request_pul
     })
 }
 
+pub fn replication_dry_run(
+    addrs: Vec<SocketAddr>,
+) -> impl Strategy<Value = replication::dry_run::Request> {
+    (gen_peer_id(), gen_urn()).prop_map(move |(peer, urn)| replication::dry_run::Request {
+        urn,
+        peer,
+        addrs: addrs.clone(),
+    })
+}
+
+pub fn ref_update() -> impl Strategy<Value = replication::dry_run::Update> {
+    (
+        any::<String>(),
+        proptest::option::of(gen_oid(git2::ObjectType::Commit)),
+        gen_oid(git2::ObjectType::Commit),
+    )
+        .prop_map(|(name, old, new)| replication::dry_run::Update { name, old, new })
+}
+
+pub fn task_id() -> impl Strategy<Value = TaskId> {
+    any::<u64>().prop_map(TaskId::from)
+}
+
+pub fn progress() -> impl Strategy<Value = Progress> {
+    (
+        any::<u64>(),
+        proptest::option::of(any::<u64>()),
+        proptest::option::of(any::<u64>()),
+    )
+        .prop_map(
+            |(received_bytes, total_objects, indexed_objects)| Progress {
+                received_bytes,
+                total_objects,
+                indexed_objects,
+            },
+        )
+}
+
+pub fn task_info() -> impl Strategy<Value = TaskInfo> {
+    (
+        task_id(),
+        gen_urn(),
+        gen_peer_id(),
+        prop_oneof![
+            Just(Phase::Queued),
+            Just(Phase::Fetching),
+            Just(Phase::Throttled)
+        ],
+        any::<u64>(),
+        proptest::option::of(progress()),
+    )
+        .prop_map(|(id, urn, peer, phase, elapsed_secs, progress)| TaskInfo {
+            id,
+            urn,
+            peer,
+            phase,
+            elapsed_secs,
+            progress,
+        })
+}
+
+pub fn connection() -> impl Strategy<Value = connections::list::Connection> {
+    (
+        gen_peer_id(),
+        collection::vec(gen_socket_addr(), 1..3),
+        any::<u64>(),
+        collection::vec(
+            (any::<String>(), any::<u64>())
+                .prop_map(|(kind, open)| connections::list::Streams { kind, open }),
+            0..3,
+        ),
+        any::<u64>(),
+        any::<u64>(),
+    )
+        .prop_map(|(peer, addrs, age_secs, streams, sent_bytes, recv_bytes)| {
+            connections::list::Connection {
+                peer,
+                addrs,
+                age_secs,
+                streams,
+                sent_bytes,
+                recv_bytes,
+            }
+        })
+}
+
+pub fn pinned_peer() -> impl Strategy<Value = pinned::PinnedPeer> {
+    (
+        gen_peer_id(),
+        prop_oneof![
+            Just(pinned::Status::Pending),
+            Just(pinned::Status::Connected),
+            (any::<u32>(), any::<u64>()).prop_map(|(attempts, backoff_secs)| {
+                pinned::Status::Disconnected {
+                    attempts,
+                    backoff_secs,
+                }
+            }),
+        ],
+    )
+        .prop_map(|(peer, status)| pinned::PinnedPeer { peer, status })
+}
+
+pub fn project_stats() -> impl Strategy<Value = project_stats::Stats> {
+    (
+        any::<u64>(),
+        any::<u64>(),
+        any::<u64>(),
+        collection::btree_map(any::<String>(), any::<u64>(), 0..3),
+    )
+        .prop_map(
+            |(remotes, updated, disk_usage, cobs)| project_stats::Stats {
+                remotes,
+                updated,
+                disk_usage,
+                cobs,
+            },
+        )
+}
+
+pub fn standby_namespace() -> impl Strategy<Value = standby::namespaces::Namespace> {
+    (
+        gen_urn(),
+        any::<u64>(),
+        collection::vec(
+            (proptest::option::of(gen_peer_id()), any::<Vec<u8>>())
+                .prop_map(|(peer, config)| standby::namespaces::Tracked { peer, config }),
+            0..3,
+        ),
+    )
+        .prop_map(|(urn, updated, tracked)| standby::namespaces::Namespace {
+            urn,
+            updated,
+            tracked,
+        })
+}
+
 pub fn request_payload() -> impl Strategy<Value = messages::RequestPayload> {
     prop_oneof![
         announce().prop_map(messages::RequestPayload::from),
         collection::vec(gen_socket_addr(), 1..3)
             .prop_flat_map(request_pull)
-            .prop_map(messages::RequestPayload::from)
+            .prop_map(messages::RequestPayload::from),
+        Just(messages::RequestPayload::from(replication::tasks::Request)),
+        task_id()
+            .prop_map(|task| messages::RequestPayload::from(replication::cancel::Request { task })),
+        Just(messages::RequestPayload::from(pinned::Request)),
+        gen_urn().prop_map(|urn| messages::RequestPayload::from(project_stats::Request { urn })),
+        (
+            proptest::option::of(any::<u64>()),
+            proptest::option::of(gen_urn()),
+            any::<u32>()
+        )
+            .prop_map(
+                |(updated_since, after, limit)| messages::RequestPayload::from(
+                    standby::namespaces::Request {
+                        updated_since,
+                        after,
+                        limit
+                    }
+                )
+            ),
+        Just(messages::RequestPayload::from(standby::promote::Request)),
+        Just(messages::RequestPayload::from(connections::list::Request)),
+        gen_peer_id().prop_map(|peer| messages::RequestPayload::from(
+            connections::disconnect::Request { peer }
+        )),
+        collection::vec(gen_socket_addr(), 0..3)
+            .prop_flat_map(replication_dry_run)
+            .prop_map(messages::RequestPayload::from),
+        usage_request().prop_map(messages::RequestPayload::from),
     ]
 }
 
+pub fn usage_format() -> impl Strategy<Value = usage::Format> {
+    prop_oneof![Just(usage::Format::Json), Just(usage::Format::Csv)]
+}
+
+pub fn usage_request() -> impl Strategy<Value = usage::Request> {
+    (
+        proptest::option::of(any::<String>()),
+        proptest::option::of(any::<String>()),
+        usage_format(),
+    )
+        .prop_map(|(from, to, format)| usage::Request { from, to, format })
+}
+
 prop_compose! {
     pub fn request()
         (user_agent in user_agent(),
@@ -102,3 +293,130 @@ pub fn request_pull_response() -> impl Strategy<Value = messages::Response<reque
             })
     })
 }
+
+pub fn replication_tasks_response(
+) -> impl Strategy<Value = messages::Response<replication::tasks::Response>> {
+    request_id().prop_flat_map(move |id| {
+        (
+            Just(id),
+            collection::vec(task_info(), 0..3)
+                .prop_flat_map(move |tasks| response_payload(replication::tasks::Response(tasks))),
+        )
+            .prop_map(move |(request_id, payload)| messages::Response {
+                payload,
+                request_id,
+            })
+    })
+}
+
+pub fn pinned_peers_response() -> impl Strategy<Value = messages::Response<pinned::Response>> {
+    request_id().prop_flat_map(move |id| {
+        (
+            Just(id),
+            collection::vec(pinned_peer(), 0..3)
+                .prop_flat_map(move |peers| response_payload(pinned::Response(peers))),
+        )
+            .prop_map(move |(request_id, payload)| messages::Response {
+                payload,
+                request_id,
+            })
+    })
+}
+
+pub fn project_stats_response() -> impl Strategy<Value = messages::Response<project_stats::Response>>
+{
+    request_id().prop_flat_map(move |id| {
+        (
+            Just(id),
+            proptest::option::of(project_stats())
+                .prop_flat_map(move |stats| response_payload(project_stats::Response(stats))),
+        )
+            .prop_map(move |(request_id, payload)| messages::Response {
+                payload,
+                request_id,
+            })
+    })
+}
+
+pub fn standby_namespaces_response(
+) -> impl Strategy<Value = messages::Response<standby::namespaces::Response>> {
+    request_id().prop_flat_map(move |id| {
+        (
+            Just(id),
+            (
+                collection::vec(standby_namespace(), 0..3),
+                proptest::option::of(gen_urn()),
+                any::<u64>(),
+            )
+                .prop_flat_map(move |(namespaces, next, as_of)| {
+                    response_payload(standby::namespaces::Response {
+                        namespaces,
+                        next,
+                        as_of,
+                    })
+                }),
+        )
+            .prop_map(move |(request_id, payload)| messages::Response {
+                payload,
+                request_id,
+            })
+    })
+}
+
+pub fn connections_response(
+) -> impl Strategy<Value = messages::Response<connections::list::Response>> {
+    request_id().prop_flat_map(move |id| {
+        (
+            Just(id),
+            collection::vec(connection(), 0..3)
+                .prop_flat_map(move |conns| response_payload(connections::list::Response(conns))),
+        )
+            .prop_map(move |(request_id, payload)| messages::Response {
+                payload,
+                request_id,
+            })
+    })
+}
+
+pub fn replication_dry_run_response(
+) -> impl Strategy<Value = messages::Response<replication::dry_run::Response>> {
+    request_id().prop_flat_map(move |id| {
+        (
+            Just(id),
+            (
+                any::<bool>(),
+                collection::vec(ref_update(), 0..3),
+                any::<u64>(),
+                collection::vec(gen_oid(git2::ObjectType::Commit), 0..3),
+                collection::vec(gen_oid(git2::ObjectType::Commit), 0..3),
+            )
+                .prop_flat_map(move |(clone, updates, up_to_date, wants, haves)| {
+                    response_payload(replication::dry_run::Response {
+                        clone,
+                        updates,
+                        up_to_date,
+                        wants,
+                        haves,
+                    })
+                }),
+        )
+            .prop_map(move |(request_id, payload)| messages::Response {
+                payload,
+                request_id,
+            })
+    })
+}
+
+pub fn usage_response() -> impl Strategy<Value = messages::Response<usage::Response>> {
+    request_id().prop_flat_map(move |id| {
+        (
+            Just(id),
+            any::<String>()
+                .prop_flat_map(move |content| response_payload(usage::Response(content))),
+        )
+            .prop_map(move |(request_id, payload)| messages::Response {
+                payload,
+                request_id,
+            })
+    })
+}
@@ -5,4 +5,9 @@
 
 mod api;
 mod args;
+mod autotrack;
+mod http;
+mod mirror;
+mod notify;
+mod replication;
 mod tracking;
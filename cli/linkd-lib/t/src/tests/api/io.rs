@@ -6,7 +6,18 @@
 use linkd_lib::api::{io, io::Transport as _, messages};
 use proptest::{array::uniform3, prelude::*};
 
-use crate::gen::{announce_response, request, request_pull_response};
+use crate::gen::{
+    announce_response,
+    connections_response,
+    pinned_peers_response,
+    project_stats_response,
+    replication_dry_run_response,
+    replication_tasks_response,
+    request,
+    request_pull_response,
+    standby_namespaces_response,
+    usage_response,
+};
 
 proptest! {
     #[test]
@@ -36,10 +47,41 @@ proptest! {
     fn test_response_round_trip_request_pull(responses in uniform3(request_pull_response())) {
         test_response_round_trip(&responses)
     }
+        #[test]
+    fn test_response_round_trip_replication_tasks(responses in uniform3(replication_tasks_response())) {
+        test_response_round_trip(&responses)
+    }
+        #[test]
+    fn test_response_round_trip_pinned_peers(responses in uniform3(pinned_peers_response())) {
+        test_response_round_trip(&responses)
+    }
+        #[test]
+    fn test_response_round_trip_project_stats(responses in uniform3(project_stats_response())) {
+        test_response_round_trip(&responses)
+    }
+        #[test]
+    fn test_response_round_trip_standby_namespaces(responses in uniform3(standby_namespaces_response())) {
+        test_response_round_trip(&responses)
+    }
+        #[test]
+    fn test_response_round_trip_connections(responses in uniform3(connections_response())) {
+        test_response_round_trip(&responses)
+    }
+        #[test]
+    fn test_response_round_trip_replication_dry_run(responses in uniform3(replication_dry_run_response())) {
+        test_response_round_trip(&responses)
+    }
+        #[test]
+    fn test_response_round_trip_usage(responses in uniform3(usage_response())) {
+        test_response_round_trip(&responses)
+    }
 }
 
 fn with_async_transport<
-    F: FnOnce(io::SocketTransport, io::SocketTransport) -> FU,
+    F: FnOnce(
+        io::StreamTransport<tokio::io::DuplexStream>,
+        io::StreamTransport<tokio::io::DuplexStream>,
+    ) -> FU,
     FU: futures::Future<Output = ()>,
 >(
     f: F,
@@ -47,8 +89,12 @@ fn with_async_transport<
     tokio::runtime::Runtime::new()
         .unwrap()
         .block_on(async move {
-            let (left, right) = tokio::net::UnixStream::pair().unwrap();
-            f(left.into(), right.into()).await
+            let (left, right) = tokio::io::duplex(64 * 1024);
+            f(
+                io::SocketTransport::from_stream(left),
+                io::SocketTransport::from_stream(right),
+            )
+            .await
         })
 }
 
@@ -0,0 +1,72 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use linkd_lib::api::{
+    messages::RequestPayload,
+    remote::{self, error, Access, Tokens},
+    replication,
+};
+
+const TOKENS: &str = "
+# operators
+operate s3cr3t
+read   l00k
+admin  r00t
+";
+
+#[test]
+fn tokens_grant_access() {
+    let tokens = Tokens::parse(TOKENS).unwrap();
+    assert_eq!(tokens.access("s3cr3t"), Some(Access::Operate));
+    assert_eq!(tokens.access("l00k"), Some(Access::Read));
+    assert_eq!(tokens.access("r00t"), Some(Access::Admin));
+    assert_eq!(tokens.access("s3cr3"), None);
+    assert_eq!(tokens.access(""), None);
+}
+
+#[test]
+fn tokens_malformed() {
+    assert_matches!(
+        Tokens::parse("admin\n"),
+        Err(error::Tokens::Malformed { line: 1 })
+    );
+    assert_matches!(
+        Tokens::parse("\nroot token\n"),
+        Err(error::Tokens::Access { line: 2, .. })
+    );
+}
+
+#[test]
+fn access_levels() {
+    let tasks = RequestPayload::from(replication::tasks::Request);
+    let cancel = RequestPayload::from(replication::cancel::Request { task: 0.into() });
+
+    assert!(Access::Read.permits(&tasks));
+    assert!(!Access::Read.permits(&cancel));
+    assert!(!Access::Operate.permits(&cancel));
+    assert!(Access::Admin.permits(&cancel));
+}
+
+#[test]
+fn handshake() {
+    let tokens = Tokens::parse(TOKENS).unwrap();
+    tokio::runtime::Runtime::new().unwrap().block_on(async {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let (granted, accepted) = futures::join!(
+            remote::login(&mut client, Some("l00k".to_owned())),
+            remote::authenticate(&mut server, |token| token.and_then(|t| tokens.access(t)))
+        );
+        assert_matches!(granted, Ok(Access::Read));
+        assert_matches!(accepted, Ok(Access::Read));
+
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let (granted, accepted) = futures::join!(
+            remote::login(&mut client, None),
+            remote::authenticate(&mut server, |token| token.and_then(|t| tokens.access(t)))
+        );
+        assert_matches!(granted, Err(error::Authenticate::Denied));
+        assert_matches!(accepted, Err(error::Authenticate::Denied));
+    })
+}
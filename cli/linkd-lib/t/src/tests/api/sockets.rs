@@ -0,0 +1,81 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::sync::Arc;
+
+use futures::StreamExt as _;
+use librad::{profile::Profile, PeerId, SecretKey};
+#[cfg(unix)]
+use link_async::incoming::UnixListenerExt as _;
+use linkd_lib::api::{
+    client::{Command, Connection},
+    io::Transport as _,
+    messages::RequestPayload,
+    replication,
+    sockets::{self, Sockets},
+};
+
+#[test]
+fn rpc_socket_round_trip() {
+    let tmp = tempfile::tempdir().unwrap();
+    let profile = Profile::from_root(tmp.path(), None).unwrap();
+    let peer_id = PeerId::from(SecretKey::new());
+
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(async move {
+            let spawner = Arc::new(link_async::Spawner::from_current().unwrap());
+            let sockets = Sockets::load(spawner, &profile, peer_id).await.unwrap();
+
+            let server = async {
+                let stream = sockets.rpc().incoming().next().await.unwrap().unwrap();
+                let mut transport = sockets::Transport::from(stream);
+                transport.recv_request().await.unwrap().unwrap()
+            };
+            let client = async {
+                let mut conn = Connection::connect("test", profile.paths().rpc_socket(&peer_id))
+                    .await
+                    .unwrap();
+                Command::replication_tasks()
+                    .execute(&mut conn)
+                    .await
+                    .unwrap();
+            };
+            let (request, ()) = futures::join!(server, client);
+            assert_eq!(
+                request.payload,
+                RequestPayload::from(replication::tasks::Request)
+            );
+
+            sockets.cleanup().unwrap();
+        })
+}
+
+#[test]
+fn sockets_lifecycle() {
+    let tmp = tempfile::tempdir().unwrap();
+    let profile = Profile::from_root(tmp.path(), None).unwrap();
+    let peer_id = PeerId::from(SecretKey::new());
+
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(async move {
+            let spawner = Arc::new(link_async::Spawner::from_current().unwrap());
+            let sockets = Sockets::load(spawner.clone(), &profile, peer_id)
+                .await
+                .unwrap();
+
+            // Another node can't take over the sockets while this one is running
+            assert!(Sockets::load(spawner.clone(), &profile, peer_id)
+                .await
+                .is_err());
+
+            // ..but can once it shut down
+            sockets.cleanup().unwrap();
+            drop(sockets);
+            let sockets = Sockets::load(spawner, &profile, peer_id).await.unwrap();
+            sockets.cleanup().unwrap();
+        })
+}
@@ -5,6 +5,7 @@
 
 use std::{
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    num::{NonZeroU32, NonZeroU64},
     path::PathBuf,
     str::FromStr,
 };
@@ -13,19 +14,23 @@ use anyhow::Result;
 use clap::Parser as _;
 
 use librad::{
-    net::Network,
+    net::{dial, Network},
     profile::{LnkHome, ProfileId},
 };
 
 use linkd_lib::args::{
     self,
+    AntiEntropyArgs,
     Args,
     KeyArgs,
     MetricsArgs,
     MetricsProvider,
     ProtocolArgs,
     ProtocolListen,
+    ReplicationArgs,
     Signer,
+    StandbyArgs,
+    TracingArgs,
     TrackingArgs,
     TrackingMode,
 };
@@ -92,6 +97,33 @@ fn bootstraps() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn pinned() -> Result<()> {
+    let pinned = vec![Seed {
+        addrs: "sprout.radicle.xyz:12345".to_string(),
+        peer: "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc".parse()?,
+        label: Some("sprout".to_string()),
+    }];
+
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--pin", "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc@sprout.radicle.xyz:12345,sprout",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            pinned,
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
 #[test]
 fn metrics_graphite() -> Result<()> {
     #[rustfmt::skip]
@@ -189,6 +221,180 @@ fn protocol_network() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn protocol_gossip_batch() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--protocol-gossip-batch",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                gossip_batch: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn protocol_gossip_compression() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--protocol-gossip-compression",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                gossip_compression: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn protocol_gossip_interest() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--protocol-gossip-interest",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                gossip_interest: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn protocol_gossip_backoff() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--protocol-gossip-backoff",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                gossip_backoff: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn protocol_private() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--protocol-private",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                private: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn protocol_bulk_bandwidth() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--protocol-bulk-bandwidth", "1048576",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                bulk_bandwidth: NonZeroU32::new(1048576),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn protocol_dial() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--protocol-address-family", "prefer-ipv6",
+            "--protocol-dns-timeout", "3",
+            "--protocol-resolver", "system",
+            "--protocol-usage-retention-days", "30",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                address_family: dial::Family::PreferV6,
+                dns_timeout: Some(3),
+                resolver: dial::Resolver::System,
+                usage_retention_days: Some(30),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
 #[test]
 fn lnk_home() -> Result<()> {
     #[rustfmt::skip]
@@ -355,6 +561,27 @@ fn signer_ssh_agent() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn signer_keychain() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--signer", "keychain",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            signer: Signer::Keychain,
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
 #[test]
 fn tmp_root() -> Result<()> {
     #[rustfmt::skip]
@@ -376,6 +603,27 @@ fn tmp_root() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn doctor() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--doctor",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            doctor: true,
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
 #[test]
 fn tracking() -> Result<()> {
     #[rustfmt::skip]
@@ -419,3 +667,116 @@ fn tracking() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn tracing() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--tracing-sample-gossip", "100",
+            "--otlp-endpoint", "http://localhost:4317",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            tracing: TracingArgs {
+                sample_gossip: NonZeroU64::new(100).unwrap(),
+                otlp_endpoint: Some("http://localhost:4317".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn anti_entropy() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--anti-entropy-interval", "600",
+            "--anti-entropy-max-fetches", "2",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            anti_entropy: AntiEntropyArgs {
+                interval_secs: Some(600),
+                max_fetches: 2,
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn replication() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--replication-workers", "8",
+            "--replication-workers-per-urn", "1",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            replication: ReplicationArgs {
+                workers: 8,
+                workers_per_urn: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn standby() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--standby-of", "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc@seed.radicle.xyz:12345",
+            "--standby-control", "seed.radicle.xyz:8777",
+            "--standby-control-ca", "/etc/linkd/ca.pem",
+            "--standby-control-token", "/etc/linkd/token",
+            "--standby-interval", "30",
+    ];
+    let parsed = Args::try_parse_from(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            standby: StandbyArgs {
+                primary: Some(Seed {
+                    addrs: "seed.radicle.xyz:12345".to_string(),
+                    peer: "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc".parse()?,
+                    label: None,
+                }),
+                control: Some("seed.radicle.xyz:8777".to_string()),
+                control_ca: Some(PathBuf::from("/etc/linkd/ca.pem")),
+                control_token: Some(PathBuf::from("/etc/linkd/token")),
+                interval_secs: 30,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
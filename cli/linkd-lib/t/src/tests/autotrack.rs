@@ -0,0 +1,39 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use pretty_assertions::assert_eq;
+use serde_json::json;
+
+use librad::PeerId;
+use linkd_lib::autotrack::authors;
+
+const AUTHOR: &str = "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc";
+const COMMENTER: &str = "hybz9gfgtd9d4pd14a6r66j5hz6f77fed4jdu7pana4fxaxbt369kg";
+
+#[test]
+fn authors_of_object_and_comments() {
+    let doc = json!({
+        "title": "Too many muppets",
+        "author": AUTHOR,
+        "comments": [
+            { "author": COMMENTER, "body": "Agreed" },
+            { "author": AUTHOR, "body": "Thanks" },
+            { "author": "kermit", "body": "Not a peer" },
+        ],
+    });
+    assert_eq!(
+        authors(&doc),
+        [AUTHOR, COMMENTER]
+            .iter()
+            .map(|peer| peer.parse::<PeerId>().unwrap())
+            .collect()
+    );
+}
+
+#[test]
+fn authors_ignores_missing_and_malformed() {
+    assert!(authors(&json!({ "title": "Anonymous" })).is_empty());
+    assert!(authors(&json!({ "author": 42, "comments": "none" })).is_empty());
+}
@@ -0,0 +1,24 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use pretty_assertions::assert_eq;
+
+use linkd_lib::http::Page;
+
+#[test]
+fn pages() {
+    let cursor = |i: &i32| i.to_string();
+    assert_eq!(
+        Page::of(3..7, 3, cursor),
+        Page {
+            per_page: 3,
+            items: vec![3, 4, 5],
+            next: Some("5".to_owned()),
+        }
+    );
+    assert_eq!(Page::of(6..7, 3, cursor).next, None);
+    assert_eq!(Page::of(3..6, 3, cursor).next, None);
+    assert_eq!(Page::of(0..0, 3, cursor).items, Vec::<i32>::new());
+}
@@ -0,0 +1,189 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use assert_matches::assert_matches;
+use it_helpers::{fixed::TestProject, tmp};
+use pretty_assertions::assert_eq;
+
+use librad::{
+    git::{refs::Refs, storage::Storage, types::Namespace},
+    SecretKey,
+};
+use linkd_lib::mirror::{self, error, Branch, Config, Mirror, Policy};
+
+const URN: &str = "rad:git:hnrkb39fr6f4jj59nfiq7tfd9aznirdu7b59o";
+const PEER: &str = "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc";
+
+#[test]
+fn parse() {
+    let interval = Duration::from_secs(60);
+    let config = Config::parse(
+        &format!(
+            "# mirrors\n\
+             {urn} git@github.com:radicle/link.git\n\
+             \n\
+             {urn} https://gitlab.com/radicle/link.git peer={peer} branch=master:main branch=release/* policy=force\n\
+             {urn} ssh://example.com/link.git pull\n",
+            urn = URN,
+            peer = PEER
+        ),
+        interval,
+    )
+    .unwrap();
+
+    assert_eq!(
+        config,
+        Config {
+            mirrors: vec![
+                Mirror {
+                    urn: URN.parse().unwrap(),
+                    url: "git@github.com:radicle/link.git".to_owned(),
+                    peer: None,
+                    branches: vec![],
+                    policy: Policy::FastForward,
+                    pull: false,
+                },
+                Mirror {
+                    urn: URN.parse().unwrap(),
+                    url: "https://gitlab.com/radicle/link.git".to_owned(),
+                    peer: Some(PEER.parse().unwrap()),
+                    branches: vec![
+                        Branch {
+                            src: "master".to_owned(),
+                            dst: "main".to_owned(),
+                        },
+                        Branch {
+                            src: "release/*".to_owned(),
+                            dst: "release/*".to_owned(),
+                        },
+                    ],
+                    policy: Policy::Force,
+                    pull: false,
+                },
+                Mirror {
+                    urn: URN.parse().unwrap(),
+                    url: "ssh://example.com/link.git".to_owned(),
+                    peer: None,
+                    branches: vec![],
+                    policy: Policy::FastForward,
+                    pull: true,
+                },
+            ],
+            interval,
+        }
+    );
+}
+
+#[test]
+fn parse_errors() {
+    let interval = Duration::from_secs(60);
+    assert_matches!(
+        Config::parse(URN, interval),
+        Err(error::Config::Malformed { line: 1 })
+    );
+    assert_matches!(
+        Config::parse(
+            &format!("\n{} https://example.com policy=merge", URN),
+            interval
+        ),
+        Err(error::Config::Option { line: 2, .. })
+    );
+    assert_matches!(
+        Config::parse(
+            &format!("{} https://example.com peer={} pull", URN, PEER),
+            interval
+        ),
+        Err(error::Config::Option { line: 1, .. })
+    );
+}
+
+fn commit(repo: &git2::Repository, refname: &str, parent: Option<git2::Oid>) -> git2::Oid {
+    let tree = {
+        let oid = repo.treebuilder(None).unwrap().write().unwrap();
+        repo.find_tree(oid).unwrap()
+    };
+    let parents = parent
+        .map(|oid| repo.find_commit(oid).unwrap())
+        .into_iter()
+        .collect::<Vec<_>>();
+    let author = git2::Signature::now("The Animal", "animal@muppets.com").unwrap();
+    repo.commit(
+        Some(refname),
+        &author,
+        &author,
+        &format!("commit on {:?}", parent),
+        &tree,
+        &parents.iter().collect::<Vec<_>>(),
+    )
+    .unwrap()
+}
+
+fn signed_head(storage: &Storage, mirror: &Mirror) -> Option<git2::Oid> {
+    let refs = Refs::load(storage, &mirror.urn, None).unwrap().unwrap();
+    refs.heads()
+        .find(|(name, _)| name.as_str() == "main")
+        .map(|(_, oid)| oid.into())
+}
+
+#[test]
+fn push_and_pull() {
+    let paths = tmp::paths();
+    let storage = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+    let monorepo = git2::Repository::open(storage.path()).unwrap();
+    let remote_dir = tempfile::tempdir().unwrap();
+    let remote = git2::Repository::init_bare(remote_dir.path()).unwrap();
+
+    let mirror = Mirror {
+        urn: project.urn(),
+        url: remote_dir.path().display().to_string(),
+        peer: None,
+        branches: vec![],
+        policy: Policy::FastForward,
+        pull: true,
+    };
+    let local_main = format!(
+        "refs/namespaces/{}/refs/heads/main",
+        Namespace::from(&mirror.urn)
+    );
+    let tip = |repo: &git2::Repository, name: &str| repo.refname_to_id(name).unwrap();
+
+    let rt = tokio::runtime::Runtime::new().unwrap();
+
+    // Local branches are pushed to the remote
+    let c1 = commit(&monorepo, &local_main, None);
+    Refs::update(&storage, &mirror.urn).unwrap();
+    let diverged = rt.block_on(mirror::push(storage.path(), &mirror)).unwrap();
+    assert!(diverged.is_empty());
+    assert_eq!(tip(&remote, "refs/heads/main"), c1);
+
+    // Fast-forwards on the remote are pulled and signed
+    let c2 = commit(&remote, "refs/heads/main", Some(c1));
+    assert_eq!(mirror::pull(&storage, &mirror).unwrap(), vec!["main"]);
+    assert_eq!(tip(&monorepo, &local_main), c2);
+    assert_eq!(signed_head(&storage, &mirror), Some(c2));
+    let scratch = monorepo.references_glob("refs/mirrors/*").unwrap();
+    assert_eq!(scratch.count(), 0);
+
+    // Diverged branches are left alone on either side
+    let c3 = commit(&monorepo, &local_main, Some(c2));
+    let c4 = commit(&remote, "refs/heads/main", Some(c2));
+    let diverged = rt.block_on(mirror::push(storage.path(), &mirror)).unwrap();
+    assert_eq!(diverged, vec!["refs/heads/main"]);
+    assert_eq!(tip(&remote, "refs/heads/main"), c4);
+    assert!(mirror::pull(&storage, &mirror).unwrap().is_empty());
+    assert_eq!(tip(&monorepo, &local_main), c3);
+
+    // Unless the policy is to force them
+    let force = Mirror {
+        policy: Policy::Force,
+        ..mirror.clone()
+    };
+    let diverged = rt.block_on(mirror::push(storage.path(), &force)).unwrap();
+    assert!(diverged.is_empty());
+    assert_eq!(tip(&remote, "refs/heads/main"), c3);
+}
@@ -0,0 +1,108 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    str::FromStr as _,
+    time::{Duration, Instant},
+};
+
+use pretty_assertions::assert_eq;
+use serde_json::json;
+
+use librad::collaborative_objects::{ObjectId, TypeName};
+use linkd_lib::notify::{changes, message, Kind, Notification, RateLimit, Smtp, Template};
+
+const URN: &str = "rad:git:hnrkb39fr6f4jj59nfiq7tfd9aznirdu7b59o";
+const OID: &str = "b88da9d7dd9b7c6bb4cbcb2a0d1f31cd7ad2f0e4";
+
+#[test]
+fn template() {
+    assert_eq!(Template::parse("Dear {handle},\n"), None);
+
+    let template =
+        Template::parse("Subject: [{typename}] {title}\n{handle}: see {id} in {urn}\n").unwrap();
+    let n = Notification {
+        kind: Kind::Mention("kermit".to_owned()),
+        urn: URN.parse().unwrap(),
+        typename: TypeName::from_str("xyz.radicle.issue").unwrap(),
+        id: ObjectId::from_str(OID).unwrap(),
+        title: "Too many muppets".to_owned(),
+    };
+    assert_eq!(
+        template.render(&n),
+        (
+            "[xyz.radicle.issue] Too many muppets".to_owned(),
+            format!("kermit: see {} in {}\n", OID, URN)
+        )
+    );
+}
+
+#[test]
+fn no_header_injection() {
+    let smtp = Smtp {
+        addr: "localhost:25".to_owned(),
+        server_name: "localhost".to_owned(),
+        tls: None,
+        credentials: None,
+        from: "linkd@example.com".to_owned(),
+        to: vec!["maintainers@example.com".to_owned()],
+    };
+    let message = message(&smtp, "[issue] Hi\r\nBcc: eve@example.com", "body\n");
+    let (headers, _) = message.split_once("\r\n\r\n").unwrap();
+    assert!(headers.lines().all(|header| !header.starts_with("Bcc:")));
+    assert!(headers.contains("Subject: [issue] Hi  Bcc: eve@example.com\r\n"));
+}
+
+#[test]
+fn changes_of_objects() {
+    let handles = vec!["kermit".to_owned(), "gonzo".to_owned()];
+    let opened = json!({ "title": "Too many muppets", "description": "cc @kermit-the-frog" });
+    assert_eq!(changes(None, &opened, &handles), vec![Kind::Opened]);
+    assert_eq!(changes(Some(&opened), &opened, &handles), vec![]);
+
+    let mentioned = json!({
+        "title": "Too many muppets",
+        "description": "cc @kermit-the-frog",
+        "comments": [{ "body": "@kermit, thoughts?" }],
+    });
+    assert_eq!(
+        changes(Some(&opened), &mentioned, &handles),
+        vec![Kind::Mention("kermit".to_owned())]
+    );
+
+    let reviewed = json!({
+        "title": "Too many muppets",
+        "description": "cc @kermit-the-frog",
+        "comments": [{ "body": "@kermit, thoughts?" }, { "body": "lgtm" }],
+        "reviewers": ["@gonzo", "fozzie"],
+    });
+    assert_eq!(
+        changes(Some(&mentioned), &reviewed, &handles),
+        vec![Kind::ReviewRequest("gonzo".to_owned())]
+    );
+
+    let commented = json!({
+        "title": "Too many muppets",
+        "description": "cc @kermit-the-frog",
+        "comments": [{ "body": "@kermit, thoughts?" }, { "body": "lgtm" }, { "body": "@kermit!" }],
+        "reviewers": ["@gonzo", "fozzie"],
+    });
+    assert_eq!(
+        changes(Some(&reviewed), &commented, &handles),
+        vec![Kind::Updated]
+    );
+}
+
+#[test]
+fn rate_limit() {
+    let mut limit = RateLimit::new(2, Duration::from_secs(60));
+    let start = Instant::now();
+    assert!(limit.allow(start));
+    assert!(limit.allow(start + Duration::from_secs(10)));
+    assert!(!limit.allow(start + Duration::from_secs(20)));
+    assert!(limit.allow(start + Duration::from_secs(60)));
+    assert!(!limit.allow(start + Duration::from_secs(69)));
+    assert!(limit.allow(start + Duration::from_secs(70)));
+}
@@ -0,0 +1,85 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::git::Urn;
+use linkd_lib::replication::{Scheduler, TaskId};
+
+fn urn(s: &str) -> Urn {
+    s.parse().unwrap()
+}
+
+fn urns() -> (Urn, Urn) {
+    (
+        urn("rad:git:hnrkb39fr6f4jj59nfiq7tfd9aznirdu7b59o"),
+        urn("rad:git:hnrkmx6trm4bu19bwa4apbxj8ftw8f7amfdyy"),
+    )
+}
+
+#[test]
+fn takes_turns_between_urns() {
+    let (a, b) = urns();
+    let mut sched = Scheduler::new(4, 4);
+    for id in 0..3 {
+        sched.enqueue(TaskId::from(id), &a);
+    }
+    sched.enqueue(TaskId::from(3), &b);
+    sched.enqueue(TaskId::from(4), &b);
+
+    let order = std::iter::from_fn(|| sched.next().map(|(id, _)| id)).collect::<Vec<_>>();
+    assert_eq!(
+        order,
+        vec![0, 3, 1, 4]
+            .into_iter()
+            .map(TaskId::from)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(sched.running(), 4);
+}
+
+#[test]
+fn limits_jobs_per_urn() {
+    let (a, b) = urns();
+    let mut sched = Scheduler::new(4, 1);
+    sched.enqueue(TaskId::from(0), &a);
+    sched.enqueue(TaskId::from(1), &a);
+
+    assert_eq!(sched.next(), Some((TaskId::from(0), a.clone())));
+    assert!(sched.is_throttled(&a));
+    assert_eq!(sched.next(), None);
+
+    sched.enqueue(TaskId::from(2), &b);
+    assert_eq!(sched.next(), Some((TaskId::from(2), b.clone())));
+
+    sched.finish(&a);
+    assert!(!sched.is_throttled(&a));
+    assert_eq!(sched.next(), Some((TaskId::from(1), a)));
+}
+
+#[test]
+fn limits_workers() {
+    let (a, b) = urns();
+    let mut sched = Scheduler::new(1, 2);
+    sched.enqueue(TaskId::from(0), &a);
+    sched.enqueue(TaskId::from(1), &b);
+
+    assert_eq!(sched.next(), Some((TaskId::from(0), a.clone())));
+    assert_eq!(sched.next(), None);
+
+    sched.finish(&a);
+    assert_eq!(sched.next(), Some((TaskId::from(1), b)));
+}
+
+#[test]
+fn dequeued_jobs_are_not_run() {
+    let (a, _) = urns();
+    let mut sched = Scheduler::new(2, 2);
+    sched.enqueue(TaskId::from(0), &a);
+    sched.enqueue(TaskId::from(1), &a);
+    sched.dequeue(TaskId::from(0), &a);
+
+    assert_eq!(sched.next(), Some((TaskId::from(1), a.clone())));
+    sched.dequeue(TaskId::from(1), &a);
+    assert_eq!(sched.next(), None);
+}
@@ -10,6 +10,7 @@ use librad::{
     SecretKey,
 };
 
+pub mod keychain;
 pub mod prompt;
 pub mod ssh;
 
@@ -0,0 +1,221 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Storing the passphrase of the key storage in a keychain.
+//!
+//! Desktop users typically don't want to enter their passphrase every time the
+//! key is needed, nor store it in a plaintext file. [`signer`] looks up the
+//! passphrase in a [`Keychain`] first, and only prompts for it if it is not
+//! found (or wrong). Once the key was unlocked, the passphrase is stored for
+//! the next time.
+//!
+//! With the `keychain` feature, [`os::Os`] uses the keychain of the operating
+//! system, ie. the macOS Keychain, the Windows Credential Manager, or the
+//! Secret Service on Linux. Other backends can be plugged in by implementing
+//! [`Keychain`].
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+
+use librad::{
+    crypto::{
+        keystore::{
+            crypto::{KdfParams, Pwhash, SecretBoxError},
+            file,
+            pinentry::{Pinentry, SecUtf8},
+            Keystore as _,
+        },
+        BoxedSigner,
+    },
+    profile::Profile,
+    SecretKey,
+};
+
+/// The service name under which passphrases are stored.
+pub const SERVICE: &str = "radicle-link";
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("keychain error")]
+    Keychain(#[source] BoxError),
+
+    #[error("failed to obtain passphrase")]
+    Pinentry(#[source] BoxError),
+
+    #[error("failed to unlock key storage")]
+    Unlock(#[source] BoxError),
+}
+
+/// A store of secrets, indexed by account name.
+pub trait Keychain {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// Get the passphrase stored for `account`, if any.
+    fn get(&self, account: &str) -> Result<Option<SecUtf8>, Self::Error>;
+
+    /// Store `passphrase` for `account`, replacing any previous one.
+    fn set(&self, account: &str, passphrase: &SecUtf8) -> Result<(), Self::Error>;
+
+    /// Remove the passphrase for `account`. Not an error if there is none.
+    fn delete(&self, account: &str) -> Result<(), Self::Error>;
+}
+
+/// The account name for the passphrase of `profile`.
+pub fn account(profile: &Profile) -> String {
+    format!("profile-{}", profile.id())
+}
+
+/// Get the signer from the file store, decrypting the secret key with the
+/// passphrase from `keychain`.
+///
+/// See [`secret_key`].
+pub fn signer<K, P>(
+    profile: &Profile,
+    keychain: &K,
+    fallback: P,
+    params: KdfParams,
+) -> Result<BoxedSigner, Error>
+where
+    K: Keychain,
+    P: Pinentry,
+    P::Error: std::error::Error + Send + Sync + 'static,
+{
+    secret_key(profile, keychain, fallback, params).map(BoxedSigner::from)
+}
+
+/// Get the secret key from the file store, decrypting it with the passphrase
+/// from `keychain`.
+///
+/// If the keychain does not hold the passphrase, or the passphrase is wrong,
+/// it is obtained from `fallback` (eg. a [`super::prompt`]) instead. If it
+/// unlocks the key, it is stored in `keychain`. A wrong passphrase is removed
+/// from `keychain`, while any other error unlocking the key storage with it is
+/// returned as [`Error::Unlock`], leaving the keychain untouched.
+pub fn secret_key<K, P>(
+    profile: &Profile,
+    keychain: &K,
+    fallback: P,
+    params: KdfParams,
+) -> Result<SecretKey, Error>
+where
+    K: Keychain,
+    P: Pinentry,
+    P::Error: std::error::Error + Send + Sync + 'static,
+{
+    let account = account(profile);
+    let unlock = |pass: SecUtf8| {
+        super::file_storage(profile, Pwhash::new(pass, params))
+            .get_key()
+            .map(|pair| pair.secret_key)
+    };
+
+    if let Some(pass) = keychain
+        .get(&account)
+        .map_err(|e| Error::Keychain(Box::new(e)))?
+    {
+        match unlock(pass) {
+            Ok(key) => return Ok(key),
+            // Only a passphrase which is definitely wrong is removed, the
+            // entry may still be good if the key storage is not readable
+            // right now.
+            Err(file::Error::Crypto(SecretBoxError::InvalidKey)) => {
+                tracing::warn!("passphrase from keychain did not unlock the key");
+                keychain
+                    .delete(&account)
+                    .map_err(|e| Error::Keychain(Box::new(e)))?;
+            },
+            Err(e) => return Err(Error::Unlock(Box::new(e))),
+        }
+    }
+
+    let pass = fallback
+        .get_passphrase()
+        .map_err(|e| Error::Pinentry(Box::new(e)))?;
+    let key = unlock(pass.clone()).map_err(|e| Error::Unlock(Box::new(e)))?;
+    if let Err(e) = keychain.set(&account, &pass) {
+        tracing::warn!(err = %e, "failed to store passphrase in keychain");
+    }
+
+    Ok(key)
+}
+
+/// A [`Keychain`] which forgets everything when dropped, eg. for testing.
+#[derive(Clone, Default)]
+pub struct Memory(Arc<Mutex<HashMap<String, SecUtf8>>>);
+
+impl Keychain for Memory {
+    type Error = std::convert::Infallible;
+
+    fn get(&self, account: &str) -> Result<Option<SecUtf8>, Self::Error> {
+        Ok(self.0.lock().unwrap().get(account).cloned())
+    }
+
+    fn set(&self, account: &str, passphrase: &SecUtf8) -> Result<(), Self::Error> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(account.to_owned(), passphrase.clone());
+        Ok(())
+    }
+
+    fn delete(&self, account: &str) -> Result<(), Self::Error> {
+        self.0.lock().unwrap().remove(account);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "keychain")]
+pub mod os {
+    use librad::crypto::keystore::pinentry::Prompt;
+
+    use super::*;
+
+    /// The keychain of the operating system.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct Os;
+
+    /// [`super::signer`], using the [`Os`] keychain and prompting on the
+    /// terminal if it does not hold the passphrase.
+    pub fn signer(profile: &Profile) -> Result<BoxedSigner, Error> {
+        secret_key(profile).map(BoxedSigner::from)
+    }
+
+    /// [`super::secret_key`], using the [`Os`] keychain and prompting on the
+    /// terminal if it does not hold the passphrase.
+    pub fn secret_key(profile: &Profile) -> Result<SecretKey, Error> {
+        let prompt = Prompt::new("please enter your passphrase: ");
+        super::secret_key(profile, &Os, prompt, KdfParams::recommended())
+    }
+
+    impl Keychain for Os {
+        type Error = keyring::Error;
+
+        fn get(&self, account: &str) -> Result<Option<SecUtf8>, Self::Error> {
+            match keyring::Entry::new(SERVICE, account).get_password() {
+                Ok(pass) => Ok(Some(SecUtf8::from(pass))),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+
+        fn set(&self, account: &str, passphrase: &SecUtf8) -> Result<(), Self::Error> {
+            keyring::Entry::new(SERVICE, account).set_password(passphrase.unsecure())
+        }
+
+        fn delete(&self, account: &str) -> Result<(), Self::Error> {
+            match keyring::Entry::new(SERVICE, account).delete_password() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(e),
+            }
+        }
+    }
+}
@@ -22,8 +22,10 @@ use librad::{
         Signer as _,
         SomeSigner,
     },
-    git::storage::ReadOnly,
+    git::storage::{read, ReadOnly},
     profile::Profile,
+    PeerId,
+    SecretKey,
     Signature,
 };
 
@@ -35,8 +37,7 @@ use super::{with_socket, SshAuthSock};
 /// See [`SshAuthSock`] for how the `ssh-agent` will be connected to. Use
 /// `SshAuthSock::default` to connect via `SSH_AUTH_SOCK`.
 pub fn signer(profile: &Profile, sock: SshAuthSock) -> Result<BoxedSigner, super::Error> {
-    let storage = ReadOnly::open(profile.paths())?;
-    let peer_id = storage.peer_id();
+    let peer_id = peer_id(profile)?;
     let pk = (*peer_id.as_public_key()).into();
     let agent = with_socket(SshAgent::new(pk), sock);
     tracing::trace!(peer=%peer_id, "obtaining signer for peer");
@@ -48,7 +49,7 @@ pub fn signer(profile: &Profile, sock: SshAuthSock) -> Result<BoxedSigner, super
         }
         .into())
     } else {
-        Err(super::Error::NoSuchKey(*peer_id))
+        Err(super::Error::NoSuchKey(peer_id))
     }
 }
 
@@ -73,12 +74,21 @@ where
     let key = store
         .get_key()
         .map_err(|err| super::Error::GetKey(err.into()))?;
-    let agent = with_socket(SshAgent::new(key.public_key.into()), sock);
-    runtime::block_on(ssh::add_key::<UnixStream>(
-        &agent,
-        key.secret_key.into(),
-        constraints,
-    ))?;
+    add_key(sock, key.secret_key, constraints)
+}
+
+/// Add the already unlocked signing `key` to the `ssh-agent`, eg. one
+/// obtained via [`keys::keychain::secret_key`].
+///
+/// See [`SshAuthSock`] for how the agent will be connected to. Use
+/// `SshAuthSock::default` to connect via `SSH_AUTH_SOCK`.
+pub fn add_key(
+    sock: SshAuthSock,
+    key: SecretKey,
+    constraints: &[Constraint],
+) -> Result<(), super::Error> {
+    let agent = with_socket(SshAgent::new(key.public().into()), sock);
+    runtime::block_on(ssh::add_key::<UnixStream>(&agent, key.into(), constraints))?;
     Ok(())
 }
 
@@ -111,8 +121,7 @@ where
 /// See [`SshAuthSock`] for how the agent will be connected to. Use
 /// `SshAuthSock::default` to connect via `SSH_AUTH_SOCK`.
 pub fn is_signer_present(profile: &Profile, sock: SshAuthSock) -> Result<bool, super::Error> {
-    let storage = ReadOnly::open(profile.paths())?;
-    let peer_id = storage.peer_id();
+    let peer_id = peer_id(profile)?;
     let pk = (*peer_id.as_public_key()).into();
     let agent = with_socket(SshAgent::new(pk), sock);
     let keys = runtime::block_on(ssh::list_keys::<UnixStream>(&agent))?;
@@ -145,3 +154,19 @@ pub fn verify(
     let pk = peer_id.as_public_key();
     Ok(pk.verify(signature, payload))
 }
+
+/// The [`PeerId`] of this `profile`.
+///
+/// The storage can't be read before it is unlocked, which in turn requires
+/// the signer, so fall back to the public key in the keystore if it is
+/// locked.
+fn peer_id(profile: &Profile) -> Result<PeerId, super::Error> {
+    match ReadOnly::open(profile.paths()) {
+        Ok(storage) => Ok(*storage.peer_id()),
+        Err(read::error::Init::Locked) => keys::file_storage(profile, keys::prompt::new())
+            .show_key()
+            .map(PeerId::from)
+            .map_err(|err| super::Error::GetKey(err.into())),
+        Err(err) => Err(err.into()),
+    }
+}
@@ -11,6 +11,7 @@ use thrussh_agent::Constraint;
 use librad::{
     crypto::{keystore::crypto::Crypto, BoxedSigner},
     profile::Profile,
+    SecretKey,
 };
 
 /// Get the signing key associated with this `profile`.
@@ -41,6 +42,18 @@ where
     unimplemented!("Windows is not supported, contributions are welcome :)")
 }
 
+/// Add the already unlocked signing `key` to the `ssh-agent`.
+///
+/// See [`SshAuthSock`] for how the agent will be connected to. Use
+/// `SshAuthSock::default` to connect via `SSH_AUTH_SOCK`.
+pub fn add_key(
+    _sock: SshAuthSock,
+    _key: SecretKey,
+    _constraints: &[Constraint],
+) -> Result<(), super::Error> {
+    unimplemented!("Windows is not supported, contributions are welcome :)")
+}
+
 /// Remove the signing key associated with this `profile` from the `ssh-agent`.
 ///
 /// See [`SshAuthSock`] for how the agent will be connected to. Use
@@ -4,6 +4,7 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 pub mod keys;
+pub mod rpc;
 pub mod runtime;
 pub mod seed;
 pub mod ser;
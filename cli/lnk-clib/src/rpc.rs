@@ -0,0 +1,24 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! The RPC API of `linkd`, as used by clients: the request and response
+//! types, their wire encoding, and the [`client`] to issue requests with.
+//!
+//! The node side of the API lives in `linkd-lib`, which re-exports these
+//! modules.
+
+pub mod announce;
+pub mod client;
+pub mod connections;
+pub mod io;
+pub mod messages;
+pub mod pinned;
+pub mod project_stats;
+pub mod remote;
+pub mod replication;
+pub mod request_pull;
+pub mod standby;
+pub mod usage;
+pub mod wire_types;
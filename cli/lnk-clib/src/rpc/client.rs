@@ -6,7 +6,8 @@
 //! This module is the client interface to the p2p node RPC API. The APIs here
 //! are designed to work in both an asynchronous and synchronous context. To
 //! start you'll need to create a [`Connection`] by calling either
-//! [`Connection::connect`]. Once you have a connection you then create a
+//! [`Connection::connect`] or, for a node controlled remotely,
+//! [`Connection::connect_remote`]. Once you have a connection you then create a
 //! command using the `commands::*` functions. A command then has various
 //! methods on it which determine exactly how the command should be executed.
 //!
@@ -16,11 +17,22 @@ use std::{marker::PhantomData, net::SocketAddr};
 
 use git_ext::Oid;
 use radicle_git_ext as git_ext;
-use tokio::net::UnixStream;
 
 use librad::{git::Urn, PeerId};
 
-use super::{announce, io, messages, request_pull};
+use super::{
+    announce,
+    connections,
+    io,
+    messages,
+    pinned,
+    project_stats,
+    remote,
+    replication,
+    request_pull,
+    standby,
+    usage,
+};
 
 pub struct Connection<T> {
     socket: T,
@@ -28,10 +40,10 @@ pub struct Connection<T> {
 }
 
 impl Connection<io::SocketTransport> {
-    /// Asynchronously connect to the domain socket given by `socket_path`. The
-    /// `user_agent` will be used to identify this client in log messages so
-    /// it's best to choose something unique. This method will block until a
-    /// connection is made.
+    /// Asynchronously connect to the domain socket (or, on Windows, the named
+    /// pipe) given by `socket_path`. The `user_agent` will be used to
+    /// identify this client in log messages so it's best to choose
+    /// something unique. This method will block until a connection is made.
     ///
     /// # Panics
     ///
@@ -40,7 +52,7 @@ impl Connection<io::SocketTransport> {
         user_agent: U,
         socket_path: P,
     ) -> Result<Self, std::io::Error> {
-        let stream = UnixStream::connect(socket_path).await?;
+        let stream = connect_local(socket_path.as_ref()).await?;
         Ok(Self {
             socket: io::SocketTransport::from(stream),
             user_agent: user_agent.to_string().into(),
@@ -48,6 +60,56 @@ impl Connection<io::SocketTransport> {
     }
 }
 
+#[cfg(unix)]
+async fn connect_local(path: &std::path::Path) -> std::io::Result<tokio::net::UnixStream> {
+    tokio::net::UnixStream::connect(path).await
+}
+
+#[cfg(windows)]
+async fn connect_local(
+    path: &std::path::Path,
+) -> std::io::Result<tokio::net::windows::named_pipe::NamedPipeClient> {
+    use std::time::Duration;
+    use tokio::net::windows::named_pipe::ClientOptions;
+
+    // Returned by `CreateFile` if all instances of the pipe are busy, which
+    // happens if another client connected after the node created the current
+    // instance, but before it created the next one.
+    const ERROR_PIPE_BUSY: i32 = 231;
+
+    loop {
+        match ClientOptions::new().open(path) {
+            Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) => {
+                link_async::sleep(Duration::from_millis(50)).await
+            },
+            res => return res,
+        }
+    }
+}
+
+impl<S> Connection<io::StreamTransport<S>>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    /// Authenticate to the remote control port of a node over `stream`, which
+    /// is typically a TLS session established by the caller. Returns the
+    /// connection along with the [`remote::Access`] granted by the node.
+    pub async fn connect_remote<U: ToString>(
+        user_agent: U,
+        mut stream: S,
+        token: Option<String>,
+    ) -> Result<(Self, remote::Access), remote::error::Authenticate> {
+        let access = remote::login(&mut stream, token).await?;
+        Ok((
+            Self {
+                socket: io::SocketTransport::from_stream(stream),
+                user_agent: user_agent.to_string().into(),
+            },
+            access,
+        ))
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ReplyError<T> {
     #[error(transparent)]
@@ -183,7 +245,7 @@ where
     ///
     /// ```no_run
     /// # async fn dothings() {
-    /// use linkd_lib::api::{io::SocketTransport, client::{Connection, Command, Reply}};
+    /// use lnk_clib::rpc::{io::SocketTransport, client::{Connection, Command, Reply}};
     ///
     /// let conn: Connection<SocketTransport> = Connection::connect("some user agent".to_string(), "<somepath>").await.unwrap();
     /// let command: Command = panic!("somehow create a command");
@@ -273,3 +335,97 @@ impl Command<request_pull::Request, request_pull::Response> {
         }
     }
 }
+
+impl Command<replication::tasks::Request, replication::tasks::Response> {
+    pub fn replication_tasks() -> Self {
+        Self {
+            payload: replication::tasks::Request,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Command<pinned::Request, pinned::Response> {
+    pub fn pinned_peers() -> Self {
+        Self {
+            payload: pinned::Request,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Command<project_stats::Request, project_stats::Response> {
+    pub fn project_stats(urn: Urn) -> Self {
+        Self {
+            payload: project_stats::Request { urn },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Command<standby::namespaces::Request, standby::namespaces::Response> {
+    pub fn standby_namespaces(updated_since: Option<u64>, after: Option<Urn>, limit: u32) -> Self {
+        Self {
+            payload: standby::namespaces::Request {
+                updated_since,
+                after,
+                limit,
+            },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Command<standby::promote::Request, standby::promote::Response> {
+    pub fn promote() -> Self {
+        Self {
+            payload: standby::promote::Request,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Command<replication::cancel::Request, replication::cancel::Response> {
+    pub fn cancel_replication(task: replication::TaskId) -> Self {
+        Self {
+            payload: replication::cancel::Request { task },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Command<replication::dry_run::Request, replication::dry_run::Response> {
+    pub fn replication_dry_run(urn: Urn, peer: PeerId, addrs: Vec<SocketAddr>) -> Self {
+        Self {
+            payload: replication::dry_run::Request { urn, peer, addrs },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Command<connections::list::Request, connections::list::Response> {
+    pub fn connections() -> Self {
+        Self {
+            payload: connections::list::Request,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Command<connections::disconnect::Request, connections::disconnect::Response> {
+    pub fn disconnect(peer: PeerId) -> Self {
+        Self {
+            payload: connections::disconnect::Request { peer },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Command<usage::Request, usage::Response> {
+    pub fn usage(from: Option<String>, to: Option<String>, format: usage::Format) -> Self {
+        Self {
+            payload: usage::Request { from, to, format },
+            _marker: PhantomData,
+        }
+    }
+}
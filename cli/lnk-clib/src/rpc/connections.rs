@@ -0,0 +1,86 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Inspect and manage the connections of the node.
+
+use std::net::SocketAddr;
+
+use librad::{net::peer::ConnectionInfo, PeerId};
+
+pub mod list {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+    pub struct Request;
+
+    #[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+    #[cbor(transparent)]
+    pub struct Response(#[n(0)] pub Vec<Connection>);
+
+    /// The connections to a single peer.
+    #[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+    pub struct Connection {
+        #[n(0)]
+        pub peer: PeerId,
+        #[n(1)]
+        pub addrs: Vec<SocketAddr>,
+        /// Seconds since the oldest connection to the peer was established.
+        #[n(2)]
+        pub age_secs: u64,
+        /// Streams currently opened by the peer, by kind.
+        #[n(3)]
+        pub streams: Vec<Streams>,
+        #[n(4)]
+        pub sent_bytes: u64,
+        #[n(5)]
+        pub recv_bytes: u64,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+    pub struct Streams {
+        /// The kind of stream, eg. `git` or `gossip`.
+        #[n(0)]
+        pub kind: String,
+        #[n(1)]
+        pub open: u64,
+    }
+
+    impl From<ConnectionInfo> for Connection {
+        fn from(info: ConnectionInfo) -> Self {
+            let mut streams = info
+                .streams
+                .open
+                .into_iter()
+                .map(|(kind, open)| Streams {
+                    kind: format!("{:?}", kind).to_lowercase(),
+                    open: open as u64,
+                })
+                .collect::<Vec<_>>();
+            streams.sort_by(|a, b| a.kind.cmp(&b.kind));
+            Self {
+                peer: info.peer,
+                addrs: info.addrs,
+                age_secs: info.age.as_secs(),
+                streams,
+                sent_bytes: info.sent_bytes,
+                recv_bytes: info.recv_bytes,
+            }
+        }
+    }
+}
+
+pub mod disconnect {
+    use super::*;
+
+    /// Close all connections to `peer`.
+    #[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+    pub struct Request {
+        #[n(0)]
+        pub peer: PeerId,
+    }
+
+    #[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+    pub struct Response;
+}
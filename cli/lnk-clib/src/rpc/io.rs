@@ -197,18 +197,68 @@ pub trait Transport {
         P: messages::RecvPayload;
 }
 
-pub struct SocketTransport {
-    reader: MessageReader<Compat<tokio::net::unix::OwnedReadHalf>>,
-    writer: MessageWriter<Compat<tokio::net::unix::OwnedWriteHalf>>,
+#[cfg(unix)]
+type LocalReadHalf = tokio::net::unix::OwnedReadHalf;
+#[cfg(unix)]
+type LocalWriteHalf = tokio::net::unix::OwnedWriteHalf;
+
+#[cfg(windows)]
+type LocalReadHalf = tokio::io::ReadHalf<tokio::net::windows::named_pipe::NamedPipeClient>;
+#[cfg(windows)]
+type LocalWriteHalf = tokio::io::WriteHalf<tokio::net::windows::named_pipe::NamedPipeClient>;
+
+/// A [`Transport`] over a byte stream, by default a client connection to the
+/// RPC socket (a Unix domain socket, or a named pipe on Windows).
+pub struct SocketTransport<R = Compat<LocalReadHalf>, W = Compat<LocalWriteHalf>> {
+    reader: MessageReader<R>,
+    writer: MessageWriter<W>,
+}
+
+/// A [`SocketTransport`] over an arbitrary stream, eg. a TLS connection.
+pub type StreamTransport<S> =
+    SocketTransport<Compat<tokio::io::ReadHalf<S>>, Compat<tokio::io::WriteHalf<S>>>;
+
+impl<R, W> SocketTransport<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader: MessageReader::new(reader),
+            writer: MessageWriter::new(writer),
+        }
+    }
 }
 
+impl<S> SocketTransport<Compat<tokio::io::ReadHalf<S>>, Compat<tokio::io::WriteHalf<S>>>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite,
+{
+    /// Create a transport over any bidirectional `stream`.
+    pub fn from_stream(stream: S) -> Self {
+        let (rx, sx) = tokio::io::split(stream);
+        Self::new(rx.compat(), sx.compat())
+    }
+}
+
+#[cfg(unix)]
 impl From<tokio::net::UnixStream> for SocketTransport {
     fn from(s: tokio::net::UnixStream) -> Self {
         let (rx, sx) = s.into_split();
-        Self {
-            reader: MessageReader::new(rx.compat()),
-            writer: MessageWriter::new(sx.compat()),
-        }
+        Self::new(rx.compat(), sx.compat())
+    }
+}
+
+#[cfg(windows)]
+impl From<tokio::net::windows::named_pipe::NamedPipeClient> for SocketTransport {
+    fn from(s: tokio::net::windows::named_pipe::NamedPipeClient) -> Self {
+        Self::from_stream(s)
+    }
+}
+
+#[cfg(windows)]
+impl From<tokio::net::windows::named_pipe::NamedPipeServer>
+    for StreamTransport<tokio::net::windows::named_pipe::NamedPipeServer>
+{
+    fn from(s: tokio::net::windows::named_pipe::NamedPipeServer) -> Self {
+        Self::from_stream(s)
     }
 }
 
@@ -220,7 +270,7 @@ pub enum SocketTransportError {
     DecodeFailed,
 }
 
-impl SocketTransport {
+impl<R, W> SocketTransport<R, W> {
     fn process_recv_response<R>(
         &mut self,
         msg_result: Result<Option<wire_types::Response>, Error>,
@@ -264,7 +314,11 @@ impl SocketTransport {
 }
 
 #[async_trait]
-impl Transport for SocketTransport {
+impl<R, W> Transport for SocketTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
     type Error = SocketTransportError;
 
     async fn send_request(&mut self, request: messages::Request) -> Result<(), Self::Error> {
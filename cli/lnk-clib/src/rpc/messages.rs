@@ -0,0 +1,302 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use rand::Rng;
+
+use super::{
+    announce,
+    connections,
+    pinned,
+    project_stats,
+    replication,
+    request_pull,
+    standby,
+    usage,
+};
+
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, minicbor::Decode, minicbor::Encode,
+)]
+#[cbor(transparent)]
+pub struct RequestId(#[n(0)] minicbor::bytes::ByteVec);
+
+impl From<RequestId> for Vec<u8> {
+    fn from(r: RequestId) -> Self {
+        r.0.to_vec()
+    }
+}
+
+impl From<Vec<u8>> for RequestId {
+    fn from(raw: Vec<u8>) -> Self {
+        Self(raw.into())
+    }
+}
+
+impl AsRef<[u8]> for RequestId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        let bytes: [u8; 16] = rng.gen();
+        RequestId(bytes.to_vec().into())
+    }
+}
+
+#[derive(Clone, Debug, minicbor::Decode, minicbor::Encode, PartialEq)]
+#[cbor(transparent)]
+pub struct UserAgent(#[n(0)] String);
+
+impl From<String> for UserAgent {
+    fn from(s: String) -> Self {
+        Self(s)
+    }
+}
+
+impl From<&str> for UserAgent {
+    fn from(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
+impl<'a> From<&'a UserAgent> for &'a str {
+    fn from(ua: &'a UserAgent) -> &'a str {
+        &ua.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RequestMode {
+    FireAndForget,
+    ReportProgress,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub user_agent: UserAgent,
+    pub mode: RequestMode,
+    pub payload: RequestPayload,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RequestPayload {
+    Announce(announce::Request),
+    RequestPull(request_pull::Request),
+    ReplicationTasks(replication::tasks::Request),
+    CancelReplication(replication::cancel::Request),
+    ReplicationDryRun(replication::dry_run::Request),
+    PinnedPeers(pinned::Request),
+    ProjectStats(project_stats::Request),
+    StandbyNamespaces(standby::namespaces::Request),
+    Promote(standby::promote::Request),
+    Connections(connections::list::Request),
+    Disconnect(connections::disconnect::Request),
+    Usage(usage::Request),
+}
+
+impl From<announce::Request> for RequestPayload {
+    fn from(x: announce::Request) -> Self {
+        Self::Announce(x)
+    }
+}
+
+impl From<request_pull::Request> for RequestPayload {
+    fn from(x: request_pull::Request) -> Self {
+        Self::RequestPull(x)
+    }
+}
+
+impl From<replication::tasks::Request> for RequestPayload {
+    fn from(x: replication::tasks::Request) -> Self {
+        Self::ReplicationTasks(x)
+    }
+}
+
+impl From<replication::cancel::Request> for RequestPayload {
+    fn from(x: replication::cancel::Request) -> Self {
+        Self::CancelReplication(x)
+    }
+}
+
+impl From<replication::dry_run::Request> for RequestPayload {
+    fn from(x: replication::dry_run::Request) -> Self {
+        Self::ReplicationDryRun(x)
+    }
+}
+
+impl From<pinned::Request> for RequestPayload {
+    fn from(x: pinned::Request) -> Self {
+        Self::PinnedPeers(x)
+    }
+}
+
+impl From<project_stats::Request> for RequestPayload {
+    fn from(x: project_stats::Request) -> Self {
+        Self::ProjectStats(x)
+    }
+}
+
+impl From<standby::namespaces::Request> for RequestPayload {
+    fn from(x: standby::namespaces::Request) -> Self {
+        Self::StandbyNamespaces(x)
+    }
+}
+
+impl From<standby::promote::Request> for RequestPayload {
+    fn from(x: standby::promote::Request) -> Self {
+        Self::Promote(x)
+    }
+}
+
+impl From<connections::list::Request> for RequestPayload {
+    fn from(x: connections::list::Request) -> Self {
+        Self::Connections(x)
+    }
+}
+
+impl From<connections::disconnect::Request> for RequestPayload {
+    fn from(x: connections::disconnect::Request) -> Self {
+        Self::Disconnect(x)
+    }
+}
+
+impl From<usage::Request> for RequestPayload {
+    fn from(x: usage::Request) -> Self {
+        Self::Usage(x)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response<P> {
+    pub request_id: RequestId,
+    pub payload: ResponsePayload<P>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResponsePayload<P> {
+    Ack,
+    Progress(String),
+    Error(String),
+    Success(P),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SomeSuccess {
+    Announce(announce::Response),
+    RequestPull(request_pull::Response),
+    ReplicationTasks(replication::tasks::Response),
+    CancelReplication(replication::cancel::Response),
+    ReplicationDryRun(replication::dry_run::Response),
+    PinnedPeers(pinned::Response),
+    ProjectStats(project_stats::Response),
+    StandbyNamespaces(standby::namespaces::Response),
+    Promote(standby::promote::Response),
+    Connections(connections::list::Response),
+    Disconnect(connections::disconnect::Response),
+    Usage(usage::Response),
+}
+
+impl From<announce::Response> for SomeSuccess {
+    fn from(x: announce::Response) -> Self {
+        Self::Announce(x)
+    }
+}
+
+impl From<request_pull::Response> for SomeSuccess {
+    fn from(x: request_pull::Response) -> Self {
+        Self::RequestPull(x)
+    }
+}
+
+impl From<replication::tasks::Response> for SomeSuccess {
+    fn from(x: replication::tasks::Response) -> Self {
+        Self::ReplicationTasks(x)
+    }
+}
+
+impl From<replication::cancel::Response> for SomeSuccess {
+    fn from(x: replication::cancel::Response) -> Self {
+        Self::CancelReplication(x)
+    }
+}
+
+impl From<replication::dry_run::Response> for SomeSuccess {
+    fn from(x: replication::dry_run::Response) -> Self {
+        Self::ReplicationDryRun(x)
+    }
+}
+
+impl From<pinned::Response> for SomeSuccess {
+    fn from(x: pinned::Response) -> Self {
+        Self::PinnedPeers(x)
+    }
+}
+
+impl From<project_stats::Response> for SomeSuccess {
+    fn from(x: project_stats::Response) -> Self {
+        Self::ProjectStats(x)
+    }
+}
+
+impl From<standby::namespaces::Response> for SomeSuccess {
+    fn from(x: standby::namespaces::Response) -> Self {
+        Self::StandbyNamespaces(x)
+    }
+}
+
+impl From<standby::promote::Response> for SomeSuccess {
+    fn from(x: standby::promote::Response) -> Self {
+        Self::Promote(x)
+    }
+}
+
+impl From<connections::list::Response> for SomeSuccess {
+    fn from(x: connections::list::Response) -> Self {
+        Self::Connections(x)
+    }
+}
+
+impl From<connections::disconnect::Response> for SomeSuccess {
+    fn from(x: connections::disconnect::Response) -> Self {
+        Self::Disconnect(x)
+    }
+}
+
+impl From<usage::Response> for SomeSuccess {
+    fn from(x: usage::Response) -> Self {
+        Self::Usage(x)
+    }
+}
+
+impl minicbor::Encode for SomeSuccess {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        match self {
+            SomeSuccess::Announce(x) => e.encode(x)?.ok(),
+            SomeSuccess::RequestPull(x) => e.encode(x)?.ok(),
+            SomeSuccess::ReplicationTasks(x) => e.encode(x)?.ok(),
+            SomeSuccess::CancelReplication(x) => e.encode(x)?.ok(),
+            SomeSuccess::ReplicationDryRun(x) => e.encode(x)?.ok(),
+            SomeSuccess::PinnedPeers(x) => e.encode(x)?.ok(),
+            SomeSuccess::ProjectStats(x) => e.encode(x)?.ok(),
+            SomeSuccess::StandbyNamespaces(x) => e.encode(x)?.ok(),
+            SomeSuccess::Promote(x) => e.encode(x)?.ok(),
+            SomeSuccess::Connections(x) => e.encode(x)?.ok(),
+            SomeSuccess::Disconnect(x) => e.encode(x)?.ok(),
+            SomeSuccess::Usage(x) => e.encode(x)?.ok(),
+        }
+    }
+}
+
+pub trait RecvPayload: for<'b> minicbor::Decode<'b> + Send + Sync + 'static {}
+impl<T> RecvPayload for T where for<'b> T: minicbor::Decode<'b> + Send + Sync + 'static {}
+
+pub trait SendPayload: minicbor::Encode + Send + Sync + 'static {}
+impl<T> SendPayload for T where T: minicbor::Encode + Send + Sync + 'static {}
@@ -0,0 +1,67 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{net::protocol::pinned, PeerId};
+
+#[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+pub struct Request;
+
+#[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+#[cbor(transparent)]
+pub struct Response(#[n(0)] pub Vec<PinnedPeer>);
+
+#[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+pub struct PinnedPeer {
+    #[n(0)]
+    pub peer: PeerId,
+    #[n(1)]
+    pub status: Status,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+pub enum Status {
+    /// The peer has not been dialled yet.
+    #[n(0)]
+    Pending,
+    /// The peer is in the active view.
+    #[n(1)]
+    Connected,
+    /// The peer could not be connected to after `attempts` tries, and will be
+    /// dialled again after `backoff_secs`.
+    #[n(2)]
+    Disconnected {
+        #[n(0)]
+        attempts: u32,
+        #[n(1)]
+        backoff_secs: u64,
+    },
+}
+
+impl From<pinned::Status> for Status {
+    fn from(s: pinned::Status) -> Self {
+        match s {
+            pinned::Status::Pending => Self::Pending,
+            pinned::Status::Connected => Self::Connected,
+            pinned::Status::Disconnected { attempts, backoff } => Self::Disconnected {
+                attempts,
+                backoff_secs: backoff.as_secs(),
+            },
+        }
+    }
+}
+
+impl From<pinned::Snapshot> for Response {
+    fn from(snapshot: pinned::Snapshot) -> Self {
+        Self(
+            snapshot
+                .into_iter()
+                .map(|(peer, status)| PinnedPeer {
+                    peer,
+                    status: status.into(),
+                })
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::collections::BTreeMap;
+
+use librad::git::{storage::stats, Urn};
+
+#[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+pub struct Request {
+    #[n(0)]
+    pub urn: Urn,
+}
+
+/// The stats of the requested project, or `None` if none were recorded.
+#[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+#[cbor(transparent)]
+pub struct Response(#[n(0)] pub Option<Stats>);
+
+#[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+pub struct Stats {
+    #[n(0)]
+    pub remotes: u64,
+    /// Seconds since the Unix epoch at which the stats were last updated.
+    #[n(1)]
+    pub updated: u64,
+    /// Estimated size in bytes of the objects of the project.
+    #[n(2)]
+    pub disk_usage: u64,
+    /// Number of collaborative objects, by type name.
+    #[n(3)]
+    pub cobs: BTreeMap<String, u64>,
+}
+
+impl From<stats::Stats> for Stats {
+    fn from(s: stats::Stats) -> Self {
+        Self {
+            remotes: s.remotes as u64,
+            updated: s.updated,
+            disk_usage: s.disk_usage,
+            cobs: s
+                .cobs
+                .into_iter()
+                .map(|(typename, n)| (typename, n as u64))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Authentication of connections to the remote control port of a node.
+//!
+//! Before sending any requests, a remote client sends an [`Authenticate`]
+//! message, and the node replies with [`Authenticated::Granted`] along with the
+//! [`Access`] level of the connection, or with [`Authenticated::Denied`].
+
+use std::{fmt, str::FromStr};
+
+use async_compat::CompatExt as _;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::{io, messages, wire_types::Message};
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Authenticate {
+        #[error("authentication timed out")]
+        Timeout,
+
+        #[error("connection closed before authenticating")]
+        Closed,
+
+        #[error("malformed authentication message")]
+        Decode,
+
+        #[error("access denied")]
+        Denied,
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+/// The operations a connection is permitted to perform.
+///
+/// Levels are ordered, each level includes the permissions of the lower
+/// levels.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, minicbor::Encode, minicbor::Decode,
+)]
+pub enum Access {
+    /// Inspect the state of the node.
+    #[n(0)]
+    Read,
+    /// Ask the node to talk to the network, eg. announce or request-pull.
+    #[n(1)]
+    Operate,
+    /// Interfere with the operation of the node, eg. cancel replications.
+    #[n(2)]
+    Admin,
+}
+
+impl Access {
+    /// The level required to perform the request `payload`.
+    pub fn required(payload: &messages::RequestPayload) -> Self {
+        use messages::RequestPayload::*;
+
+        match payload {
+            ReplicationTasks(_) | PinnedPeers(_) | ProjectStats(_) | StandbyNamespaces(_)
+            | Connections(_) | Usage(_) => Self::Read,
+            Announce(_) | RequestPull(_) | ReplicationDryRun(_) => Self::Operate,
+            CancelReplication(_) | Promote(_) | Disconnect(_) => Self::Admin,
+        }
+    }
+
+    /// Whether this level permits the request `payload`.
+    pub fn permits(&self, payload: &messages::RequestPayload) -> bool {
+        *self >= Self::required(payload)
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read => f.write_str("read"),
+            Self::Operate => f.write_str("operate"),
+            Self::Admin => f.write_str("admin"),
+        }
+    }
+}
+
+impl FromStr for Access {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "read" => Ok(Self::Read),
+            "operate" => Ok(Self::Operate),
+            "admin" => Ok(Self::Admin),
+            _ => Err(format!("unsupported access level `{}`", input)),
+        }
+    }
+}
+
+/// The first message a remote client sends.
+#[derive(Clone, Debug, PartialEq, minicbor::Encode, minicbor::Decode)]
+pub struct Authenticate {
+    /// Bearer token, may be omitted if the client authenticated via TLS
+    /// client certificate.
+    #[n(0)]
+    pub token: Option<String>,
+}
+
+/// The reply of the node to [`Authenticate`].
+#[derive(Clone, Debug, PartialEq, minicbor::Encode, minicbor::Decode)]
+pub enum Authenticated {
+    #[n(0)]
+    Granted(#[n(0)] Access),
+    #[n(1)]
+    Denied,
+}
+
+/// Run the server side of the authentication handshake over `stream`,
+/// using `grant` to determine the [`Access`] of the bearer token sent by the
+/// client, if any.
+pub async fn authenticate<T, F>(stream: &mut T, grant: F) -> Result<Access, error::Authenticate>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+    F: FnOnce(Option<&str>) -> Option<Access>,
+{
+    let msg = io::MessageReader::new((&mut *stream).compat())
+        .read_message::<Authenticate>()
+        .await
+        .map_err(|e| match e {
+            io::Error::Io(e) => error::Authenticate::Io(e),
+            _ => error::Authenticate::Decode,
+        })?
+        .ok_or(error::Authenticate::Closed)?;
+    let access = grant(msg.headers.token.as_deref());
+
+    let reply = access.map_or(Authenticated::Denied, Authenticated::Granted);
+    io::MessageWriter::new((&mut *stream).compat())
+        .write_message(&Message {
+            headers: reply,
+            payload: None,
+        })
+        .await?;
+
+    access.ok_or(error::Authenticate::Denied)
+}
+
+/// Run the client side of the authentication handshake over `stream`,
+/// returning the [`Access`] granted by the node.
+pub async fn login<T>(stream: &mut T, token: Option<String>) -> Result<Access, error::Authenticate>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    io::MessageWriter::new((&mut *stream).compat())
+        .write_message(&Message {
+            headers: Authenticate { token },
+            payload: None,
+        })
+        .await?;
+    let reply = io::MessageReader::new((&mut *stream).compat())
+        .read_message::<Authenticated>()
+        .await
+        .map_err(|e| match e {
+            io::Error::Io(e) => error::Authenticate::Io(e),
+            _ => error::Authenticate::Decode,
+        })?
+        .ok_or(error::Authenticate::Closed)?;
+
+    match reply.headers {
+        Authenticated::Granted(access) => Ok(access),
+        Authenticated::Denied => Err(error::Authenticate::Denied),
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fmt;
+
+use librad::{git::Urn, PeerId};
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, minicbor::Decode, minicbor::Encode,
+)]
+#[cbor(transparent)]
+pub struct TaskId(#[n(0)] u64);
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<u64> for TaskId {
+    fn from(id: u64) -> Self {
+        Self(id)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+pub enum Phase {
+    /// Waiting for a worker to become available.
+    #[n(0)]
+    Queued,
+    /// Fetching from the remote peer.
+    #[n(1)]
+    Fetching,
+    /// Waiting for other jobs for the same namespace to finish, as the
+    /// namespace has the maximum number of jobs running.
+    #[n(2)]
+    Throttled,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Queued => f.write_str("queued"),
+            Self::Fetching => f.write_str("fetching"),
+            Self::Throttled => f.write_str("throttled"),
+        }
+    }
+}
+
+/// The progress of a job which is fetching.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+pub struct Progress {
+    /// Bytes received from the remote peer since the job started fetching.
+    ///
+    /// If the replication backend doesn't report its progress, this is
+    /// counted on the connection to the peer, so it includes the traffic of
+    /// other jobs fetching from the same peer at the same time.
+    #[n(0)]
+    pub received_bytes: u64,
+    /// The number of objects in the packfile being fetched, if known.
+    #[n(1)]
+    pub total_objects: Option<u64>,
+    /// The number of objects of the packfile being fetched which were
+    /// indexed so far, if known.
+    #[n(2)]
+    pub indexed_objects: Option<u64>,
+}
+
+/// A snapshot of a replication job.
+#[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+pub struct TaskInfo {
+    #[n(0)]
+    pub id: TaskId,
+    #[n(1)]
+    pub urn: Urn,
+    #[n(2)]
+    pub peer: PeerId,
+    #[n(3)]
+    pub phase: Phase,
+    /// Number of seconds since the job was submitted.
+    #[n(4)]
+    pub elapsed_secs: u64,
+    /// `None` unless the job is fetching.
+    #[n(5)]
+    pub progress: Option<Progress>,
+}
+
+pub mod tasks {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+    pub struct Request;
+
+    #[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+    #[cbor(transparent)]
+    pub struct Response(#[n(0)] pub Vec<TaskInfo>);
+}
+
+pub mod cancel {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+    pub struct Request {
+        #[n(0)]
+        pub task: TaskId,
+    }
+
+    #[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+    pub struct Response;
+}
+
+pub mod dry_run {
+    use std::net::SocketAddr;
+
+    use librad::{git::Urn, net::replication, PeerId};
+    use radicle_git_ext::Oid;
+
+    /// Determine what replicating `urn` from `peer` would fetch, without
+    /// fetching anything.
+    #[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+    pub struct Request {
+        #[n(0)]
+        pub urn: Urn,
+        #[n(1)]
+        pub peer: PeerId,
+        #[n(2)]
+        pub addrs: Vec<SocketAddr>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+    pub struct Response {
+        /// Whether `urn` would be cloned, ie. is not present locally yet.
+        #[n(0)]
+        pub clone: bool,
+        /// The refs which would be updated.
+        #[n(1)]
+        pub updates: Vec<Update>,
+        /// The number of advertised refs which are already up-to-date.
+        #[n(2)]
+        pub up_to_date: u64,
+        /// The advertised tips missing locally, each requiring at least one
+        /// object to be fetched.
+        #[n(3)]
+        pub wants: Vec<Oid>,
+        /// The local tips which would be offered as common history.
+        #[n(4)]
+        pub haves: Vec<Oid>,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+    pub struct Update {
+        /// The local name of the ref.
+        #[n(0)]
+        pub name: String,
+        #[n(1)]
+        pub old: Option<Oid>,
+        #[n(2)]
+        pub new: Oid,
+    }
+
+    impl From<replication::DryRun> for Response {
+        fn from(dry_run: replication::DryRun) -> Self {
+            Self {
+                clone: matches!(dry_run.mode, replication::Mode::Clone),
+                updates: dry_run
+                    .updates
+                    .into_iter()
+                    .map(|(name, update)| Update {
+                        name: name.to_string(),
+                        old: update.old,
+                        new: update.new,
+                    })
+                    .collect(),
+                up_to_date: dry_run.up_to_date as u64,
+                wants: dry_run.wants.into_iter().collect(),
+                haves: dry_run.haves.into_iter().collect(),
+            }
+        }
+    }
+}
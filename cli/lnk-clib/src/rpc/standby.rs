@@ -0,0 +1,78 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Requests used to operate a warm standby node.
+
+use librad::{git::Urn, PeerId};
+
+pub mod namespaces {
+    use super::*;
+
+    /// The maximum number of namespaces returned per page.
+    pub const MAX_LIMIT: u32 = 1024;
+
+    /// Page through the namespaces of the node, in [`Urn`] order.
+    #[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+    pub struct Request {
+        /// Only namespaces written to at or after this time, in seconds
+        /// since the Unix epoch.
+        #[n(0)]
+        pub updated_since: Option<u64>,
+        /// Start after this namespace, see [`Response::next`].
+        #[n(1)]
+        pub after: Option<Urn>,
+        /// Capped at [`MAX_LIMIT`].
+        #[n(2)]
+        pub limit: u32,
+    }
+
+    #[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+    pub struct Response {
+        #[n(0)]
+        pub namespaces: Vec<Namespace>,
+        /// If there are more namespaces, the [`Urn`] to pass as
+        /// [`Request::after`] to obtain the next page.
+        #[n(1)]
+        pub next: Option<Urn>,
+        /// Seconds since the Unix epoch, by the clock of the node, before the
+        /// page was listed. Passing the value of the first page as
+        /// [`Request::updated_since`] yields the namespaces written to since.
+        #[n(2)]
+        pub as_of: u64,
+    }
+
+    #[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+    pub struct Namespace {
+        #[n(0)]
+        pub urn: Urn,
+        /// Seconds since the Unix epoch at which the namespace was last
+        /// written.
+        #[n(1)]
+        pub updated: u64,
+        #[n(2)]
+        pub tracked: Vec<Tracked>,
+    }
+
+    /// A tracking entry of a namespace.
+    #[derive(Clone, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+    pub struct Tracked {
+        /// The tracked peer, or `None` for the default entry.
+        #[n(0)]
+        pub peer: Option<PeerId>,
+        /// The canonical form of the tracking configuration.
+        #[n(1)]
+        #[cbor(with = "minicbor::bytes")]
+        pub config: Vec<u8>,
+    }
+}
+
+pub mod promote {
+    /// Stop following the primary, and act as a primary from now on.
+    #[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+    pub struct Request;
+
+    #[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+    pub struct Response;
+}
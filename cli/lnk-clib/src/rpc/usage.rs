@@ -0,0 +1,45 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Export the statistics of the data served to other peers, see
+//! [`librad::net::protocol::usage`].
+
+use librad::net::protocol::usage;
+
+#[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+pub struct Request {
+    /// The first day to export, as `YYYY-MM-DD`. If `None`, starts at the
+    /// oldest day recorded.
+    #[n(0)]
+    pub from: Option<String>,
+    /// The last day to export, as `YYYY-MM-DD`. If `None`, ends at the
+    /// current day.
+    #[n(1)]
+    pub to: Option<String>,
+    #[n(2)]
+    pub format: Format,
+}
+
+/// The requested days, rendered in the requested [`Format`].
+#[derive(Clone, Debug, PartialEq, minicbor::Decode, minicbor::Encode)]
+#[cbor(transparent)]
+pub struct Response(#[n(0)] pub String);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, minicbor::Decode, minicbor::Encode)]
+pub enum Format {
+    #[n(0)]
+    Json,
+    #[n(1)]
+    Csv,
+}
+
+impl From<Format> for usage::Format {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Json => Self::Json,
+            Format::Csv => Self::Csv,
+        }
+    }
+}
@@ -44,6 +44,37 @@ impl From<messages::Request> for Request {
             messages::RequestPayload::RequestPull(request_pull) => {
                 (minicbor::to_vec(request_pull).unwrap(), Kind::RequestPull)
             },
+            messages::RequestPayload::ReplicationTasks(tasks) => {
+                (minicbor::to_vec(tasks).unwrap(), Kind::ReplicationTasks)
+            },
+            messages::RequestPayload::CancelReplication(cancel) => {
+                (minicbor::to_vec(cancel).unwrap(), Kind::CancelReplication)
+            },
+            messages::RequestPayload::PinnedPeers(pinned) => {
+                (minicbor::to_vec(pinned).unwrap(), Kind::PinnedPeers)
+            },
+            messages::RequestPayload::ProjectStats(stats) => {
+                (minicbor::to_vec(stats).unwrap(), Kind::ProjectStats)
+            },
+            messages::RequestPayload::StandbyNamespaces(namespaces) => (
+                minicbor::to_vec(namespaces).unwrap(),
+                Kind::StandbyNamespaces,
+            ),
+            messages::RequestPayload::Promote(promote) => {
+                (minicbor::to_vec(promote).unwrap(), Kind::Promote)
+            },
+            messages::RequestPayload::Connections(list) => {
+                (minicbor::to_vec(list).unwrap(), Kind::Connections)
+            },
+            messages::RequestPayload::Disconnect(disconnect) => {
+                (minicbor::to_vec(disconnect).unwrap(), Kind::Disconnect)
+            },
+            messages::RequestPayload::ReplicationDryRun(dry_run) => {
+                (minicbor::to_vec(dry_run).unwrap(), Kind::ReplicationDryRun)
+            },
+            messages::RequestPayload::Usage(usage) => {
+                (minicbor::to_vec(usage).unwrap(), Kind::Usage)
+            },
         };
         Request {
             headers: Headers {
@@ -78,6 +109,32 @@ impl TryFrom<Request> for messages::Request {
             Kind::RequestPull => {
                 messages::RequestPayload::RequestPull(minicbor::decode(&payload_bytes)?)
             },
+            Kind::ReplicationTasks => {
+                messages::RequestPayload::ReplicationTasks(minicbor::decode(&payload_bytes)?)
+            },
+            Kind::CancelReplication => {
+                messages::RequestPayload::CancelReplication(minicbor::decode(&payload_bytes)?)
+            },
+            Kind::PinnedPeers => {
+                messages::RequestPayload::PinnedPeers(minicbor::decode(&payload_bytes)?)
+            },
+            Kind::ProjectStats => {
+                messages::RequestPayload::ProjectStats(minicbor::decode(&payload_bytes)?)
+            },
+            Kind::StandbyNamespaces => {
+                messages::RequestPayload::StandbyNamespaces(minicbor::decode(&payload_bytes)?)
+            },
+            Kind::Promote => messages::RequestPayload::Promote(minicbor::decode(&payload_bytes)?),
+            Kind::Connections => {
+                messages::RequestPayload::Connections(minicbor::decode(&payload_bytes)?)
+            },
+            Kind::Disconnect => {
+                messages::RequestPayload::Disconnect(minicbor::decode(&payload_bytes)?)
+            },
+            Kind::ReplicationDryRun => {
+                messages::RequestPayload::ReplicationDryRun(minicbor::decode(&payload_bytes)?)
+            },
+            Kind::Usage => messages::RequestPayload::Usage(minicbor::decode(&payload_bytes)?),
             Kind::Unknown(other) => return Err(DecodeError::UnknownRequestKind(other)),
         };
         Ok(messages::Request {
@@ -96,6 +153,26 @@ pub enum Kind {
     Announce,
     // CBOR encode and decode maps to 5
     RequestPull,
+    // CBOR encode and decode maps to 6
+    ReplicationTasks,
+    // CBOR encode and decode maps to 7
+    CancelReplication,
+    // CBOR encode and decode maps to 8
+    PinnedPeers,
+    // CBOR encode and decode maps to 9
+    ProjectStats,
+    // CBOR encode and decode maps to 10
+    StandbyNamespaces,
+    // CBOR encode and decode maps to 11
+    Promote,
+    // CBOR encode and decode maps to 12
+    Connections,
+    // CBOR encode and decode maps to 13
+    Disconnect,
+    // CBOR encode and decode maps to 14
+    ReplicationDryRun,
+    // CBOR encode and decode maps to 15
+    Usage,
     Unknown(u8),
 }
 
@@ -118,6 +195,16 @@ impl minicbor::Encode for Kind {
         let val = match self {
             Self::Announce => 1,
             Self::RequestPull => 5,
+            Self::ReplicationTasks => 6,
+            Self::CancelReplication => 7,
+            Self::PinnedPeers => 8,
+            Self::ProjectStats => 9,
+            Self::StandbyNamespaces => 10,
+            Self::Promote => 11,
+            Self::Connections => 12,
+            Self::Disconnect => 13,
+            Self::ReplicationDryRun => 14,
+            Self::Usage => 15,
             Self::Unknown(other) => *other,
         };
         e.u8(val)?;
@@ -130,6 +217,16 @@ impl<'b> minicbor::Decode<'b> for Kind {
         Ok(match d.u8()? {
             1 => Self::Announce,
             5 => Self::RequestPull,
+            6 => Self::ReplicationTasks,
+            7 => Self::CancelReplication,
+            8 => Self::PinnedPeers,
+            9 => Self::ProjectStats,
+            10 => Self::StandbyNamespaces,
+            11 => Self::Promote,
+            12 => Self::Connections,
+            13 => Self::Disconnect,
+            14 => Self::ReplicationDryRun,
+            15 => Self::Usage,
             other => Self::Unknown(other),
         })
     }
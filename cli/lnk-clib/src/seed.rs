@@ -5,9 +5,15 @@
 
 use std::{convert::TryFrom, fmt, io, net::SocketAddr, str::FromStr};
 
-use librad::{net::discovery, PeerId};
+use librad::{
+    net::{dial, discovery},
+    PeerId,
+};
 use tokio::net::{lookup_host, ToSocketAddrs};
 
+pub mod pin;
+pub use pin::Pins;
+
 pub mod store;
 pub use store::Store;
 
@@ -88,6 +94,24 @@ impl<T> Seed<T> {
             })
         }
     }
+
+    /// Resolve the `Seed`'s address using the given [`dial::Config`], ie.
+    /// its resolver and timeout. The resolved addresses are ordered by the
+    /// preferred address family.
+    pub async fn resolve_with(
+        &self,
+        config: &dial::Config,
+    ) -> Result<Seed<Vec<SocketAddr>>, error::Resolve>
+    where
+        T: fmt::Display,
+    {
+        let addrs = config.resolve(&self.addrs.to_string()).await?;
+        Ok(Seed {
+            peer: self.peer,
+            addrs,
+            label: self.label.clone(),
+        })
+    }
 }
 
 /// A list of [`Seed`]s that have been resolved.
@@ -118,15 +142,37 @@ impl Seeds {
         S::Iter: std::error::Error + Send + Sync + 'static,
         T: Clone + fmt::Display + FromStr + ToSocketAddrs,
         T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        Self::load_with(store, cutoff, &dial::Config::default(), Ok).await
+    }
+
+    /// Like [`Seeds::load`], but resolve the seeds using the given
+    /// [`dial::Config`], and pass each [`Seed`] through `check` before
+    /// resolving it, eg. [`Pins::check`].
+    pub async fn load_with<S, T, F>(
+        store: &S,
+        cutoff: impl Into<Option<usize>>,
+        dial: &dial::Config,
+        mut check: F,
+    ) -> Result<(Seeds, Vec<error::Load>), S::Scan>
+    where
+        S: Store<Addrs = T>,
+        S::Iter: std::error::Error + Send + Sync + 'static,
+        T: Clone + fmt::Display + FromStr + ToSocketAddrs,
+        T::Err: std::error::Error + Send + Sync + 'static,
+        F: FnMut(Seed<T>) -> Result<Seed<T>, error::Load>,
     {
         let mut resolved = Vec::new();
         let mut failures = Vec::new();
         let cutoff = cutoff.into();
 
         for seed in store.scan()? {
-            match seed {
-                Err(err) => failures.push(error::Load::MalformedSeed(Box::new(err))),
-                Ok(seed) => match seed.resolve().await {
+            match seed
+                .map_err(|err| error::Load::MalformedSeed(Box::new(err)))
+                .and_then(&mut check)
+            {
+                Err(err) => failures.push(err),
+                Ok(seed) => match seed.resolve_with(dial).await {
                     Ok(r) => {
                         resolved.push(r);
                         if Some(resolved.len()) == cutoff {
@@ -160,6 +206,25 @@ impl Seeds {
 
         (Self(resolved), failures)
     }
+
+    /// Like [`Seeds::resolve`], but resolve the seeds using the given
+    /// [`dial::Config`].
+    pub async fn resolve_with(
+        seeds: impl ExactSizeIterator<Item = &Seed<String>>,
+        config: &dial::Config,
+    ) -> (Self, Vec<error::Resolve>) {
+        let mut resolved = Vec::with_capacity(seeds.len());
+        let mut failures = Vec::new();
+
+        for seed in seeds {
+            match seed.resolve_with(config).await {
+                Ok(r) => resolved.push(r),
+                Err(err) => failures.push(err),
+            }
+        }
+
+        (Self(resolved), failures)
+    }
 }
 
 impl Extend<Seed<Vec<SocketAddr>>> for Seeds {
@@ -185,7 +250,7 @@ pub mod error {
     use std::io;
     use thiserror::Error;
 
-    use librad::{crypto::peer, PeerId};
+    use librad::{crypto::peer, net::dial, PeerId};
 
     #[derive(Debug, Error)]
     pub enum Load {
@@ -194,6 +259,9 @@ pub mod error {
 
         #[error(transparent)]
         Resolve(#[from] Resolve),
+
+        #[error(transparent)]
+        Pin(#[from] super::pin::error::Pin),
     }
 
     #[derive(Debug, Error)]
@@ -216,6 +284,9 @@ pub mod error {
         #[error("address `{addr}` for peer `{peer}` could be not be resolved")]
         DnsLookupFailed { peer: PeerId, addr: String },
 
+        #[error(transparent)]
+        Dial(#[from] dial::error::Resolve),
+
         #[error(transparent)]
         Io(#[from] io::Error),
     }
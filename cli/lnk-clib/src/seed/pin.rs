@@ -0,0 +1,218 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Trust-on-first-use pinning of seed identities.
+//!
+//! The first time a seed address is used, the [`PeerId`] it is configured
+//! with is recorded in the [`Pins`] file (see [`librad::paths::seed_pins`]).
+//! Since connections to a seed are authenticated by its [`PeerId`], this
+//! pins the certificate presented at that address. If the address is later
+//! configured with a different [`PeerId`], it is refused as a
+//! [`error::Pin::Mismatch`], unless the change is covered by a [`Rotation`].
+//!
+//! A [`Rotation`] is a statement signed by the old key of a seed, naming its
+//! new key. Seeds serve the rotation they announced (see
+//! [`librad::net::protocol::rotation`]) before switching keys. Rotations
+//! fetched from a seed are verified against the key it was connected to as,
+//! and collected in the file at [`librad::paths::seed_rotations`] by
+//! [`store_rotation`]. When a pinned seed has been rotated, the new
+//! [`PeerId`] is used and pinned, even if the configuration still names the
+//! old one. Rotations can be chained, so the configuration does not need to
+//! be updated at all.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use librad::PeerId;
+
+use super::Seed;
+
+pub use librad::net::protocol::rotation::Rotation;
+
+/// Load the [`Rotation`]s stored at `path`, as a JSON array.
+///
+/// A missing file is treated as empty. All rotations are verified.
+pub fn load_rotations(path: impl AsRef<Path>) -> Result<Vec<Rotation>, error::Rotation> {
+    let rotations: Vec<Rotation> = match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    for rotation in &rotations {
+        rotation.verify()?;
+    }
+    Ok(rotations)
+}
+
+/// Add `rotation` to the [`Rotation`]s stored at `path`, after verifying it.
+///
+/// Returns `false` if the rotation was already stored.
+pub fn store_rotation(path: impl AsRef<Path>, rotation: Rotation) -> Result<bool, error::Rotation> {
+    let path = path.as_ref();
+    rotation.verify()?;
+    let mut rotations = load_rotations(path)?;
+    if rotations.contains(&rotation) {
+        return Ok(false);
+    }
+    rotations.push(rotation);
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(&rotations)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(true)
+}
+
+/// The outcome of [`Pins::check`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Checked {
+    /// The address was not seen before, and is now pinned.
+    FirstUse,
+    /// The address is pinned to the configured [`PeerId`].
+    Known,
+    /// The pinned [`PeerId`] was rotated to a new one, which is now pinned.
+    Rotated { from: PeerId, to: PeerId },
+}
+
+/// The [`PeerId`]s pinned for seed addresses.
+pub struct Pins {
+    path: PathBuf,
+    pins: BTreeMap<String, PeerId>,
+    rotations: BTreeMap<PeerId, PeerId>,
+}
+
+impl Pins {
+    /// Load the pins stored at `path`, as a JSON object mapping addresses to
+    /// [`PeerId`]s.
+    ///
+    /// A missing file is treated as empty, and created on the first change.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, error::Pin> {
+        let path = path.as_ref().to_path_buf();
+        let pins = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path,
+            pins,
+            rotations: BTreeMap::new(),
+        })
+    }
+
+    /// Accept the given (verified) [`Rotation`]s.
+    pub fn with_rotations(mut self, rotations: impl IntoIterator<Item = Rotation>) -> Self {
+        self.rotations
+            .extend(rotations.into_iter().map(|r| (r.old, r.new)));
+        self
+    }
+
+    /// The [`PeerId`] pinned for `addr`, if any.
+    pub fn get(&self, addr: &str) -> Option<&PeerId> {
+        self.pins.get(addr)
+    }
+
+    /// Check `seed` against the pins, pinning it if its address was not seen
+    /// before.
+    ///
+    /// Returns the seed with the [`PeerId`] to use, which differs from the
+    /// configured one if the pinned key was rotated.
+    pub fn check(&mut self, mut seed: Seed<String>) -> Result<(Seed<String>, Checked), error::Pin> {
+        let (pinned, checked) = match self.pins.get(&seed.addrs) {
+            None => (seed.peer, Checked::FirstUse),
+            Some(pinned) => (*pinned, Checked::Known),
+        };
+
+        let chain = self.chain(pinned);
+        if !chain.contains(&seed.peer) {
+            return Err(error::Pin::Mismatch {
+                addr: seed.addrs,
+                pinned,
+                configured: seed.peer,
+            });
+        }
+        let current = *chain.last().expect("chain contains at least the pinned id");
+
+        let checked = if current != pinned && checked == Checked::Known {
+            Checked::Rotated {
+                from: pinned,
+                to: current,
+            }
+        } else {
+            checked
+        };
+        if checked != Checked::Known {
+            self.pins.insert(seed.addrs.clone(), current);
+            self.persist()?;
+        }
+
+        seed.peer = current;
+        Ok((seed, checked))
+    }
+
+    /// The successive keys of a seed, starting at `peer`.
+    fn chain(&self, peer: PeerId) -> Vec<PeerId> {
+        let mut chain = vec![peer];
+        let mut current = peer;
+        while let Some(next) = self.rotations.get(&current) {
+            // Guard against cycles.
+            if chain.contains(next) {
+                break;
+            }
+            chain.push(*next);
+            current = *next;
+        }
+        chain
+    }
+
+    fn persist(&self) -> Result<(), error::Pin> {
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(&self.pins)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use librad::{net::protocol::rotation, PeerId};
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Pin {
+        #[error(
+            "seed `{addr}` is configured as `{configured}`, but was pinned as `{pinned}` on first use"
+        )]
+        Mismatch {
+            addr: String,
+            pinned: PeerId,
+            configured: PeerId,
+        },
+
+        #[error("malformed seed pins")]
+        Json(#[from] serde_json::Error),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Rotation {
+        #[error(transparent)]
+        Verify(#[from] rotation::error::Verify),
+
+        #[error("malformed seed rotations")]
+        Json(#[from] serde_json::Error),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
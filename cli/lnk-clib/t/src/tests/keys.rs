@@ -17,9 +17,14 @@ use librad::{
     },
     git::storage::Storage,
     profile::{LnkHome, Profile, ProfileId},
+    PeerId,
     Signer as _,
 };
-use lnk_clib::keys::{file_storage, ssh};
+use lnk_clib::keys::{
+    file_storage,
+    keychain::{self, Keychain as _},
+    ssh,
+};
 use test_helpers::logging;
 
 #[test]
@@ -48,3 +53,49 @@ fn agent_signature() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn keychain_signer() -> anyhow::Result<()> {
+    logging::init();
+
+    let temp = tempdir()?;
+    let home = LnkHome::Root(temp.path().to_path_buf());
+    let profile = Profile::from_home(&home, Some(ProfileId::new()))?;
+    let key = SecretKey::new();
+    let pass = || SecUtf8::from(b"42".to_vec());
+    let wrong = || SecUtf8::from(b"43".to_vec());
+    file_storage(&profile, Pwhash::new(pass(), *KDF_PARAMS_TEST)).put_key(key.clone())?;
+
+    let chain = keychain::Memory::default();
+    let account = keychain::account(&profile);
+
+    // Not in the keychain, falls back and remembers
+    let signer = keychain::signer(&profile, &chain, pass(), *KDF_PARAMS_TEST)?;
+    assert_eq!(signer.peer_id(), PeerId::from(&key));
+    assert_eq!(chain.get(&account)?, Some(pass()));
+
+    // Found in the keychain, fallback is not consulted
+    let signer = keychain::signer(&profile, &chain, wrong(), *KDF_PARAMS_TEST)?;
+    assert_eq!(signer.peer_id(), PeerId::from(&key));
+
+    // Wrong passphrase in the keychain is replaced
+    chain.set(&account, &wrong())?;
+    keychain::signer(&profile, &chain, pass(), *KDF_PARAMS_TEST)?;
+    assert_eq!(chain.get(&account)?, Some(pass()));
+
+    // Wrong passphrase everywhere
+    chain.set(&account, &wrong())?;
+    assert!(keychain::signer(&profile, &chain, wrong(), *KDF_PARAMS_TEST).is_err());
+    assert_eq!(chain.get(&account)?, None);
+
+    // Other failures leave the keychain alone
+    chain.set(&account, &pass())?;
+    std::fs::remove_dir_all(profile.paths().keys_dir())?;
+    assert!(matches!(
+        keychain::signer(&profile, &chain, pass(), *KDF_PARAMS_TEST),
+        Err(keychain::Error::Unlock(_))
+    ));
+    assert_eq!(chain.get(&account)?, Some(pass()));
+
+    Ok(())
+}
@@ -37,3 +37,121 @@ async fn test_resolve_seeds() -> Result<()> {
 
     Ok(())
 }
+
+mod pin {
+    use librad::{net::protocol::rotation, PeerId, SecretKey};
+    use lnk_clib::seed::{
+        pin::{self, error, Checked, Rotation},
+        Pins,
+        Seed,
+    };
+
+    use super::*;
+
+    fn seed(peer: PeerId) -> Seed<String> {
+        Seed {
+            peer,
+            addrs: "seed.example.com:8776".to_owned(),
+            label: None,
+        }
+    }
+
+    #[test]
+    fn first_use_is_pinned() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("pins.json");
+        let peer = PeerId::from(SecretKey::new());
+
+        let (_, checked) = Pins::load(&path)?.check(seed(peer))?;
+        assert_eq!(checked, Checked::FirstUse);
+
+        let mut pins = Pins::load(&path)?;
+        assert_eq!(pins.get("seed.example.com:8776"), Some(&peer));
+        let (_, checked) = pins.check(seed(peer))?;
+        assert_eq!(checked, Checked::Known);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mismatch_is_refused() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("pins.json");
+        let pinned = PeerId::from(SecretKey::new());
+        let other = PeerId::from(SecretKey::new());
+
+        Pins::load(&path)?.check(seed(pinned))?;
+        assert!(matches!(
+            Pins::load(&path)?.check(seed(other)),
+            Err(error::Pin::Mismatch { pinned: p, configured: c, .. }) if p == pinned && c == other
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rotation_is_followed() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("pins.json");
+        let old = SecretKey::new();
+        let new = PeerId::from(SecretKey::new());
+
+        Pins::load(&path)?.check(seed(PeerId::from(&old)))?;
+
+        let rotation = Rotation::sign(&old, new).await?;
+        rotation.verify()?;
+        // The configuration still names the old key
+        let (rotated, checked) = Pins::load(&path)?
+            .with_rotations(Some(rotation))
+            .check(seed(PeerId::from(&old)))?;
+        assert_eq!(
+            checked,
+            Checked::Rotated {
+                from: PeerId::from(&old),
+                to: new
+            }
+        );
+        assert_eq!(rotated.peer, new);
+        assert_eq!(Pins::load(&path)?.get("seed.example.com:8776"), Some(&new));
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn forged_rotation_is_invalid() -> Result<()> {
+        let old = PeerId::from(SecretKey::new());
+        let attacker = SecretKey::new();
+        let forged = Rotation {
+            old,
+            ..Rotation::sign(&attacker, PeerId::from(SecretKey::new())).await?
+        };
+        assert!(matches!(
+            forged.verify(),
+            Err(rotation::error::Verify::Signature { .. })
+        ));
+
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("rotations.json");
+        assert!(matches!(
+            pin::store_rotation(&path, forged),
+            Err(error::Rotation::Verify(_))
+        ));
+        assert!(pin::load_rotations(&path)?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn fetched_rotation_is_stored() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("rotations.json");
+        let old = SecretKey::new();
+        let rotation = Rotation::sign(&old, PeerId::from(SecretKey::new())).await?;
+
+        assert!(pin::store_rotation(&path, rotation.clone())?);
+        assert!(!pin::store_rotation(&path, rotation.clone())?);
+        assert_eq!(pin::load_rotations(&path)?, vec![rotation]);
+
+        Ok(())
+    }
+}
@@ -9,8 +9,9 @@ use clap::Parser;
 use either::Either;
 
 use librad::{
+    canonical::Cstring,
     crypto::PublicKey,
-    git::Urn,
+    git::{alias::Name, Urn},
     identities::{
         git::Revision,
         payload::{self, KeyOrUrn},
@@ -38,6 +39,7 @@ pub enum Command {
     Refs(Refs),
     Track(tracking::Track),
     Untrack(tracking::Untrack),
+    Alias(Alias),
 }
 
 /// create, get, or modify a Radicle project
@@ -83,6 +85,13 @@ pub struct Refs {
     pub refs: refs::Options,
 }
 
+/// manage local aliases for Radicle URNs
+#[derive(Debug, Parser)]
+pub struct Alias {
+    #[clap(subcommand)]
+    pub alias: alias::Options,
+}
+
 pub mod project {
     use super::*;
 
@@ -106,6 +115,7 @@ pub mod project {
     #[derive(Debug, Parser)]
     pub enum Options {
         Create(CreateOptions),
+        Import(Import),
         Get(Get),
         List(List),
         Update(Update),
@@ -199,12 +209,60 @@ pub mod project {
         pub path: PathBuf,
     }
 
+    /// create a new Radicle project from an existing git repository, setting
+    /// up its `rad` remote and pushing its branches. If any step fails, the
+    /// project is removed again.
+    #[derive(Debug, Parser)]
+    pub struct Import {
+        /// the path of the git repository to import
+        #[clap(long)]
+        pub path: PathBuf,
+
+        /// the name of the project, defaults to the name of the directory at
+        /// `path`
+        #[clap(long)]
+        pub name: Option<Cstring>,
+
+        /// the description of the project
+        #[clap(long)]
+        pub description: Option<Cstring>,
+
+        /// the default branch of the project, defaults to the branch `HEAD`
+        /// points to
+        #[clap(long)]
+        pub default_branch: Option<Cstring>,
+
+        /// provide a list of extensions to extend the payload. The extension
+        /// must be a JSON object consisting of a namespace URL and the extended
+        /// JSON payload
+        #[clap(long, parse(try_from_str = ext_payload))]
+        pub ext: Vec<payload::Ext<serde_json::Value>>,
+
+        /// the Radicle URN pointing to a local identity that will be used for
+        /// setting `rad/self` on this project. If no URN is provided the
+        /// default identity will be used instead.
+        #[clap(long)]
+        pub whoami: Option<Urn>,
+
+        /// the initial set of delegates to initialise the project with. The
+        /// delegate can either be a Rad URN or Peer ID.
+        /// The local identity is always used as a delegate, so it is not
+        /// necessary to include it here.
+        #[clap(long, parse(try_from_str = indirect_delegation))]
+        pub delegations: Vec<KeyOrUrn<Revision>>,
+
+        /// announce the project via the linkd node running for the profile
+        #[clap(long)]
+        pub announce: bool,
+    }
+
     /// get a Radicle project
     #[derive(Debug, Parser)]
     pub struct Get {
-        /// the Radicle URN of the project
+        /// the Radicle URN of the project, an alias, or a unique prefix of
+        /// its id
         #[clap(long)]
-        pub urn: Urn,
+        pub urn: Name,
 
         /// the peer's version of the project
         #[clap(long)]
@@ -218,9 +276,10 @@ pub mod project {
     /// update a Radicle project
     #[derive(Debug, Parser)]
     pub struct Update {
-        /// the Radicle URN of the project
+        /// the Radicle URN of the project, an alias, or a unique prefix of
+        /// its id
         #[clap(long)]
-        pub urn: Urn,
+        pub urn: Name,
 
         /// the Radicle URN pointing to a local identity that will be used for
         /// setting `rad/self` on this project.
@@ -251,9 +310,10 @@ pub mod project {
     /// checkout a Radicle project to a working copy
     #[derive(Debug, Parser)]
     pub struct Checkout {
-        /// the Radicle URN of the project
+        /// the Radicle URN of the project, an alias, or a unique prefix of
+        /// its id
         #[clap(long)]
-        pub urn: Urn,
+        pub urn: Name,
 
         /// the location for creating the working copy in
         #[clap(long)]
@@ -269,9 +329,10 @@ pub mod project {
     /// review the difference between the local Radicle project and a peer's
     #[derive(Debug, Parser)]
     pub struct Diff {
-        /// the Radicle URN of the project
+        /// the Radicle URN of the project, an alias, or a unique prefix of
+        /// its id
         #[clap(long)]
-        pub urn: Urn,
+        pub urn: Name,
         /// the peer to compare to
         #[clap(long)]
         pub peer: PeerId,
@@ -281,9 +342,10 @@ pub mod project {
     /// peer's
     #[derive(Debug, Parser)]
     pub struct Accept {
-        /// the Radicle URN of the project
+        /// the Radicle URN of the project, an alias, or a unique prefix of
+        /// its id
         #[clap(long)]
-        pub urn: Urn,
+        pub urn: Name,
         /// the peer to compare to, and accept from
         #[clap(long)]
         pub peer: PeerId,
@@ -294,9 +356,10 @@ pub mod project {
 
     #[derive(Debug, Parser)]
     pub struct Tracked {
-        /// the Radicle URN of the project
+        /// the Radicle URN of the project, an alias, or a unique prefix of
+        /// its id
         #[clap(long)]
-        pub urn: Urn,
+        pub urn: Name,
     }
 }
 
@@ -499,9 +562,10 @@ pub mod any {
     /// get a Radicle identity, where the kind of identity is not known
     #[derive(Debug, Parser)]
     pub struct Get {
-        /// the Radicle URN of the identity
+        /// the Radicle URN of the identity, an alias, or a unique prefix of
+        /// its id
         #[clap(long)]
-        pub urn: Urn,
+        pub urn: Name,
     }
 
     /// list all Radicle identities
@@ -700,3 +764,67 @@ pub mod tracking {
 fn ext_payload(value: &str) -> Result<payload::Ext<serde_json::Value>, String> {
     serde_json::from_str(value).map_err(|err| err.to_string())
 }
+
+pub mod alias {
+    use super::*;
+
+    use librad::git::alias::Alias;
+
+    #[derive(Debug, Parser)]
+    pub enum Options {
+        Set(Set),
+        Remove(Remove),
+        List(List),
+        Resolve(Resolve),
+        Suggest(Suggest),
+    }
+
+    /// bind an alias to a Radicle URN
+    #[derive(Debug, Parser)]
+    pub struct Set {
+        /// the alias, which must start with a letter and contain only letters,
+        /// digits and `-`
+        #[clap(long)]
+        pub alias: Alias,
+
+        /// the Radicle URN, an alias, or a unique prefix of its id
+        #[clap(long)]
+        pub urn: Name,
+
+        /// replace the alias if it is already bound to a different URN
+        #[clap(long, short)]
+        pub force: bool,
+    }
+
+    /// remove an alias
+    #[derive(Debug, Parser)]
+    pub struct Remove {
+        #[clap(long)]
+        pub alias: Alias,
+    }
+
+    /// list all aliases and the URNs they are bound to
+    #[derive(Debug, Parser)]
+    pub struct List {}
+
+    /// resolve an alias, or a unique prefix of an id, to a Radicle URN
+    #[derive(Debug, Parser)]
+    pub struct Resolve {
+        #[clap(long)]
+        pub name: Name,
+    }
+
+    /// suggest an alias for a Radicle project, based on the alias declared
+    /// by the project or its name
+    #[derive(Debug, Parser)]
+    pub struct Suggest {
+        /// the Radicle URN of the project, an alias, or a unique prefix of
+        /// its id
+        #[clap(long)]
+        pub urn: Name,
+
+        /// bind the suggested alias to the project
+        #[clap(long)]
+        pub set: bool,
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+
+use librad::{git::alias, profile::Profile};
+use lnk_clib::{
+    keys::ssh::SshAuthSock,
+    storage::{self, ssh},
+};
+
+use crate::cli::args::alias::*;
+
+pub fn eval(profile: &Profile, sock: SshAuthSock, opts: Options) -> anyhow::Result<()> {
+    match opts {
+        Options::Set(set) => eval_set(profile, sock, set)?,
+        Options::Remove(Remove { alias }) => eval_remove(profile, sock, alias)?,
+        Options::List(List {}) => eval_list(profile)?,
+        Options::Resolve(Resolve { name }) => eval_resolve(profile, name)?,
+        Options::Suggest(suggest) => eval_suggest(profile, sock, suggest)?,
+    }
+
+    Ok(())
+}
+
+fn eval_set(
+    profile: &Profile,
+    sock: SshAuthSock,
+    Set { alias, urn, force }: Set,
+) -> anyhow::Result<()> {
+    let (_, storage) = ssh::storage(profile, sock)?;
+    let urn = alias::resolve(&storage, &urn)?;
+    alias::set(&storage, &alias, &urn, force)?;
+    Ok(())
+}
+
+fn eval_remove(profile: &Profile, sock: SshAuthSock, alias: alias::Alias) -> anyhow::Result<()> {
+    let (_, storage) = ssh::storage(profile, sock)?;
+    alias::remove(&storage, &alias)?.ok_or_else(|| anyhow!("no such alias `{}`", alias))?;
+    Ok(())
+}
+
+fn eval_list(profile: &Profile) -> anyhow::Result<()> {
+    let storage = storage::read_only(profile)?;
+    let aliases = alias::list(&storage)?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+    println!("{}", serde_json::to_string(&aliases)?);
+    Ok(())
+}
+
+fn eval_resolve(profile: &Profile, name: alias::Name) -> anyhow::Result<()> {
+    let storage = storage::read_only(profile)?;
+    let urn = alias::resolve(&storage, &name)?;
+    println!("{}", serde_json::to_string(&urn)?);
+    Ok(())
+}
+
+fn eval_suggest(
+    profile: &Profile,
+    sock: SshAuthSock,
+    Suggest { urn, set }: Suggest,
+) -> anyhow::Result<()> {
+    let (_, storage) = ssh::storage(profile, sock)?;
+    let urn = alias::resolve(&storage, &urn)?;
+    let suggested = alias::suggest(&storage, &urn)?
+        .ok_or_else(|| anyhow!("no alias could be suggested for {}", urn))?;
+    if set {
+        alias::set(&storage, &suggested, &urn, false)?;
+    }
+    println!("{}", serde_json::to_string(&suggested)?);
+    Ok(())
+}
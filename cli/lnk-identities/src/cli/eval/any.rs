@@ -4,7 +4,11 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 use librad::{
-    git::{identities, storage::ReadOnly, Urn},
+    git::{
+        alias::{self, Name},
+        identities,
+        storage::ReadOnly,
+    },
     profile::Profile,
 };
 
@@ -19,9 +23,10 @@ pub fn eval(profile: &Profile, opts: Options) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn eval_get(profile: &Profile, urn: Urn) -> anyhow::Result<()> {
+fn eval_get(profile: &Profile, urn: Name) -> anyhow::Result<()> {
     let paths = profile.paths();
     let storage = ReadOnly::open(paths)?;
+    let urn = alias::resolve(&storage, &urn)?;
     let identity =
         any::get(&storage, &urn)?.ok_or_else(|| identities::Error::NotFound(urn.clone()))?;
     println!("{}", serde_json::to_string(&any::Display::from(identity))?);
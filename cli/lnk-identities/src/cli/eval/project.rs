@@ -9,8 +9,9 @@ use anyhow::anyhow;
 
 use librad::{
     git::{
+        alias::{self, Name},
         identities,
-        storage::ReadOnly,
+        storage::{ReadOnly, ReadOnlyStorage as _},
         types::{Namespace, Reference},
         Urn,
     },
@@ -31,6 +32,7 @@ use crate::{cli::args::project::*, display, project};
 pub fn eval(profile: &Profile, sock: SshAuthSock, opts: Options) -> anyhow::Result<()> {
     match opts {
         Options::Create(CreateOptions { create }) => eval_create(profile, sock, create)?,
+        Options::Import(import) => eval_import(profile, sock, import)?,
         Options::Get(Get { urn, peer }) => eval_get(profile, urn, peer)?,
         Options::List(List {}) => eval_list(profile)?,
         Options::Update(Update {
@@ -97,8 +99,53 @@ fn eval_create(profile: &Profile, sock: SshAuthSock, create: Create) -> anyhow::
     Ok(())
 }
 
-fn eval_get(profile: &Profile, urn: Urn, peer: Option<PeerId>) -> anyhow::Result<()> {
+fn eval_import(profile: &Profile, sock: SshAuthSock, import: Import) -> anyhow::Result<()> {
+    let (signer, storage) = ssh::storage(profile, sock)?;
+    let paths = profile.paths();
+    let Import {
+        path,
+        name,
+        description,
+        default_branch,
+        ext,
+        whoami,
+        delegations,
+        announce,
+    } = import;
+    let project = project::import(
+        &storage,
+        paths.clone(),
+        signer,
+        whoami.into(),
+        delegations.into_iter().collect(),
+        project::Import {
+            path,
+            name,
+            description,
+            default_branch,
+        },
+        ext,
+    )?;
+
+    if announce {
+        let urn = project.urn();
+        let at = storage.reference_oid(&Reference::rad_signed_refs(Namespace::from(&urn), None))?;
+        let socket = paths.rpc_socket(storage.peer_id());
+        if let Err(e) = project::announce(socket, urn, at) {
+            eprintln!("project was imported, but announcing it failed: {:#}", e);
+        }
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string(&project::Display::from(project))?
+    );
+    Ok(())
+}
+
+fn eval_get(profile: &Profile, urn: Name, peer: Option<PeerId>) -> anyhow::Result<()> {
     let storage = storage::read_only(profile)?;
+    let urn = alias::resolve(&storage, &urn)?;
     let rad = Reference::rad_id(Namespace::from(&urn)).with_remote(peer);
     let urn = Urn::try_from(rad).map_err(|err| anyhow!(err))?;
     let project =
@@ -123,13 +170,14 @@ fn eval_list(profile: &Profile) -> anyhow::Result<()> {
 fn eval_update(
     profile: &Profile,
     sock: SshAuthSock,
-    urn: Urn,
+    urn: Name,
     whoami: Option<Urn>,
     payload: Option<payload::Project>,
     ext: Vec<payload::Ext<serde_json::Value>>,
     delegations: Vec<KeyOrUrn<Revision>>,
 ) -> anyhow::Result<()> {
     let (_, storage) = ssh::storage(profile, sock)?;
+    let urn = alias::resolve(&storage, &urn)?;
     let delegations = delegations.into_iter().collect();
     let project = project::update(&storage, &urn, whoami, payload, ext, delegations)?;
     println!(
@@ -142,19 +190,21 @@ fn eval_update(
 fn eval_checkout(
     profile: &Profile,
     sock: SshAuthSock,
-    urn: Urn,
+    urn: Name,
     path: PathBuf,
     peer: Option<PeerId>,
 ) -> anyhow::Result<()> {
     let (signer, storage) = ssh::storage(profile, sock)?;
+    let urn = alias::resolve(&storage, &urn)?;
     let paths = profile.paths();
     let repo = project::checkout(&storage, paths.clone(), signer, &urn, peer, path)?;
     println!("working copy created at `{}`", repo.path().display());
     Ok(())
 }
 
-fn eval_tracked(profile: &Profile, urn: Urn) -> anyhow::Result<()> {
+fn eval_tracked(profile: &Profile, urn: Name) -> anyhow::Result<()> {
     let storage = storage::read_only(profile)?;
+    let urn = alias::resolve(&storage, &urn)?;
     let peers = project::tracked(&storage, &urn)?
         .into_iter()
         .map(|peer| peer.map(|status| status.map(display::Persona::from)))
@@ -163,8 +213,9 @@ fn eval_tracked(profile: &Profile, urn: Urn) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn eval_diff(profile: &Profile, urn: Urn, peer: PeerId) -> anyhow::Result<()> {
+fn eval_diff(profile: &Profile, urn: Name, peer: PeerId) -> anyhow::Result<()> {
     let storage = storage::read_only(profile)?;
+    let urn = alias::resolve(&storage, &urn)?;
     diff(&storage, urn, peer)?;
     Ok(())
 }
@@ -172,11 +223,12 @@ fn eval_diff(profile: &Profile, urn: Urn, peer: PeerId) -> anyhow::Result<()> {
 fn eval_accept(
     profile: &Profile,
     sock: SshAuthSock,
-    urn: Urn,
+    urn: Name,
     peer: PeerId,
     force: bool,
 ) -> anyhow::Result<()> {
     let (_, storage) = storage::ssh::storage(profile, sock)?;
+    let urn = alias::resolve(&storage, &urn)?;
 
     diff(&storage, urn.clone(), peer)?;
 
@@ -23,7 +23,7 @@ pub mod refs;
 pub mod tracking;
 
 pub mod display;
-mod field;
+pub mod field;
 pub mod git;
 
 #[derive(Debug, Error)]
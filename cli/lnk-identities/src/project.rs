@@ -5,10 +5,12 @@
 
 use std::{collections::BTreeSet, convert::TryFrom as _, path::PathBuf};
 
+use anyhow::{anyhow, bail, Context as _};
 use either::Either;
 use thiserror::Error;
 
 use librad::{
+    canonical::Cstring,
     crypto::BoxedSigner,
     git::{
         identities::{self, local::LocalIdentity, project, relations, Project},
@@ -17,6 +19,7 @@ use librad::{
         types::{Namespace, Reference},
         Urn,
     },
+    git_ext,
     identities::{
         delegation::{indirect, Indirect},
         git::Revision,
@@ -26,9 +29,11 @@ use librad::{
     paths::Paths,
     PeerId,
 };
+use lnk_clib::rpc::client;
 
 use crate::{
     display,
+    field::HasBranch as _,
     git::{self, checkout, include},
     MissingDefaultIdentity,
 };
@@ -97,7 +102,7 @@ impl From<Option<Urn>> for WhoAmI {
 }
 
 impl WhoAmI {
-    fn resolve(self, storage: &Storage) -> Result<LocalIdentity, Error> {
+    pub fn resolve(self, storage: &Storage) -> Result<LocalIdentity, Error> {
         Ok(match self {
             Self::Default => identities::local::default(storage)?.ok_or(MissingDefaultIdentity)?,
             Self::Urn(urn) => identities::local::load(storage, urn.clone())?
@@ -160,6 +165,134 @@ where
     Ok(project)
 }
 
+/// The working copy to create a project from via [`import`].
+pub struct Import {
+    /// The path of the git repository, ie. its working directory.
+    pub path: PathBuf,
+    /// The name of the project. Defaults to the name of the directory at
+    /// `path`.
+    pub name: Option<Cstring>,
+    pub description: Option<Cstring>,
+    /// The default branch of the project. Defaults to the branch `HEAD` of the
+    /// repository points to.
+    pub default_branch: Option<Cstring>,
+}
+
+impl Import {
+    fn payload(&self, repo: &git2::Repository) -> anyhow::Result<payload::Project> {
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => self
+                .path
+                .canonicalize()?
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(Cstring::from)
+                .ok_or_else(|| anyhow!("cannot infer project name from {}", self.path.display()))?,
+        };
+        let default_branch = match &self.default_branch {
+            Some(branch) => branch.clone(),
+            None => {
+                let head = repo.head().context("cannot infer default branch")?;
+                if !head.is_branch() {
+                    bail!("`HEAD` is detached, provide the default branch explicitly");
+                }
+                head.shorthand()
+                    .map(Cstring::from)
+                    .ok_or_else(|| anyhow!("default branch is not valid UTF-8"))?
+            },
+        };
+        Ok(payload::Project {
+            name,
+            description: self.description.clone(),
+            default_branch: Some(default_branch),
+        })
+    }
+}
+
+/// Create a project from the existing git repository at [`Import::path`].
+///
+/// This creates the project identity, pushes the branches of the repository
+/// to the project's namespace, and sets up the `rad` remote of the repository.
+/// If any of these steps fail, the namespace is removed again, and the
+/// repository is left as it was.
+///
+/// The project is not announced, that is left to the caller, eg. by sending
+/// the returned [`Project`] and the tip of its `rad/signed_refs` to the
+/// node.
+#[allow(clippy::too_many_arguments)]
+pub fn import<T>(
+    storage: &Storage,
+    paths: Paths,
+    signer: BoxedSigner,
+    whoami: WhoAmI,
+    mut delegations: BTreeSet<KeyOrUrn<Revision>>,
+    import: Import,
+    ext: Vec<payload::Ext<T>>,
+) -> anyhow::Result<Project>
+where
+    T: serde::Serialize,
+{
+    let repo = git2::Repository::open(&import.path)
+        .with_context(|| format!("{} is not a git repository", import.path.display()))?;
+    let mut payload = ProjectPayload::new(import.payload(&repo)?);
+    for e in ext.into_iter() {
+        payload.set_ext(e)?;
+    }
+
+    let whoami = whoami.resolve(storage)?;
+    delegations.insert(KeyOrUrn::from(Either::Right(whoami.urn())));
+    let delegations = resolve_indirect(storage, delegations)?;
+
+    let urn = project::urn(storage, payload.clone(), delegations.clone())?;
+    if project::get(storage, &urn)?.is_some() {
+        bail!("project {} already exists", urn);
+    }
+    let url = LocalUrl::from(urn.clone());
+    // Fail early, before anything was written
+    let default_branch = payload.branch_or_default();
+    git::validation::branch(&repo, &default_branch)?;
+    let had_remote = git::validation::remote(&repo, &url)?.is_some();
+
+    let project = project::create(storage, whoami, payload, delegations)?;
+    let settings = transport::Settings {
+        paths: paths.clone(),
+        signer,
+    };
+    let res = git::setup_remote(&repo, settings, url, &default_branch)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| Ok(include::update(storage, &paths, &project)?));
+    if let Err(e) = res {
+        tracing::warn!(urn = %project.urn(), "import failed, rolling back");
+        if let Err(err) = storage.remove_namespace(&project.urn()) {
+            tracing::error!(urn = %project.urn(), err = %err, "failed to remove namespace");
+        }
+        if !had_remote {
+            if let Err(err) = repo.remote_delete("rad") {
+                tracing::error!(err = %err, "failed to remove `rad` remote");
+            }
+        }
+        return Err(e);
+    }
+
+    Ok(project)
+}
+
+/// Announce `urn` at `rev` via the node listening on the RPC socket at
+/// `socket`.
+pub fn announce(socket: PathBuf, urn: Urn, rev: git_ext::Oid) -> anyhow::Result<()> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    rt.block_on(async move {
+        let mut conn = client::Connection::connect("lnk-identities", socket).await?;
+        client::Command::announce(urn, rev)
+            .execute(&mut conn)
+            .await?;
+        Ok(())
+    })
+}
+
 pub fn get<S>(storage: &S, urn: &Urn) -> Result<Option<Project>, Error>
 where
     S: AsRef<ReadOnly>,
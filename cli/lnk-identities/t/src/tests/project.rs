@@ -0,0 +1,109 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::path::Path;
+
+use tempfile::tempdir;
+
+use git_ref_format::{lit, RefStr};
+use it_helpers::{fixed::TestPerson, git::create_commit, tmp};
+use librad::{
+    crypto::SecretKey,
+    git::{
+        local::url::LocalUrl,
+        storage::{ReadOnlyStorage as _, Storage},
+        types::{remote::Remote, Namespace, Reference},
+    },
+    identities::payload,
+    reflike,
+};
+use lnk_identities::project::{self, Import, WhoAmI};
+
+fn working_copy(path: &Path) -> anyhow::Result<git2::Repository> {
+    let mut opts = git2::RepositoryInitOptions::new();
+    opts.initial_head("trunk");
+    let repo = git2::Repository::init_opts(path, &opts)?;
+    create_commit(
+        &repo,
+        lit::refs_heads(RefStr::try_from_str("trunk")?).into(),
+    )?;
+    Ok(repo)
+}
+
+#[test]
+fn import_infers_payload() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let repo = working_copy(&temp.path().join("wrench"))?;
+
+    let paths = tmp::paths();
+    let signer = SecretKey::new();
+    let storage = Storage::open(&*paths, signer.clone())?;
+    let whoami = TestPerson::create(&storage)?.owner.urn();
+
+    let project = project::import(
+        &storage,
+        (*paths).clone(),
+        signer.into(),
+        WhoAmI::Urn(whoami),
+        Default::default(),
+        Import {
+            path: repo.workdir().unwrap().to_path_buf(),
+            name: None,
+            description: None,
+            default_branch: None,
+        },
+        Vec::<payload::Ext<()>>::new(),
+    )?;
+
+    let subject = &project.payload().subject;
+    assert_eq!(subject.name.as_str(), "wrench");
+    assert_eq!(subject.default_branch.as_ref().unwrap().as_str(), "trunk");
+
+    let urn = project.urn();
+    assert!(Remote::<LocalUrl>::find(&repo, reflike!("rad"))?.is_some());
+    assert!(storage.has_ref(&Reference::head(
+        Namespace::from(&urn),
+        None,
+        reflike!("trunk")
+    ))?);
+
+    Ok(())
+}
+
+#[test]
+fn import_leaves_no_trace_on_failure() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let repo = working_copy(&temp.path().join("wrench"))?;
+
+    let paths = tmp::paths();
+    let storage = Storage::open(&*paths, SecretKey::new())?;
+    let whoami = TestPerson::create(&storage)?.owner.urn();
+    let monorepo = git2::Repository::open(paths.git_dir())?;
+    let refs_before = monorepo.references()?.count();
+
+    // The project identity is created with the storage's key, but pushing to
+    // it with a different one fails, after the namespace has been written
+    let result = project::import(
+        &storage,
+        (*paths).clone(),
+        SecretKey::new().into(),
+        WhoAmI::Urn(whoami),
+        Default::default(),
+        Import {
+            path: repo.workdir().unwrap().to_path_buf(),
+            name: None,
+            description: None,
+            default_branch: None,
+        },
+        Vec::<payload::Ext<()>>::new(),
+    );
+
+    assert!(result.is_err());
+    assert_eq!(project::list(&storage)?.count(), 0);
+    assert_eq!(monorepo.references()?.count(), refs_before);
+    assert!(Remote::<LocalUrl>::find(&repo, reflike!("rad"))?.is_none());
+
+    Ok(())
+}
@@ -0,0 +1,548 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Merge requests on top of patches.
+//!
+//! A patch is a collaborative object of type [`TYPENAME`] within a project,
+//! proposing to merge a branch of its author into the default branch of the
+//! project. The branch itself is published under the author's namespace as
+//! `refs/heads/patches/<branch>`, so maintainers replicate it like any other
+//! branch of a tracked peer.
+//!
+//! The happy path is:
+//!
+//! 1. the author calls [`create`] from their working copy, and [`announce`]s
+//!    the result;
+//! 2. a maintainer replicates the author's view of the project, lists the open
+//!    patches with [`list`], and [`fetch`]es one into their working copy;
+//! 3. [`test_merge`] merges the patch into the default branch in a temporary
+//!    worktree, leaving the working copy alone, and runs the maintainer's
+//!    checks there;
+//! 4. if the checks pass, [`accept`] updates the default branch of the
+//!    maintainer's namespace, which is signed as part of their
+//!    `rad/signed_refs`, and marks the patch as merged.
+
+#[macro_use]
+extern crate lazy_static;
+
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom as _,
+    path::{Path, PathBuf},
+    str::FromStr as _,
+};
+
+use anyhow::{anyhow, bail, Context as _};
+use nonempty::NonEmpty;
+use serde::{Deserialize, Serialize};
+
+use librad::{
+    collaborative_objects::{
+        CollaborativeObject,
+        History,
+        NewObjectSpec,
+        ObjectId,
+        TypeName,
+        UpdateObjectSpec,
+    },
+    crypto::BoxedSigner,
+    git::{
+        identities::project,
+        local::{transport, url::LocalUrl},
+        storage::{ReadOnlyStorage as _, Storage},
+        types::{
+            remote::{LocalFetchspec, LocalPushspec, Remote},
+            Fetchspec,
+            Force,
+            Namespace,
+            Pushspec,
+            Reference,
+            Refspec,
+        },
+        Urn,
+    },
+    git_ext::{self as ext, OneLevel, RefLike},
+    paths::Paths,
+    reflike,
+    PeerId,
+};
+
+use lnk_identities::{field::HasBranch as _, git, project::WhoAmI};
+
+lazy_static! {
+    pub static ref TYPENAME: TypeName = TypeName::from_str("xyz.radicle.patch").unwrap();
+    static ref SCHEMA: serde_json::Value = serde_json::json!({
+        "$vocabulary": {
+            "https://alexjg.github.io/automerge-jsonschema/spec": true,
+        },
+        "type": "object",
+        "properties": {
+            "title": { "type": "string" },
+            "description": { "type": "string" },
+            "branch": { "type": "string" },
+            "author": { "type": "string" },
+            "base": { "type": "string" },
+            "head": { "type": "string" },
+            "state": { "type": "string" },
+            "merged": { "type": "string" },
+        },
+        "required": ["title", "description", "branch", "author", "base", "head", "state"],
+    });
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Open,
+    Merged,
+    Closed,
+}
+
+impl State {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Merged => "merged",
+            Self::Closed => "closed",
+        }
+    }
+}
+
+/// The contents of a patch object.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Doc {
+    pub title: String,
+    pub description: String,
+    /// The name of the branch in the working copy of the author. It is
+    /// published as `refs/heads/patches/<branch>`.
+    pub branch: String,
+    /// The peer publishing the branch.
+    pub author: PeerId,
+    /// The merge base of the branch and the default branch at the time the
+    /// patch was created.
+    pub base: ext::Oid,
+    /// The tip of the branch at the time the patch was created.
+    pub head: ext::Oid,
+    pub state: State,
+    /// The commit the patch was merged as, if [`State::Merged`].
+    #[serde(default)]
+    pub merged: Option<ext::Oid>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Patch {
+    pub id: ObjectId,
+    pub doc: Doc,
+}
+
+impl Patch {
+    fn from_object(object: &CollaborativeObject) -> anyhow::Result<Self> {
+        let doc = serde_json::from_value(realize(object.history())?)
+            .with_context(|| format!("malformed patch {}", object.id()))?;
+        Ok(Self {
+            id: *object.id(),
+            doc,
+        })
+    }
+
+    /// The name of the published branch, relative to the `heads` of the
+    /// author.
+    pub fn published(&self) -> anyhow::Result<RefLike> {
+        Ok(reflike!("patches").join(RefLike::try_from(self.doc.branch.as_str())?))
+    }
+}
+
+/// The outcome of [`test_merge`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Merge {
+    /// The patch merges cleanly as `commit`, and the checks `passed` or not.
+    Clean { commit: ext::Oid, passed: bool },
+    /// The patch does not merge cleanly, due to conflicts in the given paths.
+    Conflicts(Vec<PathBuf>),
+}
+
+/// Create a patch proposing to merge `branch` of the working copy `repo` into
+/// the default branch of the project `urn`.
+///
+/// `branch` is pushed to the monorepo as `refs/heads/patches/<branch>`, via
+/// the `rad` remote of `repo`. The patch is not announced, see [`announce`].
+#[allow(clippy::too_many_arguments)]
+pub fn create(
+    storage: &Storage,
+    paths: Paths,
+    signer: BoxedSigner,
+    whoami: WhoAmI,
+    repo: &git2::Repository,
+    urn: &Urn,
+    branch: &OneLevel,
+    title: String,
+    description: String,
+) -> anyhow::Result<Patch> {
+    let project =
+        project::get(storage, urn)?.ok_or_else(|| anyhow!("project {} not found", urn))?;
+    let default_branch = project.branch_or_die(urn.clone())?;
+    let whoami = whoami.resolve(storage)?;
+
+    let head = git::validation::branch(repo, branch)?
+        .peel_to_commit()?
+        .id();
+    let target = git::validation::branch(repo, &default_branch)?
+        .peel_to_commit()?
+        .id();
+    let base = repo
+        .merge_base(head, target)
+        .with_context(|| format!("`{}` is unrelated to `{}`", branch, default_branch))?;
+    if base == head {
+        bail!("`{}` has no commits not in `{}`", branch, default_branch);
+    }
+
+    let mut rad = rad_remote(repo, urn)?;
+    let settings = transport::Settings { paths, signer };
+    for pushed in rad.push(
+        settings,
+        repo,
+        LocalPushspec::Specs(NonEmpty::new(Pushspec::from(Refspec {
+            src: reflike!("refs/heads").join(branch.clone()),
+            dst: reflike!("refs/heads/patches").join(branch.clone()),
+            force: Force::True,
+        }))),
+    )? {
+        tracing::debug!("pushed patch branch `{}`", pushed);
+    }
+
+    let doc = Doc {
+        title,
+        description,
+        branch: branch.to_string(),
+        author: *storage.peer_id(),
+        base: base.into(),
+        head: head.into(),
+        state: State::Open,
+        merged: None,
+    };
+    let object = storage.collaborative_objects(None).create(
+        &whoami,
+        urn,
+        NewObjectSpec {
+            schema_json: SCHEMA.clone(),
+            history: init_history(&doc)?,
+            typename: TYPENAME.clone(),
+            message: Some(format!("Create patch `{}`", doc.title)),
+        },
+    )?;
+
+    Ok(Patch {
+        id: *object.id(),
+        doc,
+    })
+}
+
+/// Announce the local state of the project `urn`, including its patches, via
+/// the node listening on the RPC socket at `socket`.
+pub fn announce(storage: &Storage, socket: PathBuf, urn: &Urn) -> anyhow::Result<()> {
+    let rev = storage.reference_oid(&Reference::rad_signed_refs(Namespace::from(urn), None))?;
+    lnk_identities::project::announce(socket, urn.clone(), rev)
+}
+
+/// List the patches of the project `urn`, including the ones replicated from
+/// tracked peers.
+pub fn list(storage: &Storage, urn: &Urn) -> anyhow::Result<Vec<Patch>> {
+    storage
+        .collaborative_objects(None)
+        .list(urn, &TYPENAME)?
+        .iter()
+        .map(Patch::from_object)
+        .collect()
+}
+
+pub fn get(storage: &Storage, urn: &Urn, id: &ObjectId) -> anyhow::Result<Option<Patch>> {
+    storage
+        .collaborative_objects(None)
+        .retrieve(urn, &TYPENAME, id)?
+        .as_ref()
+        .map(Patch::from_object)
+        .transpose()
+}
+
+/// Fetch the branch of `patch` from the monorepo into the working copy
+/// `repo`, as `refs/remotes/rad/patches/<id>`.
+///
+/// The author must be tracked, and their view of the project replicated, for
+/// the branch to be present in the monorepo.
+pub fn fetch(
+    paths: Paths,
+    signer: BoxedSigner,
+    repo: &git2::Repository,
+    urn: &Urn,
+    patch: &Patch,
+) -> anyhow::Result<ext::Oid> {
+    let published = patch.published()?;
+    let src = if patch.doc.author == PeerId::from_signer(&signer) {
+        reflike!("refs/heads").join(published)
+    } else {
+        reflike!("refs/remotes")
+            .join(patch.doc.author)
+            .join(reflike!("heads"))
+            .join(published)
+    };
+    let dst = reflike!("refs/remotes/rad/patches").join(RefLike::try_from(patch.id.to_string())?);
+
+    let mut rad = rad_remote(repo, urn)?;
+    let fetched = rad
+        .fetch(
+            transport::Settings { paths, signer },
+            repo,
+            LocalFetchspec::Specs(NonEmpty::new(Fetchspec::from(Refspec {
+                src,
+                dst: dst.clone(),
+                force: Force::True,
+            }))),
+        )?
+        .find(|(name, _)| *name == dst)
+        .map(|(_, oid)| oid)
+        .ok_or_else(|| anyhow!("branch of patch {} not found", patch.id))?;
+
+    Ok(fetched.into())
+}
+
+/// Merge `patch` into `target` of the working copy `repo`, and run `check` in
+/// a temporary worktree checked out at the result.
+///
+/// The patch must have been [`fetch`]ed before. Neither the working copy, nor
+/// any of its branches is modified: the merge commit is only reachable from a
+/// temporary branch, which is removed along with the worktree once `check`
+/// returns. Use [`accept`] to update `target`.
+pub fn test_merge<F>(
+    repo: &git2::Repository,
+    patch: &Patch,
+    target: &OneLevel,
+    check: F,
+) -> anyhow::Result<Merge>
+where
+    F: FnOnce(&Path) -> anyhow::Result<bool>,
+{
+    let ours = git::validation::branch(repo, target)?.peel_to_commit()?;
+    let theirs = repo
+        .find_commit(*patch.doc.head)
+        .with_context(|| format!("head of patch {} not found, fetch it first", patch.id))?;
+
+    let commit = if ours.id() == theirs.id() || repo.graph_descendant_of(ours.id(), theirs.id())? {
+        bail!("patch {} is already merged into `{}`", patch.id, target)
+    } else if repo.graph_descendant_of(theirs.id(), ours.id())? {
+        theirs.id()
+    } else {
+        let mut index = repo.merge_commits(&ours, &theirs, None)?;
+        if index.has_conflicts() {
+            let mut paths = BTreeSet::new();
+            for conflict in index.conflicts()? {
+                let conflict = conflict?;
+                for entry in vec![conflict.ancestor, conflict.our, conflict.their]
+                    .into_iter()
+                    .flatten()
+                {
+                    paths.insert(PathBuf::from(String::from_utf8_lossy(&entry.path).as_ref()));
+                }
+            }
+            return Ok(Merge::Conflicts(paths.into_iter().collect()));
+        }
+        let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+        let sig = repo.signature()?;
+        repo.commit(
+            None,
+            &sig,
+            &sig,
+            &format!("Merge patch `{}`\n\n{}", patch.doc.title, patch.id),
+            &tree,
+            &[&ours, &theirs],
+        )?
+    };
+
+    let name = format!("rad-patch-{}", patch.id);
+    let tmp = tempfile::tempdir()?;
+    let branch = repo.branch(&name, &repo.find_commit(commit)?, true)?;
+    let passed = {
+        let mut opts = git2::WorktreeAddOptions::new();
+        opts.reference(Some(branch.get()));
+        let worktree = repo.worktree(&name, &tmp.path().join(&name), Some(&opts))?;
+        let passed = check(worktree.path());
+        if let Err(e) = worktree.prune(Some(
+            git2::WorktreePruneOptions::new()
+                .valid(true)
+                .working_tree(true),
+        )) {
+            tracing::warn!(err = %e, "failed to prune worktree `{}`", name);
+        }
+        passed
+    };
+    if let Err(e) = repo
+        .find_branch(&name, git2::BranchType::Local)
+        .and_then(|mut branch| branch.delete())
+    {
+        tracing::warn!(err = %e, "failed to delete branch `{}`", name);
+    }
+
+    Ok(Merge::Clean {
+        commit: commit.into(),
+        passed: passed?,
+    })
+}
+
+/// Accept `patch` as merged into the default branch of the project `urn`, at
+/// `commit`, as obtained from [`test_merge`].
+///
+/// The default branch of the working copy `repo` is fast-forwarded to
+/// `commit`, and pushed to the monorepo, which updates the signed refs of the
+/// local peer. If the default branch is checked out, the working tree is
+/// updated, too, unless that would overwrite local changes. The patch is then
+/// marked as [`State::Merged`]. Only delegates of the project may accept
+/// patches.
+#[allow(clippy::too_many_arguments)]
+pub fn accept(
+    storage: &Storage,
+    paths: Paths,
+    signer: BoxedSigner,
+    whoami: WhoAmI,
+    repo: &git2::Repository,
+    urn: &Urn,
+    patch: &Patch,
+    commit: ext::Oid,
+) -> anyhow::Result<Patch> {
+    let project =
+        project::get(storage, urn)?.ok_or_else(|| anyhow!("project {} not found", urn))?;
+    let local = storage.peer_id();
+    if project
+        .delegations()
+        .eligible(Some(local.as_public_key()).into_iter().collect())?
+        .is_empty()
+    {
+        bail!("{} is not a delegate of {}", local, urn);
+    }
+    if patch.doc.state != State::Open {
+        bail!("patch {} is {}", patch.id, patch.doc.state.as_str());
+    }
+    let default_branch = project.branch_or_die(urn.clone())?;
+    let whoami = whoami.resolve(storage)?;
+
+    let mut target = git::validation::branch(repo, &default_branch)?;
+    let current = target.peel_to_commit()?.id();
+    if !repo.graph_descendant_of(*commit, current)? {
+        bail!("{} is not a fast-forward of `{}`", commit, default_branch);
+    }
+    if target.is_head() && !repo.is_bare() {
+        repo.checkout_tree(
+            repo.find_commit(*commit)?.as_object(),
+            Some(git2::build::CheckoutBuilder::new().safe()),
+        )
+        .with_context(|| {
+            format!(
+                "failed to check out {}, `{}` has local changes",
+                commit, default_branch
+            )
+        })?;
+    }
+    target.set_target(*commit, &format!("rad: accept patch {}", patch.id))?;
+
+    let mut rad = rad_remote(repo, urn)?;
+    let qualified = reflike!("refs/heads").join(default_branch);
+    for pushed in rad.push(
+        transport::Settings { paths, signer },
+        repo,
+        LocalPushspec::Specs(NonEmpty::new(Pushspec::from(Refspec {
+            src: qualified.clone(),
+            dst: qualified,
+            force: Force::False,
+        }))),
+    )? {
+        tracing::debug!("pushed `{}`", pushed);
+    }
+
+    let object = storage
+        .collaborative_objects(None)
+        .retrieve(urn, &TYPENAME, &patch.id)?
+        .ok_or_else(|| anyhow!("patch {} not found", patch.id))?;
+    let object = storage.collaborative_objects(None).update(
+        &whoami,
+        urn,
+        UpdateObjectSpec {
+            object_id: patch.id,
+            typename: TYPENAME.clone(),
+            message: Some(format!("Merge patch `{}`", patch.doc.title)),
+            changes: set(
+                object.history(),
+                &[
+                    ("state", State::Merged.as_str().to_owned()),
+                    ("merged", commit.to_string()),
+                ],
+            )?,
+        },
+    )?;
+
+    Patch::from_object(&object)
+}
+
+fn rad_remote(repo: &git2::Repository, urn: &Urn) -> anyhow::Result<Remote<LocalUrl>> {
+    git::validation::remote(repo, &LocalUrl::from(urn.clone()))?
+        .ok_or_else(|| anyhow!("the working copy has no `rad` remote for {}", urn))
+}
+
+fn init_history(doc: &Doc) -> anyhow::Result<History> {
+    let fields = [
+        ("title", doc.title.clone()),
+        ("description", doc.description.clone()),
+        ("branch", doc.branch.clone()),
+        ("author", doc.author.to_string()),
+        ("base", doc.base.to_string()),
+        ("head", doc.head.to_string()),
+        ("state", doc.state.as_str().to_owned()),
+    ];
+    apply(
+        &mut automerge::Backend::new(),
+        &mut automerge::Frontend::new(),
+        &fields,
+    )
+}
+
+fn set(history: &History, fields: &[(&str, String)]) -> anyhow::Result<History> {
+    match history {
+        History::Automerge(bytes) => {
+            let mut backend = automerge::Backend::load(bytes.to_vec())?;
+            let mut frontend = automerge::Frontend::new();
+            frontend.apply_patch(backend.get_patch()?)?;
+            apply(&mut backend, &mut frontend, fields)
+        },
+    }
+}
+
+/// Set the string `fields` of the document, returning the change as a
+/// [`History`] to append.
+fn apply(
+    backend: &mut automerge::Backend,
+    frontend: &mut automerge::Frontend,
+    fields: &[(&str, String)],
+) -> anyhow::Result<History> {
+    let (_, change) = frontend.change::<_, _, automerge::InvalidChangeRequest>(None, |d| {
+        for (key, value) in fields {
+            d.add_change(automerge::LocalChange::set(
+                automerge::Path::root().key(*key),
+                automerge::Value::from(value.as_str()),
+            ))?;
+        }
+        Ok(())
+    })?;
+    let change = change.ok_or_else(|| anyhow!("empty patch change"))?;
+    let (_, change) = backend.apply_local_change(change)?;
+    Ok(History::Automerge(change.raw_bytes().to_vec()))
+}
+
+fn realize(history: &History) -> anyhow::Result<serde_json::Value> {
+    match history {
+        History::Automerge(bytes) => {
+            let backend = automerge::Backend::load(bytes.to_vec())?;
+            let mut frontend = automerge::Frontend::new();
+            frontend.apply_patch(backend.get_patch()?)?;
+            Ok(frontend.state().to_json())
+        },
+    }
+}
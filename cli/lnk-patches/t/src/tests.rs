@@ -0,0 +1,160 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::path::Path;
+
+use tempfile::tempdir;
+
+use git_ref_format::{lit, RefStr};
+use it_helpers::{fixed::TestPerson, git::create_commit, tmp};
+use librad::{
+    crypto::SecretKey,
+    git::{
+        storage::{ReadOnlyStorage as _, Storage},
+        types::{Namespace, Reference},
+    },
+    git_ext::OneLevel,
+    identities::payload,
+    reflike,
+};
+use lnk_identities::project::{self, Import, WhoAmI};
+use lnk_patches::{self as patch, Merge, State};
+
+fn working_copy(path: &Path) -> anyhow::Result<git2::Repository> {
+    let mut opts = git2::RepositoryInitOptions::new();
+    opts.initial_head("trunk");
+    let repo = git2::Repository::init_opts(path, &opts)?;
+    create_commit(
+        &repo,
+        lit::refs_heads(RefStr::try_from_str("trunk")?).into(),
+    )?;
+    Ok(repo)
+}
+
+fn commit_file(repo: &git2::Repository, branch: &str, file: &str) -> anyhow::Result<git2::Oid> {
+    let parent = repo
+        .find_branch(branch, git2::BranchType::Local)?
+        .get()
+        .peel_to_commit()?;
+    let tree = {
+        let mut builder = repo.treebuilder(Some(&parent.tree()?))?;
+        builder.insert(file, repo.blob(file.as_bytes())?, 0o100644)?;
+        repo.find_tree(builder.write()?)?
+    };
+    let author = git2::Signature::now("The Animal", "animal@muppets.com")?;
+    Ok(repo.commit(
+        Some(&format!("refs/heads/{}", branch)),
+        &author,
+        &author,
+        &format!("Add {}", file),
+        &tree,
+        &[&parent],
+    )?)
+}
+
+#[test]
+fn happy_path() -> anyhow::Result<()> {
+    let temp = tempdir()?;
+    let repo = working_copy(&temp.path().join("wrench"))?;
+
+    let paths = tmp::paths();
+    let signer = SecretKey::new();
+    let storage = Storage::open(&*paths, signer.clone())?;
+    let whoami = TestPerson::create(&storage)?.owner.urn();
+
+    let project = project::import(
+        &storage,
+        (*paths).clone(),
+        signer.clone().into(),
+        WhoAmI::Urn(whoami.clone()),
+        Default::default(),
+        Import {
+            path: repo.workdir().unwrap().to_path_buf(),
+            name: None,
+            description: None,
+            default_branch: None,
+        },
+        Vec::<payload::Ext<()>>::new(),
+    )?;
+    let urn = project.urn();
+
+    repo.branch("feature", &repo.head()?.peel_to_commit()?, false)?;
+    let head = commit_file(&repo, "feature", "README")?;
+
+    let created = patch::create(
+        &storage,
+        (*paths).clone(),
+        signer.clone().into(),
+        WhoAmI::Urn(whoami.clone()),
+        &repo,
+        &urn,
+        &OneLevel::from(reflike!("feature")),
+        "Add a README".to_owned(),
+        "Everyone needs one".to_owned(),
+    )?;
+    assert_eq!(*created.doc.head, head);
+    assert!(storage.has_ref(&Reference::head(
+        Namespace::from(&urn),
+        None,
+        reflike!("patches/feature")
+    ))?);
+
+    let listed = patch::list(&storage, &urn)?;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].doc, created.doc);
+
+    let fetched = patch::fetch(
+        (*paths).clone(),
+        signer.clone().into(),
+        &repo,
+        &urn,
+        &created,
+    )?;
+    assert_eq!(*fetched, head);
+
+    let trunk = OneLevel::from(reflike!("trunk"));
+    let merge = patch::test_merge(&repo, &created, &trunk, |path| {
+        Ok(path.join("README").exists())
+    })?;
+    let commit = match merge {
+        Merge::Clean {
+            commit,
+            passed: true,
+        } => commit,
+        other => panic!("unexpected merge outcome: {:?}", other),
+    };
+    assert!(repo
+        .find_branch(
+            &format!("rad-patch-{}", created.id),
+            git2::BranchType::Local
+        )
+        .is_err());
+
+    let accepted = patch::accept(
+        &storage,
+        (*paths).clone(),
+        signer.into(),
+        WhoAmI::Urn(whoami),
+        &repo,
+        &urn,
+        &created,
+        commit,
+    )?;
+    assert_eq!(accepted.doc.state, State::Merged);
+    assert_eq!(accepted.doc.merged, Some(commit));
+    assert_eq!(
+        storage.reference_oid(&Reference::head(
+            Namespace::from(&urn),
+            None,
+            reflike!("trunk")
+        ))?,
+        commit
+    );
+    // `trunk` is checked out
+    assert_eq!(repo.head()?.peel_to_commit()?.id(), *commit);
+    assert!(repo.workdir().unwrap().join("README").exists());
+
+    Ok(())
+}
@@ -3,6 +3,8 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+use std::path::PathBuf;
+
 use clap::Parser;
 
 use librad::profile::ProfileId;
@@ -19,6 +21,8 @@ pub struct Args {
 pub enum Command {
     Create(Create),
     Get(Get),
+    Unlock(Unlock),
+    Lock(Lock),
     Set(Set),
     List(List),
     Peer(GetPeerId),
@@ -29,7 +33,16 @@ pub enum Command {
 /// Create a new profile, generating a new secret key and initialising
 /// configurations and storage.
 #[derive(Debug, Parser)]
-pub struct Create {}
+pub struct Create {
+    /// keep the storage encrypted at rest, using gocryptfs with a passphrase
+    /// derived from the secret key
+    #[clap(long)]
+    pub encrypt_storage: bool,
+    /// where to keep the encrypted storage, defaults to a directory next to
+    /// the storage
+    #[clap(long, requires = "encrypt-storage")]
+    pub cipher_dir: Option<PathBuf>,
+}
 
 /// Get a profile, defaulting to the active profile if no identifier is given.
 #[derive(Debug, Parser)]
@@ -39,6 +52,24 @@ pub struct Get {
     pub id: Option<ProfileId>,
 }
 
+/// Unlock the encrypted storage of a profile, using its secret key from the
+/// ssh-agent. If no profile was provided, then the active one is used.
+#[derive(Debug, Parser)]
+pub struct Unlock {
+    /// the identifier of the profile to unlock
+    #[clap(long)]
+    pub id: Option<ProfileId>,
+}
+
+/// Lock the encrypted storage of a profile. If no profile was provided, then
+/// the active one is used.
+#[derive(Debug, Parser)]
+pub struct Lock {
+    /// the identifier of the profile to lock
+    #[clap(long)]
+    pub id: Option<ProfileId>,
+}
+
 /// Set the active profile.
 #[derive(Debug, Parser)]
 pub struct Set {
@@ -100,6 +131,11 @@ pub mod ssh {
         /// `ssh-agent` this is forever).
         #[clap(long, short)]
         pub time: Option<u32>,
+        /// unlock the key with the passphrase stored in the keychain of the
+        /// operating system, prompting for it and storing it there if it is
+        /// not found.
+        #[clap(long)]
+        pub keychain: bool,
     }
 
     /// Remove the profile's associated secret key from the ssh-agent. If no
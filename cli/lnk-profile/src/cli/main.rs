@@ -7,13 +7,15 @@ use std::convert::TryInto as _;
 
 use lnk_thrussh_agent::Constraint;
 
-use librad::crypto::keystore::sign;
+use librad::{crypto::keystore::sign, git::storage::encryption, profile::ProfileId};
 use lnk_clib::keys::{self, ssh::SshAuthSock};
 
 use crate::{
     create,
+    create_encrypted,
     get,
     list,
+    lock,
     paths,
     peer_id,
     set,
@@ -22,6 +24,7 @@ use crate::{
     ssh_remove,
     ssh_sign,
     ssh_verify,
+    unlock,
 };
 
 use super::args::*;
@@ -30,13 +33,50 @@ pub fn main(Args { command }: Args, sock: SshAuthSock) -> anyhow::Result<()> {
     eval(sock, command)
 }
 
+#[cfg(feature = "keychain")]
+fn ssh_add_keychain(
+    id: Option<ProfileId>,
+    sock: SshAuthSock,
+    constraints: &[Constraint],
+) -> anyhow::Result<ProfileId> {
+    Ok(crate::ssh_add_keychain(None, id, sock, constraints)?)
+}
+
+#[cfg(not(feature = "keychain"))]
+fn ssh_add_keychain(
+    _id: Option<ProfileId>,
+    _sock: SshAuthSock,
+    _constraints: &[Constraint],
+) -> anyhow::Result<ProfileId> {
+    anyhow::bail!("`--keychain` is not supported, lnk was built without the `keychain` feature")
+}
+
 fn eval(sock: SshAuthSock, command: Command) -> anyhow::Result<()> {
     match command {
-        Command::Create(Create {}) => {
-            let (profile, peer_id) = create(None, keys::prompt::new())?;
+        Command::Create(Create {
+            encrypt_storage,
+            cipher_dir,
+        }) => {
+            let (profile, peer_id) = if encrypt_storage {
+                create_encrypted(None, keys::prompt::new(), |paths| {
+                    encryption::Config::gocryptfs(
+                        cipher_dir.unwrap_or_else(|| encryption::default_cipher_dir(paths)),
+                    )
+                })?
+            } else {
+                create(None, keys::prompt::new())?
+            };
             println!("profile id: {}", profile.id());
             println!("peer id: {}", peer_id);
         },
+        Command::Unlock(Unlock { id }) => {
+            let id = unlock(None, id, sock)?;
+            println!("unlocked storage of profile id `{}`", id);
+        },
+        Command::Lock(Lock { id }) => {
+            let id = lock(None, id)?;
+            println!("locked storage of profile id `{}`", id);
+        },
         Command::Get(Get { id }) => {
             let profile = get(None, id)?;
             match profile {
@@ -67,10 +107,14 @@ fn eval(sock: SshAuthSock, command: Command) -> anyhow::Result<()> {
             println!("keys: {}", paths.keys_dir().display());
         },
         Command::Ssh(Ssh { options }) => match options {
-            ssh::Options::Add(ssh::Add { id, time }) => {
+            ssh::Options::Add(ssh::Add { id, time, keychain }) => {
                 let constraints =
                     time.map_or(vec![], |seconds| vec![Constraint::KeyLifetime { seconds }]);
-                let id = ssh_add(None, id, sock, keys::prompt::new(), &constraints)?;
+                let id = if keychain {
+                    ssh_add_keychain(id, sock, &constraints)?
+                } else {
+                    ssh_add(None, id, sock, keys::prompt::new(), &constraints)?
+                };
                 println!("added key for profile id `{}`", id);
             },
             ssh::Options::Rm(ssh::Rm { id }) => {
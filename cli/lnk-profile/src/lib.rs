@@ -17,7 +17,7 @@ use librad::{
         PublicKey,
         SecretKey,
     },
-    git::storage::{self, read, ReadOnly, Storage},
+    git::storage::{self, encryption, read, ReadOnly, Storage},
     paths::Paths,
     profile::{self, LnkHome, Profile, ProfileId},
     Signature,
@@ -32,6 +32,10 @@ pub enum Error {
     #[error(transparent)]
     AddKey(#[from] keys::ssh::Error),
     #[error(transparent)]
+    Encryption(#[from] encryption::Error),
+    #[error(transparent)]
+    Keychain(#[from] keys::keychain::Error),
+    #[error(transparent)]
     Keystore(Box<dyn error::Error + Send + Sync + 'static>),
     #[error("no active profile was found, perhaps you need to create one")]
     NoActiveProfile,
@@ -66,6 +70,38 @@ where
 
 /// Initialise a [`Profile`], generating a new [`SecretKey`] and [`Storage`].
 pub fn create<H, C: Crypto>(home: H, crypto: C) -> Result<(Profile, PeerId), Error>
+where
+    H: Into<Option<LnkHome>>,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    init(home, crypto, None)
+}
+
+/// Initialise a [`Profile`] like [`create`], but keep its [`Storage`]
+/// encrypted at rest, using the [`encryption::Config`] returned by
+/// `encryption`.
+pub fn create_encrypted<H, C: Crypto, F>(
+    home: H,
+    crypto: C,
+    encryption: F,
+) -> Result<(Profile, PeerId), Error>
+where
+    H: Into<Option<LnkHome>>,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+    F: FnOnce(&Paths) -> encryption::Config,
+{
+    init(home, crypto, Some(Box::new(encryption)))
+}
+
+type EncryptionConfig<'a> = Box<dyn FnOnce(&Paths) -> encryption::Config + 'a>;
+
+fn init<H, C: Crypto>(
+    home: H,
+    crypto: C,
+    encryption: Option<EncryptionConfig>,
+) -> Result<(Profile, PeerId), Error>
 where
     H: Into<Option<LnkHome>>,
     C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
@@ -77,11 +113,43 @@ where
     let key = SecretKey::new();
     let mut store: FileStorage<C, PublicKey, SecretKey, _> = keys::file_storage(&profile, crypto);
     store.put_key(key.clone())?;
+    if let Some(config) = encryption {
+        let paths = profile.paths();
+        encryption::setup(paths, config(paths), &encryption::derive_key(&key)?)?;
+    }
     Storage::open(profile.paths(), key.clone())?;
 
     Ok((profile, PeerId::from(key)))
 }
 
+/// Unlock the encrypted [`Storage`] of a profile, using its [`SecretKey`]
+/// from the `ssh-agent`.
+pub fn unlock<H, P>(home: H, id: P, sock: SshAuthSock) -> Result<ProfileId, Error>
+where
+    H: Into<Option<LnkHome>>,
+    P: Into<Option<ProfileId>>,
+{
+    let home = home.into().unwrap_or_default();
+    let profile = get_or_active(&home, id)?;
+    if encryption::status(profile.paths())? == encryption::Status::Locked {
+        let signer = keys::ssh::signer(&profile, sock)?;
+        encryption::unlock(profile.paths(), &encryption::derive_key(&signer)?)?;
+    }
+    Ok(profile.id().clone())
+}
+
+/// Lock the encrypted [`Storage`] of a profile.
+pub fn lock<H, P>(home: H, id: P) -> Result<ProfileId, Error>
+where
+    H: Into<Option<LnkHome>>,
+    P: Into<Option<ProfileId>>,
+{
+    let home = home.into().unwrap_or_default();
+    let profile = get_or_active(&home, id)?;
+    encryption::lock(profile.paths())?;
+    Ok(profile.id().clone())
+}
+
 /// Get the current active `ProfileId`.
 pub fn get<H>(home: H, id: Option<ProfileId>) -> Result<Option<Profile>, Error>
 where
@@ -154,6 +222,27 @@ where
     Ok(profile.id().clone())
 }
 
+/// Add a profile's [`SecretKey`] to the `ssh-agent`, unlocking the key storage
+/// with the passphrase from the keychain of the operating system, see
+/// [`keys::keychain`].
+#[cfg(feature = "keychain")]
+pub fn ssh_add_keychain<H, P>(
+    home: H,
+    id: P,
+    sock: SshAuthSock,
+    constraints: &[Constraint],
+) -> Result<ProfileId, Error>
+where
+    H: Into<Option<LnkHome>>,
+    P: Into<Option<ProfileId>>,
+{
+    let home = home.into().unwrap_or_default();
+    let profile = get_or_active(&home, id)?;
+    let key = keys::keychain::os::secret_key(&profile)?;
+    keys::ssh::add_key(sock, key, constraints)?;
+    Ok(profile.id().clone())
+}
+
 /// Remove a profile's [`SecretKey`] from the `ssh-agent`.
 pub fn ssh_remove<H, P, C>(home: H, id: P, sock: SshAuthSock, crypto: C) -> Result<ProfileId, Error>
 where
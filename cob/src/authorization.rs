@@ -0,0 +1,126 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Per-type authorization rules for collaborative objects.
+//!
+//! By default, only the maintainers of a project may create or update objects
+//! within it. A project can relax this for individual object types by adding
+//! a [`Rules`] extension to its payload, for example:
+//!
+//! ```json
+//! "https://radicle.xyz/link/cob/authorization/v1": {
+//!   "types": {
+//!     "xyz.radicle.issue": { "create": "anyone", "update": "tracked" }
+//!   }
+//! }
+//! ```
+//!
+//! The rules are taken from the revision of the project identity a change
+//! refers to, just like the set of maintainers. Changes by an author who is
+//! not authorized are rejected, along with all their descendants, when the
+//! change graph of an object is evaluated, and reported to
+//! [`crate::IdentityStorage::unauthorized`].
+
+use std::collections::BTreeMap;
+
+use link_identities::{
+    git::Urn,
+    payload::{Extension, HasNamespace, Kind},
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{ObjectId, TypeName};
+
+lazy_static::lazy_static! {
+    static ref RULES_NAMESPACE_V1: Url =
+        Url::parse("https://radicle.xyz/link/cob/authorization/v1").unwrap();
+}
+
+/// Who may perform an [`Action`] on objects of a type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// Only the maintainers of the project.
+    Delegates,
+    /// The maintainers, and anyone whose peer is tracked within the project,
+    /// as determined by [`crate::IdentityStorage::is_tracked`].
+    Tracked,
+    /// Any verified person.
+    Anyone,
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Self::Delegates
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// The initial change of an object.
+    Create,
+    /// Any subsequent change.
+    Update,
+}
+
+/// The roles required to create and update objects of a particular type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rule {
+    #[serde(default)]
+    pub create: Role,
+    #[serde(default)]
+    pub update: Role,
+}
+
+impl Rule {
+    pub fn role(&self, action: Action) -> Role {
+        match action {
+            Action::Create => self.create,
+            Action::Update => self.update,
+        }
+    }
+}
+
+/// Payload extension declaring the [`Rule`]s of a project.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rules {
+    /// Object types not listed here use [`Rule::default`].
+    pub types: BTreeMap<TypeName, Rule>,
+}
+
+impl Rules {
+    pub fn rule(&self, typename: &TypeName) -> Rule {
+        self.types.get(typename).copied().unwrap_or_default()
+    }
+}
+
+impl HasNamespace for Rules {
+    fn namespace() -> &'static Url {
+        &RULES_NAMESPACE_V1
+    }
+}
+
+impl Extension for Rules {
+    fn applies_to(kind: Kind) -> bool {
+        kind == Kind::Project
+    }
+}
+
+/// A change which was rejected because its author was not authorized to make
+/// it.
+#[derive(Clone, Debug)]
+pub struct Unauthorized<'a> {
+    /// The identity the object is stored in.
+    pub identity: &'a Urn,
+    pub typename: &'a TypeName,
+    pub object: ObjectId,
+    /// The commit of the rejected change.
+    pub change: git2::Oid,
+    /// The URN of the author of the change.
+    pub author: Urn,
+    pub action: Action,
+    pub reason: &'static str,
+}
@@ -8,6 +8,8 @@ use link_identities::{git::Urn, Person, Project, VerifiedPerson, VerifiedProject
 
 use std::collections::BTreeSet;
 
+use super::authorization::Rules;
+
 pub enum AuthDecision {
     Authorized,
     NotAuthorized { reason: &'static str },
@@ -21,6 +23,11 @@ pub trait AuthorizingIdentity: std::fmt::Debug {
     fn check_authorization(&self, principal: &VerifiedPerson) -> AuthDecision;
     /// The OID of the tip of this identity
     fn content_id(&self) -> git2::Oid;
+    /// The rules determining who, besides the principals accepted by
+    /// `check_authorization`, may make changes to COBs in this identity
+    fn authorization_rules(&self) -> Rules {
+        Rules::default()
+    }
 }
 
 impl AuthorizingIdentity for VerifiedPerson {
@@ -65,6 +72,16 @@ impl AuthorizingIdentity for VerifiedProject {
     fn content_id(&self) -> git2::Oid {
         self.content_id.into()
     }
+
+    fn authorization_rules(&self) -> Rules {
+        match self.payload().get_ext::<Rules>() {
+            Ok(rules) => rules.unwrap_or_default(),
+            Err(e) => {
+                tracing::warn!(urn=%self.urn(), err=%e, "malformed cob authorization rules, only maintainers may make changes");
+                Rules::default()
+            },
+        }
+    }
 }
 
 fn is_maintainer(project: &VerifiedProject, person: &VerifiedPerson) -> bool {
@@ -0,0 +1,106 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Clock-free ordering of changes.
+//!
+//! Each change records the commits of the changes it causally depends on in
+//! the `dependencies` blob of its tree. As the tree is what the author signs,
+//! the dependencies can't be altered without invalidating the change, unlike
+//! the parents of the commit, which also point at the schema and identity
+//! commits. When loading a change graph, only the declared dependencies are
+//! taken as edges, and a change declaring a dependency which is not one of its
+//! commit parents is rejected.
+//!
+//! Changes are evaluated in the order given by [`order`]: a topological order
+//! of the dependency graph in which ties are broken by comparing the change
+//! ids. Commit timestamps play no part in it, so a device with a wrong clock
+//! can't move its changes ahead of others, and all peers which have the same
+//! changes evaluate them in the same order, whichever order they received
+//! them in.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use thiserror::Error;
+
+/// The name of the blob recording the dependencies of a change.
+pub(crate) const DEPENDENCIES_BLOB_NAME: &str = "dependencies";
+
+#[derive(Debug, Error)]
+#[error("causal dependencies form a cycle through {} changes", .0.len())]
+pub struct Cycle<K: std::fmt::Debug>(pub Vec<K>);
+
+#[derive(Debug, Error)]
+pub enum Decode {
+    #[error("dependencies are not valid UTF-8")]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error(transparent)]
+    Oid(#[from] git2::Error),
+}
+
+/// Order the keys of `dependencies`, such that each key comes after the keys
+/// it depends on, breaking ties by the order of the keys.
+///
+/// Dependencies on keys which are not in `dependencies` are ignored, eg.
+/// because they were not replicated yet. If the dependencies contain a
+/// cycle, the keys which could not be ordered are returned as a [`Cycle`].
+pub fn order<K>(dependencies: &BTreeMap<K, BTreeSet<K>>) -> Result<Vec<K>, Cycle<K>>
+where
+    K: Clone + Ord + std::fmt::Debug,
+{
+    let mut pending = BTreeMap::new();
+    let mut dependents = BTreeMap::<&K, Vec<&K>>::new();
+    for (key, deps) in dependencies {
+        let known = deps
+            .iter()
+            .filter(|dep| *dep != key && dependencies.contains_key(*dep))
+            .collect::<Vec<_>>();
+        for dep in &known {
+            dependents.entry(*dep).or_default().push(key);
+        }
+        pending.insert(key, known.len());
+    }
+
+    let mut ready = pending
+        .iter()
+        .filter_map(|(key, n)| (*n == 0).then(|| *key))
+        .collect::<BTreeSet<_>>();
+    let mut ordered = Vec::with_capacity(dependencies.len());
+    while let Some(key) = ready.iter().next().copied() {
+        ready.remove(key);
+        pending.remove(key);
+        ordered.push(key.clone());
+        for dependent in dependents.get(key).into_iter().flatten() {
+            if let Some(n) = pending.get_mut(*dependent) {
+                *n -= 1;
+                if *n == 0 {
+                    ready.insert(*dependent);
+                }
+            }
+        }
+    }
+
+    if pending.is_empty() {
+        Ok(ordered)
+    } else {
+        Err(Cycle(pending.into_keys().cloned().collect()))
+    }
+}
+
+/// Encode the dependencies of a change, one hex-encoded commit id per line.
+pub(crate) fn encode(dependencies: &BTreeSet<git2::Oid>) -> Vec<u8> {
+    dependencies
+        .iter()
+        .map(|oid| format!("{}\n", oid))
+        .collect::<String>()
+        .into_bytes()
+}
+
+pub(crate) fn decode(bytes: &[u8]) -> Result<BTreeSet<git2::Oid>, Decode> {
+    std::str::from_utf8(bytes)?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| git2::Oid::from_str(line).map_err(Decode::from))
+        .collect()
+}
@@ -4,6 +4,7 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 use super::{
+    causal,
     change_metadata::{self, ChangeMetadata, CreateMetadataArgs},
     trailers,
     History,
@@ -13,7 +14,7 @@ use super::{
 
 use link_crypto::BoxedSigner;
 
-use std::{convert::TryFrom, fmt};
+use std::{collections::BTreeSet, convert::TryFrom, fmt};
 
 use serde::{Deserialize, Serialize};
 
@@ -25,10 +26,16 @@ pub struct Change {
     schema_commit: git2::Oid,
     /// The manifest
     manifest: Manifest,
-    /// The actual changes this change carries
-    history: History,
+    /// The actual changes this change carries, `None` if the content has been
+    /// pruned from the repository, eg. after it was redacted
+    history: Option<History>,
+    /// The OID of the blob holding the content of this change
+    content: git2::Oid,
     /// The metadata for this change
     metadata: change_metadata::ChangeMetadata,
+    /// The changes this change causally depends on, `None` for changes made
+    /// before dependencies were recorded
+    dependencies: Option<BTreeSet<git2::Oid>>,
 }
 
 impl fmt::Display for Change {
@@ -38,7 +45,7 @@ impl fmt::Display for Change {
 }
 
 pub mod error {
-    use super::{change_metadata, trailers};
+    use super::{causal, change_metadata, trailers};
     use link_crypto::BoxedSignError;
     use link_identities::git::error::Signatures;
     use thiserror::Error;
@@ -73,6 +80,12 @@ pub mod error {
         InvalidMetadata(#[from] change_metadata::LoadError),
         #[error(transparent)]
         SchemaCommitTrailer(#[from] trailers::error::InvalidSchemaTrailer),
+        #[error("./dependencies was not a blob")]
+        DependenciesNotBlob,
+        #[error("invalid dependencies: {0}")]
+        InvalidDependencies(#[from] causal::Decode),
+        #[error("dependency {0} is not a parent of the change commit")]
+        DependencyNotParent(git2::Oid),
     }
 }
 
@@ -115,6 +128,14 @@ impl Change {
         let change_blob = repo.blob(spec.history.as_bytes())?;
         tb.insert(CHANGE_BLOB_NAME, change_blob, git2::FileMode::Blob.into())?;
 
+        let dependencies = spec.tips.iter().flatten().copied().collect::<BTreeSet<_>>();
+        let dependencies_blob = repo.blob(&causal::encode(&dependencies))?;
+        tb.insert(
+            causal::DEPENDENCIES_BLOB_NAME,
+            dependencies_blob,
+            git2::FileMode::Blob.into(),
+        )?;
+
         let revision = tb.write()?;
 
         let schema_trailer = trailers::SchemaCommitTrailer::from(spec.schema_commit).into();
@@ -137,8 +158,10 @@ impl Change {
         Ok(Change {
             schema_commit: spec.schema_commit,
             manifest,
-            history: spec.history,
+            history: Some(spec.history),
+            content: change_blob,
             metadata,
+            dependencies: Some(dependencies),
         })
     }
 
@@ -157,25 +180,52 @@ impl Change {
         let manifest: Manifest =
             toml::de::from_slice(manifest_blob.content()).map_err(error::Load::InvalidManifest)?;
 
-        let history = match manifest.history_type {
-            HistoryType::Automerge => {
-                let history_tree_entry = tree
-                    .get_name(CHANGE_BLOB_NAME)
-                    .ok_or(error::Load::NoChange)?;
-                let history_object = history_tree_entry.to_object(repo)?;
+        let history_tree_entry = tree
+            .get_name(CHANGE_BLOB_NAME)
+            .ok_or(error::Load::NoChange)?;
+        if history_tree_entry.kind() != Some(git2::ObjectType::Blob) {
+            return Err(error::Load::ChangeNotBlob);
+        }
+        let content = history_tree_entry.id();
+        // The content of redacted changes may have been pruned, which does not
+        // affect the integrity of the change itself
+        let history = match history_tree_entry.to_object(repo) {
+            Ok(history_object) => {
                 let history_blob = history_object.as_blob().ok_or(error::Load::ChangeNotBlob)?;
-                History::Automerge(history_blob.content().into())
+                match manifest.history_type {
+                    HistoryType::Automerge => {
+                        Some(History::Automerge(history_blob.content().into()))
+                    },
+                }
             },
+            Err(e) if e.code() == git2::ErrorCode::NotFound => None,
+            Err(e) => return Err(e.into()),
         };
 
         let schema_commit_trailer =
             trailers::SchemaCommitTrailer::try_from(&metadata.trailers[..])?;
 
+        let dependencies = match tree.get_name(causal::DEPENDENCIES_BLOB_NAME) {
+            None => None,
+            Some(entry) => {
+                let object = entry.to_object(repo)?;
+                let blob = object.as_blob().ok_or(error::Load::DependenciesNotBlob)?;
+                let dependencies = causal::decode(blob.content())?;
+                let parents = commit.parent_ids().collect::<BTreeSet<_>>();
+                if let Some(missing) = dependencies.iter().find(|dep| !parents.contains(dep)) {
+                    return Err(error::Load::DependencyNotParent(*missing));
+                }
+                Some(dependencies)
+            },
+        };
+
         Ok(Change {
             schema_commit: schema_commit_trailer.oid(),
             manifest,
             history,
+            content,
             metadata,
+            dependencies,
         })
     }
 
@@ -191,8 +241,14 @@ impl Change {
         &self.manifest.typename
     }
 
-    pub fn history(&self) -> &History {
-        &self.history
+    /// The content of this change, `None` if it has been pruned.
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    /// The OID of the blob holding the content of this change.
+    pub fn content(&self) -> git2::Oid {
+        self.content
     }
 
     pub fn schema_commit(&self) -> git2::Oid {
@@ -203,6 +259,17 @@ impl Change {
         self.metadata.authorizing_identity_commit
     }
 
+    /// Whether this change causally depends on the change `commit`.
+    ///
+    /// Changes which don't record their dependencies depend on all their
+    /// parent commits.
+    pub fn depends_on(&self, commit: git2::Oid) -> bool {
+        self.dependencies
+            .as_ref()
+            .map(|deps| deps.contains(&commit))
+            .unwrap_or(true)
+    }
+
     pub fn valid_signatures(&self) -> bool {
         self.metadata.valid_signatures()
     }
@@ -4,7 +4,9 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 use super::{
+    causal,
     schema_change,
+    tombstone::Tombstone,
     validated_automerge::ValidatedAutomerge,
     AuthorizingIdentity,
     Change,
@@ -16,14 +18,11 @@ use super::{
     TypeName,
 };
 use link_identities::git::Urn;
-use petgraph::{
-    visit::{EdgeRef, Topo, Walker},
-    EdgeDirection,
-};
+use petgraph::{visit::EdgeRef, EdgeDirection};
 use thiserror::Error as ThisError;
 
 use std::{
-    collections::{hash_map::Entry, BTreeSet, HashMap},
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap},
     convert::TryInto,
 };
 
@@ -106,10 +105,12 @@ impl<'a> ChangeGraph<'a> {
     /// Given a graph evaluate it to produce a collaborative object. This will
     /// filter out branches of the graph which do not have valid signatures,
     /// or which do not have permission to make a change, or which make a
-    /// change which invalidates the schema of the object
+    /// change which invalidates the schema of the object. The content of the
+    /// changes in `redacted` is excluded.
     pub(super) fn evaluate<I: IdentityStorage>(
         &self,
         identities: &I,
+        redacted: &BTreeMap<git2::Oid, Tombstone>,
     ) -> (CollaborativeObject, ValidatedAutomerge) {
         let mut roots: Vec<petgraph::graph::NodeIndex<u32>> = self
             .graph
@@ -127,10 +128,12 @@ impl<'a> ChangeGraph<'a> {
             identities,
             self.authorizing_identity,
             self.repo,
+            &typename,
+            self.object_id,
+            redacted,
             self.schema().clone(),
         );
-        let topo = Topo::new(&self.graph);
-        let items = topo.iter(&self.graph).map(|idx| {
+        let items = self.causal_order().into_iter().map(|idx| {
             let node = &self.graph[idx];
             let outgoing_edges = self.graph.edges_directed(idx, EdgeDirection::Outgoing);
             let child_commits: Vec<git2::Oid> = outgoing_edges
@@ -151,6 +154,33 @@ impl<'a> ChangeGraph<'a> {
         )
     }
 
+    /// The nodes of the graph in [`causal::order`].
+    fn causal_order(&self) -> Vec<petgraph::graph::NodeIndex<u32>> {
+        let dependencies = self
+            .graph
+            .node_indices()
+            .map(|idx| {
+                let deps = self
+                    .graph
+                    .neighbors_directed(idx, EdgeDirection::Incoming)
+                    .map(|dep| self.graph[dep].commit())
+                    .collect::<BTreeSet<_>>();
+                (self.graph[idx].commit(), deps)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let indices = self
+            .graph
+            .node_indices()
+            .map(|idx| (self.graph[idx].commit(), idx))
+            .collect::<HashMap<_, _>>();
+        // The graph is loaded from commits, which can't form a cycle
+        causal::order(&dependencies)
+            .expect("change graph is acyclic")
+            .into_iter()
+            .map(|commit| indices[&commit])
+            .collect()
+    }
+
     /// Get the tips of the collaborative object
     pub(super) fn tips(&self) -> BTreeSet<git2::Oid> {
         self.graph
@@ -205,6 +235,10 @@ impl GraphBuilder {
         let author_commit = change.author_commit();
         let schema_commit = change.schema_commit();
         let authorizing_identity_commit = change.authorizing_identity_commit();
+        let dependencies = commit
+            .parent_ids()
+            .filter(|parent| change.depends_on(*parent))
+            .collect::<BTreeSet<_>>();
         if let Entry::Vacant(e) = self.node_indices.entry(commit.id()) {
             let ix = self.graph.add_node(change);
             e.insert(ix);
@@ -215,6 +249,7 @@ impl GraphBuilder {
                 if parent.id() != author_commit
                     && parent.id() != schema_commit
                     && parent.id() != authorizing_identity_commit
+                    && dependencies.contains(&parent.id())
                     && !self.has_edge(parent.id(), commit.id())
                 {
                     Some((parent, commit.id()))
@@ -4,16 +4,21 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 use crate::{
+    authorization::{Action, Role, Unauthorized},
     change::Change,
     identity_storage::{lookup_authorizing_identity, lookup_person},
+    tombstone::Tombstone,
     validated_automerge::{error::ProposalError, ValidatedAutomerge},
     AuthDecision,
     AuthorizingIdentity,
     History,
     IdentityStorage,
+    ObjectId,
     Schema,
+    TypeName,
 };
-use std::collections::{BTreeSet, HashMap};
+use link_identities::git::VerifiedPerson;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 enum RejectionReason {
     InvalidSignatures,
@@ -30,6 +35,8 @@ enum RejectionReason {
         reason: &'static str,
     },
     InvalidChange(ProposalError),
+    Redacted,
+    MissingContent,
 }
 
 struct RejectedChanges {
@@ -72,6 +79,9 @@ pub struct Evaluating<'a, I: IdentityStorage> {
     identities: &'a I,
     authorizing_identity: &'a dyn AuthorizingIdentity,
     repo: &'a git2::Repository,
+    typename: &'a TypeName,
+    object_id: ObjectId,
+    redacted: &'a BTreeMap<git2::Oid, Tombstone>,
     rejected: RejectedChanges,
     in_progress_history: ValidatedAutomerge,
 }
@@ -81,12 +91,18 @@ impl<'a, I: IdentityStorage> Evaluating<'a, I> {
         identities: &'a I,
         authorizer: &'a dyn AuthorizingIdentity,
         repo: &'a git2::Repository,
+        typename: &'a TypeName,
+        object_id: ObjectId,
+        redacted: &'a BTreeMap<git2::Oid, Tombstone>,
         schema: Schema,
     ) -> Evaluating<'a, I> {
         Evaluating {
             identities,
             authorizing_identity: authorizer,
             repo,
+            typename,
+            object_id,
+            redacted,
             rejected: RejectedChanges::new(),
             in_progress_history: ValidatedAutomerge::new(schema),
         }
@@ -164,6 +180,19 @@ impl<'a, I: IdentityStorage> Evaluating<'a, I> {
                             "rejecting invalid change"
                         );
                     },
+                    RejectionReason::Redacted => {
+                        tracing::info!(
+                            commit=?change.commit(),
+                            "excluding redacted change and its descendants"
+                        );
+                    },
+                    RejectionReason::MissingContent => {
+                        tracing::warn!(
+                            commit=?change.commit(),
+                            content=?change.content(),
+                            "rejecting change as its content is missing"
+                        );
+                    },
                 }
             } else {
                 tracing::trace!(commit=?change.commit(), "change accepted");
@@ -173,6 +202,17 @@ impl<'a, I: IdentityStorage> Evaluating<'a, I> {
     }
 
     fn evaluate_change(&mut self, change: &Change) -> Option<RejectionReason> {
+        // Check whether the change has been redacted
+        if let Some(tombstone) = self.redacted.get(&change.commit()) {
+            if tombstone.matches(change.content()) {
+                return Some(RejectionReason::Redacted);
+            }
+            tracing::warn!(
+                commit=?change.commit(),
+                "ignoring tombstone which does not match the content of the change"
+            );
+        }
+
         // Check the change signatures are valid
         if !change.valid_signatures() {
             return Some(RejectionReason::InvalidSignatures);
@@ -195,12 +235,28 @@ impl<'a, I: IdentityStorage> Evaluating<'a, I> {
         }
 
         // Check that the authorizing identity allows this change
+        let action = if ObjectId::from(change.commit()) == self.object_id {
+            Action::Create
+        } else {
+            Action::Update
+        };
         match lookup_person(self.repo, change.author_commit()) {
-            Ok(Some(author)) => match referenced_auth_identity.check_authorization(&author) {
-                AuthDecision::Authorized => {},
-                AuthDecision::NotAuthorized { reason } => {
-                    return Some(RejectionReason::Unauthorized { reason });
-                },
+            Ok(Some(author)) => {
+                match self.check_authorization(&*referenced_auth_identity, &author, action) {
+                    AuthDecision::Authorized => {},
+                    AuthDecision::NotAuthorized { reason } => {
+                        self.identities.unauthorized(&Unauthorized {
+                            identity: &self.authorizing_identity.urn(),
+                            typename: self.typename,
+                            object: self.object_id,
+                            change: change.commit(),
+                            author: author.urn(),
+                            action,
+                            reason,
+                        });
+                        return Some(RejectionReason::Unauthorized { reason });
+                    },
+                }
             },
             Ok(None) => {
                 return Some(RejectionReason::MissingAuthor {
@@ -217,15 +273,52 @@ impl<'a, I: IdentityStorage> Evaluating<'a, I> {
 
         // Check that the history the change carries is well formed and does not violate
         // the schema
-        match &change.history() {
-            History::Automerge(bytes) => match self.in_progress_history.propose_change(bytes) {
-                Ok(()) => {},
-                Err(e) => {
-                    return Some(RejectionReason::InvalidChange(e));
-                },
+        match change.history() {
+            Some(History::Automerge(bytes)) => {
+                match self.in_progress_history.propose_change(bytes) {
+                    Ok(()) => {},
+                    Err(e) => {
+                        return Some(RejectionReason::InvalidChange(e));
+                    },
+                }
             },
+            None => return Some(RejectionReason::MissingContent),
         };
 
         None
     }
+
+    /// Check whether `author` may perform `action` according to the rules of
+    /// `authorizer` for the type of object being evaluated.
+    fn check_authorization(
+        &self,
+        authorizer: &dyn AuthorizingIdentity,
+        author: &VerifiedPerson,
+        action: Action,
+    ) -> AuthDecision {
+        let denied = match authorizer.check_authorization(author) {
+            AuthDecision::Authorized => return AuthDecision::Authorized,
+            denied => denied,
+        };
+        match authorizer
+            .authorization_rules()
+            .rule(self.typename)
+            .role(action)
+        {
+            Role::Delegates => denied,
+            Role::Tracked => match self.identities.is_tracked(&authorizer.urn(), author) {
+                Ok(true) => AuthDecision::Authorized,
+                Ok(false) => AuthDecision::NotAuthorized {
+                    reason: "the author is neither a maintainer nor tracked",
+                },
+                Err(e) => {
+                    tracing::warn!(err=?e, "error looking up tracking of author");
+                    AuthDecision::NotAuthorized {
+                        reason: "could not determine whether the author is tracked",
+                    }
+                },
+            },
+            Role::Anyone => AuthDecision::Authorized,
+        }
+    }
 }
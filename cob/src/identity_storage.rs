@@ -5,7 +5,7 @@
 
 use link_identities::git::{error, Identities, Person, SomeIdentity, Urn, VerifiedPerson};
 
-use super::AuthorizingIdentity;
+use super::{authorization::Unauthorized, AuthorizingIdentity};
 
 /// Abstracts the way in which Urns are represented within a particular
 /// repository. This primarily exist because
@@ -16,6 +16,16 @@ pub trait IdentityStorage {
     type Error: std::error::Error + Send + Sync + 'static;
     /// The OID which corresponds to a particular urn
     fn delegate_oid(&self, urn: Urn) -> Result<git2::Oid, Self::Error>;
+
+    /// Whether `person` is tracked within the identity `urn`, used to evaluate
+    /// [`crate::authorization::Role::Tracked`]. By default, nobody is tracked.
+    fn is_tracked(&self, _urn: &Urn, _person: &VerifiedPerson) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    /// Called for every change which is rejected because its author was not
+    /// authorized to make it. By default, this does nothing.
+    fn unauthorized(&self, _rejected: &Unauthorized<'_>) {}
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -43,7 +43,8 @@
 //! the commits we can find for the given object. `ChangeGraph`
 //! has an `evaluate` method which traverses this directed graph validating each
 //! change with respect to their signatures, the schema, and the access control
-//! policy (only maintainers may make changes). Secondly there is the
+//! policy (by default only maintainers may make changes, see
+//! [`authorization`]). Secondly there is the
 //! `cache::ThinChangeGraph`, this is a representation that contains only the
 //! automerge history of a fully evaluated change graph and the OIDs of the tips
 //! of the graph that was used to generate the changes. For any of the CRUD
@@ -72,9 +73,12 @@ use link_crypto::{keystore::sign::Signer, BoxedSigner, PublicKey};
 use link_identities::git::{Urn, VerifiedPerson};
 use radicle_git_ext as ext;
 
+pub mod authorization;
+
 mod authorizing_identity;
 pub use authorizing_identity::{AuthDecision, AuthorizingIdentity};
 
+pub mod causal;
 mod change_metadata;
 mod trailers;
 
@@ -102,6 +106,9 @@ use validated_automerge::ValidatedAutomerge;
 mod identity_storage;
 pub use identity_storage::IdentityStorage;
 
+pub mod tombstone;
+pub use tombstone::Tombstone;
+
 pub mod internals {
     //! This module exposes implementation details of the collaborative object
     //! crate for use in testing
@@ -172,6 +179,12 @@ impl TypeName {
     pub fn regex_safe_string(&self) -> String {
         self.0.replace('.', "\\.")
     }
+
+    /// The typename under which the [`Tombstone`]s for objects of this type
+    /// are stored
+    pub fn tombstones(&self) -> TypeName {
+        TypeName(format!("{}.tombstones", self.0))
+    }
 }
 
 impl fmt::Display for TypeName {
@@ -390,6 +403,24 @@ pub mod error {
         SignerIsNotAuthor,
     }
 
+    #[derive(Debug, Error)]
+    pub enum Redact<RefsError: std::error::Error> {
+        #[error("no change {0} in object")]
+        NoSuchChange(git2::Oid),
+        #[error("the author is not a maintainer of the authorizing identity: {0}")]
+        NotAuthorized(&'static str),
+        #[error(transparent)]
+        CreateTombstone(#[from] super::tombstone::error::Create),
+        #[error(transparent)]
+        LoadChange(#[from] change::error::Load),
+        #[error(transparent)]
+        Refs(RefsError),
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+        #[error("signer must belong to the author")]
+        SignerIsNotAuthor,
+    }
+
     #[derive(Debug, Error)]
     pub enum ParseObjectId {
         #[error(transparent)]
@@ -521,6 +552,9 @@ pub fn retrieve<R: RefsStorage, I: IdentityStorage, P: AsRef<std::path::Path>>(
     let tip_refs = refs_storage
         .object_references(&authorizing_identity.urn(), typename, oid)
         .map_err(error::Retrieve::Refs)?;
+    let tombstone_refs = refs_storage
+        .object_references(&authorizing_identity.urn(), &typename.tombstones(), oid)
+        .map_err(error::Retrieve::Refs)?;
     tracing::trace!(refs=?tip_refs, "retrieving object");
     let mut cache = open_cache(cache_dir)?;
     Ok(CobRefs {
@@ -528,6 +562,7 @@ pub fn retrieve<R: RefsStorage, I: IdentityStorage, P: AsRef<std::path::Path>>(
         authorizing_identity,
         typename,
         tip_refs,
+        tombstone_refs,
     }
     .load_or_materialize::<error::Retrieve<R::Error>, _>(identity_storage, cache.as_mut(), repo)?
     .map(|tg| tg.into()))
@@ -545,6 +580,9 @@ pub fn list<R: RefsStorage, P: AsRef<std::path::Path>, I: IdentityStorage>(
     let references = refs_storage
         .type_references(&authorizing_identity.urn(), typename)
         .map_err(error::Retrieve::Refs)?;
+    let mut tombstone_references = refs_storage
+        .type_references(&authorizing_identity.urn(), &typename.tombstones())
+        .map_err(error::Retrieve::Refs)?;
     tracing::trace!(num_objects=?references.len(), "loaded references");
     let mut result = Vec::new();
     let mut cache = open_cache(cache_dir)?;
@@ -555,6 +593,7 @@ pub fn list<R: RefsStorage, P: AsRef<std::path::Path>, I: IdentityStorage>(
             authorizing_identity,
             typename,
             tip_refs,
+            tombstone_refs: tombstone_references.remove(&oid).unwrap_or_default(),
         }
         .load_or_materialize::<error::Retrieve<R::Error>, _>(
             identity_storage,
@@ -633,12 +672,21 @@ pub fn update<R: RefsStorage, I: IdentityStorage, P: AsRef<std::path::Path>>(
         None
     };
 
+    let tombstone_refs = refs_storage
+        .object_references(
+            &authorizing_identity.urn(),
+            &typename.tombstones(),
+            &object_id,
+        )
+        .map_err(error::Update::Refs)?;
+
     let mut cache = open_cache(cache_dir)?;
     let cached = CobRefs {
         authorizing_identity,
         typename,
         oid: object_id,
         tip_refs: existing_refs,
+        tombstone_refs,
     }
     .load_or_materialize::<error::Update<R::Error>, _>(identity_storage, cache.as_mut(), repo)?
     .ok_or(error::Update::NoSuchObject)?;
@@ -677,6 +725,104 @@ pub fn update<R: RefsStorage, I: IdentityStorage, P: AsRef<std::path::Path>>(
     Ok(cached.into())
 }
 
+/// The data required to redact a change of an object
+pub struct RedactArgs<'a, R: RefsStorage> {
+    /// The refs storage used to store the tombstone
+    pub refs_storage: &'a R,
+    /// The repo the object is stored in
+    pub repo: &'a git2::Repository,
+    /// The signer used to sign the tombstone
+    pub signer: &'a BoxedSigner,
+    /// The person corresponding to the signer above, which must be a
+    /// maintainer of the authorizing identity
+    pub author: &'a VerifiedPerson,
+    /// The identity the object is stored in
+    pub authorizing_identity: &'a dyn AuthorizingIdentity,
+    /// The object ID of the object containing the change
+    pub object_id: ObjectId,
+    /// The typename of the object containing the change
+    pub typename: TypeName,
+    /// The commit of the change to redact
+    pub change: git2::Oid,
+    /// An optional reason for the redaction
+    pub reason: Option<String>,
+}
+
+/// Publish a [`Tombstone`] for a change of an object, see [`tombstone`].
+pub fn redact<R: RefsStorage>(args: RedactArgs<R>) -> Result<Tombstone, error::Redact<R::Error>> {
+    let RedactArgs {
+        refs_storage,
+        repo,
+        signer,
+        author,
+        authorizing_identity,
+        object_id,
+        ref typename,
+        change,
+        reason,
+    } = args;
+    if !is_signer_for(signer, author) {
+        return Err(error::Redact::SignerIsNotAuthor);
+    }
+    if let AuthDecision::NotAuthorized { reason } = authorizing_identity.check_authorization(author)
+    {
+        return Err(error::Redact::NotAuthorized(reason));
+    }
+
+    let commit = repo
+        .find_commit(change)
+        .map_err(|_| error::Redact::NoSuchChange(change))?;
+    let loaded = Change::load(repo, &commit)?;
+    if loaded.typename() != typename {
+        return Err(error::Redact::NoSuchChange(change));
+    }
+    let tombstone = Tombstone {
+        change: change.into(),
+        content: loaded.content().into(),
+        reason,
+    };
+
+    let tombstones = typename.tombstones();
+    let previous = refs_storage
+        .object_references(&authorizing_identity.urn(), &tombstones, &object_id)
+        .map_err(error::Redact::Refs)?
+        .local
+        .map(|r| r.peel_to_commit().map(|c| c.id()))
+        .transpose()?;
+    let commit = tombstone::create(
+        repo,
+        signer,
+        authorizing_identity.content_id(),
+        author.content_id.into(),
+        previous,
+        &tombstone,
+    )?;
+    refs_storage
+        .update_ref(&authorizing_identity.urn(), &tombstones, object_id, commit)
+        .map_err(error::Redact::Refs)?;
+
+    Ok(tombstone)
+}
+
+/// Retrieve the valid [`Tombstone`]s of an object
+pub fn tombstones<R: RefsStorage, I: IdentityStorage>(
+    refs_storage: &R,
+    identity_storage: &I,
+    repo: &git2::Repository,
+    authorizing_identity: &dyn AuthorizingIdentity,
+    typename: &TypeName,
+    oid: &ObjectId,
+) -> Result<Vec<Tombstone>, error::Retrieve<R::Error>> {
+    let refs = refs_storage
+        .object_references(&authorizing_identity.urn(), &typename.tombstones(), oid)
+        .map_err(error::Retrieve::Refs)?;
+    Ok(
+        tombstone::verified(identity_storage, repo, authorizing_identity, refs.iter())?
+            .into_values()
+            .collect(),
+    )
+}
+
 /// Retrieve additional information about the change graph of an object. This
 /// is mostly useful for debugging and testing
 pub fn changegraph_info_for_object<R: RefsStorage>(
@@ -726,6 +872,8 @@ struct CobRefs<'a> {
     typename: &'a TypeName,
     /// The identity which authorizes changes to this object
     authorizing_identity: &'a dyn AuthorizingIdentity,
+    /// The references to the tips of the tombstones of the object
+    tombstone_refs: ObjectRefs<'a>,
 }
 
 impl<'a> CobRefs<'a> {
@@ -745,7 +893,20 @@ impl<'a> CobRefs<'a> {
             .iter()
             .map(|r| r.peel_to_commit().map(|c| c.id()))
             .collect::<Result<BTreeSet<git2::Oid>, git2::Error>>()?;
-        match cache.load(self.oid, &tip_oids)? {
+        let redacted = tombstone::verified(
+            identity_storage,
+            repo,
+            self.authorizing_identity,
+            self.tombstone_refs.iter(),
+        )?;
+        // The cache is keyed by the tips of the object only, so it may contain
+        // content which has been redacted since
+        let cached = if redacted.is_empty() {
+            cache.load(self.oid, &tip_oids)?
+        } else {
+            None
+        };
+        match cached {
             Some(obj) => {
                 tracing::trace!(object_id=?self.oid, "object found in cache");
                 Ok(Some(obj))
@@ -759,7 +920,7 @@ impl<'a> CobRefs<'a> {
                     self.typename,
                     &self.oid,
                 )? {
-                    let (object, valid_history) = graph.evaluate(identity_storage);
+                    let (object, valid_history) = graph.evaluate(identity_storage, &redacted);
                     let cached = cache::ThinChangeGraph::new(
                         tip_oids,
                         graph.schema().clone(),
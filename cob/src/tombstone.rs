@@ -0,0 +1,226 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Redaction of the content of individual changes.
+//!
+//! The maintainers of the authorizing identity of an object may publish a
+//! [`Tombstone`] for any change of the object, eg. because it contains
+//! personal information which must not be distributed any further. Tombstones
+//! are stored as a chain of signed commits, with the same metadata as
+//! changes, under the references of the object with typename
+//! [`crate::TypeName::tombstones`]. They are thus replicated like any other
+//! object, and subject to the same tracking configuration.
+//!
+//! When an object is evaluated, the history of every change for which a valid
+//! tombstone exists is excluded from the state of the object, and from the
+//! history written to the cache. A tombstone is valid if it is signed by a
+//! maintainer of the authorizing identity, and if the hash of the redacted
+//! content matches the one recorded in the tombstone -- the commit and tree of
+//! the change stay intact, so the integrity of the history can be verified
+//! without the content.
+//!
+//! Since automerge changes depend on all changes preceding them, the changes
+//! descending from a redacted change can not be applied either, and are
+//! rejected. Maintainers are expected to re-apply any benign content after
+//! the redaction.
+//!
+//! Note that tombstones do not remove the redacted content from the git
+//! repository. Compliant nodes should prune it, eg. by rewriting their
+//! packfiles. Changes whose content has been pruned still load, and are
+//! matched against tombstones by the OID of their content blob. Changes with
+//! missing content which are not redacted are rejected.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+};
+
+use link_crypto::BoxedSigner;
+use radicle_git_ext as ext;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    change_metadata::{self, ChangeMetadata, CreateMetadataArgs},
+    identity_storage::{lookup_authorizing_identity, lookup_person},
+    AuthDecision,
+    AuthorizingIdentity,
+    IdentityStorage,
+};
+
+const TOMBSTONE_BLOB_NAME: &str = "tombstone.toml";
+
+pub mod error {
+    use super::change_metadata;
+    use link_crypto::BoxedSignError;
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    pub enum Create {
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+        #[error(transparent)]
+        Signer(#[from] BoxedSignError),
+        #[error(transparent)]
+        Metadata(#[from] change_metadata::CreateError),
+    }
+
+    #[derive(Debug, Error)]
+    pub enum Load {
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+        #[error("no {0} in commit tree")]
+        NoTombstone(&'static str),
+        #[error("invalid tombstone: {0}")]
+        InvalidTombstone(#[from] toml::de::Error),
+        #[error(transparent)]
+        InvalidMetadata(#[from] change_metadata::LoadError),
+    }
+}
+
+/// A redaction of the content of a change.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstone {
+    /// The commit of the redacted change.
+    pub change: ext::Oid,
+    /// The hash of the redacted content.
+    pub content: ext::Oid,
+    /// An optional, human-readable reason for the redaction.
+    pub reason: Option<String>,
+}
+
+impl Tombstone {
+    /// Whether `content`, the blob OID of the content of a change, is the
+    /// content this tombstone redacts.
+    ///
+    /// Only the OID is compared, so that a change can be matched after its
+    /// content has been pruned.
+    pub fn matches(&self, content: git2::Oid) -> bool {
+        ext::Oid::from(content) == self.content
+    }
+}
+
+/// Write a commit recording `tombstone`, on top of `previous`.
+pub(crate) fn create(
+    repo: &git2::Repository,
+    signer: &BoxedSigner,
+    authorizing_identity_commit: git2::Oid,
+    author_identity_commit: git2::Oid,
+    previous: Option<git2::Oid>,
+    tombstone: &Tombstone,
+) -> Result<git2::Oid, error::Create> {
+    let mut tb = repo.treebuilder(None)?;
+    // SAFETY: we're serializing to an in memory buffer so the only source of
+    // errors here is a programming error, which we can't recover from
+    let serialized = toml::ser::to_vec(tombstone).unwrap();
+    let blob = repo.blob(&serialized)?;
+    tb.insert(TOMBSTONE_BLOB_NAME, blob, git2::FileMode::Blob.into())?;
+    let revision = tb.write()?;
+
+    let mut tips = previous.into_iter().collect::<Vec<_>>();
+    tips.push(authorizing_identity_commit);
+
+    let metadata = ChangeMetadata::create(CreateMetadataArgs {
+        revision,
+        tips,
+        message: format!("redact {}", tombstone.change),
+        extra_trailers: vec![],
+        authorizing_identity_commit,
+        author_identity_commit,
+        signer: signer.clone(),
+        repo,
+    })?;
+    Ok(metadata.commit)
+}
+
+fn load(
+    repo: &git2::Repository,
+    commit: &git2::Commit,
+) -> Result<(ChangeMetadata, Tombstone), error::Load> {
+    let metadata = ChangeMetadata::try_from(commit)?;
+    let tree = commit.tree()?;
+    let entry = tree
+        .get_name(TOMBSTONE_BLOB_NAME)
+        .ok_or(error::Load::NoTombstone(TOMBSTONE_BLOB_NAME))?;
+    let object = entry.to_object(repo)?;
+    let blob = object
+        .as_blob()
+        .ok_or(error::Load::NoTombstone(TOMBSTONE_BLOB_NAME))?;
+    let tombstone = toml::de::from_slice(blob.content())?;
+    Ok((metadata, tombstone))
+}
+
+fn is_tombstone(commit: &git2::Commit) -> bool {
+    commit
+        .tree()
+        .map(|tree| tree.get_name(TOMBSTONE_BLOB_NAME).is_some())
+        .unwrap_or(false)
+}
+
+/// Load the valid tombstones reachable from `tips`, keyed by the redacted
+/// change.
+///
+/// Tombstones which are not signed by a maintainer of `authorizing_identity`
+/// are skipped.
+pub(crate) fn verified<'a, I: IdentityStorage>(
+    identities: &I,
+    repo: &git2::Repository,
+    authorizing_identity: &dyn AuthorizingIdentity,
+    tips: impl Iterator<Item = &'a git2::Reference<'a>>,
+) -> Result<BTreeMap<git2::Oid, Tombstone>, git2::Error> {
+    let mut seen = BTreeSet::new();
+    let mut queue = Vec::new();
+    for tip in tips {
+        queue.push(tip.peel_to_commit()?);
+    }
+
+    let mut tombstones = BTreeMap::new();
+    while let Some(commit) = queue.pop() {
+        if !seen.insert(commit.id()) {
+            continue;
+        }
+        for parent in commit.parents() {
+            if is_tombstone(&parent) {
+                queue.push(parent);
+            }
+        }
+        match verify(identities, repo, authorizing_identity, &commit) {
+            Ok(tombstone) => {
+                tombstones.insert(tombstone.change.into(), tombstone);
+            },
+            Err(reason) => {
+                tracing::warn!(commit=?commit.id(), reason, "ignoring invalid tombstone");
+            },
+        }
+    }
+    Ok(tombstones)
+}
+
+fn verify<I: IdentityStorage>(
+    identities: &I,
+    repo: &git2::Repository,
+    authorizing_identity: &dyn AuthorizingIdentity,
+    commit: &git2::Commit,
+) -> Result<Tombstone, &'static str> {
+    let (metadata, tombstone) = load(repo, commit).map_err(|e| {
+        tracing::debug!(err=?e, "failed to load tombstone");
+        "malformed tombstone"
+    })?;
+    if !metadata.valid_signatures() {
+        return Err("invalid signatures");
+    }
+    let referenced =
+        lookup_authorizing_identity(identities, repo, metadata.authorizing_identity_commit)
+            .map_err(|_| "unknown authorizing identity")?;
+    if referenced.urn() != authorizing_identity.urn() {
+        return Err("wrong authorizing identity");
+    }
+    match lookup_person(repo, metadata.author_commit) {
+        Ok(Some(author)) => match referenced.check_authorization(&author) {
+            AuthDecision::Authorized => Ok(tombstone),
+            AuthDecision::NotAuthorized { reason } => Err(reason),
+        },
+        Ok(None) | Err(_) => Err("unknown author"),
+    }
+}
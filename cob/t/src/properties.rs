@@ -3,6 +3,9 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+use std::collections::{BTreeMap, BTreeSet};
+
+use cob::causal;
 use librad::{
     collaborative_objects::{ObjRefMatch, ObjRefMatcher},
     git::types::{Namespace, Reference},
@@ -49,3 +52,82 @@ proptest! {
             assert_eq!(matcher.match_ref(reference.to_string().as_str()), ObjRefMatch::Local(object_id));
     }
 }
+
+/// A random DAG over the keys `0..n`, where each key depends on some of the
+/// keys before it, as a list of `(key, dependencies)` in random order.
+fn gen_dag() -> impl Strategy<Value = Vec<(u32, BTreeSet<u32>)>> {
+    (1u32..40)
+        .prop_flat_map(|n| {
+            (0..n)
+                .map(|key| {
+                    prop::collection::btree_set(0..key.max(1), 0..(key.min(4) as usize + 1))
+                        .prop_map(move |deps| {
+                            (key, deps.into_iter().filter(|d| *d < key).collect())
+                        })
+                })
+                .collect::<Vec<_>>()
+        })
+        .prop_shuffle()
+}
+
+proptest! {
+    #[test]
+    fn causal_order_respects_dependencies(dag in gen_dag()) {
+        let dependencies = dag.into_iter().collect::<BTreeMap<_, _>>();
+        let ordered = causal::order(&dependencies).unwrap();
+        prop_assert_eq!(ordered.len(), dependencies.len());
+
+        let position = ordered
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (*key, i))
+            .collect::<BTreeMap<_, _>>();
+        for (key, deps) in &dependencies {
+            for dep in deps {
+                prop_assert!(position[dep] < position[key]);
+            }
+        }
+    }
+
+    #[test]
+    fn causal_order_converges(dag in gen_dag(), seed in any::<u64>()) {
+        use rand::{rngs::StdRng, seq::SliceRandom as _, SeedableRng as _};
+
+        // Two peers receiving the same changes in different orders
+        let mut reordered = dag.clone();
+        reordered.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        let mut ours = BTreeMap::new();
+        for (key, deps) in dag {
+            ours.insert(key, deps);
+        }
+        let mut theirs = BTreeMap::new();
+        for (key, deps) in reordered {
+            theirs.insert(key, deps);
+        }
+        prop_assert_eq!(
+            causal::order(&ours).unwrap(),
+            causal::order(&theirs).unwrap()
+        );
+    }
+
+    #[test]
+    fn causal_order_ignores_missing_dependencies(dag in gen_dag(), missing in any::<prop::sample::Index>()) {
+        let mut dependencies = dag.into_iter().collect::<BTreeMap<_, _>>();
+        let missing = *missing.get(&dependencies.keys().copied().collect::<Vec<_>>());
+        dependencies.remove(&missing);
+
+        let ordered = causal::order(&dependencies).unwrap();
+        prop_assert_eq!(ordered.len(), dependencies.len());
+        prop_assert!(!ordered.contains(&missing));
+    }
+}
+
+#[test]
+fn causal_order_detects_cycles() {
+    let dependencies = [(1, [2].into()), (2, [1].into()), (3, BTreeSet::new())]
+        .into_iter()
+        .collect::<BTreeMap<u32, BTreeSet<u32>>>();
+    let cob::causal::Cycle(cycle) = causal::order(&dependencies).unwrap_err();
+    assert_eq!(cycle, vec![1, 2]);
+}
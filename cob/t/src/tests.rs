@@ -3,9 +3,11 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod authorization;
 mod cache;
 mod schema;
 mod thin_change_graph;
+mod tombstone;
 
 use cob::TypeName;
 use std::str::FromStr;
@@ -0,0 +1,64 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::str::FromStr;
+
+use cob::{
+    authorization::{Action, Role, Rule, Rules},
+    TypeName,
+};
+use link_identities::payload::{Project, ProjectPayload};
+
+#[test]
+fn rules_from_payload() {
+    let payload: ProjectPayload = serde_json::from_value(serde_json::json!({
+        "https://radicle.xyz/link/identities/project/v1": {
+            "name": "radicle-link",
+            "description": null,
+            "default_branch": null,
+        },
+        "https://radicle.xyz/link/cob/authorization/v1": {
+            "types": {
+                "xyz.radicle.issue": { "create": "anyone", "update": "tracked" },
+                "xyz.radicle.patch": { "update": "anyone" },
+            }
+        }
+    }))
+    .unwrap();
+    let rules = payload.get_ext::<Rules>().unwrap().unwrap();
+
+    let issue = rules.rule(&TypeName::from_str("xyz.radicle.issue").unwrap());
+    assert_eq!(issue.role(Action::Create), Role::Anyone);
+    assert_eq!(issue.role(Action::Update), Role::Tracked);
+
+    let patch = rules.rule(&TypeName::from_str("xyz.radicle.patch").unwrap());
+    assert_eq!(patch.role(Action::Create), Role::Delegates);
+    assert_eq!(patch.role(Action::Update), Role::Anyone);
+
+    assert_eq!(
+        rules.rule(&TypeName::from_str("xyz.radicle.other").unwrap()),
+        Rule::default()
+    );
+}
+
+#[test]
+fn rules_roundtrip() {
+    let mut rules = Rules::default();
+    rules.types.insert(
+        TypeName::from_str("xyz.radicle.issue").unwrap(),
+        Rule {
+            create: Role::Tracked,
+            update: Role::Anyone,
+        },
+    );
+    let payload = ProjectPayload::new(Project {
+        name: "radicle-link".into(),
+        description: None,
+        default_branch: None,
+    })
+    .with_ext(rules.clone())
+    .unwrap();
+    assert_eq!(payload.get_ext::<Rules>().unwrap(), Some(rules));
+}
@@ -0,0 +1,30 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::str::FromStr;
+
+use cob::{History, Tombstone, TypeName};
+
+#[test]
+fn tombstones_typename() {
+    let typename = TypeName::from_str("xyz.radicle.issue").unwrap();
+    let tombstones = typename.tombstones();
+    assert_eq!(tombstones.to_string(), "xyz.radicle.issue.tombstones");
+    assert!(TypeName::from_str(&tombstones.to_string()).is_ok());
+}
+
+#[test]
+fn tombstone_matches_content() {
+    let history = History::Automerge(b"doxxing".to_vec());
+    let content = git2::Oid::hash_object(git2::ObjectType::Blob, history.as_bytes()).unwrap();
+    let tombstone = Tombstone {
+        change: git2::Oid::zero().into(),
+        content: content.into(),
+        reason: Some("personal information".to_owned()),
+    };
+    assert!(tombstone.matches(content));
+    let benign = git2::Oid::hash_object(git2::ObjectType::Blob, b"benign").unwrap();
+    assert!(!tombstone.matches(benign));
+}
@@ -151,8 +151,27 @@ async fn main() {
                 replication: Default::default(),
                 rate_limits: Default::default(),
                 request_pull,
+                mailbox: Default::default(),
+                bandwidth: Default::default(),
+                dial: Default::default(),
+                gossip_batch: Default::default(),
+                pinned: Default::default(),
+                lfs: Default::default(),
+                attachments: Default::default(),
+                streams: Default::default(),
+                capture: Default::default(),
+                outbox: Default::default(),
+                compression: Default::default(),
+                interest: Default::default(),
+                backoff: Default::default(),
+                checkpoint: Default::default(),
+                usage: Default::default(),
+                ls_refs: Default::default(),
+                private: false,
+                rotation: None,
             },
             storage: Default::default(),
+            reputation: Default::default(),
         })
         .unwrap();
         let bound = peer.bind().await.unwrap();
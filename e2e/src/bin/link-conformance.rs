@@ -0,0 +1,73 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{net::ToSocketAddrs, process, time::Duration};
+
+use argh::FromArgs;
+use librad::{crypto, git::Urn, net::Network};
+use radicle_link_e2e::{
+    conformance::{self, Remote},
+    logging,
+};
+
+/// Check the conformance of a remote peer to the protocol
+#[derive(FromArgs)]
+struct Options {
+    /// the peer to check, as <peer id>@<host>:<port>
+    #[argh(option, from_str_fn(parse_remote))]
+    peer: Remote,
+    /// the network the peer is on
+    #[argh(option, default = "Network::Custom(b\"localtestnet\".as_ref().into())")]
+    network: Network,
+    /// a project the peer has, to check replication
+    #[argh(option)]
+    urn: Option<Urn>,
+    /// seconds allowed for each check
+    #[argh(option, default = "10")]
+    timeout: u64,
+}
+
+fn parse_remote(s: &str) -> Result<Remote, String> {
+    match s.split_once('@') {
+        Some((peer_id, addr)) => {
+            let peer_id = peer_id
+                .parse()
+                .map_err(|e: crypto::peer::conversion::Error| e.to_string())?;
+            let addrs = addr.to_socket_addrs().map_err(|e| e.to_string())?.collect();
+            Ok(Remote { peer_id, addrs })
+        },
+
+        None => Err("missing peer id".to_owned()),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    logging::init();
+
+    let opts: Options = argh::from_env();
+    let report = conformance::run(conformance::Options {
+        network: opts.network,
+        remote: opts.peer,
+        urn: opts.urn,
+        timeout: Duration::from_secs(opts.timeout),
+    })
+    .await;
+
+    process::exit(match report {
+        Ok(report) => {
+            print!("{}", report);
+            if report.is_success() {
+                0
+            } else {
+                1
+            }
+        },
+        Err(e) => {
+            eprintln!("FATAL: {}", e);
+            2
+        },
+    })
+}
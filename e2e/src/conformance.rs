@@ -0,0 +1,491 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Conformance checks of a remote peer.
+//!
+//! The remote peer, which may be any implementation of the protocol, is
+//! driven by ephemeral local peers through the protocol's stages, from the
+//! connection handshake to replicating a project. Every [`Check`] yields a
+//! [`Status`], and the results are collected in a [`Report`].
+//!
+//! Checks are run in order, and checks which depend on an earlier one are
+//! skipped if it failed: if the handshake fails, nothing else is attempted.
+//!
+//! The gossip check needs two local peers to join the remote's membership,
+//! so the remote must accept at least two more active members.
+
+use std::{
+    fmt,
+    future::Future,
+    net::SocketAddr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use futures::StreamExt as _;
+use librad::{
+    git::{self, Urn},
+    git_ext,
+    net::{
+        discovery::{self, Discovery as _},
+        peer::{self, config::DenyAll, Peer},
+        protocol::{
+            checkpoint,
+            event::{self, upstream::Gossip},
+            gossip,
+            Interrogation,
+        },
+        Network,
+    },
+    paths::Paths,
+    PeerId,
+    SecretKey,
+};
+use tempfile::TempDir;
+
+/// The address of the peer under test.
+#[derive(Clone, Debug)]
+pub struct Remote {
+    pub peer_id: PeerId,
+    pub addrs: Vec<SocketAddr>,
+}
+
+impl From<&Remote> for (PeerId, Vec<SocketAddr>) {
+    fn from(remote: &Remote) -> Self {
+        (remote.peer_id, remote.addrs.clone())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub network: Network,
+    pub remote: Remote,
+    /// A project the remote peer has, which is replicated by
+    /// [`Check::Replication`]. The check is skipped if `None`.
+    pub urn: Option<Urn>,
+    /// Time allowed for each check.
+    pub timeout: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// Establishing a connection, ie. the QUIC handshake with a matching
+    /// network and the TLS certificate of the expected peer.
+    Handshake,
+    /// Upgrading a stream to the interrogation protocol, and requesting the
+    /// remote's peer advertisement.
+    Advertisement,
+    /// Requesting the address we appear to have.
+    EchoAddr,
+    /// Requesting the remote's URNs.
+    Urns,
+    /// Requesting the remote's time.
+    ClockSkew,
+    /// Upgrading a stream to the ping protocol.
+    Ping,
+    /// Upgrading a stream to the inventory protocol, and querying it.
+    Inventory,
+    /// Joining the remote's membership.
+    Membership,
+    /// Relaying an announcement from one local peer to another.
+    GossipEcho,
+    /// Replicating [`Options::urn`].
+    Replication,
+}
+
+impl Check {
+    pub const ALL: [Self; 10] = [
+        Self::Handshake,
+        Self::Advertisement,
+        Self::EchoAddr,
+        Self::Urns,
+        Self::ClockSkew,
+        Self::Ping,
+        Self::Inventory,
+        Self::Membership,
+        Self::GossipEcho,
+        Self::Replication,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Handshake => "handshake",
+            Self::Advertisement => "interrogation/advertisement",
+            Self::EchoAddr => "interrogation/echo-addr",
+            Self::Urns => "interrogation/urns",
+            Self::ClockSkew => "interrogation/time",
+            Self::Ping => "ping",
+            Self::Inventory => "inventory",
+            Self::Membership => "membership",
+            Self::GossipEcho => "gossip/echo",
+            Self::Replication => "replication",
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    Pass(Option<String>),
+    Fail(String),
+    Skip(String),
+}
+
+impl Status {
+    pub fn is_fail(&self) -> bool {
+        matches!(self, Self::Fail(_))
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Pass(_) => "PASS",
+            Self::Fail(_) => "FAIL",
+            Self::Skip(_) => "SKIP",
+        }
+    }
+
+    fn detail(&self) -> Option<&str> {
+        match self {
+            Self::Pass(detail) => detail.as_deref(),
+            Self::Fail(detail) | Self::Skip(detail) => Some(detail),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    pub check: Check,
+    pub status: Status,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub remote: PeerId,
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Report {
+    /// `true` if no check failed.
+    pub fn is_success(&self) -> bool {
+        !self.outcomes.iter().any(|o| o.status.is_fail())
+    }
+
+    pub fn status(&self, check: Check) -> Option<&Status> {
+        self.outcomes
+            .iter()
+            .find(|o| o.check == check)
+            .map(|o| &o.status)
+    }
+
+    fn passed(&self, check: Check) -> bool {
+        matches!(self.status(check), Some(Status::Pass(_)))
+    }
+
+    fn skip(&mut self, check: Check, reason: impl Into<String>) {
+        self.outcomes.push(Outcome {
+            check,
+            status: Status::Skip(reason.into()),
+            elapsed: Duration::ZERO,
+        })
+    }
+
+    async fn run<F, T>(&mut self, check: Check, timeout: Duration, f: F) -> Option<T>
+    where
+        F: Future<Output = Result<(T, Option<String>), String>>,
+    {
+        let start = Instant::now();
+        let (status, res) = match tokio::time::timeout(timeout, f).await {
+            Ok(Ok((t, detail))) => (Status::Pass(detail), Some(t)),
+            Ok(Err(e)) => (Status::Fail(e), None),
+            Err(_) => (Status::Fail(format!("timed out after {:?}", timeout)), None),
+        };
+        self.outcomes.push(Outcome {
+            check,
+            status,
+            elapsed: start.elapsed(),
+        });
+        res
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "conformance of {}", self.remote)?;
+        let width = Check::ALL.iter().map(|c| c.name().len()).max().unwrap_or(0);
+        for Outcome {
+            check,
+            status,
+            elapsed,
+        } in &self.outcomes
+        {
+            write!(
+                f,
+                "{:width$}  {}  {:>6}ms",
+                check.name(),
+                status.label(),
+                elapsed.as_millis(),
+                width = width
+            )?;
+            match status.detail() {
+                Some(detail) => writeln!(f, "  {}", detail)?,
+                None => writeln!(f)?,
+            }
+        }
+        let failed = self.outcomes.iter().filter(|o| o.status.is_fail()).count();
+        writeln!(f, "{} checks, {} failed", self.outcomes.len(), failed)
+    }
+}
+
+/// A local peer on temporary storage, running until dropped.
+struct Ephemeral {
+    peer: Peer<SecretKey, DenyAll>,
+    stop: Option<Box<dyn FnOnce()>>,
+    _root: TempDir,
+}
+
+impl Ephemeral {
+    async fn start(network: Network, bootstrap: Option<&Remote>) -> anyhow::Result<Self> {
+        let root = tempfile::tempdir()?;
+        let paths = Paths::from_root(root.path())?;
+        let key = SecretKey::new();
+        git::storage::Storage::init(&paths, key.clone())?;
+
+        let peer = Peer::builder(key, paths)
+            .network(network)
+            .checkpoint(checkpoint::Config {
+                enabled: false,
+                ..Default::default()
+            })
+            .reputation(peer::reputation::Config {
+                durable: false,
+                ..Default::default()
+            })
+            .build()?;
+        let bound = peer.bind().await?;
+        let disco = discovery::Static::resolve(bootstrap.map(|r| (r.peer_id, r.addrs.as_slice())))?;
+        let (stop, run) = bound.accept(disco.discover());
+        tokio::spawn(run);
+
+        Ok(Self {
+            peer,
+            stop: Some(Box::new(stop)),
+            _root: root,
+        })
+    }
+}
+
+impl Drop for Ephemeral {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop()
+        }
+    }
+}
+
+/// Run all checks against [`Options::remote`].
+///
+/// An error is returned only if the local peers could not be started.
+pub async fn run(opts: Options) -> anyhow::Result<Report> {
+    let Options {
+        network,
+        remote,
+        urn,
+        timeout,
+    } = opts;
+    let mut report = Report {
+        remote: remote.peer_id,
+        outcomes: Vec::with_capacity(Check::ALL.len()),
+    };
+
+    let local = Ephemeral::start(network.clone(), None).await?;
+    let interrogation = report
+        .run(Check::Handshake, timeout, async {
+            local
+                .peer
+                .interrogate(&remote)
+                .await
+                .map(|i| (i, None))
+                .map_err(|e| e.to_string())
+        })
+        .await;
+    let interrogation = match interrogation {
+        Some(i) => i,
+        None => {
+            for check in &Check::ALL[1..] {
+                report.skip(*check, "no connection")
+            }
+            return Ok(report);
+        },
+    };
+
+    interrogate(&mut report, &interrogation, timeout).await;
+
+    report
+        .run(Check::Ping, timeout, async {
+            let rtt = local.peer.ping(&remote).await.map_err(|e| e.to_string())?;
+            Ok(((), Some(format!("rtt {:?}", rtt))))
+        })
+        .await;
+
+    let probe = urn.clone().unwrap_or_else(unknown_urn);
+    report
+        .run(Check::Inventory, timeout, async {
+            let shared = local
+                .peer
+                .inventory(&remote, Some(probe.clone()))
+                .await
+                .map_err(|e| e.to_string())?;
+            let detail = match &urn {
+                Some(urn) if !shared.urns.contains(urn) => {
+                    return Err(format!("{} is not shared", urn))
+                },
+                Some(_) => None,
+                None if !shared.urns.is_empty() => {
+                    return Err(format!("unknown {} is shared", probe))
+                },
+                None => None,
+            };
+            Ok(((), detail))
+        })
+        .await;
+
+    gossip_echo(&mut report, &network, &remote, timeout).await?;
+
+    match urn {
+        None => report.skip(Check::Replication, "no urn given"),
+        Some(urn) => {
+            report
+                .run(Check::Replication, timeout, async {
+                    local
+                        .peer
+                        .replicate(&remote, urn, None)
+                        .await
+                        .map(|_| ((), None))
+                        .map_err(|e| e.to_string())
+                })
+                .await;
+        },
+    }
+
+    Ok(report)
+}
+
+async fn interrogate(report: &mut Report, interrogation: &Interrogation, timeout: Duration) {
+    report
+        .run(Check::Advertisement, timeout, async {
+            let ad = interrogation
+                .peer_advertisement()
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(((), Some(format!("listening on {:?}", ad.listen_addrs))))
+        })
+        .await;
+    if !report.passed(Check::Advertisement) {
+        for check in [Check::EchoAddr, Check::Urns, Check::ClockSkew] {
+            report.skip(check, "interrogation unavailable")
+        }
+        return;
+    }
+
+    report
+        .run(Check::EchoAddr, timeout, async {
+            let addr = interrogation.echo_addr().await.map_err(|e| e.to_string())?;
+            Ok(((), Some(addr.to_string())))
+        })
+        .await;
+    report
+        .run(Check::Urns, timeout, async {
+            interrogation
+                .urns()
+                .await
+                .map(|_| ((), None))
+                .map_err(|e| e.to_string())
+        })
+        .await;
+    report
+        .run(Check::ClockSkew, timeout, async {
+            let skew = interrogation
+                .clock_skew()
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(((), Some(format!("offset {}ms", skew.offset_ms))))
+        })
+        .await;
+}
+
+/// Two local peers join the remote's membership. One of them announces an
+/// update to a URN nobody has, which the remote is expected to relay to the
+/// other.
+async fn gossip_echo(
+    report: &mut Report,
+    network: &Network,
+    remote: &Remote,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let announcer = Ephemeral::start(network.clone(), Some(remote)).await?;
+    let listener = Ephemeral::start(network.clone(), Some(remote)).await?;
+
+    report
+        .run(Check::Membership, timeout, async {
+            for peer in [&announcer.peer, &listener.peer] {
+                while !peer.connected_peers().await.contains(&remote.peer_id) {
+                    tokio::time::sleep(Duration::from_millis(100)).await
+                }
+            }
+            Ok(((), None))
+        })
+        .await;
+    if !report.passed(Check::Membership) {
+        report.skip(Check::GossipEcho, "not a member");
+        return Ok(());
+    }
+
+    let urn = unknown_urn();
+    let events = listener.peer.subscribe().boxed();
+    report
+        .run(Check::GossipEcho, timeout, async {
+            announcer
+                .peer
+                .announce(gossip::Payload {
+                    urn: urn.clone(),
+                    rev: None,
+                    origin: None,
+                })
+                .map_err(|_| "announcer is not running".to_owned())?;
+            event::upstream::expect(
+                events,
+                |evt| match evt {
+                    event::Upstream::Gossip(gossip) => match gossip.as_ref() {
+                        Gossip::Put { payload, .. } => payload.urn == urn,
+                    },
+                    _ => false,
+                },
+                timeout,
+            )
+            .await
+            .map(|_| ((), None))
+            .map_err(|e| e.to_string())
+        })
+        .await;
+
+    Ok(())
+}
+
+/// A URN no peer has, unique to this run.
+fn unknown_urn() -> Urn {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let oid: git_ext::Oid = format!("{:040x}", nanos)
+        .parse()
+        .expect("40 hex digits are a valid oid");
+    Urn::new(oid)
+}
@@ -4,4 +4,5 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 pub mod credential;
+pub mod push;
 pub mod remote_helper;
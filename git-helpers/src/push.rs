@@ -0,0 +1,131 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Propagation of pushed changes to the network.
+//!
+//! A successful `git push` to a `rad://` remote updates the monorepo and
+//! regenerates `rad/signed_refs`. If a `linkd` node is running for the
+//! profile, [`propagate`] asks it to announce the new signed refs, and to
+//! request a pull of the URN from each of the configured seeds. Progress and
+//! the per-seed results are written to stderr, which git shows as part of the
+//! push output.
+//!
+//! Failing to propagate does not fail the push: the changes are in the
+//! monorepo, and will be announced the next time the node syncs.
+
+use std::{io, path::Path};
+
+use librad::{
+    git::{
+        storage::{ReadOnly, ReadOnlyStorage as _},
+        types::{Namespace, Reference},
+        Urn,
+    },
+    git_ext::Oid,
+    paths,
+    profile::Profile,
+    PeerId,
+};
+use lnk_clib::{
+    rpc::{
+        client::{Command, Connection, Replies, Reply},
+        io::SocketTransport,
+        messages::RecvPayload,
+    },
+    seed::{store::FileStore, Seeds},
+};
+
+const LINKD_CLIENT_NAME: &str = "git-remote-rad";
+
+/// Announce the signed refs of `urn` and request seeds to pull them, via the
+/// `linkd` node of `peer_id`.
+pub fn propagate(profile: &Profile, peer_id: PeerId, urn: Urn) -> anyhow::Result<()> {
+    let at = {
+        let storage = ReadOnly::open(profile.paths())?;
+        let signed_refs: Reference<_, PeerId, _> =
+            Reference::rad_signed_refs(Namespace::from(&urn), None);
+        storage.reference_oid(&signed_refs)?
+    };
+    let socket = profile.paths().rpc_socket(peer_id);
+    if !socket.exists() {
+        report("linkd is not running, changes will be announced once it is started");
+        return Ok(());
+    }
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(async move {
+            announce(&socket, urn.clone(), at).await?;
+            request_pulls(&socket, urn).await
+        })
+}
+
+async fn announce(socket: &Path, urn: Urn, at: Oid) -> anyhow::Result<()> {
+    let conn = Connection::connect(LINKD_CLIENT_NAME, socket).await?;
+    let replies = Command::announce(urn, at).execute_with_reply(conn).await?;
+    match drain(replies).await? {
+        Ok(_) => report("announced new refs"),
+        Err(msg) => report(&format!("announcement failed: {}", msg)),
+    }
+    Ok(())
+}
+
+async fn request_pulls(socket: &Path, urn: Urn) -> anyhow::Result<()> {
+    let store = FileStore::<String>::new(paths::seeds()?)?;
+    let (seeds, failures) = Seeds::load(&store, None).await?;
+    for fail in failures {
+        report(&format!("skipping seed: {}", fail));
+    }
+    if seeds.is_empty() {
+        report("no seeds configured, not requesting pulls");
+        return Ok(());
+    }
+
+    for seed in seeds.0 {
+        let name = seed.label.clone().unwrap_or_else(|| seed.peer.to_string());
+        let conn = Connection::connect(LINKD_CLIENT_NAME, socket).await?;
+        let replies = Command::request_pull(urn.clone(), seed.peer, seed.addrs)
+            .execute_with_reply(conn)
+            .await?;
+        match drain(replies).await? {
+            Ok(response) => report(&format!(
+                "{}: ok, {} refs updated, {} pruned",
+                name,
+                response.0.refs.len(),
+                response.0.pruned.len()
+            )),
+            Err(msg) => report(&format!("{}: failed: {}", name, msg)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Wait for the final reply to a command, relaying progress messages.
+///
+/// The outer error is a transport error, the inner one an error reported by
+/// the node.
+async fn drain<R>(mut replies: Replies<SocketTransport, R>) -> anyhow::Result<Result<R, String>>
+where
+    R: RecvPayload,
+{
+    loop {
+        match replies.next().await {
+            Ok(Reply::Progress { replies: next, msg }) => {
+                report(&msg);
+                replies = next;
+            },
+            Ok(Reply::Success { payload, .. }) => return Ok(Ok(payload)),
+            Ok(Reply::Error { msg, .. }) => return Ok(Err(msg)),
+            Err((_, e)) => return Err(e.into()),
+        }
+    }
+}
+
+fn report(msg: &str) {
+    use std::io::Write as _;
+    let _ = writeln!(io::stderr(), "rad: {}", msg);
+}
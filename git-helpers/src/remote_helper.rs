@@ -24,11 +24,12 @@ use librad::{
         url::LocalUrl,
     },
     profile::Profile,
+    PeerId,
     PublicKey,
     SecretKey,
 };
 
-use crate::credential;
+use crate::{credential, push};
 
 #[derive(Default)]
 pub struct Config {
@@ -58,15 +59,16 @@ See https://git-scm.com/docs/git-remote-ext for more detail."#
 
     let git_dir = env::var("GIT_DIR").map(PathBuf::from)?;
 
-    let mut transport = {
-        let profile = Profile::load()?;
+    let profile = Profile::load()?;
+    let (mut transport, peer_id) = {
         let paths = profile.paths().to_owned();
         let signer = match config.signer {
             Some(signer) => signer,
             None => get_signer(&git_dir, paths.keys_dir(), &url)?,
         };
+        let peer_id = PeerId::from_signer(&signer);
         let settings: Box<dyn CanOpenStorage> = Box::new(Settings { paths, signer });
-        Ok::<_, anyhow::Error>(LocalTransport::from(settings))
+        Ok::<_, anyhow::Error>((LocalTransport::from(settings), peer_id))
     }?;
 
     loop {
@@ -88,10 +90,18 @@ See https://git-scm.com/docs/git-remote-ext for more detail."#
 
             println!();
 
+            let urn = url.urn.clone();
+            let is_push = matches!(service, git2::transport::Service::ReceivePack);
             transport
                 .connect(url, service, Stateful, Localio::inherit())?
                 .wait()?;
 
+            if is_push {
+                if let Err(e) = push::propagate(&profile, peer_id, urn) {
+                    eprintln!("rad: failed to propagate changes: {}", e);
+                }
+            }
+
             break;
         }
 
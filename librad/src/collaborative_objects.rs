@@ -3,16 +3,25 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use crate::git::{
-    identities::{self, any::get as get_identity, local::LocalIdentity, Identities},
-    refs::{self, Refs},
-    storage::{read::Error as ReadError, ReadOnlyStorage, Storage},
-    types::{Namespace, Reference, RefsCategory},
+use crate::{
+    git::{
+        identities::{self, any::get as get_identity, local::LocalIdentity, Identities},
+        refs::{self, Refs},
+        storage::{audit, read::Error as ReadError, ReadOnlyStorage, Storage},
+        tracking,
+        types::{Namespace, Reference, RefsCategory},
+    },
+    PeerId,
 };
 
-use std::{collections::HashMap, convert::TryFrom, str::FromStr};
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::TryFrom,
+    str::FromStr,
+};
 
 pub use cob::{
+    authorization,
     AuthorizingIdentity,
     ChangeGraphInfo,
     CollaborativeObject,
@@ -23,10 +32,11 @@ pub use cob::{
     ObjectRefs,
     RefsStorage,
     Schema,
+    Tombstone,
     TypeName,
 };
 use link_crypto::BoxedSigner;
-use link_identities::git::{SomeIdentity, Urn};
+use link_identities::git::{SomeIdentity, Urn, VerifiedPerson};
 
 mod error {
     use super::RefsError;
@@ -64,6 +74,15 @@ mod error {
         ResolveAuth(#[from] ResolveAuthorizer),
     }
 
+    #[allow(clippy::large_enum_variant)]
+    #[derive(Debug, Error)]
+    pub enum Redact {
+        #[error(transparent)]
+        Cob(#[from] cob::error::Redact<RefsError>),
+        #[error(transparent)]
+        ResolveAuth(#[from] ResolveAuthorizer),
+    }
+
     #[allow(clippy::large_enum_variant)]
     #[derive(Debug, Error)]
     pub enum ResolveAuthorizer {
@@ -103,10 +122,25 @@ pub struct UpdateObjectSpec {
     pub changes: History,
 }
 
+/// The data required to redact a change of a collaborative object
+pub struct RedactSpec {
+    /// The object ID of the object containing the change
+    pub object_id: ObjectId,
+    /// The typename of the object containing the change
+    pub typename: TypeName,
+    /// The commit of the change to redact
+    pub change: git2::Oid,
+    /// An optional reason for the redaction
+    pub reason: Option<String>,
+}
+
 pub struct CollaborativeObjects<'a> {
     signer: BoxedSigner,
     store: &'a Storage,
     cache_dir: Option<std::path::PathBuf>,
+    /// Whether to record rejected changes in the audit log, see
+    /// [`CollaborativeObjects::audit_received`].
+    audit: bool,
 }
 
 impl<'a> CollaborativeObjects<'a> {
@@ -119,6 +153,62 @@ impl<'a> CollaborativeObjects<'a> {
             signer,
             store,
             cache_dir,
+            audit: false,
+        }
+    }
+
+    /// A view of the same storage which records changes rejected as
+    /// unauthorized in the audit log.
+    ///
+    /// Objects are evaluated on every read, so rejections are only audited
+    /// when changes are written or received, not when they are retrieved.
+    fn audited(&self) -> CollaborativeObjects<'a> {
+        CollaborativeObjects {
+            signer: self.signer.clone(),
+            store: self.store,
+            cache_dir: self.cache_dir.clone(),
+            audit: true,
+        }
+    }
+
+    /// Evaluate the objects whose refs were updated by a replication of
+    /// `identity_urn`, recording changes rejected as unauthorized in the
+    /// audit log.
+    ///
+    /// `updated` are the names of the updated refs, refs which are not refs of
+    /// collaborative objects are ignored. Failures are logged.
+    pub fn audit_received<'b, I>(&self, identity_urn: &Urn, updated: I)
+    where
+        I: IntoIterator<Item = &'b str>,
+    {
+        let objects = updated
+            .into_iter()
+            .filter_map(|name| {
+                let mut components = name.rsplitn(3, '/');
+                let oid = components.next()?;
+                let typename = components.next()?;
+                let category = components.next()?;
+                if category != "cobs" && !category.ends_with("/cobs") {
+                    return None;
+                }
+                Some((
+                    TypeName::from_str(typename).ok()?,
+                    ObjectId::from_str(oid).ok()?,
+                ))
+            })
+            .collect::<BTreeSet<_>>();
+
+        let audited = self.audited();
+        for (typename, oid) in objects {
+            if let Err(e) = audited.retrieve(identity_urn, &typename, &oid) {
+                tracing::debug!(
+                    urn = %identity_urn,
+                    typename = %typename,
+                    object = %oid,
+                    err = %e,
+                    "failed to evaluate received object"
+                );
+            }
         }
     }
 
@@ -187,7 +277,7 @@ impl<'a> CollaborativeObjects<'a> {
     ) -> Result<cob::CollaborativeObject, error::Update> {
         cob::update(cob::UpdateObjectArgs {
             refs_storage: self,
-            identity_storage: &self,
+            identity_storage: &&self.audited(),
             signer: &self.signer,
             repo: self.store.as_raw(),
             author: whoami,
@@ -202,6 +292,47 @@ impl<'a> CollaborativeObjects<'a> {
         .map_err(error::Update::from)
     }
 
+    /// Publish a tombstone for a change, see [`cob::tombstone`]. Only
+    /// maintainers of `within_identity` may redact changes.
+    pub fn redact(
+        &self,
+        whoami: &LocalIdentity,
+        within_identity: &Urn,
+        spec: RedactSpec,
+    ) -> Result<Tombstone, error::Redact> {
+        cob::redact(cob::RedactArgs {
+            refs_storage: self,
+            repo: self.store.as_raw(),
+            signer: &self.signer,
+            author: whoami,
+            authorizing_identity: resolve_authorizing_identity(self.store, within_identity)?
+                .as_ref(),
+            object_id: spec.object_id,
+            typename: spec.typename,
+            change: spec.change,
+            reason: spec.reason,
+        })
+        .map_err(error::Redact::from)
+    }
+
+    /// The valid tombstones of an object.
+    pub fn tombstones(
+        &self,
+        identity_urn: &Urn,
+        typename: &cob::TypeName,
+        oid: &cob::ObjectId,
+    ) -> Result<Vec<Tombstone>, error::Retrieve> {
+        cob::tombstones(
+            self,
+            &self,
+            self.store.as_raw(),
+            resolve_authorizing_identity(self.store, identity_urn)?.as_ref(),
+            typename,
+            oid,
+        )
+        .map_err(error::Retrieve::from)
+    }
+
     pub fn changegraph_info_for_object(
         &self,
         identity_urn: &Urn,
@@ -420,11 +551,42 @@ impl ObjRefMatcher {
     }
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum IdentityError {
+    #[error(transparent)]
+    Git2(#[from] git2::Error),
+    #[error(transparent)]
+    Tracking(#[from] tracking::error::IsTracked),
+}
+
 impl<'a> IdentityStorage for &'a CollaborativeObjects<'a> {
-    type Error = git2::Error;
+    type Error = IdentityError;
 
     fn delegate_oid(&self, urn: Urn) -> Result<git2::Oid, Self::Error> {
         let refname = Reference::rad_id(Namespace::from(urn));
-        self.store.as_raw().refname_to_id(&refname.to_string())
+        Ok(self.store.as_raw().refname_to_id(&refname.to_string())?)
+    }
+
+    fn is_tracked(&self, urn: &Urn, person: &VerifiedPerson) -> Result<bool, Self::Error> {
+        for key in person.delegations().iter() {
+            if tracking::is_tracked(self.store, urn, Some(PeerId::from(*key)))? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn unauthorized(&self, rejected: &authorization::Unauthorized<'_>) {
+        if !self.audit {
+            return;
+        }
+        self.store.audit(audit::Event::CobRejected {
+            urn: rejected.identity.clone(),
+            typename: rejected.typename.to_string(),
+            object: rejected.object.to_string(),
+            change: rejected.change.into(),
+            author: rejected.author.clone(),
+            reason: rejected.reason.to_owned(),
+        });
     }
 }
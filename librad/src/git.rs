@@ -3,6 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+pub mod alias;
 #[cfg(not(feature = "replication-v3"))]
 pub mod fetch;
 pub mod identities;
@@ -12,6 +13,7 @@ pub mod p2p;
 pub mod refs;
 #[cfg(not(feature = "replication-v3"))]
 pub mod replication;
+pub mod signatures;
 
 pub mod storage;
 pub use storage::Storage;
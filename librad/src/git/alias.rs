@@ -0,0 +1,393 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Human-readable aliases for URNs.
+//!
+//! An [`Alias`] is a short name for a URN, recorded in the storage config as
+//! `rad.alias.<alias>`. Aliases are local: they are not replicated, and the
+//! same name may refer to different URNs on different peers.
+//!
+//! A project may declare a [`Suggested`] alias as a payload extension, which
+//! [`suggest`] offers when the project is aliased. Otherwise, the suggestion
+//! is derived from the name of the project. Suggestions never shadow an alias
+//! bound to a different URN, and [`set`] refuses to rebind an alias unless
+//! asked to.
+//!
+//! [`resolve`] turns a [`Name`] given by a user into a URN. A full URN is
+//! taken as is, an alias takes precedence over the prefix of an id, and a
+//! prefix must match exactly one URN in storage.
+
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{
+    identities::{any, project},
+    storage::{config, ReadOnly, Storage},
+};
+use crate::identities::{
+    git::Urn,
+    payload::{Extension, HasNamespace, Kind},
+};
+
+lazy_static! {
+    static ref SUGGESTED_NAMESPACE_V1: Url =
+        Url::parse("https://radicle.xyz/link/project/alias/v1").unwrap();
+}
+
+/// The maximum length of an [`Alias`].
+pub const MAX_LEN: usize = 64;
+
+/// How many numbered variants of a suggestion are tried when it is taken.
+const MAX_SUGGESTIONS: usize = 99;
+
+pub mod error {
+    use thiserror::Error;
+
+    use super::Alias as Name;
+    use crate::{
+        git::{identities, storage::config},
+        identities::git::Urn,
+    };
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Alias {
+        #[error("empty alias")]
+        Empty,
+
+        #[error("alias is longer than {} characters", super::MAX_LEN)]
+        TooLong,
+
+        #[error(
+            "invalid alias `{0}`, it must start with a letter and contain only letters, digits and `-`"
+        )]
+        Invalid(String),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Set {
+        #[error("alias `{alias}` is already taken by {urn}")]
+        Taken { alias: Name, urn: Urn },
+
+        #[error(transparent)]
+        Config(#[from] config::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum List {
+        #[error(transparent)]
+        Config(#[from] config::Error),
+
+        #[error(transparent)]
+        Storage(#[from] crate::git::storage::read::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Resolve {
+        #[error("no alias or URN matching `{0}`")]
+        NotFound(String),
+
+        #[error("`{prefix}` is ambiguous, it matches {}", display_urns(.candidates))]
+        Ambiguous {
+            prefix: String,
+            candidates: Vec<Urn>,
+        },
+
+        #[error(transparent)]
+        Config(#[from] config::Error),
+
+        #[error(transparent)]
+        Identities(#[from] identities::Error),
+
+        #[error(transparent)]
+        Storage(#[from] crate::git::storage::read::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Suggest {
+        #[error("malformed suggested alias")]
+        Extension(#[source] serde_json::Error),
+
+        #[error(transparent)]
+        Config(#[from] config::Error),
+
+        #[error(transparent)]
+        Identities(#[from] identities::Error),
+
+        #[error(transparent)]
+        Storage(#[from] crate::git::storage::read::Error),
+    }
+
+    fn display_urns(urns: &[Urn]) -> String {
+        urns.iter()
+            .map(|urn| urn.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// A short, local name for a URN.
+///
+/// Aliases are case-insensitive, and normalised to lower case. They must
+/// start with an ASCII letter, and contain only ASCII letters, digits and
+/// `-`, so that they are valid git config keys.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Alias(String);
+
+impl Alias {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Derive an alias from a free-form `name`, eg. the name of a project.
+    ///
+    /// Runs of characters which are not allowed are replaced by a single `-`,
+    /// and leading characters which are not letters are dropped. Returns
+    /// `None` if nothing is left.
+    pub fn slugify(name: &str) -> Option<Self> {
+        let mut slug = String::with_capacity(name.len());
+        for c in name.chars() {
+            if c.is_ascii_alphabetic() || (c.is_ascii_digit() && !slug.is_empty()) {
+                slug.push(c.to_ascii_lowercase())
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-')
+            }
+        }
+        slug.truncate(MAX_LEN);
+        let slug = slug.trim_end_matches('-');
+        (!slug.is_empty()).then(|| Self(slug.to_owned()))
+    }
+
+    /// `self` with the suffix `-<n>`, eg. to avoid a collision.
+    fn numbered(&self, n: usize) -> Option<Self> {
+        let suffix = format!("-{}", n);
+        let mut base = self.0.clone();
+        base.truncate(MAX_LEN.saturating_sub(suffix.len()));
+        Self::from_str(&format!("{}{}", base.trim_end_matches('-'), suffix)).ok()
+    }
+}
+
+impl fmt::Display for Alias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for Alias {
+    type Err = error::Alias;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(error::Alias::Empty);
+        }
+        if s.len() > MAX_LEN {
+            return Err(error::Alias::TooLong);
+        }
+        let valid = s.starts_with(|c: char| c.is_ascii_alphabetic())
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(error::Alias::Invalid(s.to_owned()));
+        }
+        Ok(Self(s.to_ascii_lowercase()))
+    }
+}
+
+impl TryFrom<String> for Alias {
+    type Error = error::Alias;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Alias> for String {
+    fn from(alias: Alias) -> Self {
+        alias.0
+    }
+}
+
+/// Payload extension declaring the alias a project would like to be known
+/// by.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Suggested {
+    pub alias: Alias,
+}
+
+impl HasNamespace for Suggested {
+    fn namespace() -> &'static Url {
+        &SUGGESTED_NAMESPACE_V1
+    }
+}
+
+impl Extension for Suggested {
+    fn applies_to(kind: Kind) -> bool {
+        kind == Kind::Project
+    }
+}
+
+/// A URN as given by a user: either the URN itself, or a short name which is
+/// an [`Alias`] or a prefix of the encoded id of a URN.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Name {
+    Urn(Urn),
+    Short(String),
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Urn(urn) => write!(f, "{}", urn),
+            Self::Short(short) => f.write_str(short),
+        }
+    }
+}
+
+impl From<Urn> for Name {
+    fn from(urn: Urn) -> Self {
+        Self::Urn(urn)
+    }
+}
+
+impl FromStr for Name {
+    type Err = error::Alias;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<Urn>() {
+            Ok(urn) => Ok(Self::Urn(urn)),
+            Err(_) if s.is_empty() => Err(error::Alias::Empty),
+            Err(_) => Ok(Self::Short(s.to_owned())),
+        }
+    }
+}
+
+/// Bind `alias` to `urn`.
+///
+/// If `alias` is already bound to a different URN, [`error::Set::Taken`] is
+/// returned, unless `force` is `true`, in which case the binding is replaced.
+pub fn set(storage: &Storage, alias: &Alias, urn: &Urn, force: bool) -> Result<(), error::Set> {
+    let urn = urn.clone().with_path(None);
+    let mut config = storage.config()?;
+    match config.alias(alias)? {
+        Some(existing) if existing != urn && !force => Err(error::Set::Taken {
+            alias: alias.clone(),
+            urn: existing,
+        }),
+        _ => Ok(config.set_alias(alias, &urn)?),
+    }
+}
+
+/// Remove `alias`, returning the URN it was bound to, if any.
+pub fn remove(storage: &Storage, alias: &Alias) -> Result<Option<Urn>, config::Error> {
+    let mut config = storage.config()?;
+    let urn = config.alias(alias)?;
+    if urn.is_some() {
+        config.remove_alias(alias)?;
+    }
+    Ok(urn)
+}
+
+/// All aliases, and the URNs they are bound to.
+pub fn list<S>(storage: &S) -> Result<Vec<(Alias, Urn)>, error::List>
+where
+    S: AsRef<ReadOnly>,
+{
+    Ok(storage.as_ref().config()?.aliases()?)
+}
+
+/// The aliases bound to `urn`.
+pub fn aliases_of<S>(storage: &S, urn: &Urn) -> Result<Vec<Alias>, error::List>
+where
+    S: AsRef<ReadOnly>,
+{
+    let urn = urn.clone().with_path(None);
+    Ok(list(storage)?
+        .into_iter()
+        .filter_map(|(alias, bound)| (bound == urn).then(|| alias))
+        .collect())
+}
+
+/// Resolve `name` to a URN.
+pub fn resolve<S>(storage: &S, name: &Name) -> Result<Urn, error::Resolve>
+where
+    S: AsRef<ReadOnly>,
+{
+    let short = match name {
+        Name::Urn(urn) => return Ok(urn.clone()),
+        Name::Short(short) => short,
+    };
+
+    let storage = storage.as_ref();
+    if let Ok(alias) = short.parse::<Alias>() {
+        if let Some(urn) = storage.config()?.alias(&alias)? {
+            return Ok(urn);
+        }
+    }
+
+    let prefix = short.strip_prefix("rad:git:").unwrap_or(short);
+    let mut candidates = any::list_urns(storage)?
+        .filter(|urn| match urn {
+            Ok(urn) => urn.encode_id().starts_with(prefix),
+            Err(_) => true,
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match candidates.len() {
+        0 => Err(error::Resolve::NotFound(short.clone())),
+        1 => Ok(candidates.remove(0)),
+        _ => Err(error::Resolve::Ambiguous {
+            prefix: short.clone(),
+            candidates,
+        }),
+    }
+}
+
+/// Suggest an alias for the project `urn`.
+///
+/// This is the [`Suggested`] alias declared by the project, or else derived
+/// from its name. If the alias is bound to a different URN, a numbered
+/// variant is suggested instead, eg. `radicle-link-2`. Returns `None` if the
+/// project is not found, or no alias could be derived.
+pub fn suggest<S>(storage: &S, urn: &Urn) -> Result<Option<Alias>, error::Suggest>
+where
+    S: AsRef<ReadOnly>,
+{
+    let storage = storage.as_ref();
+    let urn = urn.clone().with_path(None);
+    let project = match project::get(storage, &urn)? {
+        None => return Ok(None),
+        Some(project) => project,
+    };
+    let base = match project
+        .payload()
+        .get_ext::<Suggested>()
+        .map_err(error::Suggest::Extension)?
+    {
+        Some(Suggested { alias }) => Some(alias),
+        None => Alias::slugify(project.payload().subject.name.as_str()),
+    };
+    let base = match base {
+        None => return Ok(None),
+        Some(base) => base,
+    };
+
+    let config = storage.config()?;
+    let candidates = Some(base.clone())
+        .into_iter()
+        .chain((2..=MAX_SUGGESTIONS).filter_map(|n| base.numbered(n)));
+    for alias in candidates {
+        match config.alias(&alias)? {
+            Some(bound) if bound != urn => continue,
+            _ => return Ok(Some(alias)),
+        }
+    }
+
+    Ok(None)
+}
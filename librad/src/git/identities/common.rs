@@ -3,14 +3,49 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use git_ext::{self as ext, is_exists_err};
-use std_ext::result::ResultExt as _;
+use git_ext as ext;
 
 use super::super::{
-    storage::{self, ReadOnlyStorage as _, Storage},
-    types::{Force, Namespace, Reference},
+    storage::{
+        self,
+        audit,
+        reflog::{self, Op},
+        txn::{self, Previous},
+        ReadOnlyStorage as _,
+        Storage,
+    },
+    types::{Namespace, Reference},
 };
-use crate::identities::git::Urn;
+use crate::identities::{
+    git::Urn,
+    payload::{Payload, Registry, Subject},
+};
+
+/// The payload extensions checked before signing an identity document.
+pub fn extensions() -> Registry {
+    Registry::builtin()
+        .with::<cob::authorization::Rules>()
+        .with::<crate::git::alias::Suggested>()
+}
+
+/// Check the payload extensions registered in [`extensions`].
+pub fn validate_payload<T>(
+    payload: &Payload<T>,
+) -> Result<(), crate::identities::payload::extension::error::Invalid>
+where
+    T: Subject,
+{
+    extensions().validate(payload)
+}
+
+/// Record in the audit log that `revision` of the identity `urn` was signed
+/// with the storage key.
+pub fn audit_signed(storage: &Storage, urn: &Urn, revision: ext::Oid) {
+    storage.audit(audit::Event::IdentitySigned {
+        urn: urn.clone(),
+        revision,
+    })
+}
 
 /// Ad-hoc helper type for conveniently managing `rad/id` refs
 pub struct IdRef<'a>(&'a Urn);
@@ -31,30 +66,55 @@ impl<'a> IdRef<'a> {
             .reference_oid(&Reference::rad_id(Namespace::from(self.0)))
     }
 
+    /// Create the `rad/id` ref, unless it already exists.
     pub fn create(
         &self,
         storage: &Storage,
         target: impl AsRef<git2::Oid>,
-    ) -> Result<(), git2::Error> {
-        Reference::rad_id(Namespace::from(self.0))
-            .create(
-                storage.as_raw(),
-                *target.as_ref(),
-                Force::False,
-                &format!("Initial rad/id for {}", self.0),
-            )
-            .and(Ok(()))
-            .or_matches(is_exists_err, || Ok(()))
+    ) -> Result<(), txn::error::Commit> {
+        self.create_in(
+            storage.transaction(self.0, reflog::Message::new(Op::Identity, "create")),
+            target,
+        )
+        .commit()
+        .map(|_| ())
+        .or_else(|e| match e {
+            txn::error::Commit::Rejected { .. } => Ok(()),
+            e => Err(e),
+        })
     }
 
     pub fn update(
         &self,
         storage: &Storage,
         target: impl AsRef<git2::Oid>,
-        msg: &str,
-    ) -> Result<(), git2::Error> {
-        Reference::rad_id(Namespace::from(self.0))
-            .create(storage.as_raw(), *target.as_ref(), Force::True, msg)
-            .and(Ok(()))
+        msg: reflog::Message,
+    ) -> Result<(), txn::error::Commit> {
+        self.update_in(storage.transaction(self.0, msg), target)
+            .commit()
+            .map(|_| ())
     }
+
+    /// Add the creation of the `rad/id` ref to `tx`.
+    pub fn create_in<'a>(
+        &self,
+        tx: txn::Transaction<'a>,
+        target: impl AsRef<git2::Oid>,
+    ) -> txn::Transaction<'a> {
+        tx.write(rad_id(), *target.as_ref(), Previous::MustNotExist)
+    }
+
+    /// Add the update of the `rad/id` ref to `tx`.
+    pub fn update_in<'a>(
+        &self,
+        tx: txn::Transaction<'a>,
+        target: impl AsRef<git2::Oid>,
+    ) -> txn::Transaction<'a> {
+        tx.write(rad_id(), *target.as_ref(), Previous::Any)
+    }
+}
+
+/// The `rad/id` ref, relative to its namespace.
+fn rad_id() -> ext::RefLike {
+    reflike!("refs/rad/id")
 }
@@ -0,0 +1,236 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Structured diffs between two revisions of an identity document.
+//!
+//! A [`Diff`] summarises what an update to a [`Person`] or [`Project`] would
+//! change, so it can be reviewed before the update is adopted -- either by a
+//! user merging an update manually, or during replication, see
+//! [`crate::git::replication::Review`].
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use either::Either;
+use git_ext as ext;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{super::storage::Storage, error::Error, Person, Project, Urn};
+use crate::{identities::delegation::Delegations as _, PublicKey};
+
+/// The changes between two revisions of an identity document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diff {
+    /// The identity both revisions belong to.
+    pub urn: Urn,
+    /// The commit of the old revision.
+    pub old: ext::Oid,
+    /// The commit of the new revision.
+    pub new: ext::Oid,
+    /// Whether the new revision descends from the old one.
+    ///
+    /// Adopting an update which is not a fast-forward discards the history
+    /// of the old revision.
+    pub fast_forward: bool,
+    pub delegates: Changes<Delegate>,
+    /// The quorum threshold, if it changed.
+    ///
+    /// See [`crate::identities::delegation::Delegations::quorum_threshold`].
+    pub threshold: Option<Change<usize>>,
+    /// The changed values of the payload, ordered by namespace and field.
+    pub payload: Vec<PayloadChange>,
+}
+
+impl Diff {
+    /// Whether the two revisions are the same.
+    pub fn is_empty(&self) -> bool {
+        self.old == self.new
+    }
+
+    /// Whether delegates were added or removed, or the quorum threshold
+    /// changed.
+    pub fn changes_delegates(&self) -> bool {
+        !self.delegates.is_empty() || self.threshold.is_some()
+    }
+
+    /// Whether adopting the update without further confirmation is safe,
+    /// ie. it is a fast-forward which does not change the delegations.
+    pub fn is_trivial(&self) -> bool {
+        self.fast_forward && !self.changes_delegates()
+    }
+}
+
+/// A delegate of an identity.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Delegate {
+    /// A key delegated to directly.
+    Key(PublicKey),
+    /// A [`Person`] delegated to indirectly, ie. all the keys it delegates
+    /// to.
+    Person(Urn),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Changes<T> {
+    pub added: BTreeSet<T>,
+    pub removed: BTreeSet<T>,
+}
+
+impl<T: Ord + Clone> Changes<T> {
+    fn between(old: &BTreeSet<T>, new: &BTreeSet<T>) -> Self {
+        Self {
+            added: new.difference(old).cloned().collect(),
+            removed: old.difference(new).cloned().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+/// A value of the payload which was added, removed or modified.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PayloadChange {
+    /// The namespace of the subject or extension the value belongs to.
+    pub namespace: String,
+    /// The field within the namespace, unless the namespace is not an object
+    /// in either revision.
+    pub field: Option<String>,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+/// Diff two revisions of a [`Person`].
+pub fn person(storage: &Storage, old: &Person, new: &Person) -> Result<Diff, Error> {
+    let delegates = |person: &Person| {
+        person
+            .doc
+            .delegations
+            .iter()
+            .copied()
+            .map(Delegate::Key)
+            .collect::<BTreeSet<_>>()
+    };
+    diff(
+        storage,
+        Revisions {
+            urn: old.urn(),
+            old: (old.content_id, delegates(old), old.quorum_threshold()),
+            new: (new.content_id, delegates(new), new.quorum_threshold()),
+        },
+        &old.doc.payload,
+        &new.doc.payload,
+    )
+}
+
+/// Diff two revisions of a [`Project`].
+pub fn project(storage: &Storage, old: &Project, new: &Project) -> Result<Diff, Error> {
+    let delegates = |project: &Project| {
+        project
+            .doc
+            .delegations
+            .iter()
+            .map(|delegate| match delegate {
+                Either::Left(key) => Delegate::Key(*key),
+                Either::Right(person) => Delegate::Person(person.urn()),
+            })
+            .collect::<BTreeSet<_>>()
+    };
+    diff(
+        storage,
+        Revisions {
+            urn: old.urn(),
+            old: (old.content_id, delegates(old), old.quorum_threshold()),
+            new: (new.content_id, delegates(new), new.quorum_threshold()),
+        },
+        &old.doc.payload,
+        &new.doc.payload,
+    )
+}
+
+struct Revisions {
+    urn: Urn,
+    old: (ext::Oid, BTreeSet<Delegate>, usize),
+    new: (ext::Oid, BTreeSet<Delegate>, usize),
+}
+
+fn diff<P: Serialize>(
+    storage: &Storage,
+    Revisions { urn, old, new }: Revisions,
+    old_payload: &P,
+    new_payload: &P,
+) -> Result<Diff, Error> {
+    let fast_forward = old.0 == new.0 || storage.as_raw().graph_descendant_of(*new.0, *old.0)?;
+    Ok(Diff {
+        urn,
+        old: old.0,
+        new: new.0,
+        fast_forward,
+        delegates: Changes::between(&old.1, &new.1),
+        threshold: (old.2 != new.2).then(|| Change {
+            old: old.2,
+            new: new.2,
+        }),
+        payload: payload(old_payload, new_payload)?,
+    })
+}
+
+fn payload<P: Serialize>(old: &P, new: &P) -> Result<Vec<PayloadChange>, Error> {
+    let mut old = namespaces(old)?;
+    let mut new = namespaces(new)?;
+    let keys = old
+        .keys()
+        .chain(new.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    let mut changes = Vec::new();
+    for namespace in keys {
+        match (old.remove(&namespace), new.remove(&namespace)) {
+            (Some(Value::Object(old)), Some(Value::Object(new))) => {
+                let fields = old
+                    .keys()
+                    .chain(new.keys())
+                    .cloned()
+                    .collect::<BTreeSet<_>>();
+                for field in fields {
+                    let (old, new) = (old.get(&field).cloned(), new.get(&field).cloned());
+                    if old != new {
+                        changes.push(PayloadChange {
+                            namespace: namespace.clone(),
+                            field: Some(field),
+                            old,
+                            new,
+                        })
+                    }
+                }
+            },
+            (old, new) if old != new => changes.push(PayloadChange {
+                namespace,
+                field: None,
+                old,
+                new,
+            }),
+            _ => {},
+        }
+    }
+
+    Ok(changes)
+}
+
+/// The payload as a map from namespace to value.
+fn namespaces<P: Serialize>(payload: &P) -> Result<BTreeMap<String, Value>, Error> {
+    match serde_json::to_value(payload)? {
+        Value::Object(map) => Ok(map.into_iter().collect()),
+        _ => Ok(BTreeMap::new()),
+    }
+}
@@ -22,6 +22,9 @@ pub enum Error {
     #[error("the URN {0} does not exist")]
     NotFound(Urn),
 
+    #[error(transparent)]
+    Extension(#[from] identities::payload::extension::error::Invalid),
+
     #[error("failed to build ref from URN")]
     RefFromUrn(#[from] reference::FromUrnError),
 
@@ -43,6 +46,9 @@ pub enum Error {
     #[error(transparent)]
     Storage(#[from] storage::Error),
 
+    #[error(transparent)]
+    Transaction(#[from] storage::txn::error::Commit),
+
     #[error(transparent)]
     Verify(#[from] identities::git::error::Verify),
 
@@ -61,6 +67,15 @@ pub enum Error {
     #[error(transparent)]
     ProjHist(#[from] identities::git::error::History<identities::git::ProjectDoc>),
 
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
     #[error(transparent)]
     Git(#[from] git2::Error),
+
+    #[error(transparent)]
+    Lease(#[from] storage::lease::Error),
+
+    #[error(transparent)]
+    NamespaceLock(#[from] storage::lock::Error),
 }
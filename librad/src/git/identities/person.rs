@@ -10,7 +10,12 @@ use radicle_git_ext::{self as ext, is_not_found_err, OneLevel};
 use super::{
     super::{
         refs::Refs,
-        storage::{self, ReadOnlyStorage as _, Storage},
+        storage::{
+            self,
+            reflog::{Message, Op},
+            ReadOnlyStorage as _,
+            Storage,
+        },
         types::Reference,
     },
     common,
@@ -112,8 +117,10 @@ pub fn create<P>(
 where
     P: Into<PersonPayload> + Debug,
 {
+    let payload = payload.into();
+    common::validate_payload(&payload)?;
     let person = {
-        let person = identities(storage).create(payload.into(), delegations, storage.signer())?;
+        let person = identities(storage).create(payload, delegations, storage.signer())?;
         let verified = identities(storage)
             .verify(*person.content_id)
             .map_err(|e| Error::Verify(e.into()))?;
@@ -121,7 +128,9 @@ where
     }?;
 
     let urn = person.urn();
+    let _lock = storage.lock_namespace(&urn)?;
     common::IdRef::from(&urn).create(storage, person.content_id)?;
+    common::audit_signed(storage, &urn, person.revision);
     person.link(storage, &urn)?;
     Refs::update(storage, &urn)?;
 
@@ -142,11 +151,21 @@ where
     P: Into<Option<PersonPayload>> + Debug,
     D: Into<Option<delegation::Direct>> + Debug,
 {
+    let payload = payload.into();
+    if let Some(payload) = &payload {
+        common::validate_payload(payload)?;
+    }
+    let _lock = storage.lock_namespace(urn)?;
     let prev = get(storage, urn)?.ok_or_else(|| Error::NotFound(urn.clone()))?;
     let prev = Verifying::from(prev).signed()?;
     let next = identities(storage).update(prev, payload, delegations, storage.signer())?;
 
-    common::IdRef::from(urn).update(storage, next.content_id, "update")?;
+    common::IdRef::from(urn).update(
+        storage,
+        next.content_id,
+        Message::new(Op::Identity, "update"),
+    )?;
+    common::audit_signed(storage, urn, next.revision);
     if let Some(local_id) = whoami.into() {
         local_id.link(storage, urn)?;
     }
@@ -158,6 +177,7 @@ where
 /// Merge and sign the [`Person`] state as seen by `from`.
 #[tracing::instrument(level = "debug", skip(storage))]
 pub fn merge(storage: &Storage, urn: &Urn, from: PeerId) -> Result<Person, Error> {
+    let _lock = storage.lock_namespace(urn)?;
     let ours = get(storage, urn)?.ok_or_else(|| Error::NotFound(urn.clone()))?;
     let theirs = {
         let (path, rad) = OneLevel::from_qualified(urn::DEFAULT_PATH.clone());
@@ -173,7 +193,12 @@ pub fn merge(storage: &Storage, urn: &Urn, from: PeerId) -> Result<Person, Error
     let theirs = Verifying::from(theirs).signed()?;
     let next = identities(storage).update_from(ours, theirs, storage.signer())?;
 
-    common::IdRef::from(urn).update(storage, next.content_id, &format!("merge from {}", from))?;
+    common::IdRef::from(urn).update(
+        storage,
+        next.content_id,
+        Message::new(Op::Identity, "merge").with_peer(from),
+    )?;
+    common::audit_signed(storage, urn, next.revision);
     Refs::update(storage, urn)?;
 
     Ok(next)
@@ -194,9 +219,7 @@ pub fn fast_forward(storage: &Storage, latest: &VerifiedPerson) -> Result<Option
     let canonical = id_ref.oid(storage)?;
     let tip = latest.content_id;
     Ok(if storage.as_raw().graph_descendant_of(*tip, *canonical)? {
-        id_ref
-            .update(storage, tip, &format!("fast-forward to {}", tip))
-            .map_err(|e| Error::Store(e.into()))?;
+        id_ref.update(storage, tip, Message::new(Op::Identity, "fast-forward"))?;
         Some(tip)
     } else {
         None
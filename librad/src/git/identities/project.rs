@@ -6,12 +6,18 @@
 use std::{convert::TryFrom, fmt::Debug};
 
 use either::Either;
-use git_ext::{is_not_found_err, OneLevel};
+use git_ext::{self as ext, is_not_found_err, OneLevel};
 
 use super::{
     super::{
         refs::Refs as Sigrefs,
-        storage::{self, ReadOnlyStorage as _, Storage},
+        storage::{
+            self,
+            reflog::{Message, Op},
+            txn::Previous,
+            ReadOnlyStorage as _,
+            Storage,
+        },
         types::{namespace, reference, Force, Reference, Single, SymbolicRef},
     },
     common,
@@ -139,9 +145,13 @@ pub fn create<P>(
 where
     P: Into<ProjectPayload> + Debug,
 {
-    let project = identities(storage).create(payload.into(), delegations, storage.signer())?;
+    let payload = payload.into();
+    common::validate_payload(&payload)?;
+    let project = identities(storage).create(payload, delegations, storage.signer())?;
     let urn = project.urn();
+    let _lock = storage.lock_namespace(&urn)?;
     ProjectRefs::Create(&project).apply(storage)?;
+    common::audit_signed(storage, &urn, project.revision);
     whoami.link(storage, &urn)?;
     Sigrefs::update(storage, &urn)?;
 
@@ -162,11 +172,17 @@ where
     P: Into<Option<ProjectPayload>> + Debug,
     D: Into<Option<IndirectDelegation>> + Debug,
 {
+    let payload = payload.into();
+    if let Some(payload) = &payload {
+        common::validate_payload(payload)?;
+    }
+    let _lock = storage.lock_namespace(urn)?;
     let prev = get(storage, urn)?.ok_or_else(|| Error::NotFound(urn.clone()))?;
     let prev = Verifying::from(prev).signed()?;
     let next = identities(storage).update(prev, payload, delegations, storage.signer())?;
 
-    ProjectRefs::Update(&next, "update").apply(storage)?;
+    ProjectRefs::Update(&next, Message::new(Op::Identity, "update")).apply(storage)?;
+    common::audit_signed(storage, urn, next.revision);
     if let Some(local_id) = whoami.into() {
         local_id.link(storage, urn)?;
     }
@@ -178,6 +194,7 @@ where
 /// Merge and sign the [`Project`] state as seen by `from`.
 #[tracing::instrument(level = "debug", skip(storage))]
 pub fn merge(storage: &Storage, urn: &Urn, from: PeerId) -> Result<Project, Error> {
+    let _lock = storage.lock_namespace(urn)?;
     let ours = get(storage, urn)?.ok_or_else(|| Error::NotFound(urn.clone()))?;
     let theirs = {
         let (path, rad) = OneLevel::from_qualified(urn::DEFAULT_PATH.clone());
@@ -193,7 +210,9 @@ pub fn merge(storage: &Storage, urn: &Urn, from: PeerId) -> Result<Project, Erro
     let theirs = Verifying::from(theirs).signed()?;
     let next = identities(storage).update_from(ours, theirs, storage.signer())?;
 
-    ProjectRefs::Update(&next, &format!("merge from {}", from)).apply(storage)?;
+    ProjectRefs::Update(&next, Message::new(Op::Identity, "merge").with_peer(from))
+        .apply(storage)?;
+    common::audit_signed(storage, urn, next.revision);
     Sigrefs::update(storage, urn)?;
 
     Ok(next)
@@ -214,22 +233,43 @@ where
 
 enum ProjectRefs<'a> {
     Create(&'a Project),
-    Update(&'a Project, &'a str),
+    Update(&'a Project, Message),
 }
 
 impl<'a> ProjectRefs<'a> {
+    /// Write the `rad/id` ref and the delegate symrefs in a single
+    /// transaction.
     pub fn apply(&self, storage: &Storage) -> Result<(), Error> {
-        for symref in self.delegates() {
-            symref.create(storage.as_raw())?;
-        }
-        match self {
-            Self::Create(project) => {
-                common::IdRef::from(&project.urn()).create(storage, project.content_id)
+        let project = self.project();
+        let urn = project.urn();
+        let id_ref = common::IdRef::from(&urn);
+        let tx = match self {
+            Self::Create(_) => {
+                let tx = storage.transaction(&urn, Message::new(Op::Identity, "create"));
+                if storage.has_urn(&urn)? {
+                    tx
+                } else {
+                    id_ref.create_in(tx, project.content_id)
+                }
             },
-            Self::Update(project, msg) => {
-                common::IdRef::from(&project.urn()).update(storage, project.content_id, msg)
+            Self::Update(_, msg) => {
+                id_ref.update_in(storage.transaction(&urn, msg.clone()), project.content_id)
             },
-        }?;
+        };
+        self.delegates()
+            .fold(tx, |tx, symref| {
+                // Relative to the namespace of the transaction
+                let source = Reference {
+                    namespace: None,
+                    ..symref.source
+                };
+                tx.symbolic(
+                    ext::RefLike::from(&source),
+                    ext::RefLike::from(&symref.target),
+                    Previous::Any,
+                )
+            })
+            .commit()?;
 
         Ok(())
     }
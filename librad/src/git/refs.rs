@@ -26,7 +26,7 @@ use std_ext::result::ResultExt as _;
 use thiserror::Error;
 
 use super::{
-    storage::{self, ReadOnlyStorage, Storage},
+    storage::{self, audit, ReadOnlyStorage, Storage},
     tracking,
     types::{Namespace, Reference, RefsCategory},
 };
@@ -202,6 +202,12 @@ pub mod stored {
 
         #[error(transparent)]
         Tracked(#[from] tracking::error::TrackedPeers),
+
+        #[error(transparent)]
+        Lease(#[from] storage::lease::Error),
+
+        #[error(transparent)]
+        NamespaceLock(#[from] storage::lock::Error),
     }
 }
 
@@ -339,6 +345,7 @@ impl Refs {
         let branch = Reference::rad_signed_refs(Namespace::from(urn), None);
         tracing::debug!("updating signed refs for {}", branch);
 
+        let lock = storage.lock_namespace(urn)?;
         let signed_refs = Self::compute(storage, urn)?.sign(storage.signer())?;
 
         let raw_git = storage.as_raw();
@@ -370,36 +377,50 @@ impl Refs {
         }
 
         let author = raw_git.signature()?;
-        let commit = raw_git.commit(
-            Some(reference::RefLike::from(&branch).as_str()),
+        let message = format!("Update rad/signed_refs for {}", urn);
+        let commit_id = raw_git.commit(
+            None,
             &author,
             &author,
-            &format!("Update rad/signed_refs for {}", urn),
+            &message,
             &tree,
             &parent.iter().collect::<Vec<&git2::Commit>>(),
-        );
-        match commit {
-            Ok(commit_id) => {
-                tracing::trace!(
-                    ?signed_refs.refs,
-                    %branch,
-                    head = %commit_id,
-                    parent = ?parent.as_ref().map(|commit| commit.id()),
-                    "updated signed refs for {}", urn
-                );
-
-                Ok(Updated::Updated {
-                    refs: signed_refs.refs,
-                    at: commit_id,
-                })
-            },
-            Err(e) => match (e.class(), e.code()) {
-                (git2::ErrorClass::Object, git2::ErrorCode::Modified) => {
-                    Ok(Updated::ConcurrentlyModified)
-                },
-                _ => Err(e.into()),
-            },
+        )?;
+
+        // Move the branch in a transaction, so the lease is checked while the
+        // ref is locked
+        let refname = reference::RefLike::from(&branch);
+        let mut txn = raw_git.transaction()?;
+        txn.lock_ref(refname.as_str())?;
+        let head = match raw_git.refname_to_id(refname.as_str()) {
+            Ok(oid) => Some(oid),
+            Err(e) if is_not_found_err(&e) => None,
+            Err(e) => return Err(e.into()),
+        };
+        if head != parent.as_ref().map(|commit| commit.id()) {
+            return Ok(Updated::ConcurrentlyModified);
         }
+        lock.check()?;
+        txn.set_target(refname.as_str(), commit_id, None, &message)?;
+        txn.commit()?;
+
+        tracing::trace!(
+            ?signed_refs.refs,
+            %branch,
+            head = %commit_id,
+            parent = ?parent.as_ref().map(|commit| commit.id()),
+            "updated signed refs for {}", urn
+        );
+        storage.audit(audit::Event::RefsSigned {
+            urn: urn.clone(),
+            at: commit_id.into(),
+        });
+        storage.reindex(urn);
+
+        Ok(Updated::Updated {
+            refs: signed_refs.refs,
+            at: commit_id,
+        })
     }
 
     pub fn sign<S>(self, signer: &S) -> Result<Signed<Verified>, signing::Error>
@@ -571,6 +592,35 @@ impl Signed<Verified> {
             Err(signed::Error::InvalidSignature(unknown.refs))
         }
     }
+
+    /// Verify several [`Signed`]s at once, using batch verification.
+    ///
+    /// The verified values are returned in the same order. If any signature
+    /// is invalid, the error carries the [`Refs`] of the first invalid one.
+    pub fn verify_batch<I>(unknown: I) -> Result<Vec<Self>, signed::Error>
+    where
+        I: IntoIterator<Item = (Signed<Unverified>, PeerId)>,
+    {
+        let mut unknown = unknown.into_iter().collect::<Vec<_>>();
+        let mut batch = crate::crypto::Batch::with_capacity(unknown.len());
+        for (i, (signed, signer)) in unknown.iter().enumerate() {
+            let canonical = signed.refs.canonical_form()?;
+            batch.queue(i, signer.as_public_key(), &signed.signature, &canonical);
+        }
+        if let Err(invalid) = batch.verify() {
+            let (first, _) = unknown.swap_remove(invalid[0]);
+            return Err(signed::Error::InvalidSignature(first.refs));
+        }
+
+        Ok(unknown
+            .into_iter()
+            .map(|(signed, _)| Signed {
+                refs: signed.refs,
+                signature: signed.signature,
+                _verified: PhantomData,
+            })
+            .collect())
+    }
 }
 
 impl<V> Deref for Signed<V> {
@@ -688,6 +738,51 @@ where
     }
 }
 
+/// Load the signed refs of several `peers`, either from their
+/// `rad/signed_refs` or the given commit, verifying the signatures in batch.
+///
+/// Peers for which no signed refs are found are absent from the result.
+pub(crate) fn load_many<S, I>(
+    storage: S,
+    urn: &Urn,
+    peers: I,
+) -> Result<BTreeMap<PeerId, Loaded>, stored::Error>
+where
+    S: AsRef<storage::ReadOnly>,
+    I: IntoIterator<Item = (PeerId, Option<git_ext::Oid>)>,
+{
+    let storage = storage.as_ref();
+    let mut found = Vec::new();
+    for (peer, at) in peers {
+        let at = match at {
+            Some(at) => at,
+            None => {
+                let sigrefs = Reference::rad_signed_refs(Namespace::from(urn), Some(peer));
+                match storage.reference_oid(&sigrefs).map(Some).or_matches(
+                    |e| matches!(e, storage::read::Error::Git(e) if is_not_found_err(e)),
+                    || Ok::<_, storage::read::Error>(None),
+                )? {
+                    Some(at) => at,
+                    None => continue,
+                }
+            },
+        };
+        if let Some(blob) = storage.blob_at(at, Path::new(stored::BLOB_PATH))? {
+            let unknown = serde_json::from_slice::<Signed<Unverified>>(blob.content())
+                .map_err(signed::Error::from)?;
+            found.push(((peer, at), (unknown, peer)));
+        }
+    }
+
+    let (keys, unknown): (Vec<_>, Vec<_>) = found.into_iter().unzip();
+    let verified = Signed::verify_batch(unknown)?;
+    Ok(keys
+        .into_iter()
+        .zip(verified)
+        .map(|((peer, at), refs)| (peer, Loaded { at, refs }))
+        .collect())
+}
+
 pub(crate) fn load_at<S>(
     storage: S,
     at: git_ext::Oid,
@@ -6,7 +6,9 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     convert::{TryFrom, TryInto},
+    fmt,
     iter,
+    sync::Arc,
 };
 
 use either::Either;
@@ -16,9 +18,14 @@ use thiserror::Error;
 
 use super::{
     fetch,
-    identities::{self, local::LocalIdentity},
+    identities::{self, diff::Diff, local::LocalIdentity},
     refs::{self, Refs},
-    storage::{self, ReadOnlyStorage, Storage},
+    storage::{
+        self,
+        reflog::{Message, Op},
+        ReadOnlyStorage,
+        Storage,
+    },
     tracking,
     types::{reference, Force, Namespace, One, Reference},
 };
@@ -74,8 +81,26 @@ pub enum Error {
     #[error(transparent)]
     Store(#[from] storage::Error),
 
+    #[error(transparent)]
+    Transaction(#[from] storage::txn::error::Commit),
+
     #[error(transparent)]
     Tracking(#[from] Tracking),
+
+    #[error(transparent)]
+    Incompatible(#[from] storage::requirements::error::Incompatible),
+
+    #[error(transparent)]
+    Lease(#[from] storage::lease::Error),
+
+    #[error(transparent)]
+    NamespaceLock(#[from] storage::lock::Error),
+
+    #[error(transparent)]
+    Config(#[from] storage::config::Error),
+
+    #[error(transparent)]
+    Subkeys(#[from] tracking::subkeys::error::Track),
 }
 
 #[derive(Debug, Error)]
@@ -86,6 +111,8 @@ pub enum Tracking {
     Untrack(#[from] tracking::error::Untrack),
     #[error(transparent)]
     Tracked(#[from] tracking::error::TrackedPeers),
+    #[error(transparent)]
+    IsTracked(#[from] tracking::error::IsTracked),
 }
 
 impl From<tracking::error::Track> for Error {
@@ -106,15 +133,63 @@ impl From<tracking::error::TrackedPeers> for Error {
     }
 }
 
+impl From<tracking::error::IsTracked> for Error {
+    fn from(err: tracking::error::IsTracked) -> Self {
+        Self::Tracking(err.into())
+    }
+}
+
 impl From<identities::error::Error> for Error {
     fn from(e: identities::error::Error) -> Self {
         Self::Identities(Box::new(e))
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Config {
     pub fetch_limit: fetch::Limit,
+    /// Confirm identity updates before adopting them.
+    ///
+    /// If set, the top-level `rad/id` of an identity we are not a delegate of
+    /// is moved to the latest revision of the delegates. Updates which are
+    /// not fast-forwards or change the delegations are only adopted if the
+    /// [`Review`] confirms them. Otherwise, they are left for the user to
+    /// merge, and the identity is reported as [`IdStatus::Uneven`].
+    ///
+    /// If not set, `rad/id` is only ever created, never updated by
+    /// replication.
+    pub review: Option<Review>,
+}
+
+/// A confirmation callback for identity updates, eg. prompting the user in an
+/// interactive context.
+///
+/// Given the [`Diff`] between our view of an identity and the update, returns
+/// whether the update should be adopted.
+#[derive(Clone)]
+pub struct Review(Arc<dyn Fn(&Diff) -> bool + Send + Sync>);
+
+impl Review {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&Diff) -> bool + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    /// Whether the update described by `diff` should be adopted.
+    ///
+    /// Updates which are [`Diff::is_trivial`] are confirmed without
+    /// consulting the callback.
+    pub fn confirm(&self, diff: &Diff) -> bool {
+        diff.is_trivial() || (self.0)(diff)
+    }
+}
+
+impl fmt::Debug for Review {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Review(..)")
+    }
 }
 
 /// The success outcome of [`self::replicate`].
@@ -156,6 +231,38 @@ pub enum Mode {
     Fetch,
 }
 
+/// The outcome of [`self::dry_run`].
+#[derive(Debug)]
+pub struct DryRun {
+    /// Whether the replication would be a clone or a fetch.
+    pub mode: Mode,
+
+    /// The refs which would be updated, by their local name, along with the
+    /// [`ext::Oid`] they currently point to, if any, and the one advertised
+    /// by the remote peer.
+    pub updates: BTreeMap<ext::RefLike, RefUpdate>,
+
+    /// The number of advertised refs which are already up-to-date.
+    pub up_to_date: usize,
+
+    /// The advertised tips which are not in the local object database.
+    ///
+    /// Each of them requires at least one object to be fetched, so the
+    /// size of this set is a lower bound of the number of objects
+    /// transferred.
+    pub wants: BTreeSet<ext::Oid>,
+
+    /// The local tips of the refs in [`DryRun::updates`], which would be
+    /// offered as common history during negotiation.
+    pub haves: BTreeSet<ext::Oid>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RefUpdate {
+    pub old: Option<ext::Oid>,
+    pub new: ext::Oid,
+}
+
 enum ModeInternal {
     Clone {
         urn: Urn,
@@ -184,8 +291,8 @@ enum ModeInternal {
 ///    information.
 ///
 /// 2. Fetch the `rad/signed_refs` of all tracked peers, and compute the
-///    eligible heads (i.e. where the `remote_peer` advertises the same tip
-///    oid as found in the signed refs)
+///    eligible heads (i.e. where the `remote_peer` advertises the same tip oid
+///    as found in the signed refs)
 ///
 /// 3. Fetch the rest (i.e. eligible heads)
 ///
@@ -216,6 +323,10 @@ where
         return Err(Error::SelfReplication);
     }
     let urn = Urn::new(fetcher.urn().id);
+    storage::requirements::ensure_compatible(storage.path(), &urn)?;
+    // Replications of the same URN must not interleave with each other, nor
+    // with local updates of the namespace
+    let lock = storage.lock_namespace(&urn)?;
     let (mut updated_tips, next) = determine_mode(
         storage,
         &mut fetcher,
@@ -231,7 +342,12 @@ where
         } => {
             let (allowed, id_status) = match identity {
                 SomeIdentity::Project(proj) => {
-                    let delegates = project::delegate_views(storage, proj, Some(remote_peer))?;
+                    let delegates = project::delegate_views(
+                        storage,
+                        proj,
+                        Some(remote_peer),
+                        config.review.as_ref(),
+                    )?;
                     let mut allowed = delegates.keys().copied().collect::<BTreeSet<_>>();
                     let rad_id = unsafe_into_urn(
                         Reference::rad_id(Namespace::from(&urn)).with_remote(remote_peer),
@@ -244,6 +360,7 @@ where
                         storage,
                         &mut fetcher,
                         config.fetch_limit,
+                        config.review.as_ref(),
                         delegates,
                         &rad_id,
                         proj,
@@ -260,7 +377,12 @@ where
                     let rad_id = unsafe_into_urn(
                         Reference::rad_id(Namespace::from(&person.urn())).with_remote(remote_peer),
                     );
-                    let id_status = person::ensure_setup(storage, &rad_id, person.clone())?;
+                    let id_status = person::ensure_setup(
+                        storage,
+                        config.review.as_ref(),
+                        &rad_id,
+                        person.clone(),
+                    )?;
                     let allowed = person
                         .delegations()
                         .iter()
@@ -295,7 +417,8 @@ where
         } => {
             let (result, updated) = match identity {
                 SomeIdentity::Project(proj) => {
-                    let delegate_views = project::delegate_views(storage, proj, None)?;
+                    let delegate_views =
+                        project::delegate_views(storage, proj, None, config.review.as_ref())?;
                     let proj = project::verify_with_delegate(storage, &urn, None)?;
                     let mut updated_delegations = project::all_delegates(&proj);
                     let rad_id = unsafe_into_urn(Reference::rad_id(Namespace::from(&urn)));
@@ -306,6 +429,7 @@ where
                         storage,
                         &mut fetcher,
                         config.fetch_limit,
+                        config.review.as_ref(),
                         delegate_views,
                         &rad_id,
                         proj,
@@ -326,7 +450,8 @@ where
                 },
                 SomeIdentity::Person(person) => {
                     let rad_id = unsafe_into_urn(Reference::rad_id(Namespace::from(&person.urn())));
-                    let id_status = person::ensure_setup(storage, &rad_id, person)?;
+                    let id_status =
+                        person::ensure_setup(storage, config.review.as_ref(), &rad_id, person)?;
                     (
                         ReplicateResult {
                             updated_tips,
@@ -346,12 +471,23 @@ where
         },
     }?;
 
+    // Fetching may take a while, bail if the namespace was taken over in the
+    // meantime
+    lock.check()?;
+
     // Ensure we're not tracking ourselves
     remove.insert(*local_peer_id);
 
     // Remove any remote tracking branches we don't need
     prune(storage, &urn, remove.iter())?;
 
+    if let Err(e) = storage.record_history(&urn) {
+        tracing::warn!(err = %e, "failed to record namespace history");
+    }
+    storage
+        .collaborative_objects(None)
+        .audit_received(&urn, result.updated_tips.keys().map(|name| name.as_str()));
+
     // TODO: At this point, the tracking graph may have changed, and/or we
     // created top-level person namespaces. We will eventually converge, but
     // perhaps we'd want to return some kind of continuation here, so the caller
@@ -359,6 +495,91 @@ where
     Ok(result)
 }
 
+/// Determine what [`self::replicate`] would fetch from the remote peer of
+/// `fetcher`, without fetching anything.
+///
+/// The refs advertised by the remote peer for the namespace of the
+/// [`fetch::Fetcher::urn`] are compared against the local remote tracking
+/// branches they would be fetched into. When the [`Urn`] is already present
+/// locally, only the refs of the delegates and tracked peers are considered.
+/// Otherwise, the delegates are not known until the identity is fetched, so
+/// the refs of all advertised peers are.
+///
+/// Note that [`self::replicate`] only fetches refs matching the signed refs
+/// of their owner, so the [`DryRun`] is an upper bound of what is actually
+/// fetched.
+#[tracing::instrument(skip(storage, fetcher))]
+pub fn dry_run<F>(storage: &Storage, fetcher: &F) -> Result<DryRun, Error>
+where
+    F: fetch::Fetcher<PeerId = PeerId, UrnId = Revision>,
+{
+    let remote_peer = *fetcher.remote_peer();
+    let local_peer_id = storage.peer_id();
+    if local_peer_id == &remote_peer {
+        return Err(Error::SelfReplication);
+    }
+    let urn = Urn::new(fetcher.urn().id);
+
+    let (mode, eligible) = if storage.has_urn(&urn)? {
+        let mut peers =
+            tracking::tracked_peers(storage, Some(&urn))?.collect::<Result<BTreeSet<_>, _>>()?;
+        if let Some(SomeIdentity::Project(proj)) = identities::any::get(storage, &urn)? {
+            peers.append(&mut project::all_delegates(&proj));
+        }
+        peers.insert(remote_peer);
+        (Mode::Fetch, Some(peers))
+    } else {
+        (Mode::Clone, None)
+    };
+
+    let namespace = format!("refs/namespaces/{}/refs/", urn.encode_id());
+    let mut dry_run = DryRun {
+        mode,
+        updates: BTreeMap::new(),
+        up_to_date: 0,
+        wants: BTreeSet::new(),
+        haves: BTreeSet::new(),
+    };
+    for (name, new) in fetcher.remote_heads().iter() {
+        let name = match name.as_str().strip_prefix(&namespace) {
+            None => continue,
+            Some(name) => name,
+        };
+        let (peer, suffix) = match name.strip_prefix("remotes/") {
+            None => (remote_peer, name),
+            Some(remote) => match remote.split_once('/') {
+                Some((peer, suffix)) => match peer.parse() {
+                    Ok(peer) => (peer, suffix),
+                    Err(_) => continue,
+                },
+                None => continue,
+            },
+        };
+        if &peer == local_peer_id || eligible.as_ref().map_or(false, |e| !e.contains(&peer)) {
+            continue;
+        }
+
+        let local = format!("{}remotes/{}/{}", namespace, peer, suffix);
+        let old = match storage.as_raw().refname_to_id(&local) {
+            Ok(oid) => Some(ext::Oid::from(oid)),
+            Err(e) if ext::is_not_found_err(&e) => None,
+            Err(e) => return Err(Error::Store(e.into())),
+        };
+        if old == Some(*new) {
+            dry_run.up_to_date += 1;
+            continue;
+        }
+        if !storage.has_object(*new)? {
+            dry_run.wants.insert(*new);
+        }
+        dry_run.haves.extend(old);
+        let local = ext::RefLike::try_from(local).expect("valid remote names are valid refs");
+        dry_run.updates.insert(local, RefUpdate { old, new: *new });
+    }
+
+    Ok(dry_run)
+}
+
 /// Identify the type of replication case we're in -- whether it's a new
 /// identity which we're cloning onto our machine or an existing identity that
 /// we are updating.
@@ -460,13 +681,32 @@ fn unsafe_into_urn(reference: Reference<git_ext::RefLike>) -> Urn {
 #[tracing::instrument(level = "trace", skip(storage, urn), fields(urn = %urn))]
 fn ensure_rad_id(storage: &Storage, urn: &Urn, tip: ext::Oid) -> Result<ext::Oid, Error> {
     let id_ref = identities::common::IdRef::from(urn);
-    id_ref
-        .create(storage, tip)
-        .map_err(|e| Error::Store(e.into()))?;
+    id_ref.create(storage, tip)?;
 
     id_ref.oid(storage).map(Into::into).map_err(Error::Store)
 }
 
+/// Update the top-level `rad/id` to the new revision of `diff`, if `review`
+/// confirms it.
+fn adopt_reviewed(
+    storage: &Storage,
+    urn: &Urn,
+    review: &Review,
+    diff: &Diff,
+) -> Result<IdStatus, Error> {
+    if review.confirm(diff) {
+        identities::common::IdRef::from(urn).update(
+            storage,
+            diff.new,
+            Message::new(Op::Identity, "review"),
+        )?;
+        Ok(IdStatus::Even)
+    } else {
+        tracing::info!(urn = %urn, old = %diff.old, new = %diff.new, "identity update not confirmed");
+        Ok(IdStatus::Uneven)
+    }
+}
+
 fn adopt_rad_self(storage: &Storage, urn: &Urn, peer: PeerId) -> Result<(), Error> {
     let rad_self = Reference::rad_self(Namespace::from(urn), peer);
 
@@ -592,6 +832,7 @@ mod person {
     #[tracing::instrument(level = "trace", skip(storage))]
     pub fn ensure_setup(
         storage: &Storage,
+        review: Option<&Review>,
         rad_id: &Urn,
         person: Person,
     ) -> Result<IdStatus, Error> {
@@ -621,7 +862,7 @@ mod person {
             },
         }?;
         // Create `rad/id` here, if not exists
-        adopt_latest(storage, &person.urn(), delegations)
+        adopt_latest(storage, review, &person.urn(), delegations)
     }
 
     /// Adopt the `rad/id` that has the most up-to-date commit from the set of
@@ -630,6 +871,7 @@ mod person {
     #[tracing::instrument(level = "trace", skip(storage))]
     pub fn adopt_latest(
         storage: &Storage,
+        review: Option<&Review>,
         urn: &Urn,
         delegates: BTreeSet<PeerId>,
     ) -> Result<IdStatus, Error> {
@@ -667,9 +909,17 @@ mod person {
         };
         let actual = ensure_rad_id(storage, urn, expected)?;
         if actual == expected {
-            Ok(Even)
-        } else {
-            Ok(Uneven)
+            return Ok(Even);
+        }
+
+        match review {
+            Some(review) if !delegates.contains_key(local_peer) => {
+                let ours = identities::person::get(storage, urn)?
+                    .ok_or_else(|| Error::MissingIdentities(urn.clone()))?;
+                let diff = identities::diff::person(storage, &ours, &latest)?;
+                adopt_reviewed(storage, urn, review, &diff)
+            },
+            _ => Ok(Uneven),
         }
     }
 }
@@ -705,6 +955,7 @@ mod project {
         storage: &Storage,
         fetcher: &mut F,
         limit: fetch::Limit,
+        review: Option<&Review>,
         delegates: BTreeMap<PeerId, project::DelegateView>,
         rad_id: &Urn,
         proj: VerifiedProject,
@@ -715,9 +966,15 @@ mod project {
     {
         let local_peer = storage.peer_id();
         let urn = proj.urn();
-        let id_status = self::adopt_latest(storage, &urn, &delegates)?;
+        let id_status = self::adopt_latest(storage, review, &urn, &delegates)?;
 
         self::track_direct(storage, &proj)?;
+        let revoked = tracking::subkeys::track(
+            storage,
+            &urn,
+            delegates.values().map(|view| &view.delegate),
+            &delegates.keys().copied().collect(),
+        )?;
         let (fetch_result, tracked) = replicate_signed_refs(
             storage,
             fetcher,
@@ -727,6 +984,7 @@ mod project {
                 .values()
                 .map(|delegate| delegate.urn.clone())
                 .collect(),
+            &revoked,
         )?;
         for peer in tracked {
             if peer != *local_peer {
@@ -743,6 +1001,9 @@ mod project {
 
     /// Fetch `rad/signed_refs` and `refs/heads` of the delegates and our
     /// tracked graph, returning the set of tracked peers.
+    ///
+    /// The `revoked` sub-keys are neither fetched nor returned, even if they
+    /// are part of the tracking graph of a delegate.
     #[tracing::instrument(
         level = "trace",
         skip(storage, fetcher, urn),
@@ -755,6 +1016,7 @@ mod project {
         limit: fetch::Limit,
         urn: &Urn,
         delegates: BTreeSet<Urn>,
+        revoked: &BTreeSet<PeerId>,
     ) -> Result<(fetch::FetchResult, BTreeSet<PeerId>), Error>
     where
         F: fetch::Fetcher<PeerId = PeerId, UrnId = Revision>,
@@ -764,6 +1026,7 @@ mod project {
         let tracked = tracking::tracked_peers(storage, Some(urn))?;
         let tracked_sigrefs = tracked
             .filter_map(|peer| match peer {
+                Ok(peer) if revoked.contains(&peer) => None,
                 Ok(peer) => match Refs::load(storage, urn, peer) {
                     Ok(Some(refs)) => Some(Ok((peer, refs))),
                     Ok(None) => None,
@@ -789,6 +1052,7 @@ mod project {
             tracked_sigrefs
                 .iter()
                 .flat_map(|(peer, refs)| iter::once(*peer).chain(refs.remotes.flatten().copied()))
+                .filter(|peer| !revoked.contains(peer))
                 .collect(),
         ))
     }
@@ -801,6 +1065,7 @@ mod project {
         storage: &Storage,
         proj: Project,
         remote_peer: Option<PeerId>,
+        review: Option<&Review>,
     ) -> Result<BTreeMap<PeerId, DelegateView>, Error> {
         let mut delegate_views = BTreeMap::new();
         let local_peer_id = storage.peer_id();
@@ -825,7 +1090,7 @@ mod project {
                                 Reference::rad_id(Namespace::from(&proj.urn()))
                                     .with_remote(peer_id),
                             );
-                            adopt_delegate_person(storage, peer_id, &person, &proj.urn())?;
+                            adopt_delegate_person(storage, review, peer_id, &person, &proj.urn())?;
                             let verified =
                                 project::verify_with_delegate(storage, &remote_urn, remote_peer)?;
                             (remote_urn, verified)
@@ -847,10 +1112,14 @@ mod project {
     }
 
     /// Persist a delegate identity in our storage.
+    ///
+    /// If a [`Review`] is given, fast-forwards of a known identity which
+    /// change its delegations are only adopted if confirmed.
     #[allow(clippy::unit_arg)]
     #[tracing::instrument(level = "trace", skip(storage))]
     pub fn adopt_delegate_person(
         storage: &Storage,
+        review: Option<&Review>,
         peer: PeerId,
         person: &VerifiedPerson,
         project_urn: &Urn,
@@ -859,7 +1128,20 @@ mod project {
 
         // if the identity is known we see if we can fast-forward it
         if storage.has_urn(&delegate_urn)? {
-            identities::person::fast_forward(storage, person)?;
+            let confirmed = match review {
+                None => true,
+                Some(review) => {
+                    let ours = identities::person::get(storage, &delegate_urn)?
+                        .ok_or_else(|| Error::MissingIdentities(delegate_urn.clone()))?;
+                    let diff = identities::diff::person(storage, &ours, person)?;
+                    diff.is_empty() || !diff.fast_forward || review.confirm(&diff)
+                },
+            };
+            if confirmed {
+                identities::person::fast_forward(storage, person)?;
+            } else {
+                tracing::info!(urn = %delegate_urn, "delegate identity update not confirmed");
+            }
         } else {
             ensure_rad_id(storage, &delegate_urn, person.content_id)?;
             track(storage, &delegate_urn, peer)?;
@@ -912,6 +1194,7 @@ mod project {
     #[tracing::instrument(level = "trace", skip(storage))]
     pub fn adopt_latest(
         storage: &Storage,
+        review: Option<&Review>,
         urn: &Urn,
         delegates: &BTreeMap<PeerId, DelegateView>,
     ) -> Result<IdStatus, Error> {
@@ -938,9 +1221,17 @@ mod project {
         };
         let actual = ensure_rad_id(storage, urn, expected)?;
         if actual == expected {
-            Ok(Even)
-        } else {
-            Ok(Uneven)
+            return Ok(Even);
+        }
+
+        match review {
+            Some(review) if !delegates.contains_key(local_peer) => {
+                let ours = identities::project::get(storage, urn)?
+                    .ok_or_else(|| Error::MissingIdentities(urn.clone()))?;
+                let diff = identities::diff::project(storage, &ours, &latest)?;
+                adopt_reviewed(storage, urn, review, &diff)
+            },
+            _ => Ok(Uneven),
         }
     }
 
@@ -0,0 +1,425 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Commit signature policies.
+//!
+//! The history replicated from a peer is authenticated by its signed refs,
+//! which says nothing about who wrote the commits. A project may declare a
+//! [`Policy`] as a payload extension, requiring that all commits on some of
+//! its branches are signed by one of a set of keys. When enabled in the
+//! replication config, the branches of the peer replicated from are checked
+//! after each replication, and violations are reported as
+//! [`crate::net::protocol::event::upstream::CommitSignatures`] events. The
+//! history is still accepted: it is up to the application to decide what to
+//! do about it.
+//!
+//! Only SSH signatures (as made by `git commit -S` with `gpg.format=ssh`) by
+//! Ed25519 keys can be verified, which covers the keys of radicle peers.
+//! Commits signed using OpenPGP are reported as [`Verdict::Unsupported`].
+//!
+//! To avoid verifying the whole history on every replication, the tip of each
+//! branch found without violations is recorded as JSON under `signatures/`,
+//! relative to the storage directory, one file per namespace (see
+//! [`crate::git::storage::json`]). Subsequent checks only visit the commits
+//! not reachable from it. The recorded tips are discarded when the policy
+//! changes.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom as _,
+};
+
+use git_ext as ext;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256, Sha512};
+use url::Url;
+
+use super::{
+    identities::project,
+    storage::{json, Storage},
+};
+use crate::{
+    identities::{
+        git::Urn,
+        payload::{Extension, HasNamespace, Kind},
+    },
+    keystore::sign,
+    PeerId,
+    PublicKey,
+    Signature,
+};
+
+lazy_static! {
+    static ref POLICY_NAMESPACE_V1: Url =
+        Url::parse("https://radicle.xyz/link/project/commit-signatures/v1").unwrap();
+}
+
+/// The namespace SSH signatures of git objects are made in.
+const SSHSIG_NAMESPACE: &str = "git";
+const SSHSIG_MAGIC: &[u8] = b"SSHSIG";
+const SSHSIG_ARMOR_BEGIN: &str = "-----BEGIN SSH SIGNATURE-----";
+const SSHSIG_ARMOR_END: &str = "-----END SSH SIGNATURE-----";
+const PGP_ARMOR_BEGIN: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// The name of the directory the verified tips are recorded in, relative to
+/// the storage directory.
+pub const DIR_NAME: &str = "signatures";
+
+const STORE: json::Store = json::Store::new(DIR_NAME, "verified tips");
+
+pub mod error {
+    use thiserror::Error;
+
+    use crate::git::{
+        identities,
+        storage::{json, read},
+    };
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Check {
+        #[error("malformed commit signature policy")]
+        Policy(#[source] serde_json::Error),
+
+        #[error(transparent)]
+        Identities(#[from] identities::Error),
+
+        #[error(transparent)]
+        Storage(#[from] read::Error),
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Load(#[from] json::error::Load),
+
+        #[error(transparent)]
+        Store(#[from] json::error::Store),
+    }
+}
+
+/// Payload extension declaring the commit signing policy of a project.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    /// The branches the policy applies to, eg. `main` for `refs/heads/main`.
+    /// If empty, it applies to the default branch of the project.
+    #[serde(default)]
+    pub branches: BTreeSet<String>,
+    /// The keys commits must be signed by.
+    pub keys: BTreeSet<PublicKey>,
+    /// Commits reachable from this commit are exempt, eg. because they were
+    /// made before the policy was introduced.
+    #[serde(default)]
+    pub since: Option<ext::Oid>,
+}
+
+impl HasNamespace for Policy {
+    fn namespace() -> &'static Url {
+        &POLICY_NAMESPACE_V1
+    }
+}
+
+impl Extension for Policy {
+    fn applies_to(kind: Kind) -> bool {
+        kind == Kind::Project
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        if self.keys.is_empty() {
+            return Err("no keys are allowed to sign commits".into());
+        }
+        Ok(())
+    }
+}
+
+impl Policy {
+    /// Whether the policy applies to `branch`, given the `default_branch` of
+    /// the project.
+    pub fn applies(&self, branch: &str, default_branch: Option<&str>) -> bool {
+        if self.branches.is_empty() {
+            default_branch == Some(branch)
+        } else {
+            self.branches.contains(branch)
+        }
+    }
+
+    /// Check the commits reachable from `tip`, but not from
+    /// [`Policy::since`], returning the ones violating the policy.
+    ///
+    /// Commits reachable from `verified`, a tip previously checked without
+    /// violations, are skipped.
+    pub fn check(
+        &self,
+        repo: &git2::Repository,
+        tip: git2::Oid,
+        verified: Option<git2::Oid>,
+    ) -> Result<Vec<Violation>, git2::Error> {
+        let mut walk = repo.revwalk()?;
+        walk.push(tip)?;
+        if let Some(since) = self.since {
+            match walk.hide(*since) {
+                Err(e) if ext::is_not_found_err(&e) => {
+                    tracing::warn!(since = %since, "commit signature policy base not found");
+                },
+                res => res?,
+            }
+        }
+        if let Some(verified) = verified {
+            match walk.hide(verified) {
+                // Pruned since, eg. after a force-push
+                Err(e) if ext::is_not_found_err(&e) => {},
+                res => res?,
+            }
+        }
+
+        let mut violations = Vec::new();
+        for oid in walk {
+            let oid = oid?;
+            let verdict = verify(repo, oid)?;
+            let ok = matches!(verdict, Verdict::Signed(ref key) if self.keys.contains(key));
+            if !ok {
+                violations.push(Violation {
+                    commit: oid.into(),
+                    verdict,
+                });
+            }
+        }
+        Ok(violations)
+    }
+}
+
+/// The result of verifying the signature of a commit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The commit carries a valid signature by the given key.
+    Signed(PublicKey),
+    /// The commit is not signed.
+    Unsigned,
+    /// The signature does not verify.
+    Invalid,
+    /// The signature could not be parsed.
+    Malformed,
+    /// The signature is of a kind we can't verify, eg. OpenPGP.
+    Unsupported,
+}
+
+/// A commit violating a [`Policy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub commit: ext::Oid,
+    /// Why the commit violates the policy. A [`Verdict::Signed`] means the
+    /// commit was signed by a key not allowed by the policy.
+    pub verdict: Verdict,
+}
+
+/// The violations found on a branch, see [`check`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BranchViolations {
+    pub branch: String,
+    pub tip: ext::Oid,
+    pub violations: Vec<Violation>,
+}
+
+/// The tips checked without violations, as of the last [`check`] of a
+/// namespace.
+#[derive(Default, Serialize, Deserialize)]
+struct Verified {
+    /// The policy the tips were checked against.
+    policy: Policy,
+    /// The verified tips, by `<peer>/<branch>`.
+    tips: BTreeMap<String, ext::Oid>,
+}
+
+/// Verify the signature of the commit `oid`.
+pub fn verify(repo: &git2::Repository, oid: git2::Oid) -> Result<Verdict, git2::Error> {
+    let (signature, data) = match repo.extract_signature(&oid, None) {
+        Ok(sig) => sig,
+        Err(e) if ext::is_not_found_err(&e) => return Ok(Verdict::Unsigned),
+        Err(e) => return Err(e),
+    };
+    let signature = match signature.as_str() {
+        Some(sig) => sig.trim(),
+        None => return Ok(Verdict::Malformed),
+    };
+    if signature.starts_with(PGP_ARMOR_BEGIN) {
+        return Ok(Verdict::Unsupported);
+    }
+    Ok(sshsig::verify(signature, &data))
+}
+
+/// Check the branches of `peer` in the project `urn` against the [`Policy`]
+/// of the project.
+///
+/// Returns only the branches with violations, and nothing if the project
+/// declares no policy. Only the commits not reachable from the tip verified by
+/// the previous check of a branch are visited.
+pub fn check(
+    storage: &Storage,
+    urn: &Urn,
+    peer: PeerId,
+) -> Result<Vec<BranchViolations>, error::Check> {
+    let project = match project::get(storage, urn)? {
+        None => return Ok(vec![]),
+        Some(project) => project,
+    };
+    let policy = match project
+        .payload()
+        .get_ext::<Policy>()
+        .map_err(error::Check::Policy)?
+    {
+        None => return Ok(vec![]),
+        Some(policy) => policy,
+    };
+    let default_branch = project
+        .payload()
+        .subject
+        .default_branch
+        .as_ref()
+        .map(|b| b.as_str());
+
+    let namespace = urn.clone().with_path(None);
+    let mut verified = match STORE.load::<Verified>(storage.path(), &namespace)? {
+        Some(verified) if verified.policy == policy => verified,
+        _ => Verified {
+            policy: policy.clone(),
+            tips: BTreeMap::new(),
+        },
+    };
+
+    let prefix = format!("refs/remotes/{}/heads/", peer);
+    let mut found = Vec::new();
+    let mut updated = false;
+    for (name, tip) in &storage.read_only().snapshot(urn)? {
+        let branch = match name.as_str().strip_prefix(&prefix) {
+            Some(branch) if policy.applies(branch, default_branch) => branch,
+            _ => continue,
+        };
+        let key = format!("{}/{}", peer, branch);
+        let previous = verified.tips.get(&key).map(|oid| **oid);
+        if previous == Some(**tip) {
+            continue;
+        }
+        let violations = policy.check(storage.as_raw(), **tip, previous)?;
+        if violations.is_empty() {
+            verified.tips.insert(key, *tip);
+            updated = true;
+        } else {
+            found.push(BranchViolations {
+                branch: branch.to_owned(),
+                tip: *tip,
+                violations,
+            })
+        }
+    }
+
+    if updated {
+        STORE.store(storage.path(), &namespace, &verified)?;
+    }
+
+    Ok(found)
+}
+
+/// Verification of `SSHSIG` signatures, as specified in
+/// <https://github.com/openssh/openssh-portable/blob/master/PROTOCOL.sshsig>.
+mod sshsig {
+    use super::*;
+
+    pub fn verify(armored: &str, data: &[u8]) -> Verdict {
+        match parse(armored) {
+            None => Verdict::Malformed,
+            Some(sig) => {
+                let digest = match sig.hash_algorithm.as_slice() {
+                    b"sha256" => Sha256::digest(data).to_vec(),
+                    b"sha512" => Sha512::digest(data).to_vec(),
+                    _ => return Verdict::Unsupported,
+                };
+                let mut signed = SSHSIG_MAGIC.to_vec();
+                put_string(&mut signed, SSHSIG_NAMESPACE.as_bytes());
+                put_string(&mut signed, &sig.reserved);
+                put_string(&mut signed, &sig.hash_algorithm);
+                put_string(&mut signed, &digest);
+                if sig.namespace == SSHSIG_NAMESPACE.as_bytes()
+                    && sig.signature.verify(&signed, &sig.key)
+                {
+                    Verdict::Signed(sig.key)
+                } else {
+                    Verdict::Invalid
+                }
+            },
+        }
+    }
+
+    struct Sig {
+        key: PublicKey,
+        namespace: Vec<u8>,
+        reserved: Vec<u8>,
+        hash_algorithm: Vec<u8>,
+        signature: Signature,
+    }
+
+    fn parse(armored: &str) -> Option<Sig> {
+        let body = armored
+            .strip_prefix(SSHSIG_ARMOR_BEGIN)?
+            .strip_suffix(SSHSIG_ARMOR_END)?
+            .split_whitespace()
+            .collect::<String>();
+        let blob = multibase::Base::Base64Pad.decode(body).ok()?;
+
+        let mut r = blob.strip_prefix(SSHSIG_MAGIC)?;
+        if take_u32(&mut r)? != 1 {
+            return None;
+        }
+        let key = {
+            let mut k = take_string(&mut r)?;
+            if take_string(&mut k)? != b"ssh-ed25519" {
+                return None;
+            }
+            PublicKey::from_slice(take_string(&mut k)?)?
+        };
+        let namespace = take_string(&mut r)?.to_vec();
+        let reserved = take_string(&mut r)?.to_vec();
+        let hash_algorithm = take_string(&mut r)?.to_vec();
+        let signature = {
+            let mut s = take_string(&mut r)?;
+            if take_string(&mut s)? != b"ssh-ed25519" {
+                return None;
+            }
+            let bytes = <[u8; 64]>::try_from(take_string(&mut s)?).ok()?;
+            Signature::from(sign::Signature(bytes))
+        };
+
+        Some(Sig {
+            key,
+            namespace,
+            reserved,
+            hash_algorithm,
+            signature,
+        })
+    }
+
+    fn take_u32(r: &mut &[u8]) -> Option<u32> {
+        if r.len() < 4 {
+            return None;
+        }
+        let (n, rest) = r.split_at(4);
+        *r = rest;
+        Some(u32::from_be_bytes([n[0], n[1], n[2], n[3]]))
+    }
+
+    fn take_string<'a>(r: &mut &'a [u8]) -> Option<&'a [u8]> {
+        let len = take_u32(r)? as usize;
+        if r.len() < len {
+            return None;
+        }
+        let (s, rest) = r.split_at(len);
+        *r = rest;
+        Some(s)
+    }
+
+    fn put_string(w: &mut Vec<u8>, s: &[u8]) {
+        w.extend_from_slice(&(s.len() as u32).to_be_bytes());
+        w.extend_from_slice(s);
+    }
+}
@@ -24,16 +24,36 @@ use crate::{
     Signer,
 };
 
+pub mod audit;
+pub mod backup;
+pub mod bundle;
 pub mod config;
+pub mod encryption;
+pub mod facade;
 #[cfg(not(feature = "replication-v3"))]
 pub mod fetcher;
+pub mod forks;
 pub mod glob;
+pub mod health;
+pub mod history;
+pub mod index;
+pub mod json;
+pub mod lease;
+pub mod lock;
 pub mod pool;
 pub mod read;
+pub mod reflog;
+pub mod requirements;
+pub mod snapshot;
+pub mod stats;
+pub mod txn;
 pub mod watch;
 
 pub use config::Config;
+pub use facade::AsyncStorage;
 pub use glob::Pattern;
+pub use health::Health;
+pub use lease::Locking;
 pub use pool::{Pool, PoolError, Pooled, PooledRef};
 pub use read::{
     Error,
@@ -44,12 +64,14 @@ pub use read::{
     References,
     ReferencesGlob,
 };
+pub use snapshot::Snapshot;
+pub use txn::Transaction;
 pub use watch::{NamespaceEvent, Watcher};
 
 pub mod error {
     use thiserror::Error;
 
-    use super::config;
+    use super::{config, encryption};
 
     #[derive(Debug, Error)]
     #[non_exhaustive]
@@ -57,16 +79,43 @@ pub mod error {
         #[error(transparent)]
         Config(#[from] config::Error),
 
+        #[error(transparent)]
+        Encryption(#[from] encryption::Error),
+
         #[error(transparent)]
         Git(#[from] git2::Error),
 
+        #[error(transparent)]
+        Lease(#[from] super::lease::Error),
+
+        #[error(
+            "storage uses object format `{0}`, which is not supported by this version of link"
+        )]
+        ObjectFormat(String),
+
         #[error("signer key does not match the key used at initialisation")]
         SignerKeyMismatch,
 
+        #[error("storage is encrypted, and must be unlocked before it can be opened")]
+        Locked,
+
         #[error(transparent)]
         TrackingMigration(#[from] Box<dyn std::error::Error + Send + Sync + 'static>),
     }
 
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum RemoveNamespace {
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Lease(#[from] super::lease::Error),
+
+        #[error(transparent)]
+        NamespaceLock(#[from] super::lock::Error),
+    }
+
     impl From<crate::git::identities::error::Error> for Init {
         fn from(err: crate::git::identities::error::Error) -> Self {
             Self::TrackingMigration(Box::new(err))
@@ -84,6 +133,8 @@ pub mod error {
 pub struct Storage {
     inner: ReadOnly,
     signer: BoxedSigner,
+    locks: lock::Namespaces,
+    leases: Option<lease::Leases>,
 }
 
 impl Storage {
@@ -100,6 +151,10 @@ impl Storage {
     /// the same way two `git` processes can access the same repository.
     /// However, if you need multiple [`Storage`]s to be shared between
     /// threads, use a [`Pool`] instead.
+    ///
+    /// Multi-step updates of a namespace should be guarded by
+    /// [`Storage::lock_namespace`], which is shared by all [`Storage`]s of
+    /// the same monorepo within the process.
     pub fn open<S>(paths: &Paths, signer: S) -> Result<Self, error::Init>
     where
         S: Signer + Clone,
@@ -107,6 +162,13 @@ impl Storage {
     {
         crate::git::init();
 
+        if encryption::status(paths)? == encryption::Status::Locked {
+            return Err(error::Init::Locked);
+        }
+        if let Some(format) = read::unsupported_object_format(paths.git_dir())? {
+            return Err(error::Init::ObjectFormat(format));
+        }
+
         let backend = match git2::Repository::open_bare(paths.git_dir()) {
             Err(e) if is_not_found_err(&e) => {
                 let mut backend = git2::Repository::init_opts(
@@ -129,9 +191,13 @@ impl Storage {
             return Err(error::Init::SignerKeyMismatch);
         }
 
+        let locks = lock::Namespaces::for_storage(backend.path());
+        let leases = leases(&backend)?;
         let storage = Self {
             inner: ReadOnly { backend, peer_id },
             signer: BoxedSigner::from(SomeSigner { signer }),
+            locks,
+            leases,
         };
 
         // NOTE: this is temporary migration code, converting v1 tracking entries into
@@ -189,12 +255,85 @@ impl Storage {
             return Err(error::Init::SignerKeyMismatch);
         }
 
+        let locks = lock::Namespaces::for_storage(ro.path());
+        let leases = leases(&ro.backend)?;
         Ok(Self {
             inner: ro,
             signer: BoxedSigner::from(SomeSigner { signer }),
+            locks,
+            leases,
         })
     }
 
+    /// Lock the namespace `urn` for writing, blocking until no other
+    /// [`Storage`] of this process holds it.
+    ///
+    /// If the storage uses [`Locking::Lease`], this also blocks until no other
+    /// process holds the namespace. The returned guard should be
+    /// [`lock::Guard::check`]ed before committing updates.
+    ///
+    /// See [`lock`] and [`lease`] for details.
+    pub fn lock_namespace(&self, urn: &Urn) -> Result<lock::Guard, lock::Error> {
+        self.lock_namespaces(Some(urn.clone()))
+    }
+
+    /// Lock all namespaces in `urns` for writing, in an order which avoids
+    /// deadlocks with other callers.
+    ///
+    /// See [`Storage::lock_namespace`].
+    pub fn lock_namespaces<I>(&self, urns: I) -> Result<lock::Guard, lock::Error>
+    where
+        I: IntoIterator<Item = Urn>,
+    {
+        let guard = self.locks.lock_many(urns)?;
+        match &self.leases {
+            None => Ok(guard),
+            Some(leases) => {
+                let held = leases.acquire_many(guard.acquired())?;
+                Ok(guard.with_leases(held, leases.ttl()))
+            },
+        }
+    }
+
+    /// Remove all references of the namespace `urn`, returning how many were
+    /// removed.
+    ///
+    /// The objects they pointed to are left to garbage collection. This is
+    /// meant for rolling back a namespace which was only just created, other
+    /// peers may still have a copy of a namespace which was announced.
+    pub fn remove_namespace(&self, urn: &Urn) -> Result<usize, error::RemoveNamespace> {
+        let lock = self.lock_namespace(urn)?;
+
+        let prefix = snapshot::namespace_prefix(urn);
+        let repo = self.as_raw();
+        let mut names = Vec::new();
+        for r in repo.references_glob(&format!("{}*", prefix))? {
+            if let Some(name) = r?.name() {
+                names.push(name.to_owned());
+            }
+        }
+
+        let mut tx = repo.transaction()?;
+        for name in &names {
+            tx.lock_ref(name)?;
+            tx.remove(name)?;
+        }
+        lock.check()?;
+        tx.commit()?;
+        self.reindex(urn);
+
+        Ok(names.len())
+    }
+
+    /// The effective [`Locking`] strategy of this storage.
+    pub fn locking(&self) -> Locking {
+        if self.leases.is_some() {
+            Locking::Lease
+        } else {
+            Locking::Local
+        }
+    }
+
     pub fn read_only(&self) -> &ReadOnly {
         &self.inner
     }
@@ -223,6 +362,49 @@ impl Storage {
         watch::Watch { storage: self }
     }
 
+    /// The [`audit::Log`] of this storage.
+    pub fn audit_log(&self) -> audit::Log {
+        audit::Log::new(self.path())
+    }
+
+    /// Record `event` in the [`audit::Log`], signed by the storage key.
+    ///
+    /// Failing to record an event does not fail the operation being recorded,
+    /// the failure is logged instead. The gap is detectable, as the event will
+    /// be missing from an otherwise valid log.
+    pub fn audit(&self, event: audit::Event) {
+        if let Err(e) = self.audit_log().append(&self.signer, event) {
+            tracing::error!(err = %e, "failed to record audit event");
+        }
+    }
+
+    /// The [`index::Index`] of the namespaces in this storage.
+    pub fn namespace_index(&self) -> index::Index {
+        index::Index::for_storage(self.path())
+    }
+
+    /// Record the current state of the namespace `urn` in the
+    /// [`index::Index`], and update its [`stats::Stats`],
+    /// [`forks::Topology`] and [`requirements::Requirements`].
+    ///
+    /// Like [`Storage::audit`], failing to do so does not fail the operation
+    /// which modified the namespace. The failure is logged, and corrected by
+    /// the next update of the namespace.
+    pub fn reindex(&self, urn: &Urn) {
+        if let Err(e) = self.namespace_index().update(self, urn) {
+            tracing::warn!(urn = %urn, err = %e, "failed to update namespace index");
+        }
+        if let Err(e) = self.update_stats(urn) {
+            tracing::warn!(urn = %urn, err = %e, "failed to update namespace stats");
+        }
+        if let Err(e) = self.update_forks(urn) {
+            tracing::warn!(urn = %urn, err = %e, "failed to update fork topology");
+        }
+        if let Err(e) = self.record_requirements(urn) {
+            tracing::warn!(urn = %urn, err = %e, "failed to record namespace requirements");
+        }
+    }
+
     pub(super) fn signer(&self) -> &BoxedSigner {
         &self.signer
     }
@@ -381,3 +563,13 @@ impl ReadOnlyStorage for Storage {
         self.inner.has_remote(urn, peer)
     }
 }
+
+/// The [`lease::Leases`] of the monorepo `repo`, if it is configured to use
+/// [`Locking::Lease`].
+fn leases(repo: &git2::Repository) -> Result<Option<lease::Leases>, error::Init> {
+    let locking = Config::try_from(repo)?.locking()?;
+    Ok(match locking.resolve(repo.path()) {
+        Locking::Lease => Some(lease::Leases::new(repo.path())),
+        _ => None,
+    })
+}
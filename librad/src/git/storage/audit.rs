@@ -0,0 +1,529 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Tamper-evident log of security-relevant operations.
+//!
+//! The audit log is an append-only file next to the monorepo, recording uses
+//! of the storage key (signing identities and `rad/signed_refs`), changes to
+//! tracking entries and their configuration, and administrative commands
+//! received by the node.
+//!
+//! Every [`Entry`] commits to its predecessor by including its hash, and is
+//! signed by the storage key. Hence, removing, reordering, or altering entries
+//! is detected by [`Log::verify`] -- unless the attacker is in possession of
+//! the key, in which case nothing stored locally can be trusted anyway.
+//!
+//! The log is stored as newline-delimited JSON, so it can be inspected with
+//! standard tools. Use [`Log::export`] to obtain a verified copy.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead as _, BufReader, Read as _, Seek as _, SeekFrom, Write as _},
+    path::{Path, PathBuf},
+    process,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use git_ext as ext;
+use link_canonical::Cjson;
+
+use crate::{identities::git::Urn, PeerId, PublicKey, Signature, Signer};
+
+/// The name of the log file, relative to the storage directory.
+pub const FILE_NAME: &str = "audit.log";
+
+/// How long [`Log::append`] waits for a concurrent writer before giving up.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How old a lock must be to be considered stale, and broken by the next
+/// writer. Appending an entry takes milliseconds, so a lock this old was left
+/// behind by a writer which crashed.
+const LOCK_STALE: Duration = Duration::from_secs(10);
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use crate::git_ext as ext;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Append {
+        #[error("audit log is locked by another writer, remove `{0}` if it is stale")]
+        Locked(std::path::PathBuf),
+
+        #[error("the last entry of the audit log is corrupt")]
+        Corrupt(#[source] serde_json::Error),
+
+        #[error(transparent)]
+        Cjson(#[from] link_canonical::CjsonError),
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+
+        #[error(transparent)]
+        Json(#[from] serde_json::Error),
+
+        #[error("failed to sign audit log entry")]
+        Sign(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Verify {
+        #[error("line {line}: malformed entry")]
+        Malformed {
+            line: usize,
+            #[source]
+            source: serde_json::Error,
+        },
+
+        #[error("entry {seq}: expected sequence number {expected}")]
+        Sequence { seq: u64, expected: u64 },
+
+        #[error("entry {seq}: does not refer to the previous entry")]
+        Chain { seq: u64 },
+
+        #[error("entry {seq}: hash mismatch, expected {expected}, found {actual}")]
+        Hash {
+            seq: u64,
+            expected: ext::Oid,
+            actual: ext::Oid,
+        },
+
+        #[error("entry {seq}: invalid signature")]
+        Signature { seq: u64 },
+
+        #[error(transparent)]
+        Cjson(#[from] link_canonical::CjsonError),
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+/// A security-relevant operation.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum Event {
+    /// A revision of the identity `urn` was signed with the storage key.
+    IdentitySigned { urn: Urn, revision: ext::Oid },
+    /// The `rad/signed_refs` of `urn` were signed with the storage key.
+    RefsSigned { urn: Urn, at: ext::Oid },
+    /// A tracking entry was created, or its configuration changed.
+    Tracked {
+        urn: Urn,
+        peer: Option<PeerId>,
+        config: ext::Oid,
+    },
+    /// A tracking entry was removed.
+    Untracked { urn: Urn, peer: Option<PeerId> },
+    /// An administrative command was received, eg. via the control socket.
+    Command { command: String, origin: String },
+    /// The namespace `urn` was restored from the backup identified by
+    /// `manifest`, see [`super::backup`].
+    Restored { urn: Urn, manifest: ext::Oid },
+    /// The bundle of `urn` exported by `peer`, identified by `manifest`, was
+    /// imported, see [`super::bundle`].
+    BundleImported {
+        urn: Urn,
+        peer: PeerId,
+        manifest: ext::Oid,
+    },
+    /// A change to the collaborative object `object` of type `typename` was
+    /// rejected, because `author` was not authorized to make it. Recorded
+    /// when the object is updated locally, or its changes were received.
+    CobRejected {
+        urn: Urn,
+        typename: String,
+        object: String,
+        change: ext::Oid,
+        author: Urn,
+        reason: String,
+    },
+    /// The refs `refs` of `urn` were updated in a single
+    /// [`super::txn::Transaction`].
+    RefsUpdated {
+        urn: Urn,
+        message: String,
+        refs: Vec<String>,
+    },
+}
+
+/// The hashed part of an [`Entry`].
+#[derive(serde::Serialize)]
+struct Body<'a> {
+    seq: u64,
+    timestamp: u64,
+    prev: Option<ext::Oid>,
+    event: &'a Event,
+}
+
+/// An entry of the audit log.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    /// Position of the entry in the log, starting at zero.
+    pub seq: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The hash of the previous entry, `None` for the first entry.
+    pub prev: Option<ext::Oid>,
+    pub event: Event,
+    /// The hash of the fields above.
+    pub hash: ext::Oid,
+    /// The signature over `hash` by the storage key.
+    pub signature: Signature,
+}
+
+impl Entry {
+    fn compute_hash(
+        seq: u64,
+        timestamp: u64,
+        prev: Option<ext::Oid>,
+        event: &Event,
+    ) -> Result<ext::Oid, error::Verify> {
+        let body = Cjson(Body {
+            seq,
+            timestamp,
+            prev,
+            event,
+        })
+        .canonical_form()?;
+        Ok(git2::Oid::hash_object(git2::ObjectType::Blob, &body)?.into())
+    }
+
+    /// Check that the entry is intact and signed by `key`.
+    pub fn verify(&self, key: &PublicKey) -> Result<(), error::Verify> {
+        let actual = Self::compute_hash(self.seq, self.timestamp, self.prev, &self.event)?;
+        if actual != self.hash {
+            return Err(error::Verify::Hash {
+                seq: self.seq,
+                expected: self.hash,
+                actual,
+            });
+        }
+        if !self.signature.verify(self.hash.as_bytes(), key) {
+            return Err(error::Verify::Signature { seq: self.seq });
+        }
+
+        Ok(())
+    }
+}
+
+/// The result of a successful [`Log::verify`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verified {
+    /// The number of entries in the log.
+    pub len: u64,
+    /// The hash of the last entry, if any.
+    pub head: Option<ext::Oid>,
+}
+
+/// Handle to the audit log file.
+#[derive(Clone, Debug)]
+pub struct Log {
+    path: PathBuf,
+}
+
+impl Log {
+    /// The audit log of the storage at `storage_path`.
+    pub fn new(storage_path: &Path) -> Self {
+        Self {
+            path: storage_path.join(FILE_NAME),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append `event`, signing it with `signer`.
+    ///
+    /// Concurrent appends, also from other processes, are serialised via a
+    /// lock file.
+    pub fn append<S>(&self, signer: &S, event: Event) -> Result<Entry, error::Append>
+    where
+        S: Signer,
+        S::Error: std::error::Error + Send + Sync + 'static,
+    {
+        let _lock = LockFile::acquire(self.path.with_extension("log.lock"))?;
+
+        let last = match last_line(&self.path)? {
+            None => None,
+            Some(line) => {
+                Some(serde_json::from_str::<Entry>(&line).map_err(error::Append::Corrupt)?)
+            },
+        };
+        let seq = last.as_ref().map(|e| e.seq + 1).unwrap_or(0);
+        let prev = last.map(|e| e.hash);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let hash = Entry::compute_hash(seq, timestamp, prev, &event).map_err(|e| match e {
+            error::Verify::Cjson(e) => error::Append::Cjson(e),
+            error::Verify::Git(e) => error::Append::Git(e),
+            other => unreachable!("hashing does not verify: {}", other),
+        })?;
+        let signature = signer
+            .sign_blocking(hash.as_bytes())
+            .map_err(|e| error::Append::Sign(Box::new(e)))?;
+
+        let entry = Entry {
+            seq,
+            timestamp,
+            prev,
+            event,
+            hash,
+            signature: signature.into(),
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(&line)?;
+        file.sync_data()?;
+
+        Ok(entry)
+    }
+
+    /// Iterate over the entries of the log, without verifying them.
+    pub fn entries(&self) -> io::Result<impl Iterator<Item = Result<Entry, error::Verify>>> {
+        let lines = match File::open(&self.path) {
+            Ok(file) => Some(BufReader::new(file).lines()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        Ok(lines.into_iter().flatten().enumerate().map(|(i, line)| {
+            let line_no = i + 1;
+            serde_json::from_str(&line?).map_err(|source| error::Verify::Malformed {
+                line: line_no,
+                source,
+            })
+        }))
+    }
+
+    /// Verify the integrity of the whole log, and that all entries were
+    /// signed by `peer`.
+    pub fn verify(&self, peer: &PeerId) -> Result<Verified, error::Verify> {
+        self.export(peer, io::sink())
+    }
+
+    /// Verify the log like [`Log::verify`], writing the entries to `out` as
+    /// newline-delimited JSON.
+    ///
+    /// Entries are written as they are verified, so `out` should be discarded
+    /// if an error is returned.
+    pub fn export<W>(&self, peer: &PeerId, mut out: W) -> Result<Verified, error::Verify>
+    where
+        W: io::Write,
+    {
+        let key = peer.as_public_key();
+        let mut verified = Verified { len: 0, head: None };
+        for entry in self.entries()? {
+            let entry = entry?;
+            if entry.seq != verified.len {
+                return Err(error::Verify::Sequence {
+                    seq: entry.seq,
+                    expected: verified.len,
+                });
+            }
+            if entry.prev != verified.head {
+                return Err(error::Verify::Chain { seq: entry.seq });
+            }
+            entry.verify(key)?;
+
+            serde_json::to_writer(&mut out, &entry).map_err(io::Error::from)?;
+            out.write_all(b"\n")?;
+            verified.len += 1;
+            verified.head = Some(entry.hash);
+        }
+        out.flush()?;
+
+        Ok(verified)
+    }
+}
+
+/// Exclusive lock on the log, held while the lock file exists.
+///
+/// The lock file records the pid of its holder and when it was taken, so that
+/// a lock left behind by a crashed writer can be broken once it is older than
+/// [`LOCK_STALE`].
+struct LockFile {
+    path: PathBuf,
+    holder: String,
+}
+
+impl LockFile {
+    fn acquire(path: PathBuf) -> Result<Self, error::Append> {
+        let holder = format!(
+            "{} {} {:016x}",
+            process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            rand::random::<u64>()
+        );
+        let mut waited = Duration::ZERO;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let lock = Self { path, holder };
+                    file.write_all(lock.holder.as_bytes())?;
+                    return Ok(lock);
+                },
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    match lock_age(&path)? {
+                        // Released in the meantime, retry right away
+                        None => continue,
+                        Some((_, age)) if age > LOCK_STALE => {
+                            if break_stale(&path)? {
+                                continue;
+                            }
+                        },
+                        Some(_) => {},
+                    }
+                    if waited >= LOCK_TIMEOUT {
+                        // A writer may have crashed while breaking the lock
+                        let breaking = path.with_extension("lock.break");
+                        return Err(error::Append::Locked(if breaking.exists() {
+                            breaking
+                        } else {
+                            path
+                        }));
+                    }
+                    let backoff = Duration::from_millis(10);
+                    thread::sleep(backoff);
+                    waited += backoff;
+                },
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Remove the lock file at `path` if it is stale, returning `false` if another
+/// writer is already breaking it.
+///
+/// Only one writer at a time may break the lock, serialised by the
+/// `<path>.break` file. The lock is checked again while holding it, so a lock
+/// which was broken and taken by another writer in the meantime is left
+/// alone.
+fn break_stale(path: &Path) -> Result<bool, error::Append> {
+    let breaking = path.with_extension("lock.break");
+    match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&breaking)
+    {
+        Ok(_) => {},
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(false),
+        Err(e) => return Err(e.into()),
+    }
+
+    let broken = match lock_age(path) {
+        Ok(Some((pid, age))) if age > LOCK_STALE => {
+            tracing::warn!(
+                path = %path.display(),
+                pid = ?pid,
+                age = ?age,
+                "breaking stale audit log lock"
+            );
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        },
+        Ok(_) => Ok(()),
+        Err(e) => Err(e),
+    };
+    fs::remove_file(&breaking)?;
+    broken?;
+
+    Ok(true)
+}
+
+/// The pid of the holder of the lock file at `path`, if known, and how long
+/// ago it was taken. `None` if the lock file does not exist.
+///
+/// Falls back to the modification time of the file if the holder did not get
+/// to write its details (yet).
+fn lock_age(path: &Path) -> io::Result<Option<(Option<u32>, Duration)>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut fields = content.split_whitespace();
+    let pid = fields.next().and_then(|pid| pid.parse().ok());
+    let taken = match fields.next().and_then(|secs| secs.parse().ok()) {
+        Some(secs) => UNIX_EPOCH + Duration::from_secs(secs),
+        None => match fs::metadata(path) {
+            Ok(meta) => meta.modified()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        },
+    };
+
+    Ok(Some((pid, taken.elapsed().unwrap_or_default())))
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        // Don't remove a lock which was broken and taken by someone else
+        match fs::read_to_string(&self.path) {
+            Ok(content) if content == self.holder => {},
+            _ => return,
+        }
+        if let Err(e) = fs::remove_file(&self.path) {
+            tracing::warn!(err = %e, path = %self.path.display(), "failed to remove audit log lock");
+        }
+    }
+}
+
+/// Read the last non-empty line of the file at `path`, without reading the
+/// whole file.
+fn last_line(path: &Path) -> io::Result<Option<String>> {
+    const CHUNK: u64 = 4096;
+
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let len = file.metadata()?.len();
+    let mut start = len;
+    let mut tail = Vec::new();
+    loop {
+        let content = String::from_utf8_lossy(&tail);
+        let trimmed = content.trim_end_matches('\n');
+        if let Some(pos) = trimmed.rfind('\n') {
+            return Ok(Some(trimmed[pos + 1..].to_owned()));
+        }
+        if start == 0 {
+            return Ok(Some(trimmed.to_owned()).filter(|line| !line.is_empty()));
+        }
+
+        let next = start.saturating_sub(CHUNK);
+        let mut chunk = vec![0; (start - next) as usize];
+        file.seek(SeekFrom::Start(next))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&tail);
+        tail = chunk;
+        start = next;
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Point-in-time backups of individual namespaces.
+//!
+//! [`ReadOnly::backup`] writes the references of a namespace, as of a
+//! [`super::Snapshot`], along with a packfile of the objects reachable from
+//! them, to an archive. [`Storage::restore`] imports such an archive, and
+//! resets the references of the namespace to the state recorded in it in a
+//! single transaction.
+//!
+//! The archive consists of the JSON-encoded [`Manifest`] on a single line,
+//! followed by the packfile. Similar to git bundles, a backup can be made
+//! _incremental_ to a previous one by passing its [`Manifest`]: objects
+//! reachable from the references recorded in the previous manifest are then
+//! omitted from the pack, and must be present in the storage the archive is
+//! restored to (eg. by restoring the previous archive first).
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom as _,
+    io::{self, BufRead, Write},
+};
+
+use git_ext as ext;
+
+use super::{
+    audit,
+    reflog::{Message, Op},
+    snapshot::namespace_prefix,
+    txn::Previous,
+    ReadOnly,
+    Storage,
+};
+use crate::identities::git::Urn;
+
+/// Version of the archive format.
+const VERSION: u8 = 1;
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use git_ext as ext;
+
+    use super::super::{lease, lock, read, txn};
+    use crate::identities::git::Urn;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Backup {
+        #[error("base manifest is for {base}, not {urn}")]
+        BaseMismatch { urn: Urn, base: Urn },
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+
+        #[error(transparent)]
+        Json(#[from] serde_json::Error),
+
+        #[error(transparent)]
+        Read(#[from] read::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Restore {
+        #[error("unsupported archive version {0}")]
+        Version(u8),
+
+        #[error("archive requires {0} to be present, restore the base archive first")]
+        MissingPrerequisite(ext::Oid),
+
+        #[error("archive is corrupt: missing object {0}")]
+        MissingObject(ext::Oid),
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+
+        #[error(transparent)]
+        Json(#[from] serde_json::Error),
+
+        #[error(transparent)]
+        Lease(#[from] lease::Error),
+
+        #[error(transparent)]
+        NamespaceLock(#[from] lock::Error),
+
+        #[error(transparent)]
+        RefName(#[from] ext::name::Error),
+
+        #[error(transparent)]
+        Transaction(#[from] txn::error::Commit),
+    }
+}
+
+/// The header of a backup archive.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    pub version: u8,
+    /// The namespace this is a backup of.
+    pub urn: Urn,
+    /// The references of the namespace, relative to it.
+    pub refs: BTreeMap<String, ext::Oid>,
+    /// The [`Manifest::id`] of the manifest this backup is incremental to.
+    pub base: Option<ext::Oid>,
+    /// Objects which were omitted from the pack, because they were recorded
+    /// in the base manifest.
+    pub prerequisites: BTreeSet<ext::Oid>,
+    /// Number of objects in the pack.
+    pub objects: usize,
+}
+
+impl Manifest {
+    /// The hash identifying this manifest.
+    pub fn id(&self) -> Result<ext::Oid, serde_json::Error> {
+        let json = serde_json::to_vec(self)?;
+        Ok(git2::Oid::hash_object(git2::ObjectType::Blob, &json)
+            .expect("hashing in-memory data can't fail")
+            .into())
+    }
+}
+
+impl ReadOnly {
+    /// Write a backup of the namespace `urn` to `out`.
+    ///
+    /// If `base` is given, the backup is incremental to it, and can only be
+    /// restored to a storage which contains the objects of `base`.
+    pub fn backup<W>(
+        &self,
+        urn: &Urn,
+        base: Option<&Manifest>,
+        mut out: W,
+    ) -> Result<Manifest, error::Backup>
+    where
+        W: Write,
+    {
+        if let Some(base) = base {
+            if base.urn != urn.clone().with_path(None) {
+                return Err(error::Backup::BaseMismatch {
+                    urn: urn.clone(),
+                    base: base.urn.clone(),
+                });
+            }
+        }
+
+        let snapshot = self.snapshot(urn)?;
+        let odb = self.backend.odb()?;
+        let prerequisites = base
+            .map(|base| {
+                base.refs
+                    .values()
+                    .filter(|oid| odb.exists(***oid))
+                    .copied()
+                    .collect::<BTreeSet<_>>()
+            })
+            .unwrap_or_default();
+
+        let mut walk = self.backend.revwalk()?;
+        let mut others = Vec::new();
+        for (_, oid) in snapshot.iter() {
+            match odb.read_header(**oid)?.1 {
+                git2::ObjectType::Commit => walk.push(**oid)?,
+                _ => others.push(**oid),
+            }
+        }
+        for oid in &prerequisites {
+            if odb.read_header(**oid)?.1 == git2::ObjectType::Commit {
+                walk.hide(**oid)?;
+            }
+        }
+
+        let mut pack = self.backend.packbuilder()?;
+        pack.insert_walk(&mut walk)?;
+        for oid in others {
+            if !prerequisites.contains(&ext::Oid::from(oid)) {
+                pack.insert_recursive(oid, None)?;
+            }
+        }
+
+        let manifest = Manifest {
+            version: VERSION,
+            urn: snapshot.urn().clone(),
+            refs: snapshot
+                .iter()
+                .map(|(name, oid)| (name.to_string(), *oid))
+                .collect(),
+            base: base.map(Manifest::id).transpose()?,
+            prerequisites,
+            objects: pack.object_count(),
+        };
+        serde_json::to_writer(&mut out, &manifest)?;
+        out.write_all(b"\n")?;
+
+        let mut res = Ok(());
+        pack.foreach(|chunk| match out.write_all(chunk) {
+            Ok(()) => true,
+            Err(e) => {
+                res = Err(e);
+                false
+            },
+        })?;
+        res?;
+        out.flush()?;
+
+        Ok(manifest)
+    }
+}
+
+impl Storage {
+    /// Restore a backup written by [`ReadOnly::backup`].
+    ///
+    /// The objects in the archive are imported, and the references of the
+    /// namespace are reset to the ones recorded in the [`Manifest`] in a single
+    /// [`super::Transaction`]: references which are not in the manifest are
+    /// removed. Committing the transaction reindexes the namespace.
+    pub fn restore<R>(&self, mut input: R) -> Result<Manifest, error::Restore>
+    where
+        R: BufRead,
+    {
+        let mut header = String::new();
+        input.read_line(&mut header)?;
+        let manifest: Manifest = serde_json::from_str(&header)?;
+        if manifest.version != VERSION {
+            return Err(error::Restore::Version(manifest.version));
+        }
+
+        let repo = self.as_raw();
+        let odb = repo.odb()?;
+        if let Some(missing) = manifest
+            .prerequisites
+            .iter()
+            .find(|oid| !odb.exists(***oid))
+        {
+            return Err(error::Restore::MissingPrerequisite(*missing));
+        }
+
+        if manifest.objects > 0 {
+            let mut writer = odb.packwriter()?;
+            io::copy(&mut input, &mut writer)?;
+            writer.commit()?;
+        }
+        if let Some(missing) = manifest.refs.values().find(|oid| !odb.exists(***oid)) {
+            return Err(error::Restore::MissingObject(*missing));
+        }
+
+        // Held throughout, so that no refs are created between listing and
+        // removing them. The transaction re-enters the lock.
+        let _lock = self.lock_namespace(&manifest.urn)?;
+
+        let prefix = namespace_prefix(&manifest.urn);
+        let mut existing = Vec::new();
+        for r in repo.references_glob(&format!("{}*", prefix))? {
+            if let Some(name) = r?.name() {
+                existing.push(name.strip_prefix(&prefix).unwrap_or(name).to_owned());
+            }
+        }
+
+        let restore_message = Message::new(Op::Restore, "restore from backup").to_string();
+        let mut tx = self.transaction(&manifest.urn, restore_message);
+        for (name, oid) in &manifest.refs {
+            tx = tx.write(ext::RefLike::try_from(name.as_str())?, *oid, Previous::Any);
+        }
+        for name in existing {
+            if !manifest.refs.contains_key(&name) {
+                tx = tx.delete(ext::RefLike::try_from(name)?, Previous::Any);
+            }
+        }
+        tx.commit()?;
+
+        self.audit(audit::Event::Restored {
+            urn: manifest.urn.clone(),
+            manifest: manifest.id()?,
+        });
+
+        Ok(manifest)
+    }
+}
@@ -0,0 +1,492 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Offline exchange of a peer's view of a namespace.
+//!
+//! [`ReadOnly::export_bundle`] writes the refs of the local peer in a
+//! namespace, as recorded in its `rad/signed_refs`, along with a packfile of
+//! the objects reachable from them, to a bundle. This covers the identity
+//! document and those of its delegates, the branches and tags, and the
+//! collaborative objects of the peer. The bundle can be carried to another
+//! machine by any means, eg. on a USB stick.
+//!
+//! [`Storage::import_bundle`] imports a bundle as if it had been fetched from
+//! the exporting peer: the signed refs and the identity are verified, and the
+//! refs are stored as the remote refs of that peer, which is tracked. If the
+//! namespace did not exist before, it is created from the imported identity.
+//!
+//! The format is the same as that of a [`super::backup`] archive: the
+//! JSON-encoded [`Manifest`] on a single line, followed by the packfile.
+//! Bundles can be made incremental to a previous one in the same way.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, BufRead, Write},
+};
+
+use git_ext as ext;
+
+use super::{
+    audit,
+    reflog::{Message, Op},
+    snapshot::namespace_prefix,
+    ReadOnly,
+    ReadOnlyStorage as _,
+    Storage,
+};
+use crate::{
+    git::{
+        identities::common::IdRef,
+        refs::{self, Refs},
+        tracking,
+    },
+    identities::git::{Person, Project, SomeIdentity, Urn, VerifiedPerson},
+    PeerId,
+};
+
+/// Version of the bundle format.
+const VERSION: u8 = 1;
+
+const SIGNED_REFS: &str = "refs/rad/signed_refs";
+const RAD_ID: &str = "refs/rad/id";
+const RAD_IDS: &str = "refs/rad/ids/";
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use git_ext as ext;
+
+    use super::super::{lease, lock, read};
+    use crate::{
+        git::{refs, tracking},
+        identities::git::Urn,
+        PeerId,
+    };
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Export {
+        #[error("no signed refs found for {0}")]
+        NoSignedRefs(Urn),
+
+        #[error("base manifest is for {base}, not {urn}")]
+        BaseMismatch { urn: Urn, base: Urn },
+
+        #[error(transparent)]
+        Refs(#[from] refs::stored::Error),
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+
+        #[error(transparent)]
+        Json(#[from] serde_json::Error),
+
+        #[error(transparent)]
+        Read(#[from] read::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[error("delegate {0} is missing from the bundle")]
+    pub struct MissingDelegate(pub Urn);
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Import {
+        #[error("unsupported bundle version {0}")]
+        Version(u8),
+
+        #[error("bundle was exported by the local peer {0}")]
+        OwnBundle(PeerId),
+
+        #[error("bundle requires {0} to be present, import the base bundle first")]
+        MissingPrerequisite(ext::Oid),
+
+        #[error("bundle is corrupt: missing object {0}")]
+        MissingObject(ext::Oid),
+
+        #[error("bundle contains no signed refs")]
+        NoSignedRefs,
+
+        #[error("ref `{0}` is not covered by the signed refs of the bundle")]
+        Unsigned(String),
+
+        #[error("bundle contains no identity")]
+        NoIdentity,
+
+        #[error("identity in bundle is {found}, not {expected}")]
+        UrnMismatch { expected: Urn, found: Urn },
+
+        #[error("the identity in the bundle failed to verify")]
+        Verify(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+
+        #[error("bundle is older than the refs of {0} already present")]
+        Stale(PeerId),
+
+        #[error(transparent)]
+        Refs(#[from] refs::stored::Error),
+
+        #[error(transparent)]
+        Track(#[from] tracking::error::Track),
+
+        #[error(transparent)]
+        Commit(#[from] crate::git::storage::txn::error::Commit),
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+
+        #[error(transparent)]
+        Json(#[from] serde_json::Error),
+
+        #[error(transparent)]
+        Lease(#[from] lease::Error),
+
+        #[error(transparent)]
+        NamespaceLock(#[from] lock::Error),
+
+        #[error(transparent)]
+        Read(#[from] read::Error),
+    }
+}
+
+/// The header of a bundle.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    pub version: u8,
+    /// The namespace the bundle is of.
+    pub urn: Urn,
+    /// The peer which exported the bundle, and signed its refs.
+    pub peer: PeerId,
+    /// The refs of `peer`, eg. `refs/heads/main`, including
+    /// `refs/rad/signed_refs`.
+    pub refs: BTreeMap<String, ext::Oid>,
+    /// The [`Manifest::id`] of the manifest this bundle is incremental to.
+    pub base: Option<ext::Oid>,
+    /// Objects which were omitted from the pack, because they were recorded
+    /// in the base manifest.
+    pub prerequisites: BTreeSet<ext::Oid>,
+    /// Number of objects in the pack.
+    pub objects: usize,
+}
+
+impl Manifest {
+    /// The hash identifying this manifest.
+    pub fn id(&self) -> Result<ext::Oid, serde_json::Error> {
+        let json = serde_json::to_vec(self)?;
+        Ok(git2::Oid::hash_object(git2::ObjectType::Blob, &json)
+            .expect("hashing in-memory data can't fail")
+            .into())
+    }
+}
+
+/// The outcome of [`Storage::import_bundle`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Imported {
+    pub manifest: Manifest,
+    /// Whether the namespace was created by the import.
+    pub created: bool,
+}
+
+/// The fully qualified refs recorded in `refs`.
+fn qualified(refs: &Refs) -> BTreeMap<String, ext::Oid> {
+    refs.categorised_refs
+        .iter()
+        .flat_map(|(category, refs)| {
+            refs.iter()
+                .map(move |(name, oid)| (format!("refs/{}/{}", category, name), *oid))
+        })
+        .collect()
+}
+
+impl ReadOnly {
+    /// Write a bundle of the refs of the local peer in the namespace `urn`
+    /// to `out`.
+    ///
+    /// If `base` is given, the bundle is incremental to it, and can only be
+    /// imported to a storage which contains the objects of `base`.
+    pub fn export_bundle<W>(
+        &self,
+        urn: &Urn,
+        base: Option<&Manifest>,
+        mut out: W,
+    ) -> Result<Manifest, error::Export>
+    where
+        W: Write,
+    {
+        let urn = urn.clone().with_path(None);
+        if let Some(base) = base {
+            if base.urn != urn {
+                return Err(error::Export::BaseMismatch {
+                    urn,
+                    base: base.urn.clone(),
+                });
+            }
+        }
+
+        let loaded = refs::load(self, &urn, None)?
+            .ok_or_else(|| error::Export::NoSignedRefs(urn.clone()))?;
+        let mut bundled = qualified(&loaded.refs);
+        bundled.insert(SIGNED_REFS.to_owned(), loaded.at);
+
+        let odb = self.backend.odb()?;
+        let prerequisites = base
+            .map(|base| {
+                base.refs
+                    .values()
+                    .filter(|oid| odb.exists(***oid))
+                    .copied()
+                    .collect::<BTreeSet<_>>()
+            })
+            .unwrap_or_default();
+
+        let mut walk = self.backend.revwalk()?;
+        let mut others = Vec::new();
+        for oid in bundled.values() {
+            match odb.read_header(**oid)?.1 {
+                git2::ObjectType::Commit => walk.push(**oid)?,
+                _ => others.push(**oid),
+            }
+        }
+        for oid in &prerequisites {
+            if odb.read_header(**oid)?.1 == git2::ObjectType::Commit {
+                walk.hide(**oid)?;
+            }
+        }
+
+        let mut pack = self.backend.packbuilder()?;
+        pack.insert_walk(&mut walk)?;
+        for oid in others {
+            if !prerequisites.contains(&ext::Oid::from(oid)) {
+                pack.insert_recursive(oid, None)?;
+            }
+        }
+
+        let manifest = Manifest {
+            version: VERSION,
+            urn,
+            peer: *self.peer_id(),
+            refs: bundled,
+            base: base.map(Manifest::id).transpose()?,
+            prerequisites,
+            objects: pack.object_count(),
+        };
+        serde_json::to_writer(&mut out, &manifest)?;
+        out.write_all(b"\n")?;
+
+        let mut res = Ok(());
+        pack.foreach(|chunk| match out.write_all(chunk) {
+            Ok(()) => true,
+            Err(e) => {
+                res = Err(e);
+                false
+            },
+        })?;
+        res?;
+        out.flush()?;
+
+        Ok(manifest)
+    }
+}
+
+impl Storage {
+    /// Import a bundle written by [`ReadOnly::export_bundle`] on another
+    /// peer.
+    ///
+    /// The objects are imported first, but no ref is updated unless the
+    /// signed refs of the exporting peer and the identity verify. The remote
+    /// refs of the exporting peer are then reset to the ones in the bundle in
+    /// a single transaction, unless they are newer than the bundle.
+    pub fn import_bundle<R>(&self, mut input: R) -> Result<Imported, error::Import>
+    where
+        R: BufRead,
+    {
+        let mut header = String::new();
+        input.read_line(&mut header)?;
+        let manifest: Manifest = serde_json::from_str(&header)?;
+        if manifest.version != VERSION {
+            return Err(error::Import::Version(manifest.version));
+        }
+        if &manifest.peer == self.peer_id() {
+            return Err(error::Import::OwnBundle(manifest.peer));
+        }
+
+        let repo = self.as_raw();
+        let odb = repo.odb()?;
+        if let Some(missing) = manifest
+            .prerequisites
+            .iter()
+            .find(|oid| !odb.exists(***oid))
+        {
+            return Err(error::Import::MissingPrerequisite(*missing));
+        }
+        if manifest.objects > 0 {
+            let mut writer = odb.packwriter()?;
+            io::copy(&mut input, &mut writer)?;
+            writer.commit()?;
+        }
+        if let Some(missing) = manifest.refs.values().find(|oid| !odb.exists(***oid)) {
+            return Err(error::Import::MissingObject(*missing));
+        }
+
+        let signed_refs = *manifest
+            .refs
+            .get(SIGNED_REFS)
+            .ok_or(error::Import::NoSignedRefs)?;
+        let loaded = refs::load_at(self, signed_refs, Some(&manifest.peer))?
+            .ok_or(error::Import::NoSignedRefs)?;
+        let signed = qualified(&loaded.refs);
+        if let Some(name) = manifest
+            .refs
+            .iter()
+            .find(|(name, oid)| name.as_str() != SIGNED_REFS && signed.get(*name) != Some(oid))
+            .map(|(name, _)| name)
+        {
+            return Err(error::Import::Unsigned(name.clone()));
+        }
+
+        let verified = self.verify_bundled(&manifest)?;
+
+        let lock = self.lock_namespace(&manifest.urn)?;
+
+        let prefix = format!(
+            "{}refs/remotes/{}/",
+            namespace_prefix(&manifest.urn),
+            manifest.peer
+        );
+        let remote_signed_refs = format!("{}rad/signed_refs", prefix);
+        if let Ok(existing) = repo.refname_to_id(&remote_signed_refs) {
+            if existing != *signed_refs && repo.graph_descendant_of(existing, *signed_refs)? {
+                return Err(error::Import::Stale(manifest.peer));
+            }
+        }
+
+        let mut existing = Vec::new();
+        for r in repo.references_glob(&format!("{}*", prefix))? {
+            if let Some(name) = r?.name() {
+                existing.push(name.to_owned());
+            }
+        }
+
+        let message = Message::new(Op::Replicate, "import bundle")
+            .with_peer(manifest.peer)
+            .to_string();
+        let mut tx = repo.transaction()?;
+        for name in &existing {
+            tx.lock_ref(name)?;
+        }
+        for (name, oid) in &manifest.refs {
+            let name = format!("{}{}", prefix, name.strip_prefix("refs/").unwrap_or(name));
+            if !existing.contains(&name) {
+                tx.lock_ref(&name)?;
+            }
+            tx.set_target(&name, **oid, None, &message)?;
+        }
+        for name in existing {
+            let rel = name.strip_prefix(&prefix).unwrap_or(&name);
+            if !manifest.refs.contains_key(&format!("refs/{}", rel)) {
+                tx.remove(&name)?;
+            }
+        }
+        lock.check()?;
+        tx.commit()?;
+        drop(lock);
+
+        let created = !self.has_urn(&manifest.urn)?;
+        if created {
+            IdRef::from(&manifest.urn).create(self, verified)?;
+        }
+        if let Err(e) = tracking::track(
+            self,
+            &manifest.urn,
+            Some(manifest.peer),
+            tracking::Config::default(),
+            tracking::policy::Track::MustNotExist,
+        )? {
+            tracing::trace!(peer = %manifest.peer, err = %e, "peer already tracked");
+        }
+        Refs::update(self, &manifest.urn)?;
+
+        self.audit(audit::Event::BundleImported {
+            urn: manifest.urn.clone(),
+            peer: manifest.peer,
+            manifest: manifest.id()?,
+        });
+
+        Ok(Imported { manifest, created })
+    }
+
+    /// Verify the identity in the bundle, creating the identities of its
+    /// delegates which don't exist locally yet.
+    ///
+    /// Returns the most recent verified revision of the identity.
+    fn verify_bundled(&self, manifest: &Manifest) -> Result<ext::Oid, error::Import> {
+        let head = *manifest.refs.get(RAD_ID).ok_or(error::Import::NoIdentity)?;
+        let identity = self
+            .read_only()
+            .identities::<Person>()
+            .some_identity(*head)
+            .map_err(|e| error::Import::Verify(e.into()))?;
+        if identity.urn() != manifest.urn {
+            return Err(error::Import::UrnMismatch {
+                expected: manifest.urn.clone(),
+                found: identity.urn(),
+            });
+        }
+
+        let mut delegates = Vec::new();
+        for (name, oid) in manifest.refs.range(RAD_IDS.to_owned()..) {
+            if !name.starts_with(RAD_IDS) {
+                break;
+            }
+            let person = self.verify_person(*oid)?;
+            delegates.push((person.urn(), person.content_id));
+        }
+
+        let content_id = match identity {
+            SomeIdentity::Project(_) => self.verify_project(head, &delegates)?,
+            _ => self.verify_person(head)?.content_id,
+        };
+
+        for (urn, content_id) in delegates {
+            if !self.has_urn(&urn)? {
+                IdRef::from(&urn).create(self, content_id)?;
+            }
+        }
+
+        Ok(content_id)
+    }
+
+    fn verify_person(&self, head: ext::Oid) -> Result<VerifiedPerson, error::Import> {
+        self.read_only()
+            .identities::<Person>()
+            .verify(*head)
+            .map_err(|e| error::Import::Verify(e.into()))
+    }
+
+    fn verify_project(
+        &self,
+        head: ext::Oid,
+        delegates: &[(Urn, ext::Oid)],
+    ) -> Result<ext::Oid, error::Import> {
+        self.read_only()
+            .identities::<Project>()
+            .verify(*head, |urn| {
+                delegates
+                    .iter()
+                    .find(|(delegate, _)| delegate == &urn)
+                    .map(|(_, oid)| **oid)
+                    .ok_or(error::MissingDelegate(urn))
+            })
+            .map(|project| project.content_id)
+            .map_err(|e| error::Import::Verify(e.into()))
+    }
+}
@@ -5,15 +5,19 @@
 
 #![allow(unused)]
 
-use std::{convert::TryFrom, io, marker::PhantomData, path::PathBuf};
+use std::{collections::BTreeMap, convert::TryFrom, io, marker::PhantomData, path::PathBuf};
 
 use crypto::BoxedSigner;
 use git_ext::{self as ext, is_not_found_err};
 use std_ext::prelude::*;
 use thiserror::Error;
 
-use super::{super::identities::local::LocalIdentity, Storage};
+use super::{super::identities::local::LocalIdentity, lease::Locking, Storage};
 use crate::{
+    git::{
+        alias::{self, Alias},
+        tracking::contacts::{self, Contact},
+    },
     identities::{
         git::{Identities, Urn, VerifiedPerson},
         urn,
@@ -27,6 +31,10 @@ const CONFIG_USER_NAME: &str = "user.name";
 const CONFIG_USER_EMAIL: &str = "user.email";
 const CONFIG_RAD_SELF: &str = "rad.self";
 const CONFIG_RAD_PEER_ID: &str = "rad.peerid";
+const CONFIG_RAD_LOCKING: &str = "rad.locking";
+const CONFIG_RAD_ALIAS: &str = "rad.alias";
+const CONFIG_RAD_CONTACT: &str = "rad.contact";
+const CONFIG_RAD_SUBKEY: &str = "rad.subkey";
 
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -40,6 +48,15 @@ pub enum Error {
     #[error(transparent)]
     Urn(#[from] urn::error::FromStr<ext::oid::FromMultihashError>),
 
+    #[error("invalid `rad.locking`: {0}")]
+    Locking(String),
+
+    #[error(transparent)]
+    Alias(#[from] alias::error::Alias),
+
+    #[error(transparent)]
+    Trust(#[from] contacts::error::Trust),
+
     #[error(transparent)]
     Git(#[from] git2::Error),
 }
@@ -174,6 +191,78 @@ where
         }
     }
 
+    /// Set the [`Locking`] strategy, which takes effect the next time the
+    /// storage is opened.
+    pub fn set_locking(&mut self, locking: Locking) -> Result<(), Error> {
+        self.inner
+            .set_str(CONFIG_RAD_LOCKING, &locking.to_string())
+            .map_err(Error::from)
+    }
+
+    /// Bind `alias` to `urn`, replacing any previous binding.
+    ///
+    /// See [`crate::git::alias::set`] for a version which guards against
+    /// collisions.
+    pub fn set_alias(&mut self, alias: &Alias, urn: &Urn) -> Result<(), Error> {
+        self.inner
+            .set_str(&alias_key(alias), &urn.to_string())
+            .map_err(Error::from)
+    }
+
+    /// Remove `alias`, if it exists.
+    pub fn remove_alias(&mut self, alias: &Alias) -> Result<(), Error> {
+        self.inner
+            .remove(&alias_key(alias))
+            .or_matches::<Error, _, _>(is_not_found_err, || Ok(()))
+    }
+
+    /// Set the [`Contact`] of `peer`, replacing any previous one.
+    ///
+    /// See [`crate::git::tracking::contacts::set`].
+    pub fn set_contact(&mut self, peer: &PeerId, contact: &Contact) -> Result<(), Error> {
+        let fields = [
+            ("alias", contact.alias.clone()),
+            ("note", contact.note.clone()),
+            ("trust", Some(contact.trust.to_string())),
+        ];
+        for (field, value) in fields {
+            let key = contact_key(peer, field);
+            match value {
+                Some(value) => self.inner.set_str(&key, &value)?,
+                None => self
+                    .inner
+                    .remove(&key)
+                    .or_matches::<Error, _, _>(is_not_found_err, || Ok(()))?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the [`Contact`] of `peer`, if it exists.
+    pub fn remove_contact(&mut self, peer: &PeerId) -> Result<(), Error> {
+        for field in ["alias", "note", "trust"] {
+            self.inner
+                .remove(&contact_key(peer, field))
+                .or_matches::<Error, _, _>(is_not_found_err, || Ok(()))?;
+        }
+        Ok(())
+    }
+
+    /// Record that `peer` is tracked for `urn` because `person` granted it a
+    /// sub-key, see [`crate::identities::subkey`].
+    pub fn set_subkey(&mut self, urn: &Urn, peer: &PeerId, person: &Urn) -> Result<(), Error> {
+        self.inner
+            .set_str(&subkey_key(urn, peer), &person.to_string())
+            .map_err(Error::from)
+    }
+
+    /// Remove the record of `peer` as a sub-key for `urn`, if it exists.
+    pub fn remove_subkey(&mut self, urn: &Urn, peer: &PeerId) -> Result<(), Error> {
+        self.inner
+            .remove(&subkey_key(urn, peer))
+            .or_matches::<Error, _, _>(is_not_found_err, || Ok(()))
+    }
+
     pub(crate) fn as_raw(&self) -> &git2::Config {
         &self.inner
     }
@@ -208,6 +297,126 @@ impl<S> Config<'_, S> {
             .map(|urn| urn.parse().map_err(Error::from))
             .transpose()
     }
+
+    /// The configured [`Locking`] strategy, [`Locking::Auto`] if not set.
+    pub fn locking(&self) -> Result<Locking, Error> {
+        self.inner
+            .get_string(CONFIG_RAD_LOCKING)
+            .map(Some)
+            .or_matches::<Error, _, _>(is_not_found_err, || Ok(None))?
+            .map(|s| s.parse().map_err(Error::Locking))
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+}
+
+impl<S> Config<'_, S> {
+    /// The URN `alias` is bound to, if any.
+    pub fn alias(&self, alias: &Alias) -> Result<Option<Urn>, Error> {
+        self.inner
+            .get_string(&alias_key(alias))
+            .map(Some)
+            .or_matches::<Error, _, _>(is_not_found_err, || Ok(None))?
+            .map(|urn| urn.parse().map_err(Error::from))
+            .transpose()
+    }
+
+    /// All aliases, sorted by name.
+    pub fn aliases(&self) -> Result<Vec<(Alias, Urn)>, Error> {
+        let prefix = format!("{}.", CONFIG_RAD_ALIAS);
+        // Later entries take precedence, as with `git config --get`
+        let mut aliases = BTreeMap::new();
+        for entry in &self.inner.entries(Some(r"^rad\.alias\."))? {
+            let entry = entry?;
+            let (name, value) = match (entry.name(), entry.value()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => continue,
+            };
+            if let Some(alias) = name.strip_prefix(&prefix) {
+                aliases.insert(alias.parse()?, value.parse()?);
+            }
+        }
+
+        Ok(aliases.into_iter().collect())
+    }
+}
+
+fn alias_key(alias: &Alias) -> String {
+    format!("{}.{}", CONFIG_RAD_ALIAS, alias)
+}
+
+impl<S> Config<'_, S> {
+    /// The [`Contact`] of `peer`, if any.
+    pub fn contact(&self, peer: &PeerId) -> Result<Option<Contact>, Error> {
+        Ok(self.contacts()?.remove(peer))
+    }
+
+    /// All contacts.
+    pub fn contacts(&self) -> Result<BTreeMap<PeerId, Contact>, Error> {
+        let prefix = format!("{}.", CONFIG_RAD_CONTACT);
+        let mut contacts = BTreeMap::<PeerId, Contact>::new();
+        for entry in &self.inner.entries(Some(r"^rad\.contact\."))? {
+            let entry = entry?;
+            let (name, value) = match (entry.name(), entry.value()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => continue,
+            };
+            let (peer, field) = match name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.rsplit_once('.'))
+            {
+                Some(x) => x,
+                None => continue,
+            };
+            let contact = contacts.entry(peer.parse()?).or_default();
+            match field {
+                "alias" => contact.alias = Some(value.to_owned()),
+                "note" => contact.note = Some(value.to_owned()),
+                "trust" => contact.trust = value.parse()?,
+                _ => {},
+            }
+        }
+
+        Ok(contacts)
+    }
+}
+
+fn contact_key(peer: &PeerId, field: &str) -> String {
+    format!("{}.{}.{}", CONFIG_RAD_CONTACT, peer, field)
+}
+
+impl<S> Config<'_, S> {
+    /// The peers which were tracked for `urn` as sub-keys, along with the
+    /// person which granted them.
+    ///
+    /// Records are kept after a grant is revoked, so that replication does
+    /// not track the peer again via the tracking graph of the delegates.
+    pub fn subkeys(&self, urn: &Urn) -> Result<BTreeMap<PeerId, Urn>, Error> {
+        let prefix = format!("{}.{}.", CONFIG_RAD_SUBKEY, urn.encode_id());
+        let mut subkeys = BTreeMap::new();
+        for entry in &self
+            .inner
+            .entries(Some(&format!(r"^rad\.subkey\.{}\.", urn.encode_id())))?
+        {
+            let entry = entry?;
+            let (name, value) = match (entry.name(), entry.value()) {
+                (Some(name), Some(value)) => (name, value),
+                _ => continue,
+            };
+            if let Some(peer) = name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(".person"))
+            {
+                subkeys.insert(peer.parse()?, value.parse()?);
+            }
+        }
+
+        Ok(subkeys)
+    }
+}
+
+fn subkey_key(urn: &Urn, peer: &PeerId) -> String {
+    format!("{}.{}.{}.person", CONFIG_RAD_SUBKEY, urn.encode_id(), peer)
 }
 
 impl Config<'_, PhantomData<Void>> {
@@ -0,0 +1,280 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Encryption of the monorepo at rest.
+//!
+//! Neither `libgit2` nor the packfile machinery allow to plug in an object
+//! store which encrypts objects and refs individually. Instead, the monorepo
+//! can be kept on an encrypted filesystem layer, such as [gocryptfs], which
+//! stores the encrypted data in a separate directory, and mounts a decrypted
+//! view of it at [`Paths::git_dir`] while it is unlocked.
+//!
+//! The layer is [`setup`] once, when the profile is created, and [`unlock`]ed
+//! before the storage is opened, eg. when the daemon starts. The passphrase
+//! handed to the layer is a [`Key`] derived from the profile key, so no
+//! additional secret needs to be remembered or stored: whoever can sign on
+//! behalf of the profile can unlock its storage. Opening a [`super::Storage`]
+//! or [`super::ReadOnly`] while the storage is [`Status::Locked`] is refused,
+//! so that nothing is ever written unencrypted to the mount point.
+//!
+//! [gocryptfs]: https://nuetzlich.net/gocryptfs/
+
+use std::{
+    fs,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use crate::{paths::Paths, SecStr, Signer};
+
+/// Name of the file recording the [`Config`], in [`Paths::keys_dir`].
+pub const CONFIG_FILE_NAME: &str = "storage-encryption.json";
+
+/// Domain separator for [`derive_key`].
+const KEY_CONTEXT: &[u8] = b"radicle-link storage encryption v1";
+
+pub mod error {
+    use std::{io, path::PathBuf, process::ExitStatus};
+
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Error {
+        #[error("storage encryption is already configured")]
+        AlreadyConfigured,
+
+        #[error("`{0}` is not empty, refusing to set up encryption on top of it")]
+        NotEmpty(PathBuf),
+
+        #[error("`{program}` exited with {status}")]
+        Command { program: String, status: ExitStatus },
+
+        #[error("`{0}` was not mounted by the unlock command")]
+        NotMounted(PathBuf),
+
+        #[error("invalid storage encryption config")]
+        Config(#[from] serde_json::Error),
+
+        #[error("error deriving the storage key")]
+        Sign(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+pub use error::Error;
+
+/// How to set up, mount and unmount the encrypted filesystem layer.
+///
+/// The commands are given as the program followed by its arguments, in
+/// which `{cipher_dir}` and `{mount_point}` are substituted by
+/// [`Config::cipher_dir`] and [`Paths::git_dir`] respectively. The
+/// passphrase is written to the standard input of `init` and `mount`,
+/// followed by a newline.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Config {
+    /// The directory holding the encrypted data.
+    pub cipher_dir: PathBuf,
+    /// Initialise [`Config::cipher_dir`], run once by [`setup`].
+    pub init: Vec<String>,
+    /// Mount the decrypted view of [`Config::cipher_dir`].
+    pub mount: Vec<String>,
+    /// Unmount it again.
+    pub unmount: Vec<String>,
+}
+
+impl Config {
+    /// Use [gocryptfs](https://nuetzlich.net/gocryptfs/), keeping the
+    /// encrypted data in `cipher_dir`.
+    pub fn gocryptfs(cipher_dir: impl Into<PathBuf>) -> Self {
+        let args = |args: &[&str]| args.iter().map(|s| s.to_string()).collect();
+        Self {
+            cipher_dir: cipher_dir.into(),
+            init: args(&["gocryptfs", "-init", "-q", "{cipher_dir}"]),
+            mount: args(&["gocryptfs", "-q", "{cipher_dir}", "{mount_point}"]),
+            unmount: args(&["fusermount", "-u", "{mount_point}"]),
+        }
+    }
+}
+
+/// The passphrase of the encrypted filesystem layer.
+pub struct Key(SecStr);
+
+impl Key {
+    fn passphrase(&self) -> String {
+        self.0
+            .unsecure()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// Derive the [`Key`] from the profile key, by hashing the signature of a
+/// fixed message.
+///
+/// Ed25519 signatures are deterministic, so this yields the same key every
+/// time, regardless of whether the signer is backed by a file or an
+/// `ssh-agent`.
+pub fn derive_key<S>(signer: &S) -> Result<Key, Error>
+where
+    S: Signer,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    let signature = signer
+        .sign_blocking(KEY_CONTEXT)
+        .map_err(|e| Error::Sign(Box::new(e)))?;
+    let key = Sha256::digest(&signature.0);
+    Ok(Key(SecStr::new(key.to_vec())))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// Encryption is not configured.
+    Unencrypted,
+    /// Encryption is configured, but the decrypted view is not mounted.
+    Locked,
+    /// The decrypted view is mounted at [`Paths::git_dir`].
+    Unlocked,
+}
+
+/// The storage encryption [`Config`] of the profile, if any.
+pub fn config(paths: &Paths) -> Result<Option<Config>, Error> {
+    match fs::read(config_path(paths)) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn status(paths: &Paths) -> Result<Status, Error> {
+    if config(paths)?.is_none() {
+        Ok(Status::Unencrypted)
+    } else if is_mounted(paths.git_dir())? {
+        Ok(Status::Unlocked)
+    } else {
+        Ok(Status::Locked)
+    }
+}
+
+/// Set up encryption of the storage, and [`unlock`] it.
+///
+/// This must be done before the storage is initialised, ie. while
+/// [`Paths::git_dir`] is still empty.
+pub fn setup(paths: &Paths, config: Config, key: &Key) -> Result<(), Error> {
+    if self::config(paths)?.is_some() {
+        return Err(Error::AlreadyConfigured);
+    }
+    ensure_empty(paths.git_dir())?;
+
+    fs::create_dir_all(&config.cipher_dir)?;
+    run(&config.init, &config, paths, Some(key))?;
+    fs::write(config_path(paths), serde_json::to_vec_pretty(&config)?)?;
+
+    unlock(paths, key)
+}
+
+/// Mount the decrypted view of the storage, unless it is already mounted.
+///
+/// Does nothing if encryption is not configured.
+pub fn unlock(paths: &Paths, key: &Key) -> Result<(), Error> {
+    let config = match config(paths)? {
+        None => return Ok(()),
+        Some(config) => config,
+    };
+    let mount_point = paths.git_dir();
+    if is_mounted(mount_point)? {
+        return Ok(());
+    }
+    ensure_empty(mount_point)?;
+
+    run(&config.mount, &config, paths, Some(key))?;
+    if !is_mounted(mount_point)? {
+        return Err(Error::NotMounted(mount_point.to_path_buf()));
+    }
+
+    Ok(())
+}
+
+/// Unmount the decrypted view of the storage, if it is mounted.
+pub fn lock(paths: &Paths) -> Result<(), Error> {
+    match config(paths)? {
+        Some(config) if is_mounted(paths.git_dir())? => run(&config.unmount, &config, paths, None),
+        _ => Ok(()),
+    }
+}
+
+/// The default location of [`Config::cipher_dir`], next to
+/// [`Paths::git_dir`].
+pub fn default_cipher_dir(paths: &Paths) -> PathBuf {
+    paths.git_dir().with_file_name("git-encrypted")
+}
+
+fn config_path(paths: &Paths) -> PathBuf {
+    paths.keys_dir().join(CONFIG_FILE_NAME)
+}
+
+fn ensure_empty(dir: &Path) -> Result<(), Error> {
+    if fs::read_dir(dir)?.next().is_some() {
+        return Err(Error::NotEmpty(dir.to_path_buf()));
+    }
+    Ok(())
+}
+
+fn run(cmd: &[String], config: &Config, paths: &Paths, key: Option<&Key>) -> Result<(), Error> {
+    let cipher_dir = config.cipher_dir.to_string_lossy();
+    let mount_point = paths.git_dir().to_string_lossy();
+    let mut args = cmd.iter().map(|arg| {
+        arg.replace("{cipher_dir}", &cipher_dir)
+            .replace("{mount_point}", &mount_point)
+    });
+    let program = args.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "empty storage encryption command",
+        )
+    })?;
+
+    let mut child = Command::new(&program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    {
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        if let Some(key) = key {
+            writeln!(stdin, "{}", key.passphrase())?;
+        }
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(Error::Command { program, status });
+    }
+
+    Ok(())
+}
+
+/// Whether a filesystem is mounted at `path`, ie. it resides on a different
+/// device than its parent.
+#[cfg(unix)]
+fn is_mounted(path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt as _;
+
+    let parent = match path.parent() {
+        None => return Ok(true),
+        Some(parent) => parent,
+    };
+    Ok(fs::metadata(path)?.dev() != fs::metadata(parent)?.dev())
+}
+
+#[cfg(not(unix))]
+fn is_mounted(_: &Path) -> io::Result<bool> {
+    Ok(false)
+}
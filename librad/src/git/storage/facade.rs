@@ -0,0 +1,169 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Async access to pooled [`Storage`].
+//!
+//! All operations on [`Storage`] are blocking, and must not be run on the
+//! async executor. [`AsyncStorage`] borrows a [`Storage`] from a pool and runs
+//! a computation on it on the blocking thread pool of a [`Spawner`], so that
+//! callers don't have to remember to do so.
+//!
+//! The number of computations running concurrently is bounded by
+//! [`Config::max_concurrency`]: the blocking thread pool grows on demand, so
+//! without a bound a burst of requests could spawn a large number of threads
+//! all contending for the same repository. Every computation runs in a
+//! `storage` span carrying the name of the operation, and computations taking
+//! longer than [`Config::slow`] are logged.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use async_lock::Semaphore;
+use link_async::Spawner;
+
+use super::{PoolError, Pooled, ReadOnly, Storage};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Maximum number of computations running concurrently.
+    pub max_concurrency: usize,
+    /// Computations taking longer than this are logged.
+    pub slow: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 2 * num_cpus::get_physical(),
+            slow: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Snapshot of the activity of an [`AsyncStorage`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Computations currently running.
+    pub running: usize,
+    /// Computations waiting for a slot.
+    pub waiting: usize,
+    /// Computations completed since startup.
+    pub completed: u64,
+    /// Completed computations which took longer than [`Config::slow`].
+    pub slow: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    running: AtomicUsize,
+    waiting: AtomicUsize,
+    completed: AtomicU64,
+    slow: AtomicU64,
+}
+
+/// Increments a counter while alive, so that it is decremented again also if
+/// the computation is cancelled.
+struct Gauge<'a>(&'a AtomicUsize);
+
+impl<'a> Gauge<'a> {
+    fn inc(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for Gauge<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Relaxed);
+    }
+}
+
+/// Runs computations on pooled [`Storage`] without blocking the executor.
+#[derive(Clone)]
+pub struct AsyncStorage<P> {
+    pool: P,
+    spawner: Arc<Spawner>,
+    slots: Arc<Semaphore>,
+    config: Config,
+    counters: Arc<Counters>,
+}
+
+impl<P> AsyncStorage<P>
+where
+    P: Pooled<Storage> + Send + Sync,
+{
+    pub fn new(pool: P, spawner: Arc<Spawner>, config: Config) -> Self {
+        Self {
+            pool,
+            spawner,
+            slots: Arc::new(Semaphore::new(config.max_concurrency)),
+            config,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    pub fn pool(&self) -> &P {
+        &self.pool
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            running: self.counters.running.load(Relaxed),
+            waiting: self.counters.waiting.load(Relaxed),
+            completed: self.counters.completed.load(Relaxed),
+            slow: self.counters.slow.load(Relaxed),
+        }
+    }
+
+    /// Borrow a [`Storage`] from the pool, and run the blocking computation
+    /// `f` on it.
+    ///
+    /// `op` names the computation in traces and logs.
+    pub async fn with<F, T>(&self, op: &'static str, f: F) -> Result<T, PoolError>
+    where
+        F: FnOnce(&Storage) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let waiting = Gauge::inc(&self.counters.waiting);
+        let slot = self.slots.acquire_arc().await;
+        drop(waiting);
+        let storage = self.pool.get().await?;
+
+        let running = Gauge::inc(&self.counters.running);
+        let span = tracing::debug_span!("storage", op);
+        let started = Instant::now();
+        let res = self
+            .spawner
+            .blocking(move || {
+                let _span = span.enter();
+                f(&storage)
+            })
+            .await;
+        let elapsed = started.elapsed();
+        drop(running);
+        self.counters.completed.fetch_add(1, Relaxed);
+        if elapsed > self.config.slow {
+            self.counters.slow.fetch_add(1, Relaxed);
+            tracing::warn!(op, ?elapsed, "slow storage operation");
+        }
+        drop(slot);
+
+        Ok(res)
+    }
+
+    /// Like [`AsyncStorage::with`], but only requiring read access.
+    pub async fn with_read_only<F, T>(&self, op: &'static str, f: F) -> Result<T, PoolError>
+    where
+        F: FnOnce(&ReadOnly) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        self.with(op, move |storage| f(storage.read_only())).await
+    }
+}
@@ -19,7 +19,11 @@ use rustc_hash::FxHasher;
 use thiserror::Error;
 use url::Url;
 
-use super::{PoolError, Storage};
+use super::{
+    reflog::{Message, Op},
+    PoolError,
+    Storage,
+};
 use crate::{
     git::{
         fetch::{self, FetchResult, Fetchspecs, RemoteHeads},
@@ -499,7 +503,11 @@ mod imp {
                             .download_tags(git2::AutotagOption::None)
                             .remote_callbacks(callbacks),
                     ),
-                    None,
+                    Some(
+                        &Message::new(Op::Replicate, "fetch")
+                            .with_peer(self.info.remote_peer)
+                            .to_string(),
+                    ),
                 );
 
                 if let Some(excessive_transfer_bytes) = excessive_transfer_bytes {
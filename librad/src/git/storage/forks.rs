@@ -0,0 +1,293 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Fork topology of a namespace.
+//!
+//! Every tracked peer may have its own idea of where a branch of a project
+//! is. Listing the remotes of a namespace tells little about how these
+//! relate: are they the same, is one just behind the other, or did they
+//! diverge, and since when? A [`Topology`] answers this per branch, by
+//! grouping the owners of a branch by the [`Head`] they point to, and
+//! recording the [`Relation`] of every pair of distinct heads.
+//!
+//! Computing merge bases is not cheap, but the relation of two commits never
+//! changes. The topology is thus updated incrementally whenever the namespace
+//! is written to (see [`Storage::reindex`]), only computing the relations of
+//! heads not seen by the previous update. It is stored as JSON under
+//! [`DIR_NAME`], relative to the storage directory, one file per namespace
+//! (see [`super::json`]).
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use git_ext as ext;
+
+use super::{json, ReadOnly, Storage};
+use crate::{identities::git::Urn, PeerId};
+
+/// The name of the forks directory, relative to the storage directory.
+pub const DIR_NAME: &str = "forks";
+
+const STORE: json::Store = json::Store::new(DIR_NAME, "fork topology");
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use crate::git::storage::{json, read};
+
+    pub use json::error::Load as Read;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Update {
+        #[error(transparent)]
+        Read(#[from] Read),
+
+        #[error(transparent)]
+        Snapshot(#[from] read::Error),
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Store(#[from] json::error::Store),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+/// Whose version of a branch.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub enum Owner {
+    /// The local peer, ie. `refs/heads`.
+    Local,
+    /// A tracked peer, ie. `refs/remotes/<peer>/heads`.
+    Remote(PeerId),
+}
+
+/// A distinct tip of a branch, and the peers pointing to it.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Head {
+    pub tip: ext::Oid,
+    pub owners: Vec<Owner>,
+}
+
+/// How the history of a head relates to another one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Relation {
+    /// The most recent common ancestor, or `None` if the histories are
+    /// unrelated.
+    pub base: Option<ext::Oid>,
+    /// Number of commits only in the history of the first head.
+    pub ahead: usize,
+    /// Number of commits only in the history of the second head.
+    pub behind: usize,
+}
+
+impl Relation {
+    /// Whether the two heads share any history.
+    pub fn is_related(&self) -> bool {
+        self.base.is_some()
+    }
+
+    /// Whether both heads have commits the other doesn't have, ie. neither
+    /// can be fast-forwarded to the other.
+    pub fn is_diverged(&self) -> bool {
+        self.is_related() && self.ahead > 0 && self.behind > 0
+    }
+}
+
+/// The [`Relation`] between the heads `a` and `b`, from the perspective of
+/// `a`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Divergence {
+    pub a: ext::Oid,
+    pub b: ext::Oid,
+    pub relation: Relation,
+}
+
+/// The fork topology of a single branch.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Branch {
+    /// The distinct heads, ordered by tip.
+    pub heads: Vec<Head>,
+    /// The relation of every pair of distinct heads, where `a < b`.
+    pub divergences: Vec<Divergence>,
+}
+
+impl Branch {
+    /// The head `owner` points to, if any.
+    pub fn head_of(&self, owner: &Owner) -> Option<&Head> {
+        self.heads.iter().find(|head| head.owners.contains(owner))
+    }
+
+    /// The [`Relation`] of the heads `a` and `b`, from the perspective of
+    /// `a`.
+    ///
+    /// Returns `None` if either isn't a head of this branch.
+    pub fn relation(&self, a: ext::Oid, b: ext::Oid) -> Option<Relation> {
+        if a == b {
+            return self
+                .heads
+                .iter()
+                .find(|head| head.tip == a)
+                .map(|_| Relation {
+                    base: Some(a),
+                    ahead: 0,
+                    behind: 0,
+                });
+        }
+        self.divergences.iter().find_map(|d| {
+            if d.a == a && d.b == b {
+                Some(d.relation)
+            } else if d.a == b && d.b == a {
+                Some(Relation {
+                    base: d.relation.base,
+                    ahead: d.relation.behind,
+                    behind: d.relation.ahead,
+                })
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// The fork topology of a namespace.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Topology {
+    /// Seconds since the Unix epoch at which the topology was last updated.
+    pub updated: u64,
+    /// The topology by branch name, eg. `main` for `refs/heads/main`.
+    pub branches: BTreeMap<String, Branch>,
+}
+
+/// Parse a namespace-relative reference name into the branch it is a head
+/// of, and its owner.
+fn branch_of(name: &str) -> Option<(&str, Owner)> {
+    if let Some(branch) = name.strip_prefix("refs/heads/") {
+        return Some((branch, Owner::Local));
+    }
+    let rest = name.strip_prefix("refs/remotes/")?;
+    let (peer, rest) = rest.split_once('/')?;
+    let branch = rest.strip_prefix("heads/")?;
+    Some((branch, Owner::Remote(peer.parse().ok()?)))
+}
+
+impl ReadOnly {
+    /// The fork [`Topology`] of the namespace `urn`, as of the last time it
+    /// was written to.
+    ///
+    /// Returns `None` if no topology was recorded for the namespace.
+    pub fn forks(&self, urn: &Urn) -> Result<Option<Topology>, error::Read> {
+        STORE.load(self.path(), &urn.clone().with_path(None))
+    }
+}
+
+impl Storage {
+    /// Update the fork [`Topology`] of the namespace `urn`.
+    ///
+    /// Returns `None`, and removes any recorded topology, if the namespace
+    /// does not exist.
+    pub fn update_forks(&self, urn: &Urn) -> Result<Option<Topology>, error::Update> {
+        let urn = urn.clone().with_path(None);
+        let snapshot = self.read_only().snapshot(&urn)?;
+        if snapshot.is_empty() {
+            STORE.remove(self.path(), &urn)?;
+            return Ok(None);
+        }
+
+        let known = STORE
+            .load::<Topology>(self.path(), &urn)?
+            .unwrap_or_default()
+            .branches
+            .into_values()
+            .flat_map(|branch| branch.divergences)
+            .map(|d| ((d.a, d.b), d.relation))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut owners = BTreeMap::<&str, BTreeMap<ext::Oid, BTreeSet<Owner>>>::new();
+        for (name, oid) in &snapshot {
+            if let Some((branch, owner)) = branch_of(name.as_str()) {
+                owners
+                    .entry(branch)
+                    .or_default()
+                    .entry(*oid)
+                    .or_default()
+                    .insert(owner);
+            }
+        }
+
+        let mut branches = BTreeMap::new();
+        for (name, heads) in owners {
+            let tips = heads.keys().copied().collect::<Vec<_>>();
+            let mut divergences = Vec::new();
+            for (i, a) in tips.iter().enumerate() {
+                for b in &tips[i + 1..] {
+                    let relation = match known.get(&(*a, *b)) {
+                        Some(relation) => *relation,
+                        None => self.relation(*a, *b)?,
+                    };
+                    divergences.push(Divergence {
+                        a: *a,
+                        b: *b,
+                        relation,
+                    });
+                }
+            }
+            branches.insert(
+                name.to_owned(),
+                Branch {
+                    heads: heads
+                        .into_iter()
+                        .map(|(tip, owners)| Head {
+                            tip,
+                            owners: owners.into_iter().collect(),
+                        })
+                        .collect(),
+                    divergences,
+                },
+            );
+        }
+
+        let topology = Topology {
+            updated: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            branches,
+        };
+        STORE.store(self.path(), &urn, &topology)?;
+
+        Ok(Some(topology))
+    }
+
+    fn relation(&self, a: ext::Oid, b: ext::Oid) -> Result<Relation, git2::Error> {
+        let repo = self.as_raw();
+        match repo.merge_base(*a, *b) {
+            Ok(base) => {
+                let (ahead, behind) = repo.graph_ahead_behind(*a, *b)?;
+                Ok(Relation {
+                    base: Some(base.into()),
+                    ahead,
+                    behind,
+                })
+            },
+            Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(Relation {
+                base: None,
+                ahead: 0,
+                behind: 0,
+            }),
+            Err(e) => Err(e),
+        }
+    }
+}
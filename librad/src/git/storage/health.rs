@@ -0,0 +1,208 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Whether the storage can currently be written to.
+//!
+//! When the disk is full, or the filesystem was remounted read-only, every
+//! fetch fails, often only after having transferred a packfile. Instead of
+//! retrying (and failing) over and over, write failures are
+//! [`Health::observe`]d by the callers of fetches, and once one was due to the
+//! storage, the storage is considered read-only: fetching and other writes are
+//! refused upfront, while reads (eg. serving `upload-pack`, answering gossip
+//! about existing data) continue as before.
+//!
+//! While read-only, [`Health::probe`] should be called periodically. It
+//! attempts a small write to the storage directory, and returns the storage
+//! to writable once it succeeds.
+//!
+//! Every change is reported to the callback given to [`Health::new`], as a
+//! [`Transition`].
+
+use std::{
+    error,
+    fmt,
+    fs,
+    io::{self, Write as _},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+/// The name of the file written by [`Health::probe`], relative to the git
+/// directory.
+pub const PROBE_FILE_NAME: &str = "write-probe";
+
+/// The interval at which a read-only storage should be probed.
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Why the storage cannot be written to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// The disk is full.
+    NoSpace,
+    /// The user's disk quota is exhausted.
+    QuotaExceeded,
+    /// The filesystem is mounted read-only.
+    ReadOnlyFilesystem,
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NoSpace => "no space left on device",
+            Self::QuotaExceeded => "disk quota exceeded",
+            Self::ReadOnlyFilesystem => "read-only file system",
+        })
+    }
+}
+
+impl Reason {
+    #[cfg(unix)]
+    fn from_io(err: &io::Error) -> Option<Self> {
+        match err.raw_os_error()? {
+            libc::ENOSPC => Some(Self::NoSpace),
+            libc::EDQUOT => Some(Self::QuotaExceeded),
+            libc::EROFS => Some(Self::ReadOnlyFilesystem),
+            _ => None,
+        }
+    }
+
+    /// On Windows, [`io::Error::raw_os_error`] is a Win32 error code.
+    #[cfg(windows)]
+    fn from_io(err: &io::Error) -> Option<Self> {
+        match err.raw_os_error()? {
+            win32::ERROR_DISK_FULL | win32::ERROR_HANDLE_DISK_FULL => Some(Self::NoSpace),
+            win32::ERROR_DISK_QUOTA_EXCEEDED => Some(Self::QuotaExceeded),
+            win32::ERROR_WRITE_PROTECT => Some(Self::ReadOnlyFilesystem),
+            _ => None,
+        }
+    }
+}
+
+/// See <https://docs.microsoft.com/en-us/windows/win32/debug/system-error-codes>
+#[cfg(windows)]
+mod win32 {
+    pub const ERROR_WRITE_PROTECT: i32 = 19;
+    pub const ERROR_HANDLE_DISK_FULL: i32 = 39;
+    pub const ERROR_DISK_FULL: i32 = 112;
+    pub const ERROR_DISK_QUOTA_EXCEEDED: i32 = 1295;
+}
+
+/// Determine if `err`, or any of its sources, is a write failure due to the
+/// storage.
+///
+/// Only [`io::Error`]s are inspected, by their OS error code. The messages of
+/// other errors are not, as they may have been relayed from remote peers.
+pub fn write_failure(err: &(dyn error::Error + 'static)) -> Option<Reason> {
+    let mut next = Some(err);
+    while let Some(err) = next {
+        let reason = err.downcast_ref::<io::Error>().and_then(Reason::from_io);
+        if reason.is_some() {
+            return reason;
+        }
+        next = err.source();
+    }
+    None
+}
+
+/// A change of the [`Health`] of the storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transition {
+    /// Writes failed, the storage is read-only from now on.
+    ReadOnly { reason: Reason },
+    /// Writes succeed again, after the storage was read-only for `after`.
+    Writable { after: Duration },
+}
+
+struct Degraded {
+    reason: Reason,
+    since: Instant,
+}
+
+/// Tracks whether the storage can be written to.
+#[derive(Clone)]
+pub struct Health {
+    git_dir: PathBuf,
+    degraded: Arc<Mutex<Option<Degraded>>>,
+    on_transition: Arc<dyn Fn(Transition) + Send + Sync>,
+}
+
+impl Health {
+    pub fn new<F>(git_dir: impl Into<PathBuf>, on_transition: F) -> Self
+    where
+        F: Fn(Transition) + Send + Sync + 'static,
+    {
+        Self {
+            git_dir: git_dir.into(),
+            degraded: Arc::new(Mutex::new(None)),
+            on_transition: Arc::new(on_transition),
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.degraded.lock().is_some()
+    }
+
+    /// Why the storage is read-only, if it is.
+    pub fn reason(&self) -> Option<Reason> {
+        self.degraded.lock().as_ref().map(|d| d.reason)
+    }
+
+    /// Inspect the error of a failed write, and switch to read-only if it was
+    /// due to the storage.
+    ///
+    /// Returns the [`Reason`] if so.
+    pub fn observe(&self, err: &(dyn error::Error + 'static)) -> Option<Reason> {
+        let reason = write_failure(err)?;
+        let entered = {
+            let mut degraded = self.degraded.lock();
+            let entered = degraded.is_none();
+            if entered {
+                *degraded = Some(Degraded {
+                    reason,
+                    since: Instant::now(),
+                });
+            }
+            entered
+        };
+        if entered {
+            tracing::error!(%reason, "storage is not writable, switching to read-only mode");
+            (self.on_transition)(Transition::ReadOnly { reason })
+        }
+        Some(reason)
+    }
+
+    /// Attempt a write to the storage directory, and switch back to writable
+    /// if it succeeds.
+    ///
+    /// This is blocking, and does nothing if the storage is not read-only.
+    pub fn probe(&self) -> io::Result<()> {
+        if !self.is_read_only() {
+            return Ok(());
+        }
+
+        let path = self.git_dir.join(PROBE_FILE_NAME);
+        let res = fs::File::create(&path)
+            .and_then(|mut file| {
+                file.write_all(&[0; 4096])?;
+                file.sync_all()
+            })
+            .and_then(|()| fs::remove_file(&path));
+        if let Err(e) = res {
+            fs::remove_file(&path).ok();
+            return Err(e);
+        }
+
+        let left = self.degraded.lock().take();
+        if let Some(Degraded { since, .. }) = left {
+            let after = since.elapsed();
+            tracing::info!(?after, "storage is writable again, leaving read-only mode");
+            (self.on_transition)(Transition::Writable { after })
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,302 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! History of the reference tips of namespaces.
+//!
+//! Replication overwrites references in place, so the storage alone can only
+//! answer what the state of a namespace is now. For audits, or to reproduce
+//! a past state, the node additionally keeps a reflog-style [`History`] per
+//! namespace: whenever a namespace is updated by replication, the references
+//! which changed since the last recorded state are appended to it.
+//!
+//! The history is stored as newline-delimited JSON under `history/`, relative
+//! to the storage directory. Only changes are recorded, so the size of the
+//! history grows with the number of updates, not with the number of
+//! references. [`History::at`] replays the changes up to a given time.
+//!
+//! To avoid replaying the whole history on every update, the latest tips are
+//! checkpointed next to the log, along with the length of the log they
+//! reflect. Recording an update only replays the entries appended since.
+//!
+//! Note that the history only records references. Objects are retained only
+//! as long as some reference points to them, so the tips returned for a past
+//! point in time may no longer be present in the storage if they were
+//! overwritten by a force-update.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead as _, BufReader, Seek as _, SeekFrom, Write as _},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use git_ext::{self as ext, RefLike};
+
+use super::{snapshot::Snapshot, ReadOnly, Storage};
+use crate::{git::types::Namespace, identities::git::Urn};
+
+/// The name of the history directory, relative to the storage directory.
+pub const DIR_NAME: &str = "history";
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use crate::git::storage::read;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Record {
+        #[error(transparent)]
+        Read(#[from] Read),
+
+        #[error(transparent)]
+        Snapshot(#[from] read::Error),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+
+        #[error(transparent)]
+        Json(#[from] serde_json::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Read {
+        #[error("line {line}: malformed entry")]
+        Malformed {
+            line: usize,
+            #[source]
+            source: serde_json::Error,
+        },
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+/// The reference tips of a namespace, relative to the namespace.
+pub type Tips = BTreeMap<RefLike, ext::Oid>;
+
+/// A recorded update of a namespace.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The references which changed, along with their new target. `None`
+    /// means the reference was removed.
+    pub changes: BTreeMap<RefLike, Option<ext::Oid>>,
+}
+
+impl Entry {
+    fn apply(self, tips: &mut Tips) {
+        for (name, target) in self.changes {
+            match target {
+                Some(oid) => tips.insert(name, oid),
+                None => tips.remove(&name),
+            };
+        }
+    }
+}
+
+/// The tips as of the first `offset` bytes, or `lines` entries, of the log.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Checkpoint {
+    offset: u64,
+    lines: usize,
+    tips: Tips,
+}
+
+/// Handle to the history of a single namespace.
+#[derive(Clone, Debug)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// The history of `urn` in the storage at `storage_path`.
+    pub fn new(storage_path: &Path, urn: &Urn) -> Self {
+        Self {
+            path: storage_path
+                .join(DIR_NAME)
+                .join(format!("{}.log", Namespace::from(urn))),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Iterate over the recorded updates, oldest first.
+    pub fn entries(&self) -> io::Result<impl Iterator<Item = Result<Entry, error::Read>>> {
+        self.entries_from(0, 0)
+    }
+
+    /// Iterate over the updates recorded after the first `offset` bytes,
+    /// which hold `skipped` entries.
+    fn entries_from(
+        &self,
+        offset: u64,
+        skipped: usize,
+    ) -> io::Result<impl Iterator<Item = Result<Entry, error::Read>>> {
+        let lines = match File::open(&self.path) {
+            Ok(mut file) => {
+                file.seek(SeekFrom::Start(offset))?;
+                Some(BufReader::new(file).lines())
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        Ok(lines
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(move |(i, line)| {
+                let line_no = skipped + i + 1;
+                serde_json::from_str(&line?).map_err(|source| error::Read::Malformed {
+                    line: line_no,
+                    source,
+                })
+            }))
+    }
+
+    fn checkpoint_path(&self) -> PathBuf {
+        self.path.with_extension("tips.json")
+    }
+
+    /// Load the checkpoint, and bring it up to date with the log.
+    ///
+    /// A missing, unreadable or stale checkpoint, eg. because the log was
+    /// truncated, is discarded, and the whole log replayed.
+    fn checkpoint(&self) -> Result<Checkpoint, error::Read> {
+        let len = match fs::metadata(&self.path) {
+            Ok(meta) => meta.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Checkpoint::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut checkpoint = fs::read(self.checkpoint_path())
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Checkpoint>(&json).ok())
+            .filter(|checkpoint| checkpoint.offset <= len)
+            .unwrap_or_default();
+        if checkpoint.offset < len {
+            for entry in self.entries_from(checkpoint.offset, checkpoint.lines)? {
+                entry?.apply(&mut checkpoint.tips);
+                checkpoint.lines += 1;
+            }
+            checkpoint.offset = len;
+        }
+        Ok(checkpoint)
+    }
+
+    fn store_checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), error::Record> {
+        let path = self.checkpoint_path();
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(checkpoint)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// The tips of the namespace as of `time`.
+    ///
+    /// Returns an empty map if `time` predates the first recorded update.
+    pub fn at(&self, time: SystemTime) -> Result<Tips, error::Read> {
+        let until = secs(time);
+        let mut tips = Tips::new();
+        for entry in self.entries()? {
+            let entry = entry?;
+            if entry.timestamp > until {
+                break;
+            }
+            entry.apply(&mut tips);
+        }
+        Ok(tips)
+    }
+
+    /// The most recently recorded tips of the namespace.
+    pub fn latest(&self) -> Result<Tips, error::Read> {
+        Ok(self.checkpoint()?.tips)
+    }
+
+    /// Record the state captured by `snapshot` at time `now`.
+    ///
+    /// Returns `None` if nothing changed since the last recorded update.
+    pub fn record(
+        &self,
+        snapshot: &Snapshot,
+        now: SystemTime,
+    ) -> Result<Option<Entry>, error::Record> {
+        let mut checkpoint = self.checkpoint()?;
+        let latest = &checkpoint.tips;
+        let mut changes = BTreeMap::new();
+        for (name, oid) in snapshot {
+            if latest.get(name) != Some(oid) {
+                changes.insert(name.clone(), Some(*oid));
+            }
+        }
+        for name in latest.keys() {
+            if snapshot.get(name).is_none() {
+                changes.insert(name.clone(), None);
+            }
+        }
+        if changes.is_empty() {
+            return Ok(None);
+        }
+
+        let entry = Entry {
+            timestamp: secs(now),
+            changes,
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        fs::create_dir_all(self.path.parent().expect("history paths have a parent"))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(&line)?;
+
+        checkpoint.offset += line.len() as u64;
+        checkpoint.lines += 1;
+        entry.clone().apply(&mut checkpoint.tips);
+        self.store_checkpoint(&checkpoint)?;
+
+        Ok(Some(entry))
+    }
+}
+
+fn secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl ReadOnly {
+    /// The [`History`] of the namespace `urn`.
+    pub fn history(&self, urn: &Urn) -> History {
+        History::new(self.path(), &urn.clone().with_path(None))
+    }
+
+    /// The tips of the namespace `urn` as of `time`, as far as they were
+    /// recorded by [`Storage::record_history`].
+    pub fn tips_at(&self, urn: &Urn, time: SystemTime) -> Result<Tips, error::Read> {
+        self.history(urn).at(time)
+    }
+}
+
+impl Storage {
+    /// Record the current tips of the namespace `urn` in its [`History`].
+    ///
+    /// Callers are expected to hold the namespace lock, so that concurrent
+    /// updates are not interleaved.
+    pub fn record_history(&self, urn: &Urn) -> Result<Option<Entry>, error::Record> {
+        let snapshot = self.read_only().snapshot(urn)?;
+        self.read_only()
+            .history(urn)
+            .record(&snapshot, SystemTime::now())
+    }
+}
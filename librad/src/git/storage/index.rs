@@ -0,0 +1,477 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Index of the namespaces in the storage.
+//!
+//! Enumerating namespaces by listing `refs/namespaces` takes time proportional
+//! to the number of references in the monorepo, which is too slow for
+//! interactive use on seeds with many projects. The [`Index`] instead records
+//! an [`Entry`] per namespace whenever its `rad/signed_refs` or its tracking
+//! configuration are written, and answers [`Index::list`] from memory.
+//!
+//! The index is stored as newline-delimited JSON at [`FILE_NAME`], relative to
+//! the storage directory, where later lines supersede earlier ones for the
+//! same URN. It is compacted once it holds more than twice as many lines as
+//! there are namespaces. Like the per-namespace [`super::lock`]s, the loaded
+//! index is shared between all [`Index`] handles of the process referring to
+//! the same storage, and only lines appended since the last access are read.
+//!
+//! Updates are best-effort: an update which failed to be recorded is corrected
+//! by the next write to the same namespace, or by [`Index::rebuild`]. If the
+//! index does not exist yet, [`list`] builds it from the storage contents.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{self, Read as _, Seek as _, SeekFrom, Write as _},
+    ops::Bound,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use git_ext::RefLike;
+use parking_lot::Mutex;
+
+use super::{glob, snapshot, ReadOnly, ReadOnlyStorage as _};
+use crate::{git::tracking, identities::git::Urn};
+
+/// The name of the index file, relative to the storage directory.
+pub const FILE_NAME: &str = "namespaces.idx";
+
+/// Compaction is not worth it below this number of lines.
+const MIN_COMPACT_LINES: usize = 1024;
+
+lazy_static! {
+    static ref REGISTRY: Mutex<HashMap<PathBuf, Weak<Inner>>> = Mutex::new(HashMap::new());
+}
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use crate::git::{storage::read, tracking};
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Read {
+        #[error("line {line}: malformed entry")]
+        Malformed {
+            line: usize,
+            #[source]
+            source: serde_json::Error,
+        },
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Write {
+        #[error(transparent)]
+        Read(#[from] Read),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+
+        #[error(transparent)]
+        Json(#[from] serde_json::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Update {
+        #[error(transparent)]
+        Storage(#[from] read::Error),
+
+        #[error(transparent)]
+        Tracking(#[from] tracking::error::TrackedPeers),
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Write(#[from] Write),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum List {
+        #[error(transparent)]
+        Read(#[from] Read),
+
+        #[error("failed to build the namespace index")]
+        Rebuild(#[from] Update),
+    }
+}
+
+/// The indexed state of a namespace.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    pub urn: Urn,
+    /// Seconds since the Unix epoch at which the namespace was last written.
+    pub updated: u64,
+    /// Whether `rad/id` exists in the namespace.
+    pub has_identity: bool,
+    /// Whether any peer is tracked for the namespace.
+    pub tracked: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Line {
+    Put(Entry),
+    Remove { urn: Urn },
+}
+
+/// Criteria which [`Entry`]s must satisfy to be returned by [`Index::list`].
+///
+/// The default filter matches all entries.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// Only namespaces for which some peer is tracked.
+    pub tracked_only: bool,
+    /// Only namespaces which have a `rad/id`.
+    pub has_identity: bool,
+    /// Only namespaces written to at or after this time.
+    pub updated_since: Option<SystemTime>,
+}
+
+impl Filter {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        (!self.tracked_only || entry.tracked)
+            && (!self.has_identity || entry.has_identity)
+            && self
+                .updated_since
+                .map(|since| entry.updated >= secs(since))
+                .unwrap_or(true)
+    }
+}
+
+/// A page of [`Entry`]s, in [`Urn`] order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Page {
+    pub entries: Vec<Entry>,
+    /// If there are more matching entries, the [`Urn`] to pass as `after`
+    /// to obtain the next page.
+    pub next: Option<Urn>,
+}
+
+#[derive(Default)]
+struct Loaded {
+    entries: BTreeMap<Urn, Entry>,
+    /// Bytes of the file consumed so far.
+    offset: u64,
+    /// Lines of the file consumed so far.
+    lines: usize,
+}
+
+struct Inner {
+    path: PathBuf,
+    loaded: Mutex<Loaded>,
+}
+
+/// Handle to the namespace index of a storage.
+#[derive(Clone)]
+pub struct Index(Arc<Inner>);
+
+impl Index {
+    /// Obtain the index of the storage at `storage_path`.
+    ///
+    /// All calls with the same `storage_path` return handles to the same
+    /// index, for as long as any handle is alive.
+    pub fn for_storage(storage_path: &Path) -> Self {
+        let storage_path = storage_path
+            .canonicalize()
+            .unwrap_or_else(|_| storage_path.to_path_buf());
+        let mut registry = REGISTRY.lock();
+        if let Some(inner) = registry.get(&storage_path).and_then(Weak::upgrade) {
+            return Self(inner);
+        }
+
+        registry.retain(|_, inner| inner.strong_count() > 0);
+        let inner = Arc::new(Inner {
+            path: storage_path.join(FILE_NAME),
+            loaded: Mutex::new(Loaded::default()),
+        });
+        registry.insert(storage_path, Arc::downgrade(&inner));
+        Self(inner)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.0.path.exists()
+    }
+
+    pub fn get(&self, urn: &Urn) -> Result<Option<Entry>, error::Read> {
+        let mut loaded = self.0.loaded.lock();
+        self.refresh(&mut loaded)?;
+        Ok(loaded.entries.get(&urn.clone().with_path(None)).cloned())
+    }
+
+    /// The number of indexed namespaces.
+    pub fn len(&self) -> Result<usize, error::Read> {
+        let mut loaded = self.0.loaded.lock();
+        self.refresh(&mut loaded)?;
+        Ok(loaded.entries.len())
+    }
+
+    pub fn is_empty(&self) -> Result<bool, error::Read> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// Return up to `limit` entries matching `filter`, starting after the
+    /// [`Urn`] `after`, or from the beginning if `None`.
+    pub fn list(
+        &self,
+        filter: &Filter,
+        after: Option<&Urn>,
+        limit: usize,
+    ) -> Result<Page, error::Read> {
+        let mut loaded = self.0.loaded.lock();
+        self.refresh(&mut loaded)?;
+
+        let start = match after {
+            Some(urn) => Bound::Excluded(urn.clone().with_path(None)),
+            None => Bound::Unbounded,
+        };
+        let mut matching = loaded
+            .entries
+            .range((start, Bound::Unbounded))
+            .map(|(_, entry)| entry)
+            .filter(|entry| filter.matches(entry));
+        let entries = matching.by_ref().take(limit).cloned().collect::<Vec<_>>();
+        let next = match matching.next() {
+            Some(_) => entries.last().map(|entry| entry.urn.clone()),
+            None => None,
+        };
+
+        Ok(Page { entries, next })
+    }
+
+    /// Record `entry`, replacing any previous entry for the same [`Urn`].
+    pub fn put(&self, mut entry: Entry) -> Result<(), error::Write> {
+        entry.urn = entry.urn.with_path(None);
+        self.append(&Line::Put(entry))
+    }
+
+    /// Remove the entry for `urn`, if any.
+    pub fn remove(&self, urn: &Urn) -> Result<(), error::Write> {
+        self.append(&Line::Remove {
+            urn: urn.clone().with_path(None),
+        })
+    }
+
+    /// Record the current state of the namespace `urn` in `storage`.
+    ///
+    /// If the namespace does not exist, its entry is removed.
+    pub fn update<S>(&self, storage: &S, urn: &Urn) -> Result<(), error::Update>
+    where
+        S: AsRef<ReadOnly>,
+    {
+        match entry(storage.as_ref(), urn, secs(SystemTime::now()))? {
+            Some(entry) => self.put(entry)?,
+            None => self.remove(urn)?,
+        }
+        Ok(())
+    }
+
+    /// Replace the index with one built from the contents of `storage`.
+    ///
+    /// Returns the number of indexed namespaces.
+    ///
+    /// Since the storage does not record when a namespace was last written,
+    /// [`Entry::updated`] is taken from the commit time of `rad/signed_refs`,
+    /// if any.
+    pub fn rebuild<S>(&self, storage: &S) -> Result<usize, error::Update>
+    where
+        S: AsRef<ReadOnly>,
+    {
+        let storage = storage.as_ref();
+
+        lazy_static! {
+            static ref GLOB: glob::RefspecMatcher =
+                refspec_pattern!("refs/namespaces/*/refs/rad/*").into();
+        }
+        let mut urns = storage
+            .reference_names_glob(GLOB.clone())?
+            .filter_map(|name| match name {
+                Ok(name) => urn_of(&name).map(Ok),
+                Err(e) => Some(Err(e)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        urns.dedup();
+
+        let mut entries = BTreeMap::new();
+        for urn in urns {
+            let updated = signed_refs_time(storage, &urn)?.unwrap_or_default();
+            if let Some(entry) = entry(storage, &urn, updated)? {
+                entries.insert(entry.urn.clone(), entry);
+            }
+        }
+
+        let mut loaded = self.0.loaded.lock();
+        loaded.entries = entries;
+        self.compact(&mut loaded).map_err(error::Write::from)?;
+
+        Ok(loaded.entries.len())
+    }
+
+    fn append(&self, line: &Line) -> Result<(), error::Write> {
+        let mut buf = serde_json::to_vec(line)?;
+        buf.push(b'\n');
+
+        let mut loaded = self.0.loaded.lock();
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.0.path)?
+            .write_all(&buf)?;
+        self.refresh(&mut loaded)?;
+        if loaded.lines > MIN_COMPACT_LINES && loaded.lines > 2 * loaded.entries.len() {
+            self.compact(&mut loaded)?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the lines appended to the file since it was last read.
+    fn refresh(&self, loaded: &mut Loaded) -> Result<(), error::Read> {
+        let mut file = match File::open(&self.0.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                *loaded = Loaded::default();
+                return Ok(());
+            },
+            Err(e) => return Err(e.into()),
+        };
+        // Compacted by another process
+        if file.metadata()?.len() < loaded.offset {
+            *loaded = Loaded::default();
+        }
+        file.seek(SeekFrom::Start(loaded.offset))?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+
+        // A concurrent writer may not have finished its line yet
+        let complete = match buf.iter().rposition(|b| *b == b'\n') {
+            Some(pos) => &buf[..=pos],
+            None => return Ok(()),
+        };
+        for line in complete.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+            loaded.lines += 1;
+            let line = serde_json::from_slice(line).map_err(|source| error::Read::Malformed {
+                line: loaded.lines,
+                source,
+            })?;
+            match line {
+                Line::Put(entry) => {
+                    loaded.entries.insert(entry.urn.clone(), entry);
+                },
+                Line::Remove { urn } => {
+                    loaded.entries.remove(&urn);
+                },
+            }
+        }
+        loaded.offset += complete.len() as u64;
+
+        Ok(())
+    }
+
+    /// Rewrite the file to contain exactly one line per entry.
+    fn compact(&self, loaded: &mut Loaded) -> Result<(), error::Write> {
+        let tmp = self.0.path.with_extension("idx.tmp");
+        let mut buf = Vec::new();
+        for entry in loaded.entries.values() {
+            serde_json::to_writer(&mut buf, &Line::Put(entry.clone()))?;
+            buf.push(b'\n');
+        }
+        {
+            let mut file = File::create(&tmp)?;
+            file.write_all(&buf)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, &self.0.path)?;
+        loaded.offset = buf.len() as u64;
+        loaded.lines = loaded.entries.len();
+
+        Ok(())
+    }
+}
+
+/// List the namespaces in `storage`, see [`Index::list`].
+///
+/// If the index does not exist yet, it is built first.
+pub fn list<S>(
+    storage: &S,
+    filter: &Filter,
+    after: Option<&Urn>,
+    limit: usize,
+) -> Result<Page, error::List>
+where
+    S: AsRef<ReadOnly>,
+{
+    let storage = storage.as_ref();
+    let index = Index::for_storage(storage.path());
+    if !index.exists() {
+        let n = index.rebuild(&storage)?;
+        tracing::info!(namespaces = n, "built namespace index");
+    }
+    Ok(index.list(filter, after, limit)?)
+}
+
+/// Determine the [`Entry`] of `urn` from the storage, or `None` if the
+/// namespace does not exist.
+fn entry(storage: &ReadOnly, urn: &Urn, updated: u64) -> Result<Option<Entry>, error::Update> {
+    let urn = urn.clone().with_path(None);
+    let prefix = snapshot::namespace_prefix(&urn);
+    let exists = storage
+        .backend
+        .references_glob(&format!("{}*", prefix))?
+        .next()
+        .is_some();
+    if !exists {
+        return Ok(None);
+    }
+
+    let has_identity = storage.has_urn(&urn)?;
+    let tracked = tracking::tracked_peers(storage, Some(&urn))?
+        .next()
+        .is_some();
+    Ok(Some(Entry {
+        urn,
+        updated,
+        has_identity,
+        tracked,
+    }))
+}
+
+fn urn_of(name: &RefLike) -> Option<Urn> {
+    use std::convert::TryFrom as _;
+
+    Urn::try_from(name.clone())
+        .ok()
+        .map(|urn| urn.with_path(None))
+}
+
+fn signed_refs_time(storage: &ReadOnly, urn: &Urn) -> Result<Option<u64>, git2::Error> {
+    let name = format!("{}refs/rad/signed_refs", snapshot::namespace_prefix(urn));
+    match storage.backend.find_reference(&name) {
+        Ok(r) => Ok(Some(r.peel_to_commit()?.time().seconds().max(0) as u64)),
+        Err(e) if git_ext::is_not_found_err(&e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
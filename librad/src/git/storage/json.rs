@@ -0,0 +1,112 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Per-namespace JSON files.
+//!
+//! Derived data which is updated whenever a namespace is written to, such as
+//! its [`super::stats`], is kept outside of git, as one JSON file per
+//! namespace in a directory relative to the storage directory. A [`Store`]
+//! names such a directory, and reads and (atomically) replaces its files.
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{git::types::Namespace, identities::git::Urn};
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Load {
+        #[error("malformed {what}")]
+        Malformed {
+            what: &'static str,
+            #[source]
+            source: serde_json::Error,
+        },
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Store {
+        #[error(transparent)]
+        Io(#[from] io::Error),
+
+        #[error(transparent)]
+        Json(#[from] serde_json::Error),
+    }
+}
+
+/// A directory of JSON files, one per namespace.
+#[derive(Clone, Copy, Debug)]
+pub struct Store {
+    dir: &'static str,
+    what: &'static str,
+}
+
+impl Store {
+    /// A store in `dir`, relative to the storage directory, holding `what`
+    /// (as reported by [`error::Load::Malformed`]).
+    pub const fn new(dir: &'static str, what: &'static str) -> Self {
+        Self { dir, what }
+    }
+
+    /// The path of the file of the namespace `urn`.
+    pub fn path(&self, storage_path: &Path, urn: &Urn) -> PathBuf {
+        storage_path
+            .join(self.dir)
+            .join(format!("{}.json", Namespace::from(urn)))
+    }
+
+    /// Read the value stored for the namespace `urn`, if any.
+    pub fn load<T>(&self, storage_path: &Path, urn: &Urn) -> Result<Option<T>, error::Load>
+    where
+        T: DeserializeOwned,
+    {
+        let bytes = match fs::read(self.path(storage_path, urn)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|source| error::Load::Malformed {
+                what: self.what,
+                source,
+            })
+    }
+
+    /// Replace the value stored for the namespace `urn` with `value`.
+    pub fn store<T>(&self, storage_path: &Path, urn: &Urn, value: &T) -> Result<(), error::Store>
+    where
+        T: Serialize,
+    {
+        let path = self.path(storage_path, urn);
+        fs::create_dir_all(path.parent().expect("namespace paths have a parent"))?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(value)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Remove the value stored for the namespace `urn`, if any.
+    pub fn remove(&self, storage_path: &Path, urn: &Urn) -> io::Result<()> {
+        match fs::remove_file(self.path(storage_path, urn)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
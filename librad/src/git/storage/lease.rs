@@ -0,0 +1,473 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Cross-process namespace locks for monorepos on network filesystems.
+//!
+//! The in-process locks of [`super::lock`] do not prevent _other_ processes
+//! from updating a namespace concurrently. Locally, this is covered by git's
+//! own lockfiles, but on NFS or SMB mounts the primitives those rely on
+//! (`O_EXCL`, `flock`) are unreliable, and a crashed client may leave locks
+//! behind which are never released.
+//!
+//! A [`Lease`] is a lock file per namespace which expires unless renewed
+//! within [`Leases::ttl`]. It is created via `link(2)`, which is atomic on
+//! NFS, and carries a _fencing token_: a number which increases with every
+//! acquisition of the lease. Once the refs of an update are locked, and
+//! before it is committed, the holder calls [`Lease::check`], which fails if
+//! the lease expired and was taken over by another process in the meantime, or
+//! if an update with a newer token was already committed.
+//!
+//! Leases held via [`super::Storage::lock_namespace`] are renewed in the
+//! background for as long as the [`super::lock::Guard`] is alive, so a long
+//! running update (eg. a large fetch) does not lose its lease to expiry.
+//!
+//! Whether leases are used is controlled by the `rad.locking` setting of the
+//! monorepo config, see [`Locking`].
+
+use std::{
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use rand::Rng as _;
+use thiserror::Error;
+
+use crate::identities::git::Urn;
+
+/// The name of the directory holding the lease files, relative to the storage
+/// directory.
+pub const DIR_NAME: &str = "leases";
+
+/// How long a lease remains valid without being renewed.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+/// How long [`Leases::acquire`] waits for a lease held by another process.
+const ACQUIRE_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("timed out waiting for the lease of {0}")]
+    Timeout(Urn),
+
+    #[error("lease of {urn} was lost: token {token} was superseded by {current}")]
+    Fenced { urn: Urn, token: u64, current: u64 },
+
+    #[error("malformed lease file `{0}`")]
+    Malformed(PathBuf),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The locking strategy of a monorepo, configured via `rad.locking`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Locking {
+    /// Use [`Locking::Lease`] if the storage is on a network filesystem, as
+    /// determined by [`probe`], and [`Locking::Local`] otherwise.
+    Auto,
+    /// Rely on in-process locks and git's lockfiles.
+    Local,
+    /// In addition, hold a [`Lease`] while updating a namespace.
+    Lease,
+}
+
+impl Default for Locking {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+impl fmt::Display for Locking {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Local => "local",
+            Self::Lease => "lease",
+        })
+    }
+}
+
+impl FromStr for Locking {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "local" => Ok(Self::Local),
+            "lease" => Ok(Self::Lease),
+            other => Err(format!(
+                "unknown locking strategy `{}`, expected one of `auto`, `local`, `lease`",
+                other
+            )),
+        }
+    }
+}
+
+impl Locking {
+    /// Resolve [`Locking::Auto`] for the storage at `path`.
+    pub fn resolve(self, path: &Path) -> Self {
+        match self {
+            Self::Auto => match probe(path) {
+                Ok(caps) if caps.network => {
+                    tracing::warn!(
+                        fs = ?caps.fs_type,
+                        path = %path.display(),
+                        "storage is on a network filesystem, using lease files for locking"
+                    );
+                    Self::Lease
+                },
+                Ok(_) => Self::Local,
+                Err(e) => {
+                    tracing::warn!(err = %e, "unable to determine filesystem type of storage");
+                    Self::Local
+                },
+            },
+            other => other,
+        }
+    }
+}
+
+/// Properties of the filesystem the storage is on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The type of the filesystem, if it could be determined.
+    pub fs_type: Option<String>,
+    /// Whether the filesystem is known to be a network filesystem.
+    pub network: bool,
+}
+
+/// Determine the [`Capabilities`] of the filesystem `path` is on.
+pub fn probe(path: &Path) -> io::Result<Capabilities> {
+    imp::probe(path)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::{ffi::CString, io, mem::MaybeUninit, os::unix::ffi::OsStrExt as _, path::Path};
+
+    use super::Capabilities;
+
+    const NETWORK: &[(u32, &str)] = &[
+        (0x6969, "nfs"),
+        (0x517b, "smb"),
+        (0xff53_4d42, "cifs"),
+        (0xfe53_4d42, "smb2"),
+        (0x5346_414f, "afs"),
+        (0x00c3_6400, "ceph"),
+        (0x0102_1997, "9p"),
+    ];
+
+    pub fn probe(path: &Path) -> io::Result<Capabilities> {
+        let cpath = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut buf = MaybeUninit::<libc::statfs>::uninit();
+        // SAFETY: `cpath` is a valid C string, and `buf` is large enough to
+        // hold a `statfs`.
+        let res = unsafe { libc::statfs(cpath.as_ptr(), buf.as_mut_ptr()) };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `statfs` succeeded, so `buf` is initialised.
+        let magic = unsafe { buf.assume_init() }.f_type as u32;
+        let network = NETWORK.iter().find(|(m, _)| *m == magic);
+
+        Ok(Capabilities {
+            fs_type: Some(
+                network
+                    .map(|(_, name)| name.to_string())
+                    .unwrap_or_else(|| format!("{:#x}", magic)),
+            ),
+            network: network.is_some(),
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod imp {
+    use std::{
+        ffi::{CStr, CString},
+        io,
+        mem::MaybeUninit,
+        os::unix::ffi::OsStrExt as _,
+        path::Path,
+    };
+
+    use super::Capabilities;
+
+    const NETWORK: &[&str] = &["nfs", "smbfs", "afpfs", "webdav"];
+
+    pub fn probe(path: &Path) -> io::Result<Capabilities> {
+        let cpath = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut buf = MaybeUninit::<libc::statfs>::uninit();
+        // SAFETY: `cpath` is a valid C string, and `buf` is large enough to
+        // hold a `statfs`.
+        let res = unsafe { libc::statfs(cpath.as_ptr(), buf.as_mut_ptr()) };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `statfs` succeeded, so `buf` is initialised, and
+        // `f_fstypename` is nul-terminated.
+        let buf = unsafe { buf.assume_init() };
+        let fs_type = unsafe { CStr::from_ptr(buf.f_fstypename.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+
+        Ok(Capabilities {
+            network: NETWORK.contains(&fs_type.as_str()),
+            fs_type: Some(fs_type),
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+mod imp {
+    use std::{io, path::Path};
+
+    use super::Capabilities;
+
+    pub fn probe(_: &Path) -> io::Result<Capabilities> {
+        Ok(Capabilities {
+            fs_type: None,
+            network: false,
+        })
+    }
+}
+
+/// The lease files of a monorepo.
+#[derive(Clone, Debug)]
+pub struct Leases {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl Leases {
+    /// The leases of the storage at `storage_path`.
+    pub fn new(storage_path: &Path) -> Self {
+        Self::with_ttl(storage_path, DEFAULT_TTL)
+    }
+
+    pub fn with_ttl(storage_path: &Path, ttl: Duration) -> Self {
+        Self {
+            dir: storage_path.join(DIR_NAME),
+            ttl,
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Acquire the lease of `urn`, blocking until it is released or expires.
+    pub fn acquire(&self, urn: &Urn) -> Result<Lease, Error> {
+        fs::create_dir_all(&self.dir)?;
+
+        let urn = urn.clone().with_path(None);
+        let id = urn.encode_id();
+        let path = self.dir.join(format!("{}.lease", id));
+        let holder = format!(
+            "{}-{:016x}",
+            std::process::id(),
+            rand::thread_rng().gen::<u64>()
+        );
+        let tmp = self.dir.join(format!("{}.{}.tmp", id, holder));
+
+        let mut waited = Duration::ZERO;
+        loop {
+            fs::write(&tmp, holder.as_bytes())?;
+            let linked = link(&tmp, &path);
+            fs::remove_file(&tmp)?;
+            if linked? {
+                break;
+            }
+
+            if self.is_expired(&path)? {
+                tracing::warn!(urn = %urn, "breaking expired lease");
+                let stale = self.dir.join(format!("{}.{}.stale", id, holder));
+                match fs::rename(&path, &stale) {
+                    Ok(()) => fs::remove_file(&stale)?,
+                    // Someone else broke it first
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+                    Err(e) => return Err(e.into()),
+                }
+                continue;
+            }
+
+            if waited >= ACQUIRE_TIMEOUT {
+                return Err(Error::Timeout(urn));
+            }
+            let backoff = Duration::from_millis(50);
+            thread::sleep(backoff);
+            waited += backoff;
+        }
+
+        let counter = self.dir.join(format!("{}.token", id));
+        let token = read_token(&counter)?.unwrap_or(0) + 1;
+        write_atomic(&self.dir, &counter, &token.to_string())?;
+        let lease = Lease {
+            urn,
+            path,
+            fence: self.dir.join(format!("{}.fence", id)),
+            dir: self.dir.clone(),
+            holder,
+            token,
+        };
+        lease.write()?;
+
+        Ok(lease)
+    }
+
+    /// Acquire the leases of all `urns`, in order.
+    pub fn acquire_many<'a, I>(&self, urns: I) -> Result<Vec<Lease>, Error>
+    where
+        I: IntoIterator<Item = &'a Urn>,
+    {
+        urns.into_iter().map(|urn| self.acquire(urn)).collect()
+    }
+
+    fn is_expired(&self, path: &Path) -> io::Result<bool> {
+        match fs::metadata(path) {
+            Ok(meta) => Ok(meta
+                .modified()?
+                .elapsed()
+                .map(|age| age > self.ttl)
+                .unwrap_or(false)),
+            // Released in the meantime, retry right away
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// A held lease, released when dropped.
+#[derive(Debug)]
+pub struct Lease {
+    urn: Urn,
+    path: PathBuf,
+    fence: PathBuf,
+    dir: PathBuf,
+    holder: String,
+    token: u64,
+}
+
+impl Lease {
+    pub fn urn(&self) -> &Urn {
+        &self.urn
+    }
+
+    /// The fencing token of this lease.
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// Verify that the lease is still held, and that no update with a newer
+    /// fencing token was committed. Renews the lease on success.
+    ///
+    /// Call this once the refs to update are locked, right before committing
+    /// the update of the namespace, so the lease can't be lost in between.
+    pub fn check(&self) -> Result<(), Error> {
+        let current = read_token(&self.path)?.unwrap_or(0);
+        if current != self.token {
+            return Err(self.fenced(current));
+        }
+        let committed = read_token(&self.fence)?.unwrap_or(0);
+        if committed > self.token {
+            return Err(self.fenced(committed));
+        }
+        write_atomic(&self.dir, &self.fence, &self.token.to_string())?;
+        self.write()?;
+
+        Ok(())
+    }
+
+    /// Extend the lease by another [`Leases::ttl`], provided it is still
+    /// held.
+    pub fn renew(&self) -> Result<(), Error> {
+        let current = read_token(&self.path)?.unwrap_or(0);
+        if current != self.token {
+            return Err(self.fenced(current));
+        }
+        self.write()
+    }
+
+    fn fenced(&self, current: u64) -> Error {
+        Error::Fenced {
+            urn: self.urn.clone(),
+            token: self.token,
+            current,
+        }
+    }
+
+    fn write(&self) -> Result<(), Error> {
+        write_atomic(
+            &self.dir,
+            &self.path,
+            &format!("{}\n{}", self.token, self.holder),
+        )
+        .map_err(Error::from)
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        // Don't remove a lease which was taken over by someone else
+        if matches!(read_token(&self.path), Ok(Some(token)) if token == self.token) {
+            if let Err(e) = fs::remove_file(&self.path) {
+                tracing::warn!(err = %e, urn = %self.urn, "failed to release lease");
+            }
+        }
+    }
+}
+
+/// Hard-link `tmp` to `path`, returning `false` if `path` already exists.
+///
+/// On NFS, the reply to a successful `link` may get lost, so the link count
+/// of `tmp` is what tells whether it succeeded.
+fn link(tmp: &Path, path: &Path) -> io::Result<bool> {
+    let res = fs::hard_link(tmp, path);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt as _;
+
+        if fs::metadata(tmp)?.nlink() == 2 {
+            return Ok(true);
+        }
+    }
+    match res {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read the token from the first line of the file at `path`.
+fn read_token(path: &Path) -> Result<Option<u64>, Error> {
+    match fs::read_to_string(path) {
+        Ok(content) => content
+            .lines()
+            .next()
+            .and_then(|line| line.trim().parse().ok())
+            .map(Some)
+            .ok_or_else(|| Error::Malformed(path.to_path_buf())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace the contents of `path` atomically, via a temporary file in `dir`.
+fn write_atomic(dir: &Path, path: &Path, content: &str) -> io::Result<()> {
+    let tmp = dir.join(format!(".{:016x}.tmp", rand::thread_rng().gen::<u64>()));
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path).map_err(|e| {
+        fs::remove_file(&tmp).ok();
+        e
+    })
+}
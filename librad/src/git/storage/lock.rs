@@ -0,0 +1,314 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Per-namespace write locks.
+//!
+//! Writes to different namespaces of the monorepo do not interfere with each
+//! other, so there is no need to serialise them. Multi-step updates of the
+//! _same_ namespace (such as replicating a URN and then updating its
+//! `rad/signed_refs`), however, must not be interleaved.
+//!
+//! Locks are advisory, and only shared between [`super::Storage`] instances
+//! of the same process which refer to the same monorepo. They are reentrant:
+//! a thread holding the lock for a namespace may acquire it again, so that
+//! eg. an identity update can take the lock and then update the signed refs,
+//! which takes it as well. The namespace is released when the outermost
+//! [`Guard`] is dropped.
+//!
+//! All ref-writing operations of [`super::Storage`] take the lock of the
+//! namespaces they write to: replication, tracking, identity and signed refs
+//! updates, and [`super::txn::Transaction::commit`].
+//!
+//! # Lock order
+//!
+//! Namespaces are ordered by their [`Urn`], and locked in ascending order:
+//! when an operation spans several namespaces, use [`Namespaces::lock_many`],
+//! which acquires all of them in this order.
+//!
+//! Some operations only discover further namespaces to write to while holding
+//! a lock, eg. replicating a project adopts the identities of its delegates,
+//! which live in namespaces of their own. Such nested acquisitions may wait
+//! for namespaces ordered after all namespaces the thread holds. Waiting for a
+//! namespace ordered before one which is held could deadlock, so a namespace
+//! out of order is only taken if it is available right away, and
+//! [`Error::Order`] is returned otherwise.
+//!
+//! To also exclude other processes, eg. when the monorepo is on a network
+//! filesystem, [`super::Storage::lock_namespace`] additionally acquires a
+//! [`lease::Lease`] per namespace if configured to do so. Re-entering a
+//! namespace shares its lease, and all leases of a monorepo are renewed by a
+//! background thread while their namespace is locked.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+        Weak,
+    },
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+use parking_lot::{Condvar, Mutex};
+
+use super::lease;
+use crate::identities::git::Urn;
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("namespace {wanted} is busy, and can't be waited for while holding {held}")]
+    Order { held: Urn, wanted: Urn },
+
+    #[error(transparent)]
+    Lease(#[from] lease::Error),
+}
+
+lazy_static! {
+    static ref REGISTRY: Mutex<HashMap<PathBuf, Weak<Inner>>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Default)]
+struct Inner {
+    locked: Mutex<HashMap<Urn, Held>>,
+    released: Condvar,
+    /// Whether the lease renewal thread was started.
+    renewing: AtomicBool,
+}
+
+/// A locked namespace.
+struct Held {
+    owner: ThreadId,
+    /// The number of live [`Guard`]s of `owner` for the namespace.
+    depth: usize,
+    /// The lease of the namespace, if the storage uses leases.
+    lease: Option<Arc<lease::Lease>>,
+}
+
+/// The set of namespace locks of a monorepo.
+#[derive(Clone)]
+pub struct Namespaces(Arc<Inner>);
+
+impl Namespaces {
+    /// Obtain the locks for the monorepo at `path`.
+    ///
+    /// All calls with the same `path` return handles to the same set of
+    /// locks, for as long as any handle is alive.
+    pub fn for_storage(path: &Path) -> Self {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let mut registry = REGISTRY.lock();
+        if let Some(inner) = registry.get(&path).and_then(Weak::upgrade) {
+            return Self(inner);
+        }
+
+        registry.retain(|_, inner| inner.strong_count() > 0);
+        let inner = Arc::new(Inner::default());
+        registry.insert(path, Arc::downgrade(&inner));
+        Self(inner)
+    }
+
+    /// Lock the namespace `urn`, blocking until it becomes available.
+    ///
+    /// The lock is released when the returned [`Guard`] is dropped.
+    pub fn lock(&self, urn: &Urn) -> Result<Guard, Error> {
+        self.lock_many(Some(urn.clone()))
+    }
+
+    /// Lock all namespaces in `urns`, blocking until all of them become
+    /// available.
+    ///
+    /// The locks are acquired in the order of the [`Urn`]s, so concurrent
+    /// callers with overlapping sets of namespaces cannot deadlock. Namespaces
+    /// already held by the calling thread are re-entered without blocking.
+    ///
+    /// Fails with [`Error::Order`], without acquiring any of the namespaces,
+    /// if one of them is busy and ordered before a namespace the calling
+    /// thread holds, see [the lock order](self#lock-order).
+    pub fn lock_many<I>(&self, urns: I) -> Result<Guard, Error>
+    where
+        I: IntoIterator<Item = Urn>,
+    {
+        let urns = urns
+            .into_iter()
+            .map(|urn| urn.with_path(None))
+            .collect::<BTreeSet<_>>();
+
+        let me = thread::current().id();
+        let mut locked = self.0.locked.lock();
+        let highest = locked
+            .iter()
+            .filter(|(_, held)| held.owner == me)
+            .map(|(urn, _)| urn.clone())
+            .max();
+        let mut held = Vec::with_capacity(urns.len());
+        let mut acquired = Vec::new();
+        let mut leases = Vec::new();
+        for urn in urns {
+            loop {
+                match locked.get(&urn).map(|held| held.owner) {
+                    None => {
+                        let held = Held {
+                            owner: me,
+                            depth: 1,
+                            lease: None,
+                        };
+                        locked.insert(urn.clone(), held);
+                        acquired.push(urn.clone());
+                        break;
+                    },
+                    Some(owner) if owner == me => {
+                        if let Some(held) = locked.get_mut(&urn) {
+                            held.depth += 1;
+                            leases.extend(held.lease.clone());
+                        }
+                        break;
+                    },
+                    Some(_) => match &highest {
+                        Some(highest) if urn < *highest => {
+                            let partial = Guard {
+                                inner: self.0.clone(),
+                                urns: held,
+                                acquired,
+                                leases,
+                            };
+                            drop(locked);
+                            drop(partial);
+                            return Err(Error::Order {
+                                held: highest.clone(),
+                                wanted: urn,
+                            });
+                        },
+                        _ => self.0.released.wait(&mut locked),
+                    },
+                }
+            }
+            held.push(urn);
+        }
+
+        Ok(Guard {
+            inner: self.0.clone(),
+            urns: held,
+            acquired,
+            leases,
+        })
+    }
+
+    /// Returns `true` if `urn` is currently locked.
+    pub fn is_locked(&self, urn: &Urn) -> bool {
+        self.0
+            .locked
+            .lock()
+            .contains_key(&urn.clone().with_path(None))
+    }
+}
+
+/// Releases the namespace locks it holds when dropped.
+#[must_use = "the locks are released immediately if the guard is not held"]
+pub struct Guard {
+    inner: Arc<Inner>,
+    urns: Vec<Urn>,
+    /// The namespaces which were not already held by the calling thread.
+    acquired: Vec<Urn>,
+    leases: Vec<Arc<lease::Lease>>,
+}
+
+impl Guard {
+    /// The namespaces held by this guard.
+    pub fn urns(&self) -> &[Urn] {
+        &self.urns
+    }
+
+    /// The namespaces this guard acquired, as opposed to re-entered.
+    pub(super) fn acquired(&self) -> &[Urn] {
+        &self.acquired
+    }
+
+    /// The leases held by this guard, if any.
+    ///
+    /// This includes the leases of re-entered namespaces.
+    pub fn leases(&self) -> &[Arc<lease::Lease>] {
+        &self.leases
+    }
+
+    /// Verify that all leases held by this guard are still valid, see
+    /// [`lease::Lease::check`]. Always succeeds if no leases are held.
+    pub fn check(&self) -> Result<(), lease::Error> {
+        self.leases.iter().try_for_each(|lease| lease.check())
+    }
+
+    /// Attach the `leases` of the [`Guard::acquired`] namespaces, and renew
+    /// them every third of `ttl` until they are released.
+    pub(super) fn with_leases(mut self, leases: Vec<lease::Lease>, ttl: Duration) -> Self {
+        if leases.is_empty() {
+            return self;
+        }
+
+        {
+            let mut locked = self.inner.locked.lock();
+            for lease in leases {
+                let lease = Arc::new(lease);
+                if let Some(held) = locked.get_mut(lease.urn()) {
+                    held.lease = Some(lease.clone());
+                }
+                self.leases.push(lease);
+            }
+        }
+
+        if !self.inner.renewing.swap(true, Ordering::AcqRel) {
+            let inner = Arc::downgrade(&self.inner);
+            thread::Builder::new()
+                .name("namespace-leases".to_owned())
+                .spawn(move || renew(inner, ttl / 3))
+                .expect("failed to spawn lease renewal thread");
+        }
+
+        self
+    }
+}
+
+/// Renew the leases of all locked namespaces every `interval`, until the
+/// locks are dropped.
+fn renew(inner: Weak<Inner>, interval: Duration) {
+    loop {
+        thread::sleep(interval);
+        let leases = match inner.upgrade() {
+            None => break,
+            Some(inner) => inner
+                .locked
+                .lock()
+                .values()
+                .filter_map(|held| held.lease.clone())
+                .collect::<Vec<_>>(),
+        };
+        for lease in leases {
+            if let Err(e) = lease.renew() {
+                tracing::warn!(err = %e, urn = %lease.urn(), "failed to renew lease");
+            }
+        }
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let mut locked = self.inner.locked.lock();
+        let mut released = Vec::new();
+        for urn in &self.urns {
+            if let Some(held) = locked.get_mut(urn) {
+                held.depth -= 1;
+                if held.depth == 0 {
+                    released.extend(locked.remove(urn).and_then(|held| held.lease));
+                }
+            }
+        }
+        // Release the leases before the locks, so other processes don't have
+        // to wait for the next thread of this process to be done
+        self.leases.clear();
+        drop(released);
+        drop(locked);
+        self.inner.released.notify_all();
+    }
+}
@@ -6,13 +6,18 @@
 use std::{
     marker::PhantomData,
     ops::{Deref, DerefMut},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+    time::Instant,
 };
 
 use deadpool::managed::{self, Manager, Object, RecycleResult};
 use parking_lot::RwLock;
 use std_ext::Void;
 use thiserror::Error;
+use tokio::sync::Notify;
 
 use super::{error, read, ReadOnly, Storage};
 use crate::{paths::Paths, Signer};
@@ -27,8 +32,149 @@ pub enum InitError {
     Write(#[from] error::Init),
 }
 
-pub type Pool<S> = deadpool::managed::Pool<S, InitError>;
-pub type PoolError = managed::PoolError<InitError>;
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum PoolError {
+    #[error("the storage pool is closed")]
+    Closed,
+
+    #[error(transparent)]
+    Get(#[from] managed::PoolError<InitError>),
+}
+
+/// Storage operations were still in flight when [`Pool::close`] gave up
+/// waiting for them.
+#[derive(Debug, Error)]
+#[error("{in_flight} storage operations still in flight")]
+pub struct Undrained {
+    pub in_flight: usize,
+}
+
+/// A pool of [`Storage`] or [`ReadOnly`] instances.
+///
+/// Before the process exits, the pool should be [`Pool::close`]d, so that
+/// operations which are underway, eg. ref transactions, get a chance to
+/// complete instead of being interrupted.
+pub struct Pool<S> {
+    inner: managed::Pool<S, InitError>,
+    lifecycle: Arc<Lifecycle>,
+}
+
+impl<S> Clone for Pool<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            lifecycle: self.lifecycle.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Lifecycle {
+    closed: AtomicBool,
+    in_flight: AtomicUsize,
+    drained: Notify,
+}
+
+/// Tracks a borrowed or requested pool instance.
+struct InFlight(Arc<Lifecycle>);
+
+impl InFlight {
+    fn new(lifecycle: &Arc<Lifecycle>) -> Self {
+        lifecycle.in_flight.fetch_add(1, SeqCst);
+        Self(lifecycle.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, SeqCst) == 1 {
+            self.0.drained.notify_waiters()
+        }
+    }
+}
+
+impl<S> Pool<S> {
+    pub fn new<M>(manager: M, max_size: usize) -> Self
+    where
+        M: Manager<S, InitError> + Send + Sync + 'static,
+    {
+        Self {
+            inner: managed::Pool::new(manager, max_size),
+            lifecycle: Arc::new(Lifecycle::default()),
+        }
+    }
+
+    /// Borrow an instance from the pool, waiting for one to become available
+    /// if necessary.
+    ///
+    /// Fails with [`PoolError::Closed`] once the pool is closed.
+    pub async fn get(&self) -> Result<PooledRef<S>, PoolError> {
+        // Register before checking, so `close` can't miss us
+        let in_flight = InFlight::new(&self.lifecycle);
+        if self.is_closed() {
+            return Err(PoolError::Closed);
+        }
+        let obj = self.inner.get().await?;
+        Ok(PooledRef {
+            obj,
+            _in_flight: in_flight,
+        })
+    }
+
+    /// Whether all instances of the pool are in use.
+    pub fn is_saturated(&self) -> bool {
+        let status = self.inner.status();
+        status.available <= 0 && status.size >= status.max_size
+    }
+
+    /// The number of instances borrowed or requested from the pool.
+    pub fn in_flight(&self) -> usize {
+        self.lifecycle.in_flight.load(SeqCst)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.lifecycle.closed.load(SeqCst)
+    }
+
+    /// Close the pool.
+    ///
+    /// No new instances are handed out after this is called. Waits until all
+    /// borrowed instances are returned, or until `deadline` passes, and
+    /// releases the idle instances.
+    ///
+    /// If instances are still borrowed at the `deadline`, [`Undrained`] is
+    /// returned. Those instances are released when they are returned.
+    pub async fn close(&self, deadline: Instant) -> Result<(), Undrained> {
+        self.lifecycle.closed.store(true, SeqCst);
+
+        let lifecycle = &self.lifecycle;
+        let drained = async {
+            loop {
+                let notified = lifecycle.drained.notified();
+                if lifecycle.in_flight.load(SeqCst) == 0 {
+                    break;
+                }
+                notified.await;
+            }
+        };
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let res = link_async::timeout(timeout, drained)
+            .await
+            .map_err(|_| Undrained {
+                in_flight: self.in_flight(),
+            });
+
+        while self.inner.status().available > 0 {
+            match self.inner.try_get().await {
+                Ok(obj) => drop(Object::take(obj)),
+                Err(_) => break,
+            }
+        }
+
+        res
+    }
+}
 
 #[async_trait]
 pub trait Pooled<S: Send> {
@@ -38,7 +184,7 @@ pub trait Pooled<S: Send> {
 #[async_trait]
 impl<S: Send> Pooled<S> for Pool<S> {
     async fn get(&self) -> Result<PooledRef<S>, PoolError> {
-        self.get().await.map(PooledRef::from)
+        Pool::get(self).await
     }
 }
 
@@ -46,19 +192,22 @@ impl<S: Send> Pooled<S> for Pool<S> {
 ///
 /// The `S` parameter can be filled by [`Storage`] for read-write access or
 /// [`ReadOnly`] for read-only access.
-pub struct PooledRef<S>(Object<S, InitError>);
+pub struct PooledRef<S> {
+    obj: Object<S, InitError>,
+    _in_flight: InFlight,
+}
 
 impl<S> Deref for PooledRef<S> {
     type Target = S;
 
     fn deref(&self) -> &Self::Target {
-        self.0.deref()
+        self.obj.deref()
     }
 }
 
 impl<S> DerefMut for PooledRef<S> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.0.deref_mut()
+        self.obj.deref_mut()
     }
 }
 
@@ -76,13 +225,7 @@ impl<S> AsMut<S> for PooledRef<S> {
 
 impl AsRef<ReadOnly> for PooledRef<Storage> {
     fn as_ref(&self) -> &ReadOnly {
-        self.0.read_only()
-    }
-}
-
-impl<S> From<Object<S, InitError>> for PooledRef<S> {
-    fn from(obj: Object<S, InitError>) -> Self {
-        Self(obj)
+        self.obj.read_only()
     }
 }
 
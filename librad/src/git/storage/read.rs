@@ -21,6 +21,7 @@ use crate::{
 
 use super::{
     config::{self, Config},
+    encryption,
     glob::{self, Pattern},
 };
 
@@ -40,7 +41,7 @@ pub enum Error {
 pub mod error {
     use thiserror::Error;
 
-    use super::config;
+    use super::{config, encryption};
 
     #[derive(Debug, Error)]
     #[non_exhaustive]
@@ -48,11 +49,42 @@ pub mod error {
         #[error(transparent)]
         Config(#[from] config::Error),
 
+        #[error(transparent)]
+        Encryption(#[from] encryption::Error),
+
+        #[error("storage is encrypted, and must be unlocked before it can be opened")]
+        Locked,
+
+        #[error(
+            "storage uses object format `{0}`, which is not supported by this version of link"
+        )]
+        ObjectFormat(String),
+
         #[error(transparent)]
         Git(#[from] git2::Error),
     }
 }
 
+/// Check the `extensions.objectFormat` of the repository at `git_dir`, if it
+/// exists, and return it if it is not supported.
+///
+/// Storage is limited to SHA-1: support for the SHA-256 object format is out
+/// of scope until `libgit2` supports it, see the "Object Format" section of the
+/// spec. This check yields a more helpful error than `libgit2` when
+/// encountering a repository in a different format.
+pub(super) fn unsupported_object_format(git_dir: &Path) -> Result<Option<String>, git2::Error> {
+    let path = git_dir.join("config");
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let format = git2::Config::open(&path)?
+        .get_string("extensions.objectformat")
+        .map(Some)
+        .or_matches::<git2::Error, _, _>(is_not_found_err, || Ok(None))?;
+    Ok(format.filter(|format| !format.eq_ignore_ascii_case("sha1")))
+}
+
 pub trait ReadOnlyStorage {
     fn has_urn(&self, urn: &Urn) -> Result<bool, Error>;
 
@@ -172,8 +204,20 @@ impl ReadOnly {
     /// the same way two `git` processes can access the same repository.
     /// However, if you need multiple [`ReadOnly`]s to be shared between
     /// threads, use a [`super::Pool`] instead.
+    ///
+    /// It is also safe to open a [`ReadOnly`] from a different process than
+    /// the one holding the write role, eg. while a node daemon is running.
+    /// Every single read observes a committed state of the respective
+    /// reference, but consecutive reads may straddle a concurrent update. Use
+    /// [`ReadOnly::snapshot`] to obtain a consistent view of a namespace.
     pub fn open(paths: &Paths) -> Result<Self, error::Init> {
         crate::git::init();
+        if encryption::status(paths)? == encryption::Status::Locked {
+            return Err(error::Init::Locked);
+        }
+        if let Some(format) = unsupported_object_format(paths.git_dir())? {
+            return Err(error::Init::ObjectFormat(format));
+        }
         let backend = git2::Repository::open(paths.git_dir())?;
         let peer_id = Config::try_from(&backend)?.peer_id()?;
         Ok(Self { backend, peer_id })
@@ -214,6 +258,25 @@ impl ReadOnly {
     pub fn identities<'a, T: 'a>(&'a self) -> Identities<'a, T> {
         Identities::from(&self.backend)
     }
+
+    /// Find the references whose target object is missing from the object
+    /// database.
+    ///
+    /// This is a quick consistency check: the history reachable from the
+    /// targets is not traversed.
+    pub fn dangling_refs(&self) -> Result<Vec<String>, Error> {
+        let odb = self.backend.odb()?;
+        let mut dangling = Vec::new();
+        for r in self.backend.references()? {
+            let r = r?;
+            if let Some(oid) = r.target() {
+                if !odb.exists(oid) {
+                    dangling.push(String::from_utf8_lossy(r.name_bytes()).into_owned());
+                }
+            }
+        }
+        Ok(dangling)
+    }
 }
 
 impl ReadOnlyStorage for ReadOnly {
@@ -0,0 +1,268 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Structured reflog messages, and typed access to the reflogs of a
+//! namespace.
+//!
+//! Refs updated by replication, tracking and identity updates carry a reflog
+//! message of the form
+//!
+//! ```text
+//! <op>: <action>[; peer=<peer id>][; request=<request id>]
+//! ```
+//!
+//! eg. `replicate: fast-forward; peer=hyn...`, which a [`Message`] renders
+//! and parses. The `peer` is the remote the update originated from, if any.
+//! The `request` identifies the API request which caused the update, and is
+//! picked up from the enclosing [`with_request`] scope.
+//!
+//! [`ReadOnly::reflog`] returns the reflog entries of all refs in a
+//! namespace, answering "who changed this ref and why". Note that git
+//! removes the reflog of a ref when the ref is deleted.
+
+use std::{cell::RefCell, convert::TryFrom, fmt, str::FromStr};
+
+use git_ext as ext;
+use thiserror::Error;
+
+use super::{read, snapshot::namespace_prefix, ReadOnly};
+use crate::{identities::git::Urn, PeerId};
+
+/// The kind of operation which updated a ref.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// Fetching from a remote peer.
+    Replicate,
+    /// Tracking a peer.
+    Track,
+    /// Untracking a peer.
+    Untrack,
+    /// Creating or updating an identity.
+    Identity,
+    /// Restoring a namespace from a backup.
+    Restore,
+}
+
+impl Op {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Replicate => "replicate",
+            Self::Track => "track",
+            Self::Untrack => "untrack",
+            Self::Identity => "identity",
+            Self::Restore => "restore",
+        }
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ParseError {
+    #[error("missing operation")]
+    MissingOp,
+    #[error("unknown operation `{0}`")]
+    UnknownOp(String),
+    #[error("malformed field `{0}`")]
+    Field(String),
+    #[error("invalid peer id")]
+    Peer(#[from] crate::crypto::peer::conversion::Error),
+}
+
+impl FromStr for Op {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Self::Replicate,
+            Self::Track,
+            Self::Untrack,
+            Self::Identity,
+            Self::Restore,
+        ]
+        .iter()
+        .copied()
+        .find(|op| op.as_str() == s)
+        .ok_or_else(|| ParseError::UnknownOp(s.to_owned()))
+    }
+}
+
+/// A structured reflog message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    pub op: Op,
+    /// What was done, eg. `fast-forward` or `create`.
+    pub action: String,
+    /// The remote peer the update originated from.
+    pub peer: Option<PeerId>,
+    /// The API request which caused the update.
+    pub request: Option<String>,
+}
+
+impl Message {
+    /// A message for `op`, with the request of the current [`with_request`]
+    /// scope, if any.
+    pub fn new(op: Op, action: impl Into<String>) -> Self {
+        Self {
+            op,
+            action: action.into(),
+            peer: None,
+            request: current_request(),
+        }
+    }
+
+    pub fn with_peer(self, peer: impl Into<Option<PeerId>>) -> Self {
+        Self {
+            peer: peer.into(),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.op, self.action)?;
+        if let Some(peer) = &self.peer {
+            write!(f, "; peer={}", peer)?;
+        }
+        if let Some(request) = &self.request {
+            write!(f, "; request={}", request)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Message> for String {
+    fn from(msg: Message) -> Self {
+        msg.to_string()
+    }
+}
+
+impl FromStr for Message {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (op, rest) = s.split_once(": ").ok_or(ParseError::MissingOp)?;
+        let mut fields = rest.split("; ");
+        let mut msg = Self {
+            op: op.parse()?,
+            action: fields.next().unwrap_or_default().to_owned(),
+            peer: None,
+            request: None,
+        };
+        for field in fields {
+            match field.split_once('=') {
+                Some(("peer", peer)) => msg.peer = Some(peer.parse()?),
+                Some(("request", request)) => msg.request = Some(request.to_owned()),
+                _ => return Err(ParseError::Field(field.to_owned())),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+thread_local! {
+    static REQUEST: RefCell<Option<String>> = RefCell::new(None);
+}
+
+fn current_request() -> Option<String> {
+    REQUEST.with(|r| r.borrow().clone())
+}
+
+/// Run `f`, recording `request` in the reflog [`Message`]s created by it.
+///
+/// The scope is per thread: storage operations run on a blocking thread must
+/// enter it on that thread.
+pub fn with_request<F, T>(request: impl Into<String>, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    struct Reset(Option<String>);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            REQUEST.with(|r| *r.borrow_mut() = prev)
+        }
+    }
+
+    let prev = REQUEST.with(|r| r.borrow_mut().replace(request.into()));
+    let _reset = Reset(prev);
+    f()
+}
+
+/// An entry in the reflog of a ref.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The name of the ref, relative to the namespace.
+    pub refname: ext::RefLike,
+    /// The previous value, `None` if the ref was created.
+    pub old: Option<ext::Oid>,
+    /// The new value.
+    pub new: ext::Oid,
+    /// The committer identity of the process which updated the ref.
+    pub committer: String,
+    /// The time of the update, in seconds since the epoch.
+    pub time: i64,
+    /// The reflog message, as written.
+    pub raw: String,
+}
+
+impl Entry {
+    /// The structured [`Message`], if the update was made by link.
+    pub fn message(&self) -> Option<Message> {
+        self.raw.parse().ok()
+    }
+}
+
+impl ReadOnly {
+    /// The reflog entries of all refs in the namespace of `urn`, most recent
+    /// first.
+    pub fn reflog(&self, urn: &Urn) -> Result<Vec<Entry>, read::Error> {
+        let prefix = namespace_prefix(urn);
+        let mut names = Vec::new();
+        for r in self.backend.references_glob(&format!("{}*", prefix))? {
+            if let Some(name) = r?.name() {
+                names.push(name.to_owned());
+            }
+        }
+
+        let mut entries = Vec::new();
+        for name in names {
+            let refname = match name
+                .strip_prefix(&prefix)
+                .and_then(|n| ext::RefLike::try_from(n).ok())
+            {
+                Some(refname) => refname,
+                None => continue,
+            };
+            for entry in self.backend.reflog(&name)?.iter() {
+                let committer = entry.committer();
+                let old = entry.id_old();
+                entries.push(Entry {
+                    refname: refname.clone(),
+                    old: (!old.is_zero()).then(|| old.into()),
+                    new: entry.id_new().into(),
+                    committer: format!(
+                        "{} <{}>",
+                        committer.name().unwrap_or_default(),
+                        committer.email().unwrap_or_default()
+                    ),
+                    time: committer.when().seconds(),
+                    raw: entry.message().unwrap_or_default().trim_end().to_owned(),
+                })
+            }
+        }
+        // Stable, so entries of the same ref and second stay most recent first
+        entries.sort_by(|a, b| b.time.cmp(&a.time));
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,337 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Per-namespace requirements.
+//!
+//! A namespace may only be understood by versions of link which support the
+//! object format of its repository, a recent enough replication protocol,
+//! and the change formats of the collaborative objects it contains.
+//! [`Requirements`] record these whenever the namespace is written to (see
+//! [`Storage::reindex`]), so that they can be checked before serving or
+//! fetching the namespace. An incompatible namespace is reported as
+//! [`error::Incompatible`], listing each [`Unmet`] requirement, rather than
+//! as an opaque failure deep inside replication.
+//!
+//! Requirements only ever increase: once recorded, a newer version of a
+//! requirement is retained even if the content which demanded it is gone.
+//!
+//! The requirements are stored as JSON under `requirements/`, relative to the
+//! storage directory, one file per namespace (see [`super::json`]).
+
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{json, ReadOnly, Storage};
+use crate::identities::git::Urn;
+
+/// The name of the requirements directory, relative to the storage directory.
+pub const DIR_NAME: &str = "requirements";
+
+const STORE: json::Store = json::Store::new(DIR_NAME, "requirements");
+
+/// The replication protocol version implemented by this version of link.
+#[cfg(not(feature = "replication-v3"))]
+pub const REPLICATION_VERSION: u8 = 2;
+/// The replication protocol version implemented by this version of link.
+#[cfg(feature = "replication-v3")]
+pub const REPLICATION_VERSION: u8 = 3;
+
+/// The lowest replication protocol version any namespace requires.
+///
+/// Both protocol versions currently in use can replicate any namespace.
+pub const MIN_REPLICATION_VERSION: u8 = 2;
+
+/// The version of the collaborative object change format supported by this
+/// version of link.
+pub const COB_VERSION: u32 = 1;
+
+/// The object format supported by this version of link.
+pub const OBJECT_FORMAT: ObjectFormat = ObjectFormat::Sha1;
+
+pub mod error {
+    use std::{fmt, io};
+
+    use thiserror::Error;
+
+    use super::Unmet;
+    use crate::{
+        git::storage::{json, read},
+        identities::git::Urn,
+    };
+
+    pub use json::error::Load as Read;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Record {
+        #[error(transparent)]
+        Read(#[from] Read),
+
+        #[error(transparent)]
+        Snapshot(#[from] read::Error),
+
+        #[error(transparent)]
+        Store(#[from] json::error::Store),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+
+    /// The namespace `urn` has requirements this version of link does not
+    /// meet.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Incompatible {
+        pub urn: Urn,
+        pub unmet: Vec<Unmet>,
+    }
+
+    impl fmt::Display for Incompatible {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "incompatible namespace {}: ", self.urn)?;
+            for (i, unmet) in self.unmet.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", unmet)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl std::error::Error for Incompatible {}
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Check {
+        #[error(transparent)]
+        Incompatible(#[from] Incompatible),
+
+        #[error(transparent)]
+        Read(#[from] Read),
+    }
+}
+
+/// The hash algorithm used to address the objects of a repository.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectFormat {
+    Sha1,
+    Sha256,
+}
+
+impl fmt::Display for ObjectFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+        })
+    }
+}
+
+/// What a version of link must support to serve or fetch a namespace.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Requirements {
+    /// The object format of the repository.
+    pub object_format: ObjectFormat,
+    /// The minimum replication protocol version.
+    pub replication: u8,
+    /// The minimum change format version of the collaborative objects, by
+    /// type name.
+    #[serde(default)]
+    pub cobs: BTreeMap<String, u32>,
+}
+
+impl Default for Requirements {
+    fn default() -> Self {
+        Self {
+            object_format: OBJECT_FORMAT,
+            replication: MIN_REPLICATION_VERSION,
+            cobs: BTreeMap::new(),
+        }
+    }
+}
+
+impl Requirements {
+    /// The requirements which are not met by this version of link.
+    pub fn unmet(&self) -> Vec<Unmet> {
+        let mut unmet = Vec::new();
+        if self.object_format != OBJECT_FORMAT {
+            unmet.push(Unmet::ObjectFormat {
+                required: self.object_format,
+                supported: OBJECT_FORMAT,
+            });
+        }
+        if self.replication > REPLICATION_VERSION {
+            unmet.push(Unmet::Replication {
+                required: self.replication,
+                supported: REPLICATION_VERSION,
+            });
+        }
+        for (typename, version) in &self.cobs {
+            if *version > COB_VERSION {
+                unmet.push(Unmet::Cob {
+                    typename: typename.clone(),
+                    required: *version,
+                    supported: COB_VERSION,
+                });
+            }
+        }
+        unmet
+    }
+
+    /// Combine `self` with `other`, retaining the higher version of each
+    /// requirement. The object format is taken from `other`.
+    pub fn merge(mut self, other: Self) -> Self {
+        self.object_format = other.object_format;
+        self.replication = self.replication.max(other.replication);
+        for (typename, version) in other.cobs {
+            let v = self.cobs.entry(typename).or_default();
+            *v = (*v).max(version);
+        }
+        self
+    }
+}
+
+/// A requirement not met by this version of link.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Unmet {
+    ObjectFormat {
+        required: ObjectFormat,
+        supported: ObjectFormat,
+    },
+    Replication {
+        required: u8,
+        supported: u8,
+    },
+    Cob {
+        typename: String,
+        required: u32,
+        supported: u32,
+    },
+}
+
+impl fmt::Display for Unmet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ObjectFormat {
+                required,
+                supported,
+            } => write!(
+                f,
+                "object format `{}` required, `{}` supported",
+                required, supported
+            ),
+            Self::Replication {
+                required,
+                supported,
+            } => write!(
+                f,
+                "replication v{} required, v{} supported",
+                required, supported
+            ),
+            Self::Cob {
+                typename,
+                required,
+                supported,
+            } => write!(
+                f,
+                "collaborative object `{}` v{} required, v{} supported",
+                typename, required, supported
+            ),
+        }
+    }
+}
+
+/// Check the recorded [`Requirements`] of the namespace `urn` of the storage
+/// at `storage_path` against this version of link.
+///
+/// Namespaces without recorded requirements are assumed to be compatible.
+/// This does not need to open the storage, and so is suitable for checking
+/// a namespace before serving it.
+pub fn check(storage_path: &Path, urn: &Urn) -> Result<(), error::Check> {
+    let urn = urn.clone().with_path(None);
+    match STORE.load::<Requirements>(storage_path, &urn)? {
+        None => Ok(()),
+        Some(reqs) => {
+            let unmet = reqs.unmet();
+            if unmet.is_empty() {
+                Ok(())
+            } else {
+                Err(error::Incompatible { urn, unmet }.into())
+            }
+        },
+    }
+}
+
+/// Like [`check`], but only fails if the namespace is
+/// [`error::Incompatible`].
+///
+/// Requirements which can't be read are logged and otherwise ignored, as
+/// they are recorded afresh by the next write to the namespace.
+pub fn ensure_compatible(storage_path: &Path, urn: &Urn) -> Result<(), error::Incompatible> {
+    match check(storage_path, urn) {
+        Err(error::Check::Incompatible(e)) => Err(e),
+        Err(e) => {
+            tracing::warn!(urn = %urn, err = %e, "failed to read namespace requirements");
+            Ok(())
+        },
+        Ok(()) => Ok(()),
+    }
+}
+
+impl ReadOnly {
+    /// The [`Requirements`] of the namespace `urn`, as of the last time it was
+    /// written to.
+    ///
+    /// Returns `None` if no requirements were recorded for the namespace.
+    pub fn requirements(&self, urn: &Urn) -> Result<Option<Requirements>, error::Read> {
+        STORE.load(self.path(), &urn.clone().with_path(None))
+    }
+
+    /// Check the [`Requirements`] of the namespace `urn`, see [`check`].
+    pub fn check_requirements(&self, urn: &Urn) -> Result<(), error::Check> {
+        check(self.path(), urn)
+    }
+}
+
+impl Storage {
+    /// Determine the [`Requirements`] of the namespace `urn` from its
+    /// content, and record them, combined with the previously recorded ones.
+    ///
+    /// Returns `None`, and removes any recorded requirements, if the
+    /// namespace does not exist.
+    pub fn record_requirements(&self, urn: &Urn) -> Result<Option<Requirements>, error::Record> {
+        let urn = urn.clone().with_path(None);
+        let snapshot = self.read_only().snapshot(&urn)?;
+        if snapshot.is_empty() {
+            STORE.remove(self.path(), &urn)?;
+            return Ok(None);
+        }
+
+        let mut determined = Requirements::default();
+        for (name, _) in &snapshot {
+            let mut components = name.as_str().split('/').skip(1);
+            let mut next = components.next();
+            if next == Some("remotes") {
+                components.next();
+                next = components.next();
+            }
+            if next == Some("cobs") {
+                if let Some(typename) = components.next() {
+                    determined.cobs.insert(typename.to_owned(), COB_VERSION);
+                }
+            }
+        }
+
+        let reqs = match STORE.load::<Requirements>(self.path(), &urn)? {
+            None => determined,
+            Some(recorded) => recorded.merge(determined),
+        };
+        STORE.store(self.path(), &urn, &reqs)?;
+
+        Ok(Some(reqs))
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Point-in-time views of the references of a namespace.
+//!
+//! A [`super::ReadOnly`] may be opened while another process (typically the
+//! node daemon) holds the write role for the monorepo. Reference updates are
+//! atomic individually, but a reader resolving several references one after
+//! the other may observe some of them before, and some after a concurrent
+//! update. A [`Snapshot`] captures all references of a namespace up-front,
+//! so that an operation answering several questions about the namespace sees
+//! a consistent view.
+
+use std::collections::{btree_map, BTreeMap};
+
+use git_ext::{self as ext, reference, RefLike};
+
+use super::{read, ReadOnly, ReadOnlyStorage as _};
+use crate::{
+    git::types::{Namespace, Reference},
+    identities::git::Urn,
+};
+
+/// Number of times the references are re-read before giving up on a stable
+/// view.
+const MAX_ATTEMPTS: usize = 5;
+
+/// The references of a namespace, as of the time [`ReadOnly::snapshot`] was
+/// called.
+///
+/// Reference names are stored relative to the namespace, ie. `refs/rad/id`
+/// rather than `refs/namespaces/<id>/refs/rad/id`. The objects the
+/// references point to can still be looked up in the storage, as objects are
+/// never removed while references to them exist.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    urn: Urn,
+    refs: BTreeMap<RefLike, ext::Oid>,
+}
+
+impl Snapshot {
+    /// The [`Urn`] of the namespace this snapshot was taken of.
+    pub fn urn(&self) -> &Urn {
+        &self.urn
+    }
+
+    /// Look up the target of `reference`, relative to the namespace.
+    pub fn get(&self, reference: &RefLike) -> Option<ext::Oid> {
+        self.refs.get(reference).copied()
+    }
+
+    /// Look up the target of a fully qualified reference, eg. a
+    /// [`Reference`] in this namespace.
+    ///
+    /// Returns `None` if the reference does not exist in the snapshot, or if
+    /// it belongs to a different namespace.
+    pub fn reference<'a, Ref>(&self, reference: &'a Ref) -> Option<ext::Oid>
+    where
+        RefLike: From<&'a Ref>,
+    {
+        let prefix = namespace_prefix(&self.urn);
+        RefLike::from(reference)
+            .as_str()
+            .strip_prefix(&prefix)
+            .and_then(|name| name.parse::<RefLike>().ok())
+            .and_then(|name| self.get(&name))
+    }
+
+    /// The target of `rad/id` of the namespace, if any.
+    pub fn tip(&self) -> Option<ext::Oid> {
+        self.reference(&Reference::rad_id(Namespace::from(&self.urn)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.refs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.refs.is_empty()
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<'_, RefLike, ext::Oid> {
+        self.refs.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Snapshot {
+    type Item = (&'a RefLike, &'a ext::Oid);
+    type IntoIter = btree_map::Iter<'a, RefLike, ext::Oid>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl ReadOnly {
+    /// Take a [`Snapshot`] of the references of the namespace `urn`.
+    ///
+    /// The references are read repeatedly until two consecutive reads agree,
+    /// which guards against observing a concurrent multi-reference update
+    /// half-way. If the namespace keeps changing, the last read is returned.
+    #[tracing::instrument(level = "debug", skip(self, urn), fields(urn = %urn))]
+    pub fn snapshot(&self, urn: &Urn) -> Result<Snapshot, read::Error> {
+        let urn = urn.clone().with_path(None);
+        let mut refs = self.namespace_refs(&urn)?;
+        for _ in 1..MAX_ATTEMPTS {
+            let again = self.namespace_refs(&urn)?;
+            if again == refs {
+                return Ok(Snapshot { urn, refs });
+            }
+            refs = again;
+        }
+
+        tracing::warn!("namespace did not settle, snapshot may be inconsistent");
+        Ok(Snapshot { urn, refs })
+    }
+
+    fn namespace_refs(&self, urn: &Urn) -> Result<BTreeMap<RefLike, ext::Oid>, read::Error> {
+        let prefix = namespace_prefix(urn);
+        let glob = globset::Glob::new(&format!("{}*", prefix))
+            .unwrap()
+            .compile_matcher();
+
+        let mut refs = BTreeMap::new();
+        for r in self.references_glob(glob)? {
+            if let Some((name, oid)) = reference::peeled(r?) {
+                if let Some(name) = name
+                    .strip_prefix(&prefix)
+                    .and_then(|name| name.parse::<RefLike>().ok())
+                {
+                    refs.insert(name, ext::Oid::from(oid));
+                }
+            }
+        }
+
+        Ok(refs)
+    }
+}
+
+pub(super) fn namespace_prefix(urn: &Urn) -> String {
+    format!("refs/namespaces/{}/", Namespace::from(urn))
+}
@@ -0,0 +1,203 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Per-namespace statistics.
+//!
+//! Answering questions like "how much space does this project take up" from
+//! the monorepo requires walking the history of every reference of the
+//! namespace. Instead, [`Stats`] are updated incrementally whenever the
+//! namespace is written to (see [`Storage::reindex`]): only the commits
+//! reachable from the new tips, but not from the tips seen by the previous
+//! update, are visited.
+//!
+//! The stats are stored as JSON under `stats/`, relative to the storage
+//! directory, one file per namespace.
+//!
+//! Note that [`Stats::disk_usage`] is an estimate: it sums the uncompressed
+//! sizes of the commits and of the blobs they introduce, and never shrinks
+//! when history is rewritten. Objects shared between namespaces are counted
+//! once per namespace.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use git_ext as ext;
+
+use super::{json, ReadOnly, Storage};
+use crate::identities::git::Urn;
+
+/// The name of the stats directory, relative to the storage directory.
+pub const DIR_NAME: &str = "stats";
+
+const STORE: json::Store = json::Store::new(DIR_NAME, "stats");
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use crate::git::storage::{json, read};
+
+    pub use json::error::Load as Read;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Update {
+        #[error(transparent)]
+        Read(#[from] Read),
+
+        #[error(transparent)]
+        Snapshot(#[from] read::Error),
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Store(#[from] json::error::Store),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+/// Statistics of a single namespace.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Stats {
+    /// Number of remotes, ie. distinct peers under `refs/remotes`.
+    pub remotes: usize,
+    /// Seconds since the Unix epoch at which the stats were last updated.
+    pub updated: u64,
+    /// Estimated size in bytes of the objects of the namespace.
+    pub disk_usage: u64,
+    /// Number of collaborative objects, by type name. Objects are counted
+    /// once, regardless of how many remotes have a copy.
+    pub cobs: BTreeMap<String, usize>,
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct Record {
+    stats: Stats,
+    /// The tips visited by the last update.
+    tips: BTreeSet<ext::Oid>,
+}
+
+impl ReadOnly {
+    /// The [`Stats`] of the namespace `urn`, as of the last time it was
+    /// written to.
+    ///
+    /// Returns `None` if no stats were recorded for the namespace.
+    pub fn stats(&self, urn: &Urn) -> Result<Option<Stats>, error::Read> {
+        let urn = urn.clone().with_path(None);
+        Ok(STORE
+            .load::<Record>(self.path(), &urn)?
+            .map(|record| record.stats))
+    }
+}
+
+impl Storage {
+    /// Update the [`Stats`] of the namespace `urn`.
+    ///
+    /// Returns `None`, and removes any recorded stats, if the namespace does
+    /// not exist.
+    pub fn update_stats(&self, urn: &Urn) -> Result<Option<Stats>, error::Update> {
+        let urn = urn.clone().with_path(None);
+        let snapshot = self.read_only().snapshot(&urn)?;
+        if snapshot.is_empty() {
+            STORE.remove(self.path(), &urn)?;
+            return Ok(None);
+        }
+
+        let Record { stats, tips } = STORE.load(self.path(), &urn)?.unwrap_or_default();
+
+        let mut remotes = BTreeSet::new();
+        let mut cobs = BTreeSet::new();
+        for (name, _) in &snapshot {
+            let mut components = name.as_str().split('/').skip(1);
+            let mut next = components.next();
+            if next == Some("remotes") {
+                remotes.extend(components.next());
+                next = components.next();
+            }
+            if next == Some("cobs") {
+                if let (Some(typename), Some(id)) = (components.next(), components.next()) {
+                    cobs.insert((typename, id));
+                }
+            }
+        }
+        let mut by_type = BTreeMap::new();
+        for (typename, _) in cobs {
+            *by_type.entry(typename.to_owned()).or_default() += 1;
+        }
+
+        let new_tips = snapshot
+            .iter()
+            .map(|(_, oid)| *oid)
+            .collect::<BTreeSet<_>>();
+        let added = self.introduced_bytes(&tips, &new_tips)?;
+
+        let record = Record {
+            stats: Stats {
+                remotes: remotes.len(),
+                updated: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default(),
+                disk_usage: stats.disk_usage.saturating_add(added),
+                cobs: by_type,
+            },
+            tips: new_tips,
+        };
+        STORE.store(self.path(), &urn, &record)?;
+
+        Ok(Some(record.stats))
+    }
+
+    /// Sum the sizes of the commits reachable from `new`, but not from `old`,
+    /// and of the blobs they introduce.
+    fn introduced_bytes(
+        &self,
+        old: &BTreeSet<ext::Oid>,
+        new: &BTreeSet<ext::Oid>,
+    ) -> Result<u64, git2::Error> {
+        let repo = self.as_raw();
+        let odb = repo.odb()?;
+        let mut walk = repo.revwalk()?;
+        let mut pushed = false;
+        for oid in new.difference(old) {
+            // Not all tips are commits, eg. tags pointing to blobs
+            if walk.push(**oid).is_ok() {
+                pushed = true;
+            }
+        }
+        if !pushed {
+            return Ok(0);
+        }
+        for oid in old {
+            // Tips which were removed since may no longer exist
+            walk.hide(**oid).ok();
+        }
+
+        let mut bytes = 0u64;
+        for oid in walk {
+            let commit = repo.find_commit(oid?)?;
+            let (size, _) = odb.read_header(commit.id())?;
+            bytes = bytes.saturating_add(size as u64);
+
+            let tree = commit.tree()?;
+            let parent = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree()?),
+                Err(_) => None,
+            };
+            let diff = repo.diff_tree_to_tree(parent.as_ref(), Some(&tree), None)?;
+            for delta in diff.deltas() {
+                bytes = bytes.saturating_add(delta.new_file().size());
+            }
+        }
+
+        Ok(bytes)
+    }
+}
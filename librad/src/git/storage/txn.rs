@@ -0,0 +1,369 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Atomic updates of multiple refs within a namespace.
+//!
+//! A [`Transaction`] collects ref creations, updates and deletions in a single
+//! namespace, across categories (eg. `refs/heads`, `refs/rad`,
+//! `refs/remotes`), and applies them all or none. Each update may state the
+//! value it expects the ref to have, in which case the whole transaction is
+//! rejected if it has a different one.
+//!
+//! All updated refs share the same reflog message, and a single
+//! [`audit::Event::RefsUpdated`] is recorded on commit. The namespace is
+//! reindexed once afterwards.
+//!
+//! Committing takes [`Storage::lock_namespace`] for the duration of the
+//! commit, and checks its lease, if any, once all updated refs are locked,
+//! right before the git transaction is committed.
+//! Multi-step updates, which read the namespace before building the
+//! transaction, should hold it throughout.
+//!
+//! ```no_run
+//! # use librad::{git::{storage::{Storage, txn::Previous}, Urn}, reflike};
+//! # fn f(storage: &Storage, urn: &Urn, a: git2::Oid, b: git2::Oid) -> Result<(), Box<dyn std::error::Error>> {
+//! storage
+//!     .transaction(urn, "publish release")
+//!     .write(reflike!("refs/heads/main"), a, Previous::MustExist)
+//!     .write(reflike!("refs/tags/v1.0"), b, Previous::MustNotExist)
+//!     .delete(reflike!("refs/heads/release-candidate"), Previous::Any)
+//!     .commit()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+
+use git_ext as ext;
+
+use super::{audit, lock, Storage};
+use crate::identities::git::Urn;
+
+pub mod error {
+    use thiserror::Error;
+
+    use super::{ext, Previous};
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Commit {
+        #[error("`{0}` is not a ref name below `refs/`")]
+        Name(ext::RefLike),
+
+        #[error("`{refname}` was expected to be {expected}, but is {}", display_oid(.actual))]
+        Rejected {
+            refname: String,
+            expected: Previous,
+            actual: Option<ext::Oid>,
+        },
+
+        #[error("symbolic target `{target}` of `{refname}` does not exist")]
+        MissingTarget { refname: String, target: String },
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+
+        #[error(transparent)]
+        Lease(#[from] crate::git::storage::lease::Error),
+
+        #[error(transparent)]
+        NamespaceLock(#[from] crate::git::storage::lock::Error),
+    }
+
+    fn display_oid(oid: &Option<ext::Oid>) -> String {
+        oid.map_or_else(|| "absent".to_owned(), |oid| format!("at {}", oid))
+    }
+}
+
+/// The value a ref is expected to have before it is updated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Previous {
+    /// Any value, including absent.
+    Any,
+    /// The ref must not exist.
+    MustNotExist,
+    /// The ref must exist, with any value.
+    MustExist,
+    /// The ref must point to the given object.
+    MustBe(ext::Oid),
+}
+
+impl fmt::Display for Previous {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => f.write_str("anything"),
+            Self::MustNotExist => f.write_str("absent"),
+            Self::MustExist => f.write_str("present"),
+            Self::MustBe(oid) => write!(f, "at {}", oid),
+        }
+    }
+}
+
+impl Previous {
+    fn allows(&self, actual: Option<ext::Oid>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::MustNotExist => actual.is_none(),
+            Self::MustExist => actual.is_some(),
+            Self::MustBe(oid) => actual == Some(*oid),
+        }
+    }
+}
+
+/// A single update of a [`Transaction`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Update {
+    /// Point `name` to `target`.
+    Write {
+        name: ext::RefLike,
+        target: ext::Oid,
+        previous: Previous,
+    },
+    /// Point `name` to the ref `target`, which is a fully qualified name and
+    /// may be in a different namespace.
+    Symbolic {
+        name: ext::RefLike,
+        target: ext::RefLike,
+        previous: Previous,
+    },
+    /// Delete `name`.
+    Delete {
+        name: ext::RefLike,
+        previous: Previous,
+    },
+}
+
+impl Update {
+    pub fn name(&self) -> &ext::RefLike {
+        match self {
+            Self::Write { name, .. } | Self::Symbolic { name, .. } | Self::Delete { name, .. } => {
+                name
+            },
+        }
+    }
+
+    fn previous(&self) -> Previous {
+        match self {
+            Self::Write { previous, .. }
+            | Self::Symbolic { previous, .. }
+            | Self::Delete { previous, .. } => *previous,
+        }
+    }
+}
+
+/// The outcome of a committed [`Transaction`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Committed {
+    /// The fully qualified names of the refs which were written or deleted.
+    pub refs: Vec<String>,
+}
+
+/// A batch of ref updates in the namespace of a [`Urn`], applied atomically
+/// by [`Transaction::commit`].
+///
+/// Ref names are relative to the namespace, eg. `refs/heads/main` or
+/// `refs/remotes/<peer>/rad/id`.
+#[must_use = "transactions must be committed"]
+pub struct Transaction<'a> {
+    storage: &'a Storage,
+    urn: Urn,
+    message: String,
+    updates: Vec<Update>,
+}
+
+impl<'a> Transaction<'a> {
+    pub(super) fn new(storage: &'a Storage, urn: &Urn, message: String) -> Self {
+        Self {
+            storage,
+            urn: urn.clone().with_path(None),
+            message,
+            updates: Vec::new(),
+        }
+    }
+
+    pub fn urn(&self) -> &Urn {
+        &self.urn
+    }
+
+    pub fn updates(&self) -> &[Update] {
+        &self.updates
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    pub fn push(mut self, update: Update) -> Self {
+        self.updates.push(update);
+        self
+    }
+
+    pub fn write(
+        self,
+        name: impl Into<ext::RefLike>,
+        target: impl Into<ext::Oid>,
+        previous: Previous,
+    ) -> Self {
+        self.push(Update::Write {
+            name: name.into(),
+            target: target.into(),
+            previous,
+        })
+    }
+
+    pub fn symbolic(
+        self,
+        name: impl Into<ext::RefLike>,
+        target: impl Into<ext::RefLike>,
+        previous: Previous,
+    ) -> Self {
+        self.push(Update::Symbolic {
+            name: name.into(),
+            target: target.into(),
+            previous,
+        })
+    }
+
+    pub fn delete(self, name: impl Into<ext::RefLike>, previous: Previous) -> Self {
+        self.push(Update::Delete {
+            name: name.into(),
+            previous,
+        })
+    }
+
+    /// Apply all updates, or none if any of them is rejected.
+    ///
+    /// Committing an empty transaction is a no-op.
+    pub fn commit(self) -> Result<Committed, error::Commit> {
+        if self.updates.is_empty() {
+            return Ok(Committed { refs: vec![] });
+        }
+
+        let lock = self.storage.lock_namespace(&self.urn)?;
+        let namespace = reflike!("refs/namespaces").join(&self.urn);
+        let updates = self
+            .updates
+            .into_iter()
+            .map(|update| {
+                let name = update.name();
+                if !name.as_str().starts_with("refs/") {
+                    return Err(error::Commit::Name(name.clone()));
+                }
+                let name = namespace.join(name);
+                Ok(match update {
+                    Update::Write {
+                        target, previous, ..
+                    } => Update::Write {
+                        name,
+                        target,
+                        previous,
+                    },
+                    Update::Symbolic {
+                        target, previous, ..
+                    } => Update::Symbolic {
+                        name,
+                        target,
+                        previous,
+                    },
+                    Update::Delete { previous, .. } => Update::Delete { name, previous },
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let refs = apply(
+            self.storage.as_raw(),
+            &lock,
+            updates.iter().map(|update| (update, self.message.as_str())),
+        )?;
+
+        self.storage.audit(audit::Event::RefsUpdated {
+            urn: self.urn.clone(),
+            message: self.message,
+            refs: refs.clone(),
+        });
+        self.storage.reindex(&self.urn);
+
+        Ok(Committed { refs })
+    }
+}
+
+/// Apply `updates` of fully qualified refs in a single git transaction, each
+/// with its own reflog message.
+///
+/// The leases of `lock` are checked once all refs are locked, so they can't be
+/// lost between the check and the commit.
+///
+/// Returns the names of the refs which were written or deleted. Unlike
+/// [`Transaction::commit`], this neither records an audit event nor
+/// reindexes, which is left to the caller.
+pub(crate) fn apply<'a>(
+    repo: &git2::Repository,
+    lock: &lock::Guard,
+    updates: impl IntoIterator<Item = (&'a Update, &'a str)>,
+) -> Result<Vec<String>, error::Commit> {
+    let mut txn = repo.transaction()?;
+    let mut refs = Vec::new();
+    for (update, message) in updates {
+        let refname = update.name().to_string();
+        txn.lock_ref(&refname)?;
+
+        let actual = match repo.find_reference(&refname) {
+            Ok(r) => r
+                .resolve()
+                .ok()
+                .and_then(|r| r.target())
+                .map(ext::Oid::from),
+            Err(e) if ext::is_not_found_err(&e) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let expected = update.previous();
+        if !expected.allows(actual) {
+            return Err(error::Commit::Rejected {
+                refname,
+                expected,
+                actual,
+            });
+        }
+
+        match update {
+            Update::Write { target, .. } => {
+                repo.reference_ensure_log(&refname)?;
+                txn.set_target(&refname, **target, None, message)?
+            },
+            Update::Symbolic { target, .. } => {
+                if let Err(e) = repo.refname_to_id(target.as_str()) {
+                    return Err(if ext::is_not_found_err(&e) {
+                        error::Commit::MissingTarget {
+                            refname,
+                            target: target.to_string(),
+                        }
+                    } else {
+                        e.into()
+                    });
+                }
+                repo.reference_ensure_log(&refname)?;
+                txn.set_symbolic_target(&refname, target.as_str(), None, message)?
+            },
+            Update::Delete { .. } => {
+                if actual.is_none() {
+                    continue;
+                }
+                txn.remove(&refname)?
+            },
+        }
+        refs.push(refname);
+    }
+    lock.check()?;
+    txn.commit()?;
+
+    Ok(refs)
+}
+
+impl Storage {
+    /// Start a [`Transaction`] in the namespace of `urn`, whose reflog
+    /// entries will carry `message`.
+    pub fn transaction(&self, urn: &Urn, message: impl Into<String>) -> Transaction<'_> {
+        Transaction::new(self, urn, message.into())
+    }
+}
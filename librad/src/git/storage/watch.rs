@@ -6,14 +6,16 @@
 use std::{
     fs,
     io,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     sync::{mpsc, Arc},
+    time::Duration,
 };
 
 use notify::Watcher as _;
 use thiserror::Error;
 
 use super::Storage;
+use crate::identities::git::Urn;
 
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -134,4 +136,71 @@ impl<'a> Watch<'a> {
 
         Ok((Watcher(Arc::new(watcher)), rx))
     }
+
+    /// Watch for updates of the `rad/signed_refs` of the local peer in any
+    /// namespace, eg. after a `git push` via `git-remote-rad` or `lnk-gitd`.
+    ///
+    /// Like [`Watch::namespaces`], this is implemented by watching reflogs,
+    /// albeit recursively, and so is subject to the same caveats. Events are
+    /// debounced by `delay`: multiple updates of the same namespace within
+    /// `delay` yield only one [`Urn`].
+    ///
+    /// Updates of the `rad/signed_refs` of remote peers are ignored.
+    pub fn signed_refs(
+        &self,
+        delay: Duration,
+    ) -> Result<(Watcher, impl Iterator<Item = Urn>), Error> {
+        use notify::{DebouncedEvent, RecursiveMode::Recursive};
+
+        fn signed_refs_urn(p: &Path) -> Option<Urn> {
+            let mut iter = p.components().map(|c| match c {
+                Component::Normal(x) => x.to_str(),
+                _ => None,
+            });
+            let prefix = [Some("refs"), Some("namespaces")];
+            if iter.by_ref().take(2).ne(prefix.iter().copied()) {
+                return None;
+            }
+            let id = iter.next()??;
+            let suffix = [Some("refs"), Some("rad"), Some("signed_refs")];
+            if iter.ne(suffix.iter().copied()) {
+                return None;
+            }
+            Urn::try_from_id(id).ok()
+        }
+
+        let repo_path = self.storage.path().to_owned();
+        let reflogs_path = repo_path.join("logs");
+        let namespaces_path = reflogs_path.join("refs/namespaces");
+
+        if !namespaces_path.exists() {
+            fs::create_dir_all(&namespaces_path)?;
+        }
+
+        let (tx, rx) = mpsc::channel();
+
+        let mut watcher = notify::watcher(tx, delay)?;
+        watcher.watch(&namespaces_path, Recursive)?;
+
+        let rx = rx.into_iter().filter_map(move |evt| {
+            tracing::trace!("{:?}", evt);
+
+            match evt {
+                DebouncedEvent::Create(path) | DebouncedEvent::Write(path) => {
+                    signed_refs_urn(path.strip_prefix(&reflogs_path).ok()?)
+                },
+                DebouncedEvent::Rename(_, path) => {
+                    signed_refs_urn(path.strip_prefix(&reflogs_path).ok()?)
+                },
+                DebouncedEvent::Error(e, path) => {
+                    tracing::warn!(err = %e, ?path, "error watching signed refs");
+                    None
+                },
+
+                _ => None,
+            }
+        });
+
+        Ok((Watcher(Arc::new(watcher)), rx))
+    }
 }
@@ -5,8 +5,10 @@
 
 pub use crate::identities::git::Urn;
 
+pub mod contacts;
 mod odb;
 mod refdb;
+pub mod subkeys;
 pub mod v1;
 
 pub use link_tracking::{
@@ -0,0 +1,273 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Local metadata about tracked peers.
+//!
+//! A [`Contact`] attaches a free-form alias, a note and a [`Trust`] level to
+//! a [`PeerId`], so that they can be shown instead of the raw peer id, eg.
+//! "Alice (laptop)". Contacts are recorded in the storage config as
+//! `rad.contact.<peer-id>.<field>`. Like tracking entries, they are local:
+//! they are not replicated, and are independent of whether the peer is
+//! actually tracked for any URN.
+//!
+//! The contact list can be moved between peers using [`export`] and
+//! [`import`], which (de)serialise it as [`Contacts`].
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::{tracked_peers, Urn};
+use crate::{
+    git::storage::{config, ReadOnly, Storage},
+    PeerId,
+};
+
+pub mod error {
+    use thiserror::Error;
+
+    use crate::git::{storage::config, tracking};
+
+    #[derive(Debug, Error)]
+    #[error("unknown trust level `{0}`")]
+    pub struct Trust(pub String);
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Tracked {
+        #[error(transparent)]
+        Config(#[from] config::Error),
+
+        #[error(transparent)]
+        TrackedPeers(#[from] tracking::error::TrackedPeers),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Import {
+        #[error("unsupported contact list version {0}")]
+        Version(u8),
+
+        #[error(transparent)]
+        Config(#[from] config::Error),
+    }
+}
+
+/// How much a peer is trusted by the local user.
+///
+/// This is purely informational, and does not affect replication.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trust {
+    /// Known not to be trustworthy.
+    Never,
+    /// No trust decision was made.
+    Unknown,
+    /// Somewhat trusted, eg. an acquaintance.
+    Marginal,
+    /// Fully trusted, eg. one's own device.
+    Full,
+}
+
+impl Default for Trust {
+    fn default() -> Self {
+        Self::Unknown
+    }
+}
+
+impl fmt::Display for Trust {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Never => "never",
+            Self::Unknown => "unknown",
+            Self::Marginal => "marginal",
+            Self::Full => "full",
+        })
+    }
+}
+
+impl FromStr for Trust {
+    type Err = error::Trust;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(Self::Never),
+            "unknown" => Ok(Self::Unknown),
+            "marginal" => Ok(Self::Marginal),
+            "full" => Ok(Self::Full),
+            _ => Err(error::Trust(s.to_owned())),
+        }
+    }
+}
+
+/// Local metadata about a peer.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contact {
+    /// A human-readable name for the peer, eg. "Alice (laptop)".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    /// Free-form notes about the peer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub trust: Trust,
+}
+
+impl Contact {
+    /// Whether none of the fields are set, in which case the contact is not
+    /// stored at all.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// The version of the [`Contacts`] format produced by [`export`].
+pub const CONTACTS_VERSION: u8 = 1;
+
+/// A contact list, as exchanged via [`export`] and [`import`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Contacts {
+    pub version: u8,
+    pub contacts: BTreeMap<PeerId, Contact>,
+}
+
+/// How [`import`] treats peers which already have a [`Contact`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Keep the existing contact.
+    Keep,
+    /// Replace the existing contact with the imported one.
+    Replace,
+}
+
+/// The [`Contact`] of `peer`, if any.
+pub fn get<S>(storage: &S, peer: &PeerId) -> Result<Option<Contact>, config::Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    storage.as_ref().config()?.contact(peer)
+}
+
+/// Set the [`Contact`] of `peer`, replacing any previous one.
+///
+/// Setting an [empty][Contact::is_empty] contact removes it.
+pub fn set(storage: &Storage, peer: &PeerId, contact: &Contact) -> Result<(), config::Error> {
+    let mut config = storage.config()?;
+    if contact.is_empty() {
+        config.remove_contact(peer)
+    } else {
+        config.set_contact(peer, contact)
+    }
+}
+
+/// Remove the [`Contact`] of `peer`, returning it if it existed.
+pub fn remove(storage: &Storage, peer: &PeerId) -> Result<Option<Contact>, config::Error> {
+    let mut config = storage.config()?;
+    let contact = config.contact(peer)?;
+    if contact.is_some() {
+        config.remove_contact(peer)?;
+    }
+    Ok(contact)
+}
+
+/// All contacts, sorted by peer id.
+pub fn list<S>(storage: &S) -> Result<Vec<(PeerId, Contact)>, config::Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    Ok(storage.as_ref().config()?.contacts()?.into_iter().collect())
+}
+
+/// The peers whose alias is `alias`, ignoring case.
+pub fn find<S>(storage: &S, alias: &str) -> Result<Vec<PeerId>, config::Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    Ok(list(storage)?
+        .into_iter()
+        .filter_map(|(peer, contact)| {
+            contact
+                .alias
+                .filter(|a| a.eq_ignore_ascii_case(alias))
+                .map(|_| peer)
+        })
+        .collect())
+}
+
+/// The alias of `peer` if it has one, otherwise its peer id.
+pub fn display_name<S>(storage: &S, peer: &PeerId) -> Result<String, config::Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    Ok(get(storage, peer)?
+        .and_then(|contact| contact.alias)
+        .unwrap_or_else(|| peer.to_string()))
+}
+
+/// The peers tracked for `urn`, or for any URN if `None`, along with their
+/// [`Contact`]s.
+pub fn tracked<S>(
+    storage: &S,
+    urn: Option<&Urn>,
+) -> Result<Vec<(PeerId, Option<Contact>)>, error::Tracked>
+where
+    S: AsRef<ReadOnly>,
+{
+    let storage = storage.as_ref();
+    let mut contacts = storage.config()?.contacts()?;
+    let mut peers = tracked_peers(storage, urn)?.collect::<Result<Vec<_>, _>>()?;
+    peers.sort();
+    peers.dedup();
+
+    Ok(peers
+        .into_iter()
+        .map(|peer| {
+            let contact = contacts.remove(&peer);
+            (peer, contact)
+        })
+        .collect())
+}
+
+/// The contact list, for [`import`] on another peer.
+pub fn export<S>(storage: &S) -> Result<Contacts, config::Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    Ok(Contacts {
+        version: CONTACTS_VERSION,
+        contacts: storage.as_ref().config()?.contacts()?,
+    })
+}
+
+/// Import a contact list produced by [`export`].
+///
+/// Returns the peers whose contact was created or replaced.
+pub fn import(
+    storage: &Storage,
+    contacts: Contacts,
+    on_conflict: OnConflict,
+) -> Result<Vec<PeerId>, error::Import> {
+    if contacts.version != CONTACTS_VERSION {
+        return Err(error::Import::Version(contacts.version));
+    }
+
+    let mut config = storage.config()?;
+    let mut imported = Vec::new();
+    for (peer, contact) in contacts.contacts {
+        if contact.is_empty() {
+            continue;
+        }
+        match config.contact(&peer)? {
+            Some(existing) if existing == contact => continue,
+            Some(_) if on_conflict == OnConflict::Keep => continue,
+            _ => {
+                config.set_contact(&peer, &contact)?;
+                imported.push(peer);
+            },
+        }
+    }
+
+    Ok(imported)
+}
@@ -3,6 +3,8 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+use std::collections::BTreeSet;
+
 use git_ext::reference::name::RefspecPattern;
 use git_ref_format::{refspec, RefString};
 use link_tracking::git::{
@@ -12,7 +14,16 @@ use link_tracking::git::{
 
 use crate::{
     git::{
-        storage::{glob, read, ReadOnly, ReadOnlyStorage, Storage},
+        storage::{
+            audit,
+            glob,
+            read,
+            reflog::{Message, Op},
+            txn::{self, Previous},
+            ReadOnly,
+            ReadOnlyStorage,
+            Storage,
+        },
         Urn,
     },
     git_ext as ext,
@@ -24,7 +35,10 @@ pub mod error {
 
     use link_tracking::git::tracking::reference;
 
-    use crate::{git::storage::read, git_ext as ext};
+    use crate::{
+        git::storage::{lease, lock, read},
+        git_ext as ext,
+    };
 
     #[derive(Debug, Error)]
     #[error("the reference was symbolic, but it is expected to be direct")]
@@ -76,6 +90,10 @@ pub mod error {
         },
         #[error(transparent)]
         Read(#[from] read::Error),
+        #[error(transparent)]
+        Lease(#[from] lease::Error),
+        #[error(transparent)]
+        NamespaceLock(#[from] lock::Error),
     }
 
     #[derive(Debug, Error)]
@@ -100,6 +118,12 @@ pub mod error {
         Read(#[from] read::Error),
         #[error(transparent)]
         SymbolicRef(#[from] SymbolicRef),
+        #[error(transparent)]
+        Transaction(#[from] crate::git::storage::txn::error::Commit),
+        #[error(transparent)]
+        Lease(#[from] lease::Error),
+        #[error(transparent)]
+        NamespaceLock(#[from] lock::Error),
         #[error("failed to write reference `{refname}` with target `{target}`")]
         Write {
             refname: String,
@@ -201,15 +225,14 @@ impl Write for Storage {
     where
         I: IntoIterator<Item = Update<'a, Self::Oid>>,
     {
-        let raw = self.as_raw();
-        let mut txn = raw.transaction().map_err(error::Txn::Acquire)?;
+        let updates = updates.into_iter().collect::<Vec<_>>();
+        let lock = self.lock_namespaces(updates.iter().map(|update| match update {
+            Update::Write { name, .. } | Update::Delete { name, .. } => {
+                name.urn.clone().into_owned()
+            },
+        }))?;
         let mut applied = Applied::default();
-        let mut reject_or_update =
-            |apply: Result<Updated<'a, Self::Oid>, PreviousError<Self::Oid>>| match apply {
-                Ok(update) => applied.updates.push(update),
-                Err(rejection) => applied.rejections.push(rejection),
-            };
-
+        let mut writes = Vec::new();
         for update in updates {
             match update {
                 Update::Write {
@@ -217,76 +240,110 @@ impl Write for Storage {
                     target,
                     previous,
                 } => {
-                    let refname = name.to_string();
-                    let message = &format!("writing reference with target `{}`", target);
-                    txn.lock_ref(&refname).map_err(|err| error::Txn::Lock {
-                        refname: refname.clone(),
-                        source: err,
-                    })?;
-                    let set = || -> Result<(), Self::TxnError> {
-                        txn.set_target(&refname, target.into(), None, message)
-                            .map_err(|err| error::Txn::Write {
-                                refname,
-                                target,
-                                source: err,
-                            })
-                    };
-                    match self.reference(&RefString::from(&name))? {
-                        Some(r) => reject_or_update(
-                            previous
-                                .guard(r.target().map(ext::Oid::from).as_ref(), set)?
-                                .map_or(Ok(Updated::Written { name, target }), Err),
-                        ),
-                        None => reject_or_update(
-                            previous
-                                .guard(None, set)?
-                                .map_or(Ok(Updated::Written { name, target }), Err),
-                        ),
+                    let actual = self
+                        .reference(&RefString::from(&name))?
+                        .and_then(|r| r.target())
+                        .map(ext::Oid::from);
+                    match previous.guard(actual.as_ref(), || Ok::<_, error::Txn>(()))? {
+                        Some(rejection) => applied.rejections.push(rejection),
+                        None => {
+                            let msg = Message::new(
+                                Op::Track,
+                                if actual.is_some() {
+                                    "update config"
+                                } else {
+                                    "create"
+                                },
+                            )
+                            .with_peer(name.remote);
+                            writes.push((
+                                txn::Update::Write {
+                                    name: ext::RefLike::from(RefString::from(&name)),
+                                    target,
+                                    previous: pinned(actual),
+                                },
+                                msg.to_string(),
+                            ));
+                            applied.updates.push(Updated::Written { name, target })
+                        },
                     }
                 },
                 Update::Delete { name, previous } => {
-                    let refname = name.to_string();
-                    txn.lock_ref(&refname).map_err(|err| error::Txn::Lock {
-                        refname: refname.clone(),
-                        source: err,
-                    })?;
-                    let delete = || -> Result<(), Self::TxnError> {
-                        txn.remove(&refname).map_err(|err| error::Txn::Delete {
-                            refname,
-                            source: err,
-                        })
+                    let actual = match self.reference(&RefString::from(&name))? {
+                        None => None,
+                        Some(r) => Some(r.target().map(ext::Oid::from).ok_or(error::SymbolicRef)?),
                     };
-                    match self.reference(&RefString::from(&name))? {
-                        Some(r) => reject_or_update(
-                            previous
-                                .guard(r.target().map(ext::Oid::from).as_ref(), delete)?
-                                .map_or(
-                                    Ok(Updated::Deleted {
+                    match actual {
+                        Some(oid) => {
+                            match previous.guard(Some(&oid), || Ok::<_, error::Txn>(()))? {
+                                Some(rejection) => applied.rejections.push(rejection),
+                                None => {
+                                    let msg =
+                                        Message::new(Op::Untrack, "delete").with_peer(name.remote);
+                                    writes.push((
+                                        txn::Update::Delete {
+                                            name: ext::RefLike::from(RefString::from(&name)),
+                                            previous: Previous::MustBe(oid),
+                                        },
+                                        msg.to_string(),
+                                    ));
+                                    applied.updates.push(Updated::Deleted {
                                         name,
-                                        previous: r
-                                            .target()
-                                            .map(Ok)
-                                            .unwrap_or(Err(error::SymbolicRef))?
-                                            .into(),
-                                    }),
-                                    Err,
-                                ),
-                        ),
+                                        previous: oid,
+                                    })
+                                },
+                            }
+                        },
                         None => match previous {
                             refdb::PreviousValue::Any
                             | refdb::PreviousValue::MustNotExist
                             | refdb::PreviousValue::IfExistsMustMatch(_) => { /* no-op */ },
-                            _ => reject_or_update(Err(PreviousError::DidNotExist)),
+                            _ => applied.rejections.push(PreviousError::DidNotExist),
                         },
                     }
                 },
             }
         }
-        txn.commit().map_err(error::Txn::Commit)?;
+        txn::apply(
+            self.as_raw(),
+            &lock,
+            writes.iter().map(|(update, msg)| (update, msg.as_str())),
+        )?;
+        for update in &applied.updates {
+            self.audit(match update {
+                Updated::Written { name, target } => audit::Event::Tracked {
+                    urn: name.urn.clone().into_owned(),
+                    peer: name.remote.into(),
+                    config: *target,
+                },
+                Updated::Deleted { name, .. } => audit::Event::Untracked {
+                    urn: name.urn.clone().into_owned(),
+                    peer: name.remote.into(),
+                },
+            })
+        }
+        let urns = applied
+            .updates
+            .iter()
+            .map(|update| match update {
+                Updated::Written { name, .. } | Updated::Deleted { name, .. } => {
+                    name.urn.clone().into_owned()
+                },
+            })
+            .collect::<BTreeSet<_>>();
+        for urn in &urns {
+            self.reindex(urn)
+        }
         Ok(applied)
     }
 }
 
+/// The [`Previous`] value pinning a ref to the value `actual` it was
+/// observed to have.
+fn pinned(actual: Option<ext::Oid>) -> Previous {
+    actual.map_or(Previous::MustNotExist, Previous::MustBe)
+}
+
 impl Prune for Storage {
     type PruneError = error::Prune;
 
@@ -298,6 +355,7 @@ impl Prune for Storage {
         urn: &Urn,
         peer: Option<PeerId>,
     ) -> Result<Pruned<Self::Ref, Self::Oid>, Self::PruneError> {
+        let lock = self.lock_namespace(urn)?;
         let namespace = reflike!("refs/namespaces").join(urn);
         let glob = match peer {
             Some(peer) => namespace
@@ -346,6 +404,7 @@ impl Prune for Storage {
                 Some(target) => pruned.push(PrunedRef::Direct { name, target }),
             }
         }
+        lock.check()?;
         txn.commit().map_err(error::Prune::Commit)?;
 
         Ok(pruned)
@@ -0,0 +1,211 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Tracking of sub-keys granted by the delegates of a project.
+//!
+//! The delegates of a project can grant [`Capability::PublishRefs`] for it to
+//! sub-keys, see [`crate::identities::subkey`]. The sub-keys are tracked, so
+//! that their `rad/signed_refs` are replicated. Sub-keys tracked this way are
+//! recorded in the storage config. Once their grant is revoked, expires, or
+//! the granting person is no longer a delegate, they are untracked and their
+//! remote refs pruned. Sub-keys which were already tracked for other reasons
+//! are left alone.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom as _,
+    time::SystemTime,
+};
+
+use either::Either;
+
+use super::{is_tracked, policy, track as track_peer, untrack, Config, UntrackArgs, Urn};
+use crate::{
+    git::{
+        identities,
+        storage::Storage,
+        types::{Namespace, Reference},
+    },
+    identities::{
+        git::{SomeIdentity, VerifiedPerson},
+        subkey::{Capability, SubKeys},
+    },
+    PeerId,
+};
+
+pub mod error {
+    use thiserror::Error;
+
+    use crate::git::{identities, storage::config, tracking};
+
+    use super::Urn;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Track {
+        #[error("identity {0} not found")]
+        MissingIdentity(Urn),
+
+        #[error(transparent)]
+        Config(#[from] config::Error),
+
+        #[error(transparent)]
+        Identities(#[from] Box<identities::Error>),
+
+        #[error(transparent)]
+        Track(#[from] tracking::error::Track),
+
+        #[error(transparent)]
+        Untrack(#[from] tracking::error::Untrack),
+
+        #[error(transparent)]
+        IsTracked(#[from] tracking::error::IsTracked),
+    }
+
+    impl From<identities::Error> for Track {
+        fn from(e: identities::Error) -> Self {
+            Self::Identities(Box::new(e))
+        }
+    }
+}
+
+/// Track the sub-keys the `persons` granted [`Capability::PublishRefs`] for
+/// the project `urn`, and untrack those which are no longer granted.
+///
+/// `persons` are the verified delegate identities of the project, and
+/// `delegates` the keys of all its delegates.
+///
+/// Returns the recorded sub-keys which are no longer granted. They should not
+/// be tracked again, even if they are part of the tracking graph of a
+/// delegate.
+#[allow(clippy::unit_arg)]
+#[tracing::instrument(level = "trace", skip(storage, persons, delegates))]
+pub fn track<'a, I>(
+    storage: &Storage,
+    urn: &Urn,
+    persons: I,
+    delegates: &BTreeSet<PeerId>,
+) -> Result<BTreeSet<PeerId>, error::Track>
+where
+    I: IntoIterator<Item = &'a VerifiedPerson>,
+{
+    let local_peer_id = storage.peer_id();
+    let granted = granted(urn, persons, SystemTime::now());
+    let mut config = storage.config()?;
+    let recorded = config.subkeys(urn)?;
+
+    for (peer, person) in &granted {
+        if peer == local_peer_id || recorded.contains_key(peer) {
+            continue;
+        }
+        match track_peer(
+            storage,
+            urn,
+            Some(*peer),
+            Config::default(),
+            policy::Track::MustNotExist,
+        )? {
+            Ok(r) => {
+                tracing::trace!(
+                    peer = %peer,
+                    person = %person,
+                    reference = %r.name,
+                    "tracked sub-key"
+                );
+                config.set_subkey(urn, peer, person)?;
+            },
+            Err(err) => {
+                tracing::trace!(peer = %peer, err = %err, "sub-key is already tracked");
+            },
+        }
+    }
+
+    let mut revoked = BTreeSet::new();
+    for peer in recorded.keys().filter(|peer| !granted.contains_key(peer)) {
+        // Promoted to a delegate, which is tracked in its own right
+        if delegates.contains(peer) {
+            config.remove_subkey(urn, peer)?;
+            continue;
+        }
+        if is_tracked(storage, urn, Some(*peer))? {
+            tracing::info!(peer = %peer, "untracking revoked sub-key");
+            let args = UntrackArgs::prune(policy::Untrack::Any);
+            if let Err(err) = untrack(storage, urn, *peer, args)? {
+                tracing::trace!(peer = %peer, err = %err, "sub-key is already untracked");
+            }
+        }
+        revoked.insert(*peer);
+    }
+
+    Ok(revoked)
+}
+
+/// [`track`] the sub-keys of the project `urn`, using the delegate identities
+/// in our own `rad/ids`.
+///
+/// Returns `None` if `urn` is not a project.
+pub fn track_project(
+    storage: &Storage,
+    urn: &Urn,
+) -> Result<Option<BTreeSet<PeerId>>, error::Track> {
+    match identities::any::get(storage, urn)? {
+        Some(SomeIdentity::Project(_)) => {},
+        _ => return Ok(None),
+    }
+    let proj = identities::project::verify(storage, urn)?
+        .ok_or_else(|| error::Track::MissingIdentity(urn.clone()))?;
+
+    let mut delegates = BTreeSet::new();
+    let mut persons = Vec::new();
+    for delegate in proj.delegations().iter() {
+        match delegate {
+            Either::Left(key) => {
+                delegates.insert(PeerId::from(*key));
+            },
+            Either::Right(person) => {
+                let in_rad_ids =
+                    Urn::try_from(Reference::rad_delegate(Namespace::from(urn), &person.urn()))
+                        .expect("namespace is set");
+                let person = identities::person::verify(storage, &in_rad_ids)?
+                    .ok_or(error::Track::MissingIdentity(in_rad_ids))?;
+                delegates.extend(person.delegations().iter().copied().map(PeerId::from));
+                persons.push(person);
+            },
+        }
+    }
+
+    track(storage, urn, &persons, &delegates).map(Some)
+}
+
+/// The sub-keys granted [`Capability::PublishRefs`] for `urn` at time `now` by
+/// any of the `persons`, along with the granting person.
+fn granted<'a, I>(urn: &Urn, persons: I, now: SystemTime) -> BTreeMap<PeerId, Urn>
+where
+    I: IntoIterator<Item = &'a VerifiedPerson>,
+{
+    let persons = persons
+        .into_iter()
+        .map(|person| (person.urn(), person))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut granted = BTreeMap::new();
+    for (person_urn, person) in persons {
+        let subkeys = match person.payload().get_ext::<SubKeys>() {
+            Ok(Some(subkeys)) => subkeys,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::warn!(person = %person_urn, err = %e, "malformed sub-keys");
+                continue;
+            },
+        };
+        for key in subkeys.allowed(urn, Capability::PublishRefs, now) {
+            granted
+                .entry(PeerId::from(*key))
+                .or_insert_with(|| person_urn.clone());
+        }
+    }
+
+    granted
+}
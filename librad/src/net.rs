@@ -7,11 +7,13 @@ use std::{borrow::Cow, fmt::Display, str::FromStr};
 
 pub mod codec;
 pub mod connection;
+pub mod dial;
 pub mod discovery;
 pub mod peer;
 pub mod protocol;
 pub mod quic;
 pub mod replication;
+pub mod schema;
 pub mod tls;
 pub mod upgrade;
 pub mod x509;
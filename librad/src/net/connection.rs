@@ -3,7 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::ops::Deref;
+use std::{fmt, ops::Deref};
 
 use futures::io::{AsyncRead, AsyncWrite};
 use futures_codec::FramedWrite;
@@ -52,14 +52,36 @@ pub trait Duplex: RemoteInfo + AsyncRead + AsyncWrite + Unpin + Send + Sync + Si
     fn split(self) -> (Self::Read, Self::Write);
 }
 
-#[derive(Clone, Copy)]
+/// Application error codes used when closing a stream or connection.
+///
+/// The codes are part of the wire protocol: they are sent to the remote end,
+/// which can recover them using [`CloseReason::from_code`] to decide whether
+/// to retry (see [`CloseReason::is_transient`]). Codes must thus never be
+/// reused for a different meaning. Codes not listed here are reserved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum CloseReason {
     ConnectionError = 3,
     ServerShutdown = 5,
+    /// The upgrade requested for the stream is not known or not supported.
     InvalidUpgrade = 6,
     TooManyConnections = 7,
     Timeout = 8,
+    /// Too many streams of the requested kind are already open on the
+    /// connection.
+    StreamLimit = 9,
+    /// The remote end sent requests at a higher rate than allowed.
+    RateLimited = 10,
+    /// The remote end is not permitted to make the request.
+    Unauthorized = 11,
+    /// The connection was closed at the request of the local operator.
+    Disconnected = 12,
+}
+
+impl fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(self.reason_phrase()))
+    }
 }
 
 impl CloseReason {
@@ -70,6 +92,44 @@ impl CloseReason {
             Self::InvalidUpgrade => b"invalid or unsupported protocol upgrade",
             Self::TooManyConnections => b"too many connections",
             Self::Timeout => b"timeout",
+            Self::StreamLimit => b"too many concurrent streams of this kind",
+            Self::RateLimited => b"rate limit exceeded",
+            Self::Unauthorized => b"unauthorized",
+            Self::Disconnected => b"disconnected by operator",
+        }
+    }
+
+    /// Whether the same request may succeed if retried later.
+    ///
+    /// If `false`, the remote end will refuse the request for as long as it
+    /// runs the same software and configuration.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::ConnectionError
+            | Self::ServerShutdown
+            | Self::TooManyConnections
+            | Self::Timeout
+            | Self::StreamLimit
+            | Self::RateLimited
+            | Self::Disconnected => true,
+            Self::InvalidUpgrade | Self::Unauthorized => false,
+        }
+    }
+
+    /// Interpret an application error code received from the remote end when
+    /// it closed a stream or connection.
+    pub fn from_code(code: u64) -> Option<Self> {
+        match code {
+            3 => Some(Self::ConnectionError),
+            5 => Some(Self::ServerShutdown),
+            6 => Some(Self::InvalidUpgrade),
+            7 => Some(Self::TooManyConnections),
+            8 => Some(Self::Timeout),
+            9 => Some(Self::StreamLimit),
+            10 => Some(Self::RateLimited),
+            11 => Some(Self::Unauthorized),
+            12 => Some(Self::Disconnected),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,259 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Settings for dialling other peers.
+//!
+//! Peers typically advertise addresses of both IP families, and the protocol
+//! dials all of them concurrently. On hosts which can only reach one family,
+//! or where one of them is broken, [`Family`] restricts which addresses are
+//! dialled, or which are dialled first.
+//!
+//! Addresses given by name, eg. those of bootstrap peers, are resolved by
+//! [`Config::resolve`], using the configured [`Resolver`] and timeout, so that
+//! a misbehaving DNS setup results in an error rather than a hang.
+
+use std::{fmt, net::SocketAddr, str::FromStr, time::Duration};
+
+pub mod error {
+    use std::{io, time::Duration};
+
+    use thiserror::Error;
+
+    use super::Family;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Resolve {
+        #[error("invalid address `{0}`, expected `<host>:<port>`")]
+        InvalidAddr(String),
+
+        #[error("resolving `{host}` timed out after {timeout:?}")]
+        Timeout { host: String, timeout: Duration },
+
+        #[error("`{addr}` has no addresses matching the address family `{family}`")]
+        NoAddrs { addr: String, family: Family },
+
+        #[cfg(feature = "trust-dns")]
+        #[error(transparent)]
+        TrustDns(#[from] trust_dns_resolver::error::ResolveError),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+/// The IP address families to dial.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Family {
+    /// Dial addresses of both families concurrently.
+    Any,
+    /// Dial IPv4 addresses first, and IPv6 addresses only if none of them
+    /// could be connected to.
+    PreferV4,
+    /// Dial IPv6 addresses first, and IPv4 addresses only if none of them
+    /// could be connected to.
+    PreferV6,
+    /// Only dial IPv4 addresses.
+    V4Only,
+    /// Only dial IPv6 addresses.
+    V6Only,
+}
+
+impl Family {
+    /// Whether `addr` may be dialled.
+    pub fn allows(&self, addr: &SocketAddr) -> bool {
+        match self {
+            Self::V4Only => addr.is_ipv4(),
+            Self::V6Only => addr.is_ipv6(),
+            Self::Any | Self::PreferV4 | Self::PreferV6 => true,
+        }
+    }
+
+    fn prefers(&self, addr: &SocketAddr) -> bool {
+        match self {
+            Self::PreferV4 => addr.is_ipv4(),
+            Self::PreferV6 => addr.is_ipv6(),
+            Self::Any | Self::V4Only | Self::V6Only => true,
+        }
+    }
+
+    /// Split `addrs` into the ones to dial first, and the ones to fall back
+    /// to if none of the former could be connected to. Addresses which may
+    /// not be dialled are dropped.
+    pub fn partition<I>(&self, addrs: I) -> (Vec<SocketAddr>, Vec<SocketAddr>)
+    where
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        addrs
+            .into_iter()
+            .filter(|addr| self.allows(addr))
+            .partition(|addr| self.prefers(addr))
+    }
+
+    /// Order `addrs` by preference, dropping the ones which may not be
+    /// dialled.
+    pub fn sort<I>(&self, addrs: I) -> Vec<SocketAddr>
+    where
+        I: IntoIterator<Item = SocketAddr>,
+    {
+        let (mut preferred, fallback) = self.partition(addrs);
+        preferred.extend(fallback);
+        preferred
+    }
+}
+
+impl Default for Family {
+    fn default() -> Self {
+        Self::Any
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Any => "any",
+            Self::PreferV4 => "prefer-ipv4",
+            Self::PreferV6 => "prefer-ipv6",
+            Self::V4Only => "ipv4-only",
+            Self::V6Only => "ipv6-only",
+        })
+    }
+}
+
+impl FromStr for Family {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(Self::Any),
+            "prefer-ipv4" => Ok(Self::PreferV4),
+            "prefer-ipv6" => Ok(Self::PreferV6),
+            "ipv4-only" => Ok(Self::V4Only),
+            "ipv6-only" => Ok(Self::V6Only),
+            _ => Err(
+                "expected one of `any`, `prefer-ipv4`, `prefer-ipv6`, `ipv4-only` or `ipv6-only`",
+            ),
+        }
+    }
+}
+
+/// How names are resolved to addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolver {
+    /// The resolver of the operating system, ie. `getaddrinfo(3)`.
+    System,
+    /// The [trust-dns] resolver, configured from `/etc/resolv.conf` (or the
+    /// registry on Windows).
+    ///
+    /// [trust-dns]: https://docs.rs/trust-dns-resolver
+    #[cfg(feature = "trust-dns")]
+    TrustDns,
+}
+
+impl Resolver {
+    async fn lookup(self, host: &str, port: u16) -> Result<Vec<SocketAddr>, error::Resolve> {
+        match self {
+            Self::System => Ok(tokio::net::lookup_host((host, port)).await?.collect()),
+            #[cfg(feature = "trust-dns")]
+            Self::TrustDns => {
+                let resolver = trust_dns_resolver::TokioAsyncResolver::tokio_from_system_conf()?;
+                let ips = resolver.lookup_ip(host).await?;
+                Ok(ips.iter().map(|ip| SocketAddr::new(ip, port)).collect())
+            },
+        }
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::System
+    }
+}
+
+impl fmt::Display for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::System => "system",
+            #[cfg(feature = "trust-dns")]
+            Self::TrustDns => "trust-dns",
+        })
+    }
+}
+
+impl FromStr for Resolver {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "system" => Ok(Self::System),
+            #[cfg(feature = "trust-dns")]
+            "trust-dns" => Ok(Self::TrustDns),
+            #[cfg(not(feature = "trust-dns"))]
+            "trust-dns" => Err("support for the `trust-dns` resolver is not enabled"),
+            _ => Err("expected `system` or `trust-dns`"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// The address families to dial.
+    ///
+    /// Default: [`Family::Any`]
+    pub family: Family,
+    /// How long to wait for a name to resolve.
+    ///
+    /// Default: 10s
+    pub dns_timeout: Duration,
+    /// How names are resolved.
+    ///
+    /// Default: [`Resolver::System`]
+    pub resolver: Resolver,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            family: Family::default(),
+            dns_timeout: Duration::from_secs(10),
+            resolver: Resolver::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Resolve `addr`, of the form `<host>:<port>`, to the addresses to dial,
+    /// ordered by preference.
+    ///
+    /// Addresses which are not names are not resolved, but still subject to
+    /// the address [`Family`].
+    pub async fn resolve(&self, addr: &str) -> Result<Vec<SocketAddr>, error::Resolve> {
+        let addrs = match addr.parse::<SocketAddr>() {
+            Ok(addr) => vec![addr],
+            Err(_) => {
+                let (host, port) = addr
+                    .rsplit_once(':')
+                    .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+                    .ok_or_else(|| error::Resolve::InvalidAddr(addr.to_owned()))?;
+                link_async::timeout(self.dns_timeout, self.resolver.lookup(host, port))
+                    .await
+                    .map_err(|link_async::Elapsed| error::Resolve::Timeout {
+                        host: host.to_owned(),
+                        timeout: self.dns_timeout,
+                    })??
+            },
+        };
+
+        let addrs = self.family.sort(addrs);
+        if addrs.is_empty() {
+            return Err(error::Resolve::NoAddrs {
+                addr: addr.to_owned(),
+                family: self.family,
+            });
+        }
+
+        Ok(addrs)
+    }
+}
@@ -3,7 +3,13 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use futures::{future, StreamExt as _, TryFutureExt as _, TryStreamExt as _};
 use link_async::Spawner;
@@ -12,8 +18,10 @@ use crate::{
     git::{self, identities::local::LocalIdentity, Urn},
     net::{
         protocol::{self, gossip},
+        quic,
         replication::{self, Replication},
     },
+    paths::Paths,
     PeerId,
     Signer,
 };
@@ -21,7 +29,7 @@ use crate::{
 pub use crate::net::protocol::{
     event::{
         self,
-        downstream::{MembershipInfo, Stats},
+        downstream::{ConnectionInfo, MembershipInfo, Stats},
         Upstream as ProtocolEvent,
     },
     Connected,
@@ -31,15 +39,21 @@ pub use crate::net::protocol::{
     RequestPullGuard,
 };
 
+pub mod builder;
+pub use builder::{Builder, Preset};
 pub mod error;
+pub mod failover;
+pub mod reputation;
 pub mod storage;
 pub use storage::Storage as PeerStorage;
+pub mod topology;
 
 #[derive(Clone)]
 pub struct Config<Signer, Guard = config::DenyAll> {
     pub signer: Signer,
     pub protocol: protocol::Config<Guard>,
     pub storage: config::Storage,
+    pub reputation: reputation::Config,
 }
 
 pub mod config {
@@ -49,6 +63,9 @@ pub mod config {
     pub struct Storage {
         pub user: UserStorage,
         pub protocol: ProtocolStorage,
+        /// Settings for running blocking storage operations, applied to both
+        /// the user-facing and the protocol storage.
+        pub blocking: crate::git::storage::facade::Config,
     }
 
     /// Settings for the user-facing storage.
@@ -75,12 +92,15 @@ pub mod config {
     pub struct ProtocolStorage {
         /// Number of [`crate::git::storage::Storage`] instances to reserve.
         pub pool_size: usize,
+        /// Bounds on the fetches triggered by gossip.
+        pub admission: super::storage::admission::Config,
     }
 
     impl Default for ProtocolStorage {
         fn default() -> Self {
             Self {
                 pool_size: num_cpus::get_physical(),
+                admission: Default::default(),
             }
         }
     }
@@ -91,10 +111,23 @@ pub struct Peer<S, G = config::DenyAll> {
     config: Config<S, G>,
     phone: protocol::TinCans,
     peer_store: PeerStorage,
-    user_store: git::storage::Pool<git::storage::Storage>,
+    user_store: git::storage::AsyncStorage<git::storage::Pool<git::storage::Storage>>,
     caches: protocol::Caches,
     spawner: Arc<Spawner>,
     repl: Replication,
+    reputations: reputation::Reputations,
+    health: git::storage::Health,
+    usage: protocol::usage::Usage,
+}
+
+impl<S> Peer<S>
+where
+    S: Signer + Clone,
+{
+    /// Start building a [`Peer`] with the default settings. See [`Builder`].
+    pub fn builder(signer: S, paths: Paths) -> Builder<S> {
+        Builder::new(signer, paths)
+    }
 }
 
 impl<S, G> Peer<S, G>
@@ -106,6 +139,13 @@ where
         let spawner = Spawner::from_current()
             .map(Arc::new)
             .ok_or(error::Init::Runtime)?;
+        Self::with_spawner(config, spawner)
+    }
+
+    /// Like [`Peer::new`], but running tasks and blocking operations on
+    /// `spawner` instead of the ambient runtime, eg. to use a dedicated
+    /// blocking thread pool (see [`link_async::spawn::Config`]).
+    pub fn with_spawner(config: Config<S, G>, spawner: Arc<Spawner>) -> Result<Self, error::Init> {
         let phone = protocol::TinCans::default();
         let storage_lock = git::storage::pool::Initialised::no();
         let pool = git::storage::Pool::new(
@@ -124,28 +164,46 @@ where
         };
 
         #[cfg(feature = "replication-v3")]
-        let repl = Replication::new(&config.protocol.paths, config.protocol.replication)?;
+        let repl = Replication::new(&config.protocol.paths, config.protocol.replication.clone())?;
         #[cfg(not(feature = "replication-v3"))]
-        let repl = Replication::new(config.protocol.replication);
-
+        let repl = Replication::new(config.protocol.replication.clone());
+
+        let reputations =
+            reputation::Reputations::new(config.reputation, config.protocol.paths.git_dir())
+                .map_err(error::Init::Reputation)?;
+        reputations.flush_periodically(spawner.clone());
+        let usage =
+            protocol::usage::Usage::open(config.protocol.usage, config.protocol.paths.git_dir())
+                .map_err(error::Init::Usage)?;
+        let health = {
+            let phone = phone.clone();
+            git::storage::Health::new(config.protocol.paths.git_dir(), move |t| phone.emit(t))
+        };
         let peer_store = PeerStorage::new(
+            PeerId::from_signer(&config.signer),
             storage::Config {
                 fetch_quota: config.protocol.rate_limits.gossip.fetches_per_peer_and_urn,
+                admission: config.storage.protocol.admission,
             },
             spawner.clone(),
-            pool,
+            git::storage::AsyncStorage::new(pool, spawner.clone(), config.storage.blocking),
             caches.urns.clone(),
             repl.clone(),
-            #[cfg(feature = "replication-v3")]
+            reputations.clone(),
+            health.clone(),
             phone.clone(),
         );
-        let user_store = git::storage::Pool::new(
-            git::storage::pool::ReadWriteConfig::new(
-                config.protocol.paths.clone(),
-                config.signer.clone(),
-                storage_lock,
+        let user_store = git::storage::AsyncStorage::new(
+            git::storage::Pool::new(
+                git::storage::pool::ReadWriteConfig::new(
+                    config.protocol.paths.clone(),
+                    config.signer.clone(),
+                    storage_lock,
+                ),
+                config.storage.user.pool_size,
             ),
-            config.storage.user.pool_size,
+            spawner.clone(),
+            config.storage.blocking,
         );
 
         Ok(Self {
@@ -156,6 +214,9 @@ where
             caches,
             spawner,
             repl,
+            reputations,
+            health,
+            usage,
         })
     }
 
@@ -236,10 +297,169 @@ where
         self.phone.membership().await
     }
 
+    /// Subscribe to changes of the membership views, starting with a
+    /// snapshot of their current members. See [`topology`].
+    pub fn membership_events(&self) -> impl futures::Stream<Item = topology::Event> {
+        topology::events(self.phone.clone())
+    }
+
     pub async fn stats(&self) -> Stats {
         self.phone.stats().await
     }
 
+    /// Smoothed round-trip time and packet loss estimates of the currently
+    /// connected peers.
+    pub async fn latency(&self) -> protocol::latency::Snapshot {
+        self.phone.latency().await
+    }
+
+    /// Transport statistics of the connections to the currently connected
+    /// peers.
+    pub async fn connection_stats(&self) -> HashMap<PeerId, quic::ConnectionStats> {
+        self.phone.connection_stats().await
+    }
+
+    /// The currently open connections, by peer.
+    pub async fn connections(&self) -> Vec<ConnectionInfo> {
+        self.phone.connections().await
+    }
+
+    /// Close all connections to `peer`, and remove it from the active view.
+    ///
+    /// Returns `false` if there was no connection to `peer`. Note that the
+    /// peer may be dialled again later, eg. if it is pinned.
+    pub async fn disconnect(&self, peer: PeerId) -> bool {
+        self.phone.disconnect(peer).await
+    }
+
+    /// The connection status of the pinned peers, see [`protocol::pinned`].
+    pub async fn pinned(&self) -> protocol::pinned::Snapshot {
+        self.phone.pinned().await
+    }
+
+    /// The number of open and refused ingress streams of the currently
+    /// connected peers, by kind. See [`protocol::mux`].
+    pub async fn stream_stats(&self) -> protocol::mux::Snapshot {
+        self.phone.streams().await
+    }
+
+    /// Order `providers` such that more reliable peers come first, and
+    /// among those of similar reliability, peers with better network quality.
+    ///
+    /// Reliability is judged by the [`reputation::Record::score`] of the
+    /// peers. Providers for which no [`protocol::latency::Stats`] are known,
+    /// eg. because there is no connection to them, are ordered last among
+    /// their peers of similar reliability.
+    pub async fn rank_providers<A>(
+        &self,
+        providers: impl IntoIterator<Item = PeerInfo<A>>,
+    ) -> Vec<PeerInfo<A>> {
+        let latency = self.latency().await;
+        self.reputations
+            .rank_with_latency(&latency, providers, |info| info.peer_id)
+    }
+
+    /// The reliability records of the peers fetched from, see
+    /// [`reputation`].
+    pub fn reputations(&self) -> &reputation::Reputations {
+        &self.reputations
+    }
+
+    /// Whether the storage can currently be written to, see
+    /// [`git::storage::health`].
+    pub fn storage_health(&self) -> &git::storage::Health {
+        &self.health
+    }
+
+    /// The statistics of the data served to other peers, see
+    /// [`protocol::usage`].
+    pub fn usage(&self) -> &protocol::usage::Usage {
+        &self.usage
+    }
+
+    /// The outcome of the liveness checks of the members of the active view,
+    /// see [`protocol::ping`].
+    pub async fn liveness(&self) -> protocol::ping::Snapshot {
+        self.phone.liveness().await
+    }
+
+    /// The latest estimates of the clock offsets of the members of the active
+    /// view, see [`protocol::skew`].
+    pub async fn clock_skew(&self) -> protocol::skew::Snapshot {
+        self.phone.clock_skew().await
+    }
+
+    /// Send an application-level ping to the given peer, and return the
+    /// round-trip time.
+    ///
+    /// If a connection to `to` does not already exist, the supplied addresses
+    /// are used to establish a new one.
+    pub async fn ping(
+        &self,
+        to: impl Into<(PeerId, Vec<SocketAddr>)>,
+    ) -> Result<Duration, error::Ping> {
+        let to = to.into();
+        let remote_peer = to.0;
+        let Connected(conn) = self
+            .connect(to)
+            .await
+            .ok_or(error::Ping::NoConnection(remote_peer))?;
+        Ok(self.phone.ping(remote_peer, conn).await?)
+    }
+
+    /// The content-addressed store of large files, see [`protocol::lfs`].
+    pub fn lfs(&self) -> protocol::lfs::Store {
+        protocol::lfs::Store::new(&self.config.protocol.paths, self.config.protocol.lfs)
+    }
+
+    /// Fetch the content of a large file from the given peer, unless it is
+    /// already stored locally.
+    ///
+    /// If a previous transfer of the same content was interrupted, only the
+    /// remainder is requested. Returns the path of the content in the
+    /// [`Self::lfs`] store.
+    pub async fn fetch_large_file(
+        &self,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+        pointer: protocol::lfs::Pointer,
+    ) -> Result<PathBuf, error::Lfs> {
+        let from = from.into();
+        let remote_peer = from.0;
+        let Connected(conn) = self
+            .connect(from)
+            .await
+            .ok_or(error::Lfs::NoConnection(remote_peer))?;
+        Ok(protocol::lfs::fetch(&self.spawner, &conn, &self.lfs(), pointer).await?)
+    }
+
+    /// The content-addressed store of attachments, see
+    /// [`protocol::attachments`].
+    pub fn attachments(&self) -> protocol::attachments::Store {
+        protocol::attachments::Store::new(
+            &self.config.protocol.paths,
+            self.config.protocol.attachments,
+        )
+    }
+
+    /// Fetch the content of an attachment from the given peer, unless it is
+    /// already stored locally.
+    ///
+    /// Returns the path of the content in the [`Self::attachments`] store.
+    pub async fn fetch_attachment(
+        &self,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+        attachment: &protocol::attachments::Attachment,
+    ) -> Result<PathBuf, error::Attachment> {
+        let from = from.into();
+        let remote_peer = from.0;
+        let Connected(conn) = self
+            .connect(from)
+            .await
+            .ok_or(error::Attachment::NoConnection(remote_peer))?;
+        let store = self.attachments();
+        Ok(protocol::attachments::fetch(&self.spawner, &conn, &store, attachment).await?)
+    }
+
     pub async fn interrogate(
         &self,
         from: impl Into<(PeerId, Vec<SocketAddr>)>,
@@ -253,6 +473,42 @@ where
         Ok(self.phone.interrogate(remote_peer, conn))
     }
 
+    /// Determine which of the `urns` the given peer also has.
+    ///
+    /// If a connection to `from` does not already exist, the supplied addresses
+    /// are used to establish a new one. The result is exact, ie. contains no
+    /// false positives, and is also emitted as an
+    /// [`event::upstream::Inventory`] event, upon which the [`Urn`]s the
+    /// peer has newer `rad/signed_refs` of are prefetched from it.
+    pub async fn inventory(
+        &self,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+        urns: impl IntoIterator<Item = Urn>,
+    ) -> Result<protocol::inventory::Shared, error::Inventory> {
+        let from = from.into();
+        let remote_peer = from.0;
+        let Connected(conn) = self
+            .connect(from)
+            .await
+            .ok_or(error::Inventory::NoConnection(remote_peer))?;
+        let urns = urns.into_iter().map(|urn| urn.with_path(None)).collect();
+        Ok(self.phone.inventory(remote_peer, conn, urns).await?)
+    }
+
+    /// Like [`Self::inventory`], but using all [`Urn`]s in local storage.
+    pub async fn local_inventory(
+        &self,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+    ) -> Result<protocol::inventory::Shared, error::Inventory> {
+        let urns = self
+            .using_read_only(|storage| {
+                git::identities::any::list_urns(storage)
+                    .map(|urns| urns.filter_map(Result::ok).collect::<Vec<_>>())
+            })
+            .await??;
+        self.inventory(from, urns).await
+    }
+
     pub async fn request_pull(
         &self,
         to: impl Into<(PeerId, Vec<SocketAddr>)>,
@@ -278,6 +534,9 @@ where
     /// The optional `whoami` parameter is used to advertise the identity the
     /// caller whishes to identify as, ie. the `rad/self` branch.
     ///
+    /// Fails with [`error::Replicate::ReadOnly`] without contacting the peer
+    /// if the storage is read-only, see [`Self::storage_health`].
+    ///
     /// Note that this method is subject to the experimental `replication-v3`
     /// feature. Do not enable `replication-v3` unless you know what you're
     /// doing.
@@ -287,16 +546,248 @@ where
         urn: Urn,
         whoami: Option<LocalIdentity>,
     ) -> Result<replication::Success, error::Replicate> {
+        if let Some(reason) = self.health.reason() {
+            return Err(error::Replicate::ReadOnly(reason));
+        }
+        let from = from.into();
+        let (remote_peer, addrs) = (from.0, from.1.clone());
+        let received = self.received_bytes(remote_peer).await;
+        let success = match self.replicate_from(from, urn.clone(), whoami).await {
+            Ok(success) => {
+                let bytes = self
+                    .received_bytes(remote_peer)
+                    .await
+                    .saturating_sub(received);
+                self.reputations
+                    .record(remote_peer, reputation::Outcome::Success { bytes });
+                success
+            },
+            Err(e) => {
+                #[cfg(feature = "replication-v3")]
+                if let error::Replicate::Replicate(replication::error::Replicate::Vetoed(veto)) = &e
+                {
+                    self.phone.emit(event::upstream::Vetoed {
+                        urn: urn.clone(),
+                        from: remote_peer,
+                        reason: veto.reason.clone(),
+                    });
+                }
+                // Failing to write is not the remote peer's fault
+                if self.health.observe(&e).is_none() {
+                    match &e {
+                        error::Replicate::Pool(_) | error::Replicate::ReadOnly(_) => {},
+                        error::Replicate::Replicate(e) if reputation::is_equivocation(e) => self
+                            .reputations
+                            .record(remote_peer, reputation::Outcome::Equivocation),
+                        _ => self
+                            .reputations
+                            .record(remote_peer, reputation::Outcome::Failure),
+                    }
+                }
+                return Err(e);
+            },
+        };
+        if self.config.protocol.replication.verify_signatures {
+            self.verify_signatures(urn.clone(), remote_peer).await;
+        }
+        let from = (remote_peer, addrs);
+        self.fetch_large_files(urn.clone(), from).await;
+        self.phone.emit(event::upstream::Replicated {
+            urn,
+            from: remote_peer,
+        });
+        Ok(success)
+    }
+
+    /// The [`replication::Transfer`] progress of replicating `urn` from
+    /// `remote`, if such a replication is currently running.
+    ///
+    /// Progress is only reported by the `replication-v3` backend.
+    pub fn replication_progress(
+        &self,
+        urn: &Urn,
+        remote: &PeerId,
+    ) -> Option<replication::Transfer> {
         #[cfg(feature = "replication-v3")]
         {
-            // TODO: errors
+            self.repl.progress(urn, remote)
+        }
+        #[cfg(not(feature = "replication-v3"))]
+        {
+            let _ = (urn, remote);
+            None
+        }
+    }
+
+    /// Determine what [`Self::replicate`] would fetch from the given peer,
+    /// without fetching anything.
+    ///
+    /// The remote peer is connected to as for [`Self::replicate`], and its
+    /// advertised refs compared against the local storage. See
+    /// [`replication::DryRun`] for what is reported.
+    ///
+    /// Dry runs are not supported by the `replication-v3` backend yet.
+    #[cfg(not(feature = "replication-v3"))]
+    pub async fn replicate_dry_run(
+        &self,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+        urn: Urn,
+    ) -> Result<replication::DryRun, error::Replicate> {
+        self.repl
+            .dry_run(&self.spawner, self.user_store.pool(), from, urn)
+            .err_into()
+            .await
+    }
+
+    /// Bytes received so far on the connection to `peer`, if any.
+    async fn received_bytes(&self, peer: PeerId) -> u64 {
+        self.connection_stats()
+            .await
+            .get(&peer)
+            .map_or(0, |stats| stats.recv_bytes)
+    }
+
+    /// Check the branches of `from` against the commit signature policy of
+    /// `urn`, and emit an [`event::upstream::CommitSignatures`] if any
+    /// commits violate it.
+    async fn verify_signatures(&self, urn: Urn, from: PeerId) {
+        let checked = {
+            let urn = urn.clone();
+            self.using_storage(move |storage| git::signatures::check(storage, &urn, from))
+                .await
+        };
+        match checked {
+            Ok(Ok(branches)) if branches.is_empty() => {},
+            Ok(Ok(branches)) => {
+                tracing::warn!(%urn, peer = %from, "commit signature policy violated");
+                self.phone.emit(event::upstream::CommitSignatures {
+                    urn,
+                    from,
+                    branches,
+                })
+            },
+            Ok(Err(e)) => {
+                tracing::warn!(err = %e, %urn, "failed to check commit signatures")
+            },
+            Err(e) => tracing::warn!(err = %e, "failed to check commit signatures"),
+        }
+    }
+
+    /// Fetch the large files referenced by pointer files on the branches of
+    /// `from` in `urn`, see [`protocol::lfs::wanted`].
+    ///
+    /// Failures are logged, but don't fail the replication: the refs are
+    /// already in place, and the content can still be fetched later via
+    /// [`Self::fetch_large_file`].
+    async fn fetch_large_files(&self, urn: Urn, from: (PeerId, Vec<SocketAddr>)) {
+        let remote_peer = from.0;
+        let wanted = {
+            let urn = urn.clone();
+            let store = self.lfs();
+            self.using_storage(move |storage| {
+                protocol::lfs::wanted(storage, &store, &urn, remote_peer)
+            })
+            .await
+        };
+        let wanted = match wanted {
+            Ok(Ok(wanted)) => wanted,
+            Ok(Err(e)) => {
+                tracing::warn!(err = %e, %urn, "failed to find large file pointers");
+                return;
+            },
+            Err(e) => {
+                tracing::warn!(err = %e, "failed to find large file pointers");
+                return;
+            },
+        };
+        for pointer in wanted {
+            if let Err(e) = self.fetch_large_file(from.clone(), pointer).await {
+                tracing::warn!(
+                    err = %e,
+                    %urn,
+                    peer = %remote_peer,
+                    oid = %pointer.oid,
+                    "failed to fetch large file"
+                );
+            }
+        }
+    }
+
+    /// Replicate `urn` from multiple candidate `providers`.
+    ///
+    /// The providers are tried in order. If replicating from a provider fails,
+    /// the error is recorded and the next provider is tried, until
+    /// `strategy` is satisfied. The returned [`failover::Outcome`] describes
+    /// which providers replication succeeded and failed from.
+    ///
+    /// An error is returned only if no provider was given, or replication
+    /// failed from all of them.
+    ///
+    /// Providers should be ordered by preference, eg. using
+    /// [`Self::rank_providers`].
+    pub async fn replicate_any<I, P>(
+        &self,
+        providers: I,
+        urn: Urn,
+        whoami: Option<LocalIdentity>,
+        strategy: failover::Strategy,
+    ) -> Result<failover::Outcome, error::ReplicateAny>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<(PeerId, Vec<SocketAddr>)>,
+    {
+        let mut outcome = failover::Outcome {
+            succeeded: Vec::new(),
+            failed: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for from in providers {
             let from = from.into();
             let remote_peer = from.0;
+            if strategy == failover::Strategy::FirstSuccess && !outcome.succeeded.is_empty() {
+                outcome.skipped.push(remote_peer);
+                continue;
+            }
+            match self.replicate(from, urn.clone(), whoami.clone()).await {
+                Ok(success) => outcome.succeeded.push((remote_peer, success)),
+                Err(e) => {
+                    tracing::warn!(
+                        err = %e,
+                        %urn,
+                        provider = %remote_peer,
+                        "replication failed, trying next provider"
+                    );
+                    outcome.failed.push((remote_peer, e))
+                },
+            }
+        }
+
+        if outcome.succeeded.is_empty() {
+            if outcome.failed.is_empty() {
+                Err(error::ReplicateAny::NoProviders)
+            } else {
+                Err(error::ReplicateAny::AllFailed(outcome.failed))
+            }
+        } else {
+            Ok(outcome)
+        }
+    }
+
+    async fn replicate_from(
+        &self,
+        from: (PeerId, Vec<SocketAddr>),
+        urn: Urn,
+        whoami: Option<LocalIdentity>,
+    ) -> Result<replication::Success, error::Replicate> {
+        #[cfg(feature = "replication-v3")]
+        {
+            // TODO: errors
+            let remote_peer = from.0;
             let Connected(conn) = self
                 .connect(from)
                 .await
                 .ok_or(error::Replicate::NoConnection(remote_peer))?;
-            let store = self.user_store.get().await?;
+            let store = self.user_store.pool().get().await?;
             self.repl
                 .replicate(&self.spawner, store, conn, urn, whoami)
                 .err_into()
@@ -305,7 +796,7 @@ where
         #[cfg(not(feature = "replication-v3"))]
         {
             self.repl
-                .replicate(&self.spawner, &self.user_store, from, urn, whoami)
+                .replicate(&self.spawner, self.user_store.pool(), from, urn, whoami)
                 .err_into()
                 .await
         }
@@ -324,6 +815,91 @@ where
         self.phone.subscribe()
     }
 
+    /// Subscribe to protocol events, resuming after the event with sequence
+    /// number `after`.
+    ///
+    /// The most recent events are retained, and replayed to the subscriber
+    /// first. If events have been dropped, either because they are no longer
+    /// retained or because the subscriber lagged behind, a
+    /// [`event::upstream::Replay::Gap`] is yielded in their place. If `after`
+    /// is `None`, only new events are yielded. If `after` stems from a
+    /// previous run, all retained events are replayed after a gap.
+    pub fn subscribe_from(
+        &self,
+        after: Option<u64>,
+    ) -> impl futures::Stream<Item = event::upstream::Replay> {
+        self.phone.subscribe_from(after)
+    }
+
+    /// Wait until `reference` points to `oid` in the local storage.
+    ///
+    /// Resolves immediately if this is already the case. Otherwise, the
+    /// reference is checked again whenever a replication of, or a gossip
+    /// update to its namespace has completed, until `timeout` elapses.
+    pub async fn wait_for(
+        &self,
+        reference: git::types::Reference<git::types::One>,
+        oid: impl Into<git2::Oid>,
+        timeout: Duration,
+    ) -> Result<(), error::WaitFor> {
+        use futures::FutureExt as _;
+        use protocol::{broadcast::PutResult, event::upstream::Gossip, RecvError};
+
+        let oid = oid.into();
+        let urn = reference.namespace.clone().map(Urn::from);
+        let affects = |evt: &ProtocolEvent| {
+            let updated = match evt {
+                ProtocolEvent::Replicated(event::upstream::Replicated { urn, .. }) => urn,
+                ProtocolEvent::Gossip(gossip) => match gossip.as_ref() {
+                    Gossip::Put {
+                        payload,
+                        result: PutResult::Applied(_),
+                        ..
+                    } => &payload.urn,
+                    _ => return false,
+                },
+                _ => return false,
+            };
+            urn.as_ref().map_or(true, |urn| urn.id == updated.id)
+        };
+
+        // Subscribe before the first check, so we don't miss any updates
+        let events = self.subscribe().fuse();
+        futures::pin_mut!(events);
+        let deadline = link_async::sleep(timeout).fuse();
+        futures::pin_mut!(deadline);
+
+        loop {
+            let has_tip = self
+                .using_read_only({
+                    let reference = reference.clone();
+                    move |storage| {
+                        use git::storage::ReadOnlyStorage as _;
+
+                        storage
+                            .reference(&reference)
+                            .map(|r| r.and_then(|r| r.target()) == Some(oid))
+                    }
+                })
+                .await??;
+            if has_tip {
+                return Ok(());
+            }
+
+            loop {
+                futures::select! {
+                    _ = deadline => return Err(error::WaitFor::Timeout),
+                    evt = events.next() => match evt {
+                        None | Some(Err(RecvError::Closed)) => return Err(error::WaitFor::Lost),
+                        Some(Err(RecvError::Lagged(_))) => break,
+                        Some(Ok(evt)) if affects(&evt) => break,
+                        Some(Ok(_)) => continue,
+                    }
+                }
+            }
+        }
+    }
+
     /// Borrow a [`git::storage::Storage`] from the pool, and run a blocking
     /// computation on it.
     pub async fn using_storage<F, T>(&self, blocking: F) -> Result<T, error::Storage>
@@ -331,8 +907,7 @@ where
         F: FnOnce(&git::storage::Storage) -> T + Send + 'static,
         T: Send + 'static,
     {
-        let storage = self.user_store.get().await?;
-        Ok(self.spawner.blocking(move || blocking(&storage)).await)
+        Ok(self.user_store.with("using_storage", blocking).await?)
     }
 
     /// Borrow a [`git::storage::ReadOnly`] from the pool, and run a blocking
@@ -342,11 +917,21 @@ where
         F: FnOnce(&git::storage::ReadOnly) -> T + Send + 'static,
         T: Send + 'static,
     {
-        let storage = self.user_store.get().await?;
         Ok(self
-            .spawner
-            .blocking(move || blocking(storage.read_only()))
-            .await)
+            .user_store
+            .with_read_only("using_read_only", blocking)
+            .await?)
+    }
+
+    /// Activity of the tasks and blocking operations of this peer.
+    pub fn executor_stats(&self) -> link_async::Stats {
+        self.spawner.stats()
+    }
+
+    /// Activity of the blocking operations on the user-facing storage, see
+    /// [`Self::using_storage`].
+    pub fn storage_stats(&self) -> git::storage::facade::Stats {
+        self.user_store.stats()
     }
 
     /// Borrow a [`git::storage::Storage`] from the pool directly.
@@ -365,10 +950,33 @@ where
     pub async fn storage(
         &self,
     ) -> Result<impl AsRef<git::storage::Storage>, git::storage::pool::PoolError> {
-        self.user_store
-            .get()
-            .map_ok(git::storage::pool::PooledRef::from)
-            .await
+        self.user_store.pool().get().await
+    }
+
+    /// Close the storage pools of this peer, in preparation for shutdown.
+    ///
+    /// Storage operations requested afterwards fail, while operations which
+    /// are underway are given until `deadline` to complete. See
+    /// [`git::storage::Pool::close`].
+    pub async fn close_storage(
+        &self,
+        deadline: Instant,
+    ) -> Result<(), git::storage::pool::Undrained> {
+        let (user, protocol) = future::join(
+            self.user_store.pool().close(deadline),
+            self.peer_store.close(deadline),
+        )
+        .await;
+        match (user, protocol) {
+            (Ok(()), Ok(())) => Ok(()),
+            (user, protocol) => Err(git::storage::pool::Undrained {
+                in_flight: [user, protocol]
+                    .iter()
+                    .filter_map(|res| res.as_ref().err())
+                    .map(|e| e.in_flight)
+                    .sum(),
+            }),
+        }
     }
 
     pub async fn bind(
@@ -381,6 +989,8 @@ where
             self.config.signer.clone(),
             self.peer_store.clone(),
             self.caches.clone(),
+            self.health.clone(),
+            self.usage.clone(),
         )
         .await
     }
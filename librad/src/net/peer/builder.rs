@@ -0,0 +1,424 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Validated construction of a [`Peer`].
+//!
+//! A [`Config`] has a lot of knobs, many of which only make sense in
+//! combination with others. Instead of assembling it by hand, a [`Builder`]
+//! (obtained via [`Peer::builder`]) starts from the defaults, or from a
+//! [`Preset`], and checks the result before the peer is created. Options
+//! which contradict each other are reported as [`error::Config`], instead of
+//! surfacing as confusing runtime failures later on.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use nonempty::NonEmpty;
+
+use super::{config, error, reputation, Config, Peer, RequestPullGuard};
+use crate::{
+    net::{
+        dial,
+        protocol::{
+            self,
+            attachments,
+            backoff,
+            batch,
+            capture,
+            checkpoint,
+            compress,
+            interest,
+            lfs,
+            mailbox,
+            membership,
+            mux,
+            outbox,
+            pinned,
+            usage,
+            Quota,
+        },
+        quic,
+        replication,
+        Network,
+    },
+    paths::Paths,
+    PeerId,
+    Signer,
+};
+
+/// Settings tuned for a particular kind of deployment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    /// A peer run on behalf of a single user, eg. on their laptop: few
+    /// connections, and few resources reserved for storage access.
+    Laptop,
+    /// An always-on peer serving many others: more connections, larger
+    /// storage pools, and more room for undelivered announcements.
+    Seed,
+}
+
+pub struct Builder<S, G = config::DenyAll> {
+    config: Config<S, G>,
+}
+
+impl<S> Builder<S>
+where
+    S: Signer + Clone,
+{
+    pub(super) fn new(signer: S, paths: Paths) -> Self {
+        Self {
+            config: Config {
+                signer,
+                protocol: protocol::Config {
+                    paths,
+                    listen_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+                    advertised_addrs: None,
+                    membership: Default::default(),
+                    network: Default::default(),
+                    replication: Default::default(),
+                    rate_limits: Default::default(),
+                    request_pull: config::DenyAll,
+                    mailbox: Default::default(),
+                    bandwidth: Default::default(),
+                    dial: Default::default(),
+                    gossip_batch: Default::default(),
+                    pinned: Default::default(),
+                    lfs: Default::default(),
+                    attachments: Default::default(),
+                    streams: Default::default(),
+                    capture: Default::default(),
+                    outbox: Default::default(),
+                    compression: Default::default(),
+                    interest: Default::default(),
+                    backoff: Default::default(),
+                    checkpoint: Default::default(),
+                    usage: Default::default(),
+                    ls_refs: Default::default(),
+                    private: false,
+                    rotation: None,
+                },
+                storage: Default::default(),
+                reputation: Default::default(),
+            },
+        }
+    }
+}
+
+impl<S, G> Builder<S, G>
+where
+    S: Signer + Clone,
+    G: RequestPullGuard,
+{
+    /// Apply the settings of `preset`, overriding any previously set values
+    /// it covers.
+    pub fn preset(mut self, preset: Preset) -> Self {
+        let cpus = num_cpus::get_physical();
+        let protocol = &mut self.config.protocol;
+        let storage = &mut self.config.storage;
+        match preset {
+            Preset::Laptop => {
+                protocol.membership = membership::Params {
+                    max_active: 3,
+                    max_passive: 15,
+                    ..Default::default()
+                };
+                storage.user.pool_size = cpus.min(2);
+                storage.protocol.pool_size = cpus.min(2);
+            },
+            Preset::Seed => {
+                protocol.membership = membership::Params {
+                    max_active: 10,
+                    max_passive: 60,
+                    ..Default::default()
+                };
+                protocol.mailbox = mailbox::Config {
+                    capacity: 256,
+                    max_peers: 8192,
+                    ..Default::default()
+                };
+                #[cfg(feature = "replication-v3")]
+                {
+                    protocol.replication.slots = cpus.max(4) * 2;
+                }
+                storage.user.pool_size = cpus;
+                storage.protocol.pool_size = cpus * 2;
+                storage.protocol.admission.max_queued = 256;
+            },
+        }
+        self
+    }
+
+    pub fn listen_addr(mut self, addr: SocketAddr) -> Self {
+        self.config.protocol.listen_addr = addr;
+        self
+    }
+
+    pub fn advertised_addrs(mut self, addrs: Option<NonEmpty<SocketAddr>>) -> Self {
+        self.config.protocol.advertised_addrs = addrs;
+        self
+    }
+
+    pub fn network(mut self, network: Network) -> Self {
+        self.config.protocol.network = network;
+        self
+    }
+
+    pub fn membership(mut self, params: membership::Params) -> Self {
+        self.config.protocol.membership = params;
+        self
+    }
+
+    pub fn replication(mut self, config: replication::Config) -> Self {
+        self.config.protocol.replication = config;
+        self
+    }
+
+    pub fn rate_limits(mut self, quota: Quota) -> Self {
+        self.config.protocol.rate_limits = quota;
+        self
+    }
+
+    /// Serve request-pulls as decided by `guard`. Denied by default.
+    pub fn request_pull<H>(self, guard: H) -> Builder<S, H>
+    where
+        H: RequestPullGuard,
+    {
+        let Config {
+            signer,
+            protocol,
+            storage,
+            reputation,
+        } = self.config;
+        Builder {
+            config: Config {
+                signer,
+                protocol: protocol::Config {
+                    paths: protocol.paths,
+                    listen_addr: protocol.listen_addr,
+                    advertised_addrs: protocol.advertised_addrs,
+                    membership: protocol.membership,
+                    network: protocol.network,
+                    replication: protocol.replication,
+                    rate_limits: protocol.rate_limits,
+                    request_pull: guard,
+                    mailbox: protocol.mailbox,
+                    bandwidth: protocol.bandwidth,
+                    dial: protocol.dial,
+                    gossip_batch: protocol.gossip_batch,
+                    pinned: protocol.pinned,
+                    lfs: protocol.lfs,
+                    attachments: protocol.attachments,
+                    streams: protocol.streams,
+                    capture: protocol.capture,
+                    outbox: protocol.outbox,
+                    compression: protocol.compression,
+                    interest: protocol.interest,
+                    backoff: protocol.backoff,
+                    checkpoint: protocol.checkpoint,
+                    usage: protocol.usage,
+                    ls_refs: protocol.ls_refs,
+                    private: protocol.private,
+                    rotation: protocol.rotation,
+                },
+                storage,
+                reputation,
+            },
+        }
+    }
+
+    pub fn mailbox(mut self, config: mailbox::Config) -> Self {
+        self.config.protocol.mailbox = config;
+        self
+    }
+
+    pub fn bandwidth(mut self, config: quic::shaping::Config) -> Self {
+        self.config.protocol.bandwidth = config;
+        self
+    }
+
+    pub fn dial(mut self, config: dial::Config) -> Self {
+        self.config.protocol.dial = config;
+        self
+    }
+
+    pub fn gossip_batch(mut self, config: batch::Config) -> Self {
+        self.config.protocol.gossip_batch = config;
+        self
+    }
+
+    pub fn pinned(mut self, config: pinned::Config) -> Self {
+        self.config.protocol.pinned = config;
+        self
+    }
+
+    pub fn lfs(mut self, config: lfs::Config) -> Self {
+        self.config.protocol.lfs = config;
+        self
+    }
+
+    pub fn attachments(mut self, config: attachments::Config) -> Self {
+        self.config.protocol.attachments = config;
+        self
+    }
+
+    pub fn streams(mut self, config: mux::Config) -> Self {
+        self.config.protocol.streams = config;
+        self
+    }
+
+    pub fn capture(mut self, config: capture::Config) -> Self {
+        self.config.protocol.capture = config;
+        self
+    }
+
+    pub fn outbox(mut self, config: outbox::Config) -> Self {
+        self.config.protocol.outbox = config;
+        self
+    }
+
+    pub fn compression(mut self, config: compress::Config) -> Self {
+        self.config.protocol.compression = config;
+        self
+    }
+
+    pub fn interest(mut self, config: interest::Config) -> Self {
+        self.config.protocol.interest = config;
+        self
+    }
+
+    pub fn backoff(mut self, config: backoff::Config) -> Self {
+        self.config.protocol.backoff = config;
+        self
+    }
+
+    pub fn checkpoint(mut self, config: checkpoint::Config) -> Self {
+        self.config.protocol.checkpoint = config;
+        self
+    }
+
+    pub fn usage(mut self, config: usage::Config) -> Self {
+        self.config.protocol.usage = config;
+        self
+    }
+
+    /// Run as a private peer, see [`protocol::Config::private`].
+    pub fn private(mut self, private: bool) -> Self {
+        self.config.protocol.private = private;
+        self
+    }
+
+    /// Serve `rotation` of the local key, see [`protocol::rotation`].
+    pub fn rotation(mut self, rotation: Option<protocol::rotation::Rotation>) -> Self {
+        self.config.protocol.rotation = rotation;
+        self
+    }
+
+    pub fn reputation(mut self, config: reputation::Config) -> Self {
+        self.config.reputation = config;
+        self
+    }
+
+    pub fn storage(mut self, config: config::Storage) -> Self {
+        self.config.storage = config;
+        self
+    }
+
+    /// Validate and return the [`Config`], without creating the [`Peer`].
+    pub fn config(self) -> Result<Config<S, G>, error::Config> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+
+    /// Validate the [`Config`], and create the [`Peer`].
+    pub fn build(self) -> Result<Peer<S, G>, error::Build> {
+        Ok(Peer::new(self.config()?)?)
+    }
+}
+
+impl<S, G> Config<S, G>
+where
+    S: Signer,
+{
+    /// Check that the settings are consistent.
+    pub fn validate(&self) -> Result<(), error::Config> {
+        use error::Config as Error;
+
+        let protocol = &self.protocol;
+
+        for addr in protocol.advertised_addrs.iter().flatten() {
+            if addr.ip().is_unspecified() || addr.port() == 0 {
+                return Err(Error::AdvertisedAddr(*addr));
+            }
+        }
+
+        let membership = &protocol.membership;
+        if membership.max_active == 0 {
+            return Err(Error::Membership("`max_active` must be greater than zero"));
+        }
+        if membership.active_random_walk_length <= membership.passive_random_walk_length {
+            return Err(Error::Membership(
+                "`active_random_walk_length` must be greater than `passive_random_walk_length`",
+            ));
+        }
+        if membership.shuffle_sample_size > membership.max_passive {
+            return Err(Error::Membership(
+                "`shuffle_sample_size` must not exceed `max_passive`",
+            ));
+        }
+        if membership.shuffle_interval.is_zero() || membership.promote_interval.is_zero() {
+            return Err(Error::Membership("intervals must be greater than zero"));
+        }
+
+        if protocol.replication.wait_slot.is_zero() {
+            return Err(Error::Replication("`wait_slot` must be greater than zero"));
+        }
+        #[cfg(feature = "replication-v3")]
+        if protocol.replication.slots == 0 {
+            return Err(Error::Replication("`slots` must be greater than zero"));
+        }
+
+        let local_id = PeerId::from_signer(&self.signer);
+        if protocol.pinned.peers.contains_key(&local_id) {
+            return Err(Error::PinnedSelf);
+        }
+        if protocol.pinned.min_backoff > protocol.pinned.max_backoff {
+            return Err(Error::Backoff);
+        }
+
+        if let Some(rotation) = &protocol.rotation {
+            if rotation.old != local_id {
+                return Err(Error::RotationKey(rotation.old));
+            }
+            rotation.verify()?;
+        }
+
+        let batch = &protocol.gossip_batch;
+        if batch.enabled && (batch.max_messages == 0 || batch.min_interval > batch.max_interval) {
+            return Err(Error::GossipBatch);
+        }
+
+        if protocol.outbox.retry_interval.is_zero() {
+            return Err(Error::Outbox);
+        }
+
+        if protocol.checkpoint.enabled && protocol.checkpoint.interval.is_zero() {
+            return Err(Error::Checkpoint);
+        }
+
+        if let Some(min) = self.reputation.min_score {
+            if !(0.0..=1.0).contains(&min) {
+                return Err(Error::MinScore);
+            }
+        }
+
+        if self.storage.user.pool_size == 0 || self.storage.protocol.pool_size == 0 {
+            return Err(Error::PoolSize);
+        }
+        if self.storage.protocol.admission.max_running == 0 {
+            return Err(Error::Admission);
+        }
+
+        Ok(())
+    }
+}
@@ -3,11 +3,16 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+use std::net::SocketAddr;
+
 use thiserror::Error;
 
 use crate::{
-    git::storage,
-    net::{protocol::cache, replication},
+    git::{self, storage},
+    net::{
+        protocol::{self, cache},
+        replication,
+    },
     PeerId,
 };
 
@@ -41,6 +46,12 @@ pub enum Init {
     #[cfg(feature = "replication-v3")]
     #[error(transparent)]
     Replication(#[from] replication::error::Init),
+
+    #[error("failed to open peer reputations")]
+    Reputation(#[source] std::io::Error),
+
+    #[error("failed to open usage statistics")]
+    Usage(#[source] protocol::usage::error::Read),
 }
 
 impl From<cache::urns::Error> for Init {
@@ -49,11 +60,68 @@ impl From<cache::urns::Error> for Init {
     }
 }
 
+/// Inconsistent [`super::Config`] settings.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Config {
+    #[error("advertised address {0} is not routable")]
+    AdvertisedAddr(SocketAddr),
+
+    #[error("invalid membership parameters: {0}")]
+    Membership(&'static str),
+
+    #[error("invalid replication settings: {0}")]
+    Replication(&'static str),
+
+    #[error("the local peer can not be pinned")]
+    PinnedSelf,
+
+    #[error("minimum backoff of pinned peers exceeds maximum")]
+    Backoff,
+
+    #[error("the announced key rotation is from `{0}`, not the local key")]
+    RotationKey(PeerId),
+
+    #[error(transparent)]
+    Rotation(#[from] protocol::rotation::error::Verify),
+
+    #[error("gossip batching enabled with an empty batch size or inverted flush intervals")]
+    GossipBatch,
+
+    #[error("announcement retry interval must be greater than zero")]
+    Outbox,
+
+    #[error("checkpoint interval must be greater than zero")]
+    Checkpoint,
+
+    #[error("minimum reputation score must be between 0 and 1")]
+    MinScore,
+
+    #[error("storage pool sizes must be greater than zero")]
+    PoolSize,
+
+    #[error("maximum number of running fetches must be greater than zero")]
+    Admission,
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Build {
+    #[error(transparent)]
+    Config(#[from] Config),
+
+    #[error(transparent)]
+    Init(#[from] Init),
+}
+
 #[derive(Debug, Error)]
 pub enum Replicate {
     #[error("no connection to {0}")]
     NoConnection(PeerId),
 
+    #[error("storage is read-only: {0}")]
+    ReadOnly(storage::health::Reason),
+
     #[error("failed to borrow storage from pool")]
     Pool(#[from] storage::PoolError),
 
@@ -61,6 +129,78 @@ pub enum Replicate {
     Replicate(#[from] replication::error::Replicate),
 }
 
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ReplicateAny {
+    #[error("no providers to replicate from")]
+    NoProviders,
+
+    #[error("replication failed from all {} providers", .0.len())]
+    AllFailed(Vec<(PeerId, Replicate)>),
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum WaitFor {
+    #[error("timeout waiting for reference to be updated")]
+    Timeout,
+
+    #[error("protocol events lost")]
+    Lost,
+
+    #[error(transparent)]
+    Storage(#[from] Storage),
+
+    #[error(transparent)]
+    Git(#[from] storage::Error),
+}
+
 #[derive(Debug, Error)]
 #[error("unable to obtain connection to {0}")]
 pub struct NoConnection(pub PeerId);
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Lfs {
+    #[error("no connection to {0}")]
+    NoConnection(PeerId),
+
+    #[error(transparent)]
+    Fetch(#[from] protocol::lfs::error::Fetch),
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Attachment {
+    #[error("no connection to {0}")]
+    NoConnection(PeerId),
+
+    #[error(transparent)]
+    Fetch(#[from] protocol::attachments::error::Fetch),
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Ping {
+    #[error("no connection to {0}")]
+    NoConnection(PeerId),
+
+    #[error(transparent)]
+    Protocol(#[from] protocol::error::Ping),
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Inventory {
+    #[error("no connection to {0}")]
+    NoConnection(PeerId),
+
+    #[error(transparent)]
+    Storage(#[from] Storage),
+
+    #[error(transparent)]
+    Identities(#[from] git::identities::Error),
+
+    #[error(transparent)]
+    Protocol(#[from] protocol::error::Inventory),
+}
@@ -0,0 +1,56 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Replication from multiple candidate providers.
+//!
+//! See [`super::Peer::replicate_any`].
+
+use crate::{net::replication, PeerId};
+
+use super::error;
+
+/// Which providers [`super::Peer::replicate_any`] replicates from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Try the providers in order, until one succeeds.
+    FirstSuccess,
+    /// Replicate from all providers, in order.
+    All,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Self::FirstSuccess
+    }
+}
+
+/// The result of replicating from multiple providers, at least one of which
+/// succeeded.
+#[derive(Debug)]
+pub struct Outcome {
+    /// The providers replication succeeded from, in the order they were tried.
+    ///
+    /// Each [`replication::Success`] describes the refs which were updated
+    /// from the respective provider.
+    pub succeeded: Vec<(PeerId, replication::Success)>,
+    /// The providers replication failed from, in the order they were tried.
+    pub failed: Vec<(PeerId, error::Replicate)>,
+    /// The providers which were not tried, because [`Strategy::FirstSuccess`]
+    /// was satisfied before.
+    pub skipped: Vec<PeerId>,
+}
+
+impl Outcome {
+    /// `true` if replication failed from any of the providers which were
+    /// tried.
+    pub fn is_partial(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    /// The providers replication succeeded from.
+    pub fn providers(&self) -> impl Iterator<Item = &PeerId> {
+        self.succeeded.iter().map(|(peer, _)| peer)
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Long-term reliability of remote peers.
+//!
+//! Latency tells us which peer is quick to reach right now, but not whether
+//! fetching from it is likely to succeed. For every peer fetched from, a
+//! [`Record`] of successful and failed fetches, bytes received and
+//! equivocations is kept, and persisted to [`FILE_NAME`] (relative to the git
+//! directory). Records are updated in memory, and written every
+//! [`Config::flush_interval`] on the blocking thread pool, as well as when the
+//! store is dropped. At most [`Config::max_entries`] records are kept, the
+//! least recently updated ones are dropped first.
+//!
+//! An equivocation is recorded when a peer presented history conflicting
+//! with what we know, or announced a revision it then did not serve.
+//!
+//! The resulting [`Record::score`] is used to order provider candidates, see
+//! [`Reputations::rank_with_latency`], both by [`super::Peer::rank_providers`]
+//! and when choosing which provider of an update announced via gossip to
+//! fetch from. If [`Config::min_score`] is set, gossip from peers below it is
+//! ignored.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use link_async::Spawner;
+use minicbor::{Decode, Encode};
+use parking_lot::Mutex;
+
+use crate::{
+    net::{protocol::latency, replication},
+    PeerId,
+};
+
+/// The name of the reputation file, relative to the git directory.
+pub const FILE_NAME: &str = "reputation";
+
+/// Score of a peer nothing is known about.
+pub const NEUTRAL_SCORE: f64 = 0.5;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// If `false`, reputations are kept in memory only, and do not survive
+    /// restarts.
+    pub durable: bool,
+    /// Gossip from peers with a lower score is not acted upon.
+    ///
+    /// Default: none
+    pub min_score: Option<f64>,
+    /// Maximum number of peers to keep records of.
+    ///
+    /// Default: 10000
+    pub max_entries: usize,
+    /// Maximum time updated records are kept in memory only.
+    ///
+    /// Default: 60s
+    pub flush_interval: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            durable: true,
+            min_score: None,
+            max_entries: 10_000,
+            flush_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// What happened when fetching from a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The fetch succeeded, transferring `bytes` from the peer.
+    Success { bytes: u64 },
+    /// The fetch failed.
+    Failure,
+    /// The peer presented conflicting history.
+    Equivocation,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Encode, Decode)]
+#[cbor(array)]
+pub struct Record {
+    #[n(0)]
+    pub fetches_succeeded: u64,
+    #[n(1)]
+    pub fetches_failed: u64,
+    #[n(2)]
+    pub bytes_served: u64,
+    #[n(3)]
+    pub equivocations: u64,
+    /// Seconds since the Unix epoch at which the record was last updated.
+    #[n(4)]
+    pub updated: u64,
+}
+
+impl Record {
+    /// Score in the range `[0, 1]`, higher is better.
+    ///
+    /// The success rate of fetches, assuming one success and one failure a
+    /// priori so peers with few fetches stay close to [`NEUTRAL_SCORE`].
+    /// Every equivocation halves the score.
+    pub fn score(&self) -> f64 {
+        let rate = (self.fetches_succeeded as f64 + 1.0)
+            / ((self.fetches_succeeded + self.fetches_failed) as f64 + 2.0);
+        rate / 2f64.powi(self.equivocations.min(64) as i32)
+    }
+
+    fn apply(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Success { bytes } => {
+                self.fetches_succeeded += 1;
+                self.bytes_served = self.bytes_served.saturating_add(bytes);
+            },
+            Outcome::Failure => self.fetches_failed += 1,
+            Outcome::Equivocation => {
+                self.fetches_failed += 1;
+                self.equivocations += 1;
+            },
+        }
+        self.updated = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+    }
+}
+
+#[derive(Clone, Debug, Encode, Decode)]
+#[cbor(array)]
+struct Entry {
+    #[n(0)]
+    peer: PeerId,
+    #[n(1)]
+    record: Record,
+}
+
+struct Inner {
+    path: Option<PathBuf>,
+    records: BTreeMap<PeerId, Record>,
+    dirty: bool,
+}
+
+impl Inner {
+    /// Take the records to write, if they changed since they were last
+    /// taken.
+    fn take_dirty(&mut self) -> Option<(PathBuf, BTreeMap<PeerId, Record>)> {
+        let path = self.path.clone()?;
+        if !self.dirty {
+            return None;
+        }
+        self.dirty = false;
+        Some((path, self.records.clone()))
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if let Some((path, records)) = self.take_dirty() {
+            if let Err(e) = store(&path, &records) {
+                tracing::warn!(err = %e, "failed to persist peer reputations")
+            }
+        }
+    }
+}
+
+/// Persistent store of [`Record`]s, by peer.
+#[derive(Clone)]
+pub struct Reputations {
+    config: Config,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Reputations {
+    /// Open the store in `git_dir`, or in memory if [`Config::durable`] is
+    /// `false`.
+    pub fn new(config: Config, git_dir: &Path) -> io::Result<Self> {
+        if config.durable {
+            Self::open(config, git_dir.join(FILE_NAME))
+        } else {
+            Ok(Self::in_memory(config))
+        }
+    }
+
+    pub fn open(config: Config, path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let records = match fs::read(&path) {
+            Ok(bytes) => minicbor::decode::<Vec<Entry>>(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+                .into_iter()
+                .map(|Entry { peer, record }| (peer, record))
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self::with(config, Some(path), records))
+    }
+
+    pub fn in_memory(config: Config) -> Self {
+        Self::with(config, None, BTreeMap::new())
+    }
+
+    fn with(config: Config, path: Option<PathBuf>, records: BTreeMap<PeerId, Record>) -> Self {
+        Self {
+            config,
+            inner: Arc::new(Mutex::new(Inner {
+                path,
+                records,
+                dirty: false,
+            })),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn record(&self, peer: PeerId, outcome: Outcome) {
+        let mut inner = self.inner.lock();
+        inner.records.entry(peer).or_default().apply(outcome);
+        if inner.records.len() > self.config.max_entries {
+            let stale = inner
+                .records
+                .iter()
+                .filter(|(other, _)| **other != peer)
+                .min_by_key(|(_, record)| record.updated)
+                .map(|(other, _)| *other);
+            if let Some(stale) = stale {
+                inner.records.remove(&stale);
+            }
+        }
+        inner.dirty = true;
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<Record> {
+        self.inner.lock().records.get(peer).copied()
+    }
+
+    /// The [`Record::score`] of `peer`, or [`NEUTRAL_SCORE`] if unknown.
+    pub fn score(&self, peer: &PeerId) -> f64 {
+        self.get(peer).map_or(NEUTRAL_SCORE, |r| r.score())
+    }
+
+    /// Whether gossip from `peer` should be acted upon, see
+    /// [`Config::min_score`].
+    pub fn is_acceptable(&self, peer: &PeerId) -> bool {
+        self.config
+            .min_score
+            .map_or(true, |min| self.score(peer) >= min)
+    }
+
+    pub fn all(&self) -> BTreeMap<PeerId, Record> {
+        self.inner.lock().records.clone()
+    }
+
+    /// Forget the reputation of `peer`, or of all peers if `None`.
+    pub fn reset(&self, peer: Option<PeerId>) {
+        let mut inner = self.inner.lock();
+        match peer {
+            Some(peer) => {
+                inner.records.remove(&peer);
+            },
+            None => inner.records.clear(),
+        }
+        inner.dirty = true;
+    }
+
+    /// Order `candidates` by descending score, in steps of a tenth.
+    ///
+    /// The sort is stable, so candidates of similar score retain their
+    /// relative order, eg. by latency.
+    pub fn rank<T, F>(&self, candidates: impl IntoIterator<Item = T>, peer_id: F) -> Vec<T>
+    where
+        F: Fn(&T) -> PeerId,
+    {
+        let inner = self.inner.lock();
+        let mut ranked = candidates
+            .into_iter()
+            .map(|c| {
+                let score = inner
+                    .records
+                    .get(&peer_id(&c))
+                    .map_or(NEUTRAL_SCORE, Record::score);
+                ((score * 10.0).round() as u8, c)
+            })
+            .collect::<Vec<_>>();
+        ranked.sort_by(|(a, _), (b, _)| b.cmp(a));
+        ranked.into_iter().map(|(_, c)| c).collect()
+    }
+
+    /// Order `candidates` as per [`Reputations::rank`], and among those of
+    /// similar score by network quality as per [`latency::rank`].
+    pub fn rank_with_latency<T, F>(
+        &self,
+        latency: &latency::Snapshot,
+        candidates: impl IntoIterator<Item = T>,
+        peer_id: F,
+    ) -> Vec<T>
+    where
+        F: Fn(&T) -> PeerId,
+    {
+        self.rank(latency::rank(latency, candidates, &peer_id), &peer_id)
+    }
+
+    /// Write the records if they changed, on the blocking thread pool of
+    /// `spawner`.
+    pub async fn flush(&self, spawner: &Spawner) {
+        flush(spawner, &self.inner).await
+    }
+
+    /// [`Reputations::flush`] every [`Config::flush_interval`], for as long
+    /// as any handle to the store exists.
+    pub(super) fn flush_periodically(&self, spawner: Arc<Spawner>) {
+        let inner = Arc::downgrade(&self.inner);
+        let interval = self.config.flush_interval;
+        spawner
+            .clone()
+            .spawn(async move {
+                loop {
+                    link_async::sleep(interval).await;
+                    match inner.upgrade() {
+                        Some(inner) => flush(&spawner, &inner).await,
+                        None => break,
+                    }
+                }
+            })
+            .detach()
+    }
+}
+
+async fn flush(spawner: &Spawner, inner: &Arc<Mutex<Inner>>) {
+    let (path, records) = match inner.lock().take_dirty() {
+        Some(dirty) => dirty,
+        None => return,
+    };
+    if let Err(e) = spawner.blocking(move || store(&path, &records)).await {
+        tracing::warn!(err = %e, "failed to persist peer reputations");
+        inner.lock().dirty = true;
+    }
+}
+
+fn store(path: &Path, records: &BTreeMap<PeerId, Record>) -> io::Result<()> {
+    let entries = records
+        .iter()
+        .map(|(peer, record)| Entry {
+            peer: *peer,
+            record: *record,
+        })
+        .collect::<Vec<_>>();
+    let bytes =
+        minicbor::to_vec(&entries).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)
+}
+
+/// Whether replication failed because the peer presented history
+/// conflicting with ours.
+#[cfg(not(feature = "replication-v3"))]
+pub(super) fn is_equivocation(err: &replication::error::Replicate) -> bool {
+    use crate::git::replication::Error;
+
+    matches!(
+        err,
+        replication::error::Replicate::Replication(Error::Fork { .. })
+    )
+}
+
+#[cfg(feature = "replication-v3")]
+pub(super) fn is_equivocation(_: &replication::error::Replicate) -> bool {
+    false
+}
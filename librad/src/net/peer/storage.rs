@@ -3,71 +3,135 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use crypto::peer::Originates;
 use either::Either::{self, Left, Right};
-use futures::TryFutureExt as _;
 use git_ext::{self as ext, reference};
 use link_async::Spawner;
 use nonzero_ext::nonzero;
+use parking_lot::Mutex;
 
-#[cfg(feature = "replication-v3")]
-use crate::net::protocol::TinCans;
+use super::reputation::{self, Reputations};
 use crate::{
     git::{
-        storage::{self, Pool, PoolError, PooledRef, ReadOnlyStorage as _},
+        storage::{self, AsyncStorage, Health, Pool, PoolError, PooledRef, ReadOnlyStorage as _},
         tracking,
         Urn,
     },
     identities::urn,
     net::{
-        protocol::{broadcast, cache, gossip},
+        protocol::{broadcast, cache, gossip, TinCans},
         replication::{self, Replication},
     },
     rate_limit::{Keyed, RateLimiter},
     PeerId,
 };
 
+pub mod admission;
+pub use admission::Admission;
+
 mod error;
 pub use error::Error;
 
+const POOL_EXPECT: &str = "unable to acquire storage from pool";
+
+/// How long to wait for other providers of an update announced via gossip,
+/// before choosing which one to fetch it from, if other updates of the same
+/// URN are being fetched.
+const COALESCE_WINDOW: Duration = Duration::from_millis(100);
+
 #[derive(Clone, Copy)]
 pub struct Config {
     pub fetch_quota: governor::Quota,
+    pub admission: admission::Config,
 }
 
 #[derive(Clone)]
 pub struct Storage {
-    pool: Pool<storage::Storage>,
+    local_peer_id: PeerId,
+    pool: AsyncStorage<Pool<storage::Storage>>,
     urns: cache::urns::Filter,
     rate: Arc<RateLimiter<Keyed<(PeerId, Urn)>>>,
+    admission: Admission,
     exec: Arc<Spawner>,
     repl: Replication,
-    #[cfg(feature = "replication-v3")]
+    reputations: Reputations,
+    health: Health,
     tins: TinCans,
+    /// Providers of the updates currently being fetched, which have not been
+    /// tried yet.
+    pending: Arc<Mutex<HashMap<Announced, Vec<Provider>>>>,
+}
+
+/// An update announced via gossip, by any provider.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Announced {
+    urn: Urn,
+    origin: PeerId,
+    head: Option<git2::Oid>,
+}
+
+type Provider = (PeerId, Vec<SocketAddr>);
+
+/// An update being fetched, see [`Storage::offer`].
+///
+/// Its untried providers are removed from [`Storage::pending`] when dropped,
+/// also if the fetch is cancelled.
+struct Pending {
+    pending: Arc<Mutex<HashMap<Announced, Vec<Provider>>>>,
+    announced: Announced,
+    /// Whether other updates of the same URN were being fetched when this one
+    /// was offered.
+    contended: bool,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.pending.lock().remove(&self.announced);
+    }
+}
+
+/// The outcome of fetching an announced update from one provider.
+enum Attempt {
+    /// The result of the announcement.
+    Done(broadcast::PutResult<gossip::Payload>),
+    /// The provider failed, try the next one if any.
+    Failed(broadcast::PutResult<gossip::Payload>),
 }
 
 impl Storage {
     pub fn new(
+        local_peer_id: PeerId,
         conf: Config,
         exec: Arc<Spawner>,
-        pool: Pool<storage::Storage>,
+        pool: AsyncStorage<Pool<storage::Storage>>,
         urns: cache::urns::Filter,
         repl: Replication,
-        #[cfg(feature = "replication-v3")] tins: TinCans,
+        reputations: Reputations,
+        health: Health,
+        tins: TinCans,
     ) -> Self {
         Self {
+            local_peer_id,
             pool,
             urns,
             rate: Arc::new(RateLimiter::keyed(
                 conf.fetch_quota,
                 nonzero!(256 * 1024usize),
             )),
+            admission: Admission::new(conf.admission),
             exec,
             repl,
-            #[cfg(feature = "replication-v3")]
+            reputations,
+            health,
             tins,
+            pending: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -75,6 +139,16 @@ impl Storage {
         self.rate.check_key(&(remote_peer, urn)).is_err()
     }
 
+    /// Whether all storage instances of the pool are in use.
+    fn is_pool_saturated(&self) -> bool {
+        self.pool.pool().is_saturated()
+    }
+
+    /// Close the storage pool, see [`Pool::close`].
+    pub(super) async fn close(&self, deadline: Instant) -> Result<(), storage::pool::Undrained> {
+        self.pool.pool().close(deadline).await
+    }
+
     async fn git_fetch(
         &self,
         from: impl Into<(PeerId, Vec<SocketAddr>)>,
@@ -87,7 +161,8 @@ impl Storage {
             }
         }
 
-        let git = self.pool.get().await?;
+        let _admitted = self.admission.admit(self.is_pool_saturated()).await?;
+        let git = self.pool.pool().get().await?;
         let urn = urn_context(*git.peer_id(), urn);
         let from = from.into();
         let remote_peer = from.0;
@@ -97,23 +172,33 @@ impl Storage {
 
         #[cfg(feature = "replication-v3")]
         {
-            use crate::net::protocol::Connected;
+            use crate::net::protocol::{event, Connected};
 
             match self.tins.connect(from).await {
                 None => Err(Error::NoConnection { remote_peer }),
                 Some(Connected(conn)) => {
-                    self.repl
-                        .replicate(&self.exec, git, conn, urn, None)
-                        .err_into::<Error>()
-                        .await
+                    let res = self
+                        .repl
+                        .replicate(&self.exec, git, conn, urn.clone(), None)
+                        .await;
+                    if let Err(replication::error::Replicate::Vetoed(veto)) = &res {
+                        self.tins.emit(event::upstream::Vetoed {
+                            urn,
+                            from: remote_peer,
+                            reason: veto.reason.clone(),
+                        });
+                    }
+                    res.map_err(Error::from)
                 },
             }
         }
         #[cfg(not(feature = "replication-v3"))]
         {
+            use futures::TryFutureExt as _;
+
             drop(git);
             self.repl
-                .replicate(&self.exec, &self.pool, from, urn, None)
+                .replicate(&self.exec, self.pool.pool(), from, urn, None)
                 .err_into::<Error>()
                 .await
         }
@@ -125,27 +210,24 @@ impl Storage {
         urn: Either<Urn, Originates<Urn>>,
         head: impl Into<Option<git2::Oid>>,
     ) -> bool {
-        let git = self
-            .pool
-            .get()
-            .await
-            .expect("unable to acquire storage from pool");
-        let urn = urn_context(*git.peer_id(), urn);
+        let local_peer_id = *self.pool.pool().get().await.expect(POOL_EXPECT).peer_id();
+        let urn = urn_context(local_peer_id, urn);
 
         if !self.urns.contains(&urn.clone().with_path(None).into()) {
             return false;
         }
 
         let head = head.into().map(ext::Oid::from);
-        self.exec
-            .blocking(move || match head {
-                None => git.as_ref().has_urn(&urn).unwrap_or(false),
+        self.pool
+            .with_read_only("git_has", move |git| match head {
+                None => git.has_urn(&urn).unwrap_or(false),
                 Some(head) => {
-                    git.as_ref().has_commit(&urn, head).unwrap_or(false)
-                        || git.as_ref().has_tag(&urn, head).unwrap_or(false)
+                    git.has_commit(&urn, head).unwrap_or(false)
+                        || git.has_tag(&urn, head).unwrap_or(false)
                 },
             })
             .await
+            .expect(POOL_EXPECT)
     }
 
     /// If the storage does not yet have the given `urn` *and* the default
@@ -153,13 +235,144 @@ impl Storage {
     /// want to passively replicate the `urn`. Otherwise, the `urn` is only
     /// considered tracked if we have a tracked entry for the given `peer`.
     async fn is_tracked(&self, urn: Urn, peer: PeerId) -> Result<bool, Error> {
-        let git = self.pool.get().await?;
-        self.exec
-            .blocking(move || -> Result<bool, Error> {
-                Ok(tracking::is_tracked(git.as_ref(), &urn, Some(peer))?
-                    || tracking::default_only(git.as_ref(), &urn)?)
+        self.pool
+            .with("is_tracked", move |git| -> Result<bool, Error> {
+                Ok(tracking::is_tracked(git, &urn, Some(peer))?
+                    || tracking::default_only(git, &urn)?)
             })
+            .await?
+    }
+
+    /// Offer `provider` for fetching the `announced` update.
+    ///
+    /// Returns a [`Pending`] guard if the caller should fetch the update, or
+    /// `None` if a fetch is already in progress, which will fall back to
+    /// `provider` if need be.
+    fn offer(&self, announced: &Announced, provider: Provider) -> Option<Pending> {
+        use std::collections::hash_map::Entry::*;
+
+        let mut pending = self.pending.lock();
+        let contended = pending.keys().any(|other| other.urn == announced.urn);
+        match pending.entry(announced.clone()) {
+            Vacant(entry) => {
+                entry.insert(vec![provider]);
+                Some(Pending {
+                    pending: Arc::clone(&self.pending),
+                    announced: announced.clone(),
+                    contended,
+                })
+            },
+            Occupied(mut entry) => {
+                let untried = entry.get_mut();
+                if !untried.iter().any(|(peer, _)| *peer == provider.0) {
+                    untried.push(provider);
+                }
+                None
+            },
+        }
+    }
+
+    /// Remove the best ranked of the untried providers of `announced`, see
+    /// [`Reputations::rank_with_latency`].
+    async fn next_provider(&self, announced: &Announced) -> Option<Provider> {
+        let latency = self.tins.latency().await;
+        let mut pending = self.pending.lock();
+        let untried = pending.get_mut(announced)?;
+        let mut ranked = self
+            .reputations
+            .rank_with_latency(&latency, untried.drain(..), |(peer, _)| *peer)
+            .into_iter();
+        let best = ranked.next();
+        untried.extend(ranked);
+        best
+    }
+
+    /// Fetch the `announced` update from `provider`.
+    async fn fetch_from(
+        &self,
+        (provider, addr_hints): Provider,
+        announced: &Announced,
+        has: &gossip::Payload,
+    ) -> Attempt {
+        use broadcast::PutResult;
+
+        let Announced { urn, origin, head } = announced.clone();
+        let urn = Right(Originates {
+            from: origin,
+            value: urn,
+        });
+
+        match self
+            .git_fetch((provider, addr_hints), urn.clone(), head)
             .await
+        {
+            Ok(_) => {
+                // Verify that the announced data is stored locally now.
+                //
+                // If it is, rewrite the gossip message to use the `origin`
+                // we determined -- everyone down the line may now fetch
+                // the that remote from us.
+                //
+                // Otherwise, the `provider` must be lying -- we are
+                // tracking them, and there was no error, but the data is
+                // still not there. In this case, try the next provider, or
+                // return `Stale` to terminate the broadcast here.
+                if self.git_has(urn, head).await {
+                    self.reputations
+                        .record(provider, reputation::Outcome::Success { bytes: 0 });
+                    Attempt::Done(PutResult::Applied(gossip::Payload {
+                        origin: Some(origin),
+                        ..has.clone()
+                    }))
+                } else {
+                    tracing::warn!(
+                        provider = %provider,
+                        announced = ?has,
+                        "provider announced non-existent rev"
+                    );
+                    self.reputations
+                        .record(provider, reputation::Outcome::Equivocation);
+                    Attempt::Failed(PutResult::Stale)
+                }
+            },
+
+            Err(e) => match e {
+                Error::KnownObject(_) => Attempt::Done(PutResult::Stale),
+                Error::Overloaded(admission::Overloaded { retry_after }) => {
+                    tracing::warn!(
+                        urn = %has.urn,
+                        queued = self.admission.queued(),
+                        "too many fetches in progress, not fetching"
+                    );
+                    Attempt::Done(PutResult::Overloaded { retry_after })
+                },
+                Error::RateLimited { remote_peer, urn } => {
+                    tracing::warn!(
+                        "skipped fetch of {} from {} due to rate limiting",
+                        remote_peer,
+                        urn
+                    );
+                    Attempt::Failed(PutResult::Stale)
+                },
+                x if self.health.observe(&x).is_some() => Attempt::Done(PutResult::Uninteresting),
+                x => {
+                    tracing::error!(err = %x, "fetch error");
+                    match &x {
+                        Error::Replication(e) if reputation::is_equivocation(e) => {
+                            self.reputations
+                                .record(provider, reputation::Outcome::Equivocation);
+                            Attempt::Failed(PutResult::Error)
+                        },
+                        Error::Replication(_) | Error::NoConnection { .. } => {
+                            self.reputations
+                                .record(provider, reputation::Outcome::Failure);
+                            Attempt::Failed(PutResult::Error)
+                        },
+                        _ => Attempt::Done(PutResult::Error),
+                    }
+                },
+            },
+        }
     }
 }
 
@@ -214,6 +427,13 @@ impl broadcast::LocalStorage<SocketAddr> for Storage {
         // If the `has` doesn't tell us to look into a specific remote-tracking
         // branch, assume we want the `provider`'s.
         let origin = has.origin.unwrap_or(provider);
+
+        // Our own update, which we announced ourselves and have already
+        if origin == self.local_peer_id {
+            tracing::trace!(provider = %provider, urn = %has.urn, "not fetching own update");
+            return PutResult::Stale;
+        }
+
         let is_tracked = match self.is_tracked(has.urn.clone(), origin).await {
             Ok(b) => b,
             Err(e) => {
@@ -222,62 +442,56 @@ impl broadcast::LocalStorage<SocketAddr> for Storage {
             },
         };
 
-        if is_tracked {
-            let urn = Right(Originates {
-                from: origin,
-                value: has.urn.clone(),
-            });
-            let head = has.rev.as_ref().map(|gossip::Rev::Git(head)| *head);
+        if !is_tracked {
+            return PutResult::Uninteresting;
+        }
 
-            match self
-                .git_fetch((provider, addr_hints), urn.clone(), head)
-                .await
-            {
-                Ok(_) => {
-                    // Verify that the announced data is stored locally now.
-                    //
-                    // If it is, rewrite the gossip message to use the `origin`
-                    // we determined -- everyone down the line may now fetch
-                    // the that remote from us.
-                    //
-                    // Otherwise, the `provider` must be lying -- we are
-                    // tracking them, and there was no error, but the data is
-                    // still not there. In this case, returning `Stale` will
-                    // just terminate the broadcast here.
-                    if self.git_has(urn, head).await {
-                        PutResult::Applied(gossip::Payload {
-                            origin: Some(origin),
-                            ..has
-                        })
-                    } else {
-                        tracing::warn!(
-                            provider = %provider,
-                            announced = ?has,
-                            "provider announced non-existent rev"
-                        );
-                        PutResult::Stale
-                    }
-                },
+        if !self.reputations.is_acceptable(&provider) {
+            tracing::debug!(
+                provider = %provider,
+                score = self.reputations.score(&provider),
+                "ignoring provider of low reputation"
+            );
+            return PutResult::Stale;
+        }
 
-                Err(e) => match e {
-                    Error::KnownObject(_) => PutResult::Stale,
-                    Error::RateLimited { remote_peer, urn } => {
-                        tracing::warn!(
-                            "skipped fetch of {} from {} due to rate limiting",
-                            remote_peer,
-                            urn
-                        );
-                        PutResult::Stale
-                    },
-                    x => {
-                        tracing::error!(err = %x, "fetch error");
-                        PutResult::Error
-                    },
+        // Relay, but don't attempt to fetch what we couldn't store
+        if self.health.is_read_only() {
+            tracing::debug!(urn = %has.urn, "storage is read-only, not fetching");
+            return PutResult::Uninteresting;
+        }
+
+        let announced = Announced {
+            urn: has.urn.clone(),
+            origin,
+            head: has.rev.as_ref().map(|gossip::Rev::Git(head)| *head),
+        };
+        let pending = match self.offer(&announced, (provider, addr_hints)) {
+            Some(pending) => pending,
+            None => {
+                tracing::trace!(provider = %provider, "update is already being fetched");
+                return PutResult::Stale;
+            },
+        };
+        // Other updates of the URN are in flight, give their providers a
+        // chance to offer this one, too
+        if pending.contended {
+            link_async::sleep(COALESCE_WINDOW).await;
+        }
+
+        let mut result = PutResult::Error;
+        while let Some(provider) = self.next_provider(&announced).await {
+            match self.fetch_from(provider, &announced, &has).await {
+                Attempt::Done(done) => {
+                    result = done;
+                    break;
                 },
+                Attempt::Failed(failed) => result = failed,
             }
-        } else {
-            PutResult::Uninteresting
         }
+        drop(pending);
+
+        result
     }
 
     #[tracing::instrument(level = "debug", skip(self))]
@@ -299,6 +513,6 @@ impl broadcast::LocalStorage<SocketAddr> for Storage {
 #[async_trait]
 impl storage::Pooled<storage::Storage> for Storage {
     async fn get(&self) -> Result<PooledRef<storage::Storage>, PoolError> {
-        self.pool.get().await.map(PooledRef::from)
+        self.pool.pool().get().await
     }
 }
@@ -0,0 +1,123 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Admission control for fetches triggered by gossip.
+//!
+//! A fetch is admitted right away while fewer than [`Config::max_running`]
+//! fetches are in progress, and the storage pool has an idle instance. When
+//! either is exhausted, up to [`Config::max_queued`] fetches wait for a slot
+//! to become available. Any further fetch is rejected as [`Overloaded`], which
+//! is reported back to the gossiping neighbour (see
+//! [`crate::net::protocol::backoff`]) instead of piling up tasks.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_lock::{Semaphore, SemaphoreGuardArc};
+use thiserror::Error;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Maximum number of fetches in progress at the same time.
+    pub max_running: usize,
+    /// Maximum number of fetches waiting to be run.
+    pub max_queued: usize,
+    /// The time neighbours are asked to back off for when a fetch is
+    /// rejected.
+    pub retry_after: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_running: 16,
+            max_queued: 64,
+            retry_after: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("too many fetches in progress, retry after {retry_after:?}")]
+pub struct Overloaded {
+    pub retry_after: Duration,
+}
+
+/// Proof of admission. The slot is released when this is dropped.
+pub struct Admitted {
+    _slot: SemaphoreGuardArc,
+}
+
+#[derive(Clone)]
+pub struct Admission {
+    config: Config,
+    slots: Arc<Semaphore>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl Admission {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            slots: Arc::new(Semaphore::new(config.max_running)),
+            queued: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Number of fetches currently waiting for a slot.
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Admit a fetch, waiting for a slot if necessary.
+    ///
+    /// `pool_saturated` indicates that the storage pool has no idle
+    /// instances, in which case the fetch is queued even if a slot is
+    /// available.
+    pub async fn admit(&self, pool_saturated: bool) -> Result<Admitted, Overloaded> {
+        if !pool_saturated {
+            if let Some(slot) = self.slots.try_acquire_arc() {
+                return Ok(Admitted { _slot: slot });
+            }
+        }
+
+        let queued = Queued::enter(&self.queued, self.config.max_queued).ok_or(Overloaded {
+            retry_after: self.config.retry_after,
+        })?;
+        let slot = self.slots.acquire_arc().await;
+        drop(queued);
+
+        Ok(Admitted { _slot: slot })
+    }
+}
+
+/// A place in the queue, given up when dropped.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl<'a> Queued<'a> {
+    fn enter(queued: &'a AtomicUsize, max: usize) -> Option<Self> {
+        queued
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < max).then(|| n + 1)
+            })
+            .ok()
+            .map(|_| Self(queued))
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
@@ -20,6 +20,9 @@ pub enum Error {
     #[error("too many fetches from {remote_peer}")]
     RateLimited { remote_peer: PeerId, urn: git::Urn },
 
+    #[error(transparent)]
+    Overloaded(#[from] super::admission::Overloaded),
+
     #[error("no connection to {remote_peer}")]
     NoConnection { remote_peer: PeerId },
 
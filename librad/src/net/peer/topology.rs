@@ -0,0 +1,106 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Changes of the membership views, as a typed stream.
+//!
+//! The protocol reports changes of the active view as
+//! [`membership::Transition`]s among all other [`event::Upstream`] events.
+//! [`super::Peer::membership_events`] picks them out, and tells apart peers
+//! which join the active view from peers which are promoted from the passive
+//! view, along with the sizes of both views after the change.
+//!
+//! The first [`Event`] is always a [`Event::Snapshot`] of the views. Another
+//! snapshot is yielded whenever the subscriber lagged behind and missed
+//! changes, so consumers can always resynchronise their state.
+
+use futures::{Stream, StreamExt as _};
+
+use crate::{
+    net::protocol::{
+        event::{self, downstream::MembershipInfo},
+        membership::Transition,
+        RecvError,
+        TinCans,
+    },
+    PeerId,
+};
+
+/// The number of peers in the active and passive views.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Sizes {
+    pub active: usize,
+    pub passive: usize,
+}
+
+impl From<&MembershipInfo> for Sizes {
+    fn from(info: &MembershipInfo) -> Self {
+        Self {
+            active: info.active.len(),
+            passive: info.passive.len(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The current members of the views.
+    Snapshot {
+        active: Vec<PeerId>,
+        passive: Vec<PeerId>,
+    },
+    /// A peer not previously known joined the active view.
+    Joined { peer: PeerId, sizes: Sizes },
+    /// A peer was promoted from the passive to the active view.
+    Promoted { peer: PeerId, sizes: Sizes },
+    /// A peer was demoted from the active to the passive view.
+    Demoted { peer: PeerId, sizes: Sizes },
+    /// A peer was removed from either view.
+    Left { peer: PeerId, sizes: Sizes },
+}
+
+impl Event {
+    fn snapshot(info: &MembershipInfo) -> Self {
+        Self::Snapshot {
+            active: info.active.clone(),
+            passive: info.passive.clone(),
+        }
+    }
+}
+
+pub(super) fn events(phone: TinCans) -> impl Stream<Item = Event> {
+    async_stream::stream! {
+        // Subscribe before taking the snapshot, so no change goes missing
+        let upstream = phone.subscribe();
+        futures::pin_mut!(upstream);
+
+        let mut view = phone.membership().await;
+        yield Event::snapshot(&view);
+
+        while let Some(evt) = upstream.next().await {
+            match evt {
+                Ok(event::Upstream::Membership(transition)) => {
+                    let next = phone.membership().await;
+                    let sizes = Sizes::from(&next);
+                    let event = match transition {
+                        Transition::Promoted(info) if view.passive.contains(&info.peer_id) => {
+                            Event::Promoted { peer: info.peer_id, sizes }
+                        },
+                        Transition::Promoted(info) => Event::Joined { peer: info.peer_id, sizes },
+                        Transition::Demoted(info) => Event::Demoted { peer: info.peer_id, sizes },
+                        Transition::Evicted(info) => Event::Left { peer: info.peer_id, sizes },
+                    };
+                    view = next;
+                    yield event
+                },
+                Ok(_) => {},
+                Err(RecvError::Lagged(_)) => {
+                    view = phone.membership().await;
+                    yield Event::snapshot(&view)
+                },
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+}
@@ -7,7 +7,8 @@ use std::{fmt::Debug, future::Future, net::SocketAddr, sync::Arc};
 
 use async_stream::stream;
 use futures::{stream::BoxStream, StreamExt};
-use link_async::Spawner;
+use link_async::{CancellationToken, Spawner};
+use link_git::protocol::upload_pack;
 use nonempty::NonEmpty;
 use nonzero_ext::nonzero;
 use rand_pcg::Pcg64Mcg;
@@ -16,6 +17,7 @@ use tracing::Instrument as _;
 
 use super::{
     connection::{LocalAddr, LocalPeer},
+    dial,
     quic,
     upgrade,
     Network,
@@ -29,18 +31,38 @@ use crate::{
     Signer,
 };
 
+pub mod attachments;
+pub mod backoff;
+pub mod batch;
 pub mod broadcast;
 
 pub mod cache;
 pub use cache::Caches;
 
+pub mod capture;
+pub mod cas;
+pub mod checkpoint;
+pub mod compress;
+
 pub mod error;
 pub mod event;
 pub mod gossip;
+pub mod interest;
 pub mod interrogation;
+pub mod inventory;
 pub mod io;
+pub mod latency;
+pub mod lfs;
+pub mod mailbox;
 pub mod membership;
+pub mod mux;
+pub mod outbox;
+pub mod ping;
+pub mod pinned;
 pub mod request_pull;
+pub mod rotation;
+pub mod skew;
+pub mod usage;
 
 mod info;
 pub use info::{Capability, PartialPeerInfo, PeerAdvertisement, PeerInfo};
@@ -70,6 +92,34 @@ pub struct Config<Guard = config::DenyAll> {
     pub replication: replication::Config,
     pub rate_limits: Quota,
     pub request_pull: Guard,
+    pub mailbox: mailbox::Config,
+    pub bandwidth: quic::shaping::Config,
+    pub dial: dial::Config,
+    pub gossip_batch: batch::Config,
+    pub pinned: pinned::Config,
+    pub lfs: lfs::Config,
+    pub attachments: attachments::Config,
+    pub streams: mux::Config,
+    pub capture: capture::Config,
+    pub outbox: outbox::Config,
+    pub compression: compress::Config,
+    pub interest: interest::Config,
+    pub backoff: backoff::Config,
+    pub checkpoint: checkpoint::Config,
+    pub usage: usage::Config,
+    /// Limits imposed on `ls-refs` requests served to other peers.
+    pub ls_refs: upload_pack::Limits,
+    /// Run as a private peer.
+    ///
+    /// A private peer connects to other peers, but refuses inbound
+    /// connections. It advertises no listen addresses, and announces
+    /// [`Capability::Private`], so that other peers neither include it in
+    /// shuffles nor forward its joins. Its address is thus only known to the
+    /// peers it connects to, typically the configured seeds.
+    pub private: bool,
+    /// The rotation of the local key to serve to peers asking for it, see
+    /// [`rotation`].
+    pub rotation: Option<rotation::Rotation>,
     // TODO: transport, ...
 }
 
@@ -130,6 +180,18 @@ impl<S, G> Bound<S, G> {
         self.state.endpoint.listen_addrs()
     }
 
+    /// Obtain a [`capture::Replayer`] feeding captured messages into this
+    /// protocol instance.
+    pub fn replayer(&self) -> capture::Replayer<S, G>
+    where
+        S: Clone,
+        G: Clone,
+    {
+        capture::Replayer {
+            state: self.state.clone(),
+        }
+    }
+
     /// Start accepting connections from remote peers.
     ///
     /// Returns a tuple of
@@ -177,6 +239,8 @@ pub async fn bind<Sign, Store, Guard>(
     signer: Sign,
     storage: Store,
     caches: cache::Caches,
+    health: storage::Health,
+    usage: usage::Usage,
 ) -> Result<Bound<Store, Guard>, error::Bootstrap>
 where
     Sign: Signer + Clone + Send + Sync + 'static,
@@ -190,6 +254,7 @@ where
         config.listen_addr,
         config.advertised_addrs,
         config.network,
+        config.bandwidth,
     )
     .await?;
     let (membership, periodic) = membership::Hpv::<_, SocketAddr>::new(
@@ -197,20 +262,49 @@ where
         Pcg64Mcg::new(rand::random()),
         config.membership,
     );
+    for peer in config.pinned.peers.keys() {
+        membership.pin(*peer);
+    }
     let gossip = broadcast::State::new(
         Storage::new(storage.clone(), config.rate_limits.storage.clone()),
         (),
     );
+    let inventory = inventory::State::new(Storage::new(
+        storage.clone(),
+        config.rate_limits.storage.clone(),
+    ));
     let request_pull = request_pull::State::new(
         Storage::new(storage, config.rate_limits.storage),
         config.paths.clone(),
         config.request_pull,
     );
+    let lfs = lfs::Store::new(&config.paths, config.lfs);
+    let attachments = attachments::Store::new(&config.paths, config.attachments);
+    let capture = config
+        .capture
+        .path
+        .as_deref()
+        .map(capture::Recorder::create)
+        .transpose()
+        .map_err(error::Bootstrap::Capture)?;
+    let outbox = outbox::Outbox::new(config.outbox, config.paths.git_dir())
+        .map_err(error::Bootstrap::Outbox)?;
+    let mailbox = mailbox::Mailbox::open(config.mailbox, config.paths.git_dir())
+        .map_err(error::Bootstrap::Mailbox)?;
     let limits = RateLimits {
         membership: Arc::new(RateLimiter::keyed(
             config.rate_limits.membership,
             nonzero!(1024 * 1024usize),
         )),
+        offenders: state::Offenders::new(config.rate_limits.membership_ban),
+        attachments: Arc::new(RateLimiter::keyed(
+            config.rate_limits.attachments,
+            nonzero!(256 * 1024usize),
+        )),
+        inventory: Arc::new(RateLimiter::keyed(
+            config.rate_limits.inventory,
+            nonzero!(256 * 1024usize),
+        )),
     };
 
     let state = State {
@@ -218,14 +312,54 @@ where
         endpoint,
         membership,
         gossip,
+        inventory,
         request_pull,
         phone: phone.clone(),
         config: StateConfig {
             paths: Arc::new(config.paths),
+            checkpoint: config.checkpoint,
+            ls_refs: config.ls_refs,
+            rotation: config.rotation,
+            family: config.dial.family,
+            capabilities: Arc::new(
+                config
+                    .gossip_batch
+                    .enabled
+                    .then(|| Capability::GossipBatch)
+                    .into_iter()
+                    .chain(
+                        config
+                            .compression
+                            .enabled
+                            .then(|| Capability::GossipCompression),
+                    )
+                    .chain(config.interest.enabled.then(|| Capability::GossipInterest))
+                    .chain(config.backoff.enabled.then(|| Capability::GossipBackoff))
+                    .chain(config.private.then(|| Capability::Private))
+                    .chain(Some(Capability::Ping))
+                    .collect(),
+            ),
         },
         caches,
+        health,
+        usage,
         spawner,
+        cancel: CancellationToken::new(),
         limits,
+        latency: latency::Tracker::default(),
+        liveness: ping::Liveness::default(),
+        skew: skew::Tracker::default(),
+        lfs,
+        attachments,
+        mailbox,
+        batches: batch::Batches::new(config.gossip_batch),
+        pinned: pinned::Pinned::new(config.pinned),
+        streams: mux::Streams::new(config.streams),
+        capture,
+        outbox,
+        compression: compress::Compression::new(config.compression),
+        interests: interest::Interests::new(config.interest),
+        backoffs: backoff::Backoffs::new(config.backoff),
     };
 
     Ok(Bound {
@@ -268,10 +402,22 @@ where
 
     let endpoint = state.endpoint.clone();
     let spawner = state.spawner.clone();
+    let cancel = state.cancel.clone();
 
     let tasks = [
         spawner.spawn(accept::disco(state.clone(), disco)),
         spawner.spawn(accept::periodic(state.clone(), periodic)),
+        spawner.spawn(accept::latency(state.clone())),
+        spawner.spawn(accept::liveness(state.clone())),
+        spawner.spawn(accept::clock_skew(state.clone())),
+        spawner.spawn(accept::mailbox(state.clone(), phone.subscribe())),
+        spawner.spawn(accept::mailbox_expiry(state.clone())),
+        spawner.spawn(accept::interest(state.clone(), phone.subscribe())),
+        spawner.spawn(accept::inventory(state.clone(), phone.subscribe())),
+        spawner.spawn(accept::pinned(state.clone())),
+        spawner.spawn(accept::outbox(state.clone())),
+        spawner.spawn(accept::checkpoint(state.clone())),
+        spawner.spawn(accept::storage_health(state.clone())),
         spawner.spawn(accept::ground_control(
             state.clone(),
             stream! {
@@ -295,7 +441,12 @@ where
         .in_current_span()
     };
 
-    (move || endpoint.close(), run)
+    let shutdown = move || {
+        cancel.cancel();
+        endpoint.close()
+    };
+
+    (shutdown, run)
 }
 
 pub trait ProtocolStorage<A>:
@@ -3,24 +3,41 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{iter, net::SocketAddr};
+use std::{collections::BTreeSet, iter, net::SocketAddr, time::Duration};
 
-use futures::stream::{self, StreamExt as _};
+use futures::{
+    future,
+    stream::{self, StreamExt as _},
+};
 
 use super::{
+    broadcast,
+    cache,
+    checkpoint,
     control,
     event,
     gossip,
     io,
+    latency,
+    mailbox,
     membership,
+    ping,
+    pinned,
+    skew,
     tick,
+    Capability,
+    PartialPeerInfo,
     PeerInfo,
     ProtocolStorage,
     RecvError,
     RequestPullGuard,
     State,
 };
-use crate::PeerId;
+use crate::{
+    git::storage::health,
+    net::connection::{CloseReason, RemoteAddr as _},
+    PeerId,
+};
 
 #[tracing::instrument(skip(state, disco))]
 pub(super) async fn disco<S, G, D>(state: State<S, G>, disco: D)
@@ -32,7 +49,9 @@ where
     disco
         .for_each(|(peer, addrs)| {
             let state = state.clone();
-            async move { io::discovered(state, peer, addrs).await }
+            async move {
+                io::discovered(state, peer, addrs).await;
+            }
         })
         .await
 }
@@ -53,10 +72,7 @@ where
                         .into_iter()
                         .map(|info| tick::Tock::AttemptSend {
                             to: info,
-                            message: state
-                                .membership
-                                .hello(io::peer_advertisement(&state.endpoint)())
-                                .into(),
+                            message: state.membership.hello(state.peer_advertisement()()).into(),
                         })
                         .collect::<Vec<_>>(),
                 )
@@ -66,14 +82,14 @@ where
                 recipient,
                 sample,
                 ttl,
-            }) => {
+            }) if !state.is_private() => {
                 tracing::info!("initiating shuffle");
                 stream::iter(vec![tick::Tock::SendConnected {
                     to: recipient,
                     message: membership::Message::Shuffle {
                         origin: PeerInfo {
                             peer_id: state.local_id,
-                            advertised_info: io::peer_advertisement(&state.endpoint)(),
+                            advertised_info: state.peer_advertisement()(),
                             seen_addrs: iter::empty().into(),
                         },
                         peers: sample,
@@ -83,6 +99,13 @@ where
                 }])
             },
 
+            // Shuffling would make the recipients reply to us, and pass our
+            // address on to other peers.
+            membership::Periodic::Shuffle(_) => {
+                tracing::debug!("not shuffling, running as private peer");
+                stream::iter(vec![])
+            },
+
             membership::Periodic::Tickle => {
                 // Tickle connections in the partial view.
                 //
@@ -112,6 +135,441 @@ where
         .await;
 }
 
+#[tracing::instrument(skip(state))]
+pub(super) async fn latency<S, G>(state: State<S, G>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    let ticks = link_async::interval(latency::SAMPLE_INTERVAL, Duration::from_secs(1));
+    futures::pin_mut!(ticks);
+    while ticks.next().await.is_some() {
+        let connected = state.endpoint.peers().into_iter().collect::<BTreeSet<_>>();
+        for peer in &connected {
+            if let Some(conn) = state.endpoint.get_connection(*peer) {
+                state
+                    .latency
+                    .record(*peer, latency::Sample::from_connection(&conn));
+            }
+        }
+        state.latency.retain(|peer| connected.contains(peer));
+        state.streams.retain(|peer| connected.contains(peer));
+        state.emit(Some(event::upstream::Latency(state.latency.snapshot())));
+        state.emit(Some(event::upstream::ConnectionStats(
+            state.endpoint.connection_stats(),
+        )));
+    }
+}
+
+#[tracing::instrument(skip(state))]
+pub(super) async fn liveness<S, G>(state: State<S, G>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    let ticks = link_async::interval(ping::INTERVAL, Duration::from_secs(1));
+    futures::pin_mut!(ticks);
+    while ticks.next().await.is_some() {
+        // Peers which don't advertise the capability can't decode the request
+        let pings = state
+            .membership
+            .active()
+            .into_iter()
+            .filter(|peer| state.membership.has_capability(peer, &Capability::Ping))
+            .filter_map(|peer| {
+                state.endpoint.get_connection(peer).map(|conn| {
+                    let state = state.clone();
+                    async move {
+                        if let Err(e) = control::ping_connection(&state, &conn, peer).await {
+                            tracing::debug!(%peer, err = %e, "ping failed");
+                            if state.liveness.get(&peer).map_or(false, |h| !h.is_alive()) {
+                                tracing::warn!(%peer, "peer unresponsive, disconnecting");
+                                conn.close(CloseReason::Timeout);
+                            }
+                        }
+                    }
+                })
+            });
+        future::join_all(pings).await;
+
+        let connected = state.endpoint.peers().into_iter().collect::<BTreeSet<_>>();
+        state.liveness.retain(|peer| connected.contains(peer));
+    }
+}
+
+#[tracing::instrument(skip(state))]
+pub(super) async fn clock_skew<S, G>(state: State<S, G>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    let ticks = link_async::interval(skew::INTERVAL, Duration::from_secs(1));
+    futures::pin_mut!(ticks);
+    while ticks.next().await.is_some() {
+        let queries = state.membership.active().into_iter().filter_map(|peer| {
+            state.endpoint.get_connection(peer).map(|conn| {
+                let state = state.clone();
+                async move {
+                    if let Err(e) = control::clock_skew_connection(&state, &conn, peer).await {
+                        tracing::debug!(%peer, err = %e, "failed to obtain remote time");
+                    }
+                }
+            })
+        });
+        future::join_all(queries).await;
+
+        let connected = state.endpoint.peers().into_iter().collect::<BTreeSet<_>>();
+        state.skew.retain(|peer| connected.contains(peer));
+        if let Some(median_offset_ms) = state.skew.check() {
+            tracing::warn!(
+                median_offset_ms,
+                "local clock appears to be skewed, signature timestamps and cache expiry may misbehave"
+            );
+            state.emit(Some(event::upstream::ClockSkew {
+                median_offset_ms,
+                peers: state.skew.snapshot().len(),
+            }));
+        }
+    }
+}
+
+#[tracing::instrument(skip(state, events))]
+pub(super) async fn mailbox<S, G, E>(state: State<S, G>, events: E)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+    E: futures::Stream<Item = Result<event::Upstream, RecvError>>,
+{
+    use event::{upstream::Gossip, Upstream};
+
+    futures::pin_mut!(events);
+    while let Some(x) = events.next().await {
+        match x {
+            Err(RecvError::Closed) => break,
+
+            Err(RecvError::Lagged(i)) => {
+                tracing::warn!("mailbox skipped {} events", i)
+            },
+
+            Ok(Upstream::Gossip(put)) => {
+                let Gossip::Put {
+                    provider,
+                    payload,
+                    result,
+                } = *put;
+                state.mailbox.interested(provider.peer_id, &payload.urn);
+                if let broadcast::PutResult::Applied(ap) = result {
+                    let n = state
+                        .mailbox
+                        .deposit(&ap, |peer| state.endpoint.get_connection(*peer).is_some());
+                    tracing::trace!(urn = %ap.urn, "deposited in {} mailboxes", n);
+                }
+            },
+
+            Ok(Upstream::Membership(membership::Transition::Promoted(info))) => {
+                let letters = state.mailbox.collect(&info.peer_id);
+                if letters.is_empty() {
+                    continue;
+                }
+                tracing::debug!(
+                    peer = %info.peer_id,
+                    "delivering {} pending announcements",
+                    letters.len()
+                );
+                let origin = PeerInfo {
+                    peer_id: state.local_id,
+                    advertised_info: state.peer_advertisement()(),
+                    seen_addrs: iter::empty().into(),
+                };
+                for payload in letters {
+                    tick::tock(
+                        state.clone(),
+                        tick::Tock::SendConnected {
+                            to: info.peer_id,
+                            message: broadcast::Message::have(origin.clone(), payload).into(),
+                        },
+                    )
+                    .await
+                }
+            },
+
+            Ok(_) => {},
+        }
+    }
+}
+
+/// Declare the local interests to neighbours which advertised
+/// [`Capability::GossipInterest`] when they are promoted, and to all of them
+/// whenever the URN cache is rebuilt. See [`super::interest`].
+#[tracing::instrument(skip(state, events))]
+pub(super) async fn interest<S, G, E>(state: State<S, G>, events: E)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+    E: futures::Stream<Item = Result<event::Upstream, RecvError>>,
+{
+    use event::{upstream::Caches, Upstream};
+    use membership::Transition::*;
+
+    if !state.interests.config().enabled {
+        return;
+    }
+
+    futures::pin_mut!(events);
+    while let Some(x) = events.next().await {
+        match x {
+            Err(RecvError::Closed) => break,
+
+            Err(RecvError::Lagged(i)) => {
+                tracing::warn!("interest skipped {} events", i)
+            },
+
+            Ok(Upstream::Membership(Promoted(info))) => {
+                declare_interest(&state, Some(info.peer_id)).await
+            },
+
+            Ok(Upstream::Membership(Demoted(PeerInfo { peer_id, .. })))
+            | Ok(Upstream::Membership(Evicted(PartialPeerInfo { peer_id, .. }))) => {
+                state.interests.remove(&peer_id);
+            },
+
+            Ok(Upstream::Caches(Caches::Urns(cache::urns::Event::Rebuilt { .. }))) => {
+                declare_interest(&state, state.membership.active()).await
+            },
+
+            Ok(_) => {},
+        }
+    }
+}
+
+async fn declare_interest<S, G>(state: &State<S, G>, peers: impl IntoIterator<Item = PeerId>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    let recipients = peers
+        .into_iter()
+        .filter(|peer| {
+            state
+                .membership
+                .has_capability(peer, &Capability::GossipInterest)
+        })
+        .collect::<Vec<_>>();
+    if recipients.is_empty() {
+        return;
+    }
+
+    let filter = state.caches.urns.get().clone();
+    for to in recipients {
+        tracing::debug!(peer = %to, "declaring interests");
+        tick::tock(
+            state.clone(),
+            tick::Tock::SendConnected {
+                to,
+                message: io::Rpc::Interest(filter.clone()),
+            },
+        )
+        .await
+    }
+}
+
+/// Prefetch the URNs a reconciled inventory revealed to be out of date, see
+/// [`super::inventory::State::prefetch`].
+#[tracing::instrument(skip(state, events))]
+pub(super) async fn inventory<S, G, E>(state: State<S, G>, events: E)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+    E: futures::Stream<Item = Result<event::Upstream, RecvError>>,
+{
+    use event::{upstream::Inventory, Upstream};
+
+    futures::pin_mut!(events);
+    while let Some(x) = events.next().await {
+        match x {
+            Err(RecvError::Closed) => break,
+
+            Err(RecvError::Lagged(i)) => {
+                tracing::warn!("inventory skipped {} events", i)
+            },
+
+            Ok(Upstream::Inventory(Inventory {
+                peer, signed_refs, ..
+            })) => {
+                let addrs = match state.endpoint.get_connection(peer) {
+                    Some(conn) => vec![conn.remote_addr()],
+                    None => continue,
+                };
+                match state
+                    .inventory
+                    .prefetch(&state.spawner, peer, addrs, signed_refs)
+                    .await
+                {
+                    Ok(n) => tracing::debug!(%peer, "prefetched {} urns", n),
+                    Err(e) => tracing::warn!(%peer, err = %e, "inventory prefetch failed"),
+                }
+            },
+
+            Ok(_) => {},
+        }
+    }
+}
+
+#[tracing::instrument(skip(state))]
+pub(super) async fn pinned<S, G>(state: State<S, G>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+    G: RequestPullGuard,
+{
+    future::join_all(
+        state
+            .pinned
+            .config()
+            .peers
+            .clone()
+            .into_iter()
+            .map(|(peer, addrs)| redial(state.clone(), peer, addrs)),
+    )
+    .await;
+}
+
+/// Keep the pinned `peer` in the active view, redialling it with exponential
+/// backoff whenever it isn't.
+#[tracing::instrument(skip(state, addrs))]
+async fn redial<S, G>(state: State<S, G>, peer: PeerId, addrs: Vec<SocketAddr>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+    G: RequestPullGuard,
+{
+    let min_backoff = state.pinned.config().min_backoff;
+    let max_backoff = state.pinned.config().max_backoff;
+
+    let mut attempts = 0;
+    let mut backoff = min_backoff;
+    loop {
+        if !state.membership.is_active(&peer) {
+            attempts += 1;
+            tracing::debug!(attempts, "dialling pinned peer");
+            match state.endpoint.get_connection(peer) {
+                Some(conn) => io::join(&state, peer, &conn).await,
+                None => io::discovered(state.clone(), peer, addrs.clone()).await,
+            };
+        }
+
+        let status = if state.membership.is_active(&peer) {
+            attempts = 0;
+            backoff = min_backoff;
+            pinned::Status::Connected
+        } else {
+            pinned::Status::Disconnected { attempts, backoff }
+        };
+        if state.pinned.update(peer, status) {
+            state.emit(Some(event::upstream::Pinned { peer, status }));
+        }
+
+        link_async::sleep(backoff).await;
+        if attempts > 0 {
+            backoff = (backoff * 2).min(max_backoff);
+        }
+    }
+}
+
+#[tracing::instrument(skip(state))]
+pub(super) async fn mailbox_expiry<S, G>(state: State<S, G>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    let ticks = link_async::interval(mailbox::EXPIRY_INTERVAL, Duration::from_secs(1));
+    futures::pin_mut!(ticks);
+    while ticks.next().await.is_some() {
+        state.mailbox.expire();
+        let mailbox = state.mailbox.clone();
+        if let Err(e) = state.spawner.blocking(move || mailbox.flush()).await {
+            tracing::warn!(err = %e, "failed to write mailboxes")
+        }
+    }
+}
+
+/// Retry announcements which could not be delivered to any peer.
+#[tracing::instrument(skip(state))]
+pub(super) async fn outbox<S, G>(state: State<S, G>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    use event::downstream::Gossip;
+
+    let ticks = link_async::interval(state.outbox.retry_interval(), Duration::from_secs(1));
+    futures::pin_mut!(ticks);
+    while ticks.next().await.is_some() {
+        if state.membership.broadcast_recipients(None).is_empty() {
+            continue;
+        }
+        for (seq, payload) in state.outbox.pending() {
+            tracing::debug!(urn = %payload.urn, "retrying announcement");
+            if control::broadcast_to(&state, Gossip::Announce(payload), None).await {
+                let outbox = state.outbox.clone();
+                state.spawner.blocking(move || outbox.delivered(seq)).await
+            }
+        }
+    }
+}
+
+/// Restore the last [`checkpoint`], if fresh enough, and take a new one
+/// every [`checkpoint::Config::interval`].
+#[tracing::instrument(skip(state))]
+pub(super) async fn checkpoint<S, G>(state: State<S, G>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+    G: RequestPullGuard,
+{
+    let config = state.config.checkpoint;
+    if !config.enabled {
+        return;
+    }
+    let path = state.config.paths.git_dir().join(checkpoint::FILE_NAME);
+
+    match checkpoint::Checkpoint::load(&path) {
+        Ok(Some(cp)) if cp.age() <= config.max_age => checkpoint::restore(state.clone(), cp).await,
+        Ok(Some(cp)) => tracing::info!(age = ?cp.age(), "ignoring stale protocol checkpoint"),
+        Ok(None) => {},
+        Err(e) => tracing::warn!(err = ?e, "unable to load protocol checkpoint"),
+    }
+
+    let ticks = link_async::interval(config.interval, Duration::from_secs(1));
+    futures::pin_mut!(ticks);
+    while ticks.next().await.is_some() {
+        if state.health.is_read_only() {
+            continue;
+        }
+        if let Err(e) = checkpoint::take(&state).store(&path) {
+            tracing::warn!(err = ?e, "unable to store protocol checkpoint")
+        }
+    }
+}
+
+/// Probe the storage every [`health::PROBE_INTERVAL`] while it is read-only,
+/// so normal operation resumes once it can be written to again.
+#[tracing::instrument(skip(state))]
+pub(super) async fn storage_health<S, G>(state: State<S, G>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    let ticks = link_async::interval(health::PROBE_INTERVAL, Duration::from_secs(1));
+    futures::pin_mut!(ticks);
+    while ticks.next().await.is_some() {
+        if !state.health.is_read_only() {
+            continue;
+        }
+        let health = state.health.clone();
+        if let Err(e) = state.spawner.blocking(move || health.probe()).await {
+            tracing::debug!(err = %e, "storage is still not writable")
+        }
+    }
+}
+
 #[tracing::instrument(skip(state, rx))]
 pub(super) async fn ground_control<S, G, E>(state: State<S, G>, rx: E)
 where
@@ -119,7 +577,7 @@ where
     G: RequestPullGuard,
     E: futures::Stream<Item = Result<event::Downstream, RecvError>>,
 {
-    use event::Downstream;
+    use event::{downstream::Gossip, Downstream};
 
     futures::pin_mut!(rx);
     while let Some(x) = rx.next().await {
@@ -134,11 +592,15 @@ where
             },
 
             Ok(evt) => match evt {
+                Downstream::Gossip(Gossip::Announce(x)) => control::announce(&state, x).await,
                 Downstream::Gossip(x) => control::gossip(&state, x, None).await,
                 Downstream::Info(x) => control::info(&state, x),
                 Downstream::Interrogation(x) => control::interrogation(x).await,
+                Downstream::Inventory(x) => control::inventory(x).await,
+                Downstream::Ping(x) => control::ping(&state, x).await,
                 Downstream::RequestPull(x) => control::request_pull(x).await,
                 Downstream::Connect(x) => control::connect(&state, x).await,
+                Downstream::Disconnect(x) => control::disconnect(&state, x).await,
             },
         }
     }
@@ -0,0 +1,309 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Small binary attachments, such as avatars or screenshots.
+//!
+//! Identity payloads and cob changes are replicated with every fetch of the
+//! namespace they belong to, so binary content should not be embedded in
+//! them. Instead, it is kept in a content-addressed [`Store`] (see [`cas`],
+//! which is shared with [large files](super::lfs)), and referred to by an
+//! [`Attachment`], which records the SHA-256 and size of the content.
+//! [`Attachment`]s can be serialised as part of any payload, the [`Avatar`]
+//! payload extension being one example.
+//!
+//! The content is transferred on demand over a dedicated stream, see
+//! [`crate::net::upgrade::Attachments`]. Attachments exceeding
+//! [`Config::max_size`] are neither served nor fetched, and every remote peer
+//! may only request so many attachments per unit of time, see
+//! [`crate::net::protocol::Quota::attachments`].
+//!
+//! Unlike [large files](super::lfs), attachments are small, so transfers are
+//! not resumable: the content is received into memory, and only written to
+//! the store once its hash was verified.
+
+use std::{
+    fs,
+    io::{self, Read as _},
+    path::{Path, PathBuf},
+};
+
+use futures::StreamExt as _;
+use link_async::Spawner;
+use minicbor::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{cas, io as proto_io, quic};
+use crate::{
+    identities::payload::{Extension, HasNamespace},
+    paths::Paths,
+};
+
+pub use super::{
+    cas::Oid,
+    lfs::{CHUNK_SIZE, FRAMED_BUFSIZ},
+};
+
+mod rpc;
+pub use rpc::{Error, Request, Response};
+
+lazy_static! {
+    static ref AVATAR_NAMESPACE_V1: Url =
+        Url::parse("https://radicle.xyz/link/identities/avatar/v1").unwrap();
+}
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use super::{rpc, Oid};
+    use crate::net::{connection::CloseReason, protocol::error::Rpc, quic};
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Store {
+        #[error("{size} bytes exceed the limit of {max} bytes")]
+        TooLarge { size: u64, max: u64 },
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Fetch {
+        #[error("{size} bytes exceed the limit of {max} bytes")]
+        TooLarge { size: u64, max: u64 },
+
+        #[error("received more than the expected {0} bytes")]
+        Overflow(u64),
+
+        #[error("expected {expected} bytes, received {received}")]
+        Truncated { expected: u64, received: u64 },
+
+        #[error("content of {expected} does not match, got {actual}")]
+        Corrupt { expected: Oid, actual: Oid },
+
+        #[error("error response: {0:?}")]
+        ErrorResponse(rpc::Error),
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+
+        #[error(transparent)]
+        Rpc(#[from] Box<Rpc<quic::BidiStream>>),
+    }
+
+    impl Fetch {
+        /// The [`CloseReason`] the remote end gave for closing the stream or
+        /// connection, if any.
+        pub fn close_reason(&self) -> Option<CloseReason> {
+            match self {
+                Self::Rpc(e) => e.close_reason(),
+                _ => None,
+            }
+        }
+    }
+
+    impl From<Rpc<quic::BidiStream>> for Fetch {
+        fn from(e: Rpc<quic::BidiStream>) -> Self {
+            Self::Rpc(Box::new(e))
+        }
+    }
+}
+
+/// A reference to the content of an attachment.
+#[derive(
+    Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Encode, Decode,
+)]
+#[serde(rename_all = "camelCase")]
+#[cbor(array)]
+pub struct Attachment {
+    /// The SHA-256 of the content.
+    #[n(0)]
+    pub oid: Oid,
+    /// The size of the content in bytes.
+    #[n(1)]
+    pub size: u64,
+    /// The media type of the content, eg. `image/png`.
+    #[n(2)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+}
+
+/// Payload extension attaching a picture to a person or project.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Avatar {
+    pub image: Attachment,
+}
+
+impl HasNamespace for Avatar {
+    fn namespace() -> &'static Url {
+        &AVATAR_NAMESPACE_V1
+    }
+}
+
+impl Extension for Avatar {
+    fn validate(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        match &self.image.media_type {
+            Some(media_type) if !media_type.starts_with("image/") => {
+                Err(format!("avatar of media type `{}` is not an image", media_type).into())
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Attachments exceeding this size are neither stored, served nor
+    /// fetched.
+    pub max_size: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_size: 4 * 1024 * 1024,
+        }
+    }
+}
+
+/// Storage of attachments.
+///
+/// The content is kept in a [`cas::Store`] under `attachments`, relative to
+/// the monorepo.
+#[derive(Clone, Debug)]
+pub struct Store {
+    objects: cas::Store,
+    config: Config,
+}
+
+impl Store {
+    pub fn new(paths: &Paths, config: Config) -> Self {
+        Self {
+            objects: cas::Store::new(paths.git_dir().join("attachments")),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The path of the content of `oid`.
+    pub fn path(&self, oid: &Oid) -> PathBuf {
+        self.objects.path(oid)
+    }
+
+    pub fn contains(&self, oid: &Oid) -> bool {
+        self.objects.contains(oid)
+    }
+
+    /// Add `content` to the store, returning the [`Attachment`] referring to
+    /// it.
+    pub fn put(
+        &self,
+        content: &[u8],
+        media_type: Option<String>,
+    ) -> Result<Attachment, error::Store> {
+        let size = content.len() as u64;
+        self.check_size(size)?;
+        let oid = Oid::digest(content);
+        if !self.contains(&oid) {
+            self.objects.insert(&oid, content)?;
+        }
+        Ok(Attachment {
+            oid,
+            size,
+            media_type,
+        })
+    }
+
+    /// Copy the file at `path` into the store, returning the [`Attachment`]
+    /// referring to it.
+    pub fn import(
+        &self,
+        path: &Path,
+        media_type: Option<String>,
+    ) -> Result<Attachment, error::Store> {
+        let file = fs::File::open(path)?;
+        self.check_size(file.metadata()?.len())?;
+        // Guard against the file growing after we looked at its size
+        let mut content = Vec::new();
+        file.take(self.config.max_size + 1)
+            .read_to_end(&mut content)?;
+        self.put(&content, media_type)
+    }
+
+    /// Read the content of `oid`.
+    pub fn read(&self, oid: &Oid) -> io::Result<Vec<u8>> {
+        self.objects.read(oid)
+    }
+
+    fn check_size(&self, size: u64) -> Result<(), error::Store> {
+        let max = self.config.max_size;
+        if size > max {
+            return Err(error::Store::TooLarge { size, max });
+        }
+        Ok(())
+    }
+}
+
+/// Fetch the content `attachment` refers to over `conn`, unless it is already
+/// in the `store`.
+///
+/// Returns the path of the content in the `store`.
+pub(crate) async fn fetch(
+    spawner: &Spawner,
+    conn: &quic::Connection,
+    store: &Store,
+    attachment: &Attachment,
+) -> Result<PathBuf, error::Fetch> {
+    let Attachment { oid, size, .. } = *attachment;
+    let max = store.config.max_size;
+    if size > max {
+        return Err(error::Fetch::TooLarge { size, max });
+    }
+    if store.contains(&oid) {
+        return Ok(store.path(&oid));
+    }
+
+    let mut content = Vec::with_capacity(size as usize);
+    let resp = proto_io::send::multi_response(conn, Request { oid }, FRAMED_BUFSIZ).await?;
+    futures::pin_mut!(resp);
+    while let Some(resp) = resp.next().await {
+        match resp? {
+            Response::Error(e) => return Err(error::Fetch::ErrorResponse(e)),
+            Response::Chunk(chunk) => {
+                if (content.len() + chunk.len()) as u64 > size {
+                    return Err(error::Fetch::Overflow(size));
+                }
+                content.extend_from_slice(&chunk);
+            },
+        }
+    }
+
+    let received = content.len() as u64;
+    if received != size {
+        return Err(error::Fetch::Truncated {
+            expected: size,
+            received,
+        });
+    }
+    let actual = Oid::digest(&content);
+    if actual != oid {
+        return Err(error::Fetch::Corrupt {
+            expected: oid,
+            actual,
+        });
+    }
+
+    let objects = store.objects.clone();
+    Ok(spawner
+        .blocking(move || objects.insert(&oid, &content))
+        .await?)
+}
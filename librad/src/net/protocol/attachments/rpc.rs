@@ -0,0 +1,50 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use minicbor::{Decode, Encode};
+
+use super::Oid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+#[cbor(array)]
+pub struct Request {
+    /// The content to transfer.
+    #[n(0)]
+    pub oid: Oid,
+}
+
+/// The responder sends the requested content as a sequence of
+/// [`Response::Chunk`]s, and closes the stream after the last one.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum Response {
+    #[n(0)]
+    #[cbor(array)]
+    Error(#[n(0)] Error),
+
+    #[n(1)]
+    #[cbor(array)]
+    Chunk(
+        #[n(0)]
+        #[cbor(with = "minicbor::bytes")]
+        Vec<u8>,
+    ),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+#[cbor(index_only)]
+pub enum Error {
+    /// The responder does not have the content.
+    #[n(0)]
+    NotFound,
+    /// The content exceeds the responder's size limit.
+    #[n(1)]
+    TooLarge,
+    /// The requester exceeded its quota of requests.
+    #[n(2)]
+    RateLimited,
+    /// The responder failed to read the content.
+    #[n(3)]
+    Internal,
+}
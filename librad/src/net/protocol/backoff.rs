@@ -0,0 +1,135 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Backing off from overloaded neighbours.
+//!
+//! When the local storage can't admit another fetch triggered by gossip (see
+//! [`crate::net::peer::storage::admission`]), the announcement is relayed
+//! unmodified, so that other peers may fetch from the original provider, and
+//! the neighbour which sent it is told to back off for some time by a
+//! [`Signal`]. Signals are only sent to peers which advertise
+//! [`Capability::GossipBackoff`].
+//!
+//! A neighbour which received a [`Signal`] does not send requests for
+//! retransmission ([`super::broadcast::Message::Want`]) to the overloaded peer
+//! until the signalled time has elapsed, so that it is not asked to provide
+//! what it can't fetch in the first place. Announcements are still sent, as
+//! the overloaded peer can cheaply relay or reject them.
+//!
+//! [`Capability::GossipBackoff`]: super::Capability::GossipBackoff
+
+use std::{
+    collections::HashMap,
+    convert::TryInto as _,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::RwLock;
+
+use crate::PeerId;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Whether to advertise [`super::Capability::GossipBackoff`], signal
+    /// overload to neighbours which advertise it, too, and honour signals
+    /// received from neighbours.
+    ///
+    /// Peers running versions which don't know about the capability fail to
+    /// decode advertisements containing it, so this should only be enabled
+    /// once the network has been upgraded.
+    pub enabled: bool,
+    /// Upper bound for the time a neighbour is backed off from, regardless of
+    /// what it signalled.
+    pub max_backoff: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+/// The signal sent to a neighbour whose announcement could not be acted upon,
+/// because the local peer is overloaded.
+#[derive(Clone, Copy, Debug, PartialEq, minicbor::Encode, minicbor::Decode)]
+#[cbor(array)]
+pub struct Signal {
+    /// The number of seconds after which the neighbour may try again.
+    #[n(0)]
+    pub retry_after: u32,
+}
+
+impl Signal {
+    pub fn new(retry_after: Duration) -> Self {
+        Self {
+            retry_after: retry_after.as_secs().try_into().unwrap_or(u32::MAX),
+        }
+    }
+
+    pub fn retry_after(&self) -> Duration {
+        Duration::from_secs(self.retry_after.into())
+    }
+}
+
+/// The neighbours which signalled that they are overloaded.
+#[derive(Clone)]
+pub struct Backoffs {
+    config: Config,
+    until: Arc<RwLock<HashMap<PeerId, Instant>>>,
+}
+
+impl Backoffs {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            until: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Record the [`Signal`] sent by `peer`, replacing any it sent before.
+    ///
+    /// Signals are ignored unless backing off is [`Config::enabled`].
+    pub fn record(&self, peer: PeerId, signal: Signal) {
+        if self.config.enabled {
+            let backoff = signal.retry_after().min(self.config.max_backoff);
+            self.until.write().insert(peer, Instant::now() + backoff);
+        }
+    }
+
+    /// Whether `peer` signalled that it is overloaded, and the time it asked
+    /// for has not yet elapsed.
+    pub fn is_backing_off(&self, peer: &PeerId) -> bool {
+        let now = Instant::now();
+        match self.until.read().get(peer) {
+            None => return false,
+            Some(until) if *until > now => return true,
+            Some(_) => {},
+        }
+        self.until.write().retain(|_, until| *until > now);
+        false
+    }
+
+    /// The number of neighbours currently backed off from.
+    pub fn len(&self) -> usize {
+        let now = Instant::now();
+        self.until
+            .read()
+            .values()
+            .filter(|until| **until > now)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
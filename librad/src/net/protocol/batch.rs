@@ -0,0 +1,148 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Batching of outgoing gossip.
+//!
+//! During push storms, a peer may have to relay many announcements to the
+//! same neighbours in quick succession. Instead of framing each of them
+//! separately, messages to peers which advertise [`Capability::GossipBatch`]
+//! are buffered per peer, and sent as a single CBOR array once either
+//! [`Config::max_messages`] are pending, or the flush interval elapsed.
+//!
+//! The flush interval adapts to the traffic: it starts out at
+//! [`Config::min_interval`], so a lone announcement is only delayed
+//! marginally, and doubles (up to [`Config::max_interval`]) whenever a flush
+//! finds more than one message in the buffer. It halves again when a flush
+//! finds a single message only.
+//!
+//! [`Capability::GossipBatch`]: super::Capability::GossipBatch
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use parking_lot::Mutex;
+
+use super::broadcast;
+use crate::PeerId;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Whether to advertise [`super::Capability::GossipBatch`], and batch
+    /// gossip to peers which advertise it, too.
+    ///
+    /// Peers running versions which don't know about the capability fail to
+    /// decode advertisements containing it, so this should only be enabled
+    /// once the network has been upgraded.
+    pub enabled: bool,
+    /// Number of pending messages at which a batch is sent immediately.
+    pub max_messages: usize,
+    /// The shortest flush interval.
+    pub min_interval: Duration,
+    /// The longest flush interval.
+    pub max_interval: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_messages: 64,
+            min_interval: Duration::from_millis(5),
+            max_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// The outcome of [`Batches::push`].
+#[derive(Debug)]
+pub enum Push<M> {
+    /// The buffer is full, the batch must be sent now.
+    Flush(Vec<M>),
+    /// The message was added to an empty buffer. The caller must arrange for
+    /// [`Batches::take`] to be called after the given interval.
+    Schedule(Duration),
+    /// The message was added to a buffer which is already scheduled to be
+    /// flushed.
+    Buffered,
+}
+
+struct Buffer<M> {
+    pending: Vec<M>,
+    interval: Duration,
+}
+
+/// Per-peer buffers of outgoing gossip.
+#[derive(Clone)]
+pub struct Batches<M = broadcast::Message<std::net::SocketAddr, super::gossip::Payload>> {
+    config: Config,
+    buffers: Arc<Mutex<HashMap<PeerId, Buffer<M>>>>,
+}
+
+impl<M> Batches<M> {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            buffers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Buffer `msg` for `to`.
+    pub fn push(&self, to: PeerId, msg: M) -> Push<M> {
+        let mut buffers = self.buffers.lock();
+        let buf = buffers.entry(to).or_insert_with(|| Buffer {
+            pending: Vec::new(),
+            interval: self.config.min_interval,
+        });
+        buf.pending.push(msg);
+        if buf.pending.len() >= self.config.max_messages.max(1) {
+            buf.interval = self.config.max_interval;
+            Push::Flush(buf.pending.drain(..).collect())
+        } else if buf.pending.len() == 1 {
+            Push::Schedule(buf.interval)
+        } else {
+            Push::Buffered
+        }
+    }
+
+    /// Take the pending messages for `to`, adapting the flush interval.
+    ///
+    /// Returns `None` if nothing is pending, eg. because the buffer was
+    /// flushed due to reaching [`Config::max_messages`] in the meantime.
+    pub fn take(&self, to: &PeerId) -> Option<Vec<M>> {
+        let mut buffers = self.buffers.lock();
+        let buf = buffers.get_mut(to)?;
+        buf.interval = if buf.pending.len() > 1 {
+            (buf.interval * 2).min(self.config.max_interval)
+        } else {
+            (buf.interval / 2).max(self.config.min_interval)
+        };
+        if buf.pending.is_empty() {
+            None
+        } else {
+            Some(buf.pending.drain(..).collect())
+        }
+    }
+
+    /// Discard the buffer of `peer`, eg. because the connection was lost.
+    pub fn remove(&self, peer: &PeerId) -> usize {
+        self.buffers
+            .lock()
+            .remove(peer)
+            .map(|buf| buf.pending.len())
+            .unwrap_or(0)
+    }
+
+    /// The current flush interval for `peer`.
+    pub fn interval(&self, peer: &PeerId) -> Duration {
+        self.buffers
+            .lock()
+            .get(peer)
+            .map(|buf| buf.interval)
+            .unwrap_or(self.config.min_interval)
+    }
+}
@@ -15,7 +15,7 @@ use parking_lot::RwLock;
 use thiserror::Error;
 use tracing::{debug, warn};
 
-use super::{event::upstream as event, tick, PeerInfo};
+use super::{backoff, event::upstream as event, io, tick, PeerInfo};
 use crate::{PeerId, Signature};
 
 mod metrics;
@@ -347,6 +347,23 @@ where
                     Some(remote_id),
                 ),
 
+                Overloaded { retry_after } => {
+                    let mut tocks = broadcast(
+                        Have {
+                            origin,
+                            val,
+                            ext: Some(ext.unwrap_or_default().next_hop()),
+                        },
+                        Some(remote_id),
+                    );
+                    tocks.push(SendConnected {
+                        to: remote_id,
+                        message: io::Rpc::Backoff(backoff::Signal::new(retry_after)),
+                    });
+
+                    tocks
+                },
+
                 Stale => vec![],
             };
 
@@ -3,6 +3,8 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+use std::time::Duration;
+
 use crate::PeerId;
 
 /// Result of applying a broadcast update to local storage.
@@ -37,6 +39,14 @@ pub enum PutResult<Update> {
     /// [`super::Message`] stays unmodified. Additionally, the local peer
     /// may ask for a retransmission of the `Update` at a later point.
     Error,
+
+    /// The local peer is too busy to fetch the `Update` at this time.
+    ///
+    /// The `Update` will be relayed, while the `origin` of the
+    /// [`super::Message`] stays unmodified, so other peers may fetch it from
+    /// the provider. The neighbour which sent the [`super::Message`] is asked
+    /// to back off for `retry_after`, see [`crate::net::protocol::backoff`].
+    Overloaded { retry_after: Duration },
 }
 
 #[async_trait]
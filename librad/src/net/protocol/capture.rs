@@ -0,0 +1,213 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Capture and replay of inbound protocol messages.
+//!
+//! Some misbehaviour only shows up with the exact sequence of messages a node
+//! received in production. When [`Config::path`] is set, the protocol appends
+//! a [`Record`] for every inbound stream upgrade, gossip and membership
+//! message to the file at that path, along with the time it was received.
+//!
+//! The capture can later be loaded with [`read`], and fed into a fresh
+//! protocol instance using a [`Replayer`], eg. to reproduce an incident in a
+//! regression test. Upgrades are only recorded for context, replaying them
+//! has no effect.
+//!
+//! The capture file is a sequence of CBOR-encoded [`Record`]s. Captures may
+//! contain sensitive information (such as the addresses of peers), and grow
+//! without bounds: capturing is meant to be enabled temporarily.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write as _},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+use super::{broadcast, gossip, io::recv, membership, ProtocolStorage, RequestPullGuard, State};
+use crate::{net::upgrade::UpgradeRequest, PeerId};
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Read {
+        #[error("record {index}: malformed")]
+        Malformed {
+            index: usize,
+            #[source]
+            source: minicbor::decode::Error,
+        },
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    /// If set, inbound messages are appended to the file at this path.
+    pub path: Option<PathBuf>,
+}
+
+/// An inbound protocol message.
+#[derive(Clone, Debug, PartialEq, minicbor::Encode, minicbor::Decode)]
+pub enum Event {
+    #[n(0)]
+    #[cbor(array)]
+    Upgrade(#[n(0)] UpgradeRequest),
+
+    #[n(1)]
+    #[cbor(array)]
+    Gossip(#[n(0)] broadcast::Message<SocketAddr, gossip::Payload>),
+
+    #[n(2)]
+    #[cbor(array)]
+    Membership(#[n(0)] membership::Message<SocketAddr>),
+}
+
+/// A captured [`Event`], along with where and when it was received.
+#[derive(Clone, Debug, PartialEq, minicbor::Encode, minicbor::Decode)]
+#[cbor(array)]
+pub struct Record {
+    /// Milliseconds since the capture was started.
+    #[n(0)]
+    pub offset_ms: u64,
+    #[n(1)]
+    pub remote_id: PeerId,
+    #[n(2)]
+    pub remote_addr: SocketAddr,
+    #[n(3)]
+    pub event: Event,
+}
+
+/// Appends [`Record`]s to a capture file.
+#[derive(Clone)]
+pub struct Recorder {
+    out: Arc<Mutex<BufWriter<File>>>,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Start a new capture at `path`, appending to it if it exists.
+    ///
+    /// Note that the offsets of the records are relative to the start of
+    /// _this_ capture, so appending to an existing capture yields
+    /// non-monotonic offsets.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            out: Arc::new(Mutex::new(BufWriter::new(file))),
+            started: Instant::now(),
+        })
+    }
+
+    /// Record `event`, received from `remote_id` at `remote_addr`.
+    ///
+    /// Errors are logged, but otherwise ignored.
+    pub fn record(&self, remote_id: PeerId, remote_addr: SocketAddr, event: Event) {
+        let record = Record {
+            offset_ms: self.started.elapsed().as_millis() as u64,
+            remote_id,
+            remote_addr,
+            event,
+        };
+        let res = minicbor::to_vec(&record)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .and_then(|bytes| {
+                let mut out = self.out.lock();
+                out.write_all(&bytes)?;
+                out.flush()
+            });
+        if let Err(e) = res {
+            tracing::warn!(err = %e, "failed to capture protocol message");
+        }
+    }
+}
+
+/// Read all [`Record`]s from the capture file at `path`.
+pub fn read(path: &Path) -> Result<Vec<Record>, error::Read> {
+    decode(&std::fs::read(path)?)
+}
+
+/// Decode a sequence of [`Record`]s.
+pub fn decode(bytes: &[u8]) -> Result<Vec<Record>, error::Read> {
+    let mut decoder = minicbor::Decoder::new(bytes);
+    let mut records = Vec::new();
+    while decoder.position() < bytes.len() {
+        let record = decoder.decode().map_err(|source| error::Read::Malformed {
+            index: records.len(),
+            source,
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// How fast to replay a capture.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pace {
+    /// Apply records as fast as possible.
+    Immediate,
+    /// Wait between records as long as passed between them when they were
+    /// captured.
+    Recorded,
+}
+
+/// Feeds captured [`Record`]s into a protocol instance.
+///
+/// Obtained via [`super::Bound::replayer`].
+pub struct Replayer<S, G> {
+    pub(super) state: State<S, G>,
+}
+
+impl<S, G> Replayer<S, G>
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+    G: RequestPullGuard,
+{
+    /// Apply `records` in order, as if they were received from the network.
+    ///
+    /// Returns the number of gossip and membership messages applied.
+    pub async fn replay<I>(&self, records: I, pace: Pace) -> usize
+    where
+        I: IntoIterator<Item = Record>,
+    {
+        let mut applied = 0;
+        let mut last = None;
+        for record in records {
+            if pace == Pace::Recorded {
+                if let Some(last) = last {
+                    let wait = record.offset_ms.saturating_sub(last);
+                    link_async::sleep(Duration::from_millis(wait)).await;
+                }
+                last = Some(record.offset_ms);
+            }
+
+            let remote_id = record.remote_id;
+            match record.event {
+                Event::Upgrade(kind) => {
+                    tracing::debug!(%remote_id, ?kind, "replay: upgrade");
+                },
+                Event::Gossip(msg) => {
+                    recv::apply_gossip(&self.state, remote_id, msg).await;
+                    applied += 1;
+                },
+                Event::Membership(msg) => {
+                    recv::apply_membership(&self.state, remote_id, record.remote_addr, msg).await;
+                    applied += 1;
+                },
+            }
+        }
+        applied
+    }
+}
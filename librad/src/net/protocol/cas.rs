@@ -0,0 +1,257 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Content-addressed storage of files kept outside of git.
+//!
+//! Both [large files](super::lfs) and [attachments](super::attachments) are
+//! referred to by the SHA-256 of their content, and stored in a [`Store`]
+//! under `<root>/objects/<aa>/<bb>/<oid>` — the layout used by `git lfs`, so
+//! existing tooling can be pointed at it. Content is only moved into place
+//! once its hash was verified, so everything under `objects` is complete.
+//! Content still being received is kept under `<root>/incomplete/<oid>`.
+
+use std::{
+    fmt,
+    fs,
+    io::{self, Read, Seek as _, SeekFrom, Write as _},
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+};
+
+use sha2::{Digest as _, Sha256};
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    use super::Oid;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Store {
+        #[error("content of {expected} does not match, got {actual} ({size} bytes)")]
+        Corrupt {
+            expected: Oid,
+            actual: Oid,
+            size: u64,
+        },
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+/// The SHA-256 of some content.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Oid([u8; 32]);
+
+impl Oid {
+    pub fn digest(data: &[u8]) -> Self {
+        Self(Sha256::digest(data).into())
+    }
+}
+
+impl fmt::Display for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sha256:{}", self)
+    }
+}
+
+impl FromStr for Oid {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err("expected 64 hex digits");
+        }
+        let mut oid = [0; 32];
+        for (i, b) in oid.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|_| "invalid hex digit")?;
+        }
+        Ok(Self(oid))
+    }
+}
+
+impl serde::Serialize for Oid {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Oid {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s: String = serde::Deserialize::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl minicbor::Encode for Oid {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        e.bytes(&self.0)?;
+        Ok(())
+    }
+}
+
+impl<'de> minicbor::Decode<'de> for Oid {
+    fn decode(d: &mut minicbor::Decoder<'de>) -> Result<Self, minicbor::decode::Error> {
+        let mut oid = [0; 32];
+        let bytes = d.bytes()?;
+        if bytes.len() != oid.len() {
+            return Err(minicbor::decode::Error::Message("expected 32 bytes"));
+        }
+        oid.copy_from_slice(bytes);
+        Ok(Self(oid))
+    }
+}
+
+/// Size of the buffer content is hashed and copied with.
+const BUFSIZ: usize = 64 * 1024;
+
+#[derive(Clone, Debug)]
+pub struct Store {
+    root: Arc<PathBuf>,
+}
+
+impl Store {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root: Arc::new(root),
+        }
+    }
+
+    /// The path of the complete content of `oid`.
+    pub fn path(&self, oid: &Oid) -> PathBuf {
+        let hex = oid.to_string();
+        self.root
+            .join("objects")
+            .join(&hex[0..2])
+            .join(&hex[2..4])
+            .join(hex)
+    }
+
+    fn partial_path(&self, oid: &Oid) -> PathBuf {
+        self.root.join("incomplete").join(oid.to_string())
+    }
+
+    pub fn contains(&self, oid: &Oid) -> bool {
+        self.path(oid).is_file()
+    }
+
+    /// Read the content of `oid`.
+    pub fn read(&self, oid: &Oid) -> io::Result<Vec<u8>> {
+        fs::read(self.path(oid))
+    }
+
+    /// Read up to `len` bytes of the content of `oid`, starting at `offset`.
+    /// Returns an empty chunk at the end of the content.
+    pub fn read_chunk(&self, oid: &Oid, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut file = fs::File::open(self.path(oid))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buf = Vec::with_capacity(len);
+        file.take(len as u64).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Copy the content of `reader` into the store, returning its [`Oid`] and
+    /// size.
+    pub fn import<R: Read>(&self, mut reader: R) -> io::Result<(Oid, u64)> {
+        let mut tmp = self.tmp()?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; BUFSIZ];
+        let mut size = 0;
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            tmp.write_all(&buf[..n])?;
+            size += n as u64;
+        }
+        let oid = Oid(hasher.finalize().into());
+        self.persist(tmp, &oid)?;
+        Ok((oid, size))
+    }
+
+    /// Write `content`, which the caller verified to hash to `oid`, to the
+    /// store.
+    pub fn insert(&self, oid: &Oid, content: &[u8]) -> io::Result<PathBuf> {
+        let mut tmp = self.tmp()?;
+        tmp.write_all(content)?;
+        self.persist(tmp, oid)
+    }
+
+    /// The number of bytes received so far of `oid`.
+    pub fn partial_len(&self, oid: &Oid) -> u64 {
+        fs::metadata(self.partial_path(oid))
+            .map(|meta| meta.len())
+            .unwrap_or(0)
+    }
+
+    /// Append `chunk` to the partial content of `oid`.
+    pub fn append(&self, oid: &Oid, chunk: &[u8]) -> io::Result<()> {
+        let path = self.partial_path(oid);
+        fs::create_dir_all(path.parent().expect("partial paths have a parent"))?;
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(chunk)
+    }
+
+    /// Verify the partial content of `oid`, and move it into place if it
+    /// matches. Corrupt content is discarded.
+    pub fn finish(&self, oid: &Oid) -> Result<PathBuf, error::Store> {
+        let partial = self.partial_path(oid);
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut fs::File::open(&partial)?, &mut hasher)?;
+        let actual = Oid(hasher.finalize().into());
+        if &actual != oid {
+            fs::remove_file(&partial)?;
+            return Err(error::Store::Corrupt {
+                expected: *oid,
+                actual,
+                size,
+            });
+        }
+        let dst = self.path(oid);
+        fs::create_dir_all(dst.parent().expect("object paths have a parent"))?;
+        fs::rename(partial, &dst)?;
+        Ok(dst)
+    }
+
+    fn tmp(&self) -> io::Result<tempfile::NamedTempFile> {
+        let tmp_dir = self.root.join("tmp");
+        fs::create_dir_all(&tmp_dir)?;
+        tempfile::NamedTempFile::new_in(tmp_dir)
+    }
+
+    fn persist(&self, tmp: tempfile::NamedTempFile, oid: &Oid) -> io::Result<PathBuf> {
+        let dst = self.path(oid);
+        fs::create_dir_all(dst.parent().expect("object paths have a parent"))?;
+        tmp.persist(&dst).map_err(|e| e.error)?;
+        Ok(dst)
+    }
+}
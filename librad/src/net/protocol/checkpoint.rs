@@ -0,0 +1,259 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Checkpoints of the protocol runtime state.
+//!
+//! A freshly started peer knows nothing but its bootstrap nodes: it has to
+//! rebuild its membership views, relearn whom to store announcements for, and
+//! forgets which peers misbehaved. For a seed, this means serving its
+//! network poorly for a while after every restart. To shorten this warm-up
+//! period, a [`Checkpoint`] of the runtime state is written to [`FILE_NAME`]
+//! (relative to the git directory) every [`Config::interval`], and restored
+//! on startup, unless it is older than [`Config::max_age`]:
+//!
+//! * the peers of the passive view, and those of the active view which
+//!   advertised themselves, are added to the passive view,
+//! * the peers of the active view are dialled, as if they were discovered,
+//! * the [`super::mailbox`] interest table and pending announcements are
+//!   restored, accounting for the time spent offline,
+//! * peers banned for breaching the membership rate limit remain banned for the
+//!   rest of their ban.
+//!
+//! The checkpoint is a single CBOR-encoded [`Checkpoint`], replaced
+//! atomically.
+
+use std::{
+    fs,
+    io,
+    net::SocketAddr,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::stream::{self, StreamExt as _};
+use minicbor::{Decode, Encode};
+
+use super::{
+    gossip,
+    io as protocol_io,
+    PartialPeerInfo,
+    PeerInfo,
+    ProtocolStorage,
+    RequestPullGuard,
+    State,
+};
+use crate::{identities::git::Urn, PeerId};
+
+/// The name of the checkpoint file, relative to the git directory.
+pub const FILE_NAME: &str = "protocol.checkpoint";
+
+/// Maximum number of formerly active peers dialled concurrently on restore.
+const MAX_CONCURRENT_DIALS: usize = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// If `false`, no checkpoints are taken, and none are restored.
+    pub enabled: bool,
+    /// Interval at which checkpoints are taken.
+    pub interval: Duration,
+    /// Checkpoints older than this are not restored.
+    pub max_age: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: Duration::from_secs(60),
+            max_age: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+/// A peer banned for breaching a rate limit.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[cbor(array)]
+pub struct Offender {
+    #[n(0)]
+    pub peer: PeerId,
+    /// Seconds the ban had left when the checkpoint was taken.
+    #[n(1)]
+    pub remaining: u64,
+}
+
+/// Peers interested in updates to a URN, see [`super::mailbox`].
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[cbor(array)]
+pub struct Interest {
+    #[n(0)]
+    pub urn: Urn,
+    /// Least recently recorded first.
+    #[n(1)]
+    pub peers: Vec<PeerId>,
+}
+
+/// An announcement pending for a peer, see [`super::mailbox`].
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[cbor(array)]
+pub struct Letter {
+    #[n(0)]
+    pub peer: PeerId,
+    /// Seconds since the announcement was deposited, when the checkpoint was
+    /// taken.
+    #[n(1)]
+    pub age: u64,
+    #[n(2)]
+    pub payload: gossip::Payload,
+}
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[cbor(array)]
+pub struct Checkpoint {
+    /// Seconds since the Unix epoch at which the checkpoint was taken.
+    #[n(0)]
+    pub taken: u64,
+    #[n(1)]
+    pub active: Vec<PartialPeerInfo<SocketAddr>>,
+    #[n(2)]
+    pub passive: Vec<PeerInfo<SocketAddr>>,
+    #[n(3)]
+    pub offenders: Vec<Offender>,
+    #[n(4)]
+    pub interest: Vec<Interest>,
+    #[n(5)]
+    pub letters: Vec<Letter>,
+}
+
+impl Checkpoint {
+    /// Read the checkpoint at `path`, if any.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        match fs::read(path) {
+            Ok(bytes) => minicbor::decode(&bytes)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Atomically replace the checkpoint at `path`.
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        let bytes =
+            minicbor::to_vec(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let tmp = path.with_extension("checkpoint.tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Time elapsed since the checkpoint was taken.
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(now().saturating_sub(self.taken))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Capture the runtime state of `state`.
+pub(super) fn take<S, G>(state: &State<S, G>) -> Checkpoint {
+    let (interest, letters) = state.mailbox.snapshot();
+    Checkpoint {
+        taken: now(),
+        active: state.membership.active_info(),
+        passive: state.membership.passive_info(),
+        offenders: state
+            .limits
+            .offenders
+            .list()
+            .into_iter()
+            .map(|(peer, remaining)| Offender {
+                peer,
+                remaining: remaining.as_secs(),
+            })
+            .collect(),
+        interest: interest
+            .into_iter()
+            .map(|(urn, peers)| Interest { urn, peers })
+            .collect(),
+        letters: letters
+            .into_iter()
+            .map(|(peer, age, payload)| Letter {
+                peer,
+                age: age.as_secs(),
+                payload,
+            })
+            .collect(),
+    }
+}
+
+/// Restore `checkpoint` into `state`, and dial the formerly active peers.
+pub(super) async fn restore<S, G>(state: State<S, G>, checkpoint: Checkpoint)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+    G: RequestPullGuard,
+{
+    let offline = checkpoint.age();
+    tracing::info!(
+        active = checkpoint.active.len(),
+        passive = checkpoint.passive.len(),
+        offline = ?offline,
+        "restoring protocol checkpoint"
+    );
+
+    for offender in checkpoint.offenders {
+        if let Some(remaining) = Duration::from_secs(offender.remaining).checked_sub(offline) {
+            state.limits.offenders.ban(offender.peer, remaining)
+        }
+    }
+    let offenders = &state.limits.offenders;
+
+    state.mailbox.restore(
+        checkpoint
+            .interest
+            .into_iter()
+            .map(|Interest { urn, peers }| (urn, peers)),
+        checkpoint
+            .letters
+            .into_iter()
+            .map(|Letter { peer, age, payload }| {
+                (peer, Duration::from_secs(age) + offline, payload)
+            }),
+    );
+
+    state.membership.restore_passive(
+        checkpoint
+            .passive
+            .into_iter()
+            .chain(
+                checkpoint
+                    .active
+                    .iter()
+                    .cloned()
+                    .filter_map(|info| info.sequence()),
+            )
+            .filter(|info| !offenders.is_banned(&info.peer_id)),
+    );
+
+    let dials = checkpoint
+        .active
+        .into_iter()
+        .filter(|info| !offenders.is_banned(&info.peer_id))
+        .map(<(PeerId, Vec<SocketAddr>)>::from)
+        .collect::<Vec<_>>();
+    stream::iter(dials)
+        .for_each_concurrent(MAX_CONCURRENT_DIALS, |(peer, addrs)| {
+            let state = state.clone();
+            async move {
+                if !protocol_io::discovered(state, peer, addrs).await {
+                    tracing::debug!(%peer, "could not reconnect to formerly active peer");
+                }
+            }
+        })
+        .await
+}
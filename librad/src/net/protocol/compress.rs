@@ -0,0 +1,226 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Compression of gossip and membership frames.
+//!
+//! Gossip and membership messages are small, and consist mostly of the same
+//! structure (peer ids, URNs, addresses) over and over again. General purpose
+//! compression gains little on such short inputs, but zstd with a dictionary
+//! of typical messages does. If [`Config::enabled`], the local peer advertises
+//! [`Capability::GossipCompression`], and frames of at least
+//! [`Config::min_size`] bytes sent to peers which advertise it, too, are
+//! compressed, if that actually makes them smaller.
+//!
+//! A compressed frame is sent as a CBOR byte string in place of the frame
+//! itself. Peers only ever receive compressed frames if they advertised the
+//! capability, so this is backwards-compatible.
+//!
+//! The [`dictionary`] is a raw-content dictionary built from CBOR-encoded
+//! templates of the most common messages, and so must be identical on both
+//! ends: changing it requires a new capability. Denser dictionaries can be
+//! [`train`]ed from captured traffic (see [`super::capture`]).
+//!
+//! [`Capability::GossipCompression`]: super::Capability::GossipCompression
+
+use std::{
+    collections::BTreeSet,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use data::BoundedVec;
+use once_cell::sync::Lazy;
+
+use super::{
+    broadcast,
+    gossip,
+    info::{Capability, PeerAdvertisement, PeerInfo},
+    membership,
+};
+use crate::{identities::git::Urn, PeerId, SecretKey};
+
+/// Upper bound of the size of a decompressed frame.
+pub const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Whether to advertise [`Capability::GossipCompression`], and compress
+    /// frames to peers which advertise it, too.
+    ///
+    /// Like [`super::batch::Config::enabled`], this should only be enabled
+    /// once the network has been upgraded.
+    pub enabled: bool,
+    /// The zstd compression level.
+    pub level: i32,
+    /// Frames smaller than this are sent uncompressed.
+    pub min_size: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            level: 3,
+            min_size: 64,
+        }
+    }
+}
+
+/// Compression counters, in bytes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    /// Size of the compressed frames sent, before compression.
+    pub sent_plain: u64,
+    /// Size of the compressed frames sent.
+    pub sent_compressed: u64,
+    /// Size of the compressed frames received.
+    pub recv_compressed: u64,
+    /// Size of the compressed frames received, after decompression.
+    pub recv_plain: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    sent_plain: AtomicU64,
+    sent_compressed: AtomicU64,
+    recv_compressed: AtomicU64,
+    recv_plain: AtomicU64,
+}
+
+#[derive(Clone)]
+pub struct Compression {
+    config: Config,
+    counters: Arc<Counters>,
+}
+
+impl Compression {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            counters: Arc::new(Counters::default()),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Compress the encoded frame `plain`.
+    ///
+    /// Returns `None` if the frame should be sent as is.
+    pub fn compress(&self, plain: &[u8]) -> Option<Vec<u8>> {
+        if !self.config.enabled || plain.len() < self.config.min_size {
+            return None;
+        }
+        let compressed = zstd::bulk::Compressor::with_dictionary(self.config.level, dictionary())
+            .and_then(|mut c| c.compress(plain));
+        match compressed {
+            Ok(compressed) if compressed.len() < plain.len() => {
+                let c = &self.counters;
+                c.sent_plain
+                    .fetch_add(plain.len() as u64, Ordering::Relaxed);
+                c.sent_compressed
+                    .fetch_add(compressed.len() as u64, Ordering::Relaxed);
+                Some(compressed)
+            },
+            Ok(_) => None,
+            Err(e) => {
+                tracing::warn!(err = %e, "failed to compress frame");
+                None
+            },
+        }
+    }
+
+    /// Decompress a frame received from a peer.
+    pub fn decompress(&self, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        let plain = zstd::bulk::Decompressor::with_dictionary(dictionary())?
+            .decompress(compressed, MAX_FRAME_SIZE)?;
+        let c = &self.counters;
+        c.recv_compressed
+            .fetch_add(compressed.len() as u64, Ordering::Relaxed);
+        c.recv_plain
+            .fetch_add(plain.len() as u64, Ordering::Relaxed);
+        Ok(plain)
+    }
+
+    pub fn stats(&self) -> Stats {
+        let c = &self.counters;
+        Stats {
+            sent_plain: c.sent_plain.load(Ordering::Relaxed),
+            sent_compressed: c.sent_compressed.load(Ordering::Relaxed),
+            recv_compressed: c.recv_compressed.load(Ordering::Relaxed),
+            recv_plain: c.recv_plain.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The dictionary shared by all peers advertising
+/// [`Capability::GossipCompression`].
+pub fn dictionary() -> &'static [u8] {
+    static DICT: Lazy<Vec<u8>> = Lazy::new(templates);
+    &DICT
+}
+
+fn templates() -> Vec<u8> {
+    let peer_id = PeerId::from(SecretKey::from_seed([7; 32]));
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 8776);
+    let advertisement = PeerAdvertisement {
+        capabilities: vec![Capability::GossipBatch, Capability::GossipCompression]
+            .into_iter()
+            .collect::<BTreeSet<_>>(),
+        ..PeerAdvertisement::new(addr)
+    };
+    let info = PeerInfo {
+        peer_id,
+        advertised_info: advertisement.clone(),
+        seen_addrs: BoundedVec::singleton(addr),
+    };
+    let payload = gossip::Payload {
+        urn: Urn::new(git2::Oid::zero().into()).with_path(reflike!("refs/heads/master")),
+        rev: Some(gossip::Rev::Git(git2::Oid::zero())),
+        origin: Some(peer_id),
+    };
+
+    let mut dict = Vec::new();
+    let gossip = [
+        broadcast::Message::<_, _>::Have {
+            origin: info.clone(),
+            val: payload.clone(),
+            ext: None,
+        },
+        broadcast::Message::Want {
+            origin: info.clone(),
+            val: payload,
+            ext: None,
+        },
+    ];
+    let membership = [
+        membership::Message::Join {
+            info: advertisement,
+        },
+        membership::Message::Shuffle {
+            origin: info.clone(),
+            peers: vec![info],
+            ttl: 3,
+        },
+    ];
+    for msg in &gossip {
+        minicbor::encode(msg, &mut dict).expect("encoding to a Vec is infallible");
+    }
+    for msg in &membership {
+        minicbor::encode(msg, &mut dict).expect("encoding to a Vec is infallible");
+    }
+    dict
+}
+
+/// Train a dictionary of at most `max_size` bytes from sample frames, eg.
+/// obtained from a [`super::capture`].
+pub fn train(samples: &[Vec<u8>], max_size: usize) -> io::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, max_size)
+}
@@ -3,9 +3,17 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{iter, net::SocketAddr};
+use std::{
+    collections::BTreeMap,
+    iter,
+    net::SocketAddr,
+    time::{Duration, SystemTime},
+};
 
-use futures::stream::{self, StreamExt as _};
+use futures::{
+    future,
+    stream::{self, StreamExt as _},
+};
 
 use super::{
     broadcast,
@@ -13,15 +21,22 @@ use super::{
     event,
     gossip,
     interrogation,
+    inventory,
     io,
+    ping,
+    quic,
     request_pull,
+    skew,
     tick,
     PeerInfo,
     ProtocolStorage,
     RequestPullGuard,
     State,
 };
-use crate::PeerId;
+use crate::{
+    net::connection::{CloseReason, RemoteAddr as _, RemotePeer as _},
+    PeerId,
+};
 
 pub(super) async fn gossip<S, G>(
     state: &State<S, G>,
@@ -30,17 +45,36 @@ pub(super) async fn gossip<S, G>(
 ) where
     S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
     G: RequestPullGuard,
+{
+    broadcast_to(state, evt, exclude).await;
+}
+
+/// Like [`gossip`], but returns whether the message was sent to at least one
+/// peer.
+pub(super) async fn broadcast_to<S, G>(
+    state: &State<S, G>,
+    evt: event::downstream::Gossip,
+    exclude: Option<PeerId>,
+) -> bool
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
 {
     use event::downstream::Gossip;
 
     let origin = PeerInfo {
         peer_id: state.local_id,
-        advertised_info: io::peer_advertisement(&state.endpoint)(),
+        advertised_info: state.peer_advertisement()(),
         seen_addrs: iter::empty().into(),
     };
     // TODO: answer `Want`s from a provider cache
     let rpc = match evt {
-        Gossip::Announce(payload) => broadcast::Message::have(origin, payload),
+        Gossip::Announce(payload) => {
+            state.mailbox.deposit(&payload, |peer| {
+                state.endpoint.get_connection(*peer).is_some()
+            });
+            broadcast::Message::have(origin, payload)
+        },
         Gossip::Query(payload) => broadcast::Message::want(origin, payload),
     };
     stream::iter(
@@ -53,10 +87,39 @@ pub(super) async fn gossip<S, G>(
                 message: rpc.clone().into(),
             }),
     )
-    .for_each(|tock| tick::tock(state.clone(), tock))
+    .then(|tock| tick::deliver(state.clone(), tock))
+    .fold(false, |sent, ok| future::ready(sent || ok))
     .await
 }
 
+/// Announce a local update, queueing it in the [`super::outbox`] until it was
+/// sent to at least one peer.
+pub(super) async fn announce<S, G>(state: &State<S, G>, payload: gossip::Payload)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    let queued = {
+        let outbox = state.outbox.clone();
+        let payload = payload.clone();
+        state
+            .spawner
+            .blocking(move || outbox.enqueue(&payload))
+            .await
+    };
+    let seq = match queued {
+        Some(seq) => seq,
+        None => {
+            tracing::debug!(urn = %payload.urn, "tip already propagated, not announcing");
+            return;
+        },
+    };
+    if broadcast_to(state, event::downstream::Gossip::Announce(payload), None).await {
+        let outbox = state.outbox.clone();
+        state.spawner.blocking(move || outbox.delivered(seq)).await
+    }
+}
+
 pub(super) fn info<S, G>(state: &State<S, G>, evt: event::downstream::Info)
 where
     S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
@@ -94,11 +157,86 @@ where
                     caches: CacheStats {
                         urns: state.caches.urns.stats(),
                     },
+                    mailbox: state.mailbox.stats(),
+                    compression: state.compression.stats(),
                 })
                 .ok();
             }
         },
+
+        Info::Latency(reply) => {
+            let chan = reply.lock().take();
+            if let Some(tx) = chan {
+                tx.send(state.latency.snapshot()).ok();
+            }
+        },
+
+        Info::ConnectionStats(reply) => {
+            let chan = reply.lock().take();
+            if let Some(tx) = chan {
+                tx.send(state.endpoint.connection_stats()).ok();
+            }
+        },
+
+        Info::Pinned(reply) => {
+            let chan = reply.lock().take();
+            if let Some(tx) = chan {
+                tx.send(state.pinned.snapshot()).ok();
+            }
+        },
+
+        Info::Streams(reply) => {
+            let chan = reply.lock().take();
+            if let Some(tx) = chan {
+                tx.send(state.streams.snapshot()).ok();
+            }
+        },
+
+        Info::Liveness(reply) => {
+            let chan = reply.lock().take();
+            if let Some(tx) = chan {
+                tx.send(state.liveness.snapshot()).ok();
+            }
+        },
+
+        Info::ClockSkew(reply) => {
+            let chan = reply.lock().take();
+            if let Some(tx) = chan {
+                tx.send(state.skew.snapshot()).ok();
+            }
+        },
+
+        Info::Connections(reply) => {
+            let chan = reply.lock().take();
+            if let Some(tx) = chan {
+                tx.send(connections(state)).ok();
+            }
+        },
+    }
+}
+
+fn connections<S, G>(state: &State<S, G>) -> Vec<event::downstream::ConnectionInfo> {
+    use event::downstream::ConnectionInfo;
+
+    let mut streams = state.streams.snapshot();
+    let mut by_peer = BTreeMap::<PeerId, ConnectionInfo>::new();
+    for (conn, established) in state.endpoint.connections() {
+        let peer = conn.remote_peer_id();
+        let stats = conn.stats();
+        let info = by_peer.entry(peer).or_insert_with(|| ConnectionInfo {
+            peer,
+            addrs: vec![],
+            age: Duration::ZERO,
+            streams: streams.remove(&peer).unwrap_or_default(),
+            sent_bytes: 0,
+            recv_bytes: 0,
+        });
+        info.addrs.push(conn.remote_addr());
+        info.age = info.age.max(established.elapsed());
+        info.sent_bytes += stats.sent_bytes;
+        info.recv_bytes += stats.recv_bytes;
     }
+    by_peer.into_values().collect()
 }
 
 pub(super) async fn interrogation(
@@ -120,6 +258,104 @@ pub(super) async fn interrogation(
     }
 }
 
+pub(super) async fn inventory(
+    event::downstream::Inventory {
+        conn,
+        peer,
+        request,
+        reply,
+    }: event::downstream::Inventory,
+) {
+    use inventory::Response;
+
+    let chan = reply.lock().take();
+    if let Some(tx) = chan {
+        let resp = match io::send::single_response(&conn, request, inventory::FRAMED_BUFSIZ).await {
+            Err(e) => Err(e.into()),
+            Ok(None) => Err(error::Inventory::NoResponse(peer)),
+            Ok(Some(Response::Page(page))) => Ok(page),
+            Ok(Some(Response::Error(e))) => Err(error::Inventory::ErrorResponse(e)),
+        };
+        tx.send(resp).ok();
+    }
+}
+
+pub(super) async fn ping<S, G>(
+    state: &State<S, G>,
+    event::downstream::Ping { conn, peer, reply }: event::downstream::Ping,
+) {
+    let chan = reply.lock().take();
+    if let Some(tx) = chan {
+        tx.send(ping_connection(state, &conn, peer).await).ok();
+    }
+}
+
+/// Ping `peer` over `conn`, and record the outcome in [`State::liveness`].
+pub(super) async fn ping_connection<S, G>(
+    state: &State<S, G>,
+    conn: &quic::Connection,
+    peer: PeerId,
+) -> Result<Duration, error::Ping> {
+    let nonce = rand::random();
+    let start = std::time::Instant::now();
+    let resp = link_async::timeout(
+        ping::TIMEOUT,
+        io::send::single_response(conn, ping::Request { nonce }, ping::FRAMED_BUFSIZ),
+    )
+    .await;
+    let res = match resp {
+        Err(link_async::Elapsed) => Err(error::Ping::Timeout(peer)),
+        Ok(Err(e)) => Err(e.into()),
+        Ok(Ok(None)) => Err(error::Ping::NoResponse(peer)),
+        Ok(Ok(Some(ping::Response { nonce: echo }))) if echo != nonce => {
+            Err(error::Ping::InvalidResponse(peer))
+        },
+        Ok(Ok(Some(_))) => Ok(start.elapsed()),
+    };
+    match &res {
+        Ok(rtt) => {
+            state.liveness.success(peer, *rtt);
+        },
+        // Refusing the upgrade, or failing to decode the request, is not a
+        // sign of the peer being unresponsive
+        Err(error::Ping::Timeout(_) | error::Ping::InvalidResponse(_)) => {
+            state.liveness.failure(peer);
+        },
+        Err(_) => {},
+    }
+    res
+}
+
+pub(super) async fn clock_skew_connection<S, G>(
+    state: &State<S, G>,
+    conn: &quic::Connection,
+    peer: PeerId,
+) -> Result<skew::Estimate, error::Interrogation> {
+    let sent = SystemTime::now();
+    let resp = link_async::timeout(
+        skew::TIMEOUT,
+        io::send::single_response(
+            conn,
+            interrogation::Request::GetTime,
+            interrogation::FRAMED_BUFSIZ,
+        ),
+    )
+    .await;
+    let estimate = match resp {
+        Err(link_async::Elapsed) | Ok(Ok(None)) => Err(error::Interrogation::NoResponse(peer)),
+        Ok(Err(e)) => Err(e.into()),
+        Ok(Ok(Some(interrogation::Response::Time(remote)))) => {
+            Ok(skew::Estimate::new(sent, remote, SystemTime::now()))
+        },
+        Ok(Ok(Some(interrogation::Response::Error(e)))) => {
+            Err(error::Interrogation::ErrorResponse(e))
+        },
+        Ok(Ok(Some(_))) => Err(error::Interrogation::InvalidResponse),
+    }?;
+    state.skew.record(peer, estimate);
+    Ok(estimate)
+}
+
 pub(super) async fn request_pull(
     event::downstream::RequestPull {
         conn,
@@ -142,6 +378,37 @@ pub(super) async fn request_pull(
     }
 }
 
+/// Close all connections to a peer, and remove it from the active view.
+pub(super) async fn disconnect<S, G>(
+    state: &State<S, G>,
+    event::downstream::Disconnect { peer, reply }: event::downstream::Disconnect,
+) where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+    G: RequestPullGuard,
+{
+    let conns = state
+        .endpoint
+        .connections()
+        .into_iter()
+        .filter(|(conn, _)| conn.remote_peer_id() == peer)
+        .collect::<Vec<_>>();
+    let connected = !conns.is_empty();
+    for (conn, _) in conns {
+        conn.close(CloseReason::Disconnected);
+    }
+    state.endpoint.disconnect(&peer);
+    if connected {
+        tracing::info!(%peer, "disconnected on request");
+        state.batches.remove(&peer);
+        io::recv::connection_lost(state.clone(), peer).await;
+    }
+
+    let chan = reply.lock().take();
+    if let Some(tx) = chan {
+        tx.send(connected).ok();
+    }
+}
+
 pub(super) async fn connect<S, G>(
     state: &State<S, G>,
     event::downstream::Connect {
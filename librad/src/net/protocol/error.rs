@@ -7,8 +7,12 @@ use std::fmt::Debug;
 
 use thiserror::Error;
 
-use super::interrogation;
-use crate::{git::storage::pool::PoolError, net::quic, PeerId};
+use super::{interrogation, inventory};
+use crate::{
+    git::storage::pool::PoolError,
+    net::{connection::CloseReason, quic},
+    PeerId,
+};
 
 mod internal;
 pub(super) use internal::*;
@@ -21,6 +25,15 @@ pub enum Bootstrap {
 
     #[error(transparent)]
     Quic(#[from] quic::Error),
+
+    #[error("failed to open capture file")]
+    Capture(#[source] std::io::Error),
+
+    #[error("failed to open announcement queue")]
+    Outbox(#[source] std::io::Error),
+
+    #[error("failed to open mailboxes")]
+    Mailbox(#[source] std::io::Error),
 }
 
 #[derive(Debug, Error)]
@@ -42,6 +55,44 @@ pub enum Interrogation {
     Rpc(#[from] Box<internal::Rpc<quic::BidiStream>>),
 }
 
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Inventory {
+    #[error("no response from {0}")]
+    NoResponse(PeerId),
+
+    #[error("error response: {0:?}")]
+    ErrorResponse(interrogation::Error),
+
+    #[error("too many URNs")]
+    Filter(#[from] inventory::error::Filter),
+
+    #[error("network stack not available")]
+    Unavailable,
+
+    #[error(transparent)]
+    Rpc(#[from] Box<internal::Rpc<quic::BidiStream>>),
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Ping {
+    #[error("no response from {0}")]
+    NoResponse(PeerId),
+
+    #[error("response from {0} does not match the request")]
+    InvalidResponse(PeerId),
+
+    #[error("timed out waiting for response from {0}")]
+    Timeout(PeerId),
+
+    #[error("network stack not available")]
+    Unavailable,
+
+    #[error(transparent)]
+    Rpc(#[from] Box<internal::Rpc<quic::BidiStream>>),
+}
+
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum RequestPull {
@@ -61,8 +112,64 @@ impl From<internal::Rpc<quic::BidiStream>> for Interrogation {
     }
 }
 
+impl From<internal::Rpc<quic::BidiStream>> for Inventory {
+    fn from(e: internal::Rpc<quic::BidiStream>) -> Self {
+        Self::Rpc(Box::new(e))
+    }
+}
+
+impl From<internal::Rpc<quic::BidiStream>> for Ping {
+    fn from(e: internal::Rpc<quic::BidiStream>) -> Self {
+        Self::Rpc(Box::new(e))
+    }
+}
+
 impl From<internal::Rpc<quic::BidiStream>> for RequestPull {
     fn from(e: internal::Rpc<quic::BidiStream>) -> Self {
         Self::Rpc(Box::new(e))
     }
 }
+
+impl Interrogation {
+    /// The [`CloseReason`] the remote end gave for closing the stream or
+    /// connection, if any.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::Rpc(e) => e.close_reason(),
+            _ => None,
+        }
+    }
+}
+
+impl Inventory {
+    /// The [`CloseReason`] the remote end gave for closing the stream or
+    /// connection, if any.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::Rpc(e) => e.close_reason(),
+            _ => None,
+        }
+    }
+}
+
+impl Ping {
+    /// The [`CloseReason`] the remote end gave for closing the stream or
+    /// connection, if any.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::Rpc(e) => e.close_reason(),
+            _ => None,
+        }
+    }
+}
+
+impl RequestPull {
+    /// The [`CloseReason`] the remote end gave for closing the stream or
+    /// connection, if any.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::Rpc(e) => e.close_reason(),
+            _ => None,
+        }
+    }
+}
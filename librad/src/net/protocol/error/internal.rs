@@ -13,6 +13,7 @@ use thiserror::Error;
 use crate::{
     net::{
         codec::{CborCodecError, CborError},
+        connection::CloseReason,
         protocol::{membership, PeerInfo},
         quic,
         upgrade,
@@ -90,6 +91,22 @@ pub enum Rpc<S: 'static> {
     Io(#[from] io::Error),
 }
 
+impl<S> Rpc<S> {
+    /// The [`CloseReason`] the remote end gave for closing the stream or
+    /// connection, if the error was caused by it doing so.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::Upgrade(upgrade::Error {
+                source: upgrade::ErrorSource::Io(e),
+                ..
+            }) => quic::error::close_reason(e),
+            Self::Quic(e) => e.close_reason(),
+            Self::Io(e) => quic::error::close_reason(e),
+            _ => None,
+        }
+    }
+}
+
 // As per usual, the derive macro generates too strict bounds on the type
 // parameter
 impl<S> Debug for Rpc<S> {
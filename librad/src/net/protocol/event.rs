@@ -5,7 +5,24 @@
 
 use std::{collections::HashMap, net::SocketAddr};
 
-use super::{broadcast, cache, error, gossip, interrogation, membership, quic, request_pull};
+use super::{
+    broadcast,
+    cache,
+    compress,
+    error,
+    gossip,
+    interrogation,
+    inventory,
+    latency,
+    mailbox,
+    membership,
+    mux,
+    ping,
+    pinned,
+    quic,
+    request_pull,
+    skew,
+};
 use crate::PeerId;
 
 #[derive(Clone)]
@@ -13,14 +30,17 @@ pub enum Downstream {
     Gossip(downstream::Gossip),
     Info(downstream::Info),
     Interrogation(downstream::Interrogation),
+    Inventory(downstream::Inventory),
+    Ping(downstream::Ping),
     RequestPull(downstream::RequestPull),
     Connect(downstream::Connect),
+    Disconnect(downstream::Disconnect),
 }
 
 pub mod downstream {
     use super::*;
 
-    use std::sync::Arc;
+    use std::{sync::Arc, time::Duration};
 
     use parking_lot::Mutex;
     use tokio::sync::{mpsc, oneshot};
@@ -48,6 +68,29 @@ pub mod downstream {
         ConnectedPeers(Reply<Vec<PeerId>>),
         Membership(Reply<MembershipInfo>),
         Stats(Reply<Stats>),
+        Latency(Reply<latency::Snapshot>),
+        Pinned(Reply<pinned::Snapshot>),
+        Streams(Reply<mux::Snapshot>),
+        Liveness(Reply<ping::Snapshot>),
+        ClockSkew(Reply<skew::Snapshot>),
+        ConnectionStats(Reply<HashMap<PeerId, quic::ConnectionStats>>),
+        Connections(Reply<Vec<ConnectionInfo>>),
+    }
+
+    /// The connections to a single peer.
+    #[derive(Clone, Debug)]
+    pub struct ConnectionInfo {
+        pub peer: PeerId,
+        /// The remote addresses of the connections to the peer.
+        pub addrs: Vec<SocketAddr>,
+        /// Time since the oldest of the connections was established.
+        pub age: Duration,
+        /// Ingress streams opened by the peer, by kind.
+        pub streams: mux::Stats,
+        /// Bytes sent across all connections to the peer.
+        pub sent_bytes: u64,
+        /// Bytes received across all connections to the peer.
+        pub recv_bytes: u64,
     }
 
     #[derive(Clone, Debug, Default)]
@@ -63,6 +106,8 @@ pub mod downstream {
         pub membership_active: usize,
         pub membership_passive: usize,
         pub caches: CacheStats,
+        pub mailbox: mailbox::Stats,
+        pub compression: compress::Stats,
     }
 
     #[derive(Clone, Copy, Debug, Default)]
@@ -79,6 +124,21 @@ pub mod downstream {
             Reply<Result<interrogation::Response<'static, SocketAddr>, error::Interrogation>>,
     }
 
+    #[derive(Clone)]
+    pub struct Inventory {
+        pub conn: quic::Connection,
+        pub peer: PeerId,
+        pub request: inventory::Request,
+        pub reply: Reply<Result<inventory::Page, error::Inventory>>,
+    }
+
+    #[derive(Clone)]
+    pub struct Ping {
+        pub conn: quic::Connection,
+        pub peer: PeerId,
+        pub reply: Reply<Result<Duration, error::Ping>>,
+    }
+
     #[derive(Clone)]
     pub struct RequestPull {
         pub conn: quic::Connection,
@@ -91,6 +151,13 @@ pub mod downstream {
         pub peer: (PeerId, Vec<SocketAddr>),
         pub reply: Reply<Option<quic::Connection>>,
     }
+
+    #[derive(Clone)]
+    pub struct Disconnect {
+        pub peer: PeerId,
+        /// Whether there was a connection to `peer`.
+        pub reply: Reply<bool>,
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -100,12 +167,21 @@ pub enum Upstream {
     Gossip(Box<upstream::Gossip<SocketAddr, gossip::Payload>>),
     Membership(membership::Transition<SocketAddr>),
     Caches(upstream::Caches),
+    Latency(upstream::Latency),
+    Inventory(upstream::Inventory),
+    Replicated(upstream::Replicated),
+    Vetoed(upstream::Vetoed),
+    Pinned(upstream::Pinned),
+    ConnectionStats(upstream::ConnectionStats),
+    ClockSkew(upstream::ClockSkew),
+    CommitSignatures(upstream::CommitSignatures),
+    Storage(upstream::Storage),
 }
 
 pub mod upstream {
     use super::*;
 
-    use std::time::Duration;
+    use std::{collections::BTreeMap, time::Duration};
 
     use futures::{pin_mut, FutureExt as _, StreamExt as _};
     use thiserror::Error;
@@ -167,6 +243,162 @@ pub mod upstream {
         }
     }
 
+    /// Periodic [`latency::Stats`] of all connected peers.
+    #[derive(Clone, Debug)]
+    pub struct Latency(pub latency::Snapshot);
+
+    impl From<Latency> for Upstream {
+        fn from(l: Latency) -> Self {
+            Self::Latency(l)
+        }
+    }
+
+    /// Outcome of reconciling the inventory with a peer.
+    #[derive(Clone, Debug)]
+    pub struct Inventory {
+        /// The peer the inventory was reconciled with.
+        pub peer: PeerId,
+        /// The [`crate::git::Urn`]s both the local and the remote peer have.
+        pub shared: Vec<crate::git::Urn>,
+        /// The tips of the remote peer's `rad/signed_refs` of the
+        /// [`Self::shared`] URNs, see [`inventory::Shared::signed_refs`].
+        pub signed_refs: BTreeMap<crate::git::Urn, git_ext::Oid>,
+    }
+
+    impl From<Inventory> for Upstream {
+        fn from(i: Inventory) -> Self {
+            Self::Inventory(i)
+        }
+    }
+
+    /// Triggered after [`crate::net::peer::Peer::replicate`] completed
+    /// successfully.
+    #[derive(Clone, Debug)]
+    pub struct Replicated {
+        /// The replicated [`crate::git::Urn`].
+        pub urn: crate::git::Urn,
+        /// The peer replicated from.
+        pub from: PeerId,
+    }
+
+    impl From<Replicated> for Upstream {
+        fn from(r: Replicated) -> Self {
+            Self::Replicated(r)
+        }
+    }
+
+    /// Ref updates fetched by [`crate::net::peer::Peer::replicate`], or by a
+    /// gossip-triggered fetch, were rejected by a pre-apply replication hook.
+    ///
+    /// Only emitted with the `replication-v3` feature.
+    #[derive(Clone, Debug)]
+    pub struct Vetoed {
+        pub urn: crate::git::Urn,
+        /// The peer replicated from.
+        pub from: PeerId,
+        /// The reason given by the hook.
+        pub reason: String,
+    }
+
+    impl From<Vetoed> for Upstream {
+        fn from(v: Vetoed) -> Self {
+            Self::Vetoed(v)
+        }
+    }
+
+    /// Periodic [`quic::ConnectionStats`] of all connected peers.
+    #[derive(Clone, Debug)]
+    pub struct ConnectionStats(pub HashMap<PeerId, quic::ConnectionStats>);
+
+    impl From<ConnectionStats> for Upstream {
+        fn from(s: ConnectionStats) -> Self {
+            Self::ConnectionStats(s)
+        }
+    }
+
+    /// The [`pinned::Status`] of a pinned peer changed.
+    #[derive(Clone, Debug)]
+    pub struct Pinned {
+        pub peer: PeerId,
+        pub status: pinned::Status,
+    }
+
+    impl From<Pinned> for Upstream {
+        fn from(p: Pinned) -> Self {
+            Self::Pinned(p)
+        }
+    }
+
+    /// The local clock appears to be skewed relative to the clocks of the
+    /// connected peers, see [`skew`].
+    #[derive(Clone, Debug)]
+    pub struct ClockSkew {
+        /// Median number of milliseconds the clocks of the peers are ahead of
+        /// the local clock. Negative if the local clock is ahead.
+        pub median_offset_ms: i64,
+        /// Number of peers the median was taken over.
+        pub peers: usize,
+    }
+
+    impl From<ClockSkew> for Upstream {
+        fn from(s: ClockSkew) -> Self {
+            Self::ClockSkew(s)
+        }
+    }
+
+    /// Commits replicated from a peer violate the commit signature policy of
+    /// the project, see [`crate::git::signatures`].
+    #[derive(Clone, Debug)]
+    pub struct CommitSignatures {
+        pub urn: crate::git::Urn,
+        /// The peer replicated from.
+        pub from: PeerId,
+        pub branches: Vec<crate::git::signatures::BranchViolations>,
+    }
+
+    impl From<CommitSignatures> for Upstream {
+        fn from(s: CommitSignatures) -> Self {
+            Self::CommitSignatures(s)
+        }
+    }
+
+    /// The storage became read-only, or writable again, see
+    /// [`crate::git::storage::health`].
+    ///
+    /// While read-only, no fetches are attempted, but existing data continues
+    /// to be served.
+    pub type Storage = crate::git::storage::health::Transition;
+
+    impl From<Storage> for Upstream {
+        fn from(t: Storage) -> Self {
+            Self::Storage(t)
+        }
+    }
+
+    /// An [`Upstream`] event tagged with its position in the sequence of all
+    /// events emitted by the protocol.
+    #[derive(Clone, Debug)]
+    pub struct Sequenced {
+        pub seq: u64,
+        pub event: Upstream,
+    }
+
+    /// Item of a resumable event subscription, see
+    /// [`crate::net::peer::Peer::subscribe_from`].
+    #[derive(Clone, Debug)]
+    pub enum Replay {
+        Event(Sequenced),
+        /// The events with sequence numbers `from..=to` were dropped.
+        ///
+        /// If the subscription was resumed after an event which was not
+        /// emitted yet, `from` is zero, and the sequence numbers of the
+        /// events following the gap may be lower than `to`.
+        Gap {
+            from: u64,
+            to: u64,
+        },
+    }
+
     #[derive(Debug, Error)]
     pub enum ExpectError {
         #[error("timeout waiting for matching event")]
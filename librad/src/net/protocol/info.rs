@@ -11,11 +11,89 @@ use typenum::U16;
 
 use crate::PeerId;
 
-#[derive(Debug, Clone, Eq, Ord, PartialEq, PartialOrd, Encode, Decode)]
-#[repr(u8)]
+/// A protocol extension supported by a peer.
+///
+/// Capabilities not known to the local peer are decoded as
+/// [`Capability::Unknown`], so that newer peers can be understood.
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
 pub enum Capability {
-    #[n(0)]
-    Reserved = 0,
+    Reserved,
+    /// The peer accepts batches of gossip messages, see
+    /// [`super::batch`].
+    GossipBatch,
+    /// The peer accepts compressed gossip and membership frames, see
+    /// [`super::compress`].
+    GossipCompression,
+    /// The peer does not accept inbound connections, and must not be
+    /// included in shuffles or forwarded joins. See
+    /// [`super::Config::private`].
+    Private,
+    /// The peer declares the URNs it is interested in, and accepts
+    /// declarations from its neighbours, see [`super::interest`].
+    GossipInterest,
+    /// The peer signals when it is too busy to act upon gossip, and backs off
+    /// from neighbours which signal the same, see [`super::backoff`].
+    GossipBackoff,
+    /// The peer answers liveness checks, see [`super::ping`].
+    Ping,
+    /// A capability introduced by a later version of the protocol.
+    Unknown(u32),
+}
+
+impl Capability {
+    fn code(&self) -> u32 {
+        match self {
+            Self::Reserved => 0,
+            Self::GossipBatch => 1,
+            Self::GossipCompression => 2,
+            Self::Private => 3,
+            Self::GossipInterest => 4,
+            Self::GossipBackoff => 5,
+            Self::Ping => 6,
+            Self::Unknown(n) => *n,
+        }
+    }
+}
+
+impl From<u32> for Capability {
+    fn from(n: u32) -> Self {
+        match n {
+            0 => Self::Reserved,
+            1 => Self::GossipBatch,
+            2 => Self::GossipCompression,
+            3 => Self::Private,
+            4 => Self::GossipInterest,
+            5 => Self::GossipBackoff,
+            6 => Self::Ping,
+            x => Self::Unknown(x),
+        }
+    }
+}
+
+// Encoded like a unit variant of an enum deriving `Encode`, ie. `[n, []]`,
+// which is what peers predating `Unknown` expect.
+
+impl Encode for Capability {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        e.array(2)?.u32(self.code())?.array(0)?;
+        Ok(())
+    }
+}
+
+impl<'b> Decode<'b> for Capability {
+    fn decode(d: &mut minicbor::Decoder<'b>) -> Result<Self, minicbor::decode::Error> {
+        let len = d.array()?;
+        let code = d.u32()?;
+        for _ in 1..len.ok_or(minicbor::decode::Error::Message(
+            "expected definite length array",
+        ))? {
+            d.skip()?;
+        }
+        Ok(Self::from(code))
+    }
 }
 
 pub type PeerInfo<Addr> = GenericPeerInfo<Addr, PeerAdvertisement<Addr>>;
@@ -99,16 +177,61 @@ impl<Addr> GenericPeerInfo<Addr, PeerAdvertisement<Addr>> {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Decode, Encode)]
-#[cbor(array)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PeerAdvertisement<Addr> {
-    #[n(0)]
     pub listen_addrs: BoundedVec<U16, Addr>,
-
-    #[n(2)]
     pub capabilities: BTreeSet<Capability>,
 }
 
+// Peers predating [`Capability::Unknown`] fail to decode the whole
+// advertisement if it contains a capability they don't know. They expect the
+// capabilities at index 2, and skip the ones after it, so the capabilities are
+// sent at index 3, and an empty set at index 2.
+
+impl<Addr> Encode for PeerAdvertisement<Addr>
+where
+    Addr: Encode,
+{
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        e.array(4)?
+            .encode(&self.listen_addrs)?
+            .null()?
+            .array(0)?
+            .encode(&self.capabilities)?;
+        Ok(())
+    }
+}
+
+impl<'b, Addr> Decode<'b> for PeerAdvertisement<Addr>
+where
+    Addr: Decode<'b>,
+{
+    fn decode(d: &mut minicbor::Decoder<'b>) -> Result<Self, minicbor::decode::Error> {
+        use minicbor::decode::Error;
+
+        let len = d
+            .array()?
+            .ok_or(Error::Message("expected definite length array"))?;
+        let mut listen_addrs = None;
+        let mut capabilities = BTreeSet::new();
+        for i in 0..len {
+            match i {
+                0 => listen_addrs = Some(d.decode()?),
+                2 | 3 => capabilities.extend(d.decode::<BTreeSet<Capability>>()?),
+                _ => d.skip()?,
+            }
+        }
+
+        Ok(Self {
+            listen_addrs: listen_addrs.ok_or(Error::MissingValue(0, "listen_addrs"))?,
+            capabilities,
+        })
+    }
+}
+
 impl<Addr> PeerAdvertisement<Addr> {
     pub fn new(listen_addr: Addr) -> Self {
         Self {
@@ -116,4 +239,9 @@ impl<Addr> PeerAdvertisement<Addr> {
             capabilities: BTreeSet::default(),
         }
     }
+
+    /// Whether the peer advertised [`Capability::Private`].
+    pub fn is_private(&self) -> bool {
+        self.capabilities.contains(&Capability::Private)
+    }
 }
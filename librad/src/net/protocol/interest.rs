@@ -0,0 +1,104 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Scoping of gossip to the interests of neighbours.
+//!
+//! Peers which advertise [`Capability::GossipInterest`] declare the URNs they
+//! are interested in to their active neighbours, by sending them the [`Xor`]
+//! filter of the URNs they store (see [`super::cache::urns`]). They do so when
+//! a neighbour is promoted, and whenever the filter is rebuilt.
+//!
+//! An announcement of a URN which is not in the declared filter of a neighbour
+//! is not forwarded to it: as [`Xor`] filters have no false negatives, the
+//! neighbour provably doesn't care about it. This mostly benefits small peers
+//! connected to hubs, which would otherwise receive all the gossip the hub
+//! relays. Neighbours which did not declare their interests, eg. because they
+//! don't support the capability, keep receiving all gossip.
+//!
+//! Note that a peer which is not sent an announcement can't relay it either,
+//! so scoping trades some redundancy of the overlay for bandwidth.
+//!
+//! [`Capability::GossipInterest`]: super::Capability::GossipInterest
+
+use std::{collections::HashMap, sync::Arc};
+
+use parking_lot::RwLock;
+
+use crate::{
+    git::Urn,
+    identities::{SomeUrn, Xor},
+    PeerId,
+};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Config {
+    /// Whether to advertise [`super::Capability::GossipInterest`], declare
+    /// the local interests to neighbours which advertise it, too, and scope
+    /// the gossip sent to neighbours which declared theirs.
+    ///
+    /// Peers running versions which don't know about the capability fail to
+    /// decode advertisements containing it, so this should only be enabled
+    /// once the network has been upgraded.
+    pub enabled: bool,
+}
+
+/// The interests declared by neighbours.
+#[derive(Clone)]
+pub struct Interests {
+    config: Config,
+    declared: Arc<RwLock<HashMap<PeerId, Arc<Xor>>>>,
+}
+
+impl Interests {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            declared: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Record the interests declared by `peer`, replacing any it declared
+    /// before.
+    ///
+    /// Declarations are ignored unless scoping is [`Config::enabled`].
+    pub fn declare(&self, peer: PeerId, filter: Xor) {
+        if self.config.enabled {
+            self.declared.write().insert(peer, Arc::new(filter));
+        }
+    }
+
+    /// Forget the interests declared by `peer`, eg. when it is no longer a
+    /// neighbour.
+    ///
+    /// Returns `true` if `peer` had declared any.
+    pub fn remove(&self, peer: &PeerId) -> bool {
+        self.declared.write().remove(peer).is_some()
+    }
+
+    /// Whether gossip about `urn` should be sent to `peer`.
+    ///
+    /// This is the case unless `peer` declared interests which don't include
+    /// `urn`.
+    pub fn is_interested(&self, peer: &PeerId, urn: &Urn) -> bool {
+        self.declared
+            .read()
+            .get(peer)
+            .map(|filter| filter.contains(&SomeUrn::Git(urn.clone())))
+            .unwrap_or(true)
+    }
+
+    /// The number of neighbours which declared their interests.
+    pub fn len(&self) -> usize {
+        self.declared.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
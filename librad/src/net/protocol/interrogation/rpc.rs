@@ -6,7 +6,7 @@
 use std::borrow::Cow;
 
 use super::PeerAdvertisement;
-use crate::identities::xor;
+use crate::{identities::xor, net::protocol::rotation::Rotation};
 
 #[derive(Clone, Copy, Debug, minicbor::Encode, minicbor::Decode)]
 pub enum Request {
@@ -27,6 +27,18 @@ pub enum Request {
     #[n(2)]
     #[cbor(array)]
     GetUrns,
+
+    /// Ask the remote peer for the current time according to its clock.
+    ///
+    /// Used to estimate clock skew, see [`crate::net::protocol::skew`].
+    #[n(3)]
+    #[cbor(array)]
+    GetTime,
+
+    /// Ask the remote peer whether it announced a rotation of its key.
+    #[n(4)]
+    #[cbor(array)]
+    GetRotation,
 }
 
 #[derive(minicbor::Encode, minicbor::Decode)]
@@ -56,6 +68,17 @@ where
     #[n(3)]
     #[cbor(array)]
     Urns(#[n(0)] Cow<'a, xor::Xor>),
+
+    /// Response to a [`Request::GetTime`], in milliseconds since the UNIX
+    /// epoch.
+    #[n(4)]
+    #[cbor(array)]
+    Time(#[n(0)] u64),
+
+    /// Response to a [`Request::GetRotation`].
+    #[n(5)]
+    #[cbor(array)]
+    Rotation(#[n(0)] Option<Rotation>),
 }
 
 /// Error response.
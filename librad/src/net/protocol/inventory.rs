@@ -0,0 +1,299 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Reconciliation of the sets of URNs two peers have in common.
+//!
+//! The requester sends a compact [`Xor`] filter of the URNs it is interested
+//! in, and the responder replies with the URNs it has which match the filter.
+//! Because the responder only ever returns URNs it actually stores, and the
+//! requester can discard the (rare) false positives of its own filter, the
+//! outcome is exact -- unlike testing local URNs against the responder's
+//! filter as obtained via [`super::interrogation::Request::GetUrns`].
+//!
+//! Results are returned in [`Page`]s, ordered by [`Urn`]. The requester
+//! proceeds by repeating the request with [`Request::after`] set to the last
+//! [`Urn`] of the previous page, until [`Page::more`] is `false`.
+//!
+//! Along with the [`Urn`]s, the responder reports the tips of its own
+//! `rad/signed_refs`. By comparing them against its copy of the responder's
+//! signed refs, the requester can tell which [`Urn`]s it is missing updates
+//! for, eg. because it missed an announcement during a partition. The
+//! [`super::event::upstream::Inventory`] event emitted after a reconciliation
+//! drives the prefetching of those, see [`State::prefetch`].
+//!
+//! To keep paging cheap, the responder serves all pages from a sorted
+//! snapshot of its [`Urn`]s, which is rebuilt at most every
+//! [`SNAPSHOT_TTL`]. Requests are also subject to a per-peer rate limit.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use git_ext as ext;
+
+use link_async::Spawner;
+use parking_lot::Mutex;
+use thiserror::Error;
+use typenum::Unsigned as _;
+
+use super::{broadcast, gossip};
+use crate::{
+    git::{
+        identities,
+        storage::{self, PoolError, ReadOnlyStorage as _},
+        types::{Namespace, Reference},
+        Urn,
+    },
+    identities::{xor, SomeUrn, Xor},
+    PeerId,
+};
+
+mod rpc;
+pub use rpc::{Error, Page, Request, Response};
+
+/// Buffer size for reading and writing inventory RPC messages, dominated by
+/// the size of the [`Xor`] filter in the [`Request`].
+pub const FRAMED_BUFSIZ: usize = xor::MaxFingerprints::USIZE * 3;
+
+/// Maximum number of [`Urn`]s returned in a single [`Page`].
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// How long a snapshot of the local [`Urn`]s is used to serve pages,
+/// before it is rebuilt from storage.
+pub const SNAPSHOT_TTL: Duration = Duration::from_secs(30);
+
+pub mod error {
+    use super::*;
+
+    #[derive(Debug, Error)]
+    pub enum Page {
+        #[error(transparent)]
+        Pool(#[from] PoolError),
+
+        #[error(transparent)]
+        Identities(#[from] identities::Error),
+
+        #[error(transparent)]
+        Read(#[from] storage::read::Error),
+    }
+
+    #[derive(Debug, Error)]
+    pub enum Prefetch {
+        #[error(transparent)]
+        Pool(#[from] PoolError),
+
+        #[error(transparent)]
+        Read(#[from] storage::read::Error),
+    }
+
+    #[derive(Debug, Error)]
+    pub enum Filter {
+        #[error(transparent)]
+        Build(#[from] xor::BuildError<Infallible>),
+    }
+}
+
+/// Build the [`Request`] for the first page of a reconciliation of `urns`.
+pub fn request<'a>(urns: impl IntoIterator<Item = &'a Urn>) -> Result<Request, error::Filter> {
+    let (filter, _) = Xor::try_from_iter(
+        urns.into_iter()
+            .map(|urn| Ok::<_, Infallible>(SomeUrn::from(urn.clone().with_path(None)))),
+    )?;
+    Ok(Request {
+        filter,
+        after: None,
+    })
+}
+
+/// Keep only the [`Urn`]s of `page` which are in `interest`, ie. discard false
+/// positives.
+pub fn exact(interest: &BTreeSet<Urn>, page: Page) -> impl Iterator<Item = Urn> + '_ {
+    page.urns
+        .into_iter()
+        .filter(move |urn| interest.contains(urn))
+}
+
+/// The [`Urn`]s two peers have in common, as determined by
+/// [`crate::net::peer::Peer::inventory`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Shared {
+    pub urns: Vec<Urn>,
+    /// The tips of the remote peer's `rad/signed_refs` of [`Shared::urns`],
+    /// see [`Page::signed_refs`].
+    pub signed_refs: BTreeMap<Urn, ext::Oid>,
+}
+
+impl Shared {
+    /// Add the [`exact`] results of `page`.
+    pub fn extend(&mut self, interest: &BTreeSet<Urn>, page: Page) {
+        let signed_refs = page.signed_refs.clone().unwrap_or_default();
+        for urn in exact(interest, page) {
+            if let Some(tip) = signed_refs.get(&urn) {
+                self.signed_refs.insert(urn.clone(), *tip);
+            }
+            self.urns.push(urn);
+        }
+    }
+}
+
+/// The sorted [`Urn`]s of the local storage at some point in time.
+#[derive(Clone)]
+struct Snapshot {
+    urns: Arc<Vec<Urn>>,
+    taken: Instant,
+}
+
+impl Snapshot {
+    fn take(storage: &storage::Storage) -> Result<Self, identities::Error> {
+        let mut urns = identities::any::list_urns(storage)?
+            .filter_map(|urn| urn.ok())
+            .collect::<Vec<_>>();
+        urns.sort();
+        Ok(Self {
+            urns: Arc::new(urns),
+            taken: Instant::now(),
+        })
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.taken.elapsed() < SNAPSHOT_TTL
+    }
+
+    /// The [`Urn`]s greater than `after`, if given, which are in `filter`.
+    fn page(&self, filter: &Xor, after: Option<&Urn>) -> (Vec<Urn>, bool) {
+        let start = after
+            .map(|after| self.urns.partition_point(|urn| urn <= after))
+            .unwrap_or(0);
+        let mut urns = self.urns[start..]
+            .iter()
+            .filter(|urn| filter.contains(&SomeUrn::from((*urn).clone())))
+            .take(MAX_PAGE_SIZE + 1)
+            .cloned()
+            .collect::<Vec<_>>();
+        let more = urns.len() > MAX_PAGE_SIZE;
+        urns.truncate(MAX_PAGE_SIZE);
+        (urns, more)
+    }
+}
+
+/// State for serving inventory requests.
+#[derive(Clone)]
+pub struct State<S> {
+    storage: S,
+    snapshot: Arc<Mutex<Option<Snapshot>>>,
+}
+
+impl<S> State<S> {
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            snapshot: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl<S> State<S>
+where
+    S: storage::Pooled<storage::Storage> + Send + Sync + 'static,
+{
+    /// Compute the [`Page`] of local [`Urn`]s matching `request`.
+    ///
+    /// Pages are cut from the current snapshot of the local [`Urn`]s, so a
+    /// requester paging through the results sees a consistent view unless
+    /// the snapshot expires in between.
+    pub async fn page(&self, spawner: &Spawner, request: Request) -> Result<Page, error::Page> {
+        let storage = self.storage.get().await?;
+        let snapshot = self.snapshot.clone();
+        spawner
+            .blocking(move || {
+                let current = {
+                    let mut snapshot = snapshot.lock();
+                    match snapshot.as_ref() {
+                        Some(current) if current.is_fresh() => current.clone(),
+                        _ => {
+                            let current = Snapshot::take(&storage)?;
+                            *snapshot = Some(current.clone());
+                            current
+                        },
+                    }
+                };
+                let Request { filter, after } = request;
+                let (urns, more) = current.page(&filter, after.as_ref());
+
+                let mut signed_refs = BTreeMap::new();
+                for urn in &urns {
+                    let sigrefs = Reference::rad_signed_refs(Namespace::from(urn), None);
+                    if let Some(tip) = storage.reference(&sigrefs)?.and_then(|r| r.target()) {
+                        signed_refs.insert(urn.clone(), tip.into());
+                    }
+                }
+
+                Ok(Page {
+                    urns,
+                    more,
+                    signed_refs: Some(signed_refs),
+                })
+            })
+            .await
+    }
+
+    /// Fetch the [`Urn`]s `peer` reported `signed_refs` for, if they differ
+    /// from the local copy of its `rad/signed_refs`.
+    ///
+    /// The fetches go through the same path as gossip announcements, and are
+    /// thus subject to tracking and fetch rate limits. Returns the number of
+    /// [`Urn`]s which were updated.
+    pub async fn prefetch(
+        &self,
+        spawner: &Spawner,
+        peer: PeerId,
+        addrs: Vec<SocketAddr>,
+        signed_refs: BTreeMap<Urn, ext::Oid>,
+    ) -> Result<usize, error::Prefetch>
+    where
+        S: broadcast::LocalStorage<SocketAddr, Update = gossip::Payload>,
+    {
+        use broadcast::LocalStorage as _;
+
+        let storage = self.storage.get().await?;
+        let stale = spawner
+            .blocking(move || {
+                let mut stale = Vec::new();
+                for (urn, tip) in signed_refs {
+                    let sigrefs = Reference::rad_signed_refs(Namespace::from(&urn), peer);
+                    let local = storage.reference(&sigrefs)?.and_then(|r| r.target());
+                    if local.map(ext::Oid::from) != Some(tip) {
+                        stale.push(urn);
+                    }
+                }
+                Ok::<_, error::Prefetch>(stale)
+            })
+            .await?;
+
+        let mut updated = 0;
+        for urn in stale {
+            let res = self
+                .storage
+                .put(
+                    (peer, addrs.clone()),
+                    gossip::Payload {
+                        urn,
+                        rev: None,
+                        origin: None,
+                    },
+                )
+                .await;
+            if let broadcast::PutResult::Applied(_) = res {
+                updated += 1;
+            }
+        }
+
+        Ok(updated)
+    }
+}
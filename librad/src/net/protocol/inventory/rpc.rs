@@ -0,0 +1,56 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::collections::BTreeMap;
+
+use git_ext as ext;
+use minicbor::{Decode, Encode};
+
+use crate::{git::Urn, identities::xor::Xor};
+
+pub use super::super::interrogation::Error;
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[cbor(array)]
+pub struct Request {
+    /// The [`Urn`]s the requester is interested in.
+    #[n(0)]
+    pub filter: Xor,
+    /// Only return [`Urn`]s greater than this one.
+    #[n(1)]
+    pub after: Option<Urn>,
+}
+
+#[derive(Clone, Debug, Encode, Decode)]
+pub enum Response {
+    /// An application-level error occurred, which prevented the responder from
+    /// fulfilling the request.
+    #[n(0)]
+    #[cbor(array)]
+    Error(#[n(0)] Error),
+
+    /// The responder's [`Urn`]s matching the [`Request::filter`].
+    #[n(1)]
+    #[cbor(array)]
+    Page(#[n(0)] Page),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Encode, Decode)]
+#[cbor(array)]
+pub struct Page {
+    /// Matching [`Urn`]s, in ascending order.
+    #[n(0)]
+    pub urns: Vec<Urn>,
+    /// Whether there are more matching [`Urn`]s after the last one in
+    /// [`Page::urns`].
+    #[n(1)]
+    pub more: bool,
+    /// The tips of the responder's own `rad/signed_refs` of the [`Urn`]s in
+    /// [`Page::urns`], where it has any.
+    ///
+    /// Absent if the responder predates this field.
+    #[n(2)]
+    pub signed_refs: Option<BTreeMap<Urn, ext::Oid>>,
+}
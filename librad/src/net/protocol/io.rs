@@ -3,20 +3,23 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{iter, net::SocketAddr};
+use std::{collections::BTreeSet, iter, net::SocketAddr};
 
 use data::BoundedVec;
 
 use super::{
     gossip,
-    info::{PartialPeerInfo, PeerAdvertisement},
+    info::{Capability, PartialPeerInfo, PeerAdvertisement},
     membership,
     Endpoint,
     ProtocolStorage,
     RequestPullGuard,
     State,
 };
-use crate::{net::connection::RemoteAddr as _, PeerId};
+use crate::{
+    net::{connection::RemoteAddr as _, quic},
+    PeerId,
+};
 
 mod codec;
 
@@ -31,61 +34,90 @@ pub use send::{rpc::Rpc, send_rpc};
 
 pub(super) mod streams;
 
+/// Connect to the `peer` and join the membership, unless already connected.
+///
+/// Returns `true` if a new connection was established.
 #[tracing::instrument(skip(state, peer, addrs), fields(remote_id = %peer))]
-pub(super) async fn discovered<S, G>(state: State<S, G>, peer: PeerId, addrs: Vec<SocketAddr>)
+pub(super) async fn discovered<S, G>(
+    state: State<S, G>,
+    peer: PeerId,
+    addrs: Vec<SocketAddr>,
+) -> bool
 where
     S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
     G: RequestPullGuard,
 {
     if state.has_connection(peer) {
-        return;
+        return false;
     }
 
-    if let Some((conn, ingress)) = connect(&state.endpoint, peer, addrs).await {
-        let rpc_sent = send_rpc::<_, ()>(
-            &conn,
+    match connect(&state.endpoint, peer, addrs).await {
+        Some((conn, ingress)) if join(&state, peer, &conn).await => {
             state
-                .membership
-                .hello(peer_advertisement(&state.endpoint)()),
-        )
-        .await;
+                .spawner
+                .spawn_cancellable(&state.cancel, streams::incoming(state.clone(), ingress))
+                .detach();
+            true
+        },
+        _ => false,
+    }
+}
 
-        match rpc_sent {
-            Err(e) => tracing::warn!(err = ?e, "failed to send membership hello"),
-            Ok(()) => {
-                let membership::TnT { trans, ticks } =
-                    state.membership.connection_established(PartialPeerInfo {
-                        peer_id: peer,
-                        advertised_info: None,
-                        seen_addrs: BoundedVec::singleton(conn.remote_addr()),
-                    });
+/// Send a membership hello to `peer` over `conn`, and add it to the active
+/// view.
+///
+/// Returns `false` if the hello could not be sent.
+pub(super) async fn join<S, G>(state: &State<S, G>, peer: PeerId, conn: &quic::Connection) -> bool
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+    G: RequestPullGuard,
+{
+    let rpc_sent = send_rpc::<_, ()>(
+        conn,
+        None,
+        state.membership.hello(state.peer_advertisement()()),
+    )
+    .await;
 
-                state.emit(trans);
-                state
-                    .tick(membership::tocks(
-                        &state.membership,
-                        peer_advertisement(&state.endpoint),
-                        ticks,
-                    ))
-                    .await;
-                state
-                    .spawner
-                    .spawn(streams::incoming(state.clone(), ingress))
-                    .detach();
-            },
-        }
+    match rpc_sent {
+        Err(e) => {
+            tracing::warn!(err = ?e, "failed to send membership hello");
+            false
+        },
+        Ok(()) => {
+            let membership::TnT { trans, ticks } =
+                state.membership.connection_established(PartialPeerInfo {
+                    peer_id: peer,
+                    advertised_info: None,
+                    seen_addrs: BoundedVec::singleton(conn.remote_addr()),
+                });
+
+            state.emit(trans);
+            state
+                .tick(membership::tocks(
+                    &state.membership,
+                    state.peer_advertisement(),
+                    ticks,
+                ))
+                .await;
+            true
+        },
     }
 }
 
-pub(super) fn peer_advertisement(
-    endpoint: &Endpoint,
-) -> impl Fn() -> PeerAdvertisement<SocketAddr> + '_ {
+pub(super) fn peer_advertisement<'a>(
+    endpoint: &'a Endpoint,
+    capabilities: &'a BTreeSet<Capability>,
+) -> impl Fn() -> PeerAdvertisement<SocketAddr> + 'a {
     move || {
         let mut listen_addrs = BoundedVec::from(iter::empty());
-        listen_addrs.extend_fill(endpoint.listen_addrs());
+        // Private peers don't reveal where they can be reached.
+        if !capabilities.contains(&Capability::Private) {
+            listen_addrs.extend_fill(endpoint.listen_addrs());
+        }
         PeerAdvertisement {
             listen_addrs,
-            capabilities: Default::default(),
+            capabilities: capabilities.clone(),
         }
     }
 }
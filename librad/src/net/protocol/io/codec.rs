@@ -3,14 +3,172 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::net::SocketAddr;
+use std::{io, net::SocketAddr};
 
-use crate::net::{
-    codec::CborCodec,
-    protocol::{broadcast, membership},
+use crate::{
+    identities::Xor,
+    net::{
+        codec::CborCodec,
+        protocol::{backoff, broadcast, compress, membership},
+    },
 };
 
 pub type Codec<T> = CborCodec<T, T>;
 
-pub type Gossip<T> = Codec<broadcast::Message<SocketAddr, T>>;
-pub type Membership = Codec<membership::Message<SocketAddr>>;
+pub type Gossip<T> = Codec<Compressible<GossipFrame<SocketAddr, T>>>;
+pub type Membership = Codec<Compressible<membership::Message<SocketAddr>>>;
+
+/// A frame which may have been compressed.
+///
+/// A plain frame is encoded as is. A compressed frame is encoded as a CBOR
+/// byte string, and only sent to peers which advertised
+/// [`Capability::GossipCompression`].
+///
+/// [`Capability::GossipCompression`]: crate::net::protocol::Capability::GossipCompression
+#[derive(Clone, Debug, PartialEq)]
+pub enum Compressible<T> {
+    Plain(T),
+    Compressed(Vec<u8>),
+}
+
+impl<T> Compressible<T>
+where
+    for<'b> T: minicbor::Decode<'b>,
+{
+    /// Obtain the plain frame, decompressing it if necessary.
+    pub fn decompress(self, compression: &compress::Compression) -> io::Result<T> {
+        match self {
+            Self::Plain(t) => Ok(t),
+            Self::Compressed(bytes) => {
+                let plain = compression.decompress(&bytes)?;
+                minicbor::decode(&plain).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            },
+        }
+    }
+}
+
+impl<T> minicbor::Encode for Compressible<T>
+where
+    T: minicbor::Encode,
+{
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        match self {
+            Self::Plain(t) => t.encode(e),
+            Self::Compressed(bytes) => {
+                e.bytes(bytes)?;
+                Ok(())
+            },
+        }
+    }
+}
+
+impl<'b, T> minicbor::Decode<'b> for Compressible<T>
+where
+    T: minicbor::Decode<'b>,
+{
+    fn decode(d: &mut minicbor::Decoder<'b>) -> Result<Self, minicbor::decode::Error> {
+        use minicbor::data::Type;
+
+        match d.datatype()? {
+            Type::Bytes => Ok(Self::Compressed(d.bytes()?.to_vec())),
+            _ => d.decode().map(Self::Plain),
+        }
+    }
+}
+
+/// A frame on the gossip stream.
+///
+/// A single message is encoded as is, so peers which don't support batches
+/// can still decode it. A batch is encoded as a CBOR array of messages, and
+/// only sent to peers which advertised [`Capability::GossipBatch`].
+///
+/// A declaration of interests is encoded as a CBOR map from `0` to the
+/// [`Xor`] filter, and only sent to peers which advertised
+/// [`Capability::GossipInterest`].
+///
+/// A [`backoff::Signal`] is encoded as a CBOR map from `1` to the signal, and
+/// only sent to peers which advertised [`Capability::GossipBackoff`].
+///
+/// [`Capability::GossipBatch`]: crate::net::protocol::Capability::GossipBatch
+/// [`Capability::GossipInterest`]: crate::net::protocol::Capability::GossipInterest
+/// [`Capability::GossipBackoff`]: crate::net::protocol::Capability::GossipBackoff
+#[derive(Clone, Debug, PartialEq)]
+pub enum GossipFrame<A, P> {
+    One(broadcast::Message<A, P>),
+    Batch(Vec<broadcast::Message<A, P>>),
+    Interest(Xor),
+    Backoff(backoff::Signal),
+}
+
+impl<A, P> GossipFrame<A, P> {
+    /// The gossip messages contained in the frame, if any.
+    pub fn into_messages(self) -> Vec<broadcast::Message<A, P>> {
+        match self {
+            Self::One(msg) => vec![msg],
+            Self::Batch(msgs) => msgs,
+            Self::Interest(_) | Self::Backoff(_) => vec![],
+        }
+    }
+}
+
+impl<A, P> minicbor::Encode for GossipFrame<A, P>
+where
+    A: minicbor::Encode,
+    P: minicbor::Encode,
+{
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        match self {
+            Self::One(msg) => msg.encode(e),
+            Self::Batch(msgs) => msgs.encode(e),
+            Self::Interest(filter) => {
+                e.map(1)?.u8(0)?;
+                filter.encode(e)
+            },
+            Self::Backoff(signal) => {
+                e.map(1)?.u8(1)?;
+                signal.encode(e)
+            },
+        }
+    }
+}
+
+impl<'b, A, P> minicbor::Decode<'b> for GossipFrame<A, P>
+where
+    A: minicbor::Decode<'b>,
+    P: minicbor::Decode<'b>,
+{
+    fn decode(d: &mut minicbor::Decoder<'b>) -> Result<Self, minicbor::decode::Error> {
+        use minicbor::data::Type;
+
+        if let Type::Map = d.datatype()? {
+            return match (d.map()?, d.u8()?) {
+                (Some(1), 0) => d.decode().map(Self::Interest),
+                (Some(1), 1) => d.decode().map(Self::Backoff),
+                _ => Err(minicbor::decode::Error::Message(
+                    "unknown gossip frame, expected interest declaration or backoff signal",
+                )),
+            };
+        }
+
+        // A message is an array starting with the variant index, a batch is an
+        // array of arrays.
+        let is_batch = {
+            let mut probe = d.probe();
+            match probe.array()? {
+                Some(0) => true,
+                _ => matches!(probe.datatype()?, Type::Array | Type::ArrayIndef),
+            }
+        };
+        if is_batch {
+            d.decode().map(Self::Batch)
+        } else {
+            d.decode().map(Self::One)
+        }
+    }
+}
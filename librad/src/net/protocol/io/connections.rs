@@ -17,6 +17,8 @@ pub use super::error;
 use super::streams;
 use crate::{
     net::{
+        connection::{CloseReason, RemotePeer as _},
+        dial,
         protocol::{
             event::upstream as event,
             gossip,
@@ -56,10 +58,21 @@ where
     futures::pin_mut!(ingress);
     while let Some(conn) = ingress.next().await {
         match conn {
-            Ok((_, streams)) => {
+            Ok((conn, streams)) => {
+                let remote_id = conn.remote_peer_id();
+                if state.limits.offenders.is_banned(&remote_id) {
+                    tracing::debug!(remote_id = %remote_id, "refusing connection from banned peer");
+                    conn.close(CloseReason::RateLimited);
+                    continue;
+                }
+                if state.is_private() {
+                    tracing::debug!(remote_id = %remote_id, "refusing inbound connection, running as private peer");
+                    conn.close(CloseReason::Unauthorized);
+                    continue;
+                }
                 state
                     .spawner
-                    .spawn(streams::incoming(state.clone(), streams))
+                    .spawn_cancellable(&state.cancel, streams::incoming(state.clone(), streams))
                     .detach();
             },
             Err(err) => match err {
@@ -83,6 +96,7 @@ pub async fn connect<'a, Addrs>(
     endpoint: &Endpoint,
     remote_id: PeerId,
     addrs: Addrs,
+    family: dial::Family,
 ) -> Option<(
     quic::Connection,
     quic::IncomingStreams<
@@ -107,11 +121,17 @@ where
     }
 
     let addrs = addrs.into_iter().filter(routable).collect::<IndexSet<_>>();
-    if addrs.is_empty() {
-        tracing::debug!("no routable addrs");
-        None
-    } else {
-        future::select_ok(addrs.iter().map(|addr| {
+    let (preferred, fallback) = family.partition(addrs);
+    if preferred.is_empty() && fallback.is_empty() {
+        tracing::debug!(%family, "no routable addrs");
+        return None;
+    }
+
+    for addrs in [preferred, fallback] {
+        if addrs.is_empty() {
+            continue;
+        }
+        let conn = future::select_ok(addrs.iter().map(|addr| {
             let mut endpoint = endpoint.clone();
             tracing::info!(remote_addr = %addr, "establishing connection");
             Box::pin(async move {
@@ -124,8 +144,11 @@ where
                     .await
             })
         }))
-        .await
-        .ok()
-        .map(|(success, _pending)| success)
+        .await;
+        if let Ok((success, _pending)) = conn {
+            return Some(success);
+        }
     }
+
+    None
 }
@@ -3,17 +3,29 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod attachments;
+pub(in crate::net::protocol) use attachments::attachments;
+
 mod git;
 pub(in crate::net::protocol) use git::git;
 
 mod gossip;
-pub(in crate::net::protocol) use gossip::gossip;
+pub(in crate::net::protocol) use gossip::{apply_gossip, gossip};
 
 pub(in crate::net::protocol) mod interrogation;
 pub(in crate::net::protocol) use interrogation::interrogation;
 
+mod inventory;
+pub(in crate::net::protocol) use inventory::inventory;
+
+mod ping;
+pub(in crate::net::protocol) use ping::ping;
+
+mod lfs;
+pub(in crate::net::protocol) use lfs::lfs;
+
 mod membership;
-pub(in crate::net::protocol) use membership::{connection_lost, membership};
+pub(in crate::net::protocol) use membership::{apply_membership, connection_lost, membership};
 
 pub(in crate::net::protocol) mod request_pull;
 pub(in crate::net::protocol) use request_pull::request_pull;
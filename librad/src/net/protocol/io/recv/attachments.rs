@@ -0,0 +1,107 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::net::SocketAddr;
+
+use futures::{
+    io::{AsyncRead, AsyncWrite, BufReader, BufWriter, IntoSink},
+    SinkExt as _,
+    StreamExt as _,
+};
+use futures_codec::FramedRead;
+
+use crate::net::{
+    connection::Duplex,
+    protocol::{
+        attachments::{self, Error, Request, Response},
+        gossip,
+        io::codec,
+        ProtocolStorage,
+        State,
+    },
+    upgrade::{self, Upgraded},
+};
+
+pub(in crate::net::protocol) async fn attachments<S, G, T>(
+    state: State<S, G>,
+    stream: Upgraded<upgrade::Attachments, T>,
+) where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    T: Duplex<Addr = SocketAddr>,
+    T::Read: AsyncRead + Unpin,
+    T::Write: AsyncWrite + Unpin,
+{
+    let remote_id = stream.remote_peer_id();
+    let (recv, send) = stream.into_stream().split();
+    let recv = BufReader::with_capacity(attachments::FRAMED_BUFSIZ, recv);
+    let send = BufWriter::with_capacity(attachments::FRAMED_BUFSIZ, send);
+    let mut sink = send.into_sink();
+
+    let mut recv = FramedRead::new(recv, codec::Codec::<Request>::new());
+    if let Some(x) = recv.next().await {
+        match x {
+            Err(e) => tracing::warn!(err = ?e, "attachments recv error"),
+            Ok(Request { oid }) => {
+                if state.limits.attachments.check_key(&remote_id).is_err() {
+                    tracing::debug!(remote_id = %remote_id, %oid, "attachment quota exceeded");
+                    send_response(&mut sink, &Response::Error(Error::RateLimited)).await;
+                    return;
+                }
+
+                let store = state.attachments.clone();
+                let size = {
+                    let path = store.path(&oid);
+                    state
+                        .spawner
+                        .blocking(move || std::fs::metadata(path).map(|meta| meta.len()))
+                        .await
+                };
+                match size {
+                    Err(_) => {
+                        send_response(&mut sink, &Response::Error(Error::NotFound)).await;
+                        return;
+                    },
+                    Ok(size) if size > store.config().max_size => {
+                        send_response(&mut sink, &Response::Error(Error::TooLarge)).await;
+                        return;
+                    },
+                    Ok(_) => {},
+                }
+
+                let content = {
+                    let store = store.clone();
+                    state.spawner.blocking(move || store.read(&oid)).await
+                };
+                match content {
+                    Err(e) => {
+                        tracing::error!(err = ?e, %oid, "error reading attachment");
+                        send_response(&mut sink, &Response::Error(Error::Internal)).await;
+                    },
+                    Ok(content) => {
+                        for chunk in content.chunks(attachments::CHUNK_SIZE) {
+                            if !send_response(&mut sink, &Response::Chunk(chunk.to_vec())).await {
+                                break;
+                            }
+                        }
+                    },
+                }
+            },
+        }
+    }
+}
+
+async fn send_response<W>(sink: &mut IntoSink<W, Vec<u8>>, resp: &Response) -> bool
+where
+    W: AsyncWrite + Unpin,
+{
+    let buf = minicbor::to_vec(resp).expect("encoding to a vec is infallible");
+    match sink.send(buf).await {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!(err = ?e, "attachments send error");
+            false
+        },
+    }
+}
@@ -4,17 +4,29 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{io, process::ExitStatus};
+use std::{
+    io,
+    pin::Pin,
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
 
 use futures::io::{AsyncRead, AsyncWrite};
-use link_git::protocol::upload_pack::{upload_pack, Header};
+use link_git::protocol::upload_pack::{upload_pack_guarded, Header};
 use thiserror::Error;
 use tracing::{error, info};
 
-use crate::net::{
-    connection::Duplex,
-    protocol::State,
-    upgrade::{self, Upgraded},
+use crate::{
+    git::{storage::requirements, Urn},
+    net::{
+        connection::Duplex,
+        protocol::{usage, State},
+        upgrade::{self, Upgraded},
+    },
 };
 
 #[derive(Debug, Error)]
@@ -45,13 +57,37 @@ where
     T::Read: AsyncRead + Unpin,
     T::Write: AsyncWrite + Unpin,
 {
+    let remote_id = stream.remote_peer_id();
     let (recv, send) = stream.into_stream().split();
     let git_dir = state.config.paths.git_dir();
+    let fetch = Arc::new(AtomicBool::new(false));
+    let bytes = Arc::new(AtomicU64::new(0));
+    let recv = Sniff::new(recv, fetch.clone());
+    let send = Counting::new(send, bytes.clone());
 
-    let (Header { path, host, extra }, run) = upload_pack(git_dir, recv, send).await?;
+    // Refuse to serve namespaces this version can't make sense of, so the
+    // client gets to know why instead of receiving a broken pack
+    let guard = |namespace: &str| match Urn::try_from_id(namespace) {
+        Err(_) => Ok(()),
+        Ok(urn) => requirements::ensure_compatible(git_dir, &urn).map_err(|e| e.to_string()),
+    };
+    let (Header { path, host, extra }, run) =
+        upload_pack_guarded(git_dir, state.config.ls_refs, guard, recv, send).await?;
     info!(%path, ?host, ?extra, "upload-pack");
 
     let status = run.await?;
+    // legacy clients redundantly send a full URN
+    if let Ok(urn) = Urn::try_from_id(path.strip_prefix("rad:git:").unwrap_or(&path)) {
+        let served = usage::Served {
+            fetch: fetch.load(Ordering::Relaxed) && status.success(),
+            bytes: bytes.load(Ordering::Relaxed),
+        };
+        let store = state.usage.clone();
+        state
+            .spawner
+            .blocking(move || store.record(&urn, remote_id, served))
+            .await;
+    }
     // XXX: #![feature(exit_status_error)] ?
     // https://github.com/rust-lang/rust/issues/84908
     if !status.success() {
@@ -60,3 +96,90 @@ where
 
     Ok(())
 }
+
+/// The number of bytes read from the client within which a request for objects
+/// is expected, if any.
+const SNIFF_LIMIT: usize = 4096;
+
+/// Reader which notes whether the client asks for objects, as opposed to only
+/// listing refs.
+struct Sniff<R> {
+    inner: R,
+    /// What was read so far, until it is known whether objects are asked for.
+    seen: Option<Vec<u8>>,
+    fetch: Arc<AtomicBool>,
+}
+
+impl<R> Sniff<R> {
+    fn new(inner: R, fetch: Arc<AtomicBool>) -> Self {
+        Self {
+            inner,
+            seen: Some(Vec::new()),
+            fetch,
+        }
+    }
+
+    fn sniff(&mut self, buf: &[u8]) {
+        if let Some(seen) = self.seen.as_mut() {
+            let take = buf.len().min(SNIFF_LIMIT - seen.len());
+            seen.extend_from_slice(&buf[..take]);
+            // protocol v2 issues a `fetch` command, v0 and v1 go straight to
+            // the `want` lines
+            let contains = |needle: &[u8]| seen.windows(needle.len()).any(|w| w == needle);
+            if contains(b"command=fetch") || contains(b"want ") {
+                self.fetch.store(true, Ordering::Relaxed);
+                self.seen = None;
+            } else if seen.len() >= SNIFF_LIMIT {
+                self.seen = None;
+            }
+        }
+    }
+}
+
+impl<R> AsyncRead for Sniff<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let read = futures::ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.sniff(&buf[..read]);
+        Poll::Ready(Ok(read))
+    }
+}
+
+/// Writer which counts the bytes sent to the client.
+struct Counting<W> {
+    inner: W,
+    bytes: Arc<AtomicU64>,
+}
+
+impl<W> Counting<W> {
+    fn new(inner: W, bytes: Arc<AtomicU64>) -> Self {
+        Self { inner, bytes }
+    }
+}
+
+impl<W> AsyncWrite for Counting<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let written = futures::ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        this.bytes.fetch_add(written as u64, Ordering::Relaxed);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
@@ -13,12 +13,13 @@ use futures_codec::FramedRead;
 
 use crate::{
     net::{
-        connection::RemotePeer,
+        connection::RemoteInfo,
         protocol::{
             broadcast,
+            capture,
             gossip,
             info::PeerInfo,
-            io::{codec, peer_advertisement},
+            io::codec,
             membership,
             ProtocolStorage,
             RequestPullGuard,
@@ -35,16 +36,17 @@ pub(in crate::net::protocol) async fn gossip<S, G, T>(
 ) where
     S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
     G: RequestPullGuard,
-    T: RemotePeer + AsyncRead + Unpin,
+    T: RemoteInfo<Addr = SocketAddr> + AsyncRead + Unpin,
 {
     let remote_id = stream.remote_peer_id();
+    let remote_addr = stream.remote_addr();
 
     let mut recv = FramedRead::new(
         BufReader::with_capacity(100, stream.into_stream()),
         codec::Gossip::new(),
     );
 
-    while let Some(x) = recv.next().await {
+    'recv: while let Some(x) = recv.next().await {
         match x {
             Err(e) => {
                 tracing::warn!(err = ?e, "gossip recv error");
@@ -53,7 +55,7 @@ pub(in crate::net::protocol) async fn gossip<S, G, T>(
                 state
                     .tick(membership::tocks(
                         &state.membership,
-                        peer_advertisement(&state.endpoint),
+                        state.peer_advertisement(),
                         ticks,
                     ))
                     .await;
@@ -61,46 +63,87 @@ pub(in crate::net::protocol) async fn gossip<S, G, T>(
                 break;
             },
 
-            Ok(msg) => {
-                let peer_info = || PeerInfo {
-                    peer_id: state.local_id,
-                    advertised_info: peer_advertisement(&state.endpoint)(),
-                    seen_addrs: iter::empty().into(),
-                };
-                match state
-                    .gossip
-                    .apply(&state.membership, peer_info, remote_id, msg)
-                    .await
-                {
-                    // Partial view states diverge apparently, and the stream is
-                    // (assumed to be) unidirectional. Thus, send a DISCONNECT
-                    // to sync states.
-                    Err(broadcast::Error::Unsolicited { remote_id, .. }) => {
-                        tracing::warn!(
-                            remote_id = %remote_id,
-                            "unsolicited broadcast message, sending disconnect"
-                        );
-                        state
-                            .tick(membership::tocks(
-                                &state.membership,
-                                peer_advertisement(&state.endpoint),
-                                Some(disconnect(remote_id)),
-                            ))
-                            .await;
-
-                        break;
-                    },
-
-                    Ok((may_event, tocks)) => {
-                        state.emit(may_event);
-                        state.tick(tocks).await;
+            Ok(frame) => {
+                let frame = match frame.decompress(&state.compression) {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        tracing::warn!(err = ?e, "dropping undecodable compressed gossip");
+                        continue;
                     },
+                };
+                if let codec::GossipFrame::Interest(filter) = frame {
+                    tracing::trace!("interests declared");
+                    state.interests.declare(remote_id, filter);
+                    continue;
+                }
+                if let codec::GossipFrame::Backoff(signal) = frame {
+                    tracing::debug!(retry_after = signal.retry_after, "neighbour overloaded");
+                    state.backoffs.record(remote_id, signal);
+                    continue;
+                }
+                for msg in frame.into_messages() {
+                    if let Some(recorder) = &state.capture {
+                        recorder.record(remote_id, remote_addr, capture::Event::Gossip(msg.clone()))
+                    }
+                    if !apply_gossip(&state, remote_id, msg).await {
+                        break 'recv;
+                    }
                 }
             },
         }
     }
 }
 
+/// Apply a single gossip message received from `remote_id`.
+///
+/// Returns `false` if the stream the message was received on should be
+/// closed.
+pub(in crate::net::protocol) async fn apply_gossip<S, G>(
+    state: &State<S, G>,
+    remote_id: PeerId,
+    msg: broadcast::Message<SocketAddr, gossip::Payload>,
+) -> bool
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+    G: RequestPullGuard,
+{
+    let peer_info = || PeerInfo {
+        peer_id: state.local_id,
+        advertised_info: state.peer_advertisement()(),
+        seen_addrs: iter::empty().into(),
+    };
+    match state
+        .gossip
+        .apply(&state.membership, peer_info, remote_id, msg)
+        .await
+    {
+        // Partial view states diverge apparently, and the stream is
+        // (assumed to be) unidirectional. Thus, send a DISCONNECT
+        // to sync states.
+        Err(broadcast::Error::Unsolicited { remote_id, .. }) => {
+            tracing::warn!(
+                remote_id = %remote_id,
+                "unsolicited broadcast message, sending disconnect"
+            );
+            state
+                .tick(membership::tocks(
+                    &state.membership,
+                    state.peer_advertisement(),
+                    Some(disconnect(remote_id)),
+                ))
+                .await;
+
+            false
+        },
+
+        Ok((may_event, tocks)) => {
+            state.emit(may_event);
+            state.tick(tocks).await;
+            true
+        },
+    }
+}
+
 fn disconnect<A>(remote_id: PeerId) -> membership::Tick<A> {
     membership::Tick::Reply {
         to: remote_id,
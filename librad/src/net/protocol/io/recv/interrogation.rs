@@ -20,8 +20,10 @@ use crate::{
         protocol::{
             cache,
             interrogation::{self, Request, Response},
-            io::{self, codec},
-            Endpoint,
+            io::codec,
+            rotation::Rotation,
+            skew,
+            PeerAdvertisement,
             State,
         },
         upgrade::{self, Upgraded},
@@ -59,14 +61,20 @@ pub(in crate::net::protocol) async fn interrogation<S, G, T>(
         match x {
             Err(e) => tracing::warn!(err = ?e, "interrogation recv error"),
             Ok(req) => {
-                let resp = handle_request(&state.endpoint, &state.caches.urns, remote_addr, req)
-                    .map(Cow::from)
-                    .unwrap_or_else(|e| {
-                        tracing::error!(err = ?e, "error handling request");
-                        match e {
-                            Error::Cbor(_) => Cow::from(&*INTERNAL_ERROR),
-                        }
-                    });
+                let resp = handle_request(
+                    state.peer_advertisement(),
+                    &state.caches.urns,
+                    state.config.rotation.as_ref(),
+                    remote_addr,
+                    req,
+                )
+                .map(Cow::from)
+                .unwrap_or_else(|e| {
+                    tracing::error!(err = ?e, "error handling request");
+                    match e {
+                        Error::Cbor(_) => Cow::from(&*INTERNAL_ERROR),
+                    }
+                });
 
                 if let Err(e) = send.into_sink().send(resp).await {
                     tracing::warn!(err = ?e, "interrogation send error")
@@ -77,18 +85,19 @@ pub(in crate::net::protocol) async fn interrogation<S, G, T>(
 }
 
 fn handle_request(
-    endpoint: &Endpoint,
+    advertisement: impl Fn() -> PeerAdvertisement<SocketAddr>,
     urns: &cache::urns::Filter,
+    rotation: Option<&Rotation>,
     remote_addr: SocketAddr,
     req: interrogation::Request,
 ) -> Result<Vec<u8>, Error> {
     use either::Either::*;
 
     match req {
-        Request::GetAdvertisement => {
-            Left(Response::Advertisement(io::peer_advertisement(endpoint)()))
-        },
+        Request::GetAdvertisement => Left(Response::Advertisement(advertisement())),
         Request::EchoAddr => Left(Response::YourAddr(remote_addr)),
+        Request::GetTime => Left(Response::Time(skew::now_ms())),
+        Request::GetRotation => Left(Response::Rotation(rotation.cloned())),
         Request::GetUrns => {
             let urns = urns.get();
             Right(encode(&Response::<SocketAddr>::Urns(Cow::Borrowed(&*urns))))
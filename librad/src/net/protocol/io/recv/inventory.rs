@@ -0,0 +1,81 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::net::SocketAddr;
+
+use futures::{
+    io::{AsyncRead, AsyncWrite, BufReader, BufWriter},
+    SinkExt as _,
+    StreamExt as _,
+};
+use futures_codec::FramedRead;
+
+use crate::net::{
+    connection::Duplex,
+    protocol::{
+        gossip,
+        interrogation,
+        inventory::{self, Response},
+        io::codec,
+        ProtocolStorage,
+        State,
+    },
+    upgrade::{self, Upgraded},
+};
+
+lazy_static! {
+    static ref INTERNAL_ERROR: Vec<u8> =
+        minicbor::to_vec(&Response::Error(interrogation::Error::Internal)).unwrap();
+    static ref RATE_LIMITED: Vec<u8> = minicbor::to_vec(&Response::Error(
+        interrogation::Error::TemporarilyUnavailable
+    ))
+    .unwrap();
+}
+
+pub(in crate::net::protocol) async fn inventory<S, G, T>(
+    state: State<S, G>,
+    stream: Upgraded<upgrade::Inventory, T>,
+) where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    T: Duplex<Addr = SocketAddr>,
+    T::Read: AsyncRead + Unpin,
+    T::Write: AsyncWrite + Unpin,
+{
+    let remote_id = stream.remote_peer_id();
+    let (recv, send) = stream.into_stream().split();
+    let recv = BufReader::with_capacity(inventory::FRAMED_BUFSIZ, recv);
+    let send = BufWriter::with_capacity(inventory::FRAMED_BUFSIZ, send);
+
+    let mut recv = FramedRead::new(recv, codec::Codec::<inventory::Request>::new());
+    if let Some(x) = recv.next().await {
+        match x {
+            Err(e) => tracing::warn!(err = ?e, "inventory recv error"),
+            Ok(req) => {
+                if state.limits.inventory.check_key(&remote_id).is_err() {
+                    tracing::debug!(remote_id = %remote_id, "inventory quota exceeded");
+                    if let Err(e) = send.into_sink().send(RATE_LIMITED.clone()).await {
+                        tracing::warn!(err = ?e, "inventory send error")
+                    }
+                    return;
+                }
+
+                let resp = match state.inventory.page(&state.spawner, req).await {
+                    Ok(page) => minicbor::to_vec(&Response::Page(page)).unwrap_or_else(|e| {
+                        tracing::error!(err = ?e, "error encoding response");
+                        INTERNAL_ERROR.clone()
+                    }),
+                    Err(e) => {
+                        tracing::error!(err = ?e, "error handling request");
+                        INTERNAL_ERROR.clone()
+                    },
+                };
+
+                if let Err(e) = send.into_sink().send(resp).await {
+                    tracing::warn!(err = ?e, "inventory send error")
+                }
+            },
+        }
+    }
+}
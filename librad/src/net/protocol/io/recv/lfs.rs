@@ -0,0 +1,107 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::net::SocketAddr;
+
+use futures::{
+    io::{AsyncRead, AsyncWrite, BufReader, BufWriter, IntoSink},
+    SinkExt as _,
+    StreamExt as _,
+};
+use futures_codec::FramedRead;
+
+use crate::net::{
+    connection::Duplex,
+    protocol::{
+        gossip,
+        io::codec,
+        lfs::{self, Error, Request, Response},
+        ProtocolStorage,
+        State,
+    },
+    upgrade::{self, Upgraded},
+};
+
+pub(in crate::net::protocol) async fn lfs<S, G, T>(
+    state: State<S, G>,
+    stream: Upgraded<upgrade::Lfs, T>,
+) where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    T: Duplex<Addr = SocketAddr>,
+    T::Read: AsyncRead + Unpin,
+    T::Write: AsyncWrite + Unpin,
+{
+    let (recv, send) = stream.into_stream().split();
+    let recv = BufReader::with_capacity(lfs::FRAMED_BUFSIZ, recv);
+    let send = BufWriter::with_capacity(lfs::FRAMED_BUFSIZ, send);
+    let mut sink = send.into_sink();
+
+    let mut recv = FramedRead::new(recv, codec::Codec::<Request>::new());
+    if let Some(x) = recv.next().await {
+        match x {
+            Err(e) => tracing::warn!(err = ?e, "lfs recv error"),
+            Ok(Request { oid, offset }) => {
+                let store = state.lfs.clone();
+                let size = {
+                    let path = store.path(&oid);
+                    state
+                        .spawner
+                        .blocking(move || std::fs::metadata(path).map(|meta| meta.len()))
+                        .await
+                };
+                let resp = match size {
+                    Err(_) => Some(Response::Error(Error::NotFound)),
+                    Ok(size) if size > store.config().max_size => {
+                        Some(Response::Error(Error::TooLarge))
+                    },
+                    Ok(_) => None,
+                };
+                if let Some(resp) = resp {
+                    send_response(&mut sink, &resp).await;
+                    return;
+                }
+
+                let mut offset = offset;
+                loop {
+                    let chunk = {
+                        let store = store.clone();
+                        state
+                            .spawner
+                            .blocking(move || store.read_chunk(&oid, offset))
+                            .await
+                    };
+                    match chunk {
+                        Err(e) => {
+                            tracing::error!(err = ?e, %oid, "error reading large file");
+                            send_response(&mut sink, &Response::Error(Error::Internal)).await;
+                            break;
+                        },
+                        Ok(chunk) if chunk.is_empty() => break,
+                        Ok(chunk) => {
+                            offset += chunk.len() as u64;
+                            if !send_response(&mut sink, &Response::Chunk(chunk)).await {
+                                break;
+                            }
+                        },
+                    }
+                }
+            },
+        }
+    }
+}
+
+async fn send_response<W>(sink: &mut IntoSink<W, Vec<u8>>, resp: &Response) -> bool
+where
+    W: AsyncWrite + Unpin,
+{
+    let buf = minicbor::to_vec(resp).expect("encoding to a vec is infallible");
+    match sink.send(buf).await {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::warn!(err = ?e, "lfs send error");
+            false
+        },
+    }
+}
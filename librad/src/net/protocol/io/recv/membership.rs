@@ -13,16 +13,9 @@ use futures_codec::FramedRead;
 
 use crate::{
     net::{
-        connection::RemoteInfo,
+        connection::{CloseReason, RemoteInfo},
         peer::RequestPullGuard,
-        protocol::{
-            gossip,
-            io::{codec, peer_advertisement},
-            membership,
-            tick,
-            ProtocolStorage,
-            State,
-        },
+        protocol::{capture, gossip, io::codec, membership, tick, ProtocolStorage, State},
         upgrade::{self, Upgraded},
     },
     PeerId,
@@ -58,49 +51,92 @@ pub(in crate::net::protocol) async fn membership<S, G, T>(
                 break;
             },
 
-            Ok(msg) => {
+            Ok(frame) => {
+                let msg = match frame.decompress(&state.compression) {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        tracing::warn!(err = ?e, "membership decompression error");
+                        self::connection_lost(state, remote_id).await;
+                        break;
+                    },
+                };
                 if state.limits.membership.check_key(&remote_id).is_err() {
                     tracing::warn!(remote_id = %remote_id, "rate limit breached, disconnecting peer");
-
+                    state.limits.offenders.record(remote_id);
                     let disconnect = membership::tocks(
                         &state.membership,
-                        peer_advertisement(&state.endpoint),
+                        state.peer_advertisement(),
                         Some(membership::Tick::Reply {
                             to: remote_id,
                             message: membership::Message::Disconnect,
                         }),
-                    )
-                    .into_iter()
-                    // membership flooding is not ok, disconnect hard
-                    .chain(Some(tick::Tock::Disconnect { peer: remote_id }));
+                    );
                     state.tick(disconnect).await;
+                    // membership flooding is not ok, disconnect hard, telling
+                    // the peer why
+                    match state.endpoint.get_connection(remote_id) {
+                        Some(conn) => conn.close(CloseReason::RateLimited),
+                        None => {
+                            state
+                                .tick(Some(tick::Tock::Disconnect { peer: remote_id }))
+                                .await
+                        },
+                    }
                     self::connection_lost(state, remote_id).await;
 
                     break;
                 }
 
-                match membership::apply(
-                    &state.membership,
-                    peer_advertisement(&state.endpoint),
-                    remote_id,
-                    remote_addr,
-                    msg,
-                ) {
-                    Err(e) => {
-                        tracing::warn!(err = ?e, "membership error");
-                        break;
-                    },
-
-                    Ok((trans, tocks)) => {
-                        state.emit(trans);
-                        state.tick(tocks).await
-                    },
+                if let Some(recorder) = &state.capture {
+                    recorder.record(
+                        remote_id,
+                        remote_addr,
+                        capture::Event::Membership(msg.clone()),
+                    )
+                }
+                if !apply_membership(&state, remote_id, remote_addr, msg).await {
+                    break;
                 }
             },
         }
     }
 }
 
+/// Apply a single membership message received from `remote_id` at
+/// `remote_addr`.
+///
+/// Returns `false` if the stream the message was received on should be
+/// closed.
+pub(in crate::net::protocol) async fn apply_membership<S, G>(
+    state: &State<S, G>,
+    remote_id: PeerId,
+    remote_addr: SocketAddr,
+    msg: membership::Message<SocketAddr>,
+) -> bool
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+    G: RequestPullGuard,
+{
+    match membership::apply(
+        &state.membership,
+        state.peer_advertisement(),
+        remote_id,
+        remote_addr,
+        msg,
+    ) {
+        Err(e) => {
+            tracing::warn!(err = ?e, "membership error");
+            false
+        },
+
+        Ok((trans, tocks)) => {
+            state.emit(trans);
+            state.tick(tocks).await;
+            true
+        },
+    }
+}
+
 pub(in crate::net::protocol) async fn connection_lost<S, G>(state: State<S, G>, remote_id: PeerId)
 where
     S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
@@ -111,7 +147,7 @@ where
     state
         .tick(membership::tocks(
             &state.membership,
-            peer_advertisement(&state.endpoint),
+            state.peer_advertisement(),
             ticks,
         ))
         .await
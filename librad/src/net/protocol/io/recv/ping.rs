@@ -0,0 +1,47 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::net::SocketAddr;
+
+use futures::{
+    io::{AsyncRead, AsyncWrite, BufReader, BufWriter},
+    SinkExt as _,
+    StreamExt as _,
+};
+use futures_codec::FramedRead;
+
+use crate::net::{
+    connection::Duplex,
+    protocol::{
+        io::codec,
+        ping::{self, Response},
+    },
+    upgrade::{self, Upgraded},
+};
+
+pub(in crate::net::protocol) async fn ping<T>(stream: Upgraded<upgrade::Ping, T>)
+where
+    T: Duplex<Addr = SocketAddr>,
+    T::Read: AsyncRead + Unpin,
+    T::Write: AsyncWrite + Unpin,
+{
+    let (recv, send) = stream.into_stream().split();
+    let recv = BufReader::with_capacity(ping::FRAMED_BUFSIZ, recv);
+    let send = BufWriter::with_capacity(ping::FRAMED_BUFSIZ, send);
+
+    let mut recv = FramedRead::new(recv, codec::Codec::<ping::Request>::new());
+    if let Some(x) = recv.next().await {
+        match x {
+            Err(e) => tracing::warn!(err = ?e, "ping recv error"),
+            Ok(req) => {
+                let resp = minicbor::to_vec(&Response { nonce: req.nonce })
+                    .expect("encoding to a vec is infallible");
+                if let Err(e) = send.into_sink().send(resp).await {
+                    tracing::warn!(err = ?e, "ping send error")
+                }
+            },
+        }
+    }
+}
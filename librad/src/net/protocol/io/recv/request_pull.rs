@@ -124,6 +124,10 @@ where
     G: protocol::RequestPullGuard,
     W: AsyncWrite + Unpin,
 {
+    if let Some(reason) = state.health.reason() {
+        return error::read_only(reason).into();
+    }
+
     report.progress(progress::authorizing(&urn)).await;
     match state.request_pull.guard(&peer, &urn) {
         Ok(guard) => report.progress(progress::guard(guard)).await,
@@ -141,7 +145,10 @@ where
             gossip(&state, peer, &urn, tips).await;
             success.into()
         },
-        Err(err) => error::replication_error(err).into(),
+        Err(err) => {
+            state.health.observe(&err);
+            error::replication_error(err).into()
+        },
     }
 }
 
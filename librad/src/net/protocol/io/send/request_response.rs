@@ -11,7 +11,17 @@ use futures_codec::Framed;
 use crate::net::{
     codec::CborCodec,
     connection::{RemoteAddr as _, RemotePeer as _},
-    protocol::{error, interrogation, quic, request_pull, upgrade},
+    protocol::{
+        attachments,
+        error,
+        interrogation,
+        inventory,
+        lfs,
+        ping,
+        quic,
+        request_pull,
+        upgrade,
+    },
 };
 
 pub trait Request {
@@ -20,12 +30,36 @@ pub trait Request {
     const UPGRADE: Self::Upgrade;
 }
 
+impl Request for attachments::Request {
+    type Response = attachments::Response;
+    type Upgrade = upgrade::Attachments;
+    const UPGRADE: Self::Upgrade = upgrade::Attachments;
+}
+
 impl Request for interrogation::Request {
     type Response = interrogation::Response<'static, SocketAddr>;
     type Upgrade = upgrade::Interrogation;
     const UPGRADE: Self::Upgrade = upgrade::Interrogation;
 }
 
+impl Request for inventory::Request {
+    type Response = inventory::Response;
+    type Upgrade = upgrade::Inventory;
+    const UPGRADE: Self::Upgrade = upgrade::Inventory;
+}
+
+impl Request for lfs::Request {
+    type Response = lfs::Response;
+    type Upgrade = upgrade::Lfs;
+    const UPGRADE: Self::Upgrade = upgrade::Lfs;
+}
+
+impl Request for ping::Request {
+    type Response = ping::Response;
+    type Upgrade = upgrade::Ping;
+    const UPGRADE: Self::Upgrade = upgrade::Ping;
+}
+
 impl Request for request_pull::Request {
     type Response = request_pull::Response;
     type Upgrade = upgrade::RequestPull;
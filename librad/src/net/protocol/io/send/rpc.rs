@@ -8,17 +8,35 @@ use std::{net::SocketAddr, ops::DerefMut as _};
 use futures::{SinkExt as _, TryFutureExt as _};
 use futures_codec::FramedWrite;
 
-use crate::net::{
-    connection::{RemoteAddr as _, RemotePeer},
-    protocol::{broadcast, error, io::codec, membership},
-    quic,
-    upgrade,
+use crate::{
+    identities::Xor,
+    net::{
+        connection::{RemoteAddr as _, RemotePeer},
+        protocol::{backoff, broadcast, compress, error, io::codec, membership},
+        quic,
+        upgrade,
+    },
 };
 
 #[derive(Debug)]
 pub enum Rpc<A, P> {
     Membership(membership::Message<A>),
     Gossip(broadcast::Message<A, P>),
+    /// Several gossip messages sent as a single frame. Must only be sent to
+    /// peers which advertised [`Capability::GossipBatch`].
+    ///
+    /// [`Capability::GossipBatch`]: crate::net::protocol::Capability::GossipBatch
+    GossipBatch(Vec<broadcast::Message<A, P>>),
+    /// Declaration of the URNs the local peer is interested in. Must only be
+    /// sent to peers which advertised [`Capability::GossipInterest`].
+    ///
+    /// [`Capability::GossipInterest`]: crate::net::protocol::Capability::GossipInterest
+    Interest(Xor),
+    /// Signal that the local peer is too busy to act upon gossip. Must only
+    /// be sent to peers which advertised [`Capability::GossipBackoff`].
+    ///
+    /// [`Capability::GossipBackoff`]: crate::net::protocol::Capability::GossipBackoff
+    Backoff(backoff::Signal),
 }
 
 impl<A, P> From<membership::Message<A>> for Rpc<A, P> {
@@ -35,7 +53,7 @@ impl<A, P> From<broadcast::Message<A, P>> for Rpc<A, P> {
 
 #[allow(clippy::unit_arg)]
 #[tracing::instrument(
-    skip(conn, rpc),
+    skip(conn, compression, rpc),
     fields(
         remote_id = %conn.remote_peer_id(),
         remote_addr = %conn.remote_addr()
@@ -44,6 +62,7 @@ impl<A, P> From<broadcast::Message<A, P>> for Rpc<A, P> {
 )]
 pub async fn send_rpc<R, P>(
     conn: &quic::Connection,
+    compression: Option<&compress::Compression>,
     rpc: R,
 ) -> Result<(), error::Rpc<quic::SendStream>>
 where
@@ -82,21 +101,71 @@ where
                 .await
                 .map_err(into_protocol_error)?;
             FramedWrite::new(stream.deref_mut(), codec::Membership::new())
-                .send(msg)
+                .send(compressed(compression, msg))
                 .await?;
         },
 
         Gossip(msg) => {
-            let mut stream = conn
-                .borrow_uni(StreamIndex::Gossip, |s| {
-                    upgrade::upgrade(s, upgrade::Gossip).map_ok(|upgraded| upgraded.into_stream())
-                })
-                .await
-                .map_err(into_protocol_error)?;
-            FramedWrite::new(stream.deref_mut(), codec::Gossip::new())
-                .send(msg)
-                .await?;
+            send_gossip(conn, compressed(compression, codec::GossipFrame::One(msg))).await?
+        },
+        GossipBatch(msgs) => {
+            send_gossip(
+                conn,
+                compressed(compression, codec::GossipFrame::Batch(msgs)),
+            )
+            .await?
+        },
+        Interest(filter) => {
+            send_gossip::<P>(
+                conn,
+                compressed(compression, codec::GossipFrame::Interest(filter)),
+            )
+            .await?
         },
+        Backoff(signal) => {
+            send_gossip::<P>(
+                conn,
+                compressed(compression, codec::GossipFrame::Backoff(signal)),
+            )
+            .await?
+        },
+    }
+
+    fn compressed<T>(
+        compression: Option<&compress::Compression>,
+        frame: T,
+    ) -> codec::Compressible<T>
+    where
+        T: minicbor::Encode,
+    {
+        let bytes = compression.and_then(|c| {
+            minicbor::to_vec(&frame)
+                .ok()
+                .and_then(|plain| c.compress(&plain))
+        });
+        match bytes {
+            Some(bytes) => codec::Compressible::Compressed(bytes),
+            None => codec::Compressible::Plain(frame),
+        }
+    }
+
+    async fn send_gossip<P>(
+        conn: &quic::Connection,
+        frame: codec::Compressible<codec::GossipFrame<SocketAddr, P>>,
+    ) -> Result<(), error::Rpc<quic::SendStream>>
+    where
+        P: minicbor::Encode,
+    {
+        let mut stream = conn
+            .borrow_uni(StreamIndex::Gossip, |s| {
+                upgrade::upgrade(s, upgrade::Gossip).map_ok(|upgraded| upgraded.into_stream())
+            })
+            .await
+            .map_err(into_protocol_error)?;
+        FramedWrite::new(stream.deref_mut(), codec::Gossip::new())
+            .send(frame)
+            .await?;
+        Ok(())
     }
 
     Ok(())
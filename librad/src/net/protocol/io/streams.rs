@@ -14,6 +14,7 @@ use crate::net::{
     protocol::{gossip, ProtocolStorage, RequestPullGuard, State},
     quic,
     upgrade,
+    PeerId,
 };
 
 /// Dispatch incoming streams.
@@ -39,6 +40,10 @@ pub(in crate::net::protocol) async fn incoming<S, G, I>(
     use Either::{Left, Right};
 
     let remote_id = streams.remote_peer_id();
+    // Tasks serving the streams of this connection are cancelled when it goes
+    // away, or when the protocol shuts down.
+    let cancel = state.cancel.child();
+    let _cancel_on_exit = cancel.clone().drop_guard();
 
     let streams = streams.fuse();
     futures::pin_mut!(streams);
@@ -54,11 +59,11 @@ pub(in crate::net::protocol) async fn incoming<S, G, I>(
                     Ok(s) => match s {
                         Left(bidi) => state
                             .spawner
-                            .spawn(incoming::bidi(state.clone(), bidi))
+                            .spawn_cancellable(&cancel, incoming::bidi(state.clone(), bidi))
                             .detach(),
                         Right(uni) => state
                             .spawner
-                            .spawn(incoming::uni(state.clone(), uni))
+                            .spawn_cancellable(&cancel, incoming::uni(state.clone(), uni))
                             .detach(),
                     },
                     Err(e) => {
@@ -75,7 +80,10 @@ pub(in crate::net::protocol) async fn incoming<S, G, I>(
 mod incoming {
     use super::*;
 
-    use crate::net::protocol::io::recv;
+    use crate::net::{
+        protocol::{capture, io::recv, mux},
+        upgrade::UpgradeRequest,
+    };
 
     pub(super) async fn bidi<S, G>(state: State<S, G>, stream: quic::BidiStream)
     where
@@ -84,17 +92,34 @@ mod incoming {
     {
         use upgrade::SomeUpgraded::*;
 
-        match upgrade::with_upgraded(stream).await {
+        let remote_id = stream.remote_peer_id();
+        let remote_addr = stream.remote_addr();
+        let upgraded = match upgrade::with_upgraded(stream).await {
             Err(upgrade::Error { stream, source }) => {
                 tracing::warn!(err = ?source, "invalid upgrade");
-                stream.close(CloseReason::InvalidUpgrade)
+                return stream.close(CloseReason::InvalidUpgrade);
             },
-
-            Ok(Git(up)) => recv::git(&state, up).await,
-            Ok(Gossip(up)) => recv::gossip(state, up).await,
-            Ok(Membership(up)) => recv::membership(state, up).await,
-            Ok(Interrogation(up)) => recv::interrogation(state, up).await,
-            Ok(RequestPull(up)) => recv::request_pull(state, up).await,
+            Ok(upgraded) => upgraded,
+        };
+        capture(&state, remote_id, remote_addr, upgraded.request());
+        let _slot = match acquire(&state.streams, remote_id, upgraded.request()) {
+            Some(slot) => slot,
+            None => return upgraded.into_stream().close(CloseReason::StreamLimit),
+        };
+
+        match upgraded {
+            Git(up) => {
+                up.set_class(quic::TrafficClass::Bulk);
+                recv::git(&state, up).await
+            },
+            Gossip(up) => recv::gossip(state, up).await,
+            Membership(up) => recv::membership(state, up).await,
+            Interrogation(up) => recv::interrogation(state, up).await,
+            Inventory(up) => recv::inventory(state, up).await,
+            Ping(up) => recv::ping(up).await,
+            Lfs(up) => recv::lfs(state, up).await,
+            Attachments(up) => recv::attachments(state, up).await,
+            RequestPull(up) => recv::request_pull(state, up).await,
         }
     }
 
@@ -105,18 +130,63 @@ mod incoming {
     {
         use upgrade::SomeUpgraded::*;
 
-        match upgrade::with_upgraded(stream).await {
+        let remote_id = stream.remote_peer_id();
+        let remote_addr = stream.remote_addr();
+        let upgraded = match upgrade::with_upgraded(stream).await {
             Err(upgrade::Error { stream, source }) => {
                 tracing::warn!(err = ?source, "invalid upgrade");
-                stream.close(CloseReason::InvalidUpgrade)
+                return stream.close(CloseReason::InvalidUpgrade);
+            },
+            Ok(upgraded) => upgraded,
+        };
+        capture(&state, remote_id, remote_addr, upgraded.request());
+
+        match upgraded {
+            Git(up) => deny_uni(up.into_stream(), "git"),
+            Interrogation(up) => deny_uni(up.into_stream(), "interrogation"),
+            Inventory(up) => deny_uni(up.into_stream(), "inventory"),
+            Ping(up) => deny_uni(up.into_stream(), "ping"),
+            Lfs(up) => deny_uni(up.into_stream(), "lfs"),
+            Attachments(up) => deny_uni(up.into_stream(), "attachments"),
+            RequestPull(up) => deny_uni(up.into_stream(), "request-pull"),
+
+            Gossip(up) => match acquire(&state.streams, remote_id, UpgradeRequest::Gossip) {
+                Some(_slot) => recv::gossip(state, up).await,
+                None => up.into_stream().close(CloseReason::StreamLimit),
+            },
+            Membership(up) => {
+                match acquire(&state.streams, remote_id, UpgradeRequest::Membership) {
+                    Some(_slot) => recv::membership(state, up).await,
+                    None => up.into_stream().close(CloseReason::StreamLimit),
+                }
             },
+        }
+    }
 
-            Ok(Git(up)) => deny_uni(up.into_stream(), "git"),
-            Ok(Interrogation(up)) => deny_uni(up.into_stream(), "interrogation"),
-            Ok(RequestPull(up)) => deny_uni(up.into_stream(), "request-pull"),
+    fn acquire(
+        streams: &mux::Streams,
+        remote_id: PeerId,
+        kind: UpgradeRequest,
+    ) -> Option<mux::Slot> {
+        let slot = streams.acquire(remote_id, kind);
+        if slot.is_none() {
+            tracing::warn!(
+                ?kind,
+                limit = streams.config().limit(kind),
+                "too many concurrent streams, refusing"
+            );
+        }
+        slot
+    }
 
-            Ok(Gossip(up)) => recv::gossip(state, up).await,
-            Ok(Membership(up)) => recv::membership(state, up).await,
+    fn capture<S, G>(
+        state: &State<S, G>,
+        remote_id: PeerId,
+        remote_addr: SocketAddr,
+        kind: UpgradeRequest,
+    ) {
+        if let Some(recorder) = &state.capture {
+            recorder.record(remote_id, remote_addr, capture::Event::Upgrade(kind))
         }
     }
 
@@ -0,0 +1,211 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Per-peer network quality estimates.
+//!
+//! The protocol periodically samples the round-trip time and packet loss
+//! reported by the QUIC connections to each connected peer, and folds the
+//! samples into exponentially weighted moving averages (cf. [RFC 6298]).
+//!
+//! QUIC reports packet counts since the connection was established, so the
+//! loss ratio folded into the average is the one of the sampling interval, not
+//! of the lifetime of the connection. Recent losses thus weigh as much on a
+//! long-lived connection as on a fresh one.
+//!
+//! The resulting [`Stats`] can be used to prefer well-connected peers when
+//! there is a choice, eg. when selecting a provider to replicate from.
+//!
+//! [RFC 6298]: https://tools.ietf.org/html/rfc6298
+
+use std::{collections::HashMap, hash::BuildHasherDefault, sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use rustc_hash::FxHasher;
+
+use crate::{net::quic, PeerId};
+
+/// Interval at which connections are sampled.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Weight of a new RTT sample.
+const ALPHA: f64 = 0.125;
+/// Weight of a new RTT variation sample.
+const BETA: f64 = 0.25;
+/// Weight of a new loss ratio sample.
+const GAMMA: f64 = 0.25;
+
+/// Snapshot of the [`Stats`] of all connected peers.
+pub type Snapshot = HashMap<PeerId, Stats>;
+
+/// A single measurement of a connection.
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    /// Round-trip time as currently estimated by the QUIC stack.
+    pub rtt: Duration,
+    /// Total number of packets sent on the connection.
+    pub sent_packets: u64,
+    /// Total number of packets deemed lost on the connection.
+    pub lost_packets: u64,
+}
+
+impl From<quic::ConnectionStats> for Sample {
+    fn from(stats: quic::ConnectionStats) -> Self {
+        Self {
+            rtt: stats.rtt,
+            sent_packets: stats.sent_packets,
+            lost_packets: stats.lost_packets,
+        }
+    }
+}
+
+impl Sample {
+    pub fn from_connection(conn: &quic::Connection) -> Self {
+        Self::from(conn.stats())
+    }
+
+    fn loss(&self) -> f64 {
+        if self.sent_packets == 0 {
+            0.0
+        } else {
+            (self.lost_packets as f64 / self.sent_packets as f64).min(1.0)
+        }
+    }
+
+    /// The loss ratio since `prev`, the previous sample of the peer.
+    ///
+    /// If the packet counts went backwards, the connection was replaced since
+    /// `prev`, and the ratio of the new connection is returned.
+    fn loss_since(&self, prev: &Sample) -> f64 {
+        if self.sent_packets < prev.sent_packets || self.lost_packets < prev.lost_packets {
+            return self.loss();
+        }
+        Self {
+            rtt: self.rtt,
+            sent_packets: self.sent_packets - prev.sent_packets,
+            lost_packets: self.lost_packets - prev.lost_packets,
+        }
+        .loss()
+    }
+}
+
+/// Smoothed network quality estimate for a peer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    /// Smoothed round-trip time.
+    pub srtt: Duration,
+    /// Round-trip time variation.
+    pub rttvar: Duration,
+    /// Smoothed ratio of lost to sent packets per sampling interval, in the
+    /// range `[0, 1]`.
+    pub loss: f64,
+    /// Number of samples taken.
+    pub samples: usize,
+}
+
+impl Stats {
+    fn new(sample: &Sample) -> Self {
+        Self {
+            srtt: sample.rtt,
+            rttvar: sample.rtt / 2,
+            loss: sample.loss(),
+            samples: 1,
+        }
+    }
+
+    fn update(&mut self, sample: &Sample, loss: f64) {
+        let srtt = self.srtt.as_secs_f64();
+        let rtt = sample.rtt.as_secs_f64();
+        let rttvar = self.rttvar.as_secs_f64();
+
+        self.rttvar = Duration::from_secs_f64((1.0 - BETA) * rttvar + BETA * (srtt - rtt).abs());
+        self.srtt = Duration::from_secs_f64((1.0 - ALPHA) * srtt + ALPHA * rtt);
+        self.loss = (1.0 - GAMMA) * self.loss + GAMMA * loss;
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    /// A single figure of merit, lower is better.
+    ///
+    /// This is the smoothed RTT plus four times its variation (ie. the
+    /// retransmission timeout as per RFC 6298), inflated by the loss ratio.
+    pub fn score(&self) -> Duration {
+        let rto = self.srtt + self.rttvar * 4;
+        rto.mul_f64(1.0 + 10.0 * self.loss)
+    }
+}
+
+/// Concurrent table of [`Stats`] per peer.
+#[derive(Clone, Default)]
+pub struct Tracker {
+    /// The stats of each peer, along with its last [`Sample`].
+    peers: Arc<DashMap<PeerId, (Stats, Sample), BuildHasherDefault<FxHasher>>>,
+}
+
+impl Tracker {
+    /// Fold a new [`Sample`] into the [`Stats`] for `peer`.
+    pub fn record(&self, peer: PeerId, sample: Sample) -> Stats {
+        use dashmap::mapref::entry::Entry::*;
+
+        match self.peers.entry(peer) {
+            Vacant(entry) => entry.insert((Stats::new(&sample), sample)).0,
+            Occupied(mut entry) => {
+                let (stats, last) = entry.get_mut();
+                stats.update(&sample, sample.loss_since(last));
+                *last = sample;
+                *stats
+            },
+        }
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<Stats> {
+        self.peers.get(peer).map(|entry| entry.0)
+    }
+
+    /// Forget about all peers for which `f` returns `false`.
+    pub fn retain<F>(&self, f: F)
+    where
+        F: Fn(&PeerId) -> bool,
+    {
+        self.peers.retain(|peer, _| f(peer))
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.peers.iter().map(|r| (*r.key(), r.value().0)).collect()
+    }
+
+    /// Order `candidates` by ascending [`Stats::score`].
+    ///
+    /// Candidates for which no [`Stats`] are known are sorted last, retaining
+    /// their relative order.
+    pub fn rank<T, F>(&self, candidates: impl IntoIterator<Item = T>, peer_id: F) -> Vec<T>
+    where
+        F: Fn(&T) -> PeerId,
+    {
+        rank(&self.snapshot(), candidates, peer_id)
+    }
+}
+
+/// Order `candidates` by ascending [`Stats::score`] according to `snapshot`.
+///
+/// See [`Tracker::rank`].
+pub fn rank<T, F>(
+    snapshot: &Snapshot,
+    candidates: impl IntoIterator<Item = T>,
+    peer_id: F,
+) -> Vec<T>
+where
+    F: Fn(&T) -> PeerId,
+{
+    let mut ranked = candidates
+        .into_iter()
+        .map(|c| (snapshot.get(&peer_id(&c)).map(Stats::score), c))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(a, _), (b, _)| match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    ranked.into_iter().map(|(_, c)| c).collect()
+}
@@ -0,0 +1,323 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Replication of large files outside of git packfiles.
+//!
+//! Large binary artifacts can be committed as [git LFS pointer files][spec],
+//! which only record the SHA-256 and size of the content. The content itself
+//! is kept in a content-addressed [`Store`] (see [`cas`]), and transferred on
+//! demand over a dedicated stream, see [`crate::net::upgrade::Lfs`]. This keeps
+//! packfiles small, and allows the size of large files to be limited
+//! independently of the git fetch limits.
+//!
+//! Transfers are resumable: the received content is appended to a partial file
+//! in the store, and a subsequent [`fetch`] requests the remainder starting at
+//! its length. The content is only moved into place once its hash was
+//! verified.
+//!
+//! After replicating a namespace, the content referenced by pointer files on
+//! the branches of the remote peer is fetched from it, see [`wanted`], so
+//! that large files are available along with the refs pointing at them.
+//!
+//! [spec]: https://github.com/git-lfs/git-lfs/blob/main/docs/spec.md
+
+use std::{
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use futures::StreamExt as _;
+use link_async::Spawner;
+
+use super::{cas, io as proto_io, quic};
+use crate::{
+    git::{storage::Storage, Urn},
+    paths::Paths,
+    PeerId,
+};
+
+pub use cas::Oid;
+
+mod rpc;
+pub use rpc::{Error, Request, Response};
+
+/// Size of the chunks the content is transferred in.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Buffer size for reading and writing [`Response`]s, allowing for the CBOR
+/// framing of a [`CHUNK_SIZE`] chunk.
+pub const FRAMED_BUFSIZ: usize = CHUNK_SIZE + 64;
+
+/// Pointer files larger than this are not considered.
+const MAX_POINTER_SIZE: usize = 1024;
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+pub mod error {
+    use thiserror::Error;
+
+    use super::rpc;
+    use crate::{
+        git::storage::read,
+        net::{connection::CloseReason, protocol::error::Rpc, quic},
+    };
+
+    pub use crate::net::protocol::cas::error::Store;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Fetch {
+        #[error("{size} bytes exceed the limit of {max} bytes")]
+        TooLarge { size: u64, max: u64 },
+
+        #[error("received more than the expected {0} bytes")]
+        Overflow(u64),
+
+        #[error("error response: {0:?}")]
+        ErrorResponse(rpc::Error),
+
+        #[error(transparent)]
+        Store(#[from] Store),
+
+        #[error(transparent)]
+        Rpc(#[from] Box<Rpc<quic::BidiStream>>),
+    }
+
+    impl Fetch {
+        /// The [`CloseReason`] the remote end gave for closing the stream or
+        /// connection, if any.
+        pub fn close_reason(&self) -> Option<CloseReason> {
+            match self {
+                Self::Rpc(e) => e.close_reason(),
+                _ => None,
+            }
+        }
+    }
+
+    impl From<Rpc<quic::BidiStream>> for Fetch {
+        fn from(e: Rpc<quic::BidiStream>) -> Self {
+            Self::Rpc(Box::new(e))
+        }
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Wanted {
+        #[error(transparent)]
+        Read(#[from] read::Error),
+
+        #[error(transparent)]
+        Git(#[from] git2::Error),
+    }
+}
+
+/// The content of a pointer file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Pointer {
+    pub oid: Oid,
+    pub size: u64,
+}
+
+impl Pointer {
+    /// Parse the content of a blob as a pointer file.
+    ///
+    /// Returns `None` if the blob is not a pointer file.
+    pub fn parse(blob: &[u8]) -> Option<Self> {
+        if blob.len() > MAX_POINTER_SIZE {
+            return None;
+        }
+        let mut lines = std::str::from_utf8(blob).ok()?.lines();
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+        let (mut oid, mut size) = (None, None);
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("oid", val)) => oid = val.strip_prefix("sha256:")?.parse().ok(),
+                Some(("size", val)) => size = val.parse().ok(),
+                _ => {},
+            }
+        }
+        Some(Self {
+            oid: oid?,
+            size: size?,
+        })
+    }
+
+    /// Find the pointer files in `tree`, recursively.
+    pub fn find(repo: &git2::Repository, tree: &git2::Tree) -> Result<Vec<Self>, git2::Error> {
+        let mut pointers = Vec::new();
+        tree.walk(git2::TreeWalkMode::PreOrder, |_, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                if let Ok(blob) = repo.find_blob(entry.id()) {
+                    if let Some(pointer) = Self::parse(blob.content()) {
+                        pointers.push(pointer)
+                    }
+                }
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+        pointers.sort();
+        pointers.dedup();
+        Ok(pointers)
+    }
+}
+
+impl fmt::Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", POINTER_VERSION)?;
+        writeln!(f, "oid sha256:{}", self.oid)?;
+        writeln!(f, "size {}", self.size)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Large files exceeding this size are neither served nor fetched.
+    pub max_size: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_size: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Storage of large files.
+///
+/// The content is kept in a [`cas::Store`] under `lfs`, relative to the
+/// monorepo.
+#[derive(Clone, Debug)]
+pub struct Store {
+    objects: cas::Store,
+    config: Config,
+}
+
+impl Store {
+    pub fn new(paths: &Paths, config: Config) -> Self {
+        Self {
+            objects: cas::Store::new(paths.git_dir().join("lfs")),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The path of the complete content of `oid`.
+    pub fn path(&self, oid: &Oid) -> PathBuf {
+        self.objects.path(oid)
+    }
+
+    pub fn contains(&self, oid: &Oid) -> bool {
+        self.objects.contains(oid)
+    }
+
+    /// The number of bytes received so far of `oid`.
+    pub fn partial_len(&self, oid: &Oid) -> u64 {
+        self.objects.partial_len(oid)
+    }
+
+    /// Copy the file at `path` into the store, returning the [`Pointer`] to
+    /// commit in its place.
+    pub fn import(&self, path: &Path) -> Result<Pointer, error::Store> {
+        let (oid, size) = self.objects.import(fs::File::open(path)?)?;
+        Ok(Pointer { oid, size })
+    }
+
+    /// Read up to [`CHUNK_SIZE`] bytes of the content of `oid`, starting at
+    /// `offset`. Returns an empty chunk at the end of the content.
+    pub fn read_chunk(&self, oid: &Oid, offset: u64) -> io::Result<Vec<u8>> {
+        self.objects.read_chunk(oid, offset, CHUNK_SIZE)
+    }
+}
+
+/// The pointers on the branches of `peer` in the namespace `urn`, whose
+/// content is missing from the `store`.
+///
+/// Pointers to content exceeding [`Config::max_size`] are skipped, as
+/// [`fetch`] would refuse them anyway.
+pub fn wanted(
+    storage: &Storage,
+    store: &Store,
+    urn: &Urn,
+    peer: PeerId,
+) -> Result<Vec<Pointer>, error::Wanted> {
+    let repo = storage.as_raw();
+    let prefix = format!("refs/remotes/{}/heads/", peer);
+    let mut wanted = Vec::new();
+    for (name, tip) in &storage.read_only().snapshot(urn)? {
+        if !name.as_str().starts_with(&prefix) {
+            continue;
+        }
+        let tree = repo.find_commit(**tip)?.tree()?;
+        wanted.extend(
+            Pointer::find(repo, &tree)?
+                .into_iter()
+                .filter(|p| p.size <= store.config.max_size && !store.contains(&p.oid)),
+        );
+    }
+    wanted.sort();
+    wanted.dedup();
+    Ok(wanted)
+}
+
+/// Fetch the content `pointer` refers to over `conn`, unless it is already in
+/// the `store`.
+///
+/// Returns the path of the content in the `store`.
+pub(crate) async fn fetch(
+    spawner: &Spawner,
+    conn: &quic::Connection,
+    store: &Store,
+    pointer: Pointer,
+) -> Result<PathBuf, error::Fetch> {
+    let max = store.config.max_size;
+    if pointer.size > max {
+        return Err(error::Fetch::TooLarge {
+            size: pointer.size,
+            max,
+        });
+    }
+    if store.contains(&pointer.oid) {
+        return Ok(store.path(&pointer.oid));
+    }
+
+    let mut received = store.partial_len(&pointer.oid);
+    if received < pointer.size {
+        let request = Request {
+            oid: pointer.oid,
+            offset: received,
+        };
+        let resp = proto_io::send::multi_response(conn, request, FRAMED_BUFSIZ).await?;
+        futures::pin_mut!(resp);
+        while let Some(resp) = resp.next().await {
+            match resp? {
+                Response::Error(e) => return Err(error::Fetch::ErrorResponse(e)),
+                Response::Chunk(chunk) => {
+                    received += chunk.len() as u64;
+                    if received > pointer.size {
+                        return Err(error::Fetch::Overflow(pointer.size));
+                    }
+                    let objects = store.objects.clone();
+                    spawner
+                        .blocking(move || objects.append(&pointer.oid, &chunk))
+                        .await
+                        .map_err(error::Store::from)?;
+                },
+            }
+        }
+    }
+
+    let objects = store.objects.clone();
+    Ok(spawner
+        .blocking(move || objects.finish(&pointer.oid))
+        .await?)
+}
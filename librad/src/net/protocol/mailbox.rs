@@ -0,0 +1,431 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Store-and-forward of gossip announcements.
+//!
+//! Peers which announced (or were announced as the origin of) an update for
+//! a URN are assumed to be interested in further updates to that URN. If such
+//! a peer is not connected when an update for the URN is applied locally, the
+//! announcement is kept in a bounded per-peer mailbox, and delivered when the
+//! peer is promoted to the active view again.
+//!
+//! Letters expire after [`Config::ttl`]. Unless [`Config::durable`] is
+//! `false`, the mailboxes are written to [`FILE_NAME`] (relative to the git
+//! directory) every [`EXPIRY_INTERVAL`] if they changed, as well as when they
+//! are dropped, and read back when opened, accounting for the time spent
+//! offline.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use minicbor::{Decode, Encode};
+use parking_lot::Mutex;
+
+use super::{
+    checkpoint::{Interest, Letter as StoredLetter},
+    gossip,
+};
+use crate::{identities::git::Urn, PeerId};
+
+/// The name of the mailbox file, relative to the git directory.
+pub const FILE_NAME: &str = "mailbox";
+
+/// Interval at which expired announcements are discarded, and the mailboxes
+/// are written to disk.
+pub const EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Maximum number of announcements kept per peer.
+    pub capacity: usize,
+    /// Maximum number of peers for which announcements are kept.
+    pub max_peers: usize,
+    /// Maximum number of URNs for which interest is recorded.
+    pub max_urns: usize,
+    /// Maximum number of interested peers recorded per URN.
+    pub max_interested: usize,
+    /// Duration after which an undelivered announcement is discarded.
+    pub ttl: Duration,
+    /// If `false`, the mailboxes are kept in memory only, and do not survive
+    /// restarts.
+    pub durable: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            max_peers: 1024,
+            max_urns: 8192,
+            max_interested: 32,
+            ttl: Duration::from_secs(60 * 60),
+            durable: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    /// Number of peers with pending announcements.
+    pub peers: usize,
+    /// Total number of pending announcements.
+    pub pending: usize,
+}
+
+struct Letter {
+    at: Instant,
+    payload: gossip::Payload,
+}
+
+/// The contents of [`FILE_NAME`].
+#[derive(Encode, Decode)]
+#[cbor(array)]
+struct Stored {
+    /// Seconds since the Unix epoch at which the mailboxes were written.
+    #[n(0)]
+    saved: u64,
+    #[n(1)]
+    interest: Vec<Interest>,
+    #[n(2)]
+    letters: Vec<StoredLetter>,
+}
+
+#[derive(Default)]
+struct Inner {
+    interest: HashMap<Urn, VecDeque<PeerId>>,
+    boxes: HashMap<PeerId, VecDeque<Letter>>,
+    /// Where to write the mailboxes, if they are durable.
+    path: Option<PathBuf>,
+    /// Whether the mailboxes changed since they were last written.
+    dirty: bool,
+}
+
+impl Inner {
+    #[allow(clippy::type_complexity)]
+    fn snapshot(
+        &self,
+        ttl: Duration,
+    ) -> (
+        Vec<(Urn, Vec<PeerId>)>,
+        Vec<(PeerId, Duration, gossip::Payload)>,
+    ) {
+        let interest = self
+            .interest
+            .iter()
+            .map(|(urn, peers)| (urn.clone(), peers.iter().copied().collect()))
+            .collect();
+        let letters = self
+            .boxes
+            .iter()
+            .flat_map(|(peer, letters)| {
+                letters
+                    .iter()
+                    .map(move |l| (*peer, l.at.elapsed(), l.payload.clone()))
+            })
+            .filter(|(_, age, _)| *age < ttl)
+            .collect();
+        (interest, letters)
+    }
+
+    /// Take what to write, if the mailboxes are durable and changed since
+    /// they were last taken.
+    fn take_dirty(&mut self, ttl: Duration) -> Option<(PathBuf, Stored)> {
+        let path = self.path.clone()?;
+        if !self.dirty {
+            return None;
+        }
+        self.dirty = false;
+        let (interest, letters) = self.snapshot(ttl);
+        let stored = Stored {
+            saved: now(),
+            interest: interest
+                .into_iter()
+                .map(|(urn, peers)| Interest { urn, peers })
+                .collect(),
+            letters: letters
+                .into_iter()
+                .map(|(peer, age, payload)| StoredLetter {
+                    peer,
+                    age: age.as_secs(),
+                    payload,
+                })
+                .collect(),
+        };
+        Some((path, stored))
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        // Letters are expired upon restore, so the ttl doesn't matter here
+        if let Some((path, stored)) = self.take_dirty(Duration::MAX) {
+            if let Err(e) = store(&path, &stored) {
+                tracing::warn!(err = %e, "failed to write mailboxes")
+            }
+        }
+    }
+}
+
+/// Bounded, per-peer store of pending [`gossip::Payload`]s.
+#[derive(Clone)]
+pub struct Mailbox {
+    config: Config,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Mailbox {
+    /// Create empty mailboxes, held in memory only.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            inner: Arc::new(Mutex::new(Inner::default())),
+        }
+    }
+
+    /// Open the mailboxes stored in `git_dir`, or create empty ones held in
+    /// memory only if [`Config::durable`] is `false`.
+    pub fn open(config: Config, git_dir: &Path) -> io::Result<Self> {
+        let this = Self::new(config);
+        if !config.durable {
+            return Ok(this);
+        }
+
+        let path = git_dir.join(FILE_NAME);
+        match fs::read(&path) {
+            Ok(bytes) => {
+                let stored = minicbor::decode::<Stored>(&bytes)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let offline = Duration::from_secs(now().saturating_sub(stored.saved));
+                this.restore(
+                    stored
+                        .interest
+                        .into_iter()
+                        .map(|Interest { urn, peers }| (urn, peers)),
+                    stored
+                        .letters
+                        .into_iter()
+                        .map(|StoredLetter { peer, age, payload }| {
+                            (peer, Duration::from_secs(age) + offline, payload)
+                        }),
+                );
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e),
+        }
+        let mut inner = this.inner.lock();
+        inner.path = Some(path);
+        inner.dirty = false;
+        drop(inner);
+
+        Ok(this)
+    }
+
+    /// Write the mailboxes if they are durable and changed since they were
+    /// last written.
+    ///
+    /// This blocks on disk I/O, so should not be called on an async executor.
+    pub fn flush(&self) -> io::Result<()> {
+        let dirty = self.inner.lock().take_dirty(self.config.ttl);
+        match dirty {
+            None => Ok(()),
+            Some((path, stored)) => store(&path, &stored).map_err(|e| {
+                self.inner.lock().dirty = true;
+                e
+            }),
+        }
+    }
+
+    /// Record that `peer` is interested in updates to `urn`.
+    ///
+    /// If the maximum number of interested peers for `urn` is exceeded, the
+    /// least recently recorded peer is forgotten.
+    pub fn interested(&self, peer: PeerId, urn: &Urn) {
+        let urn = urn.clone().with_path(None);
+        let mut inner = self.inner.lock();
+        if !inner.interest.contains_key(&urn) && inner.interest.len() >= self.config.max_urns {
+            tracing::debug!(%urn, "mailbox interest table full");
+            return;
+        }
+
+        let peers = inner.interest.entry(urn).or_default();
+        peers.retain(|p| p != &peer);
+        peers.push_back(peer);
+        while peers.len() > self.config.max_interested {
+            peers.pop_front();
+        }
+        inner.dirty = true;
+    }
+
+    /// Deposit `payload` for all peers interested in its URN for which
+    /// `is_connected` returns `false`.
+    ///
+    /// A pending announcement for the same URN and origin is superseded.
+    /// Returns the number of mailboxes the payload was deposited in.
+    pub fn deposit<F>(&self, payload: &gossip::Payload, is_connected: F) -> usize
+    where
+        F: Fn(&PeerId) -> bool,
+    {
+        let now = Instant::now();
+        let urn = payload.urn.clone().with_path(None);
+        let mut inner = self.inner.lock();
+        let Inner {
+            interest,
+            boxes,
+            dirty,
+            ..
+        } = &mut *inner;
+
+        let recipients = match interest.get(&urn) {
+            None => return 0,
+            Some(peers) => peers
+                .iter()
+                .filter(|peer| Some(**peer) != payload.origin && !is_connected(peer))
+                .copied()
+                .collect::<Vec<_>>(),
+        };
+
+        let mut deposited = 0;
+        for peer in recipients {
+            if !boxes.contains_key(&peer) && boxes.len() >= self.config.max_peers {
+                evict_stalest(boxes);
+            }
+
+            let letters = boxes.entry(peer).or_default();
+            letters.retain(|l| l.payload.urn != payload.urn || l.payload.origin != payload.origin);
+            letters.push_back(Letter {
+                at: now,
+                payload: payload.clone(),
+            });
+            while letters.len() > self.config.capacity {
+                letters.pop_front();
+            }
+            deposited += 1;
+            *dirty = true;
+        }
+
+        deposited
+    }
+
+    /// Take all unexpired announcements pending for `peer`, oldest first.
+    pub fn collect(&self, peer: &PeerId) -> Vec<gossip::Payload> {
+        let ttl = self.config.ttl;
+        let mut inner = self.inner.lock();
+        match inner.boxes.remove(peer) {
+            None => vec![],
+            Some(letters) => {
+                inner.dirty = true;
+                letters
+                    .into_iter()
+                    .filter(|l| l.at.elapsed() < ttl)
+                    .map(|l| l.payload)
+                    .collect()
+            },
+        }
+    }
+
+    /// Discard expired announcements and empty mailboxes.
+    pub fn expire(&self) {
+        let ttl = self.config.ttl;
+        let mut inner = self.inner.lock();
+        let before = inner.boxes.values().map(VecDeque::len).sum::<usize>();
+        inner.boxes.retain(|_, letters| {
+            letters.retain(|l| l.at.elapsed() < ttl);
+            !letters.is_empty()
+        });
+        if inner.boxes.values().map(VecDeque::len).sum::<usize>() != before {
+            inner.dirty = true;
+        }
+    }
+
+    /// The recorded interest, and the unexpired announcements along with
+    /// their age, for inclusion in a [`super::checkpoint`].
+    #[allow(clippy::type_complexity)]
+    pub fn snapshot(
+        &self,
+    ) -> (
+        Vec<(Urn, Vec<PeerId>)>,
+        Vec<(PeerId, Duration, gossip::Payload)>,
+    ) {
+        self.inner.lock().snapshot(self.config.ttl)
+    }
+
+    /// Restore the `interest` and `letters` of a [`Mailbox::snapshot`].
+    ///
+    /// Letters are expected in the order they were deposited per peer, and
+    /// are subject to the same bounds and expiry as freshly deposited ones.
+    /// Like those, they supersede a pending letter for the same URN and
+    /// origin.
+    pub fn restore(
+        &self,
+        interest: impl IntoIterator<Item = (Urn, Vec<PeerId>)>,
+        letters: impl IntoIterator<Item = (PeerId, Duration, gossip::Payload)>,
+    ) {
+        for (urn, peers) in interest {
+            for peer in peers {
+                self.interested(peer, &urn)
+            }
+        }
+
+        let now = Instant::now();
+        let mut inner = self.inner.lock();
+        for (peer, age, payload) in letters {
+            if age >= self.config.ttl {
+                continue;
+            }
+            let at = now.checked_sub(age).unwrap_or(now);
+            if !inner.boxes.contains_key(&peer) && inner.boxes.len() >= self.config.max_peers {
+                evict_stalest(&mut inner.boxes);
+            }
+            let letters = inner.boxes.entry(peer).or_default();
+            letters.retain(|l| l.payload.urn != payload.urn || l.payload.origin != payload.origin);
+            letters.push_back(Letter { at, payload });
+            while letters.len() > self.config.capacity {
+                letters.pop_front();
+            }
+            inner.dirty = true;
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        let inner = self.inner.lock();
+        Stats {
+            peers: inner.boxes.len(),
+            pending: inner.boxes.values().map(VecDeque::len).sum(),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Atomically replace the mailboxes at `path` with `stored`.
+fn store(path: &Path, stored: &Stored) -> io::Result<()> {
+    let bytes =
+        minicbor::to_vec(stored).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)
+}
+
+/// Remove the mailbox whose most recent letter is the oldest.
+fn evict_stalest(boxes: &mut HashMap<PeerId, VecDeque<Letter>>) {
+    let stalest = boxes
+        .iter()
+        .min_by_key(|(_, letters)| letters.back().map(|l| l.at))
+        .map(|(peer, _)| *peer);
+    if let Some(peer) = stalest {
+        boxes.remove(&peer);
+    }
+}
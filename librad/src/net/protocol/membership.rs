@@ -25,7 +25,7 @@ mod periodic;
 pub use periodic::Periodic;
 
 mod rpc;
-pub use rpc::Message;
+pub use rpc::{Message, Priority};
 
 mod tick;
 pub use tick::Tick;
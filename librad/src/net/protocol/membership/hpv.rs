@@ -24,7 +24,7 @@ use super::{
     Tick,
 };
 use crate::{
-    net::protocol::info::{PartialPeerInfo, PeerAdvertisement, PeerInfo},
+    net::protocol::info::{Capability, PartialPeerInfo, PeerAdvertisement, PeerInfo},
     PeerId,
 };
 
@@ -158,6 +158,20 @@ where
         self.0.read().is_known(peer)
     }
 
+    /// Whether the active peer `peer` advertised `capability`.
+    pub fn has_capability(&self, peer: &PeerId, capability: &Capability) -> bool {
+        self.0.read().view.has_capability(peer, capability)
+    }
+
+    /// Pin `peer`, see [`PartialView::pin`].
+    pub fn pin(&self, peer: PeerId) {
+        self.0.write().view.pin(peer)
+    }
+
+    pub fn is_pinned(&self, peer: &PeerId) -> bool {
+        self.0.read().view.is_pinned(peer)
+    }
+
     pub fn known(&self) -> Vec<PeerId> {
         self.0.read().known().collect()
     }
@@ -166,6 +180,26 @@ where
         self.0.read().passive().collect()
     }
 
+    pub fn active_info(&self) -> Vec<PartialPeerInfo<Addr>> {
+        self.0.read().view.active_info().collect()
+    }
+
+    pub fn passive_info(&self) -> Vec<PeerInfo<Addr>> {
+        self.0.read().view.passive_info().collect()
+    }
+
+    /// Add `peers` to the passive view, eg. when restoring from a
+    /// [`crate::net::protocol::checkpoint`].
+    ///
+    /// Peers evicted to make room are dropped silently, as nobody is
+    /// connected to them.
+    pub fn restore_passive(&self, peers: impl IntoIterator<Item = PeerInfo<Addr>>) {
+        let mut guard = self.0.write();
+        for info in peers {
+            let _evicted = guard.view.add_passive(info);
+        }
+    }
+
     #[tracing::instrument(level = "debug", skip(self))]
     #[must_use = "ticks must be interpreted"]
     pub fn connection_lost(&self, remote_peer: PeerId) -> TnT<Addr> {
@@ -202,11 +236,15 @@ where
         self.0.write().apply(remote_peer, remote_addr, rpc)
     }
 
+    /// The message to send to a peer we connected to.
+    ///
+    /// Private peers never `Join`, as that would make the recipient forward
+    /// their info to other peers.
     pub fn hello(&self, local_info: PeerAdvertisement<Addr>) -> rpc::Message<Addr> {
         use rpc::{Message::*, Priority};
 
         match self.view_stats() {
-            (0, 0) => Join { info: local_info },
+            (0, 0) if !local_info.is_private() => Join { info: local_info },
             (act, _) => Neighbour {
                 info: local_info,
                 prio: if act == 0 {
@@ -320,15 +358,12 @@ where
     }
 
     pub fn choose_passive_to_promote(&mut self) -> Vec<PeerInfo<Addr>> {
+        let active = self.view.num_active_unpinned();
         assert!(
-            self.params.max_active >= self.num_active(),
+            self.params.max_active >= active,
             "number of active peers is larger than the configured max"
         );
-        let n = self
-            .params
-            .max_active
-            .checked_sub(self.num_active())
-            .unwrap_or(1);
+        let n = self.params.max_active.checked_sub(active).unwrap_or(1);
         self.view.passive_info().choose_multiple(&mut self.rng, n)
     }
 
@@ -364,7 +399,11 @@ where
                     .add_active(info.clone().into())
                     .into_iter()
                     .collect::<TnT<_>>();
-                let fwd = self.broadcast_recipients(Some(remote_peer));
+                let fwd = if info.advertised_info.is_private() {
+                    vec![]
+                } else {
+                    self.broadcast_recipients(Some(remote_peer))
+                };
                 if !fwd.is_empty() {
                     tnt.ticks.push(All {
                         recipients: fwd,
@@ -378,6 +417,7 @@ where
                 Ok(tnt)
             },
 
+            ForwardJoin { joined, .. } if joined.advertised_info.is_private() => Ok(TnT::default()),
             ForwardJoin { joined, ttl }
                 if (ttl == 0 || !self.view.is_active_full())
                     && !self.view.is_active(&joined.peer_id)
@@ -410,7 +450,10 @@ where
             },
 
             Neighbour { info, prio } => {
-                if prio == Priority::High || !self.view.is_active_full() {
+                if prio == Priority::High
+                    || !self.view.is_active_full()
+                    || self.view.is_pinned(&remote_peer)
+                {
                     let info = peer_info_from(remote_peer, info, remote_addr);
                     Ok(self.view.add_active(info.into()).into_iter().collect())
                 } else {
@@ -498,6 +541,7 @@ where
             .view
             .active_info()
             .filter_map(|info| info.sequence())
+            .filter(|info| !info.advertised_info.is_private())
             .choose_multiple(&mut self.rng, sz);
         if sample.len() < self.params.shuffle_sample_size {
             sample.extend(
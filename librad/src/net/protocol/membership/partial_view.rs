@@ -3,12 +3,15 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{collections::BTreeMap, iter};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter,
+};
 
 use rand::seq::IteratorRandom as _;
 
 use crate::{
-    net::protocol::info::{PartialPeerInfo, PeerInfo},
+    net::protocol::info::{Capability, PartialPeerInfo, PeerInfo},
     PeerId,
 };
 
@@ -27,6 +30,7 @@ pub struct PartialView<Rng, Addr> {
     max_passive: usize,
     active: BTreeMap<PeerId, PartialPeerInfo<Addr>>,
     passive: BTreeMap<PeerId, PeerInfo<Addr>>,
+    pinned: BTreeSet<PeerId>,
 }
 
 impl<R, A> PartialView<R, A>
@@ -42,6 +46,7 @@ where
             max_passive,
             active: BTreeMap::default(),
             passive: BTreeMap::default(),
+            pinned: BTreeSet::default(),
         }
     }
 
@@ -69,6 +74,25 @@ where
         self.active.values().cloned()
     }
 
+    pub fn has_capability(&self, peer: &PeerId, capability: &Capability) -> bool {
+        self.active
+            .get(peer)
+            .and_then(|info| info.advertised_info.as_ref())
+            .map(|ad| ad.capabilities.contains(capability))
+            .unwrap_or(false)
+    }
+
+    /// Pin `peer`, so it is never demoted to make room for other peers.
+    ///
+    /// Pinned peers don't count towards the maximum size of the active view.
+    pub fn pin(&mut self, peer: PeerId) {
+        self.pinned.insert(peer);
+    }
+
+    pub fn is_pinned(&self, peer: &PeerId) -> bool {
+        self.pinned.contains(peer)
+    }
+
     pub fn passive(&self) -> impl Iterator<Item = PeerId> + '_ {
         self.passive.keys().copied()
     }
@@ -89,14 +113,26 @@ where
         self.passive.len()
     }
 
+    /// The number of active peers which are not pinned.
+    pub fn num_active_unpinned(&self) -> usize {
+        self.active
+            .keys()
+            .filter(|peer| !self.pinned.contains(peer))
+            .count()
+    }
+
     pub fn is_active_full(&self) -> bool {
-        self.active.len() >= self.max_active
+        self.num_active_unpinned() >= self.max_active
     }
 
     /// aka `dropRandomElementFromActiveView`
+    ///
+    /// Pinned peers are never chosen.
     pub fn demote_random(&mut self) -> Vec<Transition<A>> {
+        let pinned = &self.pinned;
         self.active
             .keys()
+            .filter(|peer| !pinned.contains(peer))
             .choose(&mut self.rng)
             .copied()
             .as_ref()
@@ -112,6 +148,11 @@ where
                     // We only have a partial info, ie. didn't receive any `Join`
                     // or `Neighbour`. We take the liberty to evict this pal.
                     None => vec![Transition::Evicted(demoted)],
+                    // Private peers can't be connected to, so there is no
+                    // point in keeping them around.
+                    Some(info) if info.advertised_info.is_private() => {
+                        vec![Transition::Evicted(demoted)]
+                    },
                     Some(info) => iter::once(Transition::Demoted(info.clone()))
                         .chain(self.add_passive(info))
                         .collect(),
@@ -126,7 +167,7 @@ where
             return vec![];
         }
 
-        let demoted = if self.is_active_full() {
+        let demoted = if !self.is_pinned(&info.peer_id) && self.is_active_full() {
             self.demote_random()
         } else {
             vec![]
@@ -145,10 +186,15 @@ where
     }
 
     /// aka `addNodePassiveView`
+    ///
+    /// Private peers are never added, as they don't accept connections.
     pub fn add_passive(&mut self, mut info: PeerInfo<A>) -> Vec<Transition<A>> {
         use std::collections::btree_map::Entry::*;
 
-        let evicted = if info.peer_id == self.local_id || self.is_active(&info.peer_id) {
+        let evicted = if info.peer_id == self.local_id
+            || self.is_active(&info.peer_id)
+            || info.advertised_info.is_private()
+        {
             vec![]
         } else {
             let evicted = if self.num_passive() >= self.max_passive {
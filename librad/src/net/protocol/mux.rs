@@ -0,0 +1,175 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Accounting of the streams multiplexed over the connection to a peer.
+//!
+//! All protocols share a single QUIC connection per peer, and QUIC only
+//! bounds the total number of concurrent streams. A peer opening many
+//! long-running streams of one kind (eg. git fetches) could thus use up the
+//! budget of the connection, so that gossip or membership messages can no
+//! longer get through.
+//!
+//! [`Streams`] counts the ingress streams currently open per peer and
+//! [`UpgradeRequest`], and refuses new ones exceeding the ceiling set for
+//! their kind in [`Config`]. Refused streams are closed with
+//! [`crate::net::connection::CloseReason::StreamLimit`], which the initiator
+//! can recover using [`crate::net::quic::error::close_reason`], and retry
+//! later.
+
+use std::{collections::HashMap, hash::BuildHasherDefault, sync::Arc};
+
+use dashmap::DashMap;
+use rustc_hash::FxHasher;
+
+use crate::{net::upgrade::UpgradeRequest, PeerId};
+
+/// Maximum number of concurrent ingress streams per peer, by kind.
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    pub gossip: usize,
+    pub git: usize,
+    pub membership: usize,
+    pub interrogation: usize,
+    pub inventory: usize,
+    pub ping: usize,
+    pub lfs: usize,
+    pub attachments: usize,
+    pub request_pull: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            gossip: 64,
+            git: 4,
+            membership: 64,
+            interrogation: 8,
+            inventory: 4,
+            ping: 2,
+            lfs: 2,
+            attachments: 2,
+            request_pull: 2,
+        }
+    }
+}
+
+impl Config {
+    pub fn limit(&self, kind: UpgradeRequest) -> usize {
+        match kind {
+            UpgradeRequest::Gossip => self.gossip,
+            UpgradeRequest::Git => self.git,
+            UpgradeRequest::Membership => self.membership,
+            UpgradeRequest::Interrogation => self.interrogation,
+            UpgradeRequest::Inventory => self.inventory,
+            UpgradeRequest::Ping => self.ping,
+            UpgradeRequest::Lfs => self.lfs,
+            UpgradeRequest::Attachments => self.attachments,
+            UpgradeRequest::RequestPull => self.request_pull,
+        }
+    }
+}
+
+/// Stream counts of a single peer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Streams currently open, by kind.
+    pub open: HashMap<UpgradeRequest, usize>,
+    /// Streams refused since the peer connected, by kind.
+    pub refused: HashMap<UpgradeRequest, u64>,
+}
+
+impl Stats {
+    pub fn open(&self, kind: UpgradeRequest) -> usize {
+        self.open.get(&kind).copied().unwrap_or_default()
+    }
+
+    pub fn refused(&self, kind: UpgradeRequest) -> u64 {
+        self.refused.get(&kind).copied().unwrap_or_default()
+    }
+}
+
+/// Snapshot of the [`Stats`] of all connected peers.
+pub type Snapshot = HashMap<PeerId, Stats>;
+
+type Table = DashMap<PeerId, Stats, BuildHasherDefault<FxHasher>>;
+
+/// Concurrent table of [`Stats`] per peer.
+#[derive(Clone, Default)]
+pub struct Streams {
+    config: Config,
+    peers: Arc<Table>,
+}
+
+impl Streams {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            peers: Arc::new(Table::default()),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Account for a new stream of kind `kind` from `peer`.
+    ///
+    /// Returns `None` if the limit for `kind` is reached, in which case the
+    /// stream should be refused. Otherwise, the stream is counted as open
+    /// until the returned [`Slot`] is dropped.
+    pub fn acquire(&self, peer: PeerId, kind: UpgradeRequest) -> Option<Slot> {
+        let limit = self.config.limit(kind);
+        let mut entry = self.peers.entry(peer).or_default();
+        let stats = entry.value_mut();
+        let open = stats.open.entry(kind).or_default();
+        if *open >= limit {
+            *stats.refused.entry(kind).or_default() += 1;
+            return None;
+        }
+        *open += 1;
+
+        Some(Slot {
+            peer,
+            kind,
+            peers: Arc::clone(&self.peers),
+        })
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<Stats> {
+        self.peers.get(peer).map(|stats| stats.clone())
+    }
+
+    /// Forget about all peers for which `f` returns `false`.
+    pub fn retain<F>(&self, f: F)
+    where
+        F: Fn(&PeerId) -> bool,
+    {
+        self.peers.retain(|peer, _| f(peer))
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.peers
+            .iter()
+            .map(|r| (*r.key(), r.value().clone()))
+            .collect()
+    }
+}
+
+/// An open stream, as accounted for by [`Streams::acquire`].
+pub struct Slot {
+    peer: PeerId,
+    kind: UpgradeRequest,
+    peers: Arc<Table>,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some(mut stats) = self.peers.get_mut(&self.peer) {
+            if let Some(open) = stats.open.get_mut(&self.kind) {
+                *open = open.saturating_sub(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Write-ahead queue of local announcements.
+//!
+//! Announcements of local updates are broadcast to whichever peers happen to
+//! be connected at the time. If there are none, or the daemon crashes before
+//! the broadcast went out, the update would otherwise not be announced until
+//! the next one. To prevent this, every local announcement is first appended
+//! to a log at [`FILE_NAME`] (relative to the git directory), and only marked
+//! as delivered once it was sent to at least one peer. Undelivered
+//! announcements are retried every [`Config::retry_interval`], including
+//! those left over from a previous run.
+//!
+//! The last delivered revision is remembered per URN (including its path) and
+//! origin, for the [`MAX_PROPAGATED`] most recently delivered ones, so
+//! re-announcing a tip which was already propagated is a no-op. Likewise, a
+//! newer announcement supersedes any pending one for the same URN and origin.
+//!
+//! Appending to the log blocks until the data is synced to disk, so the
+//! [`Outbox`] methods should not be called on an async executor.
+//!
+//! The log is a sequence of CBOR-encoded [`Record`]s, and is compacted when
+//! the [`Outbox`] is opened. A torn record at the end of the log (eg. due to a
+//! crash while appending) is ignored.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Write as _},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+
+use super::gossip::{Payload, Rev};
+use crate::{identities::git::Urn, PeerId};
+
+/// The name of the log file, relative to the git directory.
+pub const FILE_NAME: &str = "announcements.wal";
+
+/// The maximum number of propagated revisions remembered. The least recently
+/// delivered ones are forgotten first.
+pub const MAX_PROPAGATED: usize = 10_000;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// If `false`, the queue is kept in memory only, and does not survive
+    /// restarts.
+    pub durable: bool,
+    /// Interval at which undelivered announcements are retried.
+    pub retry_interval: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            durable: true,
+            retry_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// An entry of the log.
+#[derive(Clone, Debug, PartialEq, minicbor::Encode, minicbor::Decode)]
+pub enum Record {
+    /// An announcement was queued.
+    #[n(0)]
+    #[cbor(array)]
+    Put {
+        #[n(0)]
+        seq: u64,
+        #[n(1)]
+        payload: Payload,
+    },
+
+    /// The announcement with the given sequence number was delivered, or
+    /// superseded.
+    #[n(1)]
+    #[cbor(array)]
+    Done {
+        #[n(0)]
+        seq: u64,
+    },
+}
+
+type Key = (Urn, Option<PeerId>);
+
+fn key(payload: &Payload) -> Key {
+    (payload.urn.clone(), payload.origin)
+}
+
+#[derive(Default)]
+struct Inner {
+    pending: BTreeMap<u64, Payload>,
+    /// In the order of delivery, the most recent last.
+    propagated: IndexMap<Key, Option<Rev>>,
+    next_seq: u64,
+}
+
+impl Inner {
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::Put { seq, payload } => {
+                self.next_seq = self.next_seq.max(seq + 1);
+                self.pending.insert(seq, payload);
+            },
+            Record::Done { seq } => {
+                if let Some(payload) = self.pending.remove(&seq) {
+                    let k = key(&payload);
+                    self.propagated.shift_remove(&k);
+                    self.propagated.insert(k, payload.rev);
+                    if self.propagated.len() > MAX_PROPAGATED {
+                        self.propagated.shift_remove_index(0);
+                    }
+                }
+            },
+        }
+    }
+}
+
+fn append(log: &mut Option<File>, records: &[Record]) {
+    let log = match log.as_mut() {
+        Some(log) => log,
+        None => return,
+    };
+    let res = records
+        .iter()
+        .try_fold(Vec::new(), |mut buf, record| {
+            minicbor::encode(record, &mut buf).map(|()| buf)
+        })
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        .and_then(|buf| {
+            log.write_all(&buf)?;
+            log.sync_data()
+        });
+    if let Err(e) = res {
+        tracing::warn!(err = %e, "failed to append to announcement queue");
+    }
+}
+
+/// Durable queue of announcements not yet delivered to any peer.
+#[derive(Clone)]
+pub struct Outbox {
+    inner: Arc<Mutex<Inner>>,
+    /// Locked before `inner` is released, so records are appended in the order
+    /// they were applied, but synced without holding `inner`.
+    log: Arc<Mutex<Option<File>>>,
+    retry_interval: Duration,
+}
+
+impl Outbox {
+    /// Create an [`Outbox`] according to `config`, keeping the log at
+    /// [`FILE_NAME`] in `git_dir`.
+    pub fn new(config: Config, git_dir: &Path) -> io::Result<Self> {
+        let mut outbox = if config.durable {
+            Self::open(&git_dir.join(FILE_NAME))?
+        } else {
+            Self::in_memory()
+        };
+        outbox.retry_interval = config.retry_interval;
+        Ok(outbox)
+    }
+
+    /// An [`Outbox`] which is not backed by a file.
+    pub fn in_memory() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner::default())),
+            log: Arc::new(Mutex::new(None)),
+            retry_interval: Config::default().retry_interval,
+        }
+    }
+
+    /// Open the log at `path`, creating it if it doesn't exist.
+    ///
+    /// Pending announcements from a previous run are restored, and the log is
+    /// compacted.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut inner = Inner::default();
+        match fs::read(path) {
+            Ok(bytes) => {
+                let mut decoder = minicbor::Decoder::new(&bytes);
+                while decoder.position() < bytes.len() {
+                    match decoder.decode() {
+                        Ok(record) => inner.apply(record),
+                        Err(e) => {
+                            tracing::warn!(err = %e, "ignoring torn tail of announcement queue");
+                            break;
+                        },
+                    }
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e),
+        }
+
+        let mut records = Vec::new();
+        for (k, rev) in &inner.propagated {
+            let seq = inner.next_seq;
+            inner.next_seq += 1;
+            records.push(Record::Put {
+                seq,
+                payload: Payload {
+                    urn: k.0.clone(),
+                    rev: rev.clone(),
+                    origin: k.1,
+                },
+            });
+            records.push(Record::Done { seq });
+        }
+        records.extend(inner.pending.iter().map(|(seq, payload)| Record::Put {
+            seq: *seq,
+            payload: payload.clone(),
+        }));
+
+        let tmp = path.with_extension("wal.tmp");
+        let mut log = Some(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&tmp)?,
+        );
+        append(&mut log, &records);
+        fs::rename(&tmp, path)?;
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+            log: Arc::new(Mutex::new(log)),
+            retry_interval: Config::default().retry_interval,
+        })
+    }
+
+    pub fn retry_interval(&self) -> Duration {
+        self.retry_interval
+    }
+
+    /// Queue `payload` for delivery.
+    ///
+    /// Returns `None` if the revision was already propagated, otherwise the
+    /// sequence number to pass to [`Outbox::delivered`].
+    pub fn enqueue(&self, payload: &Payload) -> Option<u64> {
+        let mut inner = self.inner.lock();
+        let k = key(payload);
+        if inner.propagated.get(&k) == Some(&payload.rev) {
+            return None;
+        }
+
+        let mut records = inner
+            .pending
+            .iter()
+            .filter(|(_, pending)| key(pending) == k)
+            .map(|(seq, _)| Record::Done { seq: *seq })
+            .collect::<Vec<_>>();
+        for record in &records {
+            if let Record::Done { seq } = record {
+                inner.pending.remove(seq);
+            }
+        }
+        let seq = inner.next_seq;
+        records.push(Record::Put {
+            seq,
+            payload: payload.clone(),
+        });
+        inner.apply(Record::Put {
+            seq,
+            payload: payload.clone(),
+        });
+        let mut log = self.log.lock();
+        drop(inner);
+        append(&mut log, &records);
+
+        Some(seq)
+    }
+
+    /// Mark the announcement with sequence number `seq` as delivered.
+    pub fn delivered(&self, seq: u64) {
+        let mut inner = self.inner.lock();
+        if inner.pending.contains_key(&seq) {
+            inner.apply(Record::Done { seq });
+            let mut log = self.log.lock();
+            drop(inner);
+            append(&mut log, &[Record::Done { seq }]);
+        }
+    }
+
+    /// The undelivered announcements, oldest first.
+    pub fn pending(&self) -> Vec<(u64, Payload)> {
+        self.inner
+            .lock()
+            .pending
+            .iter()
+            .map(|(seq, payload)| (*seq, payload.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Application-level liveness checks.
+//!
+//! QUIC keep-alives only tell us that the remote end's transport is alive. To
+//! detect peers which are connected, but no longer serve any requests (eg.
+//! because their runtime is wedged), the protocol periodically sends a
+//! [`Request`] over a fresh stream to every member of the active view which
+//! advertised [`Capability::Ping`], and expects the [`Response`] to echo its
+//! nonce within [`TIMEOUT`].
+//!
+//! The outcomes are recorded in a [`Liveness`] table. After [`MAX_FAILURES`]
+//! consecutive pings which timed out or were answered wrongly, the connection
+//! to the peer is closed, which causes the membership protocol to replace it
+//! in the active view.
+//!
+//! [`Capability::Ping`]: super::Capability::Ping
+
+use std::{collections::HashMap, hash::BuildHasherDefault, sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use minicbor::{Decode, Encode};
+use rustc_hash::FxHasher;
+
+use crate::PeerId;
+
+/// Interval at which the members of the active view are pinged.
+pub const INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time to wait for a [`Response`].
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of consecutive failures after which a peer is considered dead.
+pub const MAX_FAILURES: u32 = 3;
+
+pub const FRAMED_BUFSIZ: usize = 32;
+
+/// Snapshot of the [`Health`] of all pinged peers.
+pub type Snapshot = HashMap<PeerId, Health>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+#[cbor(array)]
+pub struct Request {
+    #[n(0)]
+    pub nonce: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+#[cbor(array)]
+pub struct Response {
+    /// The [`Request::nonce`] this is a response to.
+    #[n(0)]
+    pub nonce: u64,
+}
+
+/// Outcome of the pings sent to a peer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Health {
+    /// Round-trip time of the last successful ping.
+    pub rtt: Option<Duration>,
+    /// Number of consecutive failed pings.
+    pub failures: u32,
+    /// Total number of pings sent.
+    pub pings: u64,
+}
+
+impl Health {
+    pub fn is_alive(&self) -> bool {
+        self.failures < MAX_FAILURES
+    }
+}
+
+/// Concurrent table of [`Health`] per peer.
+#[derive(Clone, Default)]
+pub struct Liveness {
+    peers: Arc<DashMap<PeerId, Health, BuildHasherDefault<FxHasher>>>,
+}
+
+impl Liveness {
+    /// Record a successful ping of `peer`.
+    pub fn success(&self, peer: PeerId, rtt: Duration) -> Health {
+        let mut health = self.peers.entry(peer).or_default();
+        health.rtt = Some(rtt);
+        health.failures = 0;
+        health.pings = health.pings.saturating_add(1);
+        *health
+    }
+
+    /// Record a failed ping of `peer`.
+    pub fn failure(&self, peer: PeerId) -> Health {
+        let mut health = self.peers.entry(peer).or_default();
+        health.failures = health.failures.saturating_add(1);
+        health.pings = health.pings.saturating_add(1);
+        *health
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<Health> {
+        self.peers.get(peer).map(|health| *health)
+    }
+
+    /// Forget about all peers for which `f` returns `false`.
+    pub fn retain<F>(&self, f: F)
+    where
+        F: Fn(&PeerId) -> bool,
+    {
+        self.peers.retain(|peer, _| f(peer))
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.peers.iter().map(|r| (*r.key(), *r.value())).collect()
+    }
+}
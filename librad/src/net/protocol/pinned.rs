@@ -0,0 +1,95 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Peers the protocol always stays connected to.
+//!
+//! Pinned peers (eg. the seeds of an organisation) are never demoted from the
+//! active view by the membership protocol, and don't count towards
+//! [`super::membership::Params::max_active`], so shuffles and random
+//! promotions can't push them out. Whenever a pinned peer is not in the
+//! active view, it is redialled with exponential backoff between
+//! [`Config::min_backoff`] and [`Config::max_backoff`].
+//!
+//! Changes of the [`Status`] of a pinned peer are emitted as
+//! [`super::event::upstream::Pinned`] events.
+
+use std::{collections::BTreeMap, net::SocketAddr, sync::Arc, time::Duration};
+
+use parking_lot::RwLock;
+
+use crate::PeerId;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The pinned peers, along with the addresses to dial them on.
+    pub peers: BTreeMap<PeerId, Vec<SocketAddr>>,
+    /// Delay before redialling a peer after the first failed attempt. This is
+    /// also the interval at which the connection to a pinned peer is checked.
+    pub min_backoff: Duration,
+    /// Upper bound of the delay between attempts to dial a peer.
+    pub max_backoff: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            peers: BTreeMap::new(),
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// The peer has not been dialled yet.
+    Pending,
+    /// The peer is in the active view.
+    Connected,
+    /// The peer could not be connected to after `attempts` tries, and will be
+    /// dialled again after `backoff`.
+    Disconnected { attempts: u32, backoff: Duration },
+}
+
+/// Snapshot of the [`Status`] of all pinned peers.
+pub type Snapshot = BTreeMap<PeerId, Status>;
+
+/// The pinned peers and their current [`Status`].
+#[derive(Clone)]
+pub struct Pinned {
+    config: Arc<Config>,
+    status: Arc<RwLock<Snapshot>>,
+}
+
+impl Pinned {
+    pub fn new(config: Config) -> Self {
+        let status = config
+            .peers
+            .keys()
+            .map(|peer| (*peer, Status::Pending))
+            .collect();
+        Self {
+            config: Arc::new(config),
+            status: Arc::new(RwLock::new(status)),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn is_pinned(&self, peer: &PeerId) -> bool {
+        self.config.peers.contains_key(peer)
+    }
+
+    /// Record the `status` of `peer`, returning `true` if it changed.
+    pub fn update(&self, peer: PeerId, status: Status) -> bool {
+        self.status.write().insert(peer, status) != Some(status)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.status.read().clone()
+    }
+}
@@ -99,6 +99,12 @@ pub(in crate::net::protocol) mod error {
         }
     }
 
+    pub fn read_only(reason: storage::health::Reason) -> Error {
+        Error {
+            message: format!("storage is read-only: {}", reason),
+        }
+    }
+
     pub fn guard<E: std::error::Error>(e: E) -> Error {
         Error {
             message: e.to_string(),
@@ -126,29 +132,36 @@ where
         let succ = repl.replicate(spawner, storage, conn, urn, None).await?;
 
         let storage = self.storage.get().await?;
-        succ.updated_refs()
-            .iter()
-            .try_fold(Success::default(), |mut success, up| match up {
-                Updated::Direct { name, target } => {
-                    success.refs.push(Ref {
-                        name: name.clone(),
-                        oid: (*target).into(),
-                    });
-                    Ok(success)
-                },
-                Updated::Symbolic { name, target } => {
-                    let oid = (*storage).reference_oid(target)?;
-                    success.refs.push(Ref {
-                        name: name.clone(),
-                        oid,
-                    });
-                    Ok(success)
-                },
-                Updated::Prune { name } => {
-                    success.pruned.push(name.clone());
-                    Ok(success)
-                },
+        // Resolving symbolic refs hits the storage, so must not run on the
+        // executor
+        spawner
+            .blocking(move || {
+                succ.updated_refs().iter().try_fold(
+                    Success::default(),
+                    |mut success, up| match up {
+                        Updated::Direct { name, target } => {
+                            success.refs.push(Ref {
+                                name: name.clone(),
+                                oid: (*target).into(),
+                            });
+                            Ok(success)
+                        },
+                        Updated::Symbolic { name, target } => {
+                            let oid = (*storage).reference_oid(target)?;
+                            success.refs.push(Ref {
+                                name: name.clone(),
+                                oid,
+                            });
+                            Ok(success)
+                        },
+                        Updated::Prune { name } => {
+                            success.pruned.push(name.clone());
+                            Ok(success)
+                        },
+                    },
+                )
             })
+            .await
     }
 
     /// # Errors
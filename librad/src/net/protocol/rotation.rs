@@ -0,0 +1,88 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Announced key rotations.
+//!
+//! Peers which others refer to by [`PeerId`], such as seeds, can't simply
+//! change their key: everyone configured with the old one would refuse the
+//! new identity. Instead, the operator signs a [`Rotation`] with the old key,
+//! naming the new one, and serves it (see [`super::Config::rotation`]) before
+//! switching keys. Peers ask for it via
+//! [`super::interrogation::Request::GetRotation`], and follow the rotation
+//! once the signature verifies against the key they know.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{keystore::sign, PeerId, Signature};
+
+pub mod error {
+    use thiserror::Error;
+
+    use crate::PeerId;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Verify {
+        #[error("invalid signature on rotation from `{old}` to `{new}`")]
+        Signature { old: PeerId, new: PeerId },
+
+        #[error("rotation from `{0}` to itself")]
+        Identity(PeerId),
+    }
+}
+
+/// A statement by the key `old` that the peer is now reachable as `new`.
+#[derive(
+    Clone, Debug, PartialEq, Eq, Deserialize, Serialize, minicbor::Encode, minicbor::Decode,
+)]
+#[cbor(array)]
+pub struct Rotation {
+    #[n(0)]
+    pub old: PeerId,
+    #[n(1)]
+    pub new: PeerId,
+    /// Signature by `old` over [`Rotation::payload`].
+    #[n(2)]
+    pub signature: Signature,
+}
+
+impl Rotation {
+    /// Create a [`Rotation`] from the key of `signer` to `new`.
+    pub async fn sign<S>(signer: &S, new: PeerId) -> Result<Self, S::Error>
+    where
+        S: sign::Signer,
+    {
+        let old = PeerId::from_signer(signer);
+        let signature = signer.sign(&Self::payload(&old, &new)).await?;
+        Ok(Self {
+            old,
+            new,
+            signature: signature.into(),
+        })
+    }
+
+    /// The bytes signed by the old key.
+    pub fn payload(old: &PeerId, new: &PeerId) -> Vec<u8> {
+        format!("radicle-link seed rotation {} {}", old, new).into_bytes()
+    }
+
+    /// Check that the statement was signed by [`Rotation::old`].
+    pub fn verify(&self) -> Result<(), error::Verify> {
+        if self.old == self.new {
+            return Err(error::Verify::Identity(self.old));
+        }
+        if self.signature.verify(
+            &Self::payload(&self.old, &self.new),
+            self.old.as_public_key(),
+        ) {
+            Ok(())
+        } else {
+            Err(error::Verify::Signature {
+                old: self.old,
+                new: self.new,
+            })
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Estimation of the clock skew between the local and remote peers.
+//!
+//! Signature timestamps are validated against the local clock, and cache
+//! entries expire according to it. Both misbehave in confusing ways if the
+//! local clock is far off. To detect this, the protocol periodically sends an
+//! [`super::interrogation::Request::GetTime`] to every member of the active
+//! view, which responds with its wall clock time. Assuming symmetric network
+//! latency, the remote clock read the responded time half a round-trip before
+//! the response arrived, which yields an [`Estimate`] of the offset of the
+//! remote clock relative to the local one.
+//!
+//! A single peer with a wrong clock says nothing about the local clock, so the
+//! [`Tracker`] considers the median offset across at least [`MIN_PEERS`]
+//! peers. If its magnitude exceeds [`THRESHOLD`], a
+//! [`super::event::upstream::ClockSkew`] warning is emitted.
+
+use std::{
+    collections::HashMap,
+    hash::BuildHasherDefault,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use dashmap::DashMap;
+use rustc_hash::FxHasher;
+
+use crate::PeerId;
+
+/// Interval at which the members of the active view are asked for their time.
+pub const INTERVAL: Duration = Duration::from_secs(600);
+
+/// Time to wait for a response.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Median offset beyond which the local clock is considered skewed.
+pub const THRESHOLD: Duration = Duration::from_secs(30);
+
+/// Minimum number of [`Estimate`]s required to judge the local clock.
+pub const MIN_PEERS: usize = 3;
+
+/// Snapshot of the latest [`Estimate`] of all peers.
+pub type Snapshot = HashMap<PeerId, Estimate>;
+
+/// Milliseconds since the UNIX epoch, according to the local clock.
+pub fn now_ms() -> u64 {
+    millis(SystemTime::now())
+}
+
+fn millis(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Estimated offset of a remote clock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Estimate {
+    /// Milliseconds the remote clock is ahead of the local clock. Negative if
+    /// it is behind.
+    pub offset_ms: i64,
+    /// Round-trip time of the exchange. The estimate is accurate to within
+    /// half of it.
+    pub rtt: Duration,
+}
+
+impl Estimate {
+    /// Estimate the offset from a request `sent` at some local time, which was
+    /// answered with `remote_ms` and `received` at some later local time.
+    pub fn new(sent: SystemTime, remote_ms: u64, received: SystemTime) -> Self {
+        let rtt = received.duration_since(sent).unwrap_or_default();
+        let local_ms = millis(sent).saturating_add(rtt.as_millis() as u64 / 2);
+        Self {
+            offset_ms: remote_ms as i64 - local_ms as i64,
+            rtt,
+        }
+    }
+}
+
+/// Concurrent table of the latest [`Estimate`] per peer.
+#[derive(Clone, Default)]
+pub struct Tracker {
+    peers: Arc<DashMap<PeerId, Estimate, BuildHasherDefault<FxHasher>>>,
+    warned: Arc<AtomicBool>,
+}
+
+impl Tracker {
+    pub fn record(&self, peer: PeerId, estimate: Estimate) {
+        self.peers.insert(peer, estimate);
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<Estimate> {
+        self.peers.get(peer).map(|estimate| *estimate)
+    }
+
+    /// Forget about all peers for which `f` returns `false`.
+    pub fn retain<F>(&self, f: F)
+    where
+        F: Fn(&PeerId) -> bool,
+    {
+        self.peers.retain(|peer, _| f(peer))
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.peers.iter().map(|r| (*r.key(), *r.value())).collect()
+    }
+
+    /// The median [`Estimate::offset_ms`] across all peers, or `None` if
+    /// there are fewer than [`MIN_PEERS`] estimates.
+    ///
+    /// A positive value means that the local clock is behind.
+    pub fn median_offset_ms(&self) -> Option<i64> {
+        let mut offsets = self
+            .peers
+            .iter()
+            .map(|r| r.value().offset_ms)
+            .collect::<Vec<_>>();
+        if offsets.len() < MIN_PEERS {
+            return None;
+        }
+        offsets.sort_unstable();
+        Some(offsets[offsets.len() / 2])
+    }
+
+    /// Returns the median offset if it exceeds [`THRESHOLD`], and it did not
+    /// the last time this method was called.
+    ///
+    /// This ensures a skewed clock is only reported once, until it recovers.
+    pub fn check(&self) -> Option<i64> {
+        match self.median_offset_ms() {
+            Some(offset) if offset.unsigned_abs() > THRESHOLD.as_millis() as u64 => {
+                (!self.warned.swap(true, Ordering::Relaxed)).then(|| offset)
+            },
+            _ => {
+                self.warned.store(false, Ordering::Relaxed);
+                None
+            },
+        }
+    }
+}
@@ -3,21 +3,49 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{net::SocketAddr, ops::Deref, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::SocketAddr,
+    ops::Deref,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use link_async::Spawner;
+use link_async::{CancellationToken, Spawner};
+use link_git::protocol::upload_pack;
 use nonzero_ext::nonzero;
+use parking_lot::Mutex;
 use rand_pcg::Pcg64Mcg;
 use tracing::Instrument as _;
 
 use super::{
+    attachments,
+    backoff,
+    batch,
     broadcast,
     cache,
+    capture,
+    checkpoint,
+    compress,
     event,
     gossip,
+    info::{Capability, PeerAdvertisement},
+    interest,
+    inventory,
+    io,
+    latency,
+    lfs,
+    mailbox,
     membership,
+    mux,
+    outbox,
+    ping,
+    pinned,
     request_pull,
+    rotation,
+    skew,
     tick,
+    usage,
     Endpoint,
     ProtocolStorage,
     RequestPullGuard,
@@ -25,7 +53,7 @@ use super::{
 };
 use crate::{
     git::storage::{self, PoolError, PooledRef},
-    net::quic,
+    net::{dial, quic},
     paths::Paths,
     rate_limit::{self, Direct, Keyed, RateLimiter},
     PeerId,
@@ -34,6 +62,14 @@ use crate::{
 #[derive(Clone)]
 pub(super) struct StateConfig {
     pub paths: Arc<Paths>,
+    pub checkpoint: checkpoint::Config,
+    pub ls_refs: upload_pack::Limits,
+    /// The rotation of the local key, if one was announced.
+    pub rotation: Option<rotation::Rotation>,
+    /// The address families to dial.
+    pub family: dial::Family,
+    /// The capabilities advertised by the local peer.
+    pub capabilities: Arc<BTreeSet<Capability>>,
 }
 
 /// Runtime state of a protocol instance.
@@ -45,15 +81,59 @@ pub(super) struct State<S, G> {
     pub endpoint: Endpoint,
     pub membership: membership::Hpv<Pcg64Mcg, SocketAddr>,
     pub gossip: broadcast::State<Storage<S>, ()>,
+    pub inventory: inventory::State<Storage<S>>,
     pub request_pull: request_pull::State<Storage<S>, G>,
     pub phone: TinCans,
     pub config: StateConfig,
     pub caches: cache::Caches,
+    /// Whether the storage can be written to. Incoming requests which would
+    /// write to it are refused while it can't.
+    pub health: storage::Health,
+    /// The record of the data served to other peers.
+    pub usage: usage::Usage,
     pub spawner: Arc<Spawner>,
+    /// Cancelled when the protocol shuts down. Tasks which should not outlive
+    /// the protocol must be spawned using a child of this token.
+    pub cancel: CancellationToken,
     pub limits: RateLimits,
+    pub latency: latency::Tracker,
+    pub liveness: ping::Liveness,
+    pub skew: skew::Tracker,
+    pub lfs: lfs::Store,
+    pub attachments: attachments::Store,
+    pub mailbox: mailbox::Mailbox,
+    pub batches: batch::Batches,
+    pub pinned: pinned::Pinned,
+    pub streams: mux::Streams,
+    pub capture: Option<capture::Recorder>,
+    pub outbox: outbox::Outbox,
+    pub compression: compress::Compression,
+    pub interests: interest::Interests,
+    pub backoffs: backoff::Backoffs,
 }
 
 impl<S, G> State<S, G> {
+    /// The advertisement of the local peer, as a thunk.
+    pub fn peer_advertisement(&self) -> impl Fn() -> PeerAdvertisement<SocketAddr> + '_ {
+        io::peer_advertisement(&self.endpoint, &self.config.capabilities)
+    }
+
+    /// Whether the local peer runs as a private peer, see
+    /// [`super::Config::private`].
+    pub fn is_private(&self) -> bool {
+        self.config.capabilities.contains(&Capability::Private)
+    }
+
+    /// The [`compress::Compression`] to use for frames sent to `peer`, if
+    /// both sides support it.
+    pub fn compression_for(&self, peer: &PeerId) -> Option<&compress::Compression> {
+        (self.compression.config().enabled
+            && self
+                .membership
+                .has_capability(peer, &Capability::GossipCompression))
+        .then(|| &self.compression)
+    }
+
     pub fn emit<I, E>(&self, evs: I)
     where
         I: IntoIterator<Item = E>,
@@ -92,12 +172,15 @@ where
 
         match self.endpoint.get_connection(to) {
             Some(conn) => Some(conn),
-            None => io::connect(&self.endpoint, to, addr_hints)
+            None => io::connect(&self.endpoint, to, addr_hints, self.config.family)
                 .in_current_span()
                 .await
                 .map(|(conn, ingress)| {
                     self.spawner
-                        .spawn(io::streams::incoming(self.clone(), ingress))
+                        .spawn_cancellable(
+                            &self.cancel,
+                            io::streams::incoming(self.clone(), ingress),
+                        )
                         .detach();
                     conn
                 }),
@@ -142,6 +225,7 @@ where
                     .instrument(span.clone())
                     .await
                     .ok()?;
+                stream.set_class(quic::TrafficClass::Bulk);
                 let upgraded = upgrade::upgrade(stream, upgrade::Git)
                     .inspect_err(|e| tracing::error!(err = ?e, "unable to upgrade stream"))
                     .instrument(span)
@@ -161,6 +245,66 @@ where
 #[derive(Clone)]
 pub(super) struct RateLimits {
     pub membership: Arc<RateLimiter<Keyed<PeerId>>>,
+    pub offenders: Offenders,
+    pub attachments: Arc<RateLimiter<Keyed<PeerId>>>,
+    pub inventory: Arc<RateLimiter<Keyed<PeerId>>>,
+}
+
+/// Peers banned for breaching the membership rate limit.
+///
+/// Connections from banned peers are refused until the ban expires.
+#[derive(Clone)]
+pub(super) struct Offenders {
+    ban: Duration,
+    banned: Arc<Mutex<HashMap<PeerId, Instant>>>,
+}
+
+impl Offenders {
+    pub fn new(ban: Duration) -> Self {
+        Self {
+            ban,
+            banned: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Ban `peer` for the configured duration.
+    pub fn record(&self, peer: PeerId) {
+        self.ban(peer, self.ban)
+    }
+
+    /// Ban `peer` for `duration`, unless it is already banned for longer.
+    pub fn ban(&self, peer: PeerId, duration: Duration) {
+        if duration.is_zero() {
+            return;
+        }
+        let until = Instant::now() + duration;
+        let mut banned = self.banned.lock();
+        let entry = banned.entry(peer).or_insert(until);
+        *entry = (*entry).max(until);
+    }
+
+    pub fn is_banned(&self, peer: &PeerId) -> bool {
+        let mut banned = self.banned.lock();
+        match banned.get(peer) {
+            Some(until) if *until > Instant::now() => true,
+            Some(_) => {
+                banned.remove(peer);
+                false
+            },
+            None => false,
+        }
+    }
+
+    /// The currently banned peers, along with the remaining ban duration.
+    pub fn list(&self) -> Vec<(PeerId, Duration)> {
+        let now = Instant::now();
+        let mut banned = self.banned.lock();
+        banned.retain(|_, until| *until > now);
+        banned
+            .iter()
+            .map(|(peer, until)| (*peer, until.saturating_duration_since(now)))
+            .collect()
+    }
 }
 
 /// Rate limit quota.
@@ -175,8 +319,25 @@ pub struct Quota {
     ///
     /// Default: 1/sec (burst: 10)
     pub membership: rate_limit::Quota,
+    /// Duration for which connections from a peer which breached the
+    /// membership quota are refused.
+    ///
+    /// Default: 10min
+    pub membership_ban: Duration,
     /// See [`StorageQuota`].
     pub storage: StorageQuota,
+    /// Attachment requests to serve per remote peer.
+    ///
+    /// When this limit is breached, requests from the peer are refused.
+    ///
+    /// Default: 30/min (burst: 10)
+    pub attachments: rate_limit::Quota,
+    /// Inventory pages to serve per remote peer.
+    ///
+    /// When this limit is breached, requests from the peer are refused.
+    ///
+    /// Default: 60/min (burst: 20)
+    pub inventory: rate_limit::Quota,
 }
 
 impl Default for Quota {
@@ -184,7 +345,11 @@ impl Default for Quota {
         Self {
             gossip: GossipQuota::default(),
             membership: rate_limit::Quota::per_second(nonzero!(1u32)).allow_burst(nonzero!(10u32)),
+            membership_ban: Duration::from_secs(10 * 60),
             storage: StorageQuota::default(),
+            attachments: rate_limit::Quota::per_minute(nonzero!(30u32))
+                .allow_burst(nonzero!(10u32)),
+            inventory: rate_limit::Quota::per_minute(nonzero!(60u32)).allow_burst(nonzero!(20u32)),
         }
     }
 }
@@ -10,7 +10,19 @@ use futures::{
     stream::{FuturesOrdered, StreamExt as _},
 };
 
-use super::{error, gossip, io, membership, PeerInfo, ProtocolStorage, RequestPullGuard, State};
+use super::{
+    batch,
+    broadcast,
+    error,
+    gossip,
+    io,
+    membership,
+    Capability,
+    PeerInfo,
+    ProtocolStorage,
+    RequestPullGuard,
+    State,
+};
 use crate::PeerId;
 
 #[derive(Debug)]
@@ -35,8 +47,22 @@ pub(super) enum Tock<A, P> {
     Disconnect { peer: PeerId },
 }
 
-#[tracing::instrument(level = "debug", skip(state))]
 pub(super) async fn tock<S, G>(state: State<S, G>, tock: Tock<SocketAddr, gossip::Payload>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    deliver(state, tock).await;
+}
+
+/// Like [`tock`], but returns whether `tock` itself succeeded.
+///
+/// The outcome of any tocks following from it is not taken into account.
+#[tracing::instrument(level = "debug", skip(state))]
+pub(super) async fn deliver<S, G>(
+    state: State<S, G>,
+    tock: Tock<SocketAddr, gossip::Payload>,
+) -> bool
 where
     S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
     G: RequestPullGuard,
@@ -44,8 +70,10 @@ where
     let mut mcfly = FuturesOrdered::new();
     mcfly.push(one_tock(state.clone(), tock));
 
+    let mut delivered = None;
     while let Some(res) = mcfly.next().await {
         tracing::debug!("tock");
+        delivered.get_or_insert(res.is_ok());
         let cont = res.unwrap_or_else(|e| match e {
             error::Tock::Reliable(error::ReliableSend { cont, source }) => {
                 tracing::warn!(err = ?source, "reliable send error");
@@ -59,16 +87,14 @@ where
 
         for tick in cont {
             mcfly.extend(
-                membership::tocks(
-                    &state.membership,
-                    io::peer_advertisement(&state.endpoint),
-                    Some(tick),
-                )
-                .into_iter()
-                .map(|tock| one_tock(state.clone(), tock)),
+                membership::tocks(&state.membership, state.peer_advertisement(), Some(tick))
+                    .into_iter()
+                    .map(|tock| one_tock(state.clone(), tock)),
             )
         }
     }
+
+    delivered.unwrap_or(false)
 }
 
 fn one_tock<S, G>(
@@ -82,10 +108,18 @@ where
     use Tock::*;
 
     async move {
+        let tock = match scoped(&state, tock)
+            .and_then(|tock| backed_off(&state, tock))
+            .and_then(|tock| batched(&state, tock))
+        {
+            Some(tock) => tock,
+            None => return Ok(vec![]),
+        };
         let mut events = vec![];
         let res = match tock {
             SendConnected { to, message } => match state.connection(to, None).await {
                 None => {
+                    state.batches.remove(&to);
                     let membership::TnT { trans, ticks: cont } =
                         state.membership.connection_lost(to);
                     events = trans;
@@ -96,7 +130,7 @@ where
                 },
 
                 Some(conn) => {
-                    io::send_rpc(&conn, message)
+                    io::send_rpc(&conn, state.compression_for(&to), message)
                         .map_err(|e| {
                             let membership::TnT { trans, ticks: cont } =
                                 state.membership.connection_lost(to);
@@ -138,6 +172,112 @@ where
     .boxed()
 }
 
+/// Drop announcements to connected peers which declared that they are not
+/// interested in them, see [`interest`].
+///
+/// [`interest`]: super::interest
+fn scoped<S, G>(
+    state: &State<S, G>,
+    t: Tock<SocketAddr, gossip::Payload>,
+) -> Option<Tock<SocketAddr, gossip::Payload>>
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    match t {
+        Tock::SendConnected {
+            to,
+            message: io::Rpc::Gossip(broadcast::Message::Have { val, .. }),
+        } if !state.interests.is_interested(&to, &val.urn) => {
+            tracing::trace!(%to, urn = %val.urn, "not interested, dropping announcement");
+            None
+        },
+        other => Some(other),
+    }
+}
+
+/// Drop requests for retransmission to neighbours which signalled that they
+/// are overloaded, and backoff signals to neighbours which don't understand
+/// them, see [`backoff`].
+///
+/// [`backoff`]: super::backoff
+fn backed_off<S, G>(
+    state: &State<S, G>,
+    t: Tock<SocketAddr, gossip::Payload>,
+) -> Option<Tock<SocketAddr, gossip::Payload>>
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    match t {
+        Tock::SendConnected {
+            to,
+            message: io::Rpc::Gossip(broadcast::Message::Want { .. }),
+        } if state.backoffs.is_backing_off(&to) => {
+            tracing::trace!(%to, "neighbour overloaded, dropping want");
+            None
+        },
+        Tock::SendConnected {
+            to,
+            message: io::Rpc::Backoff(_),
+        } if !(state.backoffs.config().enabled
+            && state
+                .membership
+                .has_capability(&to, &Capability::GossipBackoff)) =>
+        {
+            None
+        },
+        other => Some(other),
+    }
+}
+
+/// Buffer gossip to connected peers which accept batches, see [`batch`].
+///
+/// Returns the [`Tock`] to perform now, if any.
+fn batched<S, G>(
+    state: &State<S, G>,
+    t: Tock<SocketAddr, gossip::Payload>,
+) -> Option<Tock<SocketAddr, gossip::Payload>>
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+    G: RequestPullGuard,
+{
+    match t {
+        Tock::SendConnected {
+            to,
+            message: io::Rpc::Gossip(msg),
+        } if state.batches.config().enabled
+            && state
+                .membership
+                .has_capability(&to, &Capability::GossipBatch) =>
+        {
+            match state.batches.push(to, msg) {
+                batch::Push::Flush(msgs) => Some(Tock::SendConnected {
+                    to,
+                    message: io::Rpc::GossipBatch(msgs),
+                }),
+                batch::Push::Schedule(after) => {
+                    let spawner = state.spawner.clone();
+                    let cancel = state.cancel.clone();
+                    let state = state.clone();
+                    spawner
+                        .spawn_cancellable(&cancel, async move {
+                            link_async::sleep(after).await;
+                            if let Some(msgs) = state.batches.take(&to) {
+                                let message = io::Rpc::GossipBatch(msgs);
+                                tock(state, Tock::SendConnected { to, message }).await
+                            }
+                        })
+                        .detach();
+                    None
+                },
+                batch::Push::Buffered => None,
+            }
+        },
+        other => Some(other),
+    }
+}
+
 async fn try_connect_and_send<S, G>(
     state: &State<S, G>,
     to: &PeerInfo<SocketAddr>,
@@ -151,7 +291,7 @@ where
         .connection(to.peer_id, to.addrs().copied().collect::<Vec<_>>())
         .await
         .ok_or_else(|| error::BestEffortSend::CouldNotConnect { to: to.clone() })?;
-    io::send_rpc(&conn, message)
+    io::send_rpc(&conn, state.compression_for(&to.peer_id), message)
         .map_err(error::BestEffortSend::SendGossip)
         .await
 }
@@ -3,7 +3,12 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use parking_lot::Mutex;
 pub use tokio::sync::broadcast::error::RecvError;
@@ -15,16 +20,34 @@ use super::{
     gossip,
     info::PeerAdvertisement,
     interrogation,
+    inventory,
+    latency,
+    mux,
+    ping,
+    pinned,
     request_pull,
+    rotation::Rotation,
+    skew,
 };
 use crate::{git::Urn, identities::xor::Xor, net::quic, PeerId};
 
 pub struct Connected(pub(crate) quic::Connection);
 
+/// Number of [`event::Upstream`] events retained for replay.
+const JOURNAL_CAPACITY: usize = 1024;
+
 #[derive(Clone)]
 pub struct TinCans {
     pub(super) downstream: tincan::Sender<event::Downstream>,
-    pub(super) upstream: tincan::Sender<event::Upstream>,
+    pub(super) upstream: tincan::Sender<event::upstream::Sequenced>,
+    journal: Arc<Mutex<Journal>>,
+}
+
+/// Bounded log of the most recently emitted upstream events.
+#[derive(Default)]
+struct Journal {
+    next: u64,
+    events: VecDeque<event::upstream::Sequenced>,
 }
 
 impl TinCans {
@@ -32,6 +55,7 @@ impl TinCans {
         Self {
             downstream: tincan::channel(16).0,
             upstream: tincan::channel(16).0,
+            journal: Default::default(),
         }
     }
 
@@ -129,6 +153,180 @@ impl TinCans {
         rx.await.unwrap_or_default()
     }
 
+    pub async fn latency(&self) -> latency::Snapshot {
+        use event::downstream::Info::*;
+
+        let (tx, rx) = replier();
+        if let Err(tincan::error::SendError(e)) =
+            self.downstream.send(Downstream::Info(Latency(tx)))
+        {
+            match e {
+                Downstream::Info(Latency(reply)) => {
+                    reply
+                        .lock()
+                        .take()
+                        .expect("if chan send failed, there can't be another contender")
+                        .send(latency::Snapshot::default())
+                        .ok();
+                },
+
+                _ => unreachable!(),
+            }
+        }
+
+        rx.await.unwrap_or_default()
+    }
+
+    pub async fn connection_stats(&self) -> HashMap<PeerId, quic::ConnectionStats> {
+        use event::downstream::Info::*;
+
+        let (tx, rx) = replier();
+        if let Err(tincan::error::SendError(e)) =
+            self.downstream.send(Downstream::Info(ConnectionStats(tx)))
+        {
+            match e {
+                Downstream::Info(ConnectionStats(reply)) => {
+                    reply
+                        .lock()
+                        .take()
+                        .expect("if chan send failed, there can't be another contender")
+                        .send(HashMap::default())
+                        .ok();
+                },
+
+                _ => unreachable!(),
+            }
+        }
+
+        rx.await.unwrap_or_default()
+    }
+
+    pub async fn pinned(&self) -> pinned::Snapshot {
+        use event::downstream::Info::*;
+
+        let (tx, rx) = replier();
+        if let Err(tincan::error::SendError(e)) = self.downstream.send(Downstream::Info(Pinned(tx)))
+        {
+            match e {
+                Downstream::Info(Pinned(reply)) => {
+                    reply
+                        .lock()
+                        .take()
+                        .expect("if chan send failed, there can't be another contender")
+                        .send(pinned::Snapshot::default())
+                        .ok();
+                },
+
+                _ => unreachable!(),
+            }
+        }
+
+        rx.await.unwrap_or_default()
+    }
+
+    pub async fn streams(&self) -> mux::Snapshot {
+        use event::downstream::Info::*;
+
+        let (tx, rx) = replier();
+        if let Err(tincan::error::SendError(e)) =
+            self.downstream.send(Downstream::Info(Streams(tx)))
+        {
+            match e {
+                Downstream::Info(Streams(reply)) => {
+                    reply
+                        .lock()
+                        .take()
+                        .expect("if chan send failed, there can't be another contender")
+                        .send(mux::Snapshot::default())
+                        .ok();
+                },
+
+                _ => unreachable!(),
+            }
+        }
+
+        rx.await.unwrap_or_default()
+    }
+
+    pub async fn clock_skew(&self) -> skew::Snapshot {
+        use event::downstream::Info::*;
+
+        let (tx, rx) = replier();
+        if let Err(tincan::error::SendError(e)) =
+            self.downstream.send(Downstream::Info(ClockSkew(tx)))
+        {
+            match e {
+                Downstream::Info(ClockSkew(reply)) => {
+                    reply
+                        .lock()
+                        .take()
+                        .expect("if chan send failed, there can't be another contender")
+                        .send(skew::Snapshot::default())
+                        .ok();
+                },
+
+                _ => unreachable!(),
+            }
+        }
+
+        rx.await.unwrap_or_default()
+    }
+
+    pub async fn liveness(&self) -> ping::Snapshot {
+        use event::downstream::Info::*;
+
+        let (tx, rx) = replier();
+        if let Err(tincan::error::SendError(e)) =
+            self.downstream.send(Downstream::Info(Liveness(tx)))
+        {
+            match e {
+                Downstream::Info(Liveness(reply)) => {
+                    reply
+                        .lock()
+                        .take()
+                        .expect("if chan send failed, there can't be another contender")
+                        .send(ping::Snapshot::default())
+                        .ok();
+                },
+
+                _ => unreachable!(),
+            }
+        }
+
+        rx.await.unwrap_or_default()
+    }
+
+    /// Ping `peer`, returning the round-trip time.
+    pub async fn ping(
+        &self,
+        peer: PeerId,
+        conn: quic::Connection,
+    ) -> Result<Duration, error::Ping> {
+        use event::downstream::Ping;
+
+        let (tx, rx) = replier();
+        if let Err(tincan::error::SendError(e)) = self.downstream.send(Downstream::Ping(Ping {
+            conn,
+            peer,
+            reply: tx,
+        })) {
+            match e {
+                Downstream::Ping(Ping { reply, .. }) => {
+                    reply
+                        .lock()
+                        .take()
+                        .expect("if chan send failed, there can't be another contender")
+                        .send(Err(error::Ping::Unavailable))
+                        .ok();
+                },
+
+                _ => unreachable!(),
+            }
+        }
+
+        rx.await.unwrap_or(Err(error::Ping::Unavailable))
+    }
+
     pub fn interrogate(&self, peer: PeerId, conn: quic::Connection) -> Interrogation {
         Interrogation {
             peer,
@@ -137,6 +335,60 @@ impl TinCans {
         }
     }
 
+    /// Reconcile the inventory with `peer`, see [`inventory`].
+    ///
+    /// Returns the subset of `interest` which `peer` has, and emits an
+    /// [`event::upstream::Inventory`] event on success.
+    pub async fn inventory(
+        &self,
+        peer: PeerId,
+        conn: quic::Connection,
+        interest: BTreeSet<Urn>,
+    ) -> Result<inventory::Shared, error::Inventory> {
+        use event::downstream::Inventory;
+
+        let mut request = inventory::request(&interest)?;
+        let mut shared = inventory::Shared::default();
+        loop {
+            let (tx, rx) = replier();
+            let msg = Downstream::Inventory(Inventory {
+                conn: conn.clone(),
+                peer,
+                request: request.clone(),
+                reply: tx,
+            });
+            if let Err(tincan::error::SendError(e)) = self.downstream.send(msg) {
+                match e {
+                    Downstream::Inventory(Inventory { reply, .. }) => {
+                        reply
+                            .lock()
+                            .take()
+                            .expect("if chan send failed, there can't be another contender")
+                            .send(Err(error::Inventory::Unavailable))
+                            .ok();
+                    },
+
+                    _ => unreachable!(),
+                }
+            }
+
+            let page = rx.await.unwrap_or(Err(error::Inventory::Unavailable))?;
+            let more = page.more;
+            request.after = page.urns.last().cloned();
+            shared.extend(&interest, page);
+            if !more || request.after.is_none() {
+                break;
+            }
+        }
+
+        self.emit(event::upstream::Inventory {
+            peer,
+            shared: shared.urns.clone(),
+            signed_refs: shared.signed_refs.clone(),
+        });
+        Ok(shared)
+    }
+
     pub async fn request_pull(&self, urn: Urn, conn: quic::Connection) -> RequestPull {
         let (tx, rx) = multi_replier();
         if let Err(tincan::error::SendError(e)) =
@@ -189,13 +441,145 @@ impl TinCans {
         rx.await.ok().flatten().map(Connected)
     }
 
+    pub async fn connections(&self) -> Vec<event::downstream::ConnectionInfo> {
+        use event::downstream::Info::*;
+
+        let (tx, rx) = replier();
+        if let Err(tincan::error::SendError(e)) =
+            self.downstream.send(Downstream::Info(Connections(tx)))
+        {
+            match e {
+                Downstream::Info(Connections(reply)) => {
+                    reply
+                        .lock()
+                        .take()
+                        .expect("if chan send failed, there can't be another contender")
+                        .send(vec![])
+                        .ok();
+                },
+
+                _ => unreachable!(),
+            }
+        }
+
+        rx.await.unwrap_or_default()
+    }
+
+    pub async fn disconnect(&self, peer: PeerId) -> bool {
+        use event::downstream::Disconnect;
+
+        let (tx, rx) = replier();
+        if let Err(tincan::error::SendError(e)) = self
+            .downstream
+            .send(Downstream::Disconnect(Disconnect { peer, reply: tx }))
+        {
+            match e {
+                Downstream::Disconnect(Disconnect { reply, .. }) => {
+                    reply
+                        .lock()
+                        .take()
+                        .expect("if chan send failed, there can't be another contender")
+                        .send(false)
+                        .ok();
+                },
+
+                _ => unreachable!(),
+            }
+        }
+
+        rx.await.unwrap_or_default()
+    }
+
     pub fn subscribe(&self) -> impl futures::Stream<Item = Result<event::Upstream, RecvError>> {
         let mut r = self.upstream.subscribe();
-        async_stream::stream! { loop { yield r.recv().await } }
+        async_stream::stream! { loop { yield r.recv().await.map(|s| s.event) } }
+    }
+
+    /// Subscribe to upstream events, starting after the event with sequence
+    /// number `after`.
+    ///
+    /// Events still retained in the journal (see [`JOURNAL_CAPACITY`]) are
+    /// replayed first. If events have been dropped, either because they are
+    /// no longer retained or because the subscriber lagged behind, a
+    /// [`event::upstream::Replay::Gap`] is yielded in their place. If
+    /// `after` is `None`, only new events are yielded.
+    ///
+    /// If `after` was not emitted yet, e.g. because it stems from a previous
+    /// run, a gap up to and including `after` is yielded, and the events are
+    /// replayed from the oldest one retained.
+    ///
+    /// The stream ends when the protocol shuts down.
+    pub fn subscribe_from(
+        &self,
+        after: Option<u64>,
+    ) -> impl futures::Stream<Item = event::upstream::Replay> {
+        use event::upstream::Replay;
+
+        let (mut r, reset, replay, mut next) = {
+            let journal = self.journal.lock();
+            let r = self.upstream.subscribe();
+            match after {
+                None => (r, None, vec![], journal.next),
+                Some(after) if after >= journal.next => {
+                    let replay = journal.events.iter().cloned().collect::<Vec<_>>();
+                    let next = replay.first().map_or(journal.next, |s| s.seq);
+                    (r, Some(Replay::Gap { from: 0, to: after }), replay, next)
+                },
+                Some(after) => {
+                    let replay = journal
+                        .events
+                        .iter()
+                        .filter(|s| s.seq > after)
+                        .cloned()
+                        .collect();
+                    (r, None, replay, after + 1)
+                },
+            }
+        };
+
+        async_stream::stream! {
+            if let Some(gap) = reset {
+                yield gap;
+            }
+            for s in replay {
+                if s.seq > next {
+                    yield Replay::Gap { from: next, to: s.seq - 1 };
+                }
+                next = s.seq + 1;
+                yield Replay::Event(s);
+            }
+
+            loop {
+                match r.recv().await {
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Ok(s) if s.seq < next => continue,
+                    Ok(s) => {
+                        if s.seq > next {
+                            yield Replay::Gap { from: next, to: s.seq - 1 };
+                        }
+                        next = s.seq + 1;
+                        yield Replay::Event(s);
+                    },
+                }
+            }
+        }
     }
 
     pub(crate) fn emit(&self, evt: impl Into<event::Upstream>) {
-        self.upstream.send(evt.into()).ok();
+        let mut journal = self.journal.lock();
+        let sequenced = event::upstream::Sequenced {
+            seq: journal.next,
+            event: evt.into(),
+        };
+        journal.next += 1;
+        if journal.events.len() >= JOURNAL_CAPACITY {
+            journal.events.pop_front();
+        }
+        journal.events.push_back(sequenced.clone());
+        // Send while holding the lock, so the journal and the channel agree
+        // on the order of events.
+        self.upstream.send(sequenced).ok();
     }
 }
 
@@ -257,6 +641,37 @@ impl Interrogation {
             })
     }
 
+    /// Ask the interrogated peer for its current time, and estimate the
+    /// offset of its clock relative to the local one.
+    pub async fn clock_skew(&self) -> Result<skew::Estimate, error::Interrogation> {
+        use interrogation::{Request, Response};
+
+        let sent = SystemTime::now();
+        self.request(Request::GetTime)
+            .await
+            .and_then(|resp| match resp {
+                Response::Time(remote) => Ok(skew::Estimate::new(sent, remote, SystemTime::now())),
+                Response::Error(e) => Err(error::Interrogation::ErrorResponse(e)),
+                _ => Err(error::Interrogation::InvalidResponse),
+            })
+    }
+
+    /// Ask the interrogated peer for the [`Rotation`] of its key it
+    /// announced, if any.
+    ///
+    /// The rotation is not verified.
+    pub async fn rotation(&self) -> Result<Option<Rotation>, error::Interrogation> {
+        use interrogation::{Request, Response};
+
+        self.request(Request::GetRotation)
+            .await
+            .and_then(|resp| match resp {
+                Response::Rotation(rotation) => Ok(rotation),
+                Response::Error(e) => Err(error::Interrogation::ErrorResponse(e)),
+                _ => Err(error::Interrogation::InvalidResponse),
+            })
+    }
+
     async fn request(
         &self,
         request: interrogation::Request,
@@ -0,0 +1,434 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Daily statistics of the data served to other peers.
+//!
+//! Operators of public seeds want to know how much, and by whom, the
+//! namespaces they host are fetched, without having to run a log pipeline.
+//! Every `upload-pack` request served is recorded in the [`Usage`] store,
+//! aggregated per day (UTC): per URN, the number of fetches, the bytes sent
+//! and the distinct peers fetching; per peer, the number of fetches and the
+//! bytes sent. A request counts as a fetch if the peer asked for objects, as
+//! opposed to only listing refs.
+//!
+//! Each [`Day`] is stored as JSON under `usage/<YYYY-MM-DD>.json`, relative to
+//! the git directory. The current day is kept in memory, and written at most
+//! every [`Config::flush_interval`], once the day is over, and when the store
+//! is dropped. Days older than [`Config::retention_days`] are removed.
+//!
+//! The recorded days can be exported as JSON or CSV, see [`export`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use time::{Date, Month, OffsetDateTime};
+
+use crate::{git::Urn, PeerId};
+
+/// The name of the usage directory, relative to the git directory.
+pub const DIR_NAME: &str = "usage";
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Read {
+        #[error("invalid date `{0}`, expected `YYYY-MM-DD`")]
+        InvalidDate(String),
+
+        #[error("malformed usage record `{path}`")]
+        Malformed {
+            path: String,
+            #[source]
+            source: serde_json::Error,
+        },
+
+        #[error(transparent)]
+        Io(#[from] io::Error),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Number of days to keep, including the current one.
+    ///
+    /// Default: 90
+    pub retention_days: u32,
+    /// Maximum time the record of the current day is kept in memory only.
+    ///
+    /// Default: 60s
+    pub flush_interval: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            retention_days: 90,
+            flush_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A single request served.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Served {
+    /// Whether the peer asked for objects.
+    pub fetch: bool,
+    /// The number of bytes sent in response.
+    pub bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Counters {
+    pub fetches: u64,
+    pub bytes: u64,
+}
+
+impl Counters {
+    fn add(&mut self, served: Served) {
+        self.fetches += u64::from(served.fetch);
+        self.bytes = self.bytes.saturating_add(served.bytes);
+    }
+}
+
+/// The usage of a single namespace.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Namespace {
+    #[serde(flatten)]
+    pub counters: Counters,
+    /// The peers the namespace was served to.
+    pub peers: BTreeSet<PeerId>,
+}
+
+/// The usage of a single day.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Day {
+    /// The date, as `YYYY-MM-DD`.
+    pub date: String,
+    /// Usage by URN.
+    pub urns: BTreeMap<String, Namespace>,
+    /// Usage by peer.
+    pub peers: BTreeMap<PeerId, Counters>,
+}
+
+impl Day {
+    fn new(date: String) -> Self {
+        Self {
+            date,
+            ..Default::default()
+        }
+    }
+
+    /// The usage summed over all peers.
+    pub fn total(&self) -> Counters {
+        self.peers
+            .values()
+            .fold(Counters::default(), |acc, c| Counters {
+                fetches: acc.fetches + c.fetches,
+                bytes: acc.bytes.saturating_add(c.bytes),
+            })
+    }
+
+    fn add(&mut self, urn: &Urn, peer: PeerId, served: Served) {
+        let ns = self
+            .urns
+            .entry(urn.clone().with_path(None).to_string())
+            .or_default();
+        ns.counters.add(served);
+        ns.peers.insert(peer);
+        self.peers.entry(peer).or_default().add(served);
+    }
+}
+
+/// The formats [`Day`]s can be exported in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// An array of [`Day`]s.
+    Json,
+    /// One row per day and URN, day and peer, and day, with the columns
+    /// `date,kind,id,fetches,bytes,peers`. `kind` is one of `urn`, `peer` or
+    /// `total`, `peers` is the number of distinct peers served.
+    Csv,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+        })
+    }
+}
+
+impl FromStr for Format {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => Err("expected `json` or `csv`"),
+        }
+    }
+}
+
+/// Write `days` to `out` in the given `format`.
+pub fn export<W>(days: &[Day], format: Format, mut out: W) -> io::Result<()>
+where
+    W: io::Write,
+{
+    match format {
+        Format::Json => serde_json::to_writer_pretty(&mut out, days)?,
+        Format::Csv => {
+            writeln!(out, "date,kind,id,fetches,bytes,peers")?;
+            for day in days {
+                let total = day.total();
+                writeln!(
+                    out,
+                    "{},total,,{},{},{}",
+                    day.date,
+                    total.fetches,
+                    total.bytes,
+                    day.peers.len()
+                )?;
+                for (urn, ns) in &day.urns {
+                    writeln!(
+                        out,
+                        "{},urn,{},{},{},{}",
+                        day.date,
+                        urn,
+                        ns.counters.fetches,
+                        ns.counters.bytes,
+                        ns.peers.len()
+                    )?;
+                }
+                for (peer, counters) in &day.peers {
+                    writeln!(
+                        out,
+                        "{},peer,{},{},{},",
+                        day.date, peer, counters.fetches, counters.bytes
+                    )?;
+                }
+            }
+        },
+    }
+    out.flush()
+}
+
+struct Inner {
+    root: PathBuf,
+    today: Day,
+    dirty: bool,
+    flushed: Instant,
+}
+
+impl Inner {
+    fn flush(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        match store(&self.root, &self.today) {
+            Ok(()) => {
+                self.dirty = false;
+                self.flushed = Instant::now();
+            },
+            Err(e) => tracing::warn!(err = %e, date = %self.today.date, "failed to persist usage"),
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+/// Persistent store of [`Day`]s.
+#[derive(Clone)]
+pub struct Usage {
+    config: Config,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Usage {
+    /// Open the store in `git_dir`, removing the days past
+    /// [`Config::retention_days`].
+    pub fn open(config: Config, git_dir: &Path) -> Result<Self, error::Read> {
+        let root = git_dir.join(DIR_NAME);
+        let date = date_of(SystemTime::now());
+        prune(&root, date, config.retention_days)?;
+        let date = date.to_string();
+        let today = load(&root, &date)?.unwrap_or_else(|| Day::new(date));
+        Ok(Self {
+            config,
+            inner: Arc::new(Mutex::new(Inner {
+                root,
+                today,
+                dirty: false,
+                flushed: Instant::now(),
+            })),
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Record a request for `urn` served to `peer`.
+    pub fn record(&self, urn: &Urn, peer: PeerId, served: Served) {
+        self.record_at(SystemTime::now(), urn, peer, served)
+    }
+
+    /// Record a request for `urn` served to `peer` at the given time.
+    ///
+    /// `at` is not expected to go backwards: if it falls on another day than
+    /// the previous record, that day becomes the current one.
+    pub fn record_at(&self, at: SystemTime, urn: &Urn, peer: PeerId, served: Served) {
+        let date = date_of(at);
+        let mut inner = self.inner.lock();
+        if inner.today.date != date.to_string() {
+            inner.flush();
+            if let Err(e) = prune(&inner.root, date, self.config.retention_days) {
+                tracing::warn!(err = %e, "failed to remove expired usage");
+            }
+            let date = date.to_string();
+            inner.today = match load(&inner.root, &date) {
+                Ok(day) => day.unwrap_or_else(|| Day::new(date)),
+                Err(e) => {
+                    tracing::warn!(err = %e, %date, "failed to load usage, starting afresh");
+                    Day::new(date)
+                },
+            };
+        }
+
+        inner.today.add(urn, peer, served);
+        inner.dirty = true;
+        if inner.flushed.elapsed() >= self.config.flush_interval {
+            inner.flush()
+        }
+    }
+
+    /// Write the record of the current day.
+    pub fn flush(&self) {
+        self.inner.lock().flush()
+    }
+
+    /// The recorded [`Day`]s between `from` and `to`, inclusive, ordered by
+    /// date. The dates are given as `YYYY-MM-DD`, and default to the oldest
+    /// and the current day respectively.
+    pub fn days(&self, from: Option<&str>, to: Option<&str>) -> Result<Vec<Day>, error::Read> {
+        let from = from.map(parse_date).transpose()?.map(|d| d.to_string());
+        let to = to.map(parse_date).transpose()?.map(|d| d.to_string());
+        let in_range = |date: &str| {
+            from.as_deref().map_or(true, |from| date >= from)
+                && to.as_deref().map_or(true, |to| date <= to)
+        };
+
+        let (root, today) = {
+            let inner = self.inner.lock();
+            (inner.root.clone(), inner.today.clone())
+        };
+        let mut days = Vec::new();
+        for date in dates(&root)? {
+            if date != today.date && in_range(&date) {
+                days.extend(load(&root, &date)?);
+            }
+        }
+        if in_range(&today.date) {
+            days.push(today);
+        }
+        days.sort_by(|a, b| a.date.cmp(&b.date));
+
+        Ok(days)
+    }
+}
+
+/// Remove the days which are past `retention_days` as of `today`.
+fn prune(root: &Path, today: Date, retention_days: u32) -> io::Result<()> {
+    let keep = i64::from(retention_days.max(1)) - 1;
+    let oldest = (today - time::Duration::days(keep)).to_string();
+    for date in dates(root)? {
+        if date < oldest {
+            fs::remove_file(path(root, &date))?;
+        }
+    }
+    Ok(())
+}
+
+fn date_of(at: SystemTime) -> Date {
+    OffsetDateTime::from(at).date()
+}
+
+fn parse_date(s: &str) -> Result<Date, error::Read> {
+    let invalid = || error::Read::InvalidDate(s.to_owned());
+    let mut parts = s.splitn(3, '-');
+    let mut next = || parts.next().ok_or_else(invalid);
+    let year = next()?.parse::<i32>().map_err(|_| invalid())?;
+    let month = next()?.parse::<u8>().map_err(|_| invalid())?;
+    let day = next()?.parse::<u8>().map_err(|_| invalid())?;
+    let month = Month::try_from(month).map_err(|_| invalid())?;
+    Date::from_calendar_date(year, month, day).map_err(|_| invalid())
+}
+
+fn path(root: &Path, date: &str) -> PathBuf {
+    root.join(format!("{}.json", date))
+}
+
+/// The dates of the recorded days, in no particular order.
+fn dates(root: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut dates = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name();
+        if let Some(date) = name.to_str().and_then(|name| name.strip_suffix(".json")) {
+            if parse_date(date).is_ok() {
+                dates.push(date.to_owned());
+            }
+        }
+    }
+    Ok(dates)
+}
+
+fn load(root: &Path, date: &str) -> Result<Option<Day>, error::Read> {
+    let path = path(root, date);
+    match fs::read(&path) {
+        Ok(bytes) => {
+            serde_json::from_slice(&bytes)
+                .map(Some)
+                .map_err(|source| error::Read::Malformed {
+                    path: path.display().to_string(),
+                    source,
+                })
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn store(root: &Path, day: &Day) -> io::Result<()> {
+    fs::create_dir_all(root)?;
+    let path = path(root, &day.date);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(day)?)?;
+    fs::rename(&tmp, &path)
+}
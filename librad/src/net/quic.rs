@@ -11,6 +11,7 @@ pub use connection::{
     BoxedIncomingStreams,
     Connection,
     ConnectionId,
+    ConnectionStats,
     Conntrack,
     IncomingStreams,
 };
@@ -21,6 +22,9 @@ pub use endpoint::{BoundEndpoint, Endpoint, IncomingConnections};
 pub mod error;
 pub use error::{Error, Result};
 
+pub mod shaping;
+pub use shaping::{Shaper, TrafficClass};
+
 mod stream;
 pub use stream::{BidiStream, RecvStream, SendStream};
 
@@ -41,5 +45,13 @@ const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
 /// keep alive probes. Should tolerate the loss of 1-2 keep-alive probes.
 pub(in crate::net) const MAX_IDLE_TIMEOUT: Duration = Duration::from_secs(65);
 
+/// Maximum number of bytes sent on a connection which were not yet
+/// acknowledged by the remote peer.
+///
+/// Bounds the memory held for retransmissions, in addition to the congestion
+/// window (see [`ConnectionStats::cwnd`]), which only reflects what the link
+/// is estimated to sustain.
+const MAX_BYTES_IN_FLIGHT: u64 = 8 * 1024 * 1024;
+
 /// Maximum number of connections to a single peer.
 const MAX_PEER_CONNECTIONS: usize = 5;
@@ -13,6 +13,7 @@ use std::{
     result::Result as StdResult,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use either::Either;
@@ -23,7 +24,7 @@ use futures::{
 use quinn::NewConnection;
 use thiserror::Error;
 
-use super::{BidiStream, Error, RecvStream, Result, SendStream};
+use super::{BidiStream, Error, RecvStream, Result, SendStream, Shaper};
 use crate::{
     net::connection::{CloseReason, RemoteAddr, RemotePeer},
     PeerId,
@@ -65,27 +66,14 @@ fn incoming_streams(
         let conn = conn.clone();
         bi_streams.map_ok(move |(send, recv)| {
             conn.tickle();
-            Left(BidiStream {
-                conn: conn.clone(),
-                send: SendStream {
-                    conn: conn.clone(),
-                    send,
-                },
-                recv: RecvStream {
-                    conn: conn.clone(),
-                    recv,
-                },
-            })
+            Left(BidiStream::new(conn.clone(), send, recv))
         })
     };
     let uni = {
         let conn = conn.clone();
         uni_streams.map_ok(move |recv| {
             conn.tickle();
-            Right(RecvStream {
-                conn: conn.clone(),
-                recv,
-            })
+            Right(RecvStream::new(conn.clone(), recv))
         })
     };
     let inner = stream::select(bidi, uni).map_err(move |e| {
@@ -139,12 +127,14 @@ pub struct Connection {
     peer: PeerId,
     conn: quinn::Connection,
     track: Conntrack,
+    shaper: Shaper,
     send_streams: Arc<Vec<Mutex<Option<SendStream>>>>,
 }
 
 impl Connection {
     pub(super) fn new(
         track: Conntrack,
+        shaper: Shaper,
         reserve_send_streams: usize,
         remote_peer: PeerId,
         NewConnection {
@@ -161,6 +151,7 @@ impl Connection {
             peer: remote_peer,
             conn: connection,
             track,
+            shaper,
             send_streams: Arc::new(
                 iter::repeat_with(Default::default)
                     .take(reserve_send_streams)
@@ -184,17 +175,7 @@ impl Connection {
         })?;
         self.tickle();
 
-        Ok(BidiStream {
-            conn: self.clone(),
-            recv: RecvStream {
-                conn: self.clone(),
-                recv,
-            },
-            send: SendStream {
-                conn: self.clone(),
-                send,
-            },
-        })
+        Ok(BidiStream::new(self.clone(), send, recv))
     }
 
     pub async fn open_uni(&self) -> Result<SendStream> {
@@ -205,10 +186,7 @@ impl Connection {
         })?;
         self.tickle();
 
-        Ok(SendStream {
-            conn: self.clone(),
-            send,
-        })
+        Ok(SendStream::new(self.clone(), send))
     }
 
     /// Borrow the [`SendStream`] at index `idx` from a fixed-size set of
@@ -260,9 +238,66 @@ impl Connection {
         self.track.tickle(&self.id())
     }
 
+    pub(super) fn shaper(&self) -> &Shaper {
+        &self.shaper
+    }
+
     pub fn stable_id(&self) -> usize {
         self.conn.stable_id()
     }
+
+    /// The current best estimate of this connection's round-trip time.
+    pub fn rtt(&self) -> Duration {
+        self.conn.rtt()
+    }
+
+    /// The total number of packets sent, and the number of those deemed lost,
+    /// on this connection's current path.
+    pub fn packet_stats(&self) -> (u64, u64) {
+        let path = self.conn.stats().path;
+        (path.sent_packets, path.lost_packets)
+    }
+
+    /// Transport statistics of this connection, as reported by the QUIC stack.
+    pub fn stats(&self) -> ConnectionStats {
+        ConnectionStats::from(self.conn.stats())
+    }
+}
+
+/// Transport statistics of a [`Connection`].
+///
+/// The path statistics refer to the current path of the connection, ie. they
+/// are reset if the connection migrates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// Current best estimate of the round-trip time.
+    pub rtt: Duration,
+    /// Current congestion window, in bytes.
+    pub cwnd: u64,
+    /// Number of times the congestion controller entered recovery.
+    pub congestion_events: u64,
+    /// Number of packets sent.
+    pub sent_packets: u64,
+    /// Number of packets deemed lost.
+    pub lost_packets: u64,
+    /// Number of bytes sent in UDP datagrams.
+    pub sent_bytes: u64,
+    /// Number of bytes received in UDP datagrams.
+    pub recv_bytes: u64,
+}
+
+impl From<quinn::ConnectionStats> for ConnectionStats {
+    fn from(stats: quinn::ConnectionStats) -> Self {
+        Self {
+            rtt: stats.path.rtt,
+            cwnd: stats.path.cwnd,
+            congestion_events: stats.path.congestion_events,
+            sent_packets: stats.path.sent_packets,
+            lost_packets: stats.path.lost_packets,
+            sent_bytes: stats.udp_tx.bytes,
+            recv_bytes: stats.udp_rx.bytes,
+        }
+    }
 }
 
 impl RemotePeer for Connection {
@@ -13,6 +13,7 @@ use std::{
         Weak,
     },
     thread,
+    time::Instant,
 };
 
 use dashmap::DashMap;
@@ -34,6 +35,7 @@ type PeerConnections = DashMap<PeerId, Vec<Weak<Tracked>>, BuildHasherDefault<Fx
 struct Tracked {
     connection: Connection,
     epoch: AtomicUsize,
+    established: Instant,
 }
 
 impl Tracked {
@@ -125,6 +127,17 @@ impl Conntrack {
         self.peer_connections.iter().map(|i| *(i.key())).collect()
     }
 
+    /// Get all tracked connections, along with the time they were
+    /// established.
+    ///
+    /// Liveness of the connections is not checked.
+    pub fn connections(&self) -> Vec<(Connection, Instant)> {
+        self.connections
+            .iter()
+            .map(|r| (r.value().connection.clone(), r.value().established))
+            .collect()
+    }
+
     /// Try to get an active connection to the given peer.
     ///
     /// If multiple connections exist for the given peer, the most recent one is
@@ -169,6 +182,7 @@ impl Conntrack {
             let strong = Arc::new(Tracked {
                 connection: conn.clone(),
                 epoch: AtomicUsize::new(self.epoch.load(SeqCst)),
+                established: Instant::now(),
             });
             let weak = Arc::downgrade(&strong);
             self.connections.insert(conn.id(), strong);
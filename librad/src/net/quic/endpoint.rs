@@ -9,6 +9,7 @@ use std::{
     net::{SocketAddr, UdpSocket},
     pin::Pin,
     sync::{Arc, Weak},
+    time::Instant,
 };
 
 use futures::stream::{BoxStream, StreamExt as _, TryStreamExt as _};
@@ -19,7 +20,15 @@ use parking_lot::RwLock;
 use quinn::{NewConnection, TransportConfig};
 use socket2::{Domain, Protocol, Socket, Type};
 
-use super::{BoxedIncomingStreams, Connection, Conntrack, Error, Result};
+use super::{
+    shaping::{self, Shaper},
+    BoxedIncomingStreams,
+    Connection,
+    ConnectionStats,
+    Conntrack,
+    Error,
+    Result,
+};
 use crate::{
     net::{
         connection::{CloseReason, LocalAddr, LocalPeer},
@@ -63,6 +72,7 @@ pub struct Endpoint<const R: usize> {
     endpoint: quinn::Endpoint,
     listen_addrs: Arc<RwLock<BTreeSet<SocketAddr>>>,
     conntrack: Conntrack,
+    shaper: Shaper,
     _refcount: Arc<()>,
 }
 
@@ -73,6 +83,7 @@ impl<const R: usize> Endpoint<R> {
         listen_addr: SocketAddr,
         advertised_addrs: Option<NonEmpty<SocketAddr>>,
         network: Network,
+        shaping: shaping::Config,
     ) -> Result<BoundEndpoint<'a, R>>
     where
         S: Signer + Clone + Send + Sync + 'static,
@@ -96,17 +107,20 @@ impl<const R: usize> Endpoint<R> {
 
         let (endpoint, incoming) = make_endpoint(signer, sock, alpn(network)).await?;
         let conntrack = Conntrack::new();
+        let shaper = Shaper::new(shaping);
         let endpoint = Endpoint {
             peer_id,
             endpoint,
             listen_addrs: addrs,
             conntrack: conntrack.clone(),
+            shaper: shaper.clone(),
             _refcount: Arc::new(()),
         };
         let incoming = incoming
             .map(Ok)
             .and_then(move |connecting| {
                 let conntrack = conntrack.clone();
+                let shaper = shaper.clone();
                 async move {
                     let conn = connecting.await?;
                     let remote_peer = remote_peer(&conn)?;
@@ -114,7 +128,8 @@ impl<const R: usize> Endpoint<R> {
                         remote_peer != peer_id,
                         "self-connections are prevented in the TLS handshake"
                     );
-                    let (conn, streams) = Connection::new(conntrack.clone(), R, remote_peer, conn);
+                    let (conn, streams) =
+                        Connection::new(conntrack.clone(), shaper, R, remote_peer, conn);
                     conntrack.connected(&conn);
 
                     Ok((conn, streams.boxed()))
@@ -141,6 +156,23 @@ impl<const R: usize> Endpoint<R> {
         self.conntrack.peers()
     }
 
+    /// The [`ConnectionStats`] of the connection to each connected peer.
+    ///
+    /// If there are multiple connections to a peer, the one which would be
+    /// returned by [`Endpoint::get_connection`] is used.
+    pub fn connection_stats(&self) -> HashMap<PeerId, ConnectionStats> {
+        self.conntrack
+            .peers()
+            .into_iter()
+            .filter_map(|peer| Some((peer, self.conntrack.get(peer)?.stats())))
+            .collect()
+    }
+
+    /// All connections, along with the time they were established.
+    pub fn connections(&self) -> Vec<(Connection, Instant)> {
+        self.conntrack.connections()
+    }
+
     pub async fn connect<'a>(
         &mut self,
         peer: PeerId,
@@ -154,7 +186,8 @@ impl<const R: usize> Endpoint<R> {
             .endpoint
             .connect(addr, peer.as_dns_name().as_ref().into())?
             .await?;
-        let (conn, streams) = Connection::new(self.conntrack.clone(), R, peer, conn);
+        let (conn, streams) =
+            Connection::new(self.conntrack.clone(), self.shaper.clone(), R, peer, conn);
         self.conntrack.connected(&conn);
 
         Ok((conn, streams.boxed()))
@@ -335,6 +368,7 @@ where
 
     let mut transport_config = TransportConfig::default();
     transport_config
+        .send_window(super::MAX_BYTES_IN_FLIGHT)
         .keep_alive_interval(Some(super::KEEP_ALIVE_INTERVAL))
         // Set idle timeout anyway, as the default is smaller than our
         // keep-alive
@@ -358,6 +392,7 @@ where
 
     let mut transport_config = TransportConfig::default();
     transport_config
+        .send_window(super::MAX_BYTES_IN_FLIGHT)
         .max_idle_timeout(Some(super::MAX_IDLE_TIMEOUT))
         .expect("idle timeout is in vetted range");
 
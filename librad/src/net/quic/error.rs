@@ -6,6 +6,8 @@
 use std::io;
 use thiserror::Error;
 
+use crate::net::connection::CloseReason;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, Error)]
@@ -45,3 +47,52 @@ pub enum Connection {
     #[error(transparent)]
     Connection(#[from] quinn::ConnectionError),
 }
+
+impl Error {
+    /// The [`CloseReason`] the remote end gave for closing the connection, if
+    /// any.
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        match self {
+            Self::Connection(e) => connection_close_reason(e),
+            Self::Io(e) => close_reason(e),
+            _ => None,
+        }
+    }
+}
+
+/// The [`CloseReason`] the remote end gave for resetting or stopping the
+/// stream an I/O error was encountered on, or for closing the connection
+/// altogether, if any.
+///
+/// This allows to distinguish eg. a stream refused because of
+/// [`CloseReason::StreamLimit`] (which may be retried later) from other
+/// failures.
+pub fn close_reason(err: &io::Error) -> Option<CloseReason> {
+    let inner = err.get_ref()?;
+    if let Some(e) = inner.downcast_ref::<quinn::ReadError>() {
+        match e {
+            quinn::ReadError::Reset(code) => CloseReason::from_code(code.into_inner()),
+            quinn::ReadError::ConnectionLost(e) => connection_close_reason(e),
+            _ => None,
+        }
+    } else if let Some(e) = inner.downcast_ref::<quinn::WriteError>() {
+        match e {
+            quinn::WriteError::Stopped(code) => CloseReason::from_code(code.into_inner()),
+            quinn::WriteError::ConnectionLost(e) => connection_close_reason(e),
+            _ => None,
+        }
+    } else {
+        inner
+            .downcast_ref::<quinn::ConnectionError>()
+            .and_then(connection_close_reason)
+    }
+}
+
+fn connection_close_reason(err: &quinn::ConnectionError) -> Option<CloseReason> {
+    match err {
+        quinn::ConnectionError::ApplicationClosed(close) => {
+            CloseReason::from_code(close.error_code.into_inner())
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Bandwidth shaping by traffic class.
+//!
+//! Streams carry either [`TrafficClass::Interactive`] protocol messages
+//! (gossip, membership, interrogation, ...) or [`TrafficClass::Bulk`] data
+//! (git packfiles). On constrained links, a large replication can saturate the
+//! link, delaying protocol messages to the point where peers consider the
+//! connection dead. To prevent this:
+//!
+//! * bulk streams are sent at a lower QUIC stream priority, so that pending
+//!   interactive data is always sent first, and
+//! * the total throughput of all bulk streams of an endpoint can be capped via
+//!   [`Config::bulk_ceiling`], leaving headroom for interactive traffic and
+//!   other applications sharing the link.
+
+use std::{
+    future::Future,
+    num::NonZeroU32,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::future;
+use parking_lot::Mutex;
+
+/// The smallest amount of bytes a bulk stream waits for, unless less is
+/// requested. Prevents degenerating into tiny reads and writes when the bucket
+/// is drained.
+const MIN_GRANT: usize = 16 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TrafficClass {
+    /// Latency-sensitive protocol messages.
+    Interactive,
+    /// Throughput-oriented transfers, which may be delayed in favour of
+    /// interactive traffic.
+    Bulk,
+}
+
+impl TrafficClass {
+    /// The QUIC send priority of streams of this class. Higher values are
+    /// sent first.
+    pub fn priority(self) -> i32 {
+        match self {
+            Self::Interactive => 0,
+            Self::Bulk => -1,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Interactive => 0,
+            Self::Bulk => 1,
+        }
+    }
+
+    fn from_u8(x: u8) -> Self {
+        match x {
+            1 => Self::Bulk,
+            _ => Self::Interactive,
+        }
+    }
+}
+
+impl Default for TrafficClass {
+    fn default() -> Self {
+        Self::Interactive
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Config {
+    /// Maximum number of bytes per second all [`TrafficClass::Bulk`] streams
+    /// of an endpoint may send and receive, each. Unlimited if `None`.
+    pub bulk_ceiling: Option<NonZeroU32>,
+}
+
+/// Shared bandwidth budget of the streams of an endpoint.
+#[derive(Clone, Default)]
+pub struct Shaper {
+    bulk: Option<Arc<Buckets>>,
+}
+
+struct Buckets {
+    send: Mutex<Bucket>,
+    recv: Mutex<Bucket>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Send,
+    Recv,
+}
+
+impl Shaper {
+    pub fn new(config: Config) -> Self {
+        Self {
+            bulk: config.bulk_ceiling.map(|ceiling| {
+                Arc::new(Buckets {
+                    send: Mutex::new(Bucket::new(ceiling)),
+                    recv: Mutex::new(Bucket::new(ceiling)),
+                })
+            }),
+        }
+    }
+
+    /// Wait until up to `want` bytes may be transferred in `dir` by a stream of
+    /// `class`, returning the number of bytes granted.
+    ///
+    /// The result is greater than zero if `want` is, but may be less than
+    /// `want`.
+    pub async fn acquire(&self, class: TrafficClass, dir: Direction, want: usize) -> usize {
+        let mut delay = Delay::default();
+        future::poll_fn(|cx| self.poll_acquire(class, dir, &mut delay, cx, want)).await
+    }
+
+    /// Like [`Shaper::acquire`], but arming a timer in `delay` if the budget
+    /// is exhausted. The task is woken once enough budget is available.
+    pub(super) fn poll_acquire(
+        &self,
+        class: TrafficClass,
+        dir: Direction,
+        delay: &mut Delay,
+        cx: &mut Context,
+        want: usize,
+    ) -> Poll<usize> {
+        let bucket = match self.bucket(class, dir) {
+            Some(bucket) if want > 0 => bucket,
+            _ => return Poll::Ready(want),
+        };
+
+        loop {
+            let sleep = delay.0.get_mut();
+            if let Some(timer) = sleep.as_mut() {
+                match timer.as_mut().poll(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(()) => *sleep = None,
+                }
+            }
+            match bucket.lock().take(want) {
+                Ok(granted) => return Poll::Ready(granted),
+                Err(wait) => *sleep = Some(Box::pin(link_async::sleep(wait))),
+            }
+        }
+    }
+
+    /// Return `unused` bytes previously obtained via [`Shaper::poll_acquire`],
+    /// eg. because the transfer was shorter than requested.
+    pub(super) fn release(&self, class: TrafficClass, dir: Direction, unused: usize) {
+        if let Some(bucket) = self.bucket(class, dir) {
+            bucket.lock().put(unused)
+        }
+    }
+
+    fn bucket(&self, class: TrafficClass, dir: Direction) -> Option<&Mutex<Bucket>> {
+        match (&self.bulk, class) {
+            (Some(buckets), TrafficClass::Bulk) => Some(match dir {
+                Direction::Send => &buckets.send,
+                Direction::Recv => &buckets.recv,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Token bucket holding up to one second worth of bytes.
+struct Bucket {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(ceiling: NonZeroU32) -> Self {
+        let rate = f64::from(ceiling.get());
+        Self {
+            rate,
+            tokens: rate,
+            last: Instant::now(),
+        }
+    }
+
+    fn take(&mut self, want: usize) -> Result<usize, Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.last = now;
+
+        let need = want.min(MIN_GRANT).min(self.rate as usize).max(1) as f64;
+        if self.tokens >= need {
+            let granted = want.min(self.tokens as usize);
+            self.tokens -= granted as f64;
+            Ok(granted)
+        } else {
+            Err(Duration::from_secs_f64((need - self.tokens) / self.rate))
+        }
+    }
+
+    fn put(&mut self, unused: usize) {
+        self.tokens = (self.tokens + unused as f64).min(self.rate);
+    }
+}
+
+/// Pending wake-up of a stream waiting for budget.
+#[derive(Default)]
+pub(super) struct Delay(Mutex<Option<Pin<Box<dyn Future<Output = ()> + Send>>>>);
+
+/// The [`TrafficClass`] of a stream, which can be changed after the stream was
+/// created (eg. after a protocol upgrade).
+#[derive(Default)]
+pub(super) struct Class(AtomicU8);
+
+impl Class {
+    pub fn get(&self) -> TrafficClass {
+        TrafficClass::from_u8(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, class: TrafficClass) {
+        self.0.store(class.to_u8(), Ordering::Relaxed)
+    }
+}
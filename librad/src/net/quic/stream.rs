@@ -13,7 +13,10 @@ use std::{
 use futures::io::{AsyncRead, AsyncWrite};
 use quinn::VarInt;
 
-use super::Connection;
+use super::{
+    shaping::{Class, Delay, Direction, TrafficClass},
+    Connection,
+};
 use crate::{
     net::connection::{CloseReason, Duplex, RemoteAddr, RemotePeer},
     PeerId,
@@ -26,6 +29,26 @@ pub struct BidiStream {
 }
 
 impl BidiStream {
+    pub(super) fn new(conn: Connection, send: quinn::SendStream, recv: quinn::RecvStream) -> Self {
+        Self {
+            send: SendStream::new(conn.clone(), send),
+            recv: RecvStream::new(conn.clone(), recv),
+            conn,
+        }
+    }
+
+    /// Assign both directions of the stream to `class`.
+    ///
+    /// Streams start out as [`TrafficClass::Interactive`].
+    pub fn set_class(&self, class: TrafficClass) {
+        self.send.set_class(class);
+        self.recv.set_class(class);
+    }
+
+    pub fn class(&self) -> TrafficClass {
+        self.send.class()
+    }
+
     pub fn close(self, reason: CloseReason) {
         self.send.close(reason);
         self.recv.close(reason);
@@ -92,9 +115,28 @@ impl AsyncWrite for BidiStream {
 pub struct RecvStream {
     pub(super) conn: Connection,
     pub(super) recv: quinn::RecvStream,
+    class: Class,
+    delay: Delay,
 }
 
 impl RecvStream {
+    pub(super) fn new(conn: Connection, recv: quinn::RecvStream) -> Self {
+        Self {
+            conn,
+            recv,
+            class: Class::default(),
+            delay: Delay::default(),
+        }
+    }
+
+    pub fn set_class(&self, class: TrafficClass) {
+        self.class.set(class)
+    }
+
+    pub fn class(&self) -> TrafficClass {
+        self.class.get()
+    }
+
     pub fn close(mut self, reason: CloseReason) {
         let _ = self.recv.stop(VarInt::from_u32(reason as u32));
     }
@@ -141,7 +183,22 @@ impl AsyncRead for RecvStream {
         buf: &mut [u8],
     ) -> Poll<io::Result<usize>> {
         let this = self.get_mut();
-        let res = AsyncRead::poll_read(Pin::new(&mut this.recv), cx, buf);
+        let n = futures::ready!(this.conn.shaper().poll_acquire(
+            this.class.get(),
+            Direction::Recv,
+            &mut this.delay,
+            cx,
+            buf.len()
+        ));
+        let res = AsyncRead::poll_read(Pin::new(&mut this.recv), cx, &mut buf[..n]);
+
+        let used = match &res {
+            Poll::Ready(Ok(m)) => *m,
+            _ => 0,
+        };
+        this.conn
+            .shaper()
+            .release(this.class.get(), Direction::Recv, n - used);
 
         if let Poll::Ready(ready) = &res {
             match ready {
@@ -157,9 +214,32 @@ impl AsyncRead for RecvStream {
 pub struct SendStream {
     pub(super) conn: Connection,
     pub(super) send: quinn::SendStream,
+    class: Class,
+    delay: Delay,
 }
 
 impl SendStream {
+    pub(super) fn new(conn: Connection, send: quinn::SendStream) -> Self {
+        Self {
+            conn,
+            send,
+            class: Class::default(),
+            delay: Delay::default(),
+        }
+    }
+
+    /// Assign the stream to `class`, adjusting its send priority.
+    pub fn set_class(&self, class: TrafficClass) {
+        self.class.set(class);
+        if let Err(e) = self.send.set_priority(class.priority()) {
+            tracing::debug!(err = ?e, "failed to set stream priority");
+        }
+    }
+
+    pub fn class(&self) -> TrafficClass {
+        self.class.get()
+    }
+
     pub fn close(mut self, reason: CloseReason) {
         let _ = self.send.reset(VarInt::from_u32(reason as u32));
     }
@@ -202,7 +282,22 @@ impl RemoteAddr for SendStream {
 impl AsyncWrite for SendStream {
     fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
         let this = self.get_mut();
-        let res = AsyncWrite::poll_write(Pin::new(&mut this.send), cx, buf);
+        let n = futures::ready!(this.conn.shaper().poll_acquire(
+            this.class.get(),
+            Direction::Send,
+            &mut this.delay,
+            cx,
+            buf.len()
+        ));
+        let res = AsyncWrite::poll_write(Pin::new(&mut this.send), cx, &buf[..n]);
+
+        let used = match &res {
+            Poll::Ready(Ok(m)) => *m,
+            _ => 0,
+        };
+        this.conn
+            .shaper()
+            .release(this.class.get(), Direction::Send, n - used);
 
         if let Poll::Ready(ready) = &res {
             match ready {
@@ -15,9 +15,11 @@
 #[cfg(not(feature = "replication-v3"))]
 mod v2;
 #[cfg(not(feature = "replication-v3"))]
-pub use v2::{error, Config, IdStatus, Mode, Replication, Success};
+pub use v2::{error, Config, DryRun, IdStatus, Mode, RefUpdate, Replication, Review, Success};
 
 #[cfg(feature = "replication-v3")]
 mod v3;
 #[cfg(feature = "replication-v3")]
-pub use v3::{error, Config, Replication, Success};
+pub use v3::{error, hooks, Config, Replication, Success};
+
+pub use link_git::protocol::meter::Transfer;
@@ -22,7 +22,7 @@ use crate::{
     PeerId,
 };
 
-pub use legacy::{IdStatus, Mode};
+pub use legacy::{DryRun, IdStatus, Mode, RefUpdate, Review};
 
 pub mod error {
     use super::*;
@@ -38,10 +38,18 @@ pub mod error {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct Config {
     pub limit: git::fetch::Limit,
     pub wait_slot: Duration,
+    /// Check the branches of the peer replicated from against the commit
+    /// signature policy of the project, see [`crate::git::signatures`].
+    pub verify_signatures: bool,
+    /// Confirm identity updates before adopting them, see
+    /// [`legacy::Config::review`].
+    ///
+    /// Only meaningful in interactive contexts, so unset by default.
+    pub review: Option<Review>,
 }
 
 impl Default for Config {
@@ -49,6 +57,8 @@ impl Default for Config {
         Self {
             limit: git::fetch::Limit::default(),
             wait_slot: Duration::from_secs(20),
+            verify_signatures: false,
+            review: None,
         }
     }
 }
@@ -90,12 +100,43 @@ impl Replication {
             {
                 let config = legacy::Config {
                     fetch_limit: self.config.limit,
+                    review: self.config.review.clone(),
                 };
-                move |storage, fetcher| legacy::replicate(storage, fetcher, config, whoami.clone())
+                move |storage, fetcher| {
+                    legacy::replicate(storage, fetcher, config.clone(), whoami.clone())
+                }
             },
         )
         .await;
 
         Ok(res??)
     }
+
+    /// Determine what [`Self::replicate`] would fetch, without fetching
+    /// anything, see [`legacy::dry_run`].
+    ///
+    /// This still connects to the remote peer to obtain its advertised refs.
+    pub async fn dry_run<P>(
+        &self,
+        spawner: &Spawner,
+        pool: &P,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+        urn: Urn,
+    ) -> Result<DryRun, error::Replicate>
+    where
+        P: Pooled<Storage> + Send + 'static,
+    {
+        let (remote_peer, addr_hints) = from.into();
+        let res = retrying(
+            spawner,
+            self.fetchers.clone(),
+            pool,
+            fetcher::PeerToPeer::new(urn, remote_peer, addr_hints),
+            self.config.wait_slot,
+            |storage, fetcher| legacy::dry_run(storage, &fetcher),
+        )
+        .await;
+
+        Ok(res??)
+    }
 }
@@ -3,17 +3,20 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use async_lock::Semaphore;
 use link_async::{timeout, Spawner};
-use link_replication::io::UserInfo;
-use tracing::debug;
+use link_git::protocol::meter::Transfer;
+use link_replication::{io::UserInfo, Updated};
+use parking_lot::Mutex;
+use tracing::{debug, warn};
 
 use crate::{
     git::{
         identities::local::LocalIdentity,
-        storage::{read::ReadOnlyStorage as _, Storage},
+        storage::{read::ReadOnlyStorage as _, requirements, Storage},
+        tracking,
     },
     identities::git::Urn,
     net::{connection::RemotePeer as _, quic},
@@ -26,6 +29,9 @@ pub use link_replication::FetchLimit;
 mod context;
 use context::Context;
 
+pub mod hooks;
+use hooks::{Hooks, Journal};
+
 pub mod error {
     use thiserror::Error;
 
@@ -36,6 +42,9 @@ pub mod error {
 
         #[error("failed to open reference database")]
         Refdb(#[from] link_git::refs::db::error::Open),
+
+        #[error("failed to open replication hooks journal")]
+        Journal(#[source] std::io::Error),
     }
 
     #[derive(Debug, Error)]
@@ -45,16 +54,31 @@ pub mod error {
 
         #[error(transparent)]
         Replicate(#[from] link_replication::Error),
+
+        #[error(transparent)]
+        Vetoed(link_replication::hooks::Veto),
+
+        #[error(transparent)]
+        Incompatible(#[from] crate::git::storage::requirements::error::Incompatible),
     }
 }
 
 pub type Success = link_replication::Success<context::Urn>;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct Config {
     pub limit: FetchLimit,
     pub slots: usize,
     pub wait_slot: Duration,
+    /// Check the branches of the peer replicated from against the commit
+    /// signature policy of the project, see [`crate::git::signatures`].
+    pub verify_signatures: bool,
+    /// Hooks run around the application of ref updates, see [`hooks`].
+    pub hooks: Hooks,
+    /// The number of ref advertisements to remember, so that remote peers
+    /// only need to advertise their refs again if they changed, see
+    /// [`link_replication::io::Advertisements`]. Zero disables this.
+    pub advertisements: usize,
 }
 
 impl Default for Config {
@@ -63,6 +87,9 @@ impl Default for Config {
             limit: FetchLimit::default(),
             slots: 4,
             wait_slot: Duration::from_secs(20),
+            verify_signatures: false,
+            hooks: Hooks::default(),
+            advertisements: 1024,
         }
     }
 }
@@ -73,6 +100,9 @@ pub struct Replication {
     slots: Arc<Semaphore>,
     odb: link_replication::io::Odb,
     rdb: link_git::refs::db::Refdb,
+    journal: Option<Journal>,
+    adverts: link_replication::io::Advertisements,
+    transfers: Transfers,
 }
 
 impl Replication {
@@ -80,15 +110,36 @@ impl Replication {
         let slots = Arc::new(Semaphore::new(config.slots));
         let odb = link_replication::io::Odb::open(paths.git_dir()).map_err(error::Init::Odb)?;
         let rdb = link_git::refs::db::Refdb::open(paths.git_dir())?;
+        let journal = if config.hooks.has_post_apply() {
+            let journal = Journal::open(&paths.git_dir().join(hooks::FILE_NAME), &config.hooks)
+                .map_err(error::Init::Journal)?;
+            // Catch up on entries left over from a previous run
+            journal.deliver(&config.hooks);
+            Some(journal)
+        } else {
+            None
+        };
+
+        let adverts = link_replication::io::Advertisements::new(config.advertisements);
 
         Ok(Self {
             config,
             slots,
             odb,
             rdb,
+            journal,
+            adverts,
+            transfers: Transfers::default(),
         })
     }
 
+    /// The [`Transfer`] progress of the replication of `urn` from `remote`,
+    /// if it is currently running, see
+    /// [`link_replication::io::Network::with_progress`].
+    pub fn progress(&self, urn: &Urn, remote: &PeerId) -> Option<Transfer> {
+        self.transfers.get(urn, remote)
+    }
+
     pub async fn replicate<S>(
         &self,
         spawner: &Spawner,
@@ -100,19 +151,29 @@ impl Replication {
     where
         S: AsRef<Storage> + Send + 'static,
     {
+        requirements::ensure_compatible(store.as_ref().path(), &urn)?;
         let slot = timeout(self.config.wait_slot, self.slots.acquire_arc()).await?;
         let limit = self.config.limit;
         let odb = self.odb.clone();
         let rdb = self.rdb.clone();
+        let hooks = self.config.hooks.clone();
+        let journal = self.journal.clone();
+        let adverts = self.adverts.clone();
+        let transfers = self.transfers.clone();
         let res = spawner
             .blocking(move || {
                 let store = store.as_ref();
+                // Replications of the same URN must not interleave, but
+                // different URNs can be replicated concurrently.
+                let lock = store.lock_namespace(&urn)?;
                 let have_urn = store.has_urn(&urn)?;
                 let remote_id = conn.remote_peer_id();
                 let info = UserInfo {
                     name: store.config()?.user_name()?,
                     peer_id: *store.peer_id(),
                 };
+                let namespace = urn.clone();
+                let transfer = transfers.register(namespace.clone(), remote_id);
                 let urn = context::Urn::from(urn);
                 let refdb = link_replication::io::Refdb::new(info, odb.clone(), rdb.clone(), &urn)?;
                 let net = link_replication::io::Network::new(
@@ -120,12 +181,16 @@ impl Replication {
                     conn,
                     store.path(),
                     urn.clone(),
-                );
+                )
+                .with_advertisements(remote_id, adverts)
+                .with_progress(transfer.on_progress());
                 let mut cx = Context {
                     urn,
                     store,
                     refdb,
                     net,
+                    hooks: &hooks,
+                    journal: journal.as_ref(),
                 };
                 let whoami = whoami.map(|id| link_replication::LocalIdentity {
                     tip: id.content_id.into(),
@@ -137,17 +202,102 @@ impl Replication {
                         .collect(),
                 });
 
-                if have_urn {
+                let success = if have_urn {
+                    // Sub-keys granted since the last replication are pulled
+                    // right away, and revoked ones are no longer pulled
+                    track_subkeys(store, &namespace);
                     debug!("pull");
                     link_replication::pull(&mut cx, limit, remote_id, whoami)
                 } else {
                     debug!("clone");
                     link_replication::clone(&mut cx, limit, remote_id, whoami)
+                };
+                // Deliver whatever was applied, even if the replication failed
+                // half-way
+                if let Some(journal) = &journal {
+                    journal.deliver(&hooks);
+                }
+                let success = success?;
+                if !have_urn {
+                    track_subkeys(store, &namespace);
+                }
+                if let Err(e) = store.record_history(&namespace) {
+                    warn!(err = %e, "failed to record namespace history");
                 }
+                let received = success
+                    .updated_refs()
+                    .iter()
+                    .filter_map(|updated| match updated {
+                        Updated::Direct { name, .. } => Some(name.as_str()),
+                        _ => None,
+                    });
+                store
+                    .collaborative_objects(None)
+                    .audit_received(&namespace, received);
+                // Report if another process took over the namespace while we
+                // were busy, as the updates may have interleaved
+                lock.check()?;
+
+                Ok::<_, link_replication::Error>(success)
             })
             .await
-            .map_err(error::Replicate::Replicate);
+            .map_err(|e| match e.downcast::<link_replication::hooks::Veto>() {
+                Ok(veto) => error::Replicate::Vetoed(*veto),
+                Err(e) => error::Replicate::Replicate(e),
+            });
         drop(slot);
         res
     }
 }
+
+/// [`Transfer`] progress of the replications which are fetching, by namespace
+/// and remote peer.
+#[derive(Clone, Default)]
+struct Transfers(Arc<Mutex<HashMap<(Urn, PeerId), Transfer>>>);
+
+impl Transfers {
+    fn register(&self, urn: Urn, remote: PeerId) -> Registered {
+        let key = (urn, remote);
+        self.0.lock().insert(key.clone(), Transfer::default());
+        Registered {
+            key,
+            transfers: self.clone(),
+        }
+    }
+
+    fn get(&self, urn: &Urn, remote: &PeerId) -> Option<Transfer> {
+        self.0.lock().get(&(urn.clone(), *remote)).copied()
+    }
+}
+
+/// Removes the progress of a replication once it is done.
+struct Registered {
+    key: (Urn, PeerId),
+    transfers: Transfers,
+}
+
+impl Registered {
+    fn on_progress(&self) -> link_git::protocol::meter::OnProgress {
+        let key = self.key.clone();
+        let transfers = self.transfers.clone();
+        Arc::new(move |transfer: Transfer| {
+            if let Some(current) = transfers.0.lock().get_mut(&key) {
+                *current = transfer;
+            }
+        })
+    }
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        self.transfers.0.lock().remove(&self.key);
+    }
+}
+
+/// Track the sub-keys granted by the delegates of `urn`, if it is a project,
+/// see [`tracking::subkeys`].
+fn track_subkeys(store: &Storage, urn: &Urn) {
+    if let Err(e) = tracking::subkeys::track_project(store, urn) {
+        warn!(urn = %urn, err = %e, "failed to track sub-keys");
+    }
+}
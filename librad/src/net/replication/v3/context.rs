@@ -5,7 +5,7 @@
 
 use std::{
     borrow::Cow,
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     convert::TryFrom,
     ops::Deref,
     path::Path,
@@ -17,6 +17,7 @@ use either::{Either, Either::*};
 use git_ref_format::RefString;
 use link_git::protocol::Ref;
 use link_replication::{
+    hooks::Veto,
     io,
     namespace,
     odb::Object,
@@ -43,6 +44,7 @@ use multihash::Multihash;
 use radicle_data::NonEmptyVec;
 use std_ext::Void;
 
+use super::hooks::{Hooks, Journal};
 use crate::{
     git::{self, storage::Storage, tracking},
     identities::{
@@ -125,6 +127,8 @@ pub struct Context<'a> {
     pub(super) store: &'a Storage,
     pub(super) refdb: io::Refdb<io::Odb>,
     pub(super) net: Network,
+    pub(super) hooks: &'a Hooks,
+    pub(super) journal: Option<&'a Journal>,
 }
 
 impl<'a> Context<'a> {
@@ -354,30 +358,35 @@ impl Identities for Context<'_> {
     }
 }
 
+fn sigrefs(
+    git::refs::Loaded { at, refs: signed }: git::refs::Loaded,
+    cutoff: usize,
+) -> Sigrefs<git_ext::Oid> {
+    let refs = signed
+        .iter_categorised()
+        .map(|((name, oid), cat)| {
+            // TODO: make `Refs` use `git_ref_format`
+            let refname = RefString::try_from(format!("refs/{}/{}", cat, name))
+                .expect("`Refs::iter_categorised` yields valid refnames");
+            (refname, *oid)
+        })
+        .collect::<HashMap<_, _>>();
+    let mut remotes = git::refs::Refs::from(signed).remotes;
+    remotes.cutoff_mut(cutoff);
+    let remotes = remotes.flatten().copied().collect();
+
+    Sigrefs { at, refs, remotes }
+}
+
 impl SignedRefs for Context<'_> {
     type Oid = git_ext::Oid;
     type Error = error::Sigrefs;
 
     fn load(&self, of: &PeerId, cutoff: usize) -> Result<Option<Sigrefs<Self::Oid>>, Self::Error> {
-        match git::refs::load(&self.store, &self.urn, Some(of))? {
-            None => Ok(None),
-            Some(git::refs::Loaded { at, refs: signed }) => {
-                let refs = signed
-                    .iter_categorised()
-                    .map(|((name, oid), cat)| {
-                        // TODO: make `Refs` use `git_ref_format`
-                        let refname = RefString::try_from(format!("refs/{}/{}", cat, name))
-                            .expect("`Refs::iter_categorised` yields valid refnames");
-                        (refname, *oid)
-                    })
-                    .collect::<HashMap<_, _>>();
-                let mut remotes = git::refs::Refs::from(signed).remotes;
-                remotes.cutoff_mut(cutoff);
-                let remotes = remotes.flatten().copied().collect();
-
-                Ok(Some(Sigrefs { at, refs, remotes }))
-            },
-        }
+        Ok(
+            git::refs::load(&self.store, &self.urn, Some(of))?
+                .map(|loaded| sigrefs(loaded, cutoff)),
+        )
     }
 
     fn load_at(
@@ -386,25 +395,27 @@ impl SignedRefs for Context<'_> {
         signed_by: &PeerId,
         cutoff: usize,
     ) -> Result<Option<Sigrefs<Self::Oid>>, Self::Error> {
-        match git::refs::load_at(&self.store, treeish.into().into(), Some(signed_by))? {
-            None => Ok(None),
-            Some(git::refs::Loaded { at, refs: signed }) => {
-                let refs = signed
-                    .iter_categorised()
-                    .map(|((name, oid), cat)| {
-                        // TODO: make `Refs` use `git_ref_format`
-                        let refname = RefString::try_from(format!("refs/{}/{}", cat, name))
-                            .expect("`Refs::iter_categorised` yields valid refnames");
-                        (refname, *oid)
-                    })
-                    .collect::<HashMap<_, _>>();
-                let mut remotes = git::refs::Refs::from(signed).remotes;
-                remotes.cutoff_mut(cutoff);
-                let remotes = remotes.flatten().copied().collect();
-
-                Ok(Some(Sigrefs { at, refs, remotes }))
-            },
-        }
+        Ok(
+            git::refs::load_at(&self.store, treeish.into().into(), Some(signed_by))?
+                .map(|loaded| sigrefs(loaded, cutoff)),
+        )
+    }
+
+    fn load_many<'a, I>(
+        &self,
+        of: I,
+        cutoff: usize,
+    ) -> Result<BTreeMap<PeerId, Sigrefs<Self::Oid>>, Self::Error>
+    where
+        I: IntoIterator<Item = (&'a PeerId, Option<ObjectId>)>,
+    {
+        let of = of
+            .into_iter()
+            .map(|(id, at)| (*id, at.map(git_ext::Oid::from)));
+        Ok(git::refs::load_many(&self.store, &self.urn, of)?
+            .into_iter()
+            .map(|(id, loaded)| (id, sigrefs(loaded, cutoff)))
+            .collect())
     }
 
     fn update(&self) -> Result<Option<Self::Oid>, Self::Error> {
@@ -548,6 +559,21 @@ impl<'c> Refdb for Context<'c> {
     }
 }
 
+impl link_replication::Hooks for Context<'_> {
+    type PostApplyError = std::io::Error;
+
+    fn pre_apply(&self, remote_id: &PeerId, updates: &[Update<'_>]) -> Result<(), Veto> {
+        self.hooks.check(&self.urn, *remote_id, updates)
+    }
+
+    fn post_apply(&mut self, remote_id: &PeerId, applied: &Applied<'_>) -> std::io::Result<()> {
+        match self.journal {
+            None => Ok(()),
+            Some(journal) => journal.record(&self.urn, *remote_id, applied),
+        }
+    }
+}
+
 impl<'a> RefScan for &'a Context<'_> {
     type Oid = <&'a io::Refdb<io::Odb> as RefScan>::Oid;
     type Scan = <&'a io::Refdb<io::Odb> as RefScan>::Scan;
@@ -620,6 +646,7 @@ impl io::Connection for quic::Connection {
         use net::connection::Duplex as _;
 
         let bi = self.open_bidi().await?;
+        bi.set_class(quic::TrafficClass::Bulk);
         let up = upgrade::upgrade(bi, upgrade::Git).await?;
         Ok(up.into_stream().split())
     }
@@ -0,0 +1,409 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Hooks run around the application of ref updates during replication.
+//!
+//! [`PreApply`] hooks inspect the ref updates fetched from a peer before they
+//! are applied, and may veto them. A veto fails the replication with
+//! [`super::error::Replicate::Vetoed`], and is reported as an
+//! [`crate::net::protocol::event::upstream::Vetoed`] event.
+//!
+//! [`PostApply`] hooks are run after ref updates were applied, eg. to update
+//! a search index. The applied updates are first appended to a journal at
+//! [`FILE_NAME`] (relative to the git directory), and every post-apply hook
+//! has a durable cursor into the journal, advanced only once the hook
+//! succeeded. Entries past the cursor are delivered after every replication,
+//! and when the [`super::Replication`] is created, so a hook which fails, or
+//! is interrupted by the process exiting, is given the entry again. That is,
+//! post-apply hooks see every entry at least once, and must tolerate seeing
+//! an entry more than once.
+//!
+//! The journal is a sequence of CBOR-encoded records, and is compacted when
+//! opened: entries all post-apply hooks have processed are dropped. A newly
+//! registered hook starts at the oldest entry retained.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write as _},
+    path::Path,
+    sync::Arc,
+};
+
+use git_ext as ext;
+use git_ref_format::RefString;
+use link_replication::{hooks::Veto, Applied, Update};
+use parking_lot::Mutex;
+
+use crate::{identities::git::Urn, PeerId};
+
+/// The name of the journal file, relative to the git directory.
+pub const FILE_NAME: &str = "replication-hooks.wal";
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// A hook run before ref updates are applied.
+pub trait PreApply: Send + Sync {
+    /// Inspect the `updates` to `urn` fetched from `from`, returning the
+    /// reason to reject them, if any.
+    fn pre_apply(&self, urn: &Urn, from: PeerId, updates: &[Update<'_>]) -> Result<(), String>;
+}
+
+impl<F> PreApply for F
+where
+    F: Fn(&Urn, PeerId, &[Update<'_>]) -> Result<(), String> + Send + Sync,
+{
+    fn pre_apply(&self, urn: &Urn, from: PeerId, updates: &[Update<'_>]) -> Result<(), String> {
+        self(urn, from, updates)
+    }
+}
+
+/// A hook run after ref updates were applied.
+pub trait PostApply: Send + Sync {
+    /// Process the journal `entry`. If an error is returned, the entry is
+    /// delivered again later.
+    fn post_apply(&self, entry: &Entry) -> Result<(), BoxError>;
+}
+
+impl<F> PostApply for F
+where
+    F: Fn(&Entry) -> Result<(), BoxError> + Send + Sync,
+{
+    fn post_apply(&self, entry: &Entry) -> Result<(), BoxError> {
+        self(entry)
+    }
+}
+
+/// The hooks registered with [`super::Config::hooks`].
+///
+/// Hooks are named, for diagnostics and, in the case of post-apply hooks, to
+/// identify their cursor into the journal. Names of post-apply hooks must
+/// thus be unique and stable across restarts.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    pre: Vec<(String, Arc<dyn PreApply>)>,
+    post: Vec<(String, Arc<dyn PostApply>)>,
+}
+
+impl Hooks {
+    /// Register a hook run before ref updates are applied.
+    pub fn pre_apply<H>(mut self, name: impl Into<String>, hook: H) -> Self
+    where
+        H: PreApply + 'static,
+    {
+        self.pre.push((name.into(), Arc::new(hook)));
+        self
+    }
+
+    /// Register a hook run after ref updates were applied.
+    pub fn post_apply<H>(mut self, name: impl Into<String>, hook: H) -> Self
+    where
+        H: PostApply + 'static,
+    {
+        self.post.push((name.into(), Arc::new(hook)));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pre.is_empty() && self.post.is_empty()
+    }
+
+    /// Run the pre-apply hooks in order of registration, stopping at the
+    /// first veto.
+    pub fn check(&self, urn: &Urn, from: PeerId, updates: &[Update<'_>]) -> Result<(), Veto> {
+        for (name, hook) in &self.pre {
+            hook.pre_apply(urn, from, updates)
+                .map_err(|reason| Veto::new(format!("{}: {}", name, reason)))?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn has_post_apply(&self) -> bool {
+        !self.post.is_empty()
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field(
+                "pre",
+                &self.pre.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            )
+            .field(
+                "post",
+                &self.post.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Ref updates applied by a replication, as delivered to [`PostApply`]
+/// hooks.
+#[derive(Clone, Debug, PartialEq, minicbor::Encode, minicbor::Decode)]
+#[cbor(array)]
+pub struct Entry {
+    /// The position of the entry in the journal.
+    #[n(0)]
+    pub seq: u64,
+    #[n(1)]
+    pub urn: Urn,
+    /// The peer the updates were fetched from.
+    #[n(2)]
+    pub from: PeerId,
+    #[n(3)]
+    pub updated: Vec<Updated>,
+}
+
+/// An applied ref update, see [`link_replication::Updated`].
+#[derive(Clone, Debug, PartialEq, minicbor::Encode, minicbor::Decode)]
+pub enum Updated {
+    #[n(0)]
+    #[cbor(array)]
+    Direct {
+        #[n(0)]
+        name: RefString,
+        #[n(1)]
+        target: ext::Oid,
+    },
+
+    #[n(1)]
+    #[cbor(array)]
+    Symbolic {
+        #[n(0)]
+        name: RefString,
+        #[n(1)]
+        target: RefString,
+    },
+
+    #[n(2)]
+    #[cbor(array)]
+    Prune {
+        #[n(0)]
+        name: RefString,
+    },
+}
+
+impl From<&link_replication::Updated> for Updated {
+    fn from(up: &link_replication::Updated) -> Self {
+        match up {
+            link_replication::Updated::Direct { name, target } => Self::Direct {
+                name: name.clone(),
+                target: (*target).into(),
+            },
+            link_replication::Updated::Symbolic { name, target } => Self::Symbolic {
+                name: name.clone(),
+                target: target.clone(),
+            },
+            link_replication::Updated::Prune { name } => Self::Prune { name: name.clone() },
+        }
+    }
+}
+
+/// A record of the journal.
+#[derive(Clone, Debug, minicbor::Encode, minicbor::Decode)]
+enum Record {
+    #[n(0)]
+    #[cbor(array)]
+    Applied {
+        #[n(0)]
+        entry: Entry,
+    },
+
+    /// The named hook processed all entries up to and including `seq`.
+    #[n(1)]
+    #[cbor(array)]
+    Delivered {
+        #[n(0)]
+        hook: String,
+        #[n(1)]
+        seq: u64,
+    },
+}
+
+#[derive(Default)]
+struct Inner {
+    log: Option<File>,
+    entries: BTreeMap<u64, Entry>,
+    cursors: HashMap<String, u64>,
+    next_seq: u64,
+}
+
+impl Inner {
+    fn append(&mut self, records: &[Record]) -> io::Result<()> {
+        let log = match self.log.as_mut() {
+            Some(log) => log,
+            None => return Ok(()),
+        };
+        let buf = records
+            .iter()
+            .try_fold(Vec::new(), |mut buf, record| {
+                minicbor::encode(record, &mut buf).map(|()| buf)
+            })
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        log.write_all(&buf)?;
+        log.sync_data()
+    }
+
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::Applied { entry } => {
+                self.next_seq = self.next_seq.max(entry.seq + 1);
+                self.entries.insert(entry.seq, entry);
+            },
+            Record::Delivered { hook, seq } => {
+                // Keep the sequence numbers monotonic, even if all entries
+                // were pruned
+                self.next_seq = self.next_seq.max(seq + 1);
+                let cursor = self.cursors.entry(hook).or_default();
+                *cursor = (*cursor).max(seq);
+            },
+        }
+    }
+
+    /// Entries past the cursor of `hook`, oldest first.
+    fn pending(&self, hook: &str) -> Vec<Entry> {
+        match self.cursors.get(hook) {
+            None => self.entries.values().cloned().collect(),
+            Some(cursor) => self
+                .entries
+                .range(cursor + 1..)
+                .map(|(_, entry)| entry.clone())
+                .collect(),
+        }
+    }
+
+    /// Drop the entries processed by all of `hooks`.
+    fn prune(&mut self, hooks: &Hooks) {
+        let low = hooks
+            .post
+            .iter()
+            .map(|(name, _)| self.cursors.get(name).copied())
+            .min()
+            .flatten();
+        if let Some(low) = low {
+            self.entries = self.entries.split_off(&(low + 1));
+        }
+    }
+}
+
+/// Journal of applied ref updates, and the cursors of the [`PostApply`]
+/// hooks into it.
+///
+/// Maintained by [`super::Replication`] if any post-apply hooks are
+/// registered.
+#[derive(Clone)]
+pub struct Journal {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Journal {
+    /// Open the journal at `path`, creating it if it doesn't exist.
+    ///
+    /// The journal is compacted with respect to the post-apply `hooks`.
+    pub fn open(path: &Path, hooks: &Hooks) -> io::Result<Self> {
+        let mut inner = Inner::default();
+        match fs::read(path) {
+            Ok(bytes) => {
+                let mut decoder = minicbor::Decoder::new(&bytes);
+                while decoder.position() < bytes.len() {
+                    match decoder.decode() {
+                        Ok(record) => inner.apply(record),
+                        Err(e) => {
+                            tracing::warn!(err = %e, "ignoring torn tail of replication hooks journal");
+                            break;
+                        },
+                    }
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e),
+        }
+        inner.prune(hooks);
+        inner
+            .cursors
+            .retain(|name, _| hooks.post.iter().any(|(hook, _)| hook == name));
+
+        let mut records = inner
+            .cursors
+            .iter()
+            .map(|(hook, seq)| Record::Delivered {
+                hook: hook.clone(),
+                seq: *seq,
+            })
+            .collect::<Vec<_>>();
+        records.extend(inner.entries.values().map(|entry| Record::Applied {
+            entry: entry.clone(),
+        }));
+
+        let tmp = path.with_extension("wal.tmp");
+        inner.log = Some(
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&tmp)?,
+        );
+        inner.append(&records)?;
+        fs::rename(&tmp, path)?;
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+        })
+    }
+
+    /// Append the updates of `applied` to the journal.
+    pub fn record(&self, urn: &Urn, from: PeerId, applied: &Applied<'_>) -> io::Result<()> {
+        if applied.updated.is_empty() {
+            return Ok(());
+        }
+
+        let mut inner = self.inner.lock();
+        let entry = Entry {
+            seq: inner.next_seq,
+            urn: urn.clone(),
+            from,
+            updated: applied.updated.iter().map(Updated::from).collect(),
+        };
+        let record = Record::Applied { entry };
+        inner.append(&[record.clone()])?;
+        inner.apply(record);
+
+        Ok(())
+    }
+
+    /// The entries not yet processed by the post-apply hook named `hook`,
+    /// oldest first.
+    pub fn pending(&self, hook: &str) -> Vec<Entry> {
+        self.inner.lock().pending(hook)
+    }
+
+    /// Deliver the entries past their cursor to the post-apply `hooks`.
+    ///
+    /// Delivery to a hook stops at the first entry it fails to process.
+    pub fn deliver(&self, hooks: &Hooks) {
+        for (name, hook) in &hooks.post {
+            let pending = self.inner.lock().pending(name);
+            for entry in pending {
+                if let Err(e) = hook.post_apply(&entry) {
+                    tracing::warn!(hook = %name, seq = entry.seq, err = %e, "post-apply hook failed");
+                    break;
+                }
+
+                let mut inner = self.inner.lock();
+                let record = Record::Delivered {
+                    hook: name.clone(),
+                    seq: entry.seq,
+                };
+                if let Err(e) = inner.append(&[record.clone()]) {
+                    tracing::warn!(hook = %name, err = %e, "failed to advance post-apply cursor");
+                }
+                inner.apply(record);
+            }
+        }
+        self.inner.lock().prune(hooks);
+    }
+}
@@ -0,0 +1,416 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Machine-readable descriptions of the wire encoding of protocol messages.
+//!
+//! Every type sent over the wire by the protocol implements [`Cddl`], which
+//! describes its CBOR encoding as a [CDDL][rfc8610] type. Compound types are
+//! described by named rules, collected in [`Definitions`]. [`protocol`]
+//! collects the rules for all messages exchanged on protocol streams, and
+//! renders them via [`std::fmt::Display`]:
+//!
+//! ```no_run
+//! print!("{}", librad::net::schema::protocol());
+//! ```
+//!
+//! The descriptions are maintained alongside the [`minicbor::Encode`] and
+//! [`minicbor::Decode`] impls, and must be updated together with them. Rules
+//! of generic types are named after the instantiation used on the wire, ie.
+//! with [`SocketAddr`] addresses and [`gossip::Payload`]s.
+//!
+//! [rfc8610]: https://datatracker.ietf.org/doc/html/rfc8610
+
+use std::{borrow::Cow, collections::BTreeSet, fmt, net::SocketAddr};
+
+use data::BoundedVec;
+use typenum::Unsigned;
+
+use crate::{
+    identities::{git::Urn, xor::Xor},
+    net::{
+        protocol::{
+            broadcast,
+            gossip,
+            interrogation,
+            membership,
+            rotation::Rotation,
+            Capability,
+            PeerAdvertisement,
+            PeerInfo,
+        },
+        upgrade::UpgradeRequest,
+    },
+    PeerId,
+    PublicKey,
+    Signature,
+};
+
+/// Types with a known CBOR encoding.
+pub trait Cddl {
+    /// Describe the encoding of `Self` as a CDDL type.
+    ///
+    /// Compound types should [`Definitions::define`] a rule, and return its
+    /// name.
+    fn cddl(defs: &mut Definitions) -> String;
+}
+
+/// A set of CDDL rules, in order of definition.
+#[derive(Clone, Debug, Default)]
+pub struct Definitions {
+    rules: Vec<(&'static str, String)>,
+}
+
+impl Definitions {
+    /// Define the rule `name`, unless it is already defined, and return
+    /// `name`.
+    ///
+    /// `body` is only called if the rule is not yet defined. Rules referenced
+    /// by `body` are defined after `name`.
+    pub fn define<F>(&mut self, name: &'static str, body: F) -> String
+    where
+        F: FnOnce(&mut Self) -> String,
+    {
+        if !self.rules.iter().any(|(n, _)| *n == name) {
+            let pos = self.rules.len();
+            self.rules.push((name, String::new()));
+            let body = body(self);
+            self.rules[pos].1 = body;
+        }
+        name.to_owned()
+    }
+
+    /// The CDDL type of `T`.
+    pub fn of<T: Cddl>(&mut self) -> String {
+        T::cddl(self)
+    }
+
+    /// The rules defined so far.
+    pub fn rules(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.rules.iter().map(|(n, b)| (*n, b.as_str()))
+    }
+}
+
+impl fmt::Display for Definitions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, body) in &self.rules {
+            writeln!(f, "{} = {}", name, body)?;
+        }
+        Ok(())
+    }
+}
+
+/// The rules describing all messages exchanged on protocol streams.
+///
+/// The first rules are the messages themselves: the [`UpgradeRequest`]
+/// initiating every stream, followed by the messages of the gossip,
+/// membership and interrogation streams.
+pub fn protocol() -> Definitions {
+    let mut defs = Definitions::default();
+    defs.define("protocol-message", |defs| {
+        [
+            defs.of::<UpgradeRequest>(),
+            defs.of::<broadcast::Message<SocketAddr, gossip::Payload>>(),
+            defs.of::<membership::Message<SocketAddr>>(),
+            defs.of::<interrogation::Request>(),
+            defs.of::<interrogation::Response<'static, SocketAddr>>(),
+        ]
+        .join(" / ")
+    });
+    defs
+}
+
+/// A `minicbor` derived enum variant, ie. `[index, [fields]]`.
+fn variant(index: u32, fields: &[String]) -> String {
+    format!("[{}, [{}]]", index, fields.join(", "))
+}
+
+macro_rules! uint {
+    ($($t:ty),*) => {
+        $(
+            impl Cddl for $t {
+                fn cddl(_: &mut Definitions) -> String {
+                    "uint".to_owned()
+                }
+            }
+        )*
+    };
+}
+
+uint!(u8, u16, u32, u64, usize);
+
+impl<T: Cddl> Cddl for Option<T> {
+    fn cddl(defs: &mut Definitions) -> String {
+        format!("{} / null", defs.of::<T>())
+    }
+}
+
+impl<T: Cddl> Cddl for Vec<T> {
+    fn cddl(defs: &mut Definitions) -> String {
+        format!("[* {}]", defs.of::<T>())
+    }
+}
+
+impl<T: Cddl> Cddl for BTreeSet<T> {
+    fn cddl(defs: &mut Definitions) -> String {
+        format!("[* {}]", defs.of::<T>())
+    }
+}
+
+impl<N: Unsigned, T: Cddl> Cddl for BoundedVec<N, T> {
+    fn cddl(defs: &mut Definitions) -> String {
+        format!("[0*{} {}]", N::USIZE, defs.of::<T>())
+    }
+}
+
+impl<T: Cddl + Clone> Cddl for Cow<'_, T> {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.of::<T>()
+    }
+}
+
+impl Cddl for SocketAddr {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("socket-addr", |defs| {
+            let port = defs.define("port", |_| "uint .size 2".to_owned());
+            format!(
+                "[0, [bstr .size 4, {port}]] / [1, [bstr .size 16, {port}]]",
+                port = port
+            )
+        })
+    }
+}
+
+impl Cddl for PublicKey {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("public-key", |_| "[0, bstr .size 32]".to_owned())
+    }
+}
+
+impl Cddl for Signature {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("signature", |_| "[0, bstr .size 64]".to_owned())
+    }
+}
+
+impl Cddl for PeerId {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("peer-id", |defs| format!("[{}]", defs.of::<PublicKey>()))
+    }
+}
+
+impl Cddl for Urn {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("urn", |_| {
+            "[id: bstr, protocol: 0, path: tstr / null] ; id is a multihash, protocol 0 is git"
+                .to_owned()
+        })
+    }
+}
+
+impl Cddl for UpgradeRequest {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("upgrade-request", |_| {
+            // The break character following discriminators below 24 is not
+            // expressible in CDDL, see the docs of `UpgradeRequest`.
+            "[0, &(gossip: 0, git: 1, membership: 2, interrogation: 3, inventory: 4, ping: 5, lfs: 6, attachments: 7, request-pull: 200)]"
+                .to_owned()
+        })
+    }
+}
+
+impl Cddl for gossip::Rev {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("rev", |_| "[0, bstr .size 20] ; git oid".to_owned())
+    }
+}
+
+impl Cddl for gossip::Payload {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("gossip-payload", |defs| {
+            format!(
+                "[urn: {}, rev: {}, origin: {}]",
+                defs.of::<Urn>(),
+                defs.of::<Option<gossip::Rev>>(),
+                defs.of::<Option<PeerId>>(),
+            )
+        })
+    }
+}
+
+impl Cddl for broadcast::Ext {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("gossip-ext", |defs| {
+            format!(
+                "[seqno: uint, hop: uint, sig: {}]",
+                defs.of::<Option<Signature>>()
+            )
+        })
+    }
+}
+
+impl<A: Cddl, P: Cddl> Cddl for broadcast::Message<A, P> {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("gossip-message", |defs| {
+            let fields = [
+                defs.of::<PeerInfo<A>>(),
+                defs.of::<P>(),
+                defs.of::<Option<broadcast::Ext>>(),
+            ];
+            format!(
+                "{} ; have\n / {} ; want",
+                variant(0, &fields),
+                variant(1, &fields)
+            )
+        })
+    }
+}
+
+impl Cddl for Capability {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("capability", |_| {
+            [
+                "[0, []] ; reserved",
+                "[1, []] ; gossip batch",
+                "[2, []] ; gossip compression",
+                "[3, []] ; private",
+                "[4, []] ; gossip interest",
+                "[5, []] ; gossip backoff",
+                "[6, []] ; ping",
+                "[uint, []] ; unknown, ignored",
+            ]
+            .join("\n / ")
+        })
+    }
+}
+
+impl<A: Cddl> Cddl for PeerAdvertisement<A> {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("peer-advertisement", |defs| {
+            format!(
+                "[listen-addrs: {}, null, legacy: [], capabilities: {}]",
+                defs.of::<BoundedVec<typenum::U16, A>>(),
+                defs.of::<BTreeSet<Capability>>(),
+            )
+        })
+    }
+}
+
+impl<A: Cddl> Cddl for PeerInfo<A> {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("peer-info", |defs| {
+            format!(
+                "[peer-id: {}, advertised-info: {}, seen-addrs: {}]",
+                defs.of::<PeerId>(),
+                defs.of::<PeerAdvertisement<A>>(),
+                defs.of::<BoundedVec<typenum::U16, A>>(),
+            )
+        })
+    }
+}
+
+impl Cddl for membership::Priority {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("priority", |_| {
+            "null ; normal, high is encoded as nothing at all".to_owned()
+        })
+    }
+}
+
+impl<A: Cddl> Cddl for membership::Message<A> {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("membership-message", |defs| {
+            let ad = defs.of::<PeerAdvertisement<A>>();
+            let info = defs.of::<PeerInfo<A>>();
+            let infos = defs.of::<Vec<PeerInfo<A>>>();
+            let prio = defs.of::<membership::Priority>();
+            [
+                format!("{} ; join", variant(0, &[ad.clone()])),
+                format!(
+                    "{} ; forward join",
+                    variant(1, &[info.clone(), "ttl: uint".to_owned()])
+                ),
+                format!("{} ; neighbour", variant(2, &[ad, format!("? {}", prio)])),
+                format!(
+                    "{} ; shuffle",
+                    variant(3, &[info, infos.clone(), "ttl: uint".to_owned()])
+                ),
+                format!("{} ; shuffle reply", variant(4, &[infos])),
+                format!("{} ; disconnect", variant(5, &[])),
+            ]
+            .join("\n / ")
+        })
+    }
+}
+
+impl Cddl for interrogation::Request {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("interrogation-request", |_| {
+            [
+                format!("{} ; get advertisement", variant(0, &[])),
+                format!("{} ; echo addr", variant(1, &[])),
+                format!("{} ; get urns", variant(2, &[])),
+                format!("{} ; get time", variant(3, &[])),
+                format!("{} ; get rotation", variant(4, &[])),
+            ]
+            .join("\n / ")
+        })
+    }
+}
+
+impl Cddl for interrogation::Error {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("interrogation-error", |_| {
+            "uint .size 1 ; 0: internal, 1: temporarily unavailable".to_owned()
+        })
+    }
+}
+
+impl Cddl for Rotation {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("rotation", |defs| {
+            format!(
+                "[old: {peer}, new: {peer}, signature: {}]",
+                defs.of::<Signature>(),
+                peer = defs.of::<PeerId>(),
+            )
+        })
+    }
+}
+
+impl Cddl for Xor {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("xor-filter", |_| {
+            "[seed: uint, block-length: uint, fingerprints: [* uint .size 2]]".to_owned()
+        })
+    }
+}
+
+impl<A: Cddl + Clone + Ord> Cddl for interrogation::Response<'_, A> {
+    fn cddl(defs: &mut Definitions) -> String {
+        defs.define("interrogation-response", |defs| {
+            [
+                format!(
+                    "{} ; error",
+                    variant(0, &[defs.of::<interrogation::Error>()])
+                ),
+                format!(
+                    "{} ; advertisement",
+                    variant(1, &[defs.of::<PeerAdvertisement<A>>()])
+                ),
+                format!("{} ; your addr", variant(2, &[defs.of::<A>()])),
+                format!("{} ; urns", variant(3, &[defs.of::<Xor>()])),
+                format!(
+                    "{} ; time, in milliseconds since the epoch",
+                    variant(4, &["uint".to_owned()])
+                ),
+                format!(
+                    "{} ; rotation",
+                    variant(5, &[defs.of::<Option<Rotation>>()])
+                ),
+            ]
+            .join("\n / ")
+        })
+    }
+}
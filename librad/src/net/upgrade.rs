@@ -51,6 +51,18 @@ pub struct Interrogation;
 #[derive(Debug)]
 pub struct RequestPull;
 
+#[derive(Debug)]
+pub struct Inventory;
+
+#[derive(Debug)]
+pub struct Ping;
+
+#[derive(Debug)]
+pub struct Lfs;
+
+#[derive(Debug)]
+pub struct Attachments;
+
 /// Signal the (sub-) protocol about to be sent over a given QUIC stream.
 ///
 /// This is only valid as the first message sent by the initiator of a fresh
@@ -84,13 +96,17 @@ pub struct RequestPull;
 /// The current implementation will check in the range of 0..23 and encode the
 /// break character for backwards-compatibility. In the range of 24..255, we do
 /// not encode the break character.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum UpgradeRequest {
     Gossip = 0,
     Git = 1,
     Membership = 2,
     Interrogation = 3,
+    Inventory = 4,
+    Ping = 5,
+    Lfs = 6,
+    Attachments = 7,
     /// `RequestPull` is a temporary stream and shall be deprecated in the
     /// future, see [RFC 702][rfc].
     ///
@@ -122,6 +138,30 @@ impl From<Interrogation> for UpgradeRequest {
     }
 }
 
+impl From<Inventory> for UpgradeRequest {
+    fn from(_inventory: Inventory) -> Self {
+        UpgradeRequest::Inventory
+    }
+}
+
+impl From<Ping> for UpgradeRequest {
+    fn from(_ping: Ping) -> Self {
+        UpgradeRequest::Ping
+    }
+}
+
+impl From<Lfs> for UpgradeRequest {
+    fn from(_lfs: Lfs) -> Self {
+        UpgradeRequest::Lfs
+    }
+}
+
+impl From<Attachments> for UpgradeRequest {
+    fn from(_attachments: Attachments) -> Self {
+        UpgradeRequest::Attachments
+    }
+}
+
 impl From<RequestPull> for UpgradeRequest {
     fn from(_interrogation: RequestPull) -> Self {
         UpgradeRequest::RequestPull
@@ -154,6 +194,10 @@ impl<'de> minicbor::Decode<'de> for UpgradeRequest {
                 1 => Ok(Self::Git),
                 2 => Ok(Self::Membership),
                 3 => Ok(Self::Interrogation),
+                4 => Ok(Self::Inventory),
+                5 => Ok(Self::Ping),
+                6 => Ok(Self::Lfs),
+                7 => Ok(Self::Attachments),
                 200 => Ok(Self::RequestPull),
                 n => Err(minicbor::decode::Error::UnknownVariant(n as u32)),
             },
@@ -272,6 +316,10 @@ pub enum SomeUpgraded<S> {
     Git(Upgraded<Git, S>),
     Membership(Upgraded<Membership, S>),
     Interrogation(Upgraded<Interrogation, S>),
+    Inventory(Upgraded<Inventory, S>),
+    Ping(Upgraded<Ping, S>),
+    Lfs(Upgraded<Lfs, S>),
+    Attachments(Upgraded<Attachments, S>),
     RequestPull(Upgraded<RequestPull, S>),
 }
 
@@ -285,9 +333,42 @@ impl<S> SomeUpgraded<S> {
             Self::Git(up) => SomeUpgraded::Git(up.map(f)),
             Self::Membership(up) => SomeUpgraded::Membership(up.map(f)),
             Self::Interrogation(up) => SomeUpgraded::Interrogation(up.map(f)),
+            Self::Inventory(up) => SomeUpgraded::Inventory(up.map(f)),
+            Self::Ping(up) => SomeUpgraded::Ping(up.map(f)),
+            Self::Lfs(up) => SomeUpgraded::Lfs(up.map(f)),
+            Self::Attachments(up) => SomeUpgraded::Attachments(up.map(f)),
             Self::RequestPull(up) => SomeUpgraded::RequestPull(up.map(f)),
         }
     }
+
+    pub fn into_stream(self) -> S {
+        match self {
+            Self::Gossip(up) => up.into_stream(),
+            Self::Git(up) => up.into_stream(),
+            Self::Membership(up) => up.into_stream(),
+            Self::Interrogation(up) => up.into_stream(),
+            Self::Inventory(up) => up.into_stream(),
+            Self::Ping(up) => up.into_stream(),
+            Self::Lfs(up) => up.into_stream(),
+            Self::Attachments(up) => up.into_stream(),
+            Self::RequestPull(up) => up.into_stream(),
+        }
+    }
+
+    /// The [`UpgradeRequest`] this stream was upgraded with.
+    pub fn request(&self) -> UpgradeRequest {
+        match self {
+            Self::Gossip(_) => UpgradeRequest::Gossip,
+            Self::Git(_) => UpgradeRequest::Git,
+            Self::Membership(_) => UpgradeRequest::Membership,
+            Self::Interrogation(_) => UpgradeRequest::Interrogation,
+            Self::Inventory(_) => UpgradeRequest::Inventory,
+            Self::Ping(_) => UpgradeRequest::Ping,
+            Self::Lfs(_) => UpgradeRequest::Lfs,
+            Self::Attachments(_) => UpgradeRequest::Attachments,
+            Self::RequestPull(_) => UpgradeRequest::RequestPull,
+        }
+    }
 }
 
 pub async fn upgrade<U, S>(mut stream: S, upgrade: U) -> Result<Upgraded<U, S>, Error<S>>
@@ -334,6 +415,10 @@ where
                 UpgradeRequest::Interrogation => {
                     SomeUpgraded::Interrogation(Upgraded::new(incoming))
                 },
+                UpgradeRequest::Inventory => SomeUpgraded::Inventory(Upgraded::new(incoming)),
+                UpgradeRequest::Ping => SomeUpgraded::Ping(Upgraded::new(incoming)),
+                UpgradeRequest::Lfs => SomeUpgraded::Lfs(Upgraded::new(incoming)),
+                UpgradeRequest::Attachments => SomeUpgraded::Attachments(Upgraded::new(incoming)),
                 UpgradeRequest::RequestPull => SomeUpgraded::RequestPull(Upgraded::new(incoming)),
             };
 
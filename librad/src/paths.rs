@@ -102,14 +102,30 @@ impl Paths {
         Ok(self)
     }
 
+    /// The path of the RPC socket of the node `peer_id`.
+    ///
+    /// On Windows, this is the name of a named pipe, eg.
+    /// `\\.\pipe\link-peer-<peer_id>-rpc`.
     pub fn rpc_socket(&self, peer_id: &PeerId) -> PathBuf {
-        self.socket_dir
-            .join(format!("link-peer-{}-rpc.socket", peer_id))
+        self.socket(&format!("link-peer-{}-rpc", peer_id))
     }
 
+    /// The path of the events socket of the node `peer_id`.
+    ///
+    /// On Windows, this is the name of a named pipe, eg.
+    /// `\\.\pipe\link-peer-<peer_id>-events`.
     pub fn events_socket(&self, peer_id: &PeerId) -> PathBuf {
-        self.socket_dir
-            .join(format!("link-peer-{}-events.socket", peer_id))
+        self.socket(&format!("link-peer-{}-events", peer_id))
+    }
+
+    #[cfg(target_family = "unix")]
+    fn socket(&self, name: &str) -> PathBuf {
+        self.socket_dir.join(format!("{}.socket", name))
+    }
+
+    #[cfg(target_family = "windows")]
+    fn socket(&self, name: &str) -> PathBuf {
+        self.socket_dir.join(name)
     }
 }
 
@@ -123,6 +139,28 @@ pub fn seeds() -> Result<PathBuf, io::Error> {
     Ok(project_dirs()?.config_dir().join("seeds"))
 }
 
+/// Returns the path to the file recording the peer ids of seeds upon first
+/// use.
+///
+/// # Error
+///
+/// Returns [`io::Error`] if the configuration directory could not be
+/// determined, most likely due to the `$HOME` environment variable missing.
+pub fn seed_pins() -> Result<PathBuf, io::Error> {
+    Ok(project_dirs()?.config_dir().join("seed-pins.json"))
+}
+
+/// Returns the path to the file containing key rotations published by seed
+/// operators.
+///
+/// # Error
+///
+/// Returns [`io::Error`] if the configuration directory could not be
+/// determined, most likely due to the `$HOME` environment variable missing.
+pub fn seed_rotations() -> Result<PathBuf, io::Error> {
+    Ok(project_dirs()?.config_dir().join("seed-rotations.json"))
+}
+
 /// Returns [`ProjectDirs`] for this specific project (`radicle`).
 ///
 /// Returns [`io::Error`] if the project directories could not be determined,
@@ -140,7 +178,7 @@ pub(crate) fn project_dirs() -> Result<ProjectDirs, io::Error> {
 ///
 /// - On linux: $XDG_RUNTIME_DIR if set, otherwise /var/run
 /// - On Macos: /tmp
-/// - On windows: std::env::temp_dir
+/// - On windows: the named pipe namespace, `\\.\pipe`
 fn socket_dir() -> Result<std::path::PathBuf, io::Error> {
     socket_dir_imp()
 }
@@ -166,7 +204,8 @@ fn socket_dir_imp() -> Result<std::path::PathBuf, io::Error> {
         .unwrap_or_else(|| "/tmp".into()))
 }
 
+/// Named pipes live in their own namespace, not in the filesystem.
 #[cfg(target_family = "windows")]
 fn socket_dir_imp() -> Result<std::path::PathBuf, io::Error> {
-    Ok(std::env::temp_dir())
+    Ok(r"\\.\pipe".into())
 }
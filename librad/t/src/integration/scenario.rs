@@ -9,6 +9,7 @@ mod menage;
 mod passive_replication;
 #[cfg(feature = "replication-v3")]
 mod prune;
+mod subkey_revocation;
 mod tracked_references;
 mod updated_delegate;
 mod working_copy;
@@ -4,20 +4,22 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{convert::TryFrom, ops::Index as _, str::FromStr};
+use std::{convert::TryFrom, fs, ops::Index as _, str::FromStr};
 
-use it_helpers::{fixed::TestProject, testnet};
+use it_helpers::{fixed::TestProject, testnet, tmp};
 use lazy_static::lazy_static;
 use librad::{
     collaborative_objects::{
         CollaborativeObject,
         History,
         NewObjectSpec,
+        RedactSpec,
         TypeName,
         UpdateObjectSpec,
     },
     git::{
         identities,
+        storage::{ReadOnlyStorage as _, Storage},
         tracking,
         types::{Namespace, Reference},
     },
@@ -420,6 +422,90 @@ fn collab_object_crud() {
     })
 }
 
+#[test]
+fn redacted_content_can_be_pruned() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let key = SecretKey::new();
+    let (urn, id, redacted) = {
+        let storage = Storage::open(&*paths, key.clone()).unwrap();
+        let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+        let urn = project.urn();
+        let whoami = identities::local::load(&storage, urn.clone())
+            .unwrap()
+            .unwrap();
+        let collabs = storage.collaborative_objects(None);
+
+        let object = collabs
+            .create(
+                &whoami,
+                &urn,
+                NewObjectSpec {
+                    history: History::Automerge(init_history()),
+                    message: Some("first change".to_string()),
+                    typename: TYPENAME.clone(),
+                    schema_json: SCHEMA.clone(),
+                },
+            )
+            .unwrap();
+        let id = *object.id();
+        let doxxing = add_item(object.history(), "doxxing");
+        collabs
+            .update(
+                &whoami,
+                &urn,
+                UpdateObjectSpec {
+                    typename: TYPENAME.clone(),
+                    message: Some("add item".to_string()),
+                    object_id: id,
+                    changes: doxxing.clone(),
+                },
+            )
+            .unwrap();
+        let change = storage
+            .reference_oid(&Reference::rad_collaborative_object(
+                Namespace::from(urn.clone()),
+                None,
+                TYPENAME.clone(),
+                id,
+            ))
+            .unwrap();
+        let tombstone = collabs
+            .redact(
+                &whoami,
+                &urn,
+                RedactSpec {
+                    object_id: id,
+                    typename: TYPENAME.clone(),
+                    change: change.into(),
+                    reason: Some("personal information".to_string()),
+                },
+            )
+            .unwrap();
+        let content = git2::Oid::hash_object(git2::ObjectType::Blob, doxxing.as_bytes()).unwrap();
+        assert_eq!(git2::Oid::from(tombstone.content), content);
+
+        let object = collabs.retrieve(&urn, &TYPENAME, &id).unwrap().unwrap();
+        assert_state!(&object, serde_json::json!({ "items": [] }));
+
+        (urn, id, content)
+    };
+
+    // Prune the redacted content, which is still a loose object
+    let hex = redacted.to_string();
+    let objects = paths.git_dir().join("objects");
+    fs::remove_file(objects.join(&hex[..2]).join(&hex[2..])).unwrap();
+
+    let storage = Storage::open(&*paths, key).unwrap();
+    let object = storage
+        .collaborative_objects(None)
+        .retrieve(&urn, &TYPENAME, &id)
+        .unwrap()
+        .unwrap();
+    assert_state!(&object, serde_json::json!({ "items": [] }));
+}
+
 fn init_history() -> Vec<u8> {
     let mut backend = automerge::Backend::new();
     let mut frontend = automerge::Frontend::new();
@@ -0,0 +1,121 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use it_helpers::{
+    fixed::{TestPerson, TestProject},
+    testnet,
+};
+use librad::{
+    git::{identities, storage::Storage, tracking, Urn},
+    identities::{
+        payload::{self, PersonPayload},
+        subkey::{Capability, Grant, SubKeys},
+    },
+    PeerId,
+    SecretKey,
+};
+use test_helpers::logging;
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+/// Update the person `owner` to grant `keys` sub-keys for `proj`.
+fn grant(storage: &Storage, owner: &Urn, proj: &Urn, keys: &[PeerId]) -> anyhow::Result<()> {
+    let mut subkeys = SubKeys::default();
+    for key in keys {
+        subkeys.issue(
+            *key.as_public_key(),
+            Grant {
+                label: Some("ci".into()),
+                urns: Some(proj.clone()).into_iter().collect(),
+                capabilities: Some(Capability::PublishRefs).into_iter().collect(),
+                expires: None,
+            },
+        );
+    }
+    let payload = PersonPayload::new(payload::Person {
+        name: "alice".into(),
+    })
+    .with_ext(subkeys)?;
+    identities::person::update(storage, owner, None, Some(payload), None)?;
+    Ok(())
+}
+
+#[test]
+fn revoked_subkey_is_untracked() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let peer1 = net.peers().index(0);
+        let peer2 = net.peers().index(1);
+        let ci = PeerId::from(SecretKey::new());
+
+        let person = peer1
+            .using_storage(TestPerson::create)
+            .await
+            .unwrap()
+            .unwrap();
+        let proj = peer1
+            .using_storage(move |storage| TestProject::from_test_person(storage, person))
+            .await
+            .unwrap()
+            .unwrap();
+        let (owner, urn) = (proj.owner.urn(), proj.project.urn());
+
+        peer1
+            .using_storage({
+                let (owner, urn) = (owner.clone(), urn.clone());
+                move |storage| grant(storage, &owner, &urn, &[ci])
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        proj.pull(peer1, peer2).await.unwrap();
+
+        let is_tracked = || {
+            peer2.using_storage({
+                let urn = urn.clone();
+                move |storage| tracking::is_tracked(storage, &urn, Some(ci))
+            })
+        };
+        assert!(
+            is_tracked().await.unwrap().unwrap(),
+            "granted sub-key is tracked"
+        );
+
+        peer1
+            .using_storage({
+                let (owner, urn) = (owner.clone(), urn.clone());
+                move |storage| grant(storage, &owner, &urn, &[])
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        proj.pull(peer1, peer2).await.unwrap();
+
+        assert!(
+            !is_tracked().await.unwrap().unwrap(),
+            "revoked sub-key is untracked"
+        );
+        let recorded = peer2
+            .using_storage({
+                let urn = urn.clone();
+                move |storage| storage.config().map(|config| config.subkeys(&urn))
+            })
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(recorded.contains_key(&ci), "revoked sub-key stays recorded");
+    })
+}
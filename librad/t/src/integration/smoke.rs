@@ -4,9 +4,15 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 mod clone;
+mod connection_stats;
+#[cfg(not(feature = "replication-v3"))]
+mod dry_run;
+mod failover;
 mod fetch_limit;
 mod gossip;
 mod interrogation;
+mod membership;
+mod ping;
 mod regression;
 #[cfg(features = "replication-v3")]
 mod request_pull;
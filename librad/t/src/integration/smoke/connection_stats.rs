@@ -0,0 +1,38 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{ops::Index as _, time::Duration};
+
+use it_helpers::testnet;
+use test_helpers::logging;
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn reports_connected_peers() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let a = net.peers().index(0);
+        let b = net.peers().index(1);
+
+        let stats = a.connection_stats().await;
+        let to_b = stats
+            .get(&b.peer_id())
+            .expect("connection to b should be reported");
+        assert!(to_b.sent_packets > 0);
+        assert!(to_b.sent_bytes > 0);
+        assert!(to_b.cwnd > 0);
+        assert!(to_b.rtt > Duration::ZERO);
+        assert!(!stats.contains_key(&a.peer_id()));
+    })
+}
@@ -0,0 +1,63 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use it_helpers::{fixed::TestProject, testnet};
+use librad::{git::storage::ReadOnlyStorage as _, net::replication::Mode};
+use test_helpers::logging;
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn dry_run_does_not_fetch() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let host = net.peers().index(0);
+        let leecher = net.peers().index(1);
+        let TestProject { project, .. } = host
+            .using_storage(TestProject::create)
+            .await
+            .unwrap()
+            .unwrap();
+        let urn = project.urn();
+        let from = (host.peer_id(), host.listen_addrs().to_vec());
+
+        let dry_run = leecher
+            .replicate_dry_run(from.clone(), urn.clone())
+            .await
+            .unwrap();
+        assert_matches!(dry_run.mode, Mode::Clone);
+        assert!(!dry_run.updates.is_empty());
+        assert!(!dry_run.wants.is_empty());
+        assert!(dry_run.haves.is_empty());
+        assert!(!leecher
+            .using_storage({
+                let urn = urn.clone();
+                move |storage| storage.has_urn(&urn)
+            })
+            .await
+            .unwrap()
+            .unwrap());
+
+        leecher
+            .replicate(from.clone(), urn.clone(), None)
+            .await
+            .unwrap();
+
+        let dry_run = leecher.replicate_dry_run(from, urn).await.unwrap();
+        assert_matches!(dry_run.mode, Mode::Fetch);
+        assert!(dry_run.wants.is_empty());
+        assert!(dry_run.up_to_date > 0);
+    })
+}
@@ -0,0 +1,106 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use it_helpers::{fixed::TestProject, testnet};
+use librad::{
+    net::peer::{error, failover::Strategy},
+    PeerId,
+    SecretKey,
+};
+use test_helpers::logging;
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(3usize),
+        min_connected: 3,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn fails_over_to_next_provider() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let host = net.peers().index(0);
+        let other = net.peers().index(1);
+        let leecher = net.peers().index(2);
+        let TestProject { project, .. } = host
+            .using_storage(TestProject::create)
+            .await
+            .unwrap()
+            .unwrap();
+        let unreachable = PeerId::from(SecretKey::new());
+
+        let outcome = leecher
+            .replicate_any(
+                vec![
+                    (unreachable, vec![]),
+                    (host.peer_id(), host.listen_addrs().to_vec()),
+                    (other.peer_id(), other.listen_addrs().to_vec()),
+                ],
+                project.urn(),
+                None,
+                Strategy::FirstSuccess,
+            )
+            .await
+            .unwrap();
+
+        assert!(outcome.is_partial());
+        assert_eq!(
+            outcome.failed.iter().map(|(p, _)| *p).collect::<Vec<_>>(),
+            vec![unreachable]
+        );
+        assert_matches!(
+            outcome.failed[0].1,
+            error::Replicate::NoConnection(p) if p == unreachable
+        );
+        assert_eq!(
+            outcome.providers().copied().collect::<Vec<_>>(),
+            vec![host.peer_id()]
+        );
+        assert_eq!(outcome.skipped, vec![other.peer_id()]);
+    })
+}
+
+#[test]
+fn all_failed() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let host = net.peers().index(0);
+        let leecher = net.peers().index(1);
+        let TestProject { project, .. } = host
+            .using_storage(TestProject::create)
+            .await
+            .unwrap()
+            .unwrap();
+        let unreachable = PeerId::from(SecretKey::new());
+
+        let res = leecher
+            .replicate_any(
+                Some((unreachable, vec![])),
+                project.urn(),
+                None,
+                Strategy::All,
+            )
+            .await;
+        assert_matches!(res, Err(error::ReplicateAny::AllFailed(failed)) if failed.len() == 1);
+
+        let res = leecher
+            .replicate_any(
+                Vec::<(PeerId, Vec<_>)>::new(),
+                project.urn(),
+                None,
+                Strategy::All,
+            )
+            .await;
+        assert_matches!(res, Err(error::ReplicateAny::NoProviders));
+    })
+}
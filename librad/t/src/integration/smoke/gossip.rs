@@ -154,6 +154,20 @@ fn fetches_on_gossip_notify() {
         .await
         .unwrap();
 
+        // The fetch triggered by the announcement may still be in flight
+        peer2
+            .wait_for(
+                Reference::tag(
+                    Some(project.urn().into()),
+                    peer1.peer_id(),
+                    reflike!("MY-TAG"),
+                ),
+                tag_id,
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
         let commit_urn = project.urn().with_path(Some(
             Qualified::from(lit::refs_remotes(name::Component::from(&peer1.peer_id())))
                 .join(name::HEADS)
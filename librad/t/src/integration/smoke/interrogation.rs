@@ -76,6 +76,7 @@ fn responds() {
             requester.listen_addrs()[0],
             interrogation.echo_addr().await.unwrap()
         );
+        assert_eq!(None, interrogation.rotation().await.unwrap());
         let urns = interrogation.urns().await.unwrap();
         for urn in &[SomeUrn::Git(project.urn()), SomeUrn::Git(owner.urn())] {
             assert!(urns.contains(urn), "{} not in set", urn)
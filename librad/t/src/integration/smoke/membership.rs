@@ -0,0 +1,55 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{ops::Index as _, time::Duration};
+
+use futures::StreamExt as _;
+use it_helpers::testnet;
+use librad::net::peer::topology::Event;
+use test_helpers::logging;
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn starts_with_snapshot_and_reports_demotion() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let a = net.peers().index(0);
+        let b = net.peers().index(1);
+
+        let events = a.membership_events();
+        futures::pin_mut!(events);
+
+        match events.next().await {
+            Some(Event::Snapshot { active, .. }) => assert!(active.contains(&b.peer_id())),
+            other => panic!("expected snapshot, got {:?}", other),
+        }
+
+        assert!(a.disconnect(b.peer_id()).await);
+        link_async::timeout(Duration::from_secs(5), async {
+            while let Some(event) = events.next().await {
+                match event {
+                    Event::Demoted { peer, .. } | Event::Left { peer, .. }
+                        if peer == b.peer_id() =>
+                    {
+                        return;
+                    },
+                    _ => continue,
+                }
+            }
+            panic!("event stream ended")
+        })
+        .await
+        .expect("timed out waiting for b to be removed");
+    })
+}
@@ -0,0 +1,37 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use it_helpers::testnet;
+use test_helpers::logging;
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn responds() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let responder = net.peers().index(0);
+        let requester = net.peers().index(1);
+
+        requester
+            .ping((responder.peer_id(), responder.listen_addrs().to_vec()))
+            .await
+            .unwrap();
+        let health = requester.liveness().await;
+        let health = health.get(&responder.peer_id()).unwrap();
+        assert!(health.is_alive());
+        assert!(health.rtt.is_some());
+    })
+}
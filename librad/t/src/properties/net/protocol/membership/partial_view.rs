@@ -36,6 +36,11 @@ proptest! {
     fn evicted_peer_when_passive_is_full((local_id, remotes) in gen_peers()) {
         prop_evicted_peer_when_passive_is_full(local_id, remotes)
     }
+
+    #[test]
+    fn pinned_peer_stays_active((local_id, remotes) in gen_peers()) {
+        prop_pinned_peer_stays_active(local_id, remotes)
+    }
 }
 
 pub fn prop_evicted_peer_when_passive_is_full(local_id: PeerId, remotes: Vec<PeerId>) {
@@ -145,6 +150,25 @@ pub fn prop_demotion_when_active_is_full(local_id: PeerId, remotes: Vec<PeerId>)
     }
 }
 
+pub fn prop_pinned_peer_stays_active(local_id: PeerId, remotes: Vec<PeerId>) {
+    let mut view: PartialView<_, ()> = PartialView::new(local_id, rand::thread_rng(), 1, 1);
+    let pinned = remotes[0];
+    view.pin(pinned);
+
+    for remote in &remotes {
+        view.add_active(blank_peer_info(*remote));
+    }
+
+    assert!(view.is_active(&pinned), "pinned peer was demoted");
+    assert_eq!(view.num_active_unpinned(), remotes.len().min(2) - 1);
+    assert!(view.demote_random().iter().all(|t| match t {
+        Transition::Demoted(info) => info.peer_id != pinned,
+        Transition::Evicted(info) => info.peer_id != pinned,
+        Transition::Promoted(_) => false,
+    }));
+    assert!(view.is_active(&pinned), "pinned peer was demoted");
+}
+
 pub fn prop_ignores_local<R: rand::Rng, A: Ord + Clone>(mut view: PartialView<R, A>) {
     let local = view.local_id();
     let info = blank_peer_info(local);
@@ -3,13 +3,16 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod alias;
 #[cfg(not(feature = "replication-v3"))]
 mod fetch;
+mod identities;
 mod include;
 mod local;
 mod p2p;
 mod project;
 mod refs;
+mod signatures;
 mod storage;
 mod tracking;
 mod types;
@@ -0,0 +1,88 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        alias::{self, error, Alias, Name},
+        storage::Storage,
+    },
+    identities::payload,
+    SecretKey,
+};
+use test_helpers::logging;
+
+#[test]
+fn alias_syntax() {
+    assert_eq!("My-Proj2".parse::<Alias>().unwrap().as_str(), "my-proj2");
+    assert!("2proj".parse::<Alias>().is_err());
+    assert!("my proj".parse::<Alias>().is_err());
+    assert!("".parse::<Alias>().is_err());
+
+    assert_eq!(
+        Alias::slugify("42 Simpson's Road Rage!").unwrap().as_str(),
+        "simpson-s-road-rage"
+    );
+    assert_eq!(Alias::slugify("+++"), None);
+}
+
+#[test]
+fn resolve_and_collide() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { owner, project } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+
+    let suggested = alias::suggest(&store, &urn).unwrap().unwrap();
+    assert_eq!(suggested.as_str(), "radicle-link");
+    alias::set(&store, &suggested, &urn, false).unwrap();
+
+    // By alias, full URN, and prefix of the id
+    let by_alias = Name::Short("radicle-link".to_owned());
+    assert_eq!(alias::resolve(&store, &by_alias).unwrap(), urn);
+    assert_eq!(
+        alias::resolve(&store, &Name::from(urn.clone())).unwrap(),
+        urn
+    );
+    let prefix = Name::Short(urn.encode_id()[..8].to_owned());
+    assert_eq!(alias::resolve(&store, &prefix).unwrap(), urn);
+    assert!(matches!(
+        alias::resolve(&store, &Name::Short("nope".to_owned())),
+        Err(error::Resolve::NotFound(_))
+    ));
+
+    // Rebinding to a different URN is refused unless forced
+    let other = TestProject::from_project_payload(
+        &store,
+        owner,
+        payload::Project {
+            description: Some("the other radicle-link".into()),
+            ..TestProject::default_payload()
+        },
+    )
+    .unwrap()
+    .project
+    .urn();
+    assert!(matches!(
+        alias::set(&store, &suggested, &other, false),
+        Err(error::Set::Taken { urn: taken, .. }) if taken == urn
+    ));
+    assert_eq!(
+        alias::suggest(&store, &other).unwrap().unwrap().as_str(),
+        "radicle-link-2"
+    );
+
+    alias::set(&store, &suggested, &other, true).unwrap();
+    assert_eq!(alias::resolve(&store, &by_alias).unwrap(), other);
+    assert_eq!(
+        alias::list(&store).unwrap(),
+        vec![(suggested.clone(), other.clone())]
+    );
+
+    assert_eq!(alias::remove(&store, &suggested).unwrap(), Some(other));
+    assert!(alias::list(&store).unwrap().is_empty());
+}
@@ -0,0 +1,96 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use either::Either::Left;
+
+use it_helpers::tmp;
+use librad::{
+    git::identities::{self, diff},
+    identities::{delegation, git::IndirectDelegation, payload},
+    SecretKey,
+};
+use link_identities_test::helpers;
+
+lazy_static! {
+    static ref DYLAN: SecretKey = SecretKey::from_seed([
+        188, 166, 161, 203, 144, 68, 64, 48, 105, 98, 55, 215, 50, 154, 43, 236, 168, 133, 230, 36,
+        134, 79, 175, 109, 234, 123, 23, 114, 61, 82, 96, 52
+    ]);
+}
+
+fn payload(description: &str) -> payload::Project {
+    payload::Project {
+        name: "reMarkable 3".into(),
+        description: Some(description.into()),
+        default_branch: Some("eink".into()),
+    }
+}
+
+#[test]
+fn diff_payload_update() -> anyhow::Result<()> {
+    let storage = tmp::storage(DYLAN.clone());
+    let whoami = helpers::dylan(&storage, &DYLAN)?;
+    let old = identities::project::create(
+        &storage,
+        whoami,
+        payload("The next big thing in e-ink technology"),
+        delegation::Indirect::try_from_iter(Some(Left(DYLAN.public()))).unwrap(),
+    )?;
+    let new = identities::project::update(
+        &storage,
+        &old.urn(),
+        None,
+        payload::ProjectPayload::from(payload("The next big thing in paper")),
+        None,
+    )?;
+
+    let diff = diff::project(&storage, &old, &new)?;
+    assert!(diff.fast_forward);
+    assert!(!diff.changes_delegates());
+    assert!(diff.is_trivial());
+    assert_eq!(diff.payload.len(), 1);
+    assert_eq!(diff.payload[0].field.as_deref(), Some("description"));
+    assert_eq!(
+        diff.payload[0].new,
+        Some(serde_json::Value::from("The next big thing in paper"))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn diff_delegates_update() -> anyhow::Result<()> {
+    let storage = tmp::storage(DYLAN.clone());
+    let whoami = helpers::dylan(&storage, &DYLAN)?;
+    let old = identities::project::create(
+        &storage,
+        whoami,
+        payload("The next big thing in e-ink technology"),
+        delegation::Indirect::try_from_iter(Some(Left(DYLAN.public()))).unwrap(),
+    )?;
+    let key = SecretKey::new().public();
+    let new = identities::project::update(
+        &storage,
+        &old.urn(),
+        None,
+        None,
+        IndirectDelegation::try_from_iter(vec![Left(DYLAN.public()), Left(key)]).unwrap(),
+    )?;
+
+    let diff = diff::project(&storage, &old, &new)?;
+    assert!(diff.fast_forward);
+    assert!(diff.payload.is_empty());
+    assert_eq!(
+        diff.delegates.added.iter().cloned().collect::<Vec<_>>(),
+        vec![diff::Delegate::Key(key)]
+    );
+    assert!(diff.delegates.removed.is_empty());
+    assert!(!diff.is_trivial());
+
+    let reverse = diff::project(&storage, &new, &old)?;
+    assert!(!reverse.fast_forward);
+
+    Ok(())
+}
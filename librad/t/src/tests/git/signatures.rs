@@ -0,0 +1,175 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::signatures::{self, Policy, Verdict},
+    PublicKey,
+    SecretKey,
+};
+use sha2::{Digest as _, Sha512};
+
+fn put_string(w: &mut Vec<u8>, s: &[u8]) {
+    w.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    w.extend_from_slice(s);
+}
+
+fn ssh_string(parts: &[&[u8]]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for part in parts {
+        put_string(&mut buf, part);
+    }
+    buf
+}
+
+/// Produce an armored `SSHSIG` over `data`, as `ssh-keygen -Y sign -n git`
+/// would.
+fn sshsig(key: &SecretKey, data: &[u8]) -> String {
+    let mut signed = b"SSHSIG".to_vec();
+    put_string(&mut signed, b"git");
+    put_string(&mut signed, b"");
+    put_string(&mut signed, b"sha512");
+    put_string(&mut signed, &Sha512::digest(data));
+    let sig: [u8; 64] = key.sign(&signed).into();
+
+    let mut blob = b"SSHSIG".to_vec();
+    blob.extend_from_slice(&1u32.to_be_bytes());
+    put_string(
+        &mut blob,
+        &ssh_string(&[b"ssh-ed25519", key.public().as_ref()]),
+    );
+    put_string(&mut blob, b"git");
+    put_string(&mut blob, b"");
+    put_string(&mut blob, b"sha512");
+    put_string(&mut blob, &ssh_string(&[b"ssh-ed25519", &sig]));
+
+    let body = multibase::Base::Base64Pad.encode(blob);
+    let lines = body
+        .as_bytes()
+        .chunks(70)
+        .map(|l| std::str::from_utf8(l).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        "-----BEGIN SSH SIGNATURE-----\n{}\n-----END SSH SIGNATURE-----",
+        lines
+    )
+}
+
+fn commit(
+    repo: &git2::Repository,
+    parent: Option<git2::Oid>,
+    sign: impl FnOnce(&str) -> Option<String>,
+) -> git2::Oid {
+    let tree = repo
+        .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let author = git2::Signature::now("The Animal", "animal@muppets.com").unwrap();
+    let parent = parent.map(|oid| repo.find_commit(oid).unwrap());
+    let parents = parent.iter().collect::<Vec<_>>();
+    let buf = repo
+        .commit_create_buffer(&author, &author, "commit", &tree, &parents)
+        .unwrap();
+    let content = buf.as_str().unwrap();
+    let oid = match sign(content) {
+        None => repo.commit(None, &author, &author, "commit", &tree, &parents),
+        Some(sig) => repo.commit_signed(content, &sig, None),
+    };
+    oid.unwrap()
+}
+
+#[test]
+fn verify_sshsig() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init(tmp.path()).unwrap();
+    let key = SecretKey::new();
+    let other = SecretKey::new();
+
+    let signed = commit(&repo, None, |content| {
+        Some(sshsig(&key, content.as_bytes()))
+    });
+    assert_eq!(
+        signatures::verify(&repo, signed).unwrap(),
+        Verdict::Signed(key.public())
+    );
+
+    let forged = commit(&repo, None, |content| {
+        Some(sshsig(&key, format!("{}tampered", content).as_bytes()))
+    });
+    assert_eq!(signatures::verify(&repo, forged).unwrap(), Verdict::Invalid);
+
+    let pgp = commit(&repo, None, |_| {
+        Some("-----BEGIN PGP SIGNATURE-----\n\nabc\n-----END PGP SIGNATURE-----".to_owned())
+    });
+    assert_eq!(
+        signatures::verify(&repo, pgp).unwrap(),
+        Verdict::Unsupported
+    );
+
+    let unsigned = commit(&repo, None, |_| None);
+    assert_eq!(
+        signatures::verify(&repo, unsigned).unwrap(),
+        Verdict::Unsigned
+    );
+
+    let by_other = commit(&repo, None, |content| {
+        Some(sshsig(&other, content.as_bytes()))
+    });
+    assert_eq!(
+        signatures::verify(&repo, by_other).unwrap(),
+        Verdict::Signed(other.public())
+    );
+}
+
+#[test]
+fn policy_violations() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init(tmp.path()).unwrap();
+    let key = SecretKey::new();
+    let other = SecretKey::new();
+
+    let legacy = commit(&repo, None, |_| None);
+    let good = commit(&repo, Some(legacy), |content| {
+        Some(sshsig(&key, content.as_bytes()))
+    });
+    let bad = commit(&repo, Some(good), |content| {
+        Some(sshsig(&other, content.as_bytes()))
+    });
+
+    let policy = Policy {
+        branches: Default::default(),
+        keys: vec![PublicKey::from(key)].into_iter().collect(),
+        since: Some(legacy.into()),
+    };
+    assert!(policy.applies("main", Some("main")));
+    assert!(!policy.applies("dev", Some("main")));
+
+    let violations = policy.check(&repo, bad, None).unwrap();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(*violations[0].commit, bad);
+    assert_eq!(violations[0].verdict, Verdict::Signed(other.public()));
+
+    assert!(policy.check(&repo, good, None).unwrap().is_empty());
+}
+
+#[test]
+fn policy_skips_verified() {
+    let tmp = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init(tmp.path()).unwrap();
+    let key = SecretKey::new();
+
+    let unsigned = commit(&repo, None, |_| None);
+    let good = commit(&repo, Some(unsigned), |content| {
+        Some(sshsig(&key, content.as_bytes()))
+    });
+
+    let policy = Policy {
+        branches: Default::default(),
+        keys: vec![PublicKey::from(key)].into_iter().collect(),
+        since: None,
+    };
+    assert_eq!(policy.check(&repo, good, None).unwrap().len(), 1);
+    let violations = policy.check(&repo, good, Some(unsigned)).unwrap();
+    assert!(violations.is_empty());
+}
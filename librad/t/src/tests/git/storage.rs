@@ -3,5 +3,24 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod audit;
+mod backup;
+mod bundle;
 mod config;
+mod encryption;
+mod facade;
+mod forks;
+mod fsck;
+mod health;
+mod history;
+mod index;
+mod lease;
+mod lock;
+mod object_format;
+mod pool;
+mod reflog;
+mod requirements;
+mod snapshot;
+mod stats;
+mod txn;
 mod watch;
@@ -0,0 +1,173 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{fs, thread};
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        storage::{
+            audit::{error, Event, Log},
+            Storage,
+        },
+        tracking::{policy, track, Config},
+    },
+    PeerId,
+    SecretKey,
+};
+
+#[test]
+fn append_and_verify() {
+    let tmp = tempfile::tempdir().unwrap();
+    let key = SecretKey::new();
+    let log = Log::new(tmp.path());
+
+    assert_eq!(log.verify(&PeerId::from(&key)).unwrap().len, 0);
+    for i in 0..3 {
+        log.append(
+            &key,
+            Event::Command {
+                command: format!("command {}", i),
+                origin: "test".to_owned(),
+            },
+        )
+        .unwrap();
+    }
+
+    let mut exported = Vec::new();
+    let verified = log.export(&PeerId::from(&key), &mut exported).unwrap();
+    assert_eq!(verified.len, 3);
+    assert_eq!(exported, fs::read(log.path()).unwrap());
+    assert_matches!(
+        log.verify(&PeerId::from(SecretKey::new())),
+        Err(error::Verify::Signature { seq: 0 })
+    );
+}
+
+#[test]
+fn stale_lock_is_broken() {
+    let tmp = tempfile::tempdir().unwrap();
+    let key = SecretKey::new();
+    let log = Log::new(tmp.path());
+
+    // Left behind by a writer which crashed a long time ago
+    let lock = log.path().with_extension("log.lock");
+    fs::write(&lock, "4242 1000 00000000deadbeef").unwrap();
+
+    log.append(
+        &key,
+        Event::Command {
+            command: "command".to_owned(),
+            origin: "test".to_owned(),
+        },
+    )
+    .unwrap();
+    assert!(!lock.exists());
+    assert_eq!(log.verify(&PeerId::from(&key)).unwrap().len, 1);
+}
+
+#[test]
+fn stale_lock_is_broken_once() {
+    let tmp = tempfile::tempdir().unwrap();
+    let key = SecretKey::new();
+    let log = Log::new(tmp.path());
+
+    let lock = log.path().with_extension("log.lock");
+    fs::write(&lock, "4242 1000 00000000deadbeef").unwrap();
+
+    // All writers find the stale lock, but only one of them may break it
+    let writers = (0..8)
+        .map(|i| {
+            let (log, key) = (log.clone(), key.clone());
+            thread::spawn(move || {
+                log.append(
+                    &key,
+                    Event::Command {
+                        command: "command".to_owned(),
+                        origin: format!("writer-{}", i),
+                    },
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    for writer in writers {
+        writer.join().unwrap().unwrap();
+    }
+    assert_eq!(log.verify(&PeerId::from(&key)).unwrap().len, 8);
+}
+
+#[test]
+fn tampering_is_detected() {
+    let tmp = tempfile::tempdir().unwrap();
+    let key = SecretKey::new();
+    let log = Log::new(tmp.path());
+    for origin in ["alice", "bob", "carol"] {
+        log.append(
+            &key,
+            Event::Command {
+                command: "cancel".to_owned(),
+                origin: origin.to_owned(),
+            },
+        )
+        .unwrap();
+    }
+    let original = fs::read_to_string(log.path()).unwrap();
+
+    fs::write(log.path(), original.replace("bob", "eve")).unwrap();
+    assert_matches!(
+        log.verify(&PeerId::from(&key)),
+        Err(error::Verify::Hash { seq: 1, .. })
+    );
+
+    let without_second = original
+        .lines()
+        .enumerate()
+        .filter(|(i, _)| *i != 1)
+        .map(|(_, line)| format!("{}\n", line))
+        .collect::<String>();
+    fs::write(log.path(), without_second).unwrap();
+    assert_matches!(
+        log.verify(&PeerId::from(&key)),
+        Err(error::Verify::Sequence {
+            seq: 2,
+            expected: 1
+        })
+    );
+}
+
+#[test]
+fn storage_records_key_usage_and_tracking() {
+    let paths = tmp::paths();
+    let storage = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+    let peer = PeerId::from(SecretKey::new());
+    track(
+        &storage,
+        &project.urn(),
+        Some(peer),
+        Config::default(),
+        policy::Track::Any,
+    )
+    .unwrap()
+    .unwrap();
+
+    let log = storage.audit_log();
+    assert!(log.verify(storage.peer_id()).is_ok());
+    let events = log
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().event)
+        .collect::<Vec<_>>();
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, Event::IdentitySigned { urn, .. } if urn == &project.urn())));
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, Event::RefsSigned { urn, .. } if urn == &project.urn())));
+    assert!(events.iter().any(|e| matches!(
+        e,
+        Event::Tracked { urn, peer: Some(tracked), .. } if urn == &project.urn() && tracked == &peer
+    )));
+}
@@ -0,0 +1,123 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        storage::{backup::error, index::Filter, ReadOnly, Storage},
+        types::Namespace,
+    },
+    reflike,
+    SecretKey,
+};
+
+/// Commit on top of `refs/rad/id` of `project`, bypassing the storage.
+fn commit(paths: &librad::paths::Paths, project: &TestProject) -> git2::Oid {
+    let repo = git2::Repository::open(paths.git_dir()).unwrap();
+    let ns = Namespace::from(project.project.urn());
+    let parent = repo
+        .find_reference(&format!("refs/namespaces/{}/refs/rad/id", ns))
+        .unwrap()
+        .peel_to_commit()
+        .unwrap();
+    let tree = repo
+        .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let sig = git2::Signature::now("backup", "backup@example.com").unwrap();
+    let oid = repo
+        .commit(None, &sig, &sig, "backup test", &tree, &[&parent])
+        .unwrap();
+    repo.reference(
+        &format!("refs/namespaces/{}/refs/heads/backup-test", ns),
+        oid,
+        false,
+        "backup test",
+    )
+    .unwrap();
+    oid
+}
+
+#[test]
+fn full_backup_round_trip() {
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let project = TestProject::create(&store).unwrap();
+    let urn = project.project.urn();
+
+    let mut archive = Vec::new();
+    let manifest = store.read_only().backup(&urn, None, &mut archive).unwrap();
+    assert!(manifest.prerequisites.is_empty());
+
+    let other_paths = tmp::paths();
+    let other = Storage::open(&*other_paths, SecretKey::new()).unwrap();
+    let restored = other.restore(archive.as_slice()).unwrap();
+    assert_eq!(restored, manifest);
+
+    let replica = ReadOnly::open(&*other_paths).unwrap();
+    let snapshot = replica.snapshot(&urn).unwrap();
+    assert_eq!(
+        snapshot
+            .iter()
+            .map(|(name, oid)| (name.to_string(), *oid))
+            .collect::<std::collections::BTreeMap<_, _>>(),
+        manifest.refs
+    );
+
+    // The restored namespace is indexed
+    let indexed = other
+        .namespace_index()
+        .list(&Filter::default(), None, 10)
+        .unwrap();
+    assert!(indexed.entries.iter().any(|e| e.urn == urn));
+}
+
+#[test]
+fn incremental_backup() {
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let project = TestProject::create(&store).unwrap();
+    let urn = project.project.urn();
+
+    let mut full = Vec::new();
+    let base = store.read_only().backup(&urn, None, &mut full).unwrap();
+
+    let tip = commit(&paths, &project);
+    let mut incremental = Vec::new();
+    let manifest = store
+        .read_only()
+        .backup(&urn, Some(&base), &mut incremental)
+        .unwrap();
+    assert_eq!(manifest.base, Some(base.id().unwrap()));
+    assert!(!manifest.prerequisites.is_empty());
+    // The new commit and its (empty) tree
+    assert!(manifest.objects <= 2);
+
+    // The incremental archive can't be restored on its own
+    let other_paths = tmp::paths();
+    let other = Storage::open(&*other_paths, SecretKey::new()).unwrap();
+    assert!(matches!(
+        other.restore(incremental.as_slice()),
+        Err(error::Restore::MissingPrerequisite(_))
+    ));
+
+    other.restore(full.as_slice()).unwrap();
+    other.restore(incremental.as_slice()).unwrap();
+    let snapshot = ReadOnly::open(&*other_paths)
+        .unwrap()
+        .snapshot(&urn)
+        .unwrap();
+    assert_eq!(
+        snapshot.get(&reflike!("refs/heads/backup-test")),
+        Some(tip.into())
+    );
+
+    // Restoring the base again rewinds the namespace
+    other.restore(full.as_slice()).unwrap();
+    let snapshot = ReadOnly::open(&*other_paths)
+        .unwrap()
+        .snapshot(&urn)
+        .unwrap();
+    assert_eq!(snapshot.get(&reflike!("refs/heads/backup-test")), None);
+}
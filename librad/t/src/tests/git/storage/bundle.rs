@@ -0,0 +1,102 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        identities,
+        storage::{
+            bundle::{error, Manifest},
+            ReadOnlyStorage as _,
+            Storage,
+        },
+        tracking,
+        types::{Namespace, Reference},
+    },
+    SecretKey,
+};
+use test_helpers::logging;
+
+/// Split a bundle into its manifest and packfile.
+fn split(bundle: &[u8]) -> (Manifest, &[u8]) {
+    let nl = bundle.iter().position(|b| *b == b'\n').unwrap();
+    (
+        serde_json::from_slice(&bundle[..nl]).unwrap(),
+        &bundle[nl + 1..],
+    )
+}
+
+#[test]
+fn import_as_fetched() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, owner } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+
+    let mut bundle = Vec::new();
+    let manifest = store
+        .read_only()
+        .export_bundle(&urn, None, &mut bundle)
+        .unwrap();
+    assert_eq!(&manifest.peer, store.peer_id());
+    assert!(manifest.refs.contains_key("refs/rad/signed_refs"));
+    assert!(manifest.refs.contains_key("refs/rad/id"));
+
+    // Importing into the exporting storage is pointless
+    assert!(matches!(
+        store.import_bundle(bundle.as_slice()),
+        Err(error::Import::OwnBundle(_))
+    ));
+
+    let other_paths = tmp::paths();
+    let other = Storage::open(&*other_paths, SecretKey::new()).unwrap();
+    let imported = other.import_bundle(bundle.as_slice()).unwrap();
+    assert!(imported.created);
+    assert_eq!(imported.manifest, manifest);
+
+    let peer = manifest.peer;
+    assert!(tracking::is_tracked(&other, &urn, Some(peer)).unwrap());
+    assert!(other
+        .has_ref(&Reference::rad_signed_refs(Namespace::from(&urn), peer))
+        .unwrap());
+    let replicated = identities::project::get(&other, &urn).unwrap().unwrap();
+    assert_eq!(replicated.urn(), urn);
+    assert!(other.has_urn(&owner.urn()).unwrap());
+
+    // Importing the same bundle again is a no-op
+    let again = other.import_bundle(bundle.as_slice()).unwrap();
+    assert!(!again.created);
+}
+
+#[test]
+fn unsigned_refs_are_rejected() {
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+
+    let mut bundle = Vec::new();
+    store
+        .read_only()
+        .export_bundle(&urn, None, &mut bundle)
+        .unwrap();
+    let (mut manifest, pack) = split(&bundle);
+    let id = manifest.refs["refs/rad/id"];
+    manifest.refs.insert("refs/heads/sneaky".to_owned(), id);
+
+    let mut tampered = serde_json::to_vec(&manifest).unwrap();
+    tampered.push(b'\n');
+    tampered.extend_from_slice(pack);
+
+    let other_paths = tmp::paths();
+    let other = Storage::open(&*other_paths, SecretKey::new()).unwrap();
+    assert!(matches!(
+        other.import_bundle(tampered.as_slice()),
+        Err(error::Import::Unsigned(name)) if name == "refs/heads/sneaky"
+    ));
+    assert!(!other.has_urn(&urn).unwrap());
+}
@@ -0,0 +1,81 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fs;
+
+use librad::{
+    git::storage::{self, encryption, ReadOnly, Storage},
+    paths::Paths,
+    SecretKey,
+};
+
+/// A config whose commands succeed without mounting anything, so the storage
+/// stays locked.
+fn noop(cipher_dir: &std::path::Path) -> encryption::Config {
+    encryption::Config {
+        cipher_dir: cipher_dir.to_path_buf(),
+        init: vec!["true".to_owned()],
+        mount: vec!["true".to_owned()],
+        unmount: vec!["true".to_owned()],
+    }
+}
+
+#[test]
+fn unencrypted_by_default() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+
+    assert_eq!(
+        encryption::status(&paths).unwrap(),
+        encryption::Status::Unencrypted
+    );
+    assert!(encryption::config(&paths).unwrap().is_none());
+}
+
+#[test]
+fn locked_storage_is_not_opened() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+    let key = SecretKey::new();
+    let config = noop(&tmp.path().join("cipher"));
+
+    let res = encryption::setup(
+        &paths,
+        config.clone(),
+        &encryption::derive_key(&key).unwrap(),
+    );
+    assert!(matches!(res, Err(encryption::Error::NotMounted(_))));
+    assert_eq!(encryption::config(&paths).unwrap(), Some(config));
+    assert_eq!(
+        encryption::status(&paths).unwrap(),
+        encryption::Status::Locked
+    );
+
+    assert!(matches!(
+        Storage::open(&paths, key),
+        Err(storage::error::Init::Locked)
+    ));
+    assert!(matches!(
+        ReadOnly::open(&paths),
+        Err(storage::read::error::Init::Locked)
+    ));
+}
+
+#[test]
+fn setup_refuses_existing_storage() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+    let key = SecretKey::new();
+    Storage::open(&paths, key.clone()).unwrap();
+
+    let res = encryption::setup(
+        &paths,
+        noop(&tmp.path().join("cipher")),
+        &encryption::derive_key(&key).unwrap(),
+    );
+    assert!(matches!(res, Err(encryption::Error::NotEmpty(_))));
+    assert!(encryption::config(&paths).unwrap().is_none());
+    assert!(fs::read_dir(tmp.path().join("cipher")).is_err());
+}
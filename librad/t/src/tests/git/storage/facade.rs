@@ -0,0 +1,68 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use futures::future;
+use it_helpers::tmp;
+use librad::{
+    git::storage::{
+        facade::{self, AsyncStorage},
+        pool::{Initialised, ReadWriteConfig},
+        Pool,
+        Storage,
+    },
+    SecretKey,
+};
+use link_async::Spawner;
+
+#[tokio::test]
+async fn bounded_concurrency() {
+    let paths = tmp::paths();
+    let signer = SecretKey::new();
+    Storage::open(&*paths, signer.clone()).unwrap();
+
+    let pool = Pool::new(
+        ReadWriteConfig::new((*paths).clone(), signer, Initialised::no()),
+        4,
+    );
+    let store = AsyncStorage::new(
+        pool,
+        Arc::new(Spawner::from_current().unwrap()),
+        facade::Config {
+            max_concurrency: 2,
+            slow: Duration::from_secs(60),
+        },
+    );
+
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+    let ops = (0..8).map(|_| {
+        let running = running.clone();
+        let max_running = max_running.clone();
+        store.with("test", move |_| {
+            let now = running.fetch_add(1, SeqCst) + 1;
+            max_running.fetch_max(now, SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            running.fetch_sub(1, SeqCst);
+        })
+    });
+    for res in future::join_all(ops).await {
+        res.unwrap()
+    }
+
+    assert!(max_running.load(SeqCst) <= 2);
+    let stats = store.stats();
+    assert_eq!(stats.completed, 8);
+    assert_eq!(stats.running, 0);
+    assert_eq!(stats.waiting, 0);
+}
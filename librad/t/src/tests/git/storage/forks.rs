@@ -0,0 +1,106 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        storage::{forks::Owner, Storage},
+        types::Namespace,
+    },
+    PeerId,
+    SecretKey,
+};
+use test_helpers::logging;
+
+fn commit(repo: &git2::Repository, parents: &[&git2::Commit], msg: &str) -> git2::Oid {
+    let sig = git2::Signature::now("forks", "forks@example.com").unwrap();
+    let tree = repo
+        .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    repo.commit(None, &sig, &sig, msg, &tree, parents).unwrap()
+}
+
+#[test]
+fn divergence() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+    let namespace = Namespace::from(&urn);
+
+    let repo = git2::Repository::open(paths.git_dir()).unwrap();
+    let base = commit(&repo, &[], "base");
+    let base_commit = repo.find_commit(base).unwrap();
+    let ours = commit(&repo, &[&base_commit], "ours");
+    let theirs = commit(&repo, &[&base_commit], "theirs");
+    let unrelated = commit(&repo, &[], "unrelated");
+
+    let diverged = PeerId::from(SecretKey::new());
+    let behind = PeerId::from(SecretKey::new());
+    let stranger = PeerId::from(SecretKey::new());
+    for (name, oid) in [
+        ("refs/heads/main".to_owned(), ours),
+        (format!("refs/remotes/{}/heads/main", diverged), theirs),
+        (format!("refs/remotes/{}/heads/main", behind), base),
+        (format!("refs/remotes/{}/heads/main", stranger), unrelated),
+    ] {
+        repo.reference(
+            &format!("refs/namespaces/{}/{}", namespace, name),
+            oid,
+            false,
+            "forks test",
+        )
+        .unwrap();
+    }
+
+    let topology = store.update_forks(&urn).unwrap().unwrap();
+    let main = &topology.branches["main"];
+    assert_eq!(main.heads.len(), 4);
+    assert_eq!(main.divergences.len(), 6);
+    assert_eq!(main.head_of(&Owner::Local).unwrap().tip, ours.into());
+    assert_eq!(
+        main.head_of(&Owner::Remote(behind)).unwrap().tip,
+        base.into()
+    );
+
+    let rel = main.relation(ours.into(), theirs.into()).unwrap();
+    assert!(rel.is_diverged());
+    assert_eq!(rel.base, Some(base.into()));
+    assert_eq!((rel.ahead, rel.behind), (1, 1));
+
+    let rel = main.relation(ours.into(), base.into()).unwrap();
+    assert!(!rel.is_diverged());
+    assert_eq!((rel.ahead, rel.behind), (1, 0));
+    let rel = main.relation(base.into(), ours.into()).unwrap();
+    assert_eq!((rel.ahead, rel.behind), (0, 1));
+
+    assert!(!main
+        .relation(ours.into(), unrelated.into())
+        .unwrap()
+        .is_related());
+
+    // Incremental updates yield the same topology
+    let again = store.update_forks(&urn).unwrap().unwrap();
+    assert_eq!(again.branches, topology.branches);
+    assert_eq!(
+        store.read_only().forks(&urn).unwrap().unwrap().branches,
+        topology.branches
+    );
+}
+
+#[test]
+fn removed_with_namespace() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    assert!(store.read_only().forks(&project.urn()).unwrap().is_some());
+    store.remove_namespace(&project.urn()).unwrap();
+    assert!(store.read_only().forks(&project.urn()).unwrap().is_none());
+}
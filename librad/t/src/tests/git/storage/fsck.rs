@@ -0,0 +1,45 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fs;
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::storage::{ReadOnly, Storage},
+    SecretKey,
+};
+
+#[test]
+fn intact_storage_has_no_dangling_refs() {
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    TestProject::create(&store).unwrap();
+
+    let replica = ReadOnly::open(&*paths).unwrap();
+    assert_eq!(replica.dangling_refs().unwrap(), Vec::<String>::new());
+}
+
+#[test]
+fn detects_missing_target() {
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    TestProject::create(&store).unwrap();
+
+    // Write the ref file directly, as git2 refuses to create references to
+    // missing objects
+    let heads = paths.git_dir().join("refs").join("heads");
+    fs::create_dir_all(&heads).unwrap();
+    fs::write(
+        heads.join("dangling"),
+        "c0ffeec0ffeec0ffeec0ffeec0ffeec0ffeec0ff\n",
+    )
+    .unwrap();
+
+    let replica = ReadOnly::open(&*paths).unwrap();
+    assert_eq!(
+        replica.dangling_refs().unwrap(),
+        vec!["refs/heads/dangling".to_owned()]
+    );
+}
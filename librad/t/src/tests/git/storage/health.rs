@@ -0,0 +1,72 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use librad::git::storage::health::{self, Health, Reason, Transition};
+
+#[derive(Debug, thiserror::Error)]
+enum Wrapped {
+    #[error("fetch failed")]
+    Fetch(#[source] io::Error),
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+#[cfg(unix)]
+const ENOSPC: i32 = libc::ENOSPC;
+#[cfg(windows)]
+const ENOSPC: i32 = 112; // ERROR_DISK_FULL
+
+#[test]
+fn classifies_write_failures() {
+    let no_space = Wrapped::Fetch(io::Error::from_raw_os_error(ENOSPC));
+    assert_eq!(health::write_failure(&no_space), Some(Reason::NoSpace));
+
+    let other = Wrapped::Fetch(io::Error::from(io::ErrorKind::ConnectionReset));
+    assert_eq!(health::write_failure(&other), None)
+}
+
+#[test]
+fn ignores_error_messages() {
+    let relayed = Wrapped::Git(git2::Error::new(
+        git2::ErrorCode::GenericError,
+        git2::ErrorClass::Net,
+        "remote: fatal: write error: No space left on device",
+    ));
+    assert_eq!(health::write_failure(&relayed), None)
+}
+
+#[test]
+fn recovers_when_writable() {
+    let tmp = tempfile::tempdir().unwrap();
+    let transitions = Arc::new(Mutex::new(Vec::new()));
+    let health = Health::new(tmp.path(), {
+        let transitions = transitions.clone();
+        move |t| transitions.lock().unwrap().push(t)
+    });
+
+    let err = io::Error::from_raw_os_error(libc::ENOSPC);
+    assert_eq!(health.observe(&err), Some(Reason::NoSpace));
+    assert_eq!(health.observe(&err), Some(Reason::NoSpace));
+    assert!(health.is_read_only());
+
+    health.probe().unwrap();
+    assert!(!health.is_read_only());
+    assert!(!tmp.path().join(health::PROBE_FILE_NAME).exists());
+
+    let transitions = transitions.lock().unwrap();
+    assert_eq!(transitions.len(), 2, "{:?}", transitions);
+    assert_eq!(
+        transitions[0],
+        Transition::ReadOnly {
+            reason: Reason::NoSpace
+        }
+    );
+    assert!(matches!(transitions[1], Transition::Writable { .. }))
+}
@@ -0,0 +1,99 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{storage::Storage, types::Namespace},
+    reflike,
+    SecretKey,
+};
+use test_helpers::logging;
+
+#[test]
+fn tips_at() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+    let history = store.read_only().history(&urn);
+
+    let t0 = SystemTime::now();
+    let first = store.read_only().snapshot(&urn).unwrap();
+    assert!(history.record(&first, t0).unwrap().is_some());
+    // Nothing changed
+    assert!(history.record(&first, t0).unwrap().is_none());
+
+    let tip = first.tip().unwrap();
+    let writer = git2::Repository::open(paths.git_dir()).unwrap();
+    writer
+        .reference(
+            &format!(
+                "refs/namespaces/{}/refs/heads/history-test",
+                Namespace::from(&urn)
+            ),
+            tip.into(),
+            false,
+            "history test",
+        )
+        .unwrap();
+    let t1 = t0 + Duration::from_secs(60);
+    let second = store.read_only().snapshot(&urn).unwrap();
+    let entry = history.record(&second, t1).unwrap().unwrap();
+    assert_eq!(entry.changes.len(), 1);
+    assert_eq!(
+        entry.changes.get(&reflike!("refs/heads/history-test")),
+        Some(&Some(tip))
+    );
+
+    let before = store
+        .read_only()
+        .tips_at(&urn, t0 - Duration::from_secs(60))
+        .unwrap();
+    assert!(before.is_empty());
+
+    let at_t0 = store.read_only().tips_at(&urn, t0).unwrap();
+    assert_eq!(at_t0.len(), first.len());
+    assert_eq!(at_t0.get(&reflike!("refs/heads/history-test")), None);
+
+    let at_t1 = store.read_only().tips_at(&urn, t1).unwrap();
+    assert_eq!(at_t1.len(), second.len());
+    assert_eq!(at_t1.get(&reflike!("refs/heads/history-test")), Some(&tip));
+}
+
+#[test]
+fn record_from_checkpoint() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+    let history = store.read_only().history(&urn);
+    let checkpoint = history.path().with_extension("tips.json");
+
+    let t0 = SystemTime::now();
+    let first = store.read_only().snapshot(&urn).unwrap();
+    history.record(&first, t0).unwrap().unwrap();
+    assert!(checkpoint.is_file());
+
+    // A lost checkpoint is rebuilt from the log
+    fs::remove_file(&checkpoint).unwrap();
+    assert!(history.record(&first, t0).unwrap().is_none());
+    assert_eq!(history.latest().unwrap().len(), first.len());
+
+    // So is a checkpoint ahead of the log
+    fs::write(history.path(), b"").unwrap();
+    assert!(history.latest().unwrap().is_empty());
+    let entry = history.record(&first, t0).unwrap().unwrap();
+    assert_eq!(entry.changes.len(), first.len());
+    assert!(history.record(&first, t0).unwrap().is_none());
+}
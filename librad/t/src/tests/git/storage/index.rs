@@ -0,0 +1,122 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::{Duration, SystemTime};
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        storage::{
+            index::{self, Filter, Index},
+            Storage,
+        },
+        tracking::{policy, track, untrack, Config, UntrackArgs},
+        Urn,
+    },
+    PeerId,
+    SecretKey,
+};
+use test_helpers::logging;
+
+fn urns(page: &index::Page) -> Vec<Urn> {
+    page.entries.iter().map(|e| e.urn.clone()).collect()
+}
+
+#[test]
+fn indexed_on_write() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, owner } = TestProject::create(&store).unwrap();
+
+    let index = store.namespace_index();
+    let all = index.list(&Filter::default(), None, 10).unwrap();
+    let mut expected = vec![project.urn(), owner.urn()];
+    expected.sort();
+    assert_eq!(urns(&all), expected);
+    assert!(all.next.is_none());
+    assert!(all.entries.iter().all(|e| e.has_identity && !e.tracked));
+
+    let remote = PeerId::from(SecretKey::new());
+    track(
+        &store,
+        &project.urn(),
+        Some(remote),
+        Config::default(),
+        policy::Track::Any,
+    )
+    .unwrap()
+    .unwrap();
+    let tracked = Filter {
+        tracked_only: true,
+        ..Filter::default()
+    };
+    assert_eq!(
+        urns(&index.list(&tracked, None, 10).unwrap()),
+        vec![project.urn()]
+    );
+
+    untrack(&store, &project.urn(), remote, UntrackArgs::default())
+        .unwrap()
+        .unwrap();
+    assert!(index.list(&tracked, None, 10).unwrap().entries.is_empty());
+
+    let future = Filter {
+        updated_since: Some(SystemTime::now() + Duration::from_secs(3600)),
+        ..Filter::default()
+    };
+    assert!(index.list(&future, None, 10).unwrap().entries.is_empty());
+}
+
+#[test]
+fn pagination() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    TestProject::create(&store).unwrap();
+
+    let index = store.namespace_index();
+    let first = index.list(&Filter::default(), None, 1).unwrap();
+    assert_eq!(first.entries.len(), 1);
+    let after = first.next.clone().unwrap();
+    let second = index.list(&Filter::default(), Some(&after), 1).unwrap();
+    assert_eq!(second.entries.len(), 1);
+    assert!(second.next.is_none());
+    assert_ne!(first.entries, second.entries);
+}
+
+#[test]
+fn rebuild_matches_incremental() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    TestProject::create(&store).unwrap();
+
+    let index = store.namespace_index();
+    let incremental = urns(&index.list(&Filter::default(), None, 10).unwrap());
+
+    std::fs::remove_file(index.path()).unwrap();
+    assert!(!index.exists());
+    let rebuilt = index::list(&store, &Filter::default(), None, 10).unwrap();
+    assert!(index.exists());
+    assert_eq!(urns(&rebuilt), incremental);
+}
+
+#[test]
+fn shared_between_handles() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let index = Index::for_storage(store.path());
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    assert!(index.get(&project.urn()).unwrap().is_some());
+    assert!(store.remove_namespace(&project.urn()).unwrap() > 0);
+    assert!(index.get(&project.urn()).unwrap().is_none());
+}
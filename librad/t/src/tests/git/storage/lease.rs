@@ -0,0 +1,156 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{sync::Arc, thread, time::Duration};
+
+use librad::{
+    git::{
+        storage::{
+            lease::{self, Leases},
+            Locking,
+            Storage,
+        },
+        Urn,
+    },
+    paths::Paths,
+    SecretKey,
+};
+
+fn urn(hash: &str) -> Urn {
+    Urn::new(
+        git2::Oid::hash_object(git2::ObjectType::Blob, hash.as_bytes())
+            .unwrap()
+            .into(),
+    )
+}
+
+#[test]
+fn expired_lease_is_fenced() {
+    let tmp = tempfile::tempdir().unwrap();
+    let leases = Leases::with_ttl(tmp.path(), Duration::from_millis(100));
+    let a = urn("a");
+
+    let first = leases.acquire(&a).unwrap();
+    assert_eq!(first.token(), 1);
+    first.check().unwrap();
+
+    thread::sleep(Duration::from_millis(200));
+    let second = Leases::with_ttl(tmp.path(), Duration::from_secs(60))
+        .acquire(&a)
+        .unwrap();
+    assert_eq!(second.token(), 2);
+    assert_matches!(
+        first.check(),
+        Err(lease::Error::Fenced {
+            token: 1,
+            current: 2,
+            ..
+        })
+    );
+
+    // Dropping the superseded lease must not release the new one
+    drop(first);
+    second.check().unwrap();
+}
+
+#[test]
+fn released_lease_can_be_reacquired() {
+    let tmp = tempfile::tempdir().unwrap();
+    let leases = Leases::new(tmp.path());
+    let (a, b) = (urn("a"), urn("b"));
+
+    let held = leases.acquire_many(&[a.clone(), b]).unwrap();
+    assert_eq!(held.len(), 2);
+    drop(held);
+
+    let again = leases.acquire(&a).unwrap();
+    assert_eq!(again.token(), 2);
+    again.check().unwrap();
+}
+
+#[test]
+fn storage_locking_switch() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+    let key = SecretKey::new();
+    let a = urn("a");
+
+    {
+        let storage = Storage::open(&paths, key.clone()).unwrap();
+        assert_eq!(
+            storage.locking(),
+            Locking::Auto.resolve(storage.path()),
+            "unset `rad.locking` should be auto"
+        );
+        storage
+            .config()
+            .unwrap()
+            .set_locking(Locking::Lease)
+            .unwrap();
+    }
+
+    let storage = Storage::open(&paths, key).unwrap();
+    assert_eq!(storage.locking(), Locking::Lease);
+    let guard = storage.lock_namespace(&a).unwrap();
+    assert_eq!(guard.leases().len(), 1);
+    guard.check().unwrap();
+}
+
+#[test]
+fn reentered_namespace_shares_lease() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+    let key = SecretKey::new();
+    let a = urn("a");
+
+    Storage::open(&paths, key.clone())
+        .unwrap()
+        .config()
+        .unwrap()
+        .set_locking(Locking::Lease)
+        .unwrap();
+    let storage = Storage::open(&paths, key).unwrap();
+
+    let outer = storage.lock_namespace(&a).unwrap();
+    let token = outer.leases()[0].token();
+    {
+        let inner = storage.lock_namespace(&a).unwrap();
+        assert_eq!(inner.leases().len(), 1);
+        assert!(Arc::ptr_eq(&outer.leases()[0], &inner.leases()[0]));
+        inner.check().unwrap();
+    }
+    // Dropping the inner guard must not release the lease
+    outer.check().unwrap();
+    drop(outer);
+
+    let again = storage.lock_namespace(&a).unwrap();
+    assert_eq!(again.leases()[0].token(), token + 1);
+}
+
+#[test]
+fn renew_keeps_lease_alive() {
+    let tmp = tempfile::tempdir().unwrap();
+    let leases = Leases::with_ttl(tmp.path(), Duration::from_millis(200));
+    let a = urn("a");
+
+    let held = leases.acquire(&a).unwrap();
+    for _ in 0..4 {
+        thread::sleep(Duration::from_millis(100));
+        held.renew().unwrap();
+    }
+    // Had the lease not been renewed, this would break it
+    let other = Leases::with_ttl(tmp.path(), Duration::from_millis(200));
+    let handle = thread::spawn(move || other.acquire(&a).unwrap().token());
+    thread::sleep(Duration::from_millis(100));
+    held.check().unwrap();
+    drop(held);
+    assert_eq!(handle.join().unwrap(), 2);
+}
+
+#[test]
+fn probe_local() {
+    let tmp = tempfile::tempdir().unwrap();
+    lease::probe(tmp.path()).unwrap();
+}
@@ -0,0 +1,248 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{sync::mpsc, thread, time::Duration};
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        storage::{
+            lock::{self, Namespaces},
+            txn::Previous,
+            ReadOnlyStorage as _,
+            Storage,
+        },
+        types::{Namespace, Reference},
+        Urn,
+    },
+    reflike,
+    SecretKey,
+};
+
+fn urn(hash: &str) -> Urn {
+    Urn::new(
+        git2::Oid::hash_object(git2::ObjectType::Blob, hash.as_bytes())
+            .unwrap()
+            .into(),
+    )
+}
+
+#[test]
+fn shared_by_path() {
+    let tmp = tempfile::tempdir().unwrap();
+    let locks = Namespaces::for_storage(tmp.path());
+    let other = Namespaces::for_storage(tmp.path());
+    let a = urn("a");
+
+    let guard = locks.lock(&a).unwrap();
+    assert!(other.is_locked(&a));
+    drop(guard);
+    assert!(!other.is_locked(&a));
+}
+
+#[test]
+fn distinct_namespaces_do_not_block() {
+    let tmp = tempfile::tempdir().unwrap();
+    let locks = Namespaces::for_storage(tmp.path());
+    let (a, b) = (urn("a"), urn("b"));
+
+    let _a = locks.lock(&a).unwrap();
+    let (tx, rx) = mpsc::channel();
+    let handle = thread::spawn({
+        let locks = locks.clone();
+        move || {
+            let _b = locks.lock(&b).unwrap();
+            tx.send(()).unwrap();
+        }
+    });
+
+    assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    handle.join().unwrap();
+}
+
+#[test]
+fn same_namespace_blocks() {
+    let tmp = tempfile::tempdir().unwrap();
+    let locks = Namespaces::for_storage(tmp.path());
+    let a = urn("a");
+
+    let guard = locks.lock(&a).unwrap();
+    let (tx, rx) = mpsc::channel();
+    let handle = thread::spawn({
+        let locks = locks.clone();
+        let a = a.clone();
+        move || {
+            let _a = locks.lock(&a).unwrap();
+            tx.send(()).unwrap();
+        }
+    });
+
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    drop(guard);
+    assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    handle.join().unwrap();
+}
+
+#[test]
+fn lock_many_is_ordered() {
+    let tmp = tempfile::tempdir().unwrap();
+    let locks = Namespaces::for_storage(tmp.path());
+    let (a, b) = (urn("a"), urn("b"));
+
+    let handles = (0..8)
+        .map(|i| {
+            let locks = locks.clone();
+            let urns = if i % 2 == 0 {
+                vec![a.clone(), b.clone()]
+            } else {
+                vec![b.clone(), a.clone()]
+            };
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    let guard = locks.lock_many(urns.clone()).unwrap();
+                    assert_eq!(guard.urns().len(), 2);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        handle.join().unwrap()
+    }
+    assert!(!locks.is_locked(&a));
+    assert!(!locks.is_locked(&b));
+}
+
+#[test]
+fn reentrant() {
+    let tmp = tempfile::tempdir().unwrap();
+    let locks = Namespaces::for_storage(tmp.path());
+    let a = urn("a");
+
+    let outer = locks.lock(&a).unwrap();
+    let inner = locks.lock(&a).unwrap();
+    assert_eq!(inner.urns().len(), 1);
+    drop(inner);
+    assert!(locks.is_locked(&a));
+
+    let (tx, rx) = mpsc::channel();
+    let handle = thread::spawn({
+        let locks = locks.clone();
+        let a = a.clone();
+        move || {
+            let _a = locks.lock(&a).unwrap();
+            tx.send(()).unwrap();
+        }
+    });
+
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    drop(outer);
+    assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    handle.join().unwrap();
+    assert!(!locks.is_locked(&a));
+}
+
+#[test]
+fn out_of_order_does_not_wait() {
+    let tmp = tempfile::tempdir().unwrap();
+    let locks = Namespaces::for_storage(tmp.path());
+    let (a, b) = {
+        let (x, y) = (urn("a"), urn("b"));
+        if x < y {
+            (x, y)
+        } else {
+            (y, x)
+        }
+    };
+
+    // Available namespaces are taken out of order
+    let outer = locks.lock(&b).unwrap();
+    let inner = locks.lock(&a).unwrap();
+    drop(inner);
+
+    // Busy ones are not waited for
+    let (tx, rx) = mpsc::channel();
+    let handle = thread::spawn({
+        let locks = locks.clone();
+        let a = a.clone();
+        move || {
+            let _a = locks.lock(&a).unwrap();
+            tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(200));
+        }
+    });
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(matches!(
+        locks.lock_many(vec![a.clone(), b.clone()]),
+        Err(lock::Error::Order { .. })
+    ));
+    assert!(locks.is_locked(&b));
+    drop(outer);
+    handle.join().unwrap();
+    assert!(!locks.is_locked(&a));
+    assert!(!locks.is_locked(&b));
+}
+
+/// Two writers, each with their own [`Storage`], increment a counter stored
+/// under the same namespace. Holding the namespace lock across the
+/// read-modify-write means neither ever observes a stale value.
+#[test]
+fn concurrent_writers_same_namespace() {
+    const ROUNDS: u64 = 20;
+
+    let paths = tmp::paths();
+    let key = SecretKey::new();
+    let urn = {
+        let store = Storage::open(&*paths, key.clone()).unwrap();
+        let TestProject { project, .. } = TestProject::create(&store).unwrap();
+        project.urn()
+    };
+    let counter = Reference::head(Namespace::from(&urn), None, reflike!("counter"));
+
+    let handles = (0..2)
+        .map(|_| {
+            let paths = (*paths).clone();
+            let key = key.clone();
+            let urn = urn.clone();
+            let counter = counter.clone();
+            thread::spawn(move || {
+                let store = Storage::open(&paths, key).unwrap();
+                let repo = git2::Repository::open_bare(paths.git_dir()).unwrap();
+                for _ in 0..ROUNDS {
+                    let _lock = store.lock_namespace(&urn).unwrap();
+                    let (value, previous) = match store.reference(&counter).unwrap() {
+                        None => (0, Previous::MustNotExist),
+                        Some(r) => {
+                            let oid = r.target().unwrap();
+                            let blob = repo.find_blob(oid).unwrap();
+                            let value = std::str::from_utf8(blob.content())
+                                .unwrap()
+                                .parse::<u64>()
+                                .unwrap();
+                            (value, Previous::MustBe(oid.into()))
+                        },
+                    };
+                    thread::yield_now();
+                    let next = repo.blob((value + 1).to_string().as_bytes()).unwrap();
+                    store
+                        .transaction(&urn, "increment")
+                        .write(reflike!("refs/heads/counter"), next, previous)
+                        .commit()
+                        .unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        handle.join().unwrap()
+    }
+
+    let store = Storage::open(&*paths, key).unwrap();
+    let oid = store.reference_oid(&counter).unwrap();
+    let repo = git2::Repository::open_bare(paths.git_dir()).unwrap();
+    let blob = repo.find_blob(oid.into()).unwrap();
+    assert_eq!(blob.content(), (2 * ROUNDS).to_string().as_bytes());
+}
@@ -0,0 +1,45 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use it_helpers::tmp;
+use librad::{
+    git::storage::{error, read, ReadOnly, Storage},
+    SecretKey,
+};
+
+#[test]
+fn refuses_sha256_storage() {
+    let paths = tmp::paths();
+    {
+        let repo = git2::Repository::init_bare(paths.git_dir()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_i32("core.repositoryformatversion", 1).unwrap();
+        config.set_str("extensions.objectformat", "sha256").unwrap();
+    }
+
+    assert_matches!(
+        Storage::open(&*paths, SecretKey::new()).map(|_| ()), // map to avoid `Debug` impl
+        Err(error::Init::ObjectFormat(format)) if format == "sha256"
+    );
+    assert_matches!(
+        ReadOnly::open(&*paths).map(|_| ()),
+        Err(read::error::Init::ObjectFormat(format)) if format == "sha256"
+    );
+}
+
+#[test]
+fn accepts_explicit_sha1_storage() {
+    let paths = tmp::paths();
+    Storage::open(&*paths, SecretKey::new()).unwrap();
+    {
+        let repo = git2::Repository::open_bare(paths.git_dir()).unwrap();
+        repo.config()
+            .unwrap()
+            .set_str("extensions.objectformat", "sha1")
+            .unwrap();
+    }
+
+    assert!(ReadOnly::open(&*paths).is_ok());
+}
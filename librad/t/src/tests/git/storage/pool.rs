@@ -0,0 +1,69 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::{Duration, Instant};
+
+use it_helpers::tmp;
+use librad::{
+    git::storage::{
+        pool::{Initialised, ReadWriteConfig},
+        Pool,
+        PoolError,
+        Storage,
+    },
+    SecretKey,
+};
+
+fn pool(paths: &librad::paths::Paths) -> Pool<Storage> {
+    let signer = SecretKey::new();
+    Storage::open(paths, signer.clone()).unwrap();
+    Pool::new(
+        ReadWriteConfig::new(paths.clone(), signer, Initialised::no()),
+        2,
+    )
+}
+
+#[tokio::test]
+async fn closed_pool_rejects() {
+    let paths = tmp::paths();
+    let pool = pool(&paths);
+    drop(pool.get().await.unwrap());
+
+    pool.close(Instant::now()).await.unwrap();
+    assert!(pool.is_closed());
+    assert!(matches!(pool.get().await, Err(PoolError::Closed)));
+    assert_eq!(pool.in_flight(), 0)
+}
+
+#[tokio::test]
+async fn close_waits_for_in_flight() {
+    let paths = tmp::paths();
+    let pool = pool(&paths);
+    let storage = pool.get().await.unwrap();
+    assert_eq!(pool.in_flight(), 1);
+
+    let release = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        drop(storage)
+    });
+    pool.close(Instant::now() + Duration::from_secs(10))
+        .await
+        .unwrap();
+    assert_eq!(pool.in_flight(), 0);
+    release.await.unwrap()
+}
+
+#[tokio::test]
+async fn close_gives_up_at_deadline() {
+    let paths = tmp::paths();
+    let pool = pool(&paths);
+    let _storage = pool.get().await.unwrap();
+
+    let err = pool
+        .close(Instant::now() + Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert_eq!(err.in_flight, 1)
+}
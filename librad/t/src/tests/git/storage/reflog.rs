@@ -0,0 +1,79 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        storage::{
+            reflog::{self, Message, Op},
+            txn::Previous,
+            ReadOnlyStorage as _,
+            Storage,
+        },
+        types::{Namespace, Reference},
+    },
+    reflike,
+    PeerId,
+    SecretKey,
+};
+use test_helpers::logging;
+
+#[test]
+fn message_roundtrip() {
+    let peer = PeerId::from(SecretKey::new());
+    let msg = reflog::with_request("42", || {
+        Message::new(Op::Replicate, "fast-forward").with_peer(peer)
+    });
+    assert_eq!(msg.request.as_deref(), Some("42"));
+    assert_eq!(
+        msg.to_string(),
+        format!("replicate: fast-forward; peer={}; request=42", peer)
+    );
+    assert_eq!(msg.to_string().parse::<Message>().unwrap(), msg);
+
+    assert!(Message::new(Op::Track, "create").request.is_none());
+    assert!("commit: initial".parse::<Message>().is_err());
+}
+
+#[test]
+fn namespace_reflog() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+    let tip = store
+        .reference_oid(&Reference::rad_id(Namespace::from(&urn)))
+        .unwrap();
+
+    reflog::with_request("req-1", || {
+        store
+            .transaction(&urn, Message::new(Op::Identity, "update"))
+            .write(reflike!("refs/heads/reflog"), tip, Previous::MustNotExist)
+            .commit()
+    })
+    .unwrap();
+
+    let entries = store.read_only().reflog(&urn).unwrap();
+    let created = entries
+        .iter()
+        .find(|e| e.refname == reflike!("refs/rad/id"))
+        .expect("reflog of rad/id");
+    assert_eq!(
+        created.message().map(|m| (m.op, m.action)),
+        Some((Op::Identity, "create".to_owned()))
+    );
+
+    let updated = entries
+        .iter()
+        .find(|e| e.refname == reflike!("refs/heads/reflog"))
+        .expect("reflog of refs/heads/reflog");
+    assert_eq!(updated.old, None);
+    assert_eq!(updated.new, tip);
+    let msg = updated.message().unwrap();
+    assert_eq!(msg.op, Op::Identity);
+    assert_eq!(msg.request.as_deref(), Some("req-1"));
+}
@@ -0,0 +1,110 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fs;
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        storage::{
+            requirements::{self, error, ObjectFormat, Requirements, Unmet},
+            Storage,
+        },
+        types::Namespace,
+    },
+    SecretKey,
+};
+use test_helpers::logging;
+
+#[test]
+fn recorded_on_write() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    let reqs = store
+        .read_only()
+        .requirements(&project.urn())
+        .unwrap()
+        .unwrap();
+    assert_eq!(reqs.object_format, ObjectFormat::Sha1);
+    assert_eq!(reqs.replication, requirements::MIN_REPLICATION_VERSION);
+    assert!(reqs.cobs.is_empty());
+    assert!(reqs.unmet().is_empty());
+    assert!(store.read_only().check_requirements(&project.urn()).is_ok());
+}
+
+#[test]
+fn incompatible() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+
+    let newer = Requirements {
+        replication: requirements::REPLICATION_VERSION + 1,
+        cobs: vec![(
+            "xyz.radicle.issue".to_owned(),
+            requirements::COB_VERSION + 1,
+        )]
+        .into_iter()
+        .collect(),
+        ..Requirements::default()
+    };
+    fs::write(
+        store
+            .path()
+            .join(requirements::DIR_NAME)
+            .join(format!("{}.json", Namespace::from(&urn))),
+        serde_json::to_vec(&newer).unwrap(),
+    )
+    .unwrap();
+
+    // Requirements don't decrease when the namespace is written to
+    let recorded = store.record_requirements(&urn).unwrap().unwrap();
+    assert_eq!(recorded, newer);
+
+    match store.read_only().check_requirements(&urn) {
+        Err(error::Check::Incompatible(error::Incompatible { urn: u, unmet })) => {
+            assert_eq!(u, urn);
+            assert_eq!(
+                unmet,
+                vec![
+                    Unmet::Replication {
+                        required: requirements::REPLICATION_VERSION + 1,
+                        supported: requirements::REPLICATION_VERSION,
+                    },
+                    Unmet::Cob {
+                        typename: "xyz.radicle.issue".to_owned(),
+                        required: requirements::COB_VERSION + 1,
+                        supported: requirements::COB_VERSION,
+                    }
+                ]
+            )
+        },
+        x => panic!("expected incompatible namespace, got {:?}", x),
+    }
+    assert!(requirements::ensure_compatible(store.path(), &urn).is_err());
+}
+
+#[test]
+fn removed_with_namespace() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    store.remove_namespace(&project.urn()).unwrap();
+    assert!(store
+        .read_only()
+        .requirements(&project.urn())
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,71 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        storage::{ReadOnly, ReadOnlyStorage as _, Storage},
+        types::{Namespace, Reference},
+    },
+    reflike,
+    SecretKey,
+};
+use test_helpers::logging;
+
+#[test]
+fn snapshot_matches_storage() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    let replica = ReadOnly::open(&*paths).unwrap();
+    let snapshot = replica.snapshot(&project.urn()).unwrap();
+
+    let rad_id = Reference::rad_id(Namespace::from(project.urn()));
+    assert_eq!(snapshot.urn(), &project.urn());
+    assert_eq!(
+        snapshot.tip(),
+        Some(replica.reference_oid(&rad_id).unwrap())
+    );
+    assert_eq!(snapshot.tip(), snapshot.get(&reflike!("refs/rad/id")));
+    assert!(snapshot
+        .iter()
+        .all(|(name, _)| !name.as_str().starts_with("refs/namespaces")));
+}
+
+#[test]
+fn snapshot_is_isolated_from_writes() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    let replica = ReadOnly::open(&*paths).unwrap();
+    let before = replica.snapshot(&project.urn()).unwrap();
+
+    // Simulate a write by another process holding the write role
+    let writer = git2::Repository::open(paths.git_dir()).unwrap();
+    let tip = before.tip().unwrap();
+    writer
+        .reference(
+            &format!(
+                "refs/namespaces/{}/refs/heads/snapshot-test",
+                Namespace::from(project.urn())
+            ),
+            tip.into(),
+            false,
+            "snapshot test",
+        )
+        .unwrap();
+
+    assert_eq!(before.get(&reflike!("refs/heads/snapshot-test")), None);
+
+    let after = replica.snapshot(&project.urn()).unwrap();
+    assert_eq!(after.get(&reflike!("refs/heads/snapshot-test")), Some(tip));
+    assert_eq!(after.len(), before.len() + 1);
+}
@@ -0,0 +1,49 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{git::storage::Storage, SecretKey};
+use test_helpers::logging;
+
+#[test]
+fn updated_on_write() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    let stats = store.read_only().stats(&project.urn()).unwrap().unwrap();
+    assert_eq!(stats.remotes, 0);
+    assert!(stats.cobs.is_empty());
+    assert!(stats.disk_usage > 0);
+    assert!(stats.updated > 0);
+}
+
+#[test]
+fn incremental() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    let before = store.read_only().stats(&project.urn()).unwrap().unwrap();
+    let after = store.update_stats(&project.urn()).unwrap().unwrap();
+    assert_eq!(before.disk_usage, after.disk_usage);
+    assert_eq!(before.remotes, after.remotes);
+}
+
+#[test]
+fn removed_with_namespace() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    store.remove_namespace(&project.urn()).unwrap();
+    assert!(store.read_only().stats(&project.urn()).unwrap().is_none());
+}
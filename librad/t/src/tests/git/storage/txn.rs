@@ -0,0 +1,79 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{
+        storage::{
+            txn::{error, Previous},
+            ReadOnlyStorage as _,
+            Storage,
+        },
+        types::{Namespace, Reference},
+    },
+    reflike,
+    SecretKey,
+};
+use test_helpers::logging;
+
+#[test]
+fn commits_all_or_nothing() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+    let ns = Namespace::from(&urn);
+    let tip = store.reference_oid(&Reference::rad_id(ns.clone())).unwrap();
+
+    let committed = store
+        .transaction(&urn, "txn test")
+        .write(reflike!("refs/heads/a"), tip, Previous::MustNotExist)
+        .write(reflike!("refs/tags/b"), tip, Previous::Any)
+        .commit()
+        .unwrap();
+    assert_eq!(committed.refs.len(), 2);
+    assert!(store
+        .has_ref(&Reference::head(ns.clone(), None, reflike!("a")))
+        .unwrap());
+
+    // Rejected, as `refs/heads/a` exists: `refs/heads/c` must not be written
+    let err = store
+        .transaction(&urn, "txn test")
+        .write(reflike!("refs/heads/c"), tip, Previous::Any)
+        .write(reflike!("refs/heads/a"), tip, Previous::MustNotExist)
+        .commit()
+        .unwrap_err();
+    assert!(matches!(err, error::Commit::Rejected { .. }));
+    assert!(!store
+        .has_ref(&Reference::head(ns.clone(), None, reflike!("c")))
+        .unwrap());
+
+    store
+        .transaction(&urn, "txn test")
+        .delete(reflike!("refs/heads/a"), Previous::MustBe(tip))
+        .commit()
+        .unwrap();
+    assert!(!store
+        .has_ref(&Reference::head(ns.clone(), None, reflike!("a")))
+        .unwrap());
+}
+
+#[test]
+fn rejects_names_outside_refs() {
+    logging::init();
+
+    let paths = tmp::paths();
+    let store = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    let err = store
+        .transaction(&project.urn(), "txn test")
+        .write(reflike!("HEAD"), git2::Oid::zero(), Previous::Any)
+        .commit()
+        .unwrap_err();
+    assert!(matches!(err, error::Commit::Name(_)));
+}
@@ -3,7 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{collections::BTreeSet, convert::TryFrom as _};
+use std::{collections::BTreeSet, convert::TryFrom as _, time::Duration};
 
 use it_helpers::{fixed::TestProject, tmp};
 use librad::{
@@ -43,3 +43,24 @@ fn namespaces() {
 
     assert_eq!(expected, events)
 }
+
+#[test]
+fn signed_refs() {
+    logging::init();
+
+    let store = tmp::storage(SecretKey::new());
+    let (watcher, mut events) = store
+        .watch()
+        .signed_refs(Duration::from_millis(100))
+        .unwrap();
+    let TestProject { project, owner } = TestProject::create(&store).unwrap();
+
+    let mut expected = vec![project.urn(), owner.urn()]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    while !expected.is_empty() {
+        let urn = events.next().unwrap();
+        expected.remove(&urn);
+    }
+    drop(watcher);
+}
@@ -11,6 +11,7 @@ use librad::{
     git::{
         storage::{ReadOnlyStorage as _, Storage},
         tracking::{
+            contacts::{self, Contact, OnConflict, Trust},
             is_tracked,
             migration,
             policy,
@@ -333,3 +334,90 @@ fn migration() {
             .is_some())
     }
 }
+
+#[test]
+fn contacts_of_tracked_peers() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(&tmp).unwrap();
+    let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+    let alice = PeerId::from(SecretKey::new());
+    let bob = PeerId::from(SecretKey::new());
+    let urn = Urn::new(git2::Oid::zero().into());
+
+    for peer in [alice, bob] {
+        track(
+            &storage,
+            &urn,
+            Some(peer),
+            Config::default(),
+            policy::Track::Any,
+        )
+        .unwrap()
+        .unwrap();
+    }
+
+    let laptop = Contact {
+        alias: Some("Alice (laptop)".to_owned()),
+        note: None,
+        trust: Trust::Full,
+    };
+    contacts::set(&storage, &alice, &laptop).unwrap();
+    assert_eq!(
+        contacts::get(&storage, &alice).unwrap(),
+        Some(laptop.clone())
+    );
+    assert_eq!(
+        contacts::display_name(&storage, &alice).unwrap(),
+        "Alice (laptop)"
+    );
+    assert_eq!(
+        contacts::display_name(&storage, &bob).unwrap(),
+        bob.to_string()
+    );
+    assert_eq!(
+        contacts::find(&storage, "alice (LAPTOP)").unwrap(),
+        vec![alice]
+    );
+
+    let tracked = contacts::tracked(&storage, Some(&urn)).unwrap();
+    assert_eq!(tracked.len(), 2);
+    assert!(tracked.contains(&(alice, Some(laptop))));
+    assert!(tracked.contains(&(bob, None)));
+
+    // Setting an empty contact removes it
+    contacts::set(&storage, &alice, &Contact::default()).unwrap();
+    assert_eq!(contacts::get(&storage, &alice).unwrap(), None);
+}
+
+#[test]
+fn contacts_export_import() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path().join("a")).unwrap();
+    let from = Storage::open(&paths, SecretKey::new()).unwrap();
+    let paths = Paths::from_root(tmp.path().join("b")).unwrap();
+    let to = Storage::open(&paths, SecretKey::new()).unwrap();
+    let alice = PeerId::from(SecretKey::new());
+    let bob = PeerId::from(SecretKey::new());
+
+    let contact = |alias: &str| Contact {
+        alias: Some(alias.to_owned()),
+        note: Some("met at the conference".to_owned()),
+        trust: Trust::Marginal,
+    };
+    contacts::set(&from, &alice, &contact("alice")).unwrap();
+    contacts::set(&from, &bob, &contact("bob")).unwrap();
+    contacts::set(&to, &bob, &contact("robert")).unwrap();
+
+    let exported = contacts::export(&from).unwrap();
+    let json = serde_json::to_string(&exported).unwrap();
+    let exported = serde_json::from_str(&json).unwrap();
+
+    let imported = contacts::import(&to, exported, OnConflict::Keep).unwrap();
+    assert_eq!(imported, vec![alice]);
+    assert_eq!(contacts::get(&to, &bob).unwrap(), Some(contact("robert")));
+
+    let imported =
+        contacts::import(&to, contacts::export(&from).unwrap(), OnConflict::Replace).unwrap();
+    assert_eq!(imported, vec![bob]);
+    assert_eq!(contacts::list(&to).unwrap(), contacts::list(&from).unwrap());
+}
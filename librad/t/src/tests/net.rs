@@ -4,8 +4,14 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 mod codec;
+mod connection;
+mod dial;
 mod peer;
 mod protocol;
+#[cfg(feature = "replication-v3")]
+mod replication_hooks;
+mod schema;
+mod shaping;
 mod tls;
 mod upgrade;
 mod x509;
@@ -0,0 +1,45 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::io;
+
+use librad::net::{connection::CloseReason, quic};
+
+const ALL: [CloseReason; 9] = [
+    CloseReason::ConnectionError,
+    CloseReason::ServerShutdown,
+    CloseReason::InvalidUpgrade,
+    CloseReason::TooManyConnections,
+    CloseReason::Timeout,
+    CloseReason::StreamLimit,
+    CloseReason::RateLimited,
+    CloseReason::Unauthorized,
+    CloseReason::Disconnected,
+];
+
+#[test]
+fn permanent_close_reasons() {
+    let permanent = ALL
+        .into_iter()
+        .filter(|reason| !reason.is_transient())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        permanent,
+        vec![CloseReason::InvalidUpgrade, CloseReason::Unauthorized]
+    )
+}
+
+#[test]
+fn close_reason_from_reset() {
+    let reset = quinn::ReadError::Reset(quinn::VarInt::from_u32(CloseReason::RateLimited as u32));
+    let err = io::Error::from(reset);
+    assert_eq!(
+        quic::error::close_reason(&err),
+        Some(CloseReason::RateLimited)
+    );
+
+    let other = io::Error::new(io::ErrorKind::Other, "boom");
+    assert_eq!(quic::error::close_reason(&other), None);
+}
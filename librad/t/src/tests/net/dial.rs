@@ -0,0 +1,103 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::net::SocketAddr;
+
+use librad::net::dial::{error, Config, Family, Resolver};
+
+fn addrs() -> Vec<SocketAddr> {
+    vec![
+        "[2001:db8::1]:8776".parse().unwrap(),
+        "192.0.2.1:8776".parse().unwrap(),
+        "[2001:db8::2]:8776".parse().unwrap(),
+        "192.0.2.2:8776".parse().unwrap(),
+    ]
+}
+
+fn v4() -> Vec<SocketAddr> {
+    addrs().into_iter().filter(SocketAddr::is_ipv4).collect()
+}
+
+fn v6() -> Vec<SocketAddr> {
+    addrs().into_iter().filter(SocketAddr::is_ipv6).collect()
+}
+
+#[test]
+fn family_partition() {
+    assert_eq!(Family::Any.partition(addrs()), (addrs(), vec![]));
+    assert_eq!(Family::PreferV4.partition(addrs()), (v4(), v6()));
+    assert_eq!(Family::PreferV6.partition(addrs()), (v6(), v4()));
+    assert_eq!(Family::V4Only.partition(addrs()), (v4(), vec![]));
+    assert_eq!(Family::V6Only.partition(addrs()), (v6(), vec![]));
+}
+
+#[test]
+fn family_sort() {
+    assert_eq!(Family::Any.sort(addrs()), addrs());
+    assert_eq!(
+        Family::PreferV4.sort(addrs()),
+        v4().into_iter().chain(v6()).collect::<Vec<_>>()
+    );
+    assert_eq!(Family::V6Only.sort(addrs()), v6());
+}
+
+#[test]
+fn family_from_str() {
+    for family in [
+        Family::Any,
+        Family::PreferV4,
+        Family::PreferV6,
+        Family::V4Only,
+        Family::V6Only,
+    ] {
+        assert_eq!(family.to_string().parse::<Family>().unwrap(), family)
+    }
+    assert!("ipv5-only".parse::<Family>().is_err())
+}
+
+#[test]
+fn resolver_from_str() {
+    assert_eq!("system".parse::<Resolver>().unwrap(), Resolver::System);
+    assert!("carrier-pigeon".parse::<Resolver>().is_err())
+}
+
+#[tokio::test]
+async fn resolve_literal() {
+    let config = Config::default();
+    assert_eq!(
+        config.resolve("192.0.2.1:8776").await.unwrap(),
+        vec!["192.0.2.1:8776".parse::<SocketAddr>().unwrap()]
+    );
+
+    let config = Config {
+        family: Family::V6Only,
+        ..Config::default()
+    };
+    assert_matches!(
+        config.resolve("192.0.2.1:8776").await,
+        Err(error::Resolve::NoAddrs { .. })
+    )
+}
+
+#[tokio::test]
+async fn resolve_localhost() {
+    let config = Config {
+        family: Family::V4Only,
+        ..Config::default()
+    };
+    let addrs = config.resolve("localhost:8776").await.unwrap();
+    assert!(!addrs.is_empty());
+    assert!(addrs
+        .iter()
+        .all(|addr| addr.is_ipv4() && addr.ip().is_loopback() && addr.port() == 8776))
+}
+
+#[tokio::test]
+async fn resolve_invalid() {
+    assert_matches!(
+        Config::default().resolve("localhost").await,
+        Err(error::Resolve::InvalidAddr(_))
+    )
+}
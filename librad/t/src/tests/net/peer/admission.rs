@@ -0,0 +1,48 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::net::peer::storage::admission::{Admission, Config};
+
+fn admission() -> Admission {
+    Admission::new(Config {
+        max_running: 1,
+        max_queued: 1,
+        retry_after: Duration::from_secs(10),
+    })
+}
+
+#[tokio::test]
+async fn admits_up_to_bounds() {
+    let admission = admission();
+    let running = admission.admit(false).await.unwrap();
+
+    let queued = {
+        let admission = admission.clone();
+        tokio::spawn(async move { admission.admit(false).await.map(|_| ()) })
+    };
+    while admission.queued() == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    let rejected = admission.admit(false).await.err().unwrap();
+    assert_eq!(rejected.retry_after, Duration::from_secs(10));
+
+    drop(running);
+    queued.await.unwrap().unwrap();
+    assert_eq!(admission.queued(), 0);
+}
+
+#[tokio::test]
+async fn queues_when_pool_saturated() {
+    let admission = admission();
+    let queued = admission.admit(true).await;
+
+    // The slot was free, so the queued fetch is admitted right away, but the
+    // queue is not left occupied
+    assert!(queued.is_ok());
+    assert_eq!(admission.queued(), 0);
+}
@@ -0,0 +1,76 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::collections::BTreeMap;
+
+use nonempty::NonEmpty;
+
+use librad::{
+    net::{
+        peer::{error, Peer, Preset},
+        protocol::{membership, pinned},
+    },
+    paths::Paths,
+    PeerId,
+    SecretKey,
+};
+
+fn paths() -> (tempfile::TempDir, Paths) {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+    (tmp, paths)
+}
+
+#[test]
+fn defaults_and_presets_are_valid() {
+    let (_tmp, paths) = paths();
+    let key = SecretKey::new();
+    assert!(Peer::builder(key.clone(), paths.clone()).config().is_ok());
+    assert!(Peer::builder(key.clone(), paths.clone())
+        .preset(Preset::Laptop)
+        .config()
+        .is_ok());
+    assert!(Peer::builder(key, paths)
+        .preset(Preset::Seed)
+        .config()
+        .is_ok());
+}
+
+#[test]
+fn rejects_pinning_self() {
+    let (_tmp, paths) = paths();
+    let key = SecretKey::new();
+    let mut peers = BTreeMap::new();
+    peers.insert(PeerId::from(&key), vec![]);
+    let res = Peer::builder(key, paths)
+        .pinned(pinned::Config {
+            peers,
+            ..Default::default()
+        })
+        .config();
+    assert!(matches!(res, Err(error::Config::PinnedSelf)))
+}
+
+#[test]
+fn rejects_inverted_random_walks() {
+    let (_tmp, paths) = paths();
+    let res = Peer::builder(SecretKey::new(), paths)
+        .membership(membership::Params {
+            active_random_walk_length: 2,
+            passive_random_walk_length: 3,
+            ..Default::default()
+        })
+        .config();
+    assert!(matches!(res, Err(error::Config::Membership(_))))
+}
+
+#[test]
+fn rejects_unroutable_advertised_addrs() {
+    let (_tmp, paths) = paths();
+    let res = Peer::builder(SecretKey::new(), paths)
+        .advertised_addrs(Some(NonEmpty::new("0.0.0.0:8776".parse().unwrap())))
+        .config();
+    assert!(matches!(res, Err(error::Config::AdvertisedAddr(_))))
+}
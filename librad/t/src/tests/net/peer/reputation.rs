@@ -0,0 +1,109 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::{
+    net::{
+        peer::reputation::{Config, Outcome, Reputations, NEUTRAL_SCORE},
+        protocol::latency::{Sample, Tracker},
+    },
+    PeerId,
+    SecretKey,
+};
+
+#[test]
+fn persists_across_reopen() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("reputation");
+    let alice = PeerId::from(SecretKey::new());
+
+    let reps = Reputations::open(Config::default(), &path).unwrap();
+    reps.record(alice, Outcome::Success { bytes: 1024 });
+    reps.record(alice, Outcome::Failure);
+    drop(reps);
+
+    let reps = Reputations::open(Config::default(), &path).unwrap();
+    let record = reps.get(&alice).unwrap();
+    assert_eq!(record.fetches_succeeded, 1);
+    assert_eq!(record.fetches_failed, 1);
+    assert_eq!(record.bytes_served, 1024);
+
+    reps.reset(Some(alice));
+    drop(reps);
+    let reps = Reputations::open(Config::default(), &path).unwrap();
+    assert_eq!(reps.get(&alice), None);
+    assert_eq!(reps.score(&alice), NEUTRAL_SCORE);
+}
+
+#[test]
+fn ranks_reliable_first() {
+    let reps = Reputations::in_memory(Config {
+        min_score: Some(0.3),
+        ..Config::default()
+    });
+    let reliable = PeerId::from(SecretKey::new());
+    let unknown = PeerId::from(SecretKey::new());
+    let liar = PeerId::from(SecretKey::new());
+
+    for _ in 0..10 {
+        reps.record(reliable, Outcome::Success { bytes: 0 });
+    }
+    reps.record(liar, Outcome::Equivocation);
+
+    assert_eq!(
+        reps.rank(vec![liar, unknown, reliable], |peer| *peer),
+        vec![reliable, unknown, liar]
+    );
+    assert!(reps.is_acceptable(&unknown));
+    assert!(!reps.is_acceptable(&liar));
+}
+
+#[test]
+fn latency_breaks_ties() {
+    let reps = Reputations::in_memory(Config::default());
+    let latency = Tracker::default();
+    let reliable = PeerId::from(SecretKey::new());
+    let fast = PeerId::from(SecretKey::new());
+    let slow = PeerId::from(SecretKey::new());
+
+    for _ in 0..10 {
+        reps.record(reliable, Outcome::Success { bytes: 0 });
+    }
+    for (peer, rtt) in [(reliable, 500), (fast, 20), (slow, 200)] {
+        latency.record(
+            peer,
+            Sample {
+                rtt: Duration::from_millis(rtt),
+                sent_packets: 0,
+                lost_packets: 0,
+            },
+        );
+    }
+
+    let snapshot = latency.snapshot();
+    assert_eq!(
+        reps.rank_with_latency(&snapshot, vec![slow, reliable, fast], |peer| *peer),
+        vec![reliable, fast, slow]
+    );
+}
+
+#[test]
+fn caps_the_number_of_records() {
+    let reps = Reputations::in_memory(Config {
+        max_entries: 2,
+        ..Config::default()
+    });
+    let peers = (0..3)
+        .map(|_| PeerId::from(SecretKey::new()))
+        .collect::<Vec<_>>();
+    for peer in &peers {
+        reps.record(*peer, Outcome::Failure);
+    }
+
+    let all = reps.all();
+    assert_eq!(all.len(), 2);
+    assert!(all.contains_key(&peers[2]));
+}
@@ -3,5 +3,24 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod attachments;
+mod backoff;
+mod batch;
 mod broadcast;
+mod capabilities;
+mod capture;
+mod cas;
+mod checkpoint;
+mod compress;
 mod gossip;
+mod interest;
+mod inventory;
+mod latency;
+mod lfs;
+mod mailbox;
+mod mux;
+mod outbox;
+mod ping;
+mod private;
+mod skew;
+mod usage;
@@ -0,0 +1,108 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fs;
+
+use librad::{
+    identities::payload::Extension as _,
+    net::protocol::attachments::{
+        error,
+        Attachment,
+        Avatar,
+        Config,
+        Error,
+        Oid,
+        Request,
+        Response,
+        Store,
+    },
+    paths::Paths,
+};
+use test_helpers::roundtrip;
+
+fn avatar() -> Attachment {
+    Attachment {
+        oid: Oid::digest(b"avatar"),
+        size: 6,
+        media_type: Some("image/png".to_owned()),
+    }
+}
+
+#[test]
+fn roundtrip_attachment() {
+    roundtrip::json(avatar());
+    roundtrip::cbor(avatar());
+    roundtrip::json(Attachment {
+        media_type: None,
+        ..avatar()
+    });
+}
+
+#[test]
+fn attachment_json() {
+    assert_eq!(
+        serde_json::to_value(avatar()).unwrap(),
+        serde_json::json!({
+            "oid": Oid::digest(b"avatar").to_string(),
+            "size": 6,
+            "mediaType": "image/png",
+        })
+    )
+}
+
+#[test]
+fn roundtrip_rpc() {
+    roundtrip::cbor(Request {
+        oid: Oid::digest(b"avatar"),
+    });
+    roundtrip::cbor(Response::Chunk(vec![1, 2, 3]));
+    roundtrip::cbor(Response::Error(Error::NotFound));
+    roundtrip::cbor(Response::Error(Error::RateLimited));
+}
+
+#[test]
+fn avatar_must_be_an_image() {
+    assert!(Avatar { image: avatar() }.validate().is_ok());
+    assert!(Avatar {
+        image: Attachment {
+            media_type: Some("application/pdf".to_owned()),
+            ..avatar()
+        }
+    }
+    .validate()
+    .is_err())
+}
+
+#[test]
+fn store_put() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+    let store = Store::new(&paths, Config::default());
+
+    let attachment = store.put(b"avatar", Some("image/png".to_owned())).unwrap();
+    assert_eq!(attachment, avatar());
+    assert!(store.contains(&attachment.oid));
+    assert_eq!(store.read(&attachment.oid).unwrap(), b"avatar");
+
+    let file = tmp.path().join("avatar.png");
+    fs::write(&file, b"avatar").unwrap();
+    assert_eq!(
+        store.import(&file, Some("image/png".to_owned())).unwrap(),
+        attachment
+    )
+}
+
+#[test]
+fn store_size_limit() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+    let store = Store::new(&paths, Config { max_size: 4 });
+
+    assert_matches!(
+        store.put(b"avatar", None),
+        Err(error::Store::TooLarge { size: 6, max: 4 })
+    );
+    assert!(!store.contains(&Oid::digest(b"avatar")))
+}
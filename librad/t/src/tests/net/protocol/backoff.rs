@@ -0,0 +1,56 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::{
+    net::protocol::backoff::{Backoffs, Config, Signal},
+    PeerId,
+    SecretKey,
+};
+use test_helpers::roundtrip;
+
+fn enabled() -> Backoffs {
+    Backoffs::new(Config {
+        enabled: true,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn roundtrip_signal() {
+    roundtrip::cbor(Signal::new(Duration::from_secs(30)))
+}
+
+#[test]
+fn signalled_peers_are_backed_off() {
+    let backoffs = enabled();
+    let peer = PeerId::from(SecretKey::new());
+    let other = PeerId::from(SecretKey::new());
+
+    backoffs.record(peer, Signal::new(Duration::from_secs(30)));
+    assert!(backoffs.is_backing_off(&peer));
+    assert!(!backoffs.is_backing_off(&other));
+    assert_eq!(backoffs.len(), 1);
+}
+
+#[test]
+fn backoff_expires() {
+    let backoffs = enabled();
+    let peer = PeerId::from(SecretKey::new());
+
+    backoffs.record(peer, Signal::new(Duration::ZERO));
+    assert!(!backoffs.is_backing_off(&peer));
+    assert!(backoffs.is_empty());
+}
+
+#[test]
+fn signals_are_ignored_when_disabled() {
+    let backoffs = Backoffs::new(Config::default());
+    let peer = PeerId::from(SecretKey::new());
+
+    backoffs.record(peer, Signal::new(Duration::from_secs(30)));
+    assert!(!backoffs.is_backing_off(&peer));
+}
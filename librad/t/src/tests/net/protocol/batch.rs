@@ -0,0 +1,66 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::{
+    net::protocol::batch::{Batches, Config, Push},
+    PeerId,
+    SecretKey,
+};
+
+const MIN: Duration = Duration::from_millis(5);
+const MAX: Duration = Duration::from_millis(40);
+
+fn batches() -> Batches<usize> {
+    Batches::new(Config {
+        enabled: true,
+        max_messages: 4,
+        min_interval: MIN,
+        max_interval: MAX,
+    })
+}
+
+#[test]
+fn flushes_when_full() {
+    let batches = batches();
+    let peer = PeerId::from(SecretKey::new());
+
+    assert_matches!(batches.push(peer, 0), Push::Schedule(d) if d == MIN);
+    assert_matches!(batches.push(peer, 1), Push::Buffered);
+    assert_matches!(batches.push(peer, 2), Push::Buffered);
+    assert_matches!(batches.push(peer, 3), Push::Flush(msgs) if msgs == vec![0, 1, 2, 3]);
+    assert_eq!(batches.take(&peer), None);
+}
+
+#[test]
+fn interval_adapts() {
+    let batches = batches();
+    let peer = PeerId::from(SecretKey::new());
+
+    for expected in [MIN * 2, MIN * 4, MIN * 8, MAX] {
+        batches.push(peer, 0);
+        batches.push(peer, 1);
+        assert_eq!(batches.take(&peer), Some(vec![0, 1]));
+        assert_eq!(batches.interval(&peer), expected);
+    }
+
+    batches.push(peer, 0);
+    assert_eq!(batches.take(&peer), Some(vec![0]));
+    assert_eq!(batches.interval(&peer), MAX / 2);
+}
+
+#[test]
+fn peers_are_independent() {
+    let batches = batches();
+    let a = PeerId::from(SecretKey::new());
+    let b = PeerId::from(SecretKey::new());
+
+    assert_matches!(batches.push(a, 0), Push::Schedule(_));
+    assert_matches!(batches.push(b, 1), Push::Schedule(_));
+    assert_eq!(batches.remove(&a), 1);
+    assert_eq!(batches.take(&a), None);
+    assert_eq!(batches.take(&b), Some(vec![1]));
+}
@@ -0,0 +1,71 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::BTreeSet, net::SocketAddr};
+
+use librad::net::protocol::{Capability, PeerAdvertisement};
+use minicbor::{Decode, Encode};
+
+/// The capabilities as known by peers predating any of them.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Encode, Decode)]
+enum OldCapability {
+    #[n(0)]
+    Reserved,
+}
+
+/// The advertisement as known by peers predating any capabilities.
+#[derive(Debug, Encode, Decode)]
+#[cbor(array)]
+struct OldPeerAdvertisement {
+    #[n(0)]
+    listen_addrs: Vec<SocketAddr>,
+    #[n(2)]
+    capabilities: BTreeSet<OldCapability>,
+}
+
+fn advertisement() -> PeerAdvertisement<SocketAddr> {
+    let mut ad = PeerAdvertisement::new(([127, 0, 0, 1], 8776).into());
+    ad.capabilities.extend([
+        Capability::GossipBatch,
+        Capability::GossipCompression,
+        Capability::Private,
+        Capability::GossipInterest,
+        Capability::GossipBackoff,
+        Capability::Ping,
+        Capability::Unknown(42),
+    ]);
+    ad
+}
+
+#[test]
+fn old_peers_decode_new_advertisement() {
+    let ad = advertisement();
+    let old: OldPeerAdvertisement = minicbor::decode(&minicbor::to_vec(&ad).unwrap()).unwrap();
+    assert_eq!(old.listen_addrs, ad.listen_addrs.to_vec());
+    assert!(old.capabilities.is_empty())
+}
+
+#[test]
+fn new_peers_decode_old_advertisement() {
+    let old = OldPeerAdvertisement {
+        listen_addrs: vec![([127, 0, 0, 1], 8776).into()],
+        capabilities: Some(OldCapability::Reserved).into_iter().collect(),
+    };
+    let ad: PeerAdvertisement<SocketAddr> =
+        minicbor::decode(&minicbor::to_vec(&old).unwrap()).unwrap();
+    assert_eq!(ad.listen_addrs.to_vec(), old.listen_addrs);
+    assert_eq!(
+        ad.capabilities,
+        Some(Capability::Reserved).into_iter().collect()
+    )
+}
+
+#[test]
+fn unknown_capabilities_roundtrip() {
+    let ad = advertisement();
+    let decoded: PeerAdvertisement<SocketAddr> =
+        minicbor::decode(&minicbor::to_vec(&ad).unwrap()).unwrap();
+    assert_eq!(decoded, ad)
+}
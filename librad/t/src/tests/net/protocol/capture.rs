@@ -0,0 +1,63 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::net::SocketAddr;
+
+use librad::{
+    net::{
+        protocol::{
+            capture::{self, Event, Recorder},
+            membership,
+        },
+        upgrade::UpgradeRequest,
+    },
+    PeerId,
+    SecretKey,
+};
+
+#[test]
+fn roundtrip() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("capture");
+    let peer = PeerId::from(SecretKey::new());
+    let addr: SocketAddr = "127.0.0.1:12345".parse().unwrap();
+
+    let events = vec![
+        Event::Upgrade(UpgradeRequest::Membership),
+        Event::Membership(membership::Message::Disconnect),
+    ];
+    let recorder = Recorder::create(&path).unwrap();
+    for event in events.clone() {
+        recorder.record(peer, addr, event)
+    }
+
+    let records = capture::read(&path).unwrap();
+    assert_eq!(
+        records.iter().map(|r| r.event.clone()).collect::<Vec<_>>(),
+        events
+    );
+    assert!(records
+        .iter()
+        .all(|r| r.remote_id == peer && r.remote_addr == addr));
+}
+
+#[test]
+fn truncated() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("capture");
+    let peer = PeerId::from(SecretKey::new());
+    let addr: SocketAddr = "127.0.0.1:12345".parse().unwrap();
+
+    let recorder = Recorder::create(&path).unwrap();
+    recorder.record(peer, addr, Event::Upgrade(UpgradeRequest::Gossip));
+    recorder.record(peer, addr, Event::Upgrade(UpgradeRequest::Git));
+
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes.truncate(bytes.len() - 1);
+    assert!(matches!(
+        capture::decode(&bytes),
+        Err(capture::error::Read::Malformed { index: 1, .. })
+    ))
+}
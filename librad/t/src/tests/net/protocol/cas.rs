@@ -0,0 +1,49 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::net::protocol::cas::{error, Oid, Store};
+
+#[test]
+fn finish_partial() {
+    let tmp = tempfile::tempdir().unwrap();
+    let store = Store::new(tmp.path().to_owned());
+    let oid = Oid::digest(b"content");
+
+    store.append(&oid, b"con").unwrap();
+    assert_eq!(store.partial_len(&oid), 3);
+    assert!(!store.contains(&oid));
+    store.append(&oid, b"tent").unwrap();
+    let path = store.finish(&oid).unwrap();
+
+    assert_eq!(path, store.path(&oid));
+    assert_eq!(store.read(&oid).unwrap(), b"content");
+    assert_eq!(store.read_chunk(&oid, 3, 2).unwrap(), b"te");
+    assert_eq!(store.partial_len(&oid), 0)
+}
+
+#[test]
+fn finish_corrupt() {
+    let tmp = tempfile::tempdir().unwrap();
+    let store = Store::new(tmp.path().to_owned());
+    let oid = Oid::digest(b"content");
+
+    store.append(&oid, b"tampered").unwrap();
+    assert_matches!(
+        store.finish(&oid),
+        Err(error::Store::Corrupt { size: 8, .. })
+    );
+    assert!(!store.contains(&oid));
+    assert_eq!(store.partial_len(&oid), 0)
+}
+
+#[test]
+fn import() {
+    let tmp = tempfile::tempdir().unwrap();
+    let store = Store::new(tmp.path().to_owned());
+
+    let (oid, size) = store.import(&b"content"[..]).unwrap();
+    assert_eq!((oid, size), (Oid::digest(b"content"), 7));
+    assert_eq!(store.read(&oid).unwrap(), b"content")
+}
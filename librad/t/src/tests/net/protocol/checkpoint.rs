@@ -0,0 +1,93 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::{
+    git::Urn,
+    git_ext,
+    net::protocol::{
+        checkpoint::{Checkpoint, Interest, Letter, Offender, FILE_NAME},
+        gossip::{Payload, Rev},
+        mailbox::{Config, Mailbox},
+    },
+    PeerId,
+    SecretKey,
+};
+
+fn urn(s: &[u8]) -> Urn {
+    Urn::new(git_ext::Oid::from(
+        git2::Oid::hash_object(git2::ObjectType::Blob, s).unwrap(),
+    ))
+}
+
+fn payload(urn: &Urn, rev: &[u8]) -> Payload {
+    Payload {
+        urn: urn.clone(),
+        rev: Some(Rev::Git(
+            git2::Oid::hash_object(git2::ObjectType::Commit, rev).unwrap(),
+        )),
+        origin: None,
+    }
+}
+
+#[test]
+fn roundtrip() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join(FILE_NAME);
+    assert_eq!(Checkpoint::load(&path).unwrap(), None);
+
+    let alice = PeerId::from(SecretKey::new());
+    let bob = PeerId::from(SecretKey::new());
+    let project = urn(b"project");
+    let cp = Checkpoint {
+        taken: 1_650_000_000,
+        active: vec![],
+        passive: vec![],
+        offenders: vec![Offender {
+            peer: bob,
+            remaining: 300,
+        }],
+        interest: vec![Interest {
+            urn: project.clone(),
+            peers: vec![alice, bob],
+        }],
+        letters: vec![Letter {
+            peer: alice,
+            age: 42,
+            payload: payload(&project, b"v1"),
+        }],
+    };
+    cp.store(&path).unwrap();
+    assert_eq!(Checkpoint::load(&path).unwrap(), Some(cp));
+}
+
+#[test]
+fn mailbox_restore() {
+    let alice = PeerId::from(SecretKey::new());
+    let bob = PeerId::from(SecretKey::new());
+    let project = urn(b"project");
+    let v1 = payload(&project, b"v1");
+    let v2 = payload(&project, b"v2");
+
+    let mailbox = Mailbox::new(Config {
+        ttl: Duration::from_secs(60),
+        ..Config::default()
+    });
+    mailbox.restore(
+        vec![(project.clone(), vec![alice, bob])],
+        vec![
+            (alice, Duration::from_secs(10), v1.clone()),
+            (bob, Duration::from_secs(120), v2.clone()),
+        ],
+    );
+
+    let (interest, letters) = mailbox.snapshot();
+    assert_eq!(interest, vec![(project, vec![alice, bob])]);
+    assert_eq!(letters.len(), 1);
+    assert_eq!(mailbox.collect(&alice), vec![v1]);
+    // Expired while offline
+    assert!(mailbox.collect(&bob).is_empty());
+}
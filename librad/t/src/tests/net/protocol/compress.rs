@@ -0,0 +1,67 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::Urn,
+    git_ext,
+    net::protocol::{
+        compress::{self, Compression},
+        gossip::{Payload, Rev},
+    },
+    PeerId,
+    SecretKey,
+};
+
+fn enabled() -> Compression {
+    Compression::new(compress::Config {
+        enabled: true,
+        ..Default::default()
+    })
+}
+
+fn payloads(n: usize) -> Vec<u8> {
+    let origin = PeerId::from(SecretKey::new());
+    let payloads = (0..n)
+        .map(|i| {
+            let oid = git2::Oid::hash_object(git2::ObjectType::Blob, &i.to_be_bytes()).unwrap();
+            Payload {
+                urn: Urn::new(git_ext::Oid::from(oid)),
+                rev: Some(Rev::Git(oid)),
+                origin: Some(origin),
+            }
+        })
+        .collect::<Vec<_>>();
+    minicbor::to_vec(&payloads).unwrap()
+}
+
+#[test]
+fn roundtrip() {
+    let compression = enabled();
+    let plain = payloads(16);
+    let compressed = compression.compress(&plain).unwrap();
+    assert!(compressed.len() < plain.len());
+    assert_eq!(compression.decompress(&compressed).unwrap(), plain);
+
+    let stats = compression.stats();
+    assert_eq!(stats.sent_plain, plain.len() as u64);
+    assert_eq!(stats.sent_compressed, compressed.len() as u64);
+    assert_eq!(stats.recv_plain, plain.len() as u64);
+}
+
+#[test]
+fn disabled_or_small_frames_are_sent_as_is() {
+    let plain = payloads(16);
+    assert!(Compression::new(compress::Config::default())
+        .compress(&plain)
+        .is_none());
+    assert!(enabled().compress(&plain[..8]).is_none());
+}
+
+#[test]
+fn decompression_is_bounded() {
+    let compression = enabled();
+    let huge = zstd::bulk::compress(&vec![0u8; compress::MAX_FRAME_SIZE + 1], 3).unwrap();
+    assert!(compression.decompress(&huge).is_err());
+}
@@ -0,0 +1,77 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::convert::Infallible;
+
+use librad::{
+    git::Urn,
+    git_ext,
+    identities::{SomeUrn, Xor},
+    net::protocol::interest::{Config, Interests},
+    reflike,
+    PeerId,
+    SecretKey,
+};
+
+fn urn(i: usize) -> Urn {
+    let oid = git2::Oid::hash_object(git2::ObjectType::Blob, &i.to_be_bytes()).unwrap();
+    Urn::new(git_ext::Oid::from(oid))
+}
+
+fn filter(urns: &[Urn]) -> Xor {
+    Xor::try_from_iter(
+        urns.iter()
+            .cloned()
+            .map(|urn| Ok::<_, Infallible>(SomeUrn::Git(urn))),
+    )
+    .unwrap()
+    .0
+}
+
+fn enabled() -> Interests {
+    Interests::new(Config { enabled: true })
+}
+
+#[test]
+fn undeclared_peers_are_interested_in_everything() {
+    let interests = enabled();
+    let peer = PeerId::from(SecretKey::new());
+
+    assert!(interests.is_empty());
+    assert!((0..16).all(|i| interests.is_interested(&peer, &urn(i))));
+}
+
+#[test]
+fn declared_interests_scope_gossip() {
+    let interests = enabled();
+    let peer = PeerId::from(SecretKey::new());
+    let other = PeerId::from(SecretKey::new());
+    let tracked = (0..8).map(urn).collect::<Vec<_>>();
+
+    interests.declare(peer, filter(&tracked));
+    assert_eq!(interests.len(), 1);
+    for urn in &tracked {
+        assert!(interests.is_interested(&peer, urn));
+        // Announcements carry a path
+        assert!(
+            interests.is_interested(&peer, &urn.clone().with_path(reflike!("refs/heads/master")))
+        );
+    }
+    assert!(!interests.is_interested(&peer, &urn(100)));
+    assert!(interests.is_interested(&other, &urn(100)));
+
+    assert!(interests.remove(&peer));
+    assert!(interests.is_interested(&peer, &urn(100)));
+}
+
+#[test]
+fn declarations_are_ignored_when_disabled() {
+    let interests = Interests::new(Config::default());
+    let peer = PeerId::from(SecretKey::new());
+
+    interests.declare(peer, filter(&[urn(0)]));
+    assert!(interests.is_empty());
+    assert!(interests.is_interested(&peer, &urn(100)));
+}
@@ -0,0 +1,95 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::collections::BTreeSet;
+
+use librad::{
+    git::Urn,
+    git_ext,
+    identities::SomeUrn,
+    net::protocol::inventory::{self, Page},
+};
+use test_helpers::roundtrip;
+
+fn urn(s: &[u8]) -> Urn {
+    Urn::new(git_ext::Oid::from(
+        git2::Oid::hash_object(git2::ObjectType::Blob, s).unwrap(),
+    ))
+}
+
+#[test]
+fn request_filter_contains_interest() {
+    let interest = vec![urn(b"alpha"), urn(b"beta")];
+    let req = inventory::request(&interest).unwrap();
+
+    assert!(req.after.is_none());
+    for urn in interest {
+        assert!(req.filter.contains(&SomeUrn::from(urn)))
+    }
+}
+
+#[test]
+fn exact_discards_false_positives() {
+    let interest = vec![urn(b"alpha"), urn(b"beta")]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    let page = Page {
+        urns: vec![urn(b"alpha"), urn(b"gamma")],
+        more: false,
+        signed_refs: None,
+    };
+
+    assert_eq!(
+        inventory::exact(&interest, page).collect::<Vec<_>>(),
+        vec![urn(b"alpha")]
+    )
+}
+
+#[test]
+fn roundtrip_request() {
+    let mut req = inventory::request(&[urn(b"alpha")]).unwrap();
+    req.after = Some(urn(b"beta"));
+    roundtrip::cbor(req)
+}
+
+#[test]
+fn roundtrip_page() {
+    roundtrip::cbor(Page {
+        urns: vec![urn(b"alpha"), urn(b"beta")],
+        more: true,
+        signed_refs: Some(
+            vec![(urn(b"alpha"), git_ext::Oid::from(git2::Oid::zero()))]
+                .into_iter()
+                .collect(),
+        ),
+    })
+}
+
+#[test]
+fn shared_keeps_signed_refs_of_interest() {
+    let interest = vec![urn(b"alpha"), urn(b"beta")]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    let tip = git_ext::Oid::from(git2::Oid::zero());
+    let mut shared = inventory::Shared::default();
+    shared.extend(
+        &interest,
+        Page {
+            urns: vec![urn(b"alpha"), urn(b"gamma")],
+            more: false,
+            signed_refs: Some(
+                vec![(urn(b"alpha"), tip), (urn(b"gamma"), tip)]
+                    .into_iter()
+                    .collect(),
+            ),
+        },
+    );
+
+    assert_eq!(shared.urns, vec![urn(b"alpha")]);
+    assert_eq!(
+        shared.signed_refs.into_iter().collect::<Vec<_>>(),
+        vec![(urn(b"alpha"), tip)]
+    );
+}
@@ -0,0 +1,87 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::{
+    net::protocol::latency::{Sample, Tracker},
+    PeerId,
+    SecretKey,
+};
+
+fn sample(rtt_ms: u64, sent_packets: u64, lost_packets: u64) -> Sample {
+    Sample {
+        rtt: Duration::from_millis(rtt_ms),
+        sent_packets,
+        lost_packets,
+    }
+}
+
+#[test]
+fn first_sample_is_taken_verbatim() {
+    let tracker = Tracker::default();
+    let peer = PeerId::from(SecretKey::new());
+    let stats = tracker.record(peer, sample(100, 100, 10));
+
+    assert_eq!(stats.srtt, Duration::from_millis(100));
+    assert_eq!(stats.rttvar, Duration::from_millis(50));
+    assert!((stats.loss - 0.1).abs() < f64::EPSILON);
+    assert_eq!(stats.samples, 1);
+}
+
+#[test]
+fn samples_are_smoothed() {
+    let tracker = Tracker::default();
+    let peer = PeerId::from(SecretKey::new());
+    tracker.record(peer, sample(100, 0, 0));
+    let stats = tracker.record(peer, sample(900, 0, 0));
+
+    assert!((stats.srtt.as_secs_f64() - 0.2).abs() < 1e-6);
+    assert_eq!(stats.samples, 2);
+    assert_eq!(tracker.get(&peer), Some(stats));
+}
+
+#[test]
+fn loss_is_per_interval() {
+    let tracker = Tracker::default();
+    let peer = PeerId::from(SecretKey::new());
+    tracker.record(peer, sample(20, 100, 50));
+    // No packets lost since the previous sample
+    let stats = tracker.record(peer, sample(20, 200, 50));
+    assert!((stats.loss - 0.375).abs() < 1e-6);
+
+    // A new connection resets the counters
+    let stats = tracker.record(peer, sample(20, 10, 5));
+    assert!((stats.loss - (0.75 * 0.375 + 0.25 * 0.5)).abs() < 1e-6);
+}
+
+#[test]
+fn rank_prefers_low_latency() {
+    let tracker = Tracker::default();
+    let fast = PeerId::from(SecretKey::new());
+    let slow = PeerId::from(SecretKey::new());
+    let lossy = PeerId::from(SecretKey::new());
+    let unknown = PeerId::from(SecretKey::new());
+
+    tracker.record(fast, sample(20, 100, 0));
+    tracker.record(slow, sample(200, 100, 0));
+    tracker.record(lossy, sample(20, 100, 50));
+
+    assert_eq!(
+        tracker.rank(vec![unknown, slow, lossy, fast], |peer| *peer),
+        vec![fast, lossy, slow, unknown]
+    )
+}
+
+#[test]
+fn retain_forgets_peers() {
+    let tracker = Tracker::default();
+    let peer = PeerId::from(SecretKey::new());
+    tracker.record(peer, sample(20, 0, 0));
+    tracker.retain(|_| false);
+
+    assert!(tracker.get(&peer).is_none());
+    assert!(tracker.snapshot().is_empty())
+}
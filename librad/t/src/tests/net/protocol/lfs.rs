@@ -0,0 +1,144 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::fs;
+
+use it_helpers::{fixed::TestProject, tmp};
+use librad::{
+    git::{storage::Storage, types::Namespace},
+    net::protocol::lfs::{self, Config, Error, Oid, Pointer, Request, Response, Store, CHUNK_SIZE},
+    paths::Paths,
+    PeerId,
+    SecretKey,
+};
+use test_helpers::roundtrip;
+
+const SPEC_EXAMPLE: &str = "version https://git-lfs.github.com/spec/v1
+oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393
+size 12345
+";
+
+#[test]
+fn pointer_roundtrip() {
+    let pointer = Pointer::parse(SPEC_EXAMPLE.as_bytes()).unwrap();
+    assert_eq!(pointer.size, 12345);
+    assert_eq!(
+        pointer.oid.to_string(),
+        "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393"
+    );
+    assert_eq!(pointer.to_string(), SPEC_EXAMPLE)
+}
+
+#[test]
+fn not_a_pointer() {
+    assert_eq!(Pointer::parse(b"fn main() {}\n"), None);
+    assert_eq!(
+        Pointer::parse(b"version https://git-lfs.github.com/spec/v1\nsize 42\n"),
+        None
+    );
+    assert_eq!(Pointer::parse(&[0xff; 8]), None)
+}
+
+#[test]
+fn oid_from_str() {
+    let oid = Oid::digest(b"large");
+    assert_eq!(oid.to_string().parse::<Oid>().unwrap(), oid);
+    assert!("deadbeef".parse::<Oid>().is_err())
+}
+
+#[test]
+fn roundtrip_rpc() {
+    roundtrip::cbor(Request {
+        oid: Oid::digest(b"large"),
+        offset: 42,
+    });
+    roundtrip::cbor(Response::Chunk(vec![1, 2, 3]));
+    roundtrip::cbor(Response::Error(Error::NotFound));
+    roundtrip::cbor(Response::Error(Error::TooLarge));
+}
+
+#[test]
+fn store_import() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+    let store = Store::new(&paths, Config::default());
+
+    let content = vec![7u8; CHUNK_SIZE + 1];
+    let file = tmp.path().join("artifact.bin");
+    fs::write(&file, &content).unwrap();
+
+    let pointer = store.import(&file).unwrap();
+    assert_eq!(pointer.oid, Oid::digest(&content));
+    assert_eq!(pointer.size, content.len() as u64);
+    assert!(store.contains(&pointer.oid));
+    assert_eq!(store.partial_len(&pointer.oid), 0);
+
+    let first = store.read_chunk(&pointer.oid, 0).unwrap();
+    assert_eq!(first.len(), CHUNK_SIZE);
+    let rest = store.read_chunk(&pointer.oid, CHUNK_SIZE as u64).unwrap();
+    assert_eq!(rest, vec![7u8]);
+    let end = store
+        .read_chunk(&pointer.oid, content.len() as u64)
+        .unwrap();
+    assert!(end.is_empty())
+}
+
+#[test]
+fn wanted_pointers() {
+    let paths = tmp::paths();
+    let storage = Storage::open(&*paths, SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+    let urn = project.urn();
+    let store = Store::new(&paths, Config { max_size: 1024 });
+
+    let file = paths.git_dir().join("stored.bin");
+    fs::write(&file, b"stored").unwrap();
+    let stored = store.import(&file).unwrap();
+    let missing = Pointer {
+        oid: Oid::digest(b"missing"),
+        size: 7,
+    };
+    let too_large = Pointer {
+        oid: Oid::digest(b"too large"),
+        size: 1025,
+    };
+
+    let repo = git2::Repository::open(paths.git_dir()).unwrap();
+    let mut tree = repo.treebuilder(None).unwrap();
+    for (name, content) in [
+        ("stored.bin", stored.to_string()),
+        ("missing.bin", missing.to_string()),
+        ("too-large.bin", too_large.to_string()),
+        ("README", "not a pointer".to_owned()),
+    ] {
+        let blob = repo.blob(content.as_bytes()).unwrap();
+        tree.insert(name, blob, 0o100644).unwrap();
+    }
+    let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+    let sig = git2::Signature::now("lfs", "lfs@example.com").unwrap();
+    let commit = repo
+        .commit(None, &sig, &sig, "pointers", &tree, &[])
+        .unwrap();
+
+    let peer = PeerId::from(SecretKey::new());
+    repo.reference(
+        &format!(
+            "refs/namespaces/{}/refs/remotes/{}/heads/main",
+            Namespace::from(&urn),
+            peer
+        ),
+        commit,
+        false,
+        "lfs test",
+    )
+    .unwrap();
+
+    let wanted = lfs::wanted(&storage, &store, &urn, peer).unwrap();
+    assert_eq!(wanted, vec![missing]);
+    let other = PeerId::from(SecretKey::new());
+    assert!(lfs::wanted(&storage, &store, &urn, other)
+        .unwrap()
+        .is_empty())
+}
@@ -0,0 +1,179 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::{
+    git::Urn,
+    git_ext,
+    net::protocol::{
+        gossip::{Payload, Rev},
+        mailbox::{Config, Mailbox},
+    },
+    PeerId,
+    SecretKey,
+};
+
+fn urn(s: &[u8]) -> Urn {
+    Urn::new(git_ext::Oid::from(
+        git2::Oid::hash_object(git2::ObjectType::Blob, s).unwrap(),
+    ))
+}
+
+fn payload(urn: &Urn, rev: &[u8], origin: Option<PeerId>) -> Payload {
+    Payload {
+        urn: urn.clone(),
+        rev: Some(Rev::Git(
+            git2::Oid::hash_object(git2::ObjectType::Commit, rev).unwrap(),
+        )),
+        origin,
+    }
+}
+
+fn disconnected(_: &PeerId) -> bool {
+    false
+}
+
+#[test]
+fn delivers_to_interested_disconnected_peers() {
+    let mailbox = Mailbox::new(Config::default());
+    let alice = PeerId::from(SecretKey::new());
+    let bob = PeerId::from(SecretKey::new());
+    let carol = PeerId::from(SecretKey::new());
+    let project = urn(b"project");
+
+    mailbox.interested(alice, &project);
+    mailbox.interested(bob, &project);
+
+    let have = payload(&project, b"v1", None);
+    assert_eq!(mailbox.deposit(&have, |peer| peer == &bob), 1);
+
+    assert_eq!(mailbox.collect(&alice), vec![have]);
+    assert!(mailbox.collect(&alice).is_empty());
+    assert!(mailbox.collect(&bob).is_empty());
+    assert!(mailbox.collect(&carol).is_empty());
+}
+
+#[test]
+fn ignores_uninterested_urns() {
+    let mailbox = Mailbox::new(Config::default());
+    let alice = PeerId::from(SecretKey::new());
+
+    mailbox.interested(alice, &urn(b"project"));
+    let have = payload(&urn(b"other"), b"v1", None);
+
+    assert_eq!(mailbox.deposit(&have, disconnected), 0);
+    assert!(mailbox.collect(&alice).is_empty());
+}
+
+#[test]
+fn does_not_deliver_to_origin() {
+    let mailbox = Mailbox::new(Config::default());
+    let alice = PeerId::from(SecretKey::new());
+    let project = urn(b"project");
+
+    mailbox.interested(alice, &project);
+    let have = payload(&project, b"v1", Some(alice));
+
+    assert_eq!(mailbox.deposit(&have, disconnected), 0);
+}
+
+#[test]
+fn supersedes_previous_announcement() {
+    let mailbox = Mailbox::new(Config::default());
+    let alice = PeerId::from(SecretKey::new());
+    let project = urn(b"project");
+
+    mailbox.interested(alice, &project);
+    mailbox.deposit(&payload(&project, b"v1", None), disconnected);
+    let latest = payload(&project, b"v2", None);
+    mailbox.deposit(&latest, disconnected);
+
+    assert_eq!(mailbox.stats().pending, 1);
+    assert_eq!(mailbox.collect(&alice), vec![latest]);
+}
+
+#[test]
+fn bounded_per_peer() {
+    let mailbox = Mailbox::new(Config {
+        capacity: 2,
+        ..Config::default()
+    });
+    let alice = PeerId::from(SecretKey::new());
+    let urns = [urn(b"one"), urn(b"two"), urn(b"three")];
+
+    for urn in &urns {
+        mailbox.interested(alice, urn);
+        mailbox.deposit(&payload(urn, b"v1", None), disconnected);
+    }
+
+    let delivered = mailbox
+        .collect(&alice)
+        .into_iter()
+        .map(|p| p.urn)
+        .collect::<Vec<_>>();
+    assert_eq!(delivered, urns[1..].to_vec());
+}
+
+#[test]
+fn bounded_peers() {
+    let mailbox = Mailbox::new(Config {
+        max_peers: 1,
+        ..Config::default()
+    });
+    let alice = PeerId::from(SecretKey::new());
+    let bob = PeerId::from(SecretKey::new());
+    let project = urn(b"project");
+
+    mailbox.interested(alice, &project);
+    mailbox.deposit(&payload(&project, b"v1", None), disconnected);
+    mailbox.interested(bob, &project);
+    mailbox.deposit(&payload(&project, b"v2", None), |peer| peer == &alice);
+
+    assert_eq!(mailbox.stats().peers, 1);
+    assert!(mailbox.collect(&alice).is_empty());
+    assert_eq!(mailbox.collect(&bob).len(), 1);
+}
+
+#[test]
+fn expired_announcements_are_dropped() {
+    let mailbox = Mailbox::new(Config {
+        ttl: Duration::from_secs(0),
+        ..Config::default()
+    });
+    let alice = PeerId::from(SecretKey::new());
+    let project = urn(b"project");
+
+    mailbox.interested(alice, &project);
+    assert_eq!(
+        mailbox.deposit(&payload(&project, b"v1", None), disconnected),
+        1
+    );
+
+    mailbox.expire();
+    assert_eq!(mailbox.stats().pending, 0);
+    assert!(mailbox.collect(&alice).is_empty());
+}
+
+#[test]
+fn persists_across_reopen() {
+    let tmp = tempfile::tempdir().unwrap();
+    let alice = PeerId::from(SecretKey::new());
+    let project = urn(b"project");
+    let have = payload(&project, b"v1", None);
+
+    let mailbox = Mailbox::open(Config::default(), tmp.path()).unwrap();
+    mailbox.interested(alice, &project);
+    assert_eq!(mailbox.deposit(&have, disconnected), 1);
+    mailbox.flush().unwrap();
+
+    let mailbox = Mailbox::open(Config::default(), tmp.path()).unwrap();
+    assert_eq!(mailbox.collect(&alice), vec![have.clone()]);
+    drop(mailbox);
+
+    let mailbox = Mailbox::open(Config::default(), tmp.path()).unwrap();
+    assert!(mailbox.collect(&alice).is_empty());
+    assert_eq!(mailbox.deposit(&have, disconnected), 1);
+}
@@ -0,0 +1,66 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    net::{
+        connection::CloseReason,
+        protocol::mux::{Config, Streams},
+        upgrade::UpgradeRequest,
+    },
+    PeerId,
+    SecretKey,
+};
+
+#[test]
+fn refuses_beyond_limit() {
+    let streams = Streams::new(Config {
+        git: 2,
+        ..Config::default()
+    });
+    let peer = PeerId::from(SecretKey::new());
+
+    let a = streams.acquire(peer, UpgradeRequest::Git);
+    let b = streams.acquire(peer, UpgradeRequest::Git);
+    assert!(a.is_some() && b.is_some());
+    assert!(streams.acquire(peer, UpgradeRequest::Git).is_none());
+
+    let stats = streams.get(&peer).unwrap();
+    assert_eq!(stats.open(UpgradeRequest::Git), 2);
+    assert_eq!(stats.refused(UpgradeRequest::Git), 1);
+
+    drop(a);
+    assert_eq!(streams.get(&peer).unwrap().open(UpgradeRequest::Git), 1);
+    assert!(streams.acquire(peer, UpgradeRequest::Git).is_some());
+}
+
+#[test]
+fn limits_are_per_kind_and_peer() {
+    let streams = Streams::new(Config {
+        git: 1,
+        ..Config::default()
+    });
+    let peer = PeerId::from(SecretKey::new());
+    let other = PeerId::from(SecretKey::new());
+
+    let _git = streams.acquire(peer, UpgradeRequest::Git).unwrap();
+    assert!(streams.acquire(peer, UpgradeRequest::Git).is_none());
+    assert!(streams.acquire(peer, UpgradeRequest::Gossip).is_some());
+    assert!(streams.acquire(other, UpgradeRequest::Git).is_some());
+}
+
+#[test]
+fn close_reason_from_code() {
+    for reason in [
+        CloseReason::ConnectionError,
+        CloseReason::ServerShutdown,
+        CloseReason::InvalidUpgrade,
+        CloseReason::TooManyConnections,
+        CloseReason::Timeout,
+        CloseReason::StreamLimit,
+    ] {
+        assert_eq!(CloseReason::from_code(reason as u64), Some(reason))
+    }
+    assert_eq!(CloseReason::from_code(0), None)
+}
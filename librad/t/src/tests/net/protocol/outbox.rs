@@ -0,0 +1,132 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::Urn,
+    git_ext,
+    net::protocol::{
+        gossip::{Payload, Rev},
+        outbox::{Outbox, MAX_PROPAGATED},
+    },
+};
+
+fn urn(s: &[u8]) -> Urn {
+    Urn::new(git_ext::Oid::from(
+        git2::Oid::hash_object(git2::ObjectType::Blob, s).unwrap(),
+    ))
+}
+
+fn payload(urn: &Urn, rev: &[u8]) -> Payload {
+    Payload {
+        urn: urn.clone(),
+        rev: Some(Rev::Git(
+            git2::Oid::hash_object(git2::ObjectType::Commit, rev).unwrap(),
+        )),
+        origin: None,
+    }
+}
+
+#[test]
+fn pending_survives_reopen() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("announcements.wal");
+    let a = payload(&urn(b"a"), b"1");
+    let b = payload(&urn(b"b"), b"1");
+
+    let outbox = Outbox::open(&path).unwrap();
+    let seq = outbox.enqueue(&a).unwrap();
+    outbox.enqueue(&b).unwrap();
+    outbox.delivered(seq);
+    drop(outbox);
+
+    let outbox = Outbox::open(&path).unwrap();
+    assert_eq!(
+        outbox
+            .pending()
+            .into_iter()
+            .map(|(_, p)| p)
+            .collect::<Vec<_>>(),
+        vec![b]
+    );
+}
+
+#[test]
+fn propagated_tips_are_not_requeued() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("announcements.wal");
+    let a = payload(&urn(b"a"), b"1");
+
+    let outbox = Outbox::open(&path).unwrap();
+    let seq = outbox.enqueue(&a).unwrap();
+    outbox.delivered(seq);
+    assert_eq!(outbox.enqueue(&a), None);
+    drop(outbox);
+
+    // Also across restarts, and compactions
+    let outbox = Outbox::open(&path).unwrap();
+    drop(outbox);
+    let outbox = Outbox::open(&path).unwrap();
+    assert_eq!(outbox.enqueue(&a), None);
+    assert!(outbox.enqueue(&payload(&urn(b"a"), b"2")).is_some());
+}
+
+#[test]
+fn propagated_is_bounded() {
+    let outbox = Outbox::in_memory();
+    let first = payload(&urn(b"0"), b"1");
+    let seq = outbox.enqueue(&first).unwrap();
+    outbox.delivered(seq);
+    for i in 1..=MAX_PROPAGATED {
+        let seq = outbox
+            .enqueue(&payload(&urn(i.to_string().as_bytes()), b"1"))
+            .unwrap();
+        outbox.delivered(seq);
+    }
+    // The least recently delivered tip was forgotten
+    assert!(outbox.enqueue(&first).is_some());
+}
+
+#[test]
+fn newer_supersedes_pending() {
+    let outbox = Outbox::in_memory();
+    let urn = urn(b"a");
+    outbox.enqueue(&payload(&urn, b"1")).unwrap();
+    outbox.enqueue(&payload(&urn, b"2")).unwrap();
+    assert_eq!(
+        outbox
+            .pending()
+            .into_iter()
+            .map(|(_, p)| p)
+            .collect::<Vec<_>>(),
+        vec![payload(&urn, b"2")]
+    );
+}
+
+#[test]
+fn torn_tail() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("announcements.wal");
+    let a = payload(&urn(b"a"), b"1");
+    let b = payload(&urn(b"b"), b"1");
+
+    let outbox = Outbox::open(&path).unwrap();
+    outbox.enqueue(&a).unwrap();
+    outbox.enqueue(&b).unwrap();
+    drop(outbox);
+
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes.truncate(bytes.len() - 1);
+    std::fs::write(&path, bytes).unwrap();
+
+    let outbox = Outbox::open(&path).unwrap();
+    assert_eq!(
+        outbox
+            .pending()
+            .into_iter()
+            .map(|(_, p)| p)
+            .collect::<Vec<_>>(),
+        vec![a]
+    );
+}
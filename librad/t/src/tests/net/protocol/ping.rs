@@ -0,0 +1,59 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::{
+    net::protocol::ping::{Liveness, Request, Response, MAX_FAILURES},
+    PeerId,
+    SecretKey,
+};
+use test_helpers::roundtrip;
+
+#[test]
+fn roundtrip_request_response() {
+    roundtrip::cbor(Request { nonce: 42 });
+    roundtrip::cbor(Response { nonce: 42 });
+}
+
+#[test]
+fn dead_after_max_failures() {
+    let liveness = Liveness::default();
+    let peer = PeerId::from(SecretKey::new());
+    for _ in 1..MAX_FAILURES {
+        assert!(liveness.failure(peer).is_alive());
+    }
+    let health = liveness.failure(peer);
+    assert!(!health.is_alive());
+    assert_eq!(health.failures, MAX_FAILURES);
+    assert_eq!(health.pings, u64::from(MAX_FAILURES));
+}
+
+#[test]
+fn success_resets_failures() {
+    let liveness = Liveness::default();
+    let peer = PeerId::from(SecretKey::new());
+    liveness.failure(peer);
+    liveness.failure(peer);
+    let health = liveness.success(peer, Duration::from_millis(20));
+    assert!(health.is_alive());
+    assert_eq!(health.failures, 0);
+    assert_eq!(health.rtt, Some(Duration::from_millis(20)));
+    assert_eq!(liveness.get(&peer), Some(health));
+}
+
+#[test]
+fn retain_forgets_peers() {
+    let liveness = Liveness::default();
+    let keep = PeerId::from(SecretKey::new());
+    let forget = PeerId::from(SecretKey::new());
+    liveness.success(keep, Duration::from_millis(1));
+    liveness.success(forget, Duration::from_millis(1));
+    liveness.retain(|peer| peer == &keep);
+
+    let snapshot = liveness.snapshot();
+    assert!(snapshot.contains_key(&keep));
+    assert!(!snapshot.contains_key(&forget));
+}
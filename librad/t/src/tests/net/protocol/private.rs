@@ -0,0 +1,131 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{iter, net::SocketAddr};
+
+use librad::{
+    net::protocol::{
+        membership::{Hpv, Message, Params, PartialView, Priority, Tick, Transition},
+        Capability,
+        PeerAdvertisement,
+        PeerInfo,
+    },
+    PeerId,
+    SecretKey,
+};
+use rand::{rngs::StdRng, SeedableRng as _};
+
+fn advertisement(private: bool) -> PeerAdvertisement<SocketAddr> {
+    let mut ad = PeerAdvertisement::new(([127, 0, 0, 1], 8776).into());
+    if private {
+        ad.listen_addrs = iter::empty().into();
+        ad.capabilities.insert(Capability::Private);
+    }
+    ad
+}
+
+fn info(private: bool) -> PeerInfo<SocketAddr> {
+    PeerInfo {
+        peer_id: PeerId::from(SecretKey::new()),
+        advertised_info: advertisement(private),
+        seen_addrs: iter::once(([10, 0, 0, 1], 8776).into()).into(),
+    }
+}
+
+fn rng() -> StdRng {
+    StdRng::seed_from_u64(42)
+}
+
+#[tokio::test]
+async fn private_peers_never_join() {
+    let (hpv, _periodic) =
+        Hpv::<_, SocketAddr>::new(PeerId::from(SecretKey::new()), rng(), Params::default());
+
+    assert_matches!(hpv.hello(advertisement(false)), Message::Join { .. });
+    assert_matches!(
+        hpv.hello(advertisement(true)),
+        Message::Neighbour {
+            prio: Priority::High,
+            ..
+        }
+    );
+}
+
+#[tokio::test]
+async fn private_peers_are_not_propagated() {
+    let (hpv, _periodic) =
+        Hpv::<_, SocketAddr>::new(PeerId::from(SecretKey::new()), rng(), Params::default());
+    let private = info(true);
+    let public = info(false);
+    let _ = hpv.connection_established(private.clone().into());
+    let _ = hpv.connection_established(public.clone().into());
+
+    // Joins of private peers are not forwarded.
+    let tnt = hpv
+        .apply(
+            private.peer_id,
+            private.seen_addrs[0],
+            Message::ForwardJoin {
+                joined: info(true),
+                ttl: 0,
+            },
+        )
+        .unwrap();
+    assert!(tnt.ticks.is_empty());
+
+    // Private peers are not included in shuffle replies.
+    let origin = info(false);
+    let tnt = hpv
+        .apply(
+            public.peer_id,
+            public.seen_addrs[0],
+            Message::Shuffle {
+                origin: origin.clone(),
+                peers: vec![origin],
+                ttl: 0,
+            },
+        )
+        .unwrap();
+    let replies = tnt
+        .ticks
+        .into_iter()
+        .filter_map(|tick| match tick {
+            Tick::Try {
+                message: Message::ShuffleReply { peers },
+                ..
+            } => Some(peers),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        replies
+            .into_iter()
+            .flatten()
+            .map(|info| info.peer_id)
+            .collect::<Vec<_>>(),
+        vec![public.peer_id]
+    );
+}
+
+#[test]
+fn private_peers_are_not_kept_passive() {
+    let mut view = PartialView::new(PeerId::from(SecretKey::new()), rng(), 5, 30);
+
+    let private = info(true);
+    assert!(view.add_passive(private.clone()).is_empty());
+    assert!(!view.is_known(&private.peer_id));
+
+    let public = info(false);
+    view.add_passive(public.clone());
+    assert!(view.is_passive(&public.peer_id));
+
+    view.add_active(private.clone().into());
+    assert!(view.is_active(&private.peer_id));
+    assert_matches!(
+        view.demote(&private.peer_id).as_slice(),
+        [Transition::Evicted(_)]
+    );
+    assert!(!view.is_known(&private.peer_id));
+}
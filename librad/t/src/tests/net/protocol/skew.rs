@@ -0,0 +1,77 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use librad::{
+    net::protocol::skew::{Estimate, Tracker, MIN_PEERS, THRESHOLD},
+    PeerId,
+    SecretKey,
+};
+
+fn estimate(offset_ms: i64) -> Estimate {
+    Estimate {
+        offset_ms,
+        rtt: Duration::from_millis(10),
+    }
+}
+
+#[test]
+fn estimate_accounts_for_rtt() {
+    let sent = UNIX_EPOCH + Duration::from_secs(1_000);
+    let received = sent + Duration::from_millis(200);
+    // The remote read its clock ~100ms after we sent the request
+    let est = Estimate::new(sent, 1_000_100 + 5_000, received);
+    assert_eq!(est.offset_ms, 5_000);
+    assert_eq!(est.rtt, Duration::from_millis(200));
+
+    let est = Estimate::new(sent, 1_000_100 - 5_000, received);
+    assert_eq!(est.offset_ms, -5_000);
+}
+
+#[test]
+fn median_requires_min_peers() {
+    let tracker = Tracker::default();
+    for _ in 1..MIN_PEERS {
+        tracker.record(PeerId::from(SecretKey::new()), estimate(0));
+    }
+    assert_eq!(tracker.median_offset_ms(), None);
+    tracker.record(PeerId::from(SecretKey::new()), estimate(0));
+    assert_eq!(tracker.median_offset_ms(), Some(0));
+}
+
+#[test]
+fn single_skewed_peer_is_ignored() {
+    let tracker = Tracker::default();
+    let far_off = THRESHOLD.as_millis() as i64 * 10;
+    tracker.record(PeerId::from(SecretKey::new()), estimate(far_off));
+    tracker.record(PeerId::from(SecretKey::new()), estimate(10));
+    tracker.record(PeerId::from(SecretKey::new()), estimate(-10));
+    assert_eq!(tracker.median_offset_ms(), Some(10));
+    assert_eq!(tracker.check(), None);
+}
+
+#[test]
+fn warns_once_until_recovered() {
+    let tracker = Tracker::default();
+    let skewed = THRESHOLD.as_millis() as i64 * 2;
+    let peers = (0..MIN_PEERS)
+        .map(|_| PeerId::from(SecretKey::new()))
+        .collect::<Vec<_>>();
+    for peer in &peers {
+        tracker.record(*peer, estimate(skewed));
+    }
+    assert_eq!(tracker.check(), Some(skewed));
+    assert_eq!(tracker.check(), None);
+
+    for peer in &peers {
+        tracker.record(*peer, estimate(0));
+    }
+    assert_eq!(tracker.check(), None);
+    for peer in &peers {
+        tracker.record(*peer, estimate(-skewed));
+    }
+    assert_eq!(tracker.check(), Some(-skewed));
+}
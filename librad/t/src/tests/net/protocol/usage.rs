@@ -0,0 +1,140 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use librad::{
+    git::Urn,
+    git_ext,
+    net::protocol::usage::{self, Served, Usage},
+    PeerId,
+    SecretKey,
+};
+
+const DAY: u64 = 24 * 60 * 60;
+/// 2022-03-01T00:00:00Z
+const MARCH_1ST: u64 = 1_646_092_800;
+
+fn urn(s: &[u8]) -> Urn {
+    Urn::new(git_ext::Oid::from(
+        git2::Oid::hash_object(git2::ObjectType::Blob, s).unwrap(),
+    ))
+}
+
+fn day(n: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(MARCH_1ST + n * DAY + 3600)
+}
+
+fn fetch(bytes: u64) -> Served {
+    Served { fetch: true, bytes }
+}
+
+#[test]
+fn aggregates_per_urn_and_peer() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (a, b) = (urn(b"a"), urn(b"b"));
+    let alice = PeerId::from(SecretKey::new());
+    let bob = PeerId::from(SecretKey::new());
+
+    let store = Usage::open(Default::default(), tmp.path()).unwrap();
+    store.record_at(day(0), &a, alice, fetch(100));
+    store.record_at(day(0), &a, bob, fetch(50));
+    store.record_at(
+        day(0),
+        &b,
+        alice,
+        Served {
+            fetch: false,
+            bytes: 10,
+        },
+    );
+    drop(store);
+
+    let store = Usage::open(Default::default(), tmp.path()).unwrap();
+    let days = store.days(Some("2022-03-01"), Some("2022-03-01")).unwrap();
+    assert_eq!(days.len(), 1);
+    let day = &days[0];
+    assert_eq!(day.date, "2022-03-01");
+
+    let ns = &day.urns[&a.to_string()];
+    assert_eq!(ns.counters.fetches, 2);
+    assert_eq!(ns.counters.bytes, 150);
+    assert_eq!(ns.peers.len(), 2);
+    let ns = &day.urns[&b.to_string()];
+    assert_eq!(ns.counters.fetches, 0);
+    assert_eq!(ns.counters.bytes, 10);
+
+    assert_eq!(day.peers[&alice].fetches, 1);
+    assert_eq!(day.peers[&alice].bytes, 110);
+    assert_eq!(day.peers[&bob].bytes, 50);
+    assert_eq!(day.total().fetches, 2);
+    assert_eq!(day.total().bytes, 160);
+}
+
+#[test]
+fn prunes_expired_days() {
+    let tmp = tempfile::tempdir().unwrap();
+    let peer = PeerId::from(SecretKey::new());
+    let config = usage::Config {
+        retention_days: 2,
+        ..Default::default()
+    };
+
+    let store = Usage::open(config, tmp.path()).unwrap();
+    for n in 0..3 {
+        store.record_at(day(n), &urn(b"a"), peer, fetch(1));
+    }
+    let dates = store
+        .days(None, None)
+        .unwrap()
+        .into_iter()
+        .map(|day| day.date)
+        .collect::<Vec<_>>();
+    assert_eq!(dates, vec!["2022-03-02", "2022-03-03"]);
+}
+
+#[test]
+fn rejects_invalid_dates() {
+    let tmp = tempfile::tempdir().unwrap();
+    let store = Usage::open(Default::default(), tmp.path()).unwrap();
+    assert!(matches!(
+        store.days(Some("2022-13-01"), None),
+        Err(usage::error::Read::InvalidDate(_))
+    ));
+    assert!(matches!(
+        store.days(None, Some("yesterday")),
+        Err(usage::error::Read::InvalidDate(_))
+    ));
+}
+
+#[test]
+fn exports_csv() {
+    let tmp = tempfile::tempdir().unwrap();
+    let a = urn(b"a");
+    let peer = PeerId::from(SecretKey::new());
+
+    let store = Usage::open(Default::default(), tmp.path()).unwrap();
+    store.record_at(day(0), &a, peer, fetch(42));
+    let days = store.days(Some("2022-03-01"), Some("2022-03-01")).unwrap();
+
+    let mut out = Vec::new();
+    usage::export(&days, usage::Format::Csv, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        format!(
+            "date,kind,id,fetches,bytes,peers\n\
+             2022-03-01,total,,1,42,1\n\
+             2022-03-01,urn,{a},1,42,1\n\
+             2022-03-01,peer,{peer},1,42,\n"
+        )
+    );
+
+    let mut out = Vec::new();
+    usage::export(&days, usage::Format::Json, &mut out).unwrap();
+    assert_eq!(
+        serde_json::from_slice::<Vec<usage::Day>>(&out).unwrap(),
+        days
+    );
+}
@@ -0,0 +1,128 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    convert::TryFrom as _,
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+};
+
+use git_ref_format::RefString;
+use librad::{
+    git::Urn,
+    git_ext,
+    net::replication::hooks::{BoxError, Entry, Hooks, Journal},
+    PeerId,
+    SecretKey,
+};
+use link_replication::{Applied, Update, Updated};
+
+fn urn(s: &[u8]) -> Urn {
+    Urn::new(git_ext::Oid::from(
+        git2::Oid::hash_object(git2::ObjectType::Blob, s).unwrap(),
+    ))
+}
+
+fn pruned(name: &str) -> Applied<'static> {
+    Applied {
+        rejected: vec![],
+        updated: vec![Updated::Prune {
+            name: RefString::try_from(name).unwrap(),
+        }],
+    }
+}
+
+/// A post-apply hook counting the entries it processed, failing the first
+/// `fail` times it is called.
+fn counting(fail: usize) -> (Arc<AtomicUsize>, impl Fn(&Entry) -> Result<(), BoxError>) {
+    let seen = Arc::new(AtomicUsize::new(0));
+    let calls = AtomicUsize::new(0);
+    let hook = {
+        let seen = seen.clone();
+        move |_: &Entry| -> Result<(), BoxError> {
+            if calls.fetch_add(1, SeqCst) < fail {
+                return Err("not now".into());
+            }
+            seen.fetch_add(1, SeqCst);
+            Ok(())
+        }
+    };
+    (seen, hook)
+}
+
+fn allow(_: &Urn, _: PeerId, _: &[Update<'_>]) -> Result<(), String> {
+    Ok(())
+}
+
+fn deny(_: &Urn, _: PeerId, _: &[Update<'_>]) -> Result<(), String> {
+    Err("nope".to_owned())
+}
+
+#[test]
+fn pre_apply_veto() {
+    let hooks = Hooks::default()
+        .pre_apply("allow", allow)
+        .pre_apply("deny", deny);
+    let veto = hooks
+        .check(&urn(b"a"), PeerId::from(SecretKey::new()), &[])
+        .unwrap_err();
+    assert_eq!(veto.reason, "deny: nope")
+}
+
+#[test]
+fn post_apply_retried() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("replication-hooks.wal");
+    let (seen, hook) = counting(1);
+    let hooks = Hooks::default().post_apply("index", hook);
+    let from = PeerId::from(SecretKey::new());
+
+    let journal = Journal::open(&path, &hooks).unwrap();
+    journal
+        .record(&urn(b"a"), from, &pruned("refs/heads/a"))
+        .unwrap();
+    journal.deliver(&hooks);
+    assert_eq!(seen.load(SeqCst), 0);
+    assert_eq!(journal.pending("index").len(), 1);
+
+    journal.deliver(&hooks);
+    assert_eq!(seen.load(SeqCst), 1);
+    assert!(journal.pending("index").is_empty());
+}
+
+#[test]
+fn cursor_survives_reopen() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("replication-hooks.wal");
+    let (seen, hook) = counting(0);
+    let hooks = Hooks::default().post_apply("index", hook);
+    let from = PeerId::from(SecretKey::new());
+
+    let journal = Journal::open(&path, &hooks).unwrap();
+    journal
+        .record(&urn(b"a"), from, &pruned("refs/heads/a"))
+        .unwrap();
+    journal.deliver(&hooks);
+    // Not yet delivered when the process exits
+    journal
+        .record(&urn(b"b"), from, &pruned("refs/heads/b"))
+        .unwrap();
+    drop(journal);
+
+    let journal = Journal::open(&path, &hooks).unwrap();
+    let pending = journal.pending("index");
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].urn, urn(b"b"));
+    assert_eq!(pending[0].seq, 1);
+
+    journal.deliver(&hooks);
+    assert_eq!(seen.load(SeqCst), 2);
+    journal
+        .record(&urn(b"c"), from, &pruned("refs/heads/c"))
+        .unwrap();
+    assert_eq!(journal.pending("index")[0].seq, 2);
+}
@@ -0,0 +1,74 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::collections::BTreeSet;
+
+use librad::net::schema;
+
+const PRELUDE: &[&str] = &["bool", "bstr", "null", "tstr", "uint"];
+
+/// Type names referenced by a rule body, ignoring comments, member keys and
+/// control operators.
+fn references(body: &str) -> BTreeSet<String> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '-';
+    let mut refs = BTreeSet::new();
+    for line in body.lines() {
+        let line = line.split(';').next().unwrap();
+        let mut rest = line;
+        while let Some(start) = rest.find(is_word) {
+            let end = rest[start..]
+                .find(|c| !is_word(c))
+                .map_or(rest.len(), |n| start + n);
+            let word = &rest[start..end];
+            let operator = rest[..start].ends_with('.');
+            let key = rest[end..].starts_with(':');
+            if word.starts_with(|c: char| c.is_ascii_lowercase()) && !operator && !key {
+                refs.insert(word.to_owned());
+            }
+            rest = &rest[end..];
+        }
+    }
+    refs
+}
+
+#[test]
+fn protocol_is_closed() {
+    let defs = schema::protocol();
+    let names = defs.rules().map(|(name, _)| name).collect::<BTreeSet<_>>();
+    for (name, body) in defs.rules() {
+        for r in references(body) {
+            assert!(
+                PRELUDE.contains(&r.as_str()) || names.contains(r.as_str()),
+                "`{}` references undefined `{}`",
+                name,
+                r
+            )
+        }
+    }
+}
+
+#[test]
+fn protocol_covers_streams() {
+    let defs = schema::protocol();
+    let (root, body) = defs.rules().next().unwrap();
+    assert_eq!(root, "protocol-message");
+    for msg in [
+        "upgrade-request",
+        "gossip-message",
+        "membership-message",
+        "interrogation-request",
+        "interrogation-response",
+    ] {
+        assert!(body.contains(msg), "missing {}", msg)
+    }
+}
+
+#[test]
+fn rules_are_defined_once() {
+    let defs = schema::protocol();
+    let names = defs.rules().map(|(name, _)| name).collect::<Vec<_>>();
+    let unique = names.iter().collect::<BTreeSet<_>>();
+    assert_eq!(names.len(), unique.len())
+}
@@ -0,0 +1,72 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    num::NonZeroU32,
+    time::{Duration, Instant},
+};
+
+use librad::net::quic::{
+    shaping::{Config, Direction},
+    Shaper,
+    TrafficClass,
+};
+
+const CEILING: u32 = 64 * 1024;
+
+fn shaper() -> Shaper {
+    Shaper::new(Config {
+        bulk_ceiling: NonZeroU32::new(CEILING),
+    })
+}
+
+#[tokio::test]
+async fn interactive_is_not_shaped() {
+    let shaper = shaper();
+    let start = Instant::now();
+    for _ in 0..10 {
+        let granted = shaper
+            .acquire(TrafficClass::Interactive, Direction::Send, CEILING as usize)
+            .await;
+        assert_eq!(granted, CEILING as usize);
+    }
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn unlimited_by_default() {
+    let shaper = Shaper::new(Config::default());
+    let granted = shaper
+        .acquire(TrafficClass::Bulk, Direction::Recv, usize::MAX)
+        .await;
+    assert_eq!(granted, usize::MAX)
+}
+
+#[tokio::test]
+async fn bulk_is_limited_to_ceiling() {
+    let shaper = shaper();
+    let want = 2 * CEILING as usize;
+
+    // The bucket starts out full
+    let granted = shaper
+        .acquire(TrafficClass::Bulk, Direction::Send, want)
+        .await;
+    assert_eq!(granted, CEILING as usize);
+
+    // Then we need to wait for the smallest grant to become available
+    let start = Instant::now();
+    let granted = shaper
+        .acquire(TrafficClass::Bulk, Direction::Send, want)
+        .await;
+    assert!(granted >= 16 * 1024);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    // The other direction has its own budget
+    let start = Instant::now();
+    shaper
+        .acquire(TrafficClass::Bulk, Direction::Recv, want)
+        .await;
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
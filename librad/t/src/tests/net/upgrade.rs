@@ -8,11 +8,15 @@ use librad::{
     net::upgrade::{
         upgrade,
         with_upgraded,
+        Attachments,
         Error,
         Git,
         Gossip,
         Interrogation,
+        Inventory,
+        Lfs,
         Membership,
+        Ping,
         RequestPull,
         SomeUpgraded,
         UpgradeRequest,
@@ -76,6 +80,32 @@ async fn upgrade_interrogation() {
     )
 }
 
+#[tokio::test]
+async fn upgrade_inventory() {
+    assert_matches!(
+        test_upgrade(Inventory).await,
+        Ok(SomeUpgraded::Inventory(_))
+    )
+}
+
+#[tokio::test]
+async fn upgrade_ping() {
+    assert_matches!(test_upgrade(Ping).await, Ok(SomeUpgraded::Ping(_)))
+}
+
+#[tokio::test]
+async fn upgrade_lfs() {
+    assert_matches!(test_upgrade(Lfs).await, Ok(SomeUpgraded::Lfs(_)))
+}
+
+#[tokio::test]
+async fn upgrade_attachments() {
+    assert_matches!(
+        test_upgrade(Attachments).await,
+        Ok(SomeUpgraded::Attachments(_))
+    )
+}
+
 #[tokio::test]
 async fn upgrade_request_pull() {
     assert_matches!(
@@ -90,5 +120,9 @@ fn roundtrip_upgrade_request() {
     roundtrip::cbor(UpgradeRequest::Git);
     roundtrip::cbor(UpgradeRequest::Membership);
     roundtrip::cbor(UpgradeRequest::Interrogation);
+    roundtrip::cbor(UpgradeRequest::Inventory);
+    roundtrip::cbor(UpgradeRequest::Ping);
+    roundtrip::cbor(UpgradeRequest::Lfs);
+    roundtrip::cbor(UpgradeRequest::Attachments);
     roundtrip::cbor(UpgradeRequest::RequestPull);
 }
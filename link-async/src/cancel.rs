@@ -0,0 +1,145 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Cooperative cancellation.
+//!
+//! A [`CancellationToken`] signals to a group of tasks that they should stop.
+//! Tokens form a tree: cancelling a token cancels all tokens derived from it
+//! via [`CancellationToken::child`], but not its parent. This allows to
+//! cancel eg. all tasks serving a connection when it is closed, while
+//! shutting down the whole node cancels the tasks of all connections.
+//!
+//! Tasks spawned using [`crate::Spawner::spawn_cancellable`] are dropped at
+//! their next suspension point after their token was cancelled, even if they
+//! were detached.
+
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Arc,
+        Mutex,
+        Weak,
+    },
+};
+
+use futures::future::{self, Either};
+use tokio::sync::Notify;
+
+use crate::Cancelled;
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+    children: Mutex<Vec<Weak<Inner>>>,
+}
+
+impl Inner {
+    fn cancel(&self) {
+        if self.cancelled.swap(true, SeqCst) {
+            return;
+        }
+        self.notify.notify_waiters();
+        let children = std::mem::take(&mut *self.children.lock().unwrap());
+        for child in children.iter().filter_map(Weak::upgrade) {
+            child.cancel()
+        }
+    }
+}
+
+/// A handle to signal, and wait for, cancellation.
+///
+/// Clones of a token share the same state.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a token which is cancelled when `self` is cancelled.
+    ///
+    /// Cancelling the child does not affect `self`.
+    pub fn child(&self) -> Self {
+        let child = Self::new();
+        let mut children = self.inner.children.lock().unwrap();
+        if self.is_cancelled() {
+            child.inner.cancelled.store(true, SeqCst);
+        } else {
+            children.retain(|weak| weak.strong_count() > 0);
+            children.push(Arc::downgrade(&child.inner));
+        }
+        child
+    }
+
+    /// Cancel this token and all of its children.
+    ///
+    /// Cancelling an already cancelled token is a no-op.
+    pub fn cancel(&self) {
+        self.inner.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(SeqCst)
+    }
+
+    /// Resolves once the token is cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            // Must be created before checking the flag, so a concurrent
+            // `notify_waiters` is not missed.
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await
+        }
+    }
+
+    /// Drive `f` to completion, unless the token is cancelled first.
+    pub async fn run_until_cancelled<F>(&self, f: F) -> Result<F::Output, Cancelled>
+    where
+        F: Future,
+    {
+        let cancelled = self.cancelled();
+        futures::pin_mut!(cancelled);
+        futures::pin_mut!(f);
+        match future::select(cancelled, f).await {
+            Either::Left(((), _)) => Err(Cancelled),
+            Either::Right((out, _)) => Ok(out),
+        }
+    }
+
+    /// Cancel the token when the returned guard is dropped.
+    pub fn drop_guard(self) -> DropGuard {
+        DropGuard { token: Some(self) }
+    }
+}
+
+/// Cancels a [`CancellationToken`] when dropped, unless
+/// [`DropGuard::disarm`]ed.
+#[must_use = "dropping the guard cancels the token immediately"]
+pub struct DropGuard {
+    token: Option<CancellationToken>,
+}
+
+impl DropGuard {
+    /// Return the token without cancelling it.
+    pub fn disarm(mut self) -> CancellationToken {
+        self.token.take().expect("token is only taken once")
+    }
+}
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        if let Some(token) = self.token.take() {
+            token.cancel()
+        }
+    }
+}
@@ -8,7 +8,9 @@ use std::{
     task::{Context, Poll},
 };
 
-use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
 
 // Copied from async_std::os::unix::net::Incoming
 pub struct Incoming<'a, P: PollSocket>(&'a P);
@@ -38,6 +40,7 @@ pub trait PollSocket {
     ) -> Poll<std::io::Result<(Self::Socket, Self::SockAddr)>>;
 }
 
+#[cfg(unix)]
 impl PollSocket for UnixListener {
     type Socket = UnixStream;
     type SockAddr = tokio::net::unix::SocketAddr;
@@ -50,10 +53,12 @@ impl PollSocket for UnixListener {
     }
 }
 
+#[cfg(unix)]
 pub trait UnixListenerExt {
     fn incoming(&self) -> Incoming<'_, UnixListener>;
 }
 
+#[cfg(unix)]
 impl UnixListenerExt for UnixListener {
     fn incoming(&self) -> Incoming<'_, UnixListener> {
         Incoming(self)
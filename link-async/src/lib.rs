@@ -7,11 +7,16 @@
 
 extern crate radicle_std_ext as std_ext;
 
-mod spawn;
+pub mod cancel;
+pub use cancel::CancellationToken;
+
+pub mod spawn;
 pub use spawn::{Cancelled, JoinError, Spawner, Stats, Task};
 
 mod time;
 pub use time::{interval, sleep, timeout, Elapsed};
 
 pub mod incoming;
+pub mod supervisor;
+pub use supervisor::Supervisor;
 pub mod tasks;
@@ -7,22 +7,84 @@
 use std::{
     any::Any,
     future::Future,
+    io,
     panic,
     pin::Pin,
     sync::{
-        atomic::{AtomicUsize, Ordering::Relaxed},
+        atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed},
+        mpsc,
         Arc,
+        Mutex,
     },
     task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
 };
 
+use futures::channel::oneshot;
 use futures_util::FutureExt as _;
 use thiserror::Error;
+use tokio::sync::Semaphore;
 use tracing::Instrument as _;
 
+use crate::CancellationToken;
+
+/// Sizing of the threads backing a [`Spawner`].
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Number of threads running async tasks.
+    ///
+    /// Only honoured by runtimes created through [`Config::runtime`].
+    /// Default: one per core
+    pub worker_threads: Option<usize>,
+    /// If set, tasks spawned via [`Spawner::blocking`] run on a pool of this
+    /// many threads dedicated to the [`Spawner`].
+    ///
+    /// Otherwise, they run on a process-wide pool which grows on demand, and
+    /// is shared with all other [`Spawner`]s.
+    ///
+    /// Default: none
+    pub blocking_threads: Option<usize>,
+    /// Maximum number of tasks waiting for a thread of the dedicated blocking
+    /// pool. Once reached, [`Spawner::blocking`] waits for room in the queue
+    /// before submitting the task.
+    ///
+    /// Default: 256
+    pub blocking_queue: usize,
+    /// Blocking tasks which wait longer than this before they start running
+    /// are counted as [`Stats::saturated`].
+    ///
+    /// Default: 100ms
+    pub saturation: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            worker_threads: None,
+            blocking_threads: None,
+            blocking_queue: 256,
+            saturation: Duration::from_millis(100),
+        }
+    }
+}
+
+impl Config {
+    /// Create a multi-threaded runtime with [`Config::worker_threads`].
+    pub fn runtime(&self) -> io::Result<tokio::runtime::Runtime> {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        if let Some(n) = self.worker_threads {
+            builder.worker_threads(n);
+        }
+        builder.enable_all().build()
+    }
+}
+
 /// Wrapper around an async runtime.
 pub struct Spawner {
     inner: tokio::runtime::Handle,
+    pool: Option<BlockingPool>,
+    saturation: Duration,
     stats: StatsMut,
 }
 
@@ -38,12 +100,23 @@ impl Spawner {
 
     /// Create a [`Spawner`] from a [`tokio::runtime::Handle`].
     pub fn tokio(inner: tokio::runtime::Handle) -> Self {
+        Self::with_config(inner, Config::default())
+    }
+
+    /// Create a [`Spawner`] from a [`tokio::runtime::Handle`], sizing its
+    /// blocking thread pool according to `config`.
+    ///
+    /// # Panics
+    ///
+    /// If the threads of a dedicated blocking pool can not be spawned.
+    pub fn with_config(inner: tokio::runtime::Handle, config: Config) -> Self {
         Self {
             inner,
-            stats: StatsMut {
-                spawned: Arc::new(AtomicUsize::new(0)),
-                blocking: Arc::new(AtomicUsize::new(0)),
-            },
+            pool: config
+                .blocking_threads
+                .map(|threads| BlockingPool::new(threads, config.blocking_queue)),
+            saturation: config.saturation,
+            stats: StatsMut::default(),
         }
     }
 
@@ -85,16 +158,34 @@ impl Spawner {
         self.inner
             .spawn(
                 async move {
-                    counter.fetch_add(1, Relaxed);
-                    let res = task.await;
-                    counter.fetch_sub(1, Relaxed);
-                    res
+                    let _spawned = Gauge::inc(counter);
+                    task.await
                 }
                 .in_current_span(),
             )
             .into()
     }
 
+    /// Spawn an asynchronous task which is cancelled when `token` is.
+    ///
+    /// Like [`Spawner::spawn`], but the task is also dropped at its next
+    /// suspension point once `token` is cancelled, in which case the [`Task`]
+    /// resolves to `Err(Cancelled)`. This holds even if the [`Task`] was
+    /// detached, so long-running tasks can be detached without outliving the
+    /// context they were spawned in.
+    pub fn spawn_cancellable<T>(
+        &self,
+        token: &CancellationToken,
+        task: T,
+    ) -> Task<Result<T::Output, Cancelled>>
+    where
+        T: Future + Send + 'static,
+        T::Output: Send + 'static,
+    {
+        let token = token.clone();
+        self.spawn(async move { token.run_until_cancelled(task).await })
+    }
+
     /// Run a blocking function in an async context.
     ///
     /// The function is run on a separate thread pool, so as to not block the
@@ -103,6 +194,11 @@ impl Spawner {
     ///
     /// The `blocking` counter of [`Stats`] will be incremented once the task is
     /// scheduled for execution, and decremented when the function completes.
+    /// Until then, it is counted as `blocking_queued`.
+    ///
+    /// If the [`Spawner`] was created with [`Config::blocking_threads`], the
+    /// function runs on the dedicated pool, waiting for room in its queue if
+    /// necessary. Otherwise, it runs on the process-wide blocking pool.
     ///
     /// The task is run in the [`tracing::Span`] context active at the call site
     /// of [`blocking()`][`Spawner::blocking`].
@@ -131,16 +227,26 @@ impl Spawner {
     {
         let rt = self.inner.clone();
         let span = tracing::Span::current();
-        let counter = Arc::clone(&self.stats.blocking);
-        blocking::unblock(move || {
-            counter.fetch_add(1, Relaxed);
+        let stats = self.stats.clone();
+        let saturation = self.saturation;
+        let submitted = Instant::now();
+        let queued = Gauge::inc(Arc::clone(&stats.blocking_queued));
+        let task = move || {
+            drop(queued);
+            let waited = submitted.elapsed();
+            if waited > saturation {
+                stats.saturated.fetch_add(1, Relaxed);
+                tracing::debug!(?waited, "blocking pool saturated");
+            }
+            let _blocking = Gauge::inc(Arc::clone(&stats.blocking));
             let _span = span.enter();
             let _rt = rt.enter();
-            let res = f();
-            counter.fetch_sub(1, Relaxed);
-            res
-        })
-        .await
+            f()
+        };
+        match &self.pool {
+            Some(pool) => pool.run(task).await,
+            None => blocking::unblock(task).await,
+        }
     }
 
     /// Obtain a snapshot of some stats about this [`Spawner`].
@@ -149,9 +255,12 @@ impl Spawner {
     }
 }
 
+#[derive(Clone, Default)]
 struct StatsMut {
     spawned: Arc<AtomicUsize>,
     blocking: Arc<AtomicUsize>,
+    blocking_queued: Arc<AtomicUsize>,
+    saturated: Arc<AtomicU64>,
 }
 
 impl StatsMut {
@@ -159,10 +268,29 @@ impl StatsMut {
         Stats {
             spawned: self.spawned.load(Relaxed),
             blocking: self.blocking.load(Relaxed),
+            blocking_queued: self.blocking_queued.load(Relaxed),
+            saturated: self.saturated.load(Relaxed),
         }
     }
 }
 
+/// Increments a counter of [`StatsMut`] while alive, so that it is decremented
+/// again also if the task is dropped before it completes, or panics.
+struct Gauge(Arc<AtomicUsize>);
+
+impl Gauge {
+    fn inc(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for Gauge {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Relaxed);
+    }
+}
+
 /// Snapshot of the state of a [`Spawner`].
 pub struct Stats {
     /// Number of tasks spawned using [`Spawner::spawn`] whose futures have not
@@ -171,6 +299,72 @@ pub struct Stats {
     /// Number of tasks spawned using [`Spawner::blocking`] whose futures
     /// have not resolved yet. Includes detached tasks.
     pub blocking: usize,
+    /// Number of tasks submitted via [`Spawner::blocking`] which are waiting
+    /// for a thread.
+    pub blocking_queued: usize,
+    /// Number of tasks submitted via [`Spawner::blocking`] since startup which
+    /// waited longer than [`Config::saturation`] for a thread.
+    pub saturated: u64,
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A fixed number of threads running blocking tasks from a bounded queue.
+///
+/// The threads exit once the pool is dropped and the queue is drained.
+struct BlockingPool {
+    jobs: Mutex<mpsc::Sender<Job>>,
+    slots: Semaphore,
+}
+
+impl BlockingPool {
+    fn new(threads: usize, queue: usize) -> Self {
+        let threads = threads.max(1);
+        let (tx, rx) = mpsc::channel::<Job>();
+        let rx = Arc::new(Mutex::new(rx));
+        for i in 0..threads {
+            let rx = Arc::clone(&rx);
+            thread::Builder::new()
+                .name(format!("link-blocking-{}", i))
+                .spawn(move || loop {
+                    let job = match rx.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(mpsc::RecvError) => break,
+                    };
+                    job()
+                })
+                .expect("failed to spawn blocking pool thread");
+        }
+        Self {
+            jobs: Mutex::new(tx),
+            slots: Semaphore::new(threads + queue),
+        }
+    }
+
+    async fn run<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let _slot = self
+            .slots
+            .acquire()
+            .await
+            .expect("blocking pool semaphore is never closed");
+        let (tx, rx) = oneshot::channel();
+        self.jobs
+            .lock()
+            .unwrap()
+            .send(Box::new(move || {
+                let res = panic::catch_unwind(panic::AssertUnwindSafe(f));
+                tx.send(res).ok();
+            }))
+            .expect("blocking pool threads outlive the pool");
+        match rx.await.expect("blocking pool thread dropped a task") {
+            Ok(res) => res,
+            Err(panik) => panic::resume_unwind(panik),
+        }
+    }
 }
 
 /// A handle to a task spawned via [`Spawner::spawn`].
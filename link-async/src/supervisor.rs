@@ -0,0 +1,305 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Supervised task groups.
+//!
+//! A [`Supervisor`] runs a set of named, long-running child tasks, and
+//! restarts them according to their [`Restart`] policy when they exit. Restarts
+//! are delayed by an exponential [`Backoff`], so a child failing immediately
+//! does not spin. If a child needs to be restarted more often than permitted
+//! by the [`Intensity`], the supervisor gives up: all children are cancelled,
+//! and [`Supervisor::run`] returns an [`Escalation`].
+//!
+//! Since [`Supervisor::run`] is itself a fallible future, supervisors can be
+//! nested to form supervision trees: the escalation of a child supervisor is
+//! just a failure to its parent.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures::{
+    future::BoxFuture,
+    stream::{FuturesUnordered, StreamExt as _},
+    FutureExt as _,
+};
+use thiserror::Error;
+use tracing::Instrument as _;
+
+use crate::{JoinError, Spawner};
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// When to restart a child.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Restart {
+    /// Always restart the child, even if it completed successfully.
+    Permanent,
+    /// Restart the child only if it failed, ie. returned an error or panicked.
+    Transient,
+    /// Never restart the child.
+    Temporary,
+}
+
+/// Delay before restarting a child.
+///
+/// The delay starts at `min`, and doubles with every consecutive restart up
+/// to `max`. A child which ran for longer than `max` before exiting is
+/// considered to have recovered, and is restarted after `min` again.
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            min: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Backoff {
+    fn delay(&self, attempt: u32) -> Duration {
+        self.min
+            .checked_mul(2u32.saturating_pow(attempt))
+            .map_or(self.max, |delay| delay.min(self.max))
+    }
+}
+
+/// Maximum restart frequency of a single child.
+///
+/// If a child is restarted more than `max_restarts` times within `period`,
+/// the supervisor escalates.
+#[derive(Clone, Copy, Debug)]
+pub struct Intensity {
+    pub max_restarts: usize,
+    pub period: Duration,
+}
+
+impl Default for Intensity {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            period: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Why a child exited.
+#[derive(Debug, Error)]
+pub enum Failure {
+    #[error("exited")]
+    Exited,
+
+    #[error(transparent)]
+    Error(BoxError),
+
+    #[error("panicked")]
+    Panicked,
+
+    #[error("cancelled")]
+    Cancelled,
+}
+
+/// The restart intensity of a child was exceeded.
+#[derive(Debug, Error)]
+#[error("child `{child}` was restarted more than {} times within {:?}", .intensity.max_restarts, .intensity.period)]
+pub struct Escalation {
+    pub child: String,
+    pub intensity: Intensity,
+    #[source]
+    pub last: Failure,
+}
+
+type Start = Box<dyn Fn() -> BoxFuture<'static, Result<(), BoxError>> + Send + Sync>;
+
+struct Child {
+    name: String,
+    restart: Restart,
+    start: Start,
+    attempt: u32,
+    restarts: VecDeque<Instant>,
+}
+
+enum Event {
+    Exited {
+        child: usize,
+        started: Instant,
+        result: Result<Result<(), BoxError>, JoinError>,
+    },
+    Restart {
+        child: usize,
+    },
+}
+
+/// A group of supervised tasks.
+pub struct Supervisor {
+    spawner: Arc<Spawner>,
+    backoff: Backoff,
+    intensity: Intensity,
+    children: Vec<Child>,
+}
+
+impl Supervisor {
+    pub fn new(spawner: Arc<Spawner>) -> Self {
+        Self {
+            spawner,
+            backoff: Backoff::default(),
+            intensity: Intensity::default(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn intensity(mut self, intensity: Intensity) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Add a child named `name`.
+    ///
+    /// `start` is called to obtain the child's future whenever it is
+    /// (re-)started.
+    pub fn child<F, Fut, E>(mut self, name: impl Into<String>, restart: Restart, start: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        self.children.push(Child {
+            name: name.into(),
+            restart,
+            start: Box::new(move || start().map(|res| res.map_err(Into::into)).boxed()),
+            attempt: 0,
+            restarts: VecDeque::new(),
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Run the children until they all exited without being restarted.
+    ///
+    /// Dropping the returned future cancels all children.
+    pub async fn run(mut self) -> Result<(), Escalation> {
+        let mut events = FuturesUnordered::new();
+        for child in 0..self.children.len() {
+            events.push(self.spawn(child));
+        }
+
+        while let Some(event) = events.next().await {
+            match event {
+                Event::Restart { child } => events.push(self.spawn(child)),
+                Event::Exited {
+                    child,
+                    started,
+                    result,
+                } => {
+                    let failure = match result {
+                        Ok(Ok(())) => Failure::Exited,
+                        Ok(Err(e)) => Failure::Error(e),
+                        Err(JoinError::Panicked(_)) => Failure::Panicked,
+                        Err(JoinError::Cancelled) => Failure::Cancelled,
+                    };
+                    if let Some(delay) = self.on_exit(child, started, failure)? {
+                        events.push(
+                            crate::sleep(delay)
+                                .map(move |()| Event::Restart { child })
+                                .boxed(),
+                        );
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn spawn(&self, child: usize) -> BoxFuture<'static, Event> {
+        let Child { name, start, .. } = &self.children[child];
+        let span = tracing::info_span!("supervised", child = %name);
+        let task = self.spawner.spawn(start().instrument(span));
+        let started = Instant::now();
+        task.map(move |result| Event::Exited {
+            child,
+            started,
+            result,
+        })
+        .boxed()
+    }
+
+    /// Decide whether to restart `child`, and after which delay.
+    fn on_exit(
+        &mut self,
+        child: usize,
+        started: Instant,
+        failure: Failure,
+    ) -> Result<Option<Duration>, Escalation> {
+        let Self {
+            backoff,
+            intensity,
+            children,
+            ..
+        } = self;
+        let child = &mut children[child];
+
+        let restart = match (&failure, child.restart) {
+            (_, Restart::Temporary) => false,
+            (Failure::Exited, Restart::Transient) => false,
+            (_, Restart::Transient) | (_, Restart::Permanent) => true,
+        };
+        match &failure {
+            Failure::Exited => tracing::info!(child = %child.name, restart, "child exited"),
+            failure => tracing::warn!(child = %child.name, restart, err = %failure, "child failed"),
+        }
+        if !restart {
+            return Ok(None);
+        }
+
+        let now = Instant::now();
+        while let Some(t) = child.restarts.front() {
+            if now.duration_since(*t) <= intensity.period {
+                break;
+            }
+            child.restarts.pop_front();
+        }
+        if child.restarts.len() >= intensity.max_restarts {
+            tracing::error!(child = %child.name, "restart intensity exceeded, escalating");
+            return Err(Escalation {
+                child: child.name.clone(),
+                intensity: *intensity,
+                last: failure,
+            });
+        }
+        child.restarts.push_back(now);
+
+        if now.duration_since(started) > backoff.max {
+            child.attempt = 0;
+        }
+        let delay = backoff.delay(child.attempt);
+        child.attempt = child.attempt.saturating_add(1);
+
+        Ok(Some(delay))
+    }
+}
+
+impl Spawner {
+    /// Create a [`Supervisor`] spawning its children on this [`Spawner`].
+    pub fn supervisor(self: &Arc<Self>) -> Supervisor {
+        Supervisor::new(Arc::clone(self))
+    }
+}
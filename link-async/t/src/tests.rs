@@ -6,6 +6,9 @@
 use link_async::Spawner;
 use tokio::runtime::Runtime;
 
+mod cancel;
+mod spawn;
+mod supervisor;
 mod tasks;
 
 #[test]
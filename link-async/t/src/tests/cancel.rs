@@ -0,0 +1,74 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use futures::future;
+use link_async::{CancellationToken, Spawner};
+
+#[tokio::test]
+async fn cancels_children() {
+    let root = CancellationToken::new();
+    let child = root.child();
+    let grandchild = child.child();
+
+    root.cancel();
+    assert!(child.is_cancelled());
+    assert!(grandchild.is_cancelled());
+    link_async::timeout(Duration::from_secs(1), grandchild.cancelled())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn does_not_cancel_parent() {
+    let root = CancellationToken::new();
+    let child = root.child();
+
+    child.cancel();
+    assert!(child.is_cancelled());
+    assert!(!root.is_cancelled());
+    assert!(!root.child().child().is_cancelled());
+}
+
+#[tokio::test]
+async fn child_of_cancelled_is_cancelled() {
+    let root = CancellationToken::new();
+    root.cancel();
+    assert!(root.child().is_cancelled())
+}
+
+#[tokio::test]
+async fn detached_task_is_cancelled() {
+    let spawner = Spawner::from_current().unwrap();
+    let token = CancellationToken::new();
+    let (tx, rx) = futures::channel::oneshot::channel::<()>();
+
+    spawner
+        .spawn_cancellable(&token, async move {
+            let _tx = tx;
+            future::pending::<()>().await
+        })
+        .detach();
+    token.cancel();
+
+    // The sender is dropped along with the task
+    link_async::timeout(Duration::from_secs(1), rx)
+        .await
+        .unwrap()
+        .unwrap_err();
+}
+
+#[tokio::test]
+async fn completes_unless_cancelled() {
+    let spawner = Spawner::from_current().unwrap();
+    let token = CancellationToken::new();
+    let task = spawner.spawn_cancellable(&token, async { 42 });
+    assert_eq!(task.await.unwrap().unwrap(), 42);
+
+    let guard = token.child().drop_guard();
+    let task = spawner.spawn_cancellable(&guard.disarm(), async { 42 });
+    assert_eq!(task.await.unwrap().unwrap(), 42);
+}
@@ -0,0 +1,61 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{thread, time::Duration};
+
+use futures::future;
+use link_async::{spawn::Config, Spawner};
+use tokio::runtime::{Handle, Runtime};
+
+fn dedicated(threads: usize) -> Spawner {
+    Spawner::with_config(
+        Handle::current(),
+        Config {
+            blocking_threads: Some(threads),
+            saturation: Duration::from_millis(10),
+            ..Config::default()
+        },
+    )
+}
+
+#[tokio::test]
+async fn runs_on_dedicated_pool() {
+    let spawner = dedicated(1);
+    let name = spawner
+        .blocking(|| thread::current().name().map(ToOwned::to_owned))
+        .await;
+    assert_eq!(name.as_deref(), Some("link-blocking-0"))
+}
+
+#[tokio::test]
+async fn counts_saturation() {
+    let spawner = dedicated(1);
+    future::join_all((0..3).map(|_| spawner.blocking(|| thread::sleep(Duration::from_millis(50)))))
+        .await;
+
+    let stats = spawner.stats();
+    assert_eq!(stats.blocking, 0);
+    assert_eq!(stats.blocking_queued, 0);
+    assert!(stats.saturated >= 2, "saturated: {}", stats.saturated)
+}
+
+#[test]
+#[should_panic(expected = "you will see this")]
+fn dedicated_pool_propagates_panic() {
+    Runtime::new()
+        .unwrap()
+        .block_on(async { dedicated(1).blocking(|| panic!("you will see this")).await })
+}
+
+#[test]
+fn runtime_with_worker_threads() {
+    let rt = Config {
+        worker_threads: Some(2),
+        ..Config::default()
+    }
+    .runtime()
+    .unwrap();
+    assert_eq!(rt.block_on(async { 42 }), 42)
+}
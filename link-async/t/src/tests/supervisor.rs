@@ -0,0 +1,118 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+    time::Duration,
+};
+
+use link_async::{
+    supervisor::{Backoff, Failure, Intensity, Restart},
+    Spawner,
+};
+
+fn spawner() -> Arc<Spawner> {
+    Arc::new(Spawner::from_current().unwrap())
+}
+
+const FAST: Backoff = Backoff {
+    min: Duration::from_millis(1),
+    max: Duration::from_millis(10),
+};
+
+/// Returns a counter of the number of starts, and a child which fails until it
+/// was started `succeed_after` times.
+fn flaky(
+    succeed_after: usize,
+) -> (
+    Arc<AtomicUsize>,
+    impl Fn() -> futures::future::Ready<Result<(), &'static str>> + Send + Sync + 'static,
+) {
+    let starts = Arc::new(AtomicUsize::new(0));
+    let child = {
+        let starts = Arc::clone(&starts);
+        move || {
+            let n = starts.fetch_add(1, SeqCst) + 1;
+            futures::future::ready(if n >= succeed_after {
+                Ok(())
+            } else {
+                Err("flaky")
+            })
+        }
+    };
+    (starts, child)
+}
+
+#[tokio::test]
+async fn transient_restarts_until_success() {
+    let (starts, child) = flaky(3);
+    spawner()
+        .supervisor()
+        .backoff(FAST)
+        .child("flaky", Restart::Transient, child)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(starts.load(SeqCst), 3)
+}
+
+#[tokio::test]
+async fn temporary_is_not_restarted() {
+    let (starts, child) = flaky(3);
+    spawner()
+        .supervisor()
+        .backoff(FAST)
+        .child("flaky", Restart::Temporary, child)
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(starts.load(SeqCst), 1)
+}
+
+#[tokio::test]
+async fn escalates_when_intensity_exceeded() {
+    let (starts, child) = flaky(usize::MAX);
+    let escalation = spawner()
+        .supervisor()
+        .backoff(FAST)
+        .intensity(Intensity {
+            max_restarts: 2,
+            period: Duration::from_secs(60),
+        })
+        .child("flaky", Restart::Permanent, child)
+        .run()
+        .await
+        .unwrap_err();
+    assert_eq!(escalation.child, "flaky");
+    assert!(matches!(escalation.last, Failure::Error(_)));
+    assert_eq!(starts.load(SeqCst), 3)
+}
+
+#[tokio::test]
+async fn panics_are_failures() {
+    let starts = Arc::new(AtomicUsize::new(0));
+    spawner()
+        .supervisor()
+        .backoff(FAST)
+        .child("panicky", Restart::Transient, {
+            let starts = Arc::clone(&starts);
+            move || {
+                let n = starts.fetch_add(1, SeqCst);
+                async move {
+                    if n == 0 {
+                        panic!("first start panics")
+                    }
+                    Ok::<_, &str>(())
+                }
+            }
+        })
+        .run()
+        .await
+        .unwrap();
+    assert_eq!(starts.load(SeqCst), 2)
+}
@@ -0,0 +1,90 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Batch verification of signatures.
+//!
+//! Verifying a batch of `n` signatures at once is considerably cheaper than
+//! verifying them one by one, as the expensive part of the computation can be
+//! shared. This pays off when many signatures need to be checked together,
+//! eg. the `rad/signed_refs` of all remotes of a namespace.
+//!
+//! A batch only tells whether _all_ signatures are valid. If it fails,
+//! [`Batch::verify`] falls back to verifying each signature individually, in
+//! order to report which ones are invalid. Verification is thus no more
+//! expensive than individual verification in the worst case.
+
+use ed25519_zebra as ed25519;
+
+use crate::{PublicKey, Signature};
+
+/// Below this number of signatures, batching doesn't pay off.
+const MIN_BATCH_SIZE: usize = 4;
+
+/// A batch of signatures to verify, each associated with a tag `T`.
+#[derive(Clone, Debug)]
+pub struct Batch<T> {
+    items: Vec<(T, ed25519::batch::Item)>,
+}
+
+impl<T> Default for Batch<T> {
+    fn default() -> Self {
+        Self { items: Vec::new() }
+    }
+}
+
+impl<T> Batch<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            items: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Add the signature `sig` of `data` by `key` to the batch.
+    pub fn queue(&mut self, tag: T, key: &PublicKey, sig: &Signature, data: &[u8]) {
+        self.items
+            .push((tag, ed25519::batch::Item::from((key.0, sig.0, data))))
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Verify all signatures in the batch.
+    ///
+    /// # Errors
+    ///
+    /// If any signature is invalid, the tags of all invalid signatures are
+    /// returned, in the order they were queued.
+    pub fn verify(self) -> Result<(), Vec<T>> {
+        if self.items.len() >= MIN_BATCH_SIZE {
+            let mut verifier = ed25519::batch::Verifier::new();
+            for (_, item) in &self.items {
+                verifier.queue(item.clone());
+            }
+            if verifier.verify(rand::thread_rng()).is_ok() {
+                return Ok(());
+            }
+        }
+
+        let invalid = self
+            .items
+            .into_iter()
+            .filter_map(|(tag, item)| item.verify_single().err().map(|_| tag))
+            .collect::<Vec<_>>();
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid)
+        }
+    }
+}
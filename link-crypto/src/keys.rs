@@ -40,7 +40,7 @@ pub struct SecretKey(ed25519::SigningKey);
 
 /// The public part of a `Key``
 #[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, Hash)]
-pub struct PublicKey(ed25519::VerificationKeyBytes);
+pub struct PublicKey(pub(crate) ed25519::VerificationKeyBytes);
 
 impl From<sign::PublicKey> for PublicKey {
     fn from(other: sign::PublicKey) -> PublicKey {
@@ -56,7 +56,7 @@ impl From<PublicKey> for sign::PublicKey {
 
 /// A signature produced by `Key::sign`
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Signature(ed25519::Signature);
+pub struct Signature(pub(crate) ed25519::Signature);
 
 impl From<sign::Signature> for Signature {
     fn from(other: sign::Signature) -> Signature {
@@ -13,6 +13,9 @@ extern crate async_trait;
 pub extern crate radicle_git_ext as git_ext;
 pub extern crate radicle_keystore as keystore;
 
+pub mod batch;
+pub use batch::Batch;
+
 mod keys;
 pub use keys::{
     IntoSecretKeyError,
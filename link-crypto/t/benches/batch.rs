@@ -0,0 +1,48 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Compare individual and batch verification of signatures, as done when
+//! loading the `rad/signed_refs` of many remotes.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use link_crypto::{Batch, PublicKey, SecretKey, Signature};
+
+fn signatures(n: usize) -> Vec<(PublicKey, Signature, Vec<u8>)> {
+    (0..n)
+        .map(|i| {
+            let key = SecretKey::new();
+            let data = format!("signed refs of remote {}", i).into_bytes();
+            let sig = key.sign(&data);
+            (key.public(), sig, data)
+        })
+        .collect()
+}
+
+fn verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify");
+    for n in [10, 100, 500] {
+        let sigs = signatures(n);
+        group.bench_with_input(BenchmarkId::new("individual", n), &sigs, |b, sigs| {
+            b.iter(|| assert!(sigs.iter().all(|(key, sig, data)| key.verify(sig, data))))
+        });
+        group.bench_with_input(BenchmarkId::new("batch", n), &sigs, |b, sigs| {
+            b.iter_batched(
+                || {
+                    let mut batch = Batch::with_capacity(sigs.len());
+                    for (key, sig, data) in sigs {
+                        batch.queue((), key, sig, data);
+                    }
+                    batch
+                },
+                |batch| assert!(batch.verify().is_ok()),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish()
+}
+
+criterion_group!(benches, verify);
+criterion_main!(benches);
@@ -1,5 +1,6 @@
 // Copyright © 2022 The Radicle Link Contributors
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+mod batch;
 mod keys;
 mod peer_id;
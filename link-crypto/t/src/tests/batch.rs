@@ -0,0 +1,39 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use link_crypto::{Batch, SecretKey};
+
+fn batch(n: usize, invalid: &[usize]) -> Batch<usize> {
+    let mut batch = Batch::with_capacity(n);
+    for i in 0..n {
+        let key = SecretKey::new();
+        let data = format!("signed refs {}", i);
+        let sig = key.sign(data.as_bytes());
+        if invalid.contains(&i) {
+            batch.queue(i, &key.public(), &sig, b"tampered");
+        } else {
+            batch.queue(i, &key.public(), &sig, data.as_bytes());
+        }
+    }
+    batch
+}
+
+#[test]
+fn empty() {
+    assert_eq!(Batch::<()>::new().verify(), Ok(()))
+}
+
+#[test]
+fn all_valid() {
+    for n in [1, 3, 64] {
+        assert_eq!(batch(n, &[]).verify(), Ok(()))
+    }
+}
+
+#[test]
+fn reports_invalid() {
+    assert_eq!(batch(2, &[1]).verify(), Err(vec![1]));
+    assert_eq!(batch(64, &[3, 42]).verify(), Err(vec![3, 42]));
+}
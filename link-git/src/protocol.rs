@@ -3,12 +3,15 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+use std::io;
+
 use bstr::ByteSlice as _;
 use git_protocol::transport::client;
 use versions::Version;
 
 pub mod fetch;
 pub mod ls;
+pub mod meter;
 pub mod packwriter;
 pub mod take;
 pub mod transport;
@@ -28,3 +31,31 @@ fn remote_git_version(caps: &client::Capabilities) -> Option<Version> {
     })?;
     Version::new(agent.strip_prefix("git/")?)
 }
+
+/// The object format we are able to exchange objects in.
+///
+/// This is the format of the local storage, which is always SHA-1 for now.
+/// Exchanging objects with a SHA-256 repository would require translating
+/// object ids, which is not supported: such remotes are refused.
+const OBJECT_FORMAT: &str = "sha1";
+
+/// Refuse to talk to a server which advertises an `object-format` other than
+/// [`OBJECT_FORMAT`].
+///
+/// A server not advertising the capability at all uses SHA-1.
+fn ensure_object_format(caps: &client::Capabilities) -> io::Result<()> {
+    let format = caps
+        .capability("object-format")
+        .and_then(|cap| cap.value().map(|bs| bs.to_str_lossy().into_owned()));
+    match format {
+        Some(format) if format != OBJECT_FORMAT => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "remote repository uses object format `{}`, but only `{}` is supported: \
+                 refusing to mix object formats",
+                format, OBJECT_FORMAT
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
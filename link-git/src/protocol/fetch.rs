@@ -32,7 +32,7 @@ use versions::Version;
 pub use git_hash::ObjectId;
 pub use git_protocol::fetch::Ref;
 
-use super::{packwriter::PackWriter, remote_git_version, transport};
+use super::{ensure_object_format, packwriter::PackWriter, remote_git_version, transport};
 
 // Work around `git-upload-pack` not handling namespaces properly,
 //
@@ -138,6 +138,8 @@ impl<P: PackWriter> DelegateBlocking for Fetch<P, P::Output> {
         _: &mut Vec<(&str, Option<&str>)>,
         _: &[Ref],
     ) -> io::Result<Action> {
+        ensure_object_format(caps)?;
+
         if !self.opt.want_refs.is_empty() && !remote_supports_ref_in_want(caps) {
             return Err(io::Error::new(
                 io::ErrorKind::Unsupported,
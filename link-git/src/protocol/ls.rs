@@ -17,7 +17,43 @@ use versions::Version;
 
 pub use git_protocol::fetch::Ref;
 
-use super::{remote_git_version, transport};
+use super::{ensure_object_format, remote_git_version, transport, ObjectId};
+
+/// Name of the [extra parameter][`Options::extra_params`] carrying the
+/// [`digest`] of the refs the client has seen.
+pub(crate) const HAVE_DIGEST: &str = "have-digest";
+
+/// Name of the pseudo-ref the server advertises instead of the actual refs,
+/// if they match the [`digest`] sent by the client.
+pub(crate) const UNCHANGED: &str = "unchanged";
+
+/// Compute the digest of a ref advertisement.
+///
+/// The digest covers the names and targets of `refs`, regardless of their
+/// order. Symbolic refs are included with the object they point to.
+pub fn digest(refs: &[Ref]) -> ObjectId {
+    digest_of(refs.iter().map(|r| {
+        let (path, object, _) = r.unpack();
+        (path.clone(), object.to_owned())
+    }))
+}
+
+pub(crate) fn digest_of<I>(refs: I) -> ObjectId
+where
+    I: IntoIterator<Item = (BString, ObjectId)>,
+{
+    let mut refs = refs.into_iter().collect::<Vec<_>>();
+    refs.sort();
+
+    let mut hasher = git_features::hash::Sha1::default();
+    for (path, object) in refs {
+        hasher.update(object.to_string().as_bytes());
+        hasher.update(b" ");
+        hasher.update(path.as_slice());
+        hasher.update(b"\n");
+    }
+    ObjectId::from_20_bytes(&hasher.digest())
+}
 
 // Work around `git-upload-pack` not handling namespaces properly
 //
@@ -79,6 +115,7 @@ impl DelegateBlocking for LsRefs {
         args: &mut Vec<BString>,
         _: &mut Vec<(&str, Option<&str>)>,
     ) -> io::Result<LsRefsAction> {
+        ensure_object_format(caps)?;
         let must_namespace = must_namespace(caps);
         for prefix in &self.opt.ref_prefixes {
             let mut arg = BString::from("ref-prefix ");
@@ -127,6 +164,35 @@ impl Delegate for LsRefs {
     }
 }
 
+/// Like [`ls_refs`], but ask the server to not advertise any refs if the
+/// [`digest`] of the advertisement would be `have`.
+///
+/// Returns `None` if the server reported the refs to be unchanged. Servers not
+/// supporting this send the full advertisement, as if [`ls_refs`] was called.
+pub async fn ls_refs_unless<R, W>(
+    mut opt: Options,
+    have: ObjectId,
+    recv: R,
+    send: W,
+) -> io::Result<Option<Vec<Ref>>>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    opt.extra_params
+        .push((HAVE_DIGEST.to_owned(), Some(have.to_string())));
+    let refs = ls_refs(opt, recv, send).await?;
+    let unchanged = match refs.as_slice() {
+        [r] => {
+            let (path, object, _) = r.unpack();
+            path.as_slice() == UNCHANGED.as_bytes() && object.to_owned() == have
+        },
+        _ => false,
+    };
+
+    Ok(if unchanged { None } else { Some(refs) })
+}
+
 pub async fn ls_refs<R, W>(opt: Options, recv: R, send: W) -> io::Result<Vec<Ref>>
 where
     R: AsyncRead + Unpin,
@@ -0,0 +1,145 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use futures_lite::io::{AsyncBufRead, AsyncRead};
+
+/// Progress of receiving a packfile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Transfer {
+    /// The number of bytes received so far.
+    pub received_bytes: u64,
+    /// The number of objects in the packfile, once its header was received.
+    pub total_objects: Option<usize>,
+    /// The number of objects indexed so far, if the indexer reports it.
+    pub indexed_objects: Option<usize>,
+}
+
+/// Callback receiving [`Transfer`] progress.
+pub type OnProgress = Arc<dyn Fn(Transfer) + Send + Sync>;
+
+/// Length of the packfile header: signature, version and number of objects.
+const HEADER_LEN: usize = 12;
+
+/// Wraps the packfile stream consumed by an indexer, reporting [`Transfer`]
+/// progress as the data is consumed.
+///
+/// Once `stop` is set, all further reads return an error, so the indexer is
+/// aborted even if it doesn't check `stop` by itself.
+pub struct Metered<R> {
+    inner: R,
+    stop: Arc<AtomicBool>,
+    on_progress: Option<OnProgress>,
+    header: [u8; HEADER_LEN],
+    transfer: Transfer,
+}
+
+impl<R> Metered<R> {
+    pub fn new(inner: R, stop: Arc<AtomicBool>, on_progress: Option<OnProgress>) -> Self {
+        Self {
+            inner,
+            stop,
+            on_progress,
+            header: [0; HEADER_LEN],
+            transfer: Transfer::default(),
+        }
+    }
+
+    pub fn transfer(&self) -> Transfer {
+        self.transfer
+    }
+
+    fn guard_cancelled(&self) -> io::Result<()> {
+        if self.stop.load(Ordering::Acquire) {
+            Err(io::Error::new(io::ErrorKind::Other, "cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn advance(&mut self, amt: usize) {
+        if amt == 0 {
+            return;
+        }
+
+        self.transfer.received_bytes += amt as u64;
+        if self.transfer.total_objects.is_none()
+            && self.transfer.received_bytes >= HEADER_LEN as u64
+            && &self.header[..4] == b"PACK"
+        {
+            let mut count = [0; 4];
+            count.copy_from_slice(&self.header[8..]);
+            self.transfer.total_objects = Some(u32::from_be_bytes(count) as usize);
+        }
+        if let Some(f) = &self.on_progress {
+            f(self.transfer)
+        }
+    }
+}
+
+/// Copy the part of `buf` which belongs to the packfile header, given that
+/// `received` bytes were consumed before `buf`.
+fn capture(header: &mut [u8; HEADER_LEN], received: u64, buf: &[u8]) {
+    if let Some(rest) = header.get_mut(received as usize..) {
+        let n = rest.len().min(buf.len());
+        rest[..n].copy_from_slice(&buf[..n]);
+    }
+}
+
+impl<R> AsyncRead for Metered<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        self.guard_cancelled()?;
+
+        let this = self.get_mut();
+        Pin::new(&mut this.inner).poll_read(cx, buf).map(|ready| {
+            if let Ok(siz) = ready {
+                capture(&mut this.header, this.transfer.received_bytes, &buf[..siz]);
+                this.advance(siz);
+            }
+
+            ready
+        })
+    }
+}
+
+impl<R> AsyncBufRead for Metered<R>
+where
+    R: AsyncBufRead + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<&[u8], io::Error>> {
+        self.guard_cancelled()?;
+
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_fill_buf(cx) {
+            Poll::Ready(Ok(buf)) => {
+                capture(&mut this.header, this.transfer.received_bytes, buf);
+                Poll::Ready(Ok(buf))
+            },
+            other => other,
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        Pin::new(&mut this.inner).consume(amt);
+        this.advance(amt)
+    }
+}
@@ -17,7 +17,10 @@ use git_features::progress::Progress;
 use git_hash::ObjectId;
 use git_odb::{self as odb, pack};
 
-use super::take::TryTake;
+use super::{
+    meter::{Metered, OnProgress, Transfer},
+    take::TryTake,
+};
 
 #[cfg(feature = "git2")]
 pub use libgit::Libgit;
@@ -60,6 +63,8 @@ impl Default for Options {
 
 #[cfg(feature = "git2")]
 pub mod libgit {
+    use std::cell::Cell;
+
     use super::*;
 
     #[derive(Clone, Copy, Debug)]
@@ -69,8 +74,8 @@ pub mod libgit {
         pub deltas: usize,
     }
 
-    impl From<git2::Progress<'_>> for PackReceived {
-        fn from(p: git2::Progress<'_>) -> Self {
+    impl From<&git2::Progress<'_>> for PackReceived {
+        fn from(p: &git2::Progress<'_>) -> Self {
             Self {
                 objects: p.indexed_objects(),
                 local_objects: p.local_objects(),
@@ -79,15 +84,46 @@ pub mod libgit {
         }
     }
 
+    impl From<&git2::Progress<'_>> for Transfer {
+        fn from(p: &git2::Progress<'_>) -> Self {
+            Self {
+                received_bytes: p.received_bytes() as u64,
+                total_objects: Some(p.total_objects()),
+                indexed_objects: Some(p.indexed_objects()),
+            }
+        }
+    }
+
+    /// [`PackWriter`] using `libgit2`'s indexer.
+    ///
+    /// The packfile is indexed while it is being received. Progress is
+    /// reported per indexed object, and setting `stop` aborts the indexer.
     pub struct Libgit {
         opt: Options,
         repo: git2::Repository,
         stop: Arc<AtomicBool>,
+        on_progress: Option<OnProgress>,
     }
 
     impl Libgit {
         pub fn new(opt: Options, repo: git2::Repository, stop: Arc<AtomicBool>) -> Self {
-            Self { opt, repo, stop }
+            Self {
+                opt,
+                repo,
+                stop,
+                on_progress: None,
+            }
+        }
+
+        /// Report [`Transfer`] progress to `f` while the packfile is received.
+        pub fn with_progress<F>(self, f: F) -> Self
+        where
+            F: Fn(Transfer) + Send + Sync + 'static,
+        {
+            Self {
+                on_progress: Some(Arc::new(f)),
+                ..self
+            }
         }
 
         fn guard_cancelled(&self) -> io::Result<()> {
@@ -107,30 +143,34 @@ pub mod libgit {
             pack: impl AsyncBufRead + Unpin,
             _: impl Progress,
         ) -> io::Result<Self::Output> {
-            let mut out = None;
+            let out = Cell::new(None);
 
             let odb = self.repo.odb().map_err(io_error)?;
             let mut writer = odb.packwriter().map_err(io_error)?;
+            writer.progress(|p| {
+                if let Some(f) = &self.on_progress {
+                    f(Transfer::from(&p))
+                }
+                out.set(Some(PackReceived::from(&p)));
+                !self.stop.load(Ordering::Acquire)
+            });
 
             self.guard_cancelled()?;
             io::copy(
-                &mut BlockOn::new(TryTake::new(pack, self.opt.max_pack_bytes)),
+                &mut BlockOn::new(Metered::new(
+                    TryTake::new(pack, self.opt.max_pack_bytes),
+                    Arc::clone(&self.stop),
+                    None,
+                )),
                 &mut writer,
             )?;
 
             self.guard_cancelled()?;
-            writer
-                .progress(|p| {
-                    out = Some(p.to_owned());
-                    true
-                })
-                .commit()
-                .map(|_| ())
-                .map_err(io_error)?;
+            writer.commit().map(|_| ()).map_err(io_error)?;
             // Convince borrowchk that `out` can not possibly be borrowed anymore
             drop(writer);
 
-            Ok(out.map(Into::into))
+            Ok(out.get())
         }
     }
 
@@ -200,11 +240,16 @@ impl BuildThickener for StandardThickener {
 ///
 /// Writes the packfile into the given output directory, along with a v2
 /// index. The packfile is verified.
+///
+/// Objects are indexed as they are received, and the packfile is written to
+/// its final location directly, so no second copy is ever held on disk.
+/// Setting `stop` aborts at the next read from the stream.
 pub struct Standard<F> {
     git_dir: PathBuf,
     opt: Options,
     thick: F,
     stop: Arc<AtomicBool>,
+    on_progress: Option<OnProgress>,
 }
 
 impl<F> Standard<F> {
@@ -214,8 +259,20 @@ impl<F> Standard<F> {
             opt,
             thick,
             stop,
+            on_progress: None,
         }
     }
+
+    /// Report [`Transfer`] progress to `f` while the packfile is received.
+    ///
+    /// [`Transfer::indexed_objects`] is not known to this writer.
+    pub fn with_progress<G>(mut self, f: G) -> Self
+    where
+        G: Fn(Transfer) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Arc::new(f));
+        self
+    }
 }
 
 impl<F> Drop for Standard<F> {
@@ -244,7 +301,11 @@ impl<F: BuildThickener> PackWriter for Standard<F> {
             .build_thickener()
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
         Bundle::write_to_directory(
-            BlockOn::new(TryTake::new(pack, self.opt.max_pack_bytes)),
+            BlockOn::new(Metered::new(
+                TryTake::new(pack, self.opt.max_pack_bytes),
+                Arc::clone(&self.stop),
+                self.on_progress.clone(),
+            )),
             Some(self.git_dir.join("objects").join("pack")),
             prog,
             &self.stop,
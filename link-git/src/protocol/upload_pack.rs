@@ -6,13 +6,27 @@
 use std::{future::Future, io, path::Path, process::ExitStatus, str::FromStr};
 
 use async_process::{Command, Stdio};
-use futures_lite::io::{copy, AsyncBufReadExt as _, AsyncRead, AsyncWrite, BufReader};
+use futures_lite::io::{
+    copy,
+    sink,
+    AsyncBufReadExt as _,
+    AsyncRead,
+    AsyncWrite,
+    AsyncWriteExt as _,
+    BufReader,
+};
 use futures_util::try_join;
 use git_packetline::{self as packetline, PacketLineRef};
 use once_cell::sync::Lazy;
 use versions::Version;
 
+use super::{ls, ObjectId};
+
 mod legacy;
+mod pushback;
+use pushback::Checked;
+
+pub use pushback::Limits;
 
 #[derive(Debug, PartialEq)]
 pub struct Header {
@@ -62,14 +76,54 @@ impl FromStr for Header {
     }
 }
 
+/// Serve `git upload-pack`, imposing the default [`Limits`] on `ls-refs`.
 pub async fn upload_pack<R, W>(
     git_dir: impl AsRef<Path>,
     recv: R,
+    send: W,
+) -> io::Result<(Header, impl Future<Output = io::Result<ExitStatus>>)>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    upload_pack_with(git_dir, Limits::default(), recv, send).await
+}
+
+/// Serve `git upload-pack`, imposing `limits` on `ls-refs`.
+///
+/// The limits apply to protocol v2 only, legacy clients are served as
+/// before.
+pub async fn upload_pack_with<R, W>(
+    git_dir: impl AsRef<Path>,
+    limits: Limits,
+    recv: R,
+    send: W,
+) -> io::Result<(Header, impl Future<Output = io::Result<ExitStatus>>)>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    upload_pack_guarded(git_dir, limits, |_| Ok(()), recv, send).await
+}
+
+/// Serve `git upload-pack`, imposing `limits` on `ls-refs`, if `guard`
+/// admits the requested namespace.
+///
+/// `guard` is called with the namespace before anything is sent to the
+/// client. If it returns an error, the client is sent an `ERR` packet
+/// carrying the error message, and the returned future fails with
+/// [`io::ErrorKind::PermissionDenied`].
+pub async fn upload_pack_guarded<R, W, G>(
+    git_dir: impl AsRef<Path>,
+    limits: Limits,
+    guard: G,
+    recv: R,
     mut send: W,
 ) -> io::Result<(Header, impl Future<Output = io::Result<ExitStatus>>)>
 where
     R: AsyncRead + Unpin,
     W: AsyncWrite + Unpin,
+    G: FnOnce(&str) -> Result<(), String>,
 {
     let mut recv = BufReader::new(recv);
     let header: Header = match recv.fill_buf().await?.get(0) {
@@ -129,14 +183,45 @@ where
         .unwrap_or(0);
     // legacy
     let stateless_ls = header.extra.iter().any(|(k, _)| k == "ls");
+    let have_digest = header.extra.iter().find_map(|kv| match kv {
+        (ref k, Some(v)) if k == ls::HAVE_DIGEST => ObjectId::from_hex(v.as_bytes()).ok(),
+        _ => None,
+    });
 
     let fut = async move {
+        if let Err(reason) = guard(&namespace) {
+            pushback::reject(&mut send, &reason).await?;
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, reason));
+        }
+
+        let mut replay = Vec::new();
         if protocol_version < 2 {
             if stateless_ls {
                 return legacy::advertise_refs(git_dir, &namespace, recv, send).await;
             }
         } else {
             advertise_capabilities(&mut send).await?;
+            let checked = pushback::ls_refs(
+                git_dir.as_ref().to_path_buf(),
+                &namespace,
+                limits,
+                have_digest,
+                &mut recv,
+            )
+            .await?;
+            match checked {
+                Checked::Replay(request) => replay = request,
+                Checked::Unchanged(digest) => {
+                    pushback::unchanged(&mut send, digest).await?;
+                    // Drive the read stream to completion, as `git` would
+                    copy(&mut recv, &mut sink()).await?;
+                    return Ok(success());
+                },
+                Checked::Reject(reason) => {
+                    pushback::reject(&mut send, &reason).await?;
+                    return Err(io::Error::new(io::ErrorKind::PermissionDenied, reason));
+                },
+            }
         }
 
         let mut child = {
@@ -172,12 +257,21 @@ where
         let mut stdin = child.stdin.take().unwrap();
         let mut stdout = child.stdout.take().unwrap();
 
-        try_join!(
-            copy(&mut recv, &mut stdin),
-            copy(&mut stdout, &mut send),
-            child.status(),
-        )
-        .map(|(_, _, status)| status)
+        let request = async move {
+            if protocol_version < 2 {
+                copy(&mut recv, &mut stdin).await?;
+            } else {
+                // The connection is stateless, so only the command checked by
+                // `pushback::ls_refs` is served. Anything the client sends
+                // after it is not passed on to `git`, and closing `stdin`
+                // ends the request.
+                stdin.write_all(&replay).await?;
+            }
+            Ok::<_, io::Error>(())
+        };
+
+        try_join!(request, copy(&mut stdout, &mut send), child.status())
+            .map(|(_, _, status)| status)
     };
 
     Ok((header, fut))
@@ -208,6 +302,16 @@ where
     Ok(())
 }
 
+/// The exit status of a request served without spawning `git`.
+fn success() -> ExitStatus {
+    #[cfg(unix)]
+    use std::os::unix::process::ExitStatusExt as _;
+    #[cfg(windows)]
+    use std::os::windows::process::ExitStatusExt as _;
+
+    ExitStatus::from_raw(0)
+}
+
 fn git_version() -> io::Result<Version> {
     let out = std::process::Command::new("git")
         .arg("--version")
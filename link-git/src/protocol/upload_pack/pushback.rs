@@ -0,0 +1,231 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{io, path::PathBuf};
+
+use bstr::{BString, ByteSlice as _};
+use futures_lite::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+use git_packetline as packetline;
+use git_ref::{
+    file::{Store as Refdb, WriteReflog},
+    Target,
+};
+
+use super::invalid_data;
+use crate::protocol::{ls, ObjectId};
+
+/// Limits imposed on `ls-refs` requests, so that serving namespaces with
+/// very large numbers of refs stays cheap.
+///
+/// Requests exceeding the limits are answered with an `ERR` packet, telling
+/// the client to narrow down its request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum number of `ref-prefix` arguments of a single request.
+    pub max_ref_prefixes: usize,
+    /// The maximum number of refs a namespace may have for an `ls-refs`
+    /// request without any `ref-prefix` to be served.
+    pub max_unprefixed_refs: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_ref_prefixes: 4096,
+            max_unprefixed_refs: 10_000,
+        }
+    }
+}
+
+impl Limits {
+    /// No limits at all.
+    pub fn none() -> Self {
+        Self {
+            max_ref_prefixes: usize::MAX,
+            max_unprefixed_refs: usize::MAX,
+        }
+    }
+}
+
+/// Maximum length of a pkt-line, including the length prefix.
+const MAX_PKT_LEN: usize = 65520;
+
+/// Maximum length of the first command of a request.
+const MAX_REQUEST_LEN: usize = 64 * MAX_PKT_LEN;
+
+/// The outcome of [`ls_refs`].
+pub(super) enum Checked {
+    /// Replay the raw bytes read to `git upload-pack`.
+    Replay(Vec<u8>),
+    /// The refs requested match the digest the client has, answer with
+    /// [`unchanged`].
+    Unchanged(ObjectId),
+    /// Refuse the request for the given reason.
+    Reject(String),
+}
+
+/// Read the first command of a protocol v2 request, and check it against
+/// `limits` if it is `ls-refs`.
+///
+/// Connections are stateless, so this is the only command served. Anything
+/// the client sends after it is discarded.
+///
+/// Only the prefix count of the request is retained, and the refs of the
+/// namespace are counted without being collected, so memory use is bounded
+/// regardless of the size of the namespace.
+///
+/// If the client sent the `have` digest of the advertisement, and it matches
+/// the refs the request asks for, the request is not replayed.
+pub(super) async fn ls_refs<R>(
+    git_dir: PathBuf,
+    namespace: &str,
+    limits: Limits,
+    have: Option<ObjectId>,
+    mut recv: R,
+) -> io::Result<Checked>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = Vec::new();
+    let mut is_ls_refs = false;
+    let mut in_args = false;
+    let mut prefixes = Vec::new();
+    loop {
+        let mut hex = [0; 4];
+        match recv.read_exact(&mut hex).await {
+            // The client hung up without sending a command, leave it to git
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof && buf.is_empty() => {
+                return Ok(Checked::Replay(buf))
+            },
+            res => res?,
+        }
+        if buf.len() > MAX_REQUEST_LEN {
+            return Ok(Checked::Reject("request too large".to_owned()));
+        }
+        buf.extend_from_slice(&hex);
+        let len = std::str::from_utf8(&hex)
+            .ok()
+            .and_then(|s| usize::from_str_radix(s, 16).ok())
+            .ok_or_else(|| invalid_data("invalid pkt-line length"))?;
+        match len {
+            // flush, or response-end: end of the command
+            0 | 2 => break,
+            // delim: arguments follow
+            1 => in_args = true,
+            n if !(5..=MAX_PKT_LEN).contains(&n) => return Err(invalid_data("invalid pkt-line")),
+            n => {
+                let start = buf.len();
+                buf.resize(start + n - 4, 0);
+                recv.read_exact(&mut buf[start..]).await?;
+                let line = &buf[start..];
+                let line = line.strip_suffix(b"\n").unwrap_or(line);
+                if !in_args {
+                    is_ls_refs |= line == b"command=ls-refs";
+                } else if is_ls_refs {
+                    if let Some(prefix) = line.strip_prefix(b"ref-prefix ") {
+                        if prefixes.len() >= limits.max_ref_prefixes {
+                            return Ok(Checked::Reject(format!(
+                                "too many ref-prefix arguments, at most {} are allowed",
+                                limits.max_ref_prefixes
+                            )));
+                        }
+                        prefixes.push(BString::from(prefix));
+                    }
+                }
+            },
+        }
+    }
+
+    if is_ls_refs && prefixes.is_empty() && limits.max_unprefixed_refs < usize::MAX {
+        let max = limits.max_unprefixed_refs;
+        let git_dir = git_dir.clone();
+        let prefix = PathBuf::from("refs")
+            .join("namespaces")
+            .join(namespace)
+            .join("refs");
+        let count = blocking::unblock(move || -> io::Result<usize> {
+            let refdb = Refdb::at(git_dir, WriteReflog::Disable);
+            let packed = refdb
+                .packed_buffer()
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            let count = refdb
+                .iter_prefixed(packed.as_ref(), prefix)?
+                .take(max + 1)
+                .count();
+            Ok(count)
+        })
+        .await?;
+        if count > max {
+            return Ok(Checked::Reject(format!(
+                "namespace has more than {} refs, ref-prefix arguments are required",
+                max
+            )));
+        }
+    }
+
+    if let Some(have) = have.filter(|_| is_ls_refs) {
+        let namespace = namespace.to_owned();
+        let current = blocking::unblock(move || digest(git_dir, &namespace, &prefixes)).await?;
+        if current == Some(have) {
+            return Ok(Checked::Unchanged(have));
+        }
+    }
+
+    Ok(Checked::Replay(buf))
+}
+
+/// Compute the [`ls::digest`] of the refs of `namespace` matching `prefixes`,
+/// as `git upload-pack` would advertise them.
+///
+/// Returns `None` if the digest can not be determined without resolving
+/// symbolic refs, in which case the advertisement is sent in full.
+fn digest(git_dir: PathBuf, namespace: &str, prefixes: &[BString]) -> io::Result<Option<ObjectId>> {
+    let strip = format!("refs/namespaces/{}/", namespace);
+    let refdb = Refdb::at(git_dir, WriteReflog::Disable);
+    let packed = refdb
+        .packed_buffer()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    let mut refs = Vec::new();
+    for r in refdb.iter_prefixed(packed.as_ref(), PathBuf::from(&strip).join("refs"))? {
+        let r = r.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let name = match r.name.as_bstr().strip_prefix(strip.as_bytes()) {
+            Some(name) => BString::from(name),
+            None => continue,
+        };
+        if !prefixes.is_empty() && !prefixes.iter().any(|p| name.starts_with(p.as_slice())) {
+            continue;
+        }
+        match r.target {
+            Target::Peeled(oid) => refs.push((name, oid)),
+            Target::Symbolic(_) => return Ok(None),
+        }
+    }
+
+    Ok(Some(ls::digest_of(refs)))
+}
+
+/// Tell the client that the refs it asked for match its digest, by
+/// advertising the [`ls::UNCHANGED`] pseudo-ref pointing to the digest.
+pub(super) async fn unchanged<W>(mut send: W, digest: ObjectId) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let line = format!("{} {}", digest, ls::UNCHANGED);
+    packetline::encode::text_to_write(line.as_bytes(), &mut send).await?;
+    packetline::encode::flush_to_write(&mut send).await?;
+    send.flush().await
+}
+
+/// Refuse the request with an `ERR` packet.
+pub(super) async fn reject<W>(mut send: W, reason: &str) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let line = format!("ERR {}\n", reason);
+    send.write_all(format!("{:04x}{}", line.len() + 4, line).as_bytes())
+        .await?;
+    send.flush().await
+}
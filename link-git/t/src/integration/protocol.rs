@@ -7,12 +7,25 @@ use std::{
     collections::BTreeSet,
     io,
     path::Path,
-    sync::{atomic::AtomicBool, Arc},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+        Mutex,
+    },
 };
 
 use bstr::ByteSlice as _;
-use futures::{AsyncReadExt as _, TryFutureExt as _};
-use link_git::protocol::{fetch, ls, packwriter, upload_pack, ObjectId, PackWriter, Ref};
+use futures::{AsyncReadExt as _, AsyncWriteExt as _, TryFutureExt as _};
+use link_git::protocol::{
+    fetch,
+    ls,
+    meter::Transfer,
+    packwriter,
+    upload_pack,
+    ObjectId,
+    PackWriter,
+    Ref,
+};
 use tempfile::{tempdir, TempDir};
 
 fn upstream() -> TempDir {
@@ -94,11 +107,40 @@ fn collect_history(repo: &git2::Repository, tip: &str) -> Result<Vec<git2::Oid>,
 }
 
 fn run_ls_refs<R: AsRef<Path>>(remote: R, opt: ls::Options) -> io::Result<Vec<Ref>> {
+    run_ls_refs_with(remote, upload_pack::Limits::default(), opt)
+}
+
+fn run_ls_refs_with<R: AsRef<Path>>(
+    remote: R,
+    limits: upload_pack::Limits,
+    opt: ls::Options,
+) -> io::Result<Vec<Ref>> {
     let (client, server) = futures_ringbuf::Endpoint::pair(256, 256);
     let client = async move {
         let (recv, send) = client.split();
         ls::ls_refs(opt, recv, send).await
     };
+    let server = {
+        let (recv, send) = server.split();
+        upload_pack::upload_pack_with(&remote, limits, recv, send).and_then(|(_hdr, run)| run)
+    };
+
+    let (client_out, server_out) =
+        futures::executor::block_on(futures::future::try_join(client, server))?;
+    assert!(server_out.success());
+    Ok(client_out)
+}
+
+fn run_ls_refs_unless<R: AsRef<Path>>(
+    remote: R,
+    opt: ls::Options,
+    have: ObjectId,
+) -> io::Result<Option<Vec<Ref>>> {
+    let (client, server) = futures_ringbuf::Endpoint::pair(256, 256);
+    let client = async move {
+        let (recv, send) = client.split();
+        ls::ls_refs_unless(opt, have, recv, send).await
+    };
     let server = {
         let (recv, send) = server.split();
         upload_pack::upload_pack(&remote, recv, send).and_then(|(_hdr, run)| run)
@@ -177,6 +219,135 @@ fn smoke() {
     assert!(out.pack.is_some());
 }
 
+#[test]
+fn ls_refs_pushback() {
+    let remote = upstream();
+    let limits = upload_pack::Limits {
+        max_ref_prefixes: 2,
+        max_unprefixed_refs: 2,
+    };
+    let ls = |ref_prefixes: Vec<&str>| {
+        run_ls_refs_with(
+            &remote,
+            limits,
+            ls::Options {
+                repo: "foo".into(),
+                extra_params: vec![],
+                ref_prefixes: ref_prefixes.into_iter().map(Into::into).collect(),
+            },
+        )
+    };
+
+    // The namespace has three refs
+    assert!(ls(vec![]).is_err());
+    assert!(ls(vec!["refs/heads/", "refs/pulls/", "refs/tags/"]).is_err());
+    assert_eq!(ls(vec!["refs/heads/", "refs/pulls/"]).unwrap().len(), 3);
+}
+
+#[test]
+fn ls_refs_pushback_single_command() {
+    let remote = upstream();
+    let limits = upload_pack::Limits {
+        max_ref_prefixes: 2,
+        max_unprefixed_refs: 2,
+    };
+    let pkt = |line: &str| format!("{:04x}{}", line.len() + 4, line);
+
+    let (client, server) = futures_ringbuf::Endpoint::pair(256, 256);
+    let client = async move {
+        let (mut recv, mut send) = client.split();
+        // A permitted `ls-refs`, followed by one exceeding the limits
+        let request = [
+            pkt("git-upload-pack foo\0\0version=2\0"),
+            pkt("command=ls-refs\n"),
+            "0001".to_owned(),
+            pkt("ref-prefix refs/heads/\n"),
+            "0000".to_owned(),
+            pkt("command=ls-refs\n"),
+            "0001".to_owned(),
+            "0000".to_owned(),
+        ]
+        .concat();
+        send.write_all(request.as_bytes()).await?;
+        send.flush().await?;
+
+        let _capabilities = read_section(&mut recv).await?;
+        read_section(&mut recv).await
+    };
+    let server = {
+        let (recv, send) = server.split();
+        upload_pack::upload_pack_with(&remote, limits, recv, send).and_then(|(_hdr, run)| run)
+    };
+
+    let (refs, status) =
+        futures::executor::block_on(futures::future::try_join(client, server)).unwrap();
+    assert!(status.success());
+    assert_eq!(refs.len(), 2);
+    assert!(refs.iter().all(|r| r.contains(" refs/heads/")));
+}
+
+async fn read_section<R>(mut recv: R) -> io::Result<Vec<String>>
+where
+    R: futures::AsyncRead + Unpin,
+{
+    let mut lines = Vec::new();
+    loop {
+        let mut hex = [0; 4];
+        recv.read_exact(&mut hex).await?;
+        let len = std::str::from_utf8(&hex)
+            .ok()
+            .and_then(|s| usize::from_str_radix(s, 16).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid pkt-line"))?;
+        if len == 0 {
+            return Ok(lines);
+        }
+        let mut line = vec![0; len - 4];
+        recv.read_exact(&mut line).await?;
+        lines.push(String::from_utf8_lossy(&line).into_owned());
+    }
+}
+
+#[test]
+fn ls_refs_unchanged() {
+    let remote = upstream();
+    let opt = |ref_prefixes: Vec<&str>| ls::Options {
+        repo: "foo".into(),
+        extra_params: vec![],
+        ref_prefixes: ref_prefixes.into_iter().map(Into::into).collect(),
+    };
+
+    let refs = run_ls_refs(&remote, opt(vec!["refs/heads/"])).unwrap();
+    let have = ls::digest(&refs);
+    assert_eq!(
+        run_ls_refs_unless(&remote, opt(vec!["refs/heads/"]), have).unwrap(),
+        None
+    );
+
+    // Different prefixes, different refs
+    let all = run_ls_refs_unless(&remote, opt(vec!["refs/heads/", "refs/pulls/"]), have)
+        .unwrap()
+        .expect("refs/pulls/ was not seen before");
+    assert_eq!(all.len(), 3);
+
+    // Update a ref
+    {
+        let repo = git2::Repository::open(&remote).unwrap();
+        let next = repo
+            .refname_to_id("refs/namespaces/foo/refs/heads/next")
+            .unwrap();
+        repo.reference("refs/namespaces/foo/refs/heads/main", next, true, "ff")
+            .unwrap();
+    }
+    let updated = run_ls_refs_unless(&remote, opt(vec!["refs/heads/"]), have)
+        .unwrap()
+        .expect("refs/heads/main was updated");
+    assert_ne!(ls::digest(&updated), have);
+    assert_eq!(
+        run_ls_refs_unless(&remote, opt(vec!["refs/heads/"]), ls::digest(&updated)).unwrap(),
+        None
+    );
+}
+
 #[test]
 fn want_ref() {
     let remote = upstream();
@@ -297,6 +468,82 @@ fn clone_gitoxide() {
     })
 }
 
+#[test]
+fn clone_libgit_reports_progress() {
+    let remote = upstream();
+    let local = tempdir().unwrap();
+    let local_repo = git2::Repository::init(&local).unwrap();
+    let transfers = Arc::new(Mutex::new(Vec::new()));
+
+    clone_with(&remote, &local, {
+        let transfers = Arc::clone(&transfers);
+        move |stop| {
+            packwriter::Libgit::new(packwriter::Options::default(), local_repo, stop)
+                .with_progress(move |t| transfers.lock().unwrap().push(t))
+        }
+    });
+    assert_complete(&transfers.lock().unwrap());
+}
+
+#[test]
+fn clone_gitoxide_reports_progress() {
+    let remote = upstream();
+    let local = tempdir().unwrap();
+    let local_repo = git2::Repository::init(&local).unwrap();
+    let transfers = Arc::new(Mutex::new(Vec::new()));
+
+    clone_with(&remote, &local, {
+        let transfers = Arc::clone(&transfers);
+        move |stop| {
+            packwriter::Standard::new(
+                local_repo.path(),
+                packwriter::Options::default(),
+                packwriter::StandardThickener::new(local_repo.path()),
+                stop,
+            )
+            .with_progress(move |t| transfers.lock().unwrap().push(t))
+        }
+    });
+    assert_complete(&transfers.lock().unwrap());
+}
+
+fn assert_complete(transfers: &[Transfer]) {
+    let last = transfers.last().expect("progress was reported");
+    assert!(last.received_bytes > 0);
+    assert!(matches!(last.total_objects, Some(n) if n > 0));
+    assert!(transfers
+        .windows(2)
+        .all(|w| w[0].received_bytes <= w[1].received_bytes));
+}
+
+#[test]
+fn cancelled_gitoxide() {
+    let remote = upstream();
+    let local = tempdir().unwrap();
+    let local_repo = git2::Repository::init(&local).unwrap();
+
+    let res = run_fetch(
+        &remote,
+        fetch::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            haves: vec![],
+            wants: vec![],
+            want_refs: vec!["refs/heads/main".into()],
+        },
+        move |stop| {
+            stop.store(true, Ordering::Release);
+            packwriter::Standard::new(
+                local_repo.path(),
+                packwriter::Options::default(),
+                packwriter::StandardThickener::new(local_repo.path()),
+                stop,
+            )
+        },
+    );
+    assert!(res.is_err());
+}
+
 fn thin_pack_with<R, L, B, P>(remote: R, local: L, build_pack_writer: B)
 where
     R: AsRef<Path>,
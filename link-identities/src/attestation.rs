@@ -0,0 +1,306 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Attestations binding a personal identity to external identifiers.
+//!
+//! An [`Attestations`] payload extension lists the external identifiers a
+//! person claims to control. Since the extension is part of the identity
+//! document, the claims are signed by the identity's delegations, and
+//! replicated along with it.
+//!
+//! The reverse direction -- the external identifier vouching for the link
+//! identity -- has to be established out-of-band, by inspecting a document
+//! controlled by the external identifier:
+//!
+//! * [`Claim::Dns`]: a `TXT` record of the domain containing
+//!   `radicle-link=<urn>`
+//! * [`Claim::DidWeb`]: a [`did:web`] DID document with an `alsoKnownAs` entry
+//!   of the URN, and a verification method with the public key of one of the
+//!   delegations
+//! * [`Claim::DidKey`]: a [`did:key`] encodes the public key itself, so the
+//!   claim can be checked without further evidence
+//! * [`Claim::Social`]: a [`social::Proof`] published on the service
+//! * [`Claim::X509`]: the DER-encoded certificate with the given SHA-256
+//!   fingerprint, whose subject public key is one of the delegations
+//!
+//! Obtaining the evidence (ie. DNS and HTTPS requests) is left to the caller.
+//! For X.509 certificates, so is validating the chain of trust up to a root
+//! the caller is willing to accept.
+//!
+//! [`did:web`]: https://w3c-ccg.github.io/did-method-web/
+//! [`did:key`]: https://w3c-ccg.github.io/did-method-key/
+
+use std::{collections::BTreeSet, fmt};
+
+use canonical::Cstring;
+use crypto::PublicKey;
+use multihash::Multihash;
+use picky_asn1_x509::{Certificate, PublicKey as SubjectPublicKey};
+use sha2::{Digest as _, Sha256};
+use thiserror::Error;
+use url::Url;
+
+use crate::{
+    payload::HasNamespace,
+    urn::{HasProtocol, Urn},
+};
+
+pub mod social;
+
+lazy_static! {
+    static ref ATTESTATIONS_NAMESPACE_V1: Url =
+        Url::parse("https://radicle.xyz/link/identities/attestations/v1").unwrap();
+}
+
+/// Prefix of the `TXT` record proving control over a domain.
+pub const DNS_TXT_PREFIX: &str = "radicle-link=";
+
+/// Multicodec prefix of an ed25519 public key.
+const ED25519_PUB: [u8; 2] = [0xed, 0x01];
+
+pub mod error {
+    use super::*;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Verify {
+        #[error("malformed DID `{0}`")]
+        MalformedDid(String),
+
+        #[error("`{0}` does not refer to any of the delegation keys")]
+        UnknownKey(String),
+
+        #[error("no `TXT` record of `{domain}` refers to {urn}")]
+        MissingTxtRecord { domain: String, urn: String },
+
+        #[error("DID document is for `{actual}`, expected `{expected}`")]
+        DocumentId { expected: String, actual: String },
+
+        #[error("DID document does not list {0} in `alsoKnownAs`")]
+        NotAlsoKnownAs(String),
+
+        #[error("DID document does not contain any of the delegation keys")]
+        NoDelegationKey,
+
+        #[error("certificate fingerprint is `{actual}`, expected `{expected}`")]
+        Fingerprint { expected: String, actual: String },
+
+        #[error("subject public key of the certificate is not a delegation key")]
+        CertificateKey,
+
+        #[error(transparent)]
+        Der(#[from] picky_asn1_der::Asn1DerError),
+    }
+}
+
+/// Payload extension holding the [`Claim`]s of a person.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Attestations {
+    pub claims: Vec<Claim>,
+}
+
+impl HasNamespace for Attestations {
+    fn namespace() -> &'static Url {
+        &ATTESTATIONS_NAMESPACE_V1
+    }
+}
+
+/// A claim to control an external identifier.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Claim {
+    /// Control over a DNS domain.
+    Dns { domain: Cstring },
+    /// A `did:key` DID.
+    DidKey { did: Cstring },
+    /// A `did:web` DID.
+    DidWeb { did: Cstring },
+    /// An account on some service, proven by a [`social::Proof`] published
+    /// at `proof`.
+    Social {
+        service: Cstring,
+        handle: Cstring,
+        proof: Url,
+    },
+    /// An X.509 certificate, identified by the hex-encoded SHA-256 digest of
+    /// its DER encoding.
+    X509 { fingerprint: Cstring },
+}
+
+impl fmt::Display for Claim {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Dns { domain } => write!(f, "dns:{}", domain),
+            Self::DidKey { did } | Self::DidWeb { did } => write!(f, "{}", did),
+            Self::Social {
+                service, handle, ..
+            } => write!(f, "{}@{}", handle, service),
+            Self::X509 { fingerprint } => write!(f, "x509:{}", fingerprint),
+        }
+    }
+}
+
+/// Encode `key` as a `did:key`.
+pub fn did_key(key: &PublicKey) -> String {
+    format!("did:key:{}", public_key_multibase(key))
+}
+
+/// Encode `key` as a multicodec-prefixed, base58btc-encoded multibase string,
+/// as used by `did:key` and the `publicKeyMultibase` property of DID
+/// documents.
+pub fn public_key_multibase(key: &PublicKey) -> String {
+    let mut bytes = ED25519_PUB.to_vec();
+    bytes.extend_from_slice(key.as_ref());
+    multibase::encode(multibase::Base::Base58Btc, bytes)
+}
+
+/// Decode a `publicKeyMultibase` string as produced by
+/// [`public_key_multibase`].
+pub fn parse_public_key_multibase(s: &str) -> Option<PublicKey> {
+    let (base, bytes) = multibase::decode(s).ok()?;
+    if base != multibase::Base::Base58Btc {
+        return None;
+    }
+    bytes
+        .strip_prefix(&ED25519_PUB)
+        .and_then(PublicKey::from_slice)
+}
+
+/// The [`Url`] at which the DID document of a `did:web` is published.
+pub fn did_web_url(did: &str) -> Result<Url, error::Verify> {
+    let malformed = || error::Verify::MalformedDid(did.to_owned());
+    let id = did.strip_prefix("did:web:").ok_or_else(malformed)?;
+    let mut parts = id.split(':');
+    let host = parts
+        .next()
+        .filter(|host| !host.is_empty())
+        .ok_or_else(malformed)?
+        .replace("%3A", ":");
+    let mut url = Url::parse(&format!("https://{}/", host)).map_err(|_| malformed())?;
+    {
+        let mut segments = url.path_segments_mut().map_err(|_| malformed())?;
+        segments.pop_if_empty();
+        let path = parts.collect::<Vec<_>>();
+        if path.is_empty() {
+            segments.extend(&[".well-known", "did.json"]);
+        } else {
+            segments.extend(path).push("did.json");
+        }
+    }
+
+    Ok(url)
+}
+
+/// Verify that the `did:key` `did` refers to one of `keys`.
+pub fn verify_did_key(did: &str, keys: &BTreeSet<PublicKey>) -> Result<(), error::Verify> {
+    let key = did
+        .strip_prefix("did:key:")
+        .and_then(parse_public_key_multibase)
+        .ok_or_else(|| error::Verify::MalformedDid(did.to_owned()))?;
+    if keys.contains(&key) {
+        Ok(())
+    } else {
+        Err(error::Verify::UnknownKey(did.to_owned()))
+    }
+}
+
+/// Verify that one of the `TXT` records `txt` of `domain` refers to `urn`.
+pub fn verify_dns<R>(domain: &str, urn: &Urn<R>, txt: &[String]) -> Result<(), error::Verify>
+where
+    R: HasProtocol,
+    for<'a> &'a R: Into<Multihash>,
+{
+    let urn = urn.to_string();
+    txt.iter()
+        .any(|record| record.trim().strip_prefix(DNS_TXT_PREFIX) == Some(urn.as_str()))
+        .then(|| ())
+        .ok_or_else(|| error::Verify::MissingTxtRecord {
+            domain: domain.to_owned(),
+            urn,
+        })
+}
+
+/// Verify that the DID document `doc` of the `did:web` `did` vouches for
+/// `urn`, and lists one of `keys`.
+pub fn verify_did_web<R>(
+    did: &str,
+    urn: &Urn<R>,
+    keys: &BTreeSet<PublicKey>,
+    doc: &serde_json::Value,
+) -> Result<(), error::Verify>
+where
+    R: HasProtocol,
+    for<'a> &'a R: Into<Multihash>,
+{
+    let id = doc.get("id").and_then(|id| id.as_str()).unwrap_or_default();
+    if id != did {
+        return Err(error::Verify::DocumentId {
+            expected: did.to_owned(),
+            actual: id.to_owned(),
+        });
+    }
+
+    let urn = urn.to_string();
+    let aka = doc
+        .get("alsoKnownAs")
+        .and_then(|aka| aka.as_array())
+        .map(|aka| aka.iter().any(|x| x.as_str() == Some(urn.as_str())))
+        .unwrap_or(false);
+    if !aka {
+        return Err(error::Verify::NotAlsoKnownAs(urn));
+    }
+
+    let has_key = doc
+        .get("verificationMethod")
+        .and_then(|vm| vm.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|vm| vm.get("publicKeyMultibase").and_then(|pk| pk.as_str()))
+        .filter_map(parse_public_key_multibase)
+        .any(|key| keys.contains(&key));
+    if has_key {
+        Ok(())
+    } else {
+        Err(error::Verify::NoDelegationKey)
+    }
+}
+
+/// The fingerprint of the DER-encoded certificate `der`, as used by
+/// [`Claim::X509`].
+pub fn x509_fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Verify that the DER-encoded certificate `der` has the given `fingerprint`,
+/// and certifies one of `keys`.
+pub fn verify_x509(
+    fingerprint: &str,
+    keys: &BTreeSet<PublicKey>,
+    der: &[u8],
+) -> Result<(), error::Verify> {
+    let actual = x509_fingerprint(der);
+    if !actual.eq_ignore_ascii_case(fingerprint) {
+        return Err(error::Verify::Fingerprint {
+            expected: fingerprint.to_owned(),
+            actual,
+        });
+    }
+
+    let cert: Certificate = picky_asn1_der::from_bytes(der)?;
+    match &cert
+        .tbs_certificate
+        .subject_public_key_info
+        .subject_public_key
+    {
+        SubjectPublicKey::Ed(point) => PublicKey::from_slice(point.payload_view())
+            .filter(|key| keys.contains(key))
+            .map(|_| ())
+            .ok_or(error::Verify::CertificateKey),
+        _ => Err(error::Verify::CertificateKey),
+    }
+}
@@ -0,0 +1,410 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Social proofs.
+//!
+//! A social proof is a [`Statement`] signed by one of the delegations of a
+//! person, and published somewhere only the owner of an account on some
+//! service can publish to (eg. a gist, or a website). The identity document
+//! refers to the proof via [`super::Claim::Social`], and anyone can verify the
+//! claim by fetching the proof over HTTPS.
+//!
+//! A published proof looks like this:
+//!
+//! ```text
+//! -----BEGIN RADICLE LINK PROOF-----
+//! urn: rad:git:hnrk...
+//! key: did:key:z6Mk...
+//! service: github
+//! handle: alice
+//! signature: hyb...
+//! -----END RADICLE LINK PROOF-----
+//! ```
+//!
+//! Surrounding text is ignored, so the proof can be embedded in a larger
+//! document.
+//!
+//! Proofs are retrieved via [`Fetch`]. With the `https` feature, [`Https`]
+//! fetches them with a timeout, and refuses documents larger than a size cap.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crypto::{PublicKey, Signature, Signer};
+use lru::LruCache;
+use multihash::Multihash;
+use thiserror::Error;
+use url::Url;
+
+use super::{did_key, parse_public_key_multibase};
+use crate::urn::{HasProtocol, Urn};
+
+const BEGIN: &str = "-----BEGIN RADICLE LINK PROOF-----";
+const END: &str = "-----END RADICLE LINK PROOF-----";
+const HEADER: &str = "radicle-link proof v1";
+
+pub mod error {
+    use super::*;
+
+    #[derive(Clone, Debug, Error)]
+    #[non_exhaustive]
+    pub enum Parse {
+        #[error("no proof found")]
+        NotFound,
+
+        #[error("missing field `{0}`")]
+        Missing(&'static str),
+
+        #[error("malformed key `{0}`")]
+        Key(String),
+
+        #[error("malformed signature")]
+        Signature,
+    }
+
+    #[derive(Clone, Debug, Error)]
+    #[non_exhaustive]
+    pub enum Verify {
+        #[error("proofs must be published over https, got `{0}`")]
+        NotHttps(Url),
+
+        #[error("failed to fetch proof from `{url}`: {reason}")]
+        Fetch { url: Url, reason: String },
+
+        #[error(transparent)]
+        Parse(#[from] Parse),
+
+        #[error("proof is for {actual}, expected {expected}")]
+        Mismatch { expected: String, actual: String },
+
+        #[error("proof is not signed by a delegation key")]
+        UnknownKey,
+
+        #[error("invalid signature")]
+        Signature,
+    }
+
+    #[cfg(feature = "https")]
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Https {
+        #[error("only https urls can be fetched, got `{0}`")]
+        NotHttps(String),
+
+        #[error("document exceeds {0} bytes")]
+        TooLarge(u64),
+
+        #[error("document is not valid UTF-8")]
+        Utf8,
+
+        #[error(transparent)]
+        Request(#[from] Box<ureq::Error>),
+
+        #[error(transparent)]
+        Io(#[from] std::io::Error),
+    }
+}
+
+/// The claim that the owner of `key` is `handle` on `service`, and controls
+/// the identity `urn`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Statement {
+    pub urn: String,
+    pub key: PublicKey,
+    pub service: String,
+    pub handle: String,
+}
+
+impl Statement {
+    pub fn new<R>(urn: &Urn<R>, key: PublicKey, service: &str, handle: &str) -> Self
+    where
+        R: HasProtocol,
+        for<'a> &'a R: Into<Multihash>,
+    {
+        Self {
+            urn: urn.to_string(),
+            key,
+            service: service.to_owned(),
+            handle: handle.to_owned(),
+        }
+    }
+
+    /// The bytes the signature is computed over.
+    pub fn message(&self) -> Vec<u8> {
+        format!(
+            "{}\nurn: {}\nkey: {}\nservice: {}\nhandle: {}\n",
+            HEADER,
+            self.urn,
+            did_key(&self.key),
+            self.service,
+            self.handle
+        )
+        .into_bytes()
+    }
+
+    /// Sign the statement with `signer`, which must own [`Statement::key`].
+    pub fn sign<S>(self, signer: &S) -> Result<Proof, S::Error>
+    where
+        S: Signer,
+    {
+        let signature = signer.sign_blocking(&self.message())?;
+        Ok(Proof {
+            statement: self,
+            signature: signature.into(),
+        })
+    }
+}
+
+/// A signed [`Statement`].
+#[derive(Clone, Debug)]
+pub struct Proof {
+    pub statement: Statement,
+    pub signature: Signature,
+}
+
+impl Proof {
+    /// Find and parse the first proof in `text`.
+    pub fn parse(text: &str) -> Result<Self, error::Parse> {
+        let block = text
+            .split_once(BEGIN)
+            .and_then(|(_, rest)| rest.split_once(END))
+            .map(|(block, _)| block)
+            .ok_or(error::Parse::NotFound)?;
+
+        let mut fields = HashMap::new();
+        for line in block.lines() {
+            if let Some((k, v)) = line.split_once(':') {
+                fields.insert(k.trim(), v.trim());
+            }
+        }
+        let field =
+            |name: &'static str| fields.get(name).copied().ok_or(error::Parse::Missing(name));
+
+        let key = field("key")?;
+        let key = key
+            .strip_prefix("did:key:")
+            .and_then(parse_public_key_multibase)
+            .ok_or_else(|| error::Parse::Key(key.to_owned()))?;
+        let signature =
+            serde_json::from_value(serde_json::Value::String(field("signature")?.to_owned()))
+                .map_err(|_| error::Parse::Signature)?;
+
+        Ok(Self {
+            statement: Statement {
+                urn: field("urn")?.to_owned(),
+                key,
+                service: field("service")?.to_owned(),
+                handle: field("handle")?.to_owned(),
+            },
+            signature,
+        })
+    }
+
+    /// Verify that the proof was signed by one of `keys`, and makes the
+    /// `expected` statement (save for the key).
+    pub fn verify(
+        &self,
+        expected: &Statement,
+        keys: &BTreeSet<PublicKey>,
+    ) -> Result<(), error::Verify> {
+        let Statement {
+            urn,
+            service,
+            handle,
+            ..
+        } = &self.statement;
+        if (urn, service, handle) != (&expected.urn, &expected.service, &expected.handle) {
+            return Err(error::Verify::Mismatch {
+                expected: format!(
+                    "{} as {}@{}",
+                    expected.urn, expected.handle, expected.service
+                ),
+                actual: format!("{} as {}@{}", urn, handle, service),
+            });
+        }
+        if !keys.contains(&self.statement.key) {
+            return Err(error::Verify::UnknownKey);
+        }
+        if !self
+            .signature
+            .verify(&self.statement.message(), &self.statement.key)
+        {
+            return Err(error::Verify::Signature);
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Proof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Statement {
+            urn,
+            key,
+            service,
+            handle,
+        } = &self.statement;
+        writeln!(f, "{}", BEGIN)?;
+        writeln!(f, "urn: {}", urn)?;
+        writeln!(f, "key: {}", did_key(key))?;
+        writeln!(f, "service: {}", service)?;
+        writeln!(f, "handle: {}", handle)?;
+        writeln!(f, "signature: {}", self.signature)?;
+        write!(f, "{}", END)
+    }
+}
+
+/// Retrieve the document at a [`Url`].
+pub trait Fetch {
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    fn fetch(&self, url: &Url) -> Result<String, Self::Error>;
+}
+
+/// [`Fetch`] over HTTPS.
+///
+/// Requests time out after a configurable duration, and documents larger
+/// than a size cap are refused without reading them in full. Redirects are
+/// followed, but not to plain HTTP.
+#[cfg(feature = "https")]
+pub struct Https {
+    agent: ureq::Agent,
+    max_size: u64,
+}
+
+#[cfg(feature = "https")]
+impl Https {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+    /// Proofs are small, and usually embedded in small documents.
+    pub const DEFAULT_MAX_SIZE: u64 = 1024 * 1024;
+
+    pub fn new(timeout: Duration, max_size: u64) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+            max_size,
+        }
+    }
+}
+
+#[cfg(feature = "https")]
+impl Default for Https {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_TIMEOUT, Self::DEFAULT_MAX_SIZE)
+    }
+}
+
+#[cfg(feature = "https")]
+impl Fetch for Https {
+    type Error = error::Https;
+
+    fn fetch(&self, url: &Url) -> Result<String, Self::Error> {
+        use std::io::Read as _;
+
+        if url.scheme() != "https" {
+            return Err(error::Https::NotHttps(url.to_string()));
+        }
+        let resp = self
+            .agent
+            .request_url("GET", url)
+            .call()
+            .map_err(Box::new)?;
+        if !resp.get_url().starts_with("https://") {
+            return Err(error::Https::NotHttps(resp.get_url().to_owned()));
+        }
+
+        let mut body = Vec::new();
+        resp.into_reader()
+            .take(self.max_size + 1)
+            .read_to_end(&mut body)?;
+        if body.len() as u64 > self.max_size {
+            return Err(error::Https::TooLarge(self.max_size));
+        }
+        String::from_utf8(body).map_err(|_| error::Https::Utf8)
+    }
+}
+
+/// A verification is only reused for the same proof location, the same
+/// expected statement (in its canonical [`Statement::message`] form), and the
+/// same set of acceptable keys.
+type CacheKey = (Url, Vec<u8>, BTreeSet<PublicKey>);
+
+/// Verifies social proofs, caching the results.
+///
+/// The cache holds a bounded number of results, evicting the least recently
+/// used ones.
+pub struct Verifier<F> {
+    fetch: F,
+    ttl: Duration,
+    cache: Mutex<LruCache<CacheKey, (Instant, Result<(), error::Verify>)>>,
+}
+
+impl<F: Fetch> Verifier<F> {
+    /// The number of results cached by [`Verifier::new`].
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    /// Create a [`Verifier`] which remembers up to
+    /// [`Verifier::DEFAULT_CAPACITY`] results for `ttl`.
+    pub fn new(fetch: F, ttl: Duration) -> Self {
+        Self::with_capacity(fetch, ttl, Self::DEFAULT_CAPACITY)
+    }
+
+    /// Create a [`Verifier`] which remembers up to `capacity` results for
+    /// `ttl`.
+    pub fn with_capacity(fetch: F, ttl: Duration, capacity: usize) -> Self {
+        Self {
+            fetch,
+            ttl,
+            cache: Mutex::new(LruCache::new(capacity.max(1))),
+        }
+    }
+
+    /// Fetch the proof at `url`, and verify that it makes the `expected`
+    /// statement, signed by one of `keys`.
+    pub fn verify(
+        &self,
+        url: &Url,
+        expected: &Statement,
+        keys: &BTreeSet<PublicKey>,
+    ) -> Result<(), error::Verify> {
+        let cache_key = (url.clone(), expected.message(), keys.clone());
+        if let Some((at, res)) = self.cache.lock().unwrap().get(&cache_key) {
+            if at.elapsed() < self.ttl {
+                return res.clone();
+            }
+        }
+
+        let res = self.verify_uncached(url, expected, keys);
+        self.cache
+            .lock()
+            .unwrap()
+            .put(cache_key, (Instant::now(), res.clone()));
+        res
+    }
+
+    /// Forget all cached results.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear()
+    }
+
+    fn verify_uncached(
+        &self,
+        url: &Url,
+        expected: &Statement,
+        keys: &BTreeSet<PublicKey>,
+    ) -> Result<(), error::Verify> {
+        if url.scheme() != "https" {
+            return Err(error::Verify::NotHttps(url.clone()));
+        }
+        let text = self.fetch.fetch(url).map_err(|e| error::Verify::Fetch {
+            url: url.clone(),
+            reason: e.to_string(),
+        })?;
+        Proof::parse(&text)?.verify(expected, keys)
+    }
+}
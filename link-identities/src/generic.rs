@@ -313,15 +313,16 @@ impl<T, R, C> Verifying<Identity<T, R, C>, Untrusted> {
         C: Debug + Display,
     {
         if self.signatures.is_empty() {
-            Err(error::Verify::NoSignatures)
-        } else if !self
-            .signatures
-            .iter()
-            .all(|(pk, sig)| sig.verify(self.revision.as_ref(), pk))
-        {
-            Err(error::Verify::SignatureVerification)
-        } else {
-            Ok(self.coerce())
+            return Err(error::Verify::NoSignatures);
+        }
+
+        let mut batch = crypto::Batch::with_capacity(self.signatures.len());
+        for (pk, sig) in self.signatures.iter() {
+            batch.queue((), pk, sig, self.revision.as_ref());
+        }
+        match batch.verify() {
+            Ok(()) => Ok(self.coerce()),
+            Err(_) => Err(error::Verify::SignatureVerification),
         }
     }
 
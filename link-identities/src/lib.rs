@@ -21,12 +21,14 @@ extern crate radicle_data as data;
 extern crate radicle_git_ext as git_ext;
 extern crate radicle_std_ext as std_ext;
 
+pub mod attestation;
 pub mod delegation;
 pub mod generic;
 pub mod git;
 pub mod payload;
 pub mod relations;
 pub mod sign;
+pub mod subkey;
 
 pub mod urn;
 pub use urn::Urn;
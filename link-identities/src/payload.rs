@@ -27,6 +27,9 @@ use crate::{
     urn::{HasProtocol, Urn},
 };
 
+pub mod extension;
+pub use extension::{Extension, Kind, Registry};
+
 lazy_static! {
     /// Base [`Url`] for [`Person`]
     static ref PERSON_NAMESPACE_BASE: Url =
@@ -121,18 +124,27 @@ impl HasNamespace for Project {
 /// Internal trait which helps deal with future versions
 pub trait Subject: HasNamespace + sealed::Sealed {
     fn namespace_matches(url: &Url) -> bool;
+    fn kind() -> Kind;
 }
 
 impl Subject for Person {
     fn namespace_matches(url: &Url) -> bool {
         url.as_str().starts_with(PERSON_NAMESPACE_BASE.as_str())
     }
+
+    fn kind() -> Kind {
+        Kind::Person
+    }
 }
 
 impl Subject for Project {
     fn namespace_matches(url: &Url) -> bool {
         url.as_str().starts_with(PROJECT_NAMESPACE_BASE.as_str())
     }
+
+    fn kind() -> Kind {
+        Kind::Project
+    }
 }
 
 pub type PersonPayload = Payload<Person>;
@@ -257,6 +269,9 @@ pub enum ExtError {
 
     #[error(transparent)]
     Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Invalid(#[from] extension::error::Invalid),
 }
 
 impl<T> Payload<T>
@@ -325,6 +340,27 @@ where
     pub fn exts(&self) -> impl Iterator<Item = (&Url, &serde_json::Value)> {
         self.ext.iter()
     }
+
+    /// Get the value of the [`Extension`] `E`, checking that it is valid.
+    pub fn extension<E>(&self) -> Result<Option<E>, extension::error::Invalid>
+    where
+        E: Extension,
+    {
+        self.ext
+            .get(E::namespace())
+            .map(|val| extension::check(T::kind(), val))
+            .transpose()
+    }
+
+    /// Set the value of the [`Extension`] `E`, checking that it is valid.
+    pub fn set_extension<E>(&mut self, val: E) -> Result<(), ExtError>
+    where
+        E: Extension,
+    {
+        let json = serde_json::to_value(&val)?;
+        extension::check::<E>(T::kind(), &json)?;
+        self.set_ext(val)
+    }
 }
 
 impl<T> serde::Serialize for Payload<T>
@@ -0,0 +1,212 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Typed payload extensions.
+//!
+//! Any JSON value can be stored in a [`Payload`] under a namespace, which
+//! makes it easy for applications to step on each other's toes: one writes a
+//! value the other fails to parse, and the identity becomes unusable for the
+//! latter. An [`Extension`] is a Rust type owning a (versioned) namespace,
+//! which declares what kinds of identities it may be attached to, and which
+//! invariants its values must uphold.
+//!
+//! A [`Registry`] collects the extensions an application knows about, and
+//! checks that a payload carries only well-formed values for them before it
+//! is signed. [`Registry::builtin`] contains the extensions defined by this
+//! crate.
+
+use std::{collections::BTreeMap, fmt};
+
+use url::Url;
+
+use super::{HasNamespace, Payload, Subject};
+use crate::{attestation::Attestations, subkey::SubKeys};
+
+pub mod error {
+    use thiserror::Error;
+    use url::Url;
+
+    use super::Kind;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Invalid {
+        #[error("malformed value for extension `{namespace}`")]
+        Malformed {
+            namespace: Url,
+            #[source]
+            source: serde_json::Error,
+        },
+
+        #[error("extension `{namespace}` can not be attached to {kind} identities")]
+        Subject { namespace: Url, kind: Kind },
+
+        #[error("invalid value for extension `{namespace}`")]
+        Constraint {
+            namespace: Url,
+            #[source]
+            source: Box<dyn std::error::Error + Send + Sync + 'static>,
+        },
+
+        #[error("unknown extension `{0}`")]
+        Unknown(Url),
+    }
+}
+
+/// The kind of identity a [`Payload`] describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Person,
+    Project,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Person => f.write_str("person"),
+            Self::Project => f.write_str("project"),
+        }
+    }
+}
+
+/// A payload extension with a known Rust representation.
+///
+/// Breaking changes to the representation must be accompanied by a new
+/// [`HasNamespace::namespace`], by convention ending in the version.
+pub trait Extension: HasNamespace + serde::Serialize + serde::de::DeserializeOwned {
+    /// Whether the extension may be attached to identities of kind `kind`.
+    fn applies_to(_kind: Kind) -> bool {
+        true
+    }
+
+    /// Check invariants of the value which are not expressed by its type.
+    fn validate(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        Ok(())
+    }
+}
+
+impl Extension for Attestations {
+    fn applies_to(kind: Kind) -> bool {
+        kind == Kind::Person
+    }
+}
+
+impl Extension for SubKeys {
+    fn applies_to(kind: Kind) -> bool {
+        kind == Kind::Person
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
+        for (key, grant) in &self.keys {
+            if grant.urns.is_empty() || grant.capabilities.is_empty() {
+                return Err(format!("sub-key {} is granted nothing", key).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deserialise and validate the value of extension `E`.
+pub fn check<E>(kind: Kind, val: &serde_json::Value) -> Result<E, error::Invalid>
+where
+    E: Extension,
+{
+    let namespace = E::namespace();
+    if !E::applies_to(kind) {
+        return Err(error::Invalid::Subject {
+            namespace: namespace.clone(),
+            kind,
+        });
+    }
+    let ext =
+        serde_json::from_value::<E>(val.clone()).map_err(|source| error::Invalid::Malformed {
+            namespace: namespace.clone(),
+            source,
+        })?;
+    ext.validate()
+        .map_err(|source| error::Invalid::Constraint {
+            namespace: namespace.clone(),
+            source,
+        })?;
+    Ok(ext)
+}
+
+type Check = fn(Kind, &serde_json::Value) -> Result<(), error::Invalid>;
+
+/// The set of [`Extension`]s known to an application.
+#[derive(Clone, Default)]
+pub struct Registry {
+    known: BTreeMap<Url, Check>,
+    deny_unknown: bool,
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("known", &self.known.keys().collect::<Vec<_>>())
+            .field("deny_unknown", &self.deny_unknown)
+            .finish()
+    }
+}
+
+impl Registry {
+    /// An empty registry, which accepts any extension.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry of the extensions defined by this crate.
+    pub fn builtin() -> Self {
+        Self::new().with::<Attestations>().with::<SubKeys>()
+    }
+
+    pub fn register<E>(&mut self) -> &mut Self
+    where
+        E: Extension,
+    {
+        self.known.insert(E::namespace().clone(), |kind, val| {
+            check::<E>(kind, val).map(drop)
+        });
+        self
+    }
+
+    pub fn with<E>(mut self) -> Self
+    where
+        E: Extension,
+    {
+        self.register::<E>();
+        self
+    }
+
+    /// Reject payloads carrying extensions which are not registered.
+    ///
+    /// By default, unknown extensions are ignored, as they may belong to
+    /// applications we don't know about.
+    pub fn deny_unknown(mut self, deny: bool) -> Self {
+        self.deny_unknown = deny;
+        self
+    }
+
+    pub fn is_registered(&self, namespace: &Url) -> bool {
+        self.known.contains_key(namespace)
+    }
+
+    /// Check the values of all registered extensions present in `payload`.
+    pub fn validate<T>(&self, payload: &Payload<T>) -> Result<(), error::Invalid>
+    where
+        T: Subject,
+    {
+        for (namespace, val) in payload.exts() {
+            match self.known.get(namespace) {
+                Some(check) => check(T::kind(), val)?,
+                None if self.deny_unknown => {
+                    return Err(error::Invalid::Unknown(namespace.clone()))
+                },
+                None => {},
+            }
+        }
+        Ok(())
+    }
+}
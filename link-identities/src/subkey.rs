@@ -0,0 +1,112 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Restricted sub-keys of a personal identity.
+//!
+//! A [`SubKeys`] payload extension lists keys which may act on behalf of a
+//! person in a limited way, without being delegations of the identity. This
+//! allows eg. a CI system to publish `rad/signed_refs` for a project the
+//! person maintains, without holding the person's main key.
+//!
+//! Since the extension is part of the identity document, the grants are signed
+//! by the identity's delegations, and replicated along with it. Sub-keys can
+//! never update the identity itself: only delegations can sign new revisions.
+//! Revoking a sub-key is done by publishing a revision which no longer lists
+//! it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use canonical::Cstring;
+use crypto::PublicKey;
+use url::Url;
+
+use crate::{git::Urn, payload::HasNamespace};
+
+lazy_static! {
+    static ref SUBKEYS_NAMESPACE_V1: Url =
+        Url::parse("https://radicle.xyz/link/identities/subkeys/v1").unwrap();
+}
+
+/// What a sub-key may do on behalf of the person.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    /// Publish `rad/signed_refs`, which are then replicated like the ones of
+    /// the person's delegations.
+    PublishRefs,
+}
+
+/// The restrictions of a sub-key.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Grant {
+    /// A human-readable description, eg. the name of the CI system.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<Cstring>,
+    /// The identities the grant is valid for. Revisions are ignored.
+    pub urns: BTreeSet<Urn>,
+    pub capabilities: BTreeSet<Capability>,
+    /// Seconds since the Unix epoch after which the grant is no longer
+    /// valid.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+}
+
+impl Grant {
+    /// Whether the grant allows `capability` for `urn` at time `now`.
+    pub fn allows(&self, urn: &Urn, capability: Capability, now: SystemTime) -> bool {
+        let urn = urn.clone().with_path(None);
+        let unexpired = match self.expires {
+            None => true,
+            Some(expires) => now
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs() < expires)
+                .unwrap_or(true),
+        };
+
+        unexpired && self.capabilities.contains(&capability) && self.urns.contains(&urn)
+    }
+}
+
+/// Payload extension holding the sub-keys of a person.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SubKeys {
+    pub keys: BTreeMap<PublicKey, Grant>,
+}
+
+impl SubKeys {
+    /// Add or replace the [`Grant`] of `key`.
+    pub fn issue(&mut self, key: PublicKey, grant: Grant) -> Option<Grant> {
+        self.keys.insert(key, grant)
+    }
+
+    /// Remove the [`Grant`] of `key`.
+    pub fn revoke(&mut self, key: &PublicKey) -> Option<Grant> {
+        self.keys.remove(key)
+    }
+
+    /// The keys allowed `capability` for `urn` at time `now`.
+    pub fn allowed<'a>(
+        &'a self,
+        urn: &'a Urn,
+        capability: Capability,
+        now: SystemTime,
+    ) -> impl Iterator<Item = &'a PublicKey> + 'a {
+        self.keys
+            .iter()
+            .filter(move |(_, grant)| grant.allows(urn, capability, now))
+            .map(|(key, _)| key)
+    }
+}
+
+impl HasNamespace for SubKeys {
+    fn namespace() -> &'static Url {
+        &SUBKEYS_NAMESPACE_V1
+    }
+}
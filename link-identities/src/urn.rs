@@ -37,6 +37,33 @@ pub mod error {
         Multihash(#[from] multihash::DecodeOwnedError),
     }
 
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum FromMultihash {
+        #[error("unsupported hash algorithm: {0:?}")]
+        UnsupportedAlgorithm(multihash::Code),
+
+        #[error("invalid digest length for {algorithm}: expected {expected}, got {actual}")]
+        DigestLength {
+            algorithm: Algorithm,
+            expected: usize,
+            actual: usize,
+        },
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum IntoOid {
+        #[error("hash algorithm mismatch: expected {expected}, got {actual}")]
+        AlgorithmMismatch {
+            expected: Algorithm,
+            actual: Algorithm,
+        },
+
+        #[error(transparent)]
+        Git(#[from] ext::oid::FromMultihashError),
+    }
+
     #[derive(Debug, Error)]
     #[non_exhaustive]
     pub enum FromRefLike<E: std::error::Error + Send + Sync + 'static> {
@@ -122,6 +149,157 @@ impl TryFrom<&str> for SomeProtocol {
     }
 }
 
+/// The hash algorithm of the identifier of a [`Urn`].
+///
+/// The identifier is encoded as a [multihash], so the algorithm is part of
+/// the canonical encoding of a [`Urn`]. This allows URNs of repositories using
+/// git's SHA-1 and SHA-256 object formats to coexist, and to be told apart
+/// without further context.
+///
+/// [multihash]: https://multiformats.io/multihash/
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Algorithm {
+    /// SHA-1, the original git object format.
+    Sha1,
+    /// SHA-256, git's `extensions.objectFormat = sha256`.
+    Sha256,
+}
+
+impl Algorithm {
+    pub const fn code(&self) -> multihash::Code {
+        match self {
+            Self::Sha1 => multihash::Code::Sha1,
+            Self::Sha256 => multihash::Code::Sha2_256,
+        }
+    }
+
+    /// Length of a digest in bytes.
+    pub const fn digest_len(&self) -> usize {
+        match self {
+            Self::Sha1 => 20,
+            Self::Sha256 => 32,
+        }
+    }
+
+    /// The name git uses for the object format.
+    pub const fn object_format(&self) -> &'static str {
+        match self {
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+        }
+    }
+}
+
+impl Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.object_format())
+    }
+}
+
+impl TryFrom<multihash::Code> for Algorithm {
+    type Error = error::FromMultihash;
+
+    fn try_from(code: multihash::Code) -> Result<Self, Self::Error> {
+        match code {
+            multihash::Code::Sha1 => Ok(Self::Sha1),
+            multihash::Code::Sha2_256 => Ok(Self::Sha256),
+            other => Err(error::FromMultihash::UnsupportedAlgorithm(other)),
+        }
+    }
+}
+
+/// An identifier of a git [`Urn`] using any of the supported [`Algorithm`]s.
+///
+/// `Urn<AnyId>` can represent URNs of both SHA-1 and SHA-256 repositories, and
+/// shares the canonical string, ref and CBOR encodings with `Urn<ext::Oid>`.
+/// Use [`Urn::try_map`] with [`AnyId::into_oid`] to convert to the SHA-1 only
+/// representation used by storage.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct AnyId {
+    algorithm: Algorithm,
+    digest: Vec<u8>,
+}
+
+impl AnyId {
+    pub fn new(algorithm: Algorithm, digest: &[u8]) -> Result<Self, error::FromMultihash> {
+        if digest.len() != algorithm.digest_len() {
+            return Err(error::FromMultihash::DigestLength {
+                algorithm,
+                expected: algorithm.digest_len(),
+                actual: digest.len(),
+            });
+        }
+
+        Ok(Self {
+            algorithm,
+            digest: digest.to_vec(),
+        })
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    /// Convert to a SHA-1 [`ext::Oid`].
+    pub fn into_oid(self) -> Result<ext::Oid, error::IntoOid> {
+        match self.algorithm {
+            Algorithm::Sha1 => Ok(ext::Oid::try_from(Multihash::from(&self))?),
+            actual => Err(error::IntoOid::AlgorithmMismatch {
+                expected: Algorithm::Sha1,
+                actual,
+            }),
+        }
+    }
+}
+
+impl sealed::Sealed for AnyId {}
+
+impl HasProtocol for AnyId {
+    const PROTOCOL: &'static str = "git";
+}
+
+impl From<ext::Oid> for AnyId {
+    fn from(oid: ext::Oid) -> Self {
+        Self {
+            algorithm: Algorithm::Sha1,
+            digest: oid.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl From<&AnyId> for Multihash {
+    fn from(id: &AnyId) -> Self {
+        multihash::wrap(id.algorithm.code(), &id.digest)
+    }
+}
+
+impl From<AnyId> for Multihash {
+    fn from(id: AnyId) -> Self {
+        Self::from(&id)
+    }
+}
+
+impl TryFrom<MultihashRef<'_>> for AnyId {
+    type Error = error::FromMultihash;
+
+    fn try_from(mhash: MultihashRef) -> Result<Self, Self::Error> {
+        Self::new(Algorithm::try_from(mhash.algorithm())?, mhash.digest())
+    }
+}
+
+impl TryFrom<Multihash> for AnyId {
+    type Error = error::FromMultihash;
+
+    fn try_from(mhash: Multihash) -> Result<Self, Self::Error> {
+        Self::try_from(mhash.as_ref())
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Urn<R> {
     pub id: R,
@@ -162,6 +340,24 @@ impl<R> Urn<R> {
         }
     }
 
+    pub fn try_map<F, S, E>(self, f: F) -> Result<Urn<S>, E>
+    where
+        F: FnOnce(R) -> Result<S, E>,
+    {
+        Ok(Urn {
+            id: f(self.id)?,
+            path: self.path,
+        })
+    }
+
+    /// The hash [`Algorithm`] of [`Self::id`].
+    pub fn algorithm<'a>(&'a self) -> Result<Algorithm, error::FromMultihash>
+    where
+        &'a R: Into<Multihash>,
+    {
+        Algorithm::try_from(Into::<Multihash>::into(&self.id).algorithm())
+    }
+
     pub fn map_path<F>(self, f: F) -> Self
     where
         F: FnOnce(Option<ext::RefLike>) -> Option<ext::RefLike>,
@@ -3,8 +3,10 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod attestation;
 mod generic;
 mod git;
 mod payload;
+mod subkey;
 mod urn;
 mod xor;
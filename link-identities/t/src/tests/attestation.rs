@@ -0,0 +1,146 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::collections::BTreeSet;
+
+use librad::net::x509::Certificate;
+use link_crypto::SecretKey;
+use link_identities::{
+    attestation::{self, error, Attestations, Claim},
+    git::Urn,
+    payload::{Person, PersonPayload},
+};
+use radicle_git_ext::Oid;
+use serde_json::json;
+use test_helpers::roundtrip;
+
+mod social;
+
+lazy_static! {
+    static ref KEY: SecretKey = SecretKey::from_seed([
+        81, 151, 13, 57, 246, 76, 127, 57, 30, 125, 102, 210, 87, 132, 7, 92, 12, 122, 7, 30, 202,
+        71, 235, 169, 66, 199, 172, 11, 97, 50, 173, 150
+    ]);
+    static ref URN: Urn = Urn::new(Oid::from(git2::Oid::zero()));
+}
+
+fn keys() -> BTreeSet<link_crypto::PublicKey> {
+    Some(KEY.public()).into_iter().collect()
+}
+
+#[test]
+fn did_key_roundtrip() {
+    let did = attestation::did_key(&KEY.public());
+    assert!(did.starts_with("did:key:z6Mk"));
+    assert!(attestation::verify_did_key(&did, &keys()).is_ok());
+    assert_matches!(
+        attestation::verify_did_key(&did, &BTreeSet::new()),
+        Err(error::Verify::UnknownKey(_))
+    );
+    assert_matches!(
+        attestation::verify_did_key("did:key:lolek", &keys()),
+        Err(error::Verify::MalformedDid(_))
+    );
+}
+
+#[test]
+fn did_web_urls() {
+    assert_eq!(
+        attestation::did_web_url("did:web:example.com")
+            .unwrap()
+            .as_str(),
+        "https://example.com/.well-known/did.json"
+    );
+    assert_eq!(
+        attestation::did_web_url("did:web:example.com:user:alice")
+            .unwrap()
+            .as_str(),
+        "https://example.com/user/alice/did.json"
+    );
+    assert_matches!(
+        attestation::did_web_url("did:key:example.com"),
+        Err(error::Verify::MalformedDid(_))
+    );
+}
+
+#[test]
+fn dns() {
+    let txt = vec![
+        "v=spf1 -all".to_owned(),
+        format!("{}{}", attestation::DNS_TXT_PREFIX, *URN),
+    ];
+    assert!(attestation::verify_dns("example.com", &*URN, &txt).is_ok());
+    assert_matches!(
+        attestation::verify_dns("example.com", &*URN, &txt[..1]),
+        Err(error::Verify::MissingTxtRecord { .. })
+    );
+}
+
+#[test]
+fn did_web() {
+    let did = "did:web:example.com";
+    let doc = json!({
+        "id": did,
+        "alsoKnownAs": [URN.to_string()],
+        "verificationMethod": [{
+            "id": format!("{}#link", did),
+            "type": "Ed25519VerificationKey2020",
+            "controller": did,
+            "publicKeyMultibase": attestation::public_key_multibase(&KEY.public()),
+        }]
+    });
+    assert!(attestation::verify_did_web(did, &*URN, &keys(), &doc).is_ok());
+    assert_matches!(
+        attestation::verify_did_web(did, &*URN, &BTreeSet::new(), &doc),
+        Err(error::Verify::NoDelegationKey)
+    );
+    assert_matches!(
+        attestation::verify_did_web("did:web:example.org", &*URN, &keys(), &doc),
+        Err(error::Verify::DocumentId { .. })
+    );
+}
+
+#[test]
+fn x509() {
+    let der = Certificate::generate(&*KEY).unwrap().to_der();
+    let fingerprint = attestation::x509_fingerprint(&der);
+    assert!(attestation::verify_x509(&fingerprint, &keys(), &der).is_ok());
+    assert_matches!(
+        attestation::verify_x509(&fingerprint, &BTreeSet::new(), &der),
+        Err(error::Verify::CertificateKey)
+    );
+    assert_matches!(
+        attestation::verify_x509(&attestation::x509_fingerprint(b"lolek"), &keys(), &der),
+        Err(error::Verify::Fingerprint { .. })
+    );
+}
+
+#[test]
+fn payload_extension() {
+    let attestations = Attestations {
+        claims: vec![
+            Claim::Dns {
+                domain: "example.com".into(),
+            },
+            Claim::DidKey {
+                did: attestation::did_key(&KEY.public()).into(),
+            },
+            Claim::X509 {
+                fingerprint: attestation::x509_fingerprint(b"lolek").into(),
+            },
+        ],
+    };
+    let payload = PersonPayload::new(Person {
+        name: "alice".into(),
+    })
+    .with_ext(attestations.clone())
+    .unwrap();
+
+    assert_eq!(
+        payload.get_ext::<Attestations>().unwrap(),
+        Some(attestations)
+    );
+    roundtrip::cjson(payload)
+}
@@ -0,0 +1,145 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{cell::Cell, collections::BTreeSet, convert::Infallible, rc::Rc, time::Duration};
+
+use link_crypto::SecretKey;
+use link_identities::attestation::social::{error, Fetch, Proof, Statement, Verifier};
+use url::Url;
+
+use super::{keys, KEY, URN};
+
+fn statement() -> Statement {
+    Statement::new(&*URN, KEY.public(), "github", "alice")
+}
+
+struct Gist {
+    text: String,
+    fetched: Rc<Cell<usize>>,
+}
+
+impl Fetch for Gist {
+    type Error = Infallible;
+
+    fn fetch(&self, _: &Url) -> Result<String, Self::Error> {
+        self.fetched.set(self.fetched.get() + 1);
+        Ok(self.text.clone())
+    }
+}
+
+#[test]
+fn roundtrip() {
+    let proof = statement().sign(&*KEY).unwrap();
+    let text = format!("my link identity:\n\n{}\n\nkthxbye", proof);
+    let parsed = Proof::parse(&text).unwrap();
+
+    assert_eq!(parsed.statement, proof.statement);
+    assert_eq!(parsed.signature, proof.signature);
+    assert!(parsed.verify(&statement(), &keys()).is_ok());
+}
+
+#[test]
+fn rejects_foreign_key() {
+    let mallory = SecretKey::new();
+    let proof = Statement::new(&*URN, mallory.public(), "github", "alice")
+        .sign(&mallory)
+        .unwrap();
+
+    assert_matches!(
+        proof.verify(&statement(), &keys()),
+        Err(error::Verify::UnknownKey)
+    );
+}
+
+#[test]
+fn rejects_mismatch() {
+    let proof = Statement::new(&*URN, KEY.public(), "github", "mallory")
+        .sign(&*KEY)
+        .unwrap();
+
+    assert_matches!(
+        proof.verify(&statement(), &keys()),
+        Err(error::Verify::Mismatch { .. })
+    );
+}
+
+#[test]
+fn rejects_tampering() {
+    let proof = statement().sign(&*KEY).unwrap();
+    let text = proof.to_string().replace("alice", "mallory");
+    let tampered = Proof::parse(&text).unwrap();
+    let expected = Statement::new(&*URN, KEY.public(), "github", "mallory");
+
+    assert_matches!(
+        tampered.verify(&expected, &keys()),
+        Err(error::Verify::Signature)
+    );
+}
+
+#[test]
+fn verifier_caches() {
+    let fetched = Rc::new(Cell::new(0));
+    let gist = Gist {
+        text: statement().sign(&*KEY).unwrap().to_string(),
+        fetched: fetched.clone(),
+    };
+    let verifier = Verifier::new(gist, Duration::from_secs(60));
+    let url = Url::parse("https://gist.example.com/alice/proof").unwrap();
+
+    assert!(verifier.verify(&url, &statement(), &keys()).is_ok());
+    assert!(verifier.verify(&url, &statement(), &keys()).is_ok());
+    assert_eq!(fetched.get(), 1);
+    assert_matches!(
+        verifier.verify(
+            &Url::parse("http://gist.example.com/alice/proof").unwrap(),
+            &statement(),
+            &BTreeSet::new()
+        ),
+        Err(error::Verify::NotHttps(_))
+    );
+}
+
+#[test]
+fn verifier_cache_is_specific_to_statement_and_keys() {
+    let fetched = Rc::new(Cell::new(0));
+    let gist = Gist {
+        text: statement().sign(&*KEY).unwrap().to_string(),
+        fetched: fetched.clone(),
+    };
+    let verifier = Verifier::new(gist, Duration::from_secs(60));
+    let url = Url::parse("https://gist.example.com/alice/proof").unwrap();
+    assert!(verifier.verify(&url, &statement(), &keys()).is_ok());
+
+    let mallory = Statement::new(&*URN, KEY.public(), "github", "mallory");
+    assert_matches!(
+        verifier.verify(&url, &mallory, &keys()),
+        Err(error::Verify::Mismatch { .. })
+    );
+
+    let foreign = std::iter::once(SecretKey::new().public()).collect::<BTreeSet<_>>();
+    assert_matches!(
+        verifier.verify(&url, &statement(), &foreign),
+        Err(error::Verify::UnknownKey)
+    );
+    assert_eq!(fetched.get(), 3);
+}
+
+#[test]
+fn verifier_cache_is_bounded() {
+    let fetched = Rc::new(Cell::new(0));
+    let gist = Gist {
+        text: statement().sign(&*KEY).unwrap().to_string(),
+        fetched: fetched.clone(),
+    };
+    let verifier = Verifier::with_capacity(gist, Duration::from_secs(60), 1);
+    let alice = Url::parse("https://gist.example.com/alice/proof").unwrap();
+    let mirror = Url::parse("https://mirror.example.com/alice/proof").unwrap();
+
+    assert!(verifier.verify(&alice, &statement(), &keys()).is_ok());
+    assert!(verifier.verify(&mirror, &statement(), &keys()).is_ok());
+    // The result for `alice` was evicted
+    assert!(verifier.verify(&alice, &statement(), &keys()).is_ok());
+    assert_eq!(fetched.get(), 3);
+}
@@ -0,0 +1,121 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::collections::BTreeSet;
+
+use link_crypto::SecretKey;
+use link_identities::{
+    attestation::Attestations,
+    git::Urn,
+    payload::{
+        extension::error::Invalid,
+        Ext,
+        ExtError,
+        Person,
+        PersonPayload,
+        Project,
+        ProjectPayload,
+        Registry,
+    },
+    subkey::{Capability, Grant, SubKeys},
+};
+use radicle_git_ext::Oid;
+
+fn person() -> PersonPayload {
+    PersonPayload::new(Person {
+        name: "cloudhead".into(),
+    })
+}
+
+fn project() -> ProjectPayload {
+    ProjectPayload::new(Project {
+        name: "radicle-link".into(),
+        description: None,
+        default_branch: None,
+    })
+}
+
+fn subkeys(urns: BTreeSet<Urn>) -> SubKeys {
+    let mut subkeys = SubKeys::default();
+    subkeys.issue(
+        SecretKey::new().public(),
+        Grant {
+            label: None,
+            urns,
+            capabilities: Some(Capability::PublishRefs).into_iter().collect(),
+            expires: None,
+        },
+    );
+    subkeys
+}
+
+#[test]
+fn typed_accessors() {
+    let urn = Urn::new(Oid::from(git2::Oid::zero()));
+    let ext = subkeys(Some(urn).into_iter().collect());
+
+    let mut payload = person();
+    assert_eq!(payload.extension::<SubKeys>().unwrap(), None);
+    payload.set_extension(ext.clone()).unwrap();
+    assert_eq!(payload.extension::<SubKeys>().unwrap(), Some(ext));
+    assert!(Registry::builtin().validate(&payload).is_ok());
+}
+
+#[test]
+fn rejects_constraint_violation() {
+    let mut payload = person();
+    assert!(matches!(
+        payload.set_extension(subkeys(BTreeSet::new())),
+        Err(ExtError::Invalid(Invalid::Constraint { .. }))
+    ));
+}
+
+#[test]
+fn rejects_wrong_subject() {
+    let mut payload = project();
+    assert!(matches!(
+        payload.set_extension(Attestations::default()),
+        Err(ExtError::Invalid(Invalid::Subject { .. }))
+    ));
+
+    // Bypassing the typed setter is caught by the registry
+    payload.set_ext(Attestations::default()).unwrap();
+    assert!(matches!(
+        Registry::builtin().validate(&payload),
+        Err(Invalid::Subject { .. })
+    ));
+}
+
+#[test]
+fn rejects_malformed() {
+    let payload = person()
+        .with_ext(Ext {
+            namespace: "https://radicle.xyz/link/identities/subkeys/v1"
+                .parse()
+                .unwrap(),
+            val: "not a sub-key",
+        })
+        .unwrap();
+    assert!(matches!(
+        Registry::builtin().validate(&payload),
+        Err(Invalid::Malformed { .. })
+    ));
+    assert!(payload.extension::<SubKeys>().is_err());
+}
+
+#[test]
+fn unknown_extensions() {
+    let payload = person()
+        .with_ext(Ext {
+            namespace: "https://radicle.xyz/upstream/ethereum/v1".parse().unwrap(),
+            val: "0x42",
+        })
+        .unwrap();
+    assert!(Registry::builtin().validate(&payload).is_ok());
+    assert!(matches!(
+        Registry::builtin().deny_unknown(true).validate(&payload),
+        Err(Invalid::Unknown(_))
+    ));
+}
@@ -0,0 +1,92 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use link_crypto::SecretKey;
+use link_identities::{
+    git::Urn,
+    payload::{Person, PersonPayload},
+    subkey::{Capability, Grant, SubKeys},
+};
+use radicle_git_ext::Oid;
+use test_helpers::roundtrip;
+
+lazy_static! {
+    static ref URN: Urn = Urn::new(Oid::from(git2::Oid::zero()));
+    static ref OTHER: Urn = Urn::new(Oid::from(
+        git2::Oid::hash_object(git2::ObjectType::Blob, b"other").unwrap()
+    ));
+}
+
+fn grant(expires: Option<u64>) -> Grant {
+    Grant {
+        label: Some("ci".into()),
+        urns: Some(URN.clone()).into_iter().collect(),
+        capabilities: Some(Capability::PublishRefs).into_iter().collect(),
+        expires,
+    }
+}
+
+#[test]
+fn grant_is_restricted_to_urns() {
+    let now = UNIX_EPOCH + Duration::from_secs(1000);
+    let grant = grant(None);
+    assert!(grant.allows(&URN, Capability::PublishRefs, now));
+    assert!(!grant.allows(&OTHER, Capability::PublishRefs, now));
+}
+
+#[test]
+fn grant_expires() {
+    let grant = grant(Some(1000));
+    assert!(grant.allows(
+        &URN,
+        Capability::PublishRefs,
+        UNIX_EPOCH + Duration::from_secs(999)
+    ));
+    assert!(!grant.allows(
+        &URN,
+        Capability::PublishRefs,
+        UNIX_EPOCH + Duration::from_secs(1000)
+    ));
+}
+
+#[test]
+fn allowed_keys() {
+    let ci = SecretKey::new().public();
+    let revoked = SecretKey::new().public();
+    let mut subkeys = SubKeys::default();
+    subkeys.issue(ci, grant(None));
+    subkeys.issue(revoked, grant(None));
+    assert!(subkeys.revoke(&revoked).is_some());
+
+    let now = UNIX_EPOCH + Duration::from_secs(1000);
+    assert_eq!(
+        subkeys
+            .allowed(&URN, Capability::PublishRefs, now)
+            .collect::<Vec<_>>(),
+        vec![&ci]
+    );
+    assert_eq!(
+        subkeys
+            .allowed(&OTHER, Capability::PublishRefs, now)
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn person_payload() {
+    let mut subkeys = SubKeys::default();
+    subkeys.issue(SecretKey::new().public(), grant(Some(1000)));
+    let payload = PersonPayload::new(Person {
+        name: "alice".into(),
+    })
+    .with_ext(subkeys.clone())
+    .unwrap();
+
+    assert_eq!(payload.get_ext::<SubKeys>().unwrap(), Some(subkeys));
+    roundtrip::cjson(payload)
+}
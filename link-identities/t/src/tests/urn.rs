@@ -5,8 +5,9 @@
 
 use std::convert::TryFrom as _;
 
-use link_identities::urn::Urn;
+use link_identities::urn::{error, Algorithm, AnyId, Urn};
 use radicle_git_ext as ext;
+use test_helpers::roundtrip;
 
 #[test]
 fn is_reflike() {
@@ -39,3 +40,48 @@ fn is_reflike_with_qualified_path() {
         .as_str()
     )
 }
+
+#[test]
+fn any_id_sha1_is_compatible() {
+    let oid = ext::Oid::from(git2::Oid::hash_object(git2::ObjectType::Blob, b"lolek").unwrap());
+    let urn = Urn::new(oid).with_path(ext::RefLike::try_from("refs/heads/bolek").unwrap());
+    let any = urn.clone().map(AnyId::from);
+
+    assert_eq!(any.algorithm().unwrap(), Algorithm::Sha1);
+    assert_eq!(urn.to_string(), any.to_string());
+    assert_eq!(any.clone().try_map(AnyId::into_oid).unwrap(), urn);
+    assert_eq!(urn.to_string().parse::<Urn<AnyId>>().unwrap(), any);
+}
+
+#[test]
+fn any_id_sha256() {
+    let id = AnyId::new(Algorithm::Sha256, &[42; 32]).unwrap();
+    let urn = Urn::new(id);
+
+    assert_eq!(urn.algorithm().unwrap(), Algorithm::Sha256);
+    roundtrip::str(urn.clone());
+    roundtrip::cbor(urn.clone());
+    assert_matches!(
+        urn.to_string().parse::<Urn<ext::Oid>>(),
+        Err(error::FromStr::InvalidId(_))
+    );
+    assert_matches!(
+        urn.try_map(AnyId::into_oid),
+        Err(error::IntoOid::AlgorithmMismatch {
+            expected: Algorithm::Sha1,
+            actual: Algorithm::Sha256
+        })
+    );
+}
+
+#[test]
+fn any_id_rejects_bad_digest_length() {
+    assert_matches!(
+        AnyId::new(Algorithm::Sha256, &[42; 20]),
+        Err(error::FromMultihash::DigestLength {
+            expected: 32,
+            actual: 20,
+            ..
+        })
+    )
+}
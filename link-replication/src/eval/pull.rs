@@ -15,8 +15,10 @@ use crate::{
     sigrefs::{self, Refs},
     state::FetchState,
     validation,
+    Applied,
     Error,
     FetchLimit,
+    Hooks,
     Identities,
     LocalIdentity,
     LocalPeer,
@@ -44,6 +46,7 @@ pub(crate) fn pull<U, C>(
 where
     U: ids::Urn + Clone + Debug + Ord,
     C: Identities<Urn = U>
+        + Hooks
         + LocalPeer
         + Net
         + Refdb
@@ -127,7 +130,7 @@ where
                 },
             }
         }
-        Refdb::update(cx, tips)?
+        apply(cx, &remote_id, tips)?
     };
 
     info!("loading combined sigrefs");
@@ -189,7 +192,8 @@ where
     }
 
     info!("updating tips");
-    applied.append(&mut Refdb::update(cx, state.updates_mut().drain(..))?);
+    let tips = state.updates_mut().drain(..).collect();
+    applied.append(&mut apply(cx, &remote_id, tips)?);
     for u in &applied.updated {
         debug!("applied {:?}", u);
     }
@@ -237,3 +241,18 @@ where
         _marker: PhantomData,
     })
 }
+
+/// Apply `updates`, running the [`Hooks`] around it.
+fn apply<C>(
+    cx: &mut C,
+    remote_id: &PeerId,
+    updates: Vec<Update<'static>>,
+) -> Result<Applied<'static>, Error>
+where
+    C: Hooks + Refdb,
+{
+    Hooks::pre_apply(cx, remote_id, &updates)?;
+    let applied = Refdb::update(cx, updates)?;
+    Hooks::post_apply(cx, remote_id, &applied)?;
+    Ok(applied)
+}
@@ -0,0 +1,48 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use thiserror::Error;
+
+use crate::{Applied, PeerId, Update};
+
+/// Ref updates were rejected by [`Hooks::pre_apply`].
+#[derive(Clone, Debug, Error)]
+#[error("ref updates vetoed: {reason}")]
+pub struct Veto {
+    pub reason: String,
+}
+
+impl Veto {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Callbacks around the application of ref updates to the [`crate::Refdb`].
+///
+/// Updates are applied in more than one batch during a replication run (the
+/// identity tips first, then everything else), so the hooks may be called
+/// multiple times.
+pub trait Hooks {
+    type PostApplyError: std::error::Error + Send + Sync + 'static;
+
+    /// Called before `updates` fetched from `remote_id` are applied.
+    ///
+    /// Returning a [`Veto`] aborts the replication run before any of
+    /// `updates` are applied. Batches applied before are not rolled back.
+    fn pre_apply(&self, remote_id: &PeerId, updates: &[Update<'_>]) -> Result<(), Veto>;
+
+    /// Called after updates fetched from `remote_id` were applied.
+    ///
+    /// An error aborts the replication run, even though the updates are
+    /// already applied.
+    fn post_apply(
+        &mut self,
+        remote_id: &PeerId,
+        applied: &Applied<'_>,
+    ) -> Result<(), Self::PostApplyError>;
+}
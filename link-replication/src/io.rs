@@ -4,7 +4,7 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 mod net;
-pub use net::{Connection, Network};
+pub use net::{Advertisements, Connection, Network};
 
 mod odb;
 pub use odb::Odb;
@@ -3,14 +3,29 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{io, marker::PhantomData, path::PathBuf};
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    marker::PhantomData,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use bstr::BString;
 use futures_lite::io::{AsyncRead, AsyncWrite};
+use link_crypto::PeerId;
 use link_git::{
     protocol as git,
-    protocol::{ObjectId, Ref},
+    protocol::{
+        meter::{OnProgress, Transfer},
+        ObjectId,
+        Ref,
+    },
 };
+use parking_lot::Mutex;
 use radicle_data::NonEmptyVec;
 
 use crate::{transmit::LsRefs, Net, Odb, Refdb, Urn};
@@ -24,11 +39,69 @@ pub trait Connection {
     async fn open_stream(&self) -> Result<(Self::Read, Self::Write), Self::Error>;
 }
 
+/// The peer, namespace and ref prefixes of an `ls-refs` request.
+type AdvertisementKey = (PeerId, String, Vec<BString>);
+
+/// The refs remote peers advertised most recently, along with their
+/// [`git::ls::digest`].
+///
+/// When performing `ls-refs` against a peer the refs of which are known, only
+/// the digest is sent, and the peer responds with the full advertisement only
+/// if the refs changed, see [`git::ls::ls_refs_unless`]. This makes frequent
+/// fetches from large, but mostly idle namespaces cheap.
+///
+/// At most `capacity` advertisements are retained, evicting the ones stored
+/// first.
+#[derive(Clone)]
+pub struct Advertisements {
+    capacity: usize,
+    inner: Arc<Mutex<AdvertisementsInner>>,
+}
+
+#[derive(Default)]
+struct AdvertisementsInner {
+    refs: HashMap<AdvertisementKey, (ObjectId, Vec<Ref>)>,
+    order: VecDeque<AdvertisementKey>,
+}
+
+impl Advertisements {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Default::default(),
+        }
+    }
+
+    fn get(&self, key: &AdvertisementKey) -> Option<(ObjectId, Vec<Ref>)> {
+        self.inner.lock().refs.get(key).cloned()
+    }
+
+    fn insert(&self, key: AdvertisementKey, refs: Vec<Ref>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let digest = git::ls::digest(&refs);
+        let mut inner = self.inner.lock();
+        if inner.refs.insert(key.clone(), (digest, refs)).is_none() {
+            inner.order.push_back(key);
+            while inner.order.len() > self.capacity {
+                if let Some(oldest) = inner.order.pop_front() {
+                    inner.refs.remove(&oldest);
+                }
+            }
+        }
+    }
+}
+
 pub struct Network<U, D, B, C> {
     git_dir: PathBuf,
     urn: U,
     db: D,
     conn: C,
+    adverts: Option<(PeerId, Advertisements)>,
+    on_progress: Option<OnProgress>,
+    /// The bytes received by all packfiles fetched so far.
+    received: Arc<AtomicU64>,
     _marker: PhantomData<B>,
 }
 
@@ -39,9 +112,33 @@ impl<U, D, B, C> Network<U, D, B, C> {
             db,
             conn,
             urn,
+            adverts: None,
+            on_progress: None,
+            received: Arc::new(AtomicU64::new(0)),
             _marker: PhantomData,
         }
     }
+
+    /// Remember the refs advertised by `remote`, the peer `conn` is connected
+    /// to, in `adverts`, and only ask for them again if they changed.
+    pub fn with_advertisements(self, remote: PeerId, adverts: Advertisements) -> Self {
+        Self {
+            adverts: Some((remote, adverts)),
+            ..self
+        }
+    }
+
+    /// Report the [`Transfer`] progress of fetching packfiles to `f`.
+    ///
+    /// A replication may fetch several packfiles: the received bytes are
+    /// summed over all of them, while the object counts are those of the
+    /// packfile currently being fetched.
+    pub fn with_progress(self, f: OnProgress) -> Self {
+        Self {
+            on_progress: Some(f),
+            ..self
+        }
+    }
 }
 
 #[async_trait(?Send)]
@@ -78,16 +175,30 @@ where
             },
         };
         let (recv, send) = self.conn.open_stream().await.map_err(io_other)?;
-        git::ls_refs(
-            git::ls::Options {
-                repo: BString::from(self.urn.encode_id()),
-                extra_params: Vec::default(),
-                ref_prefixes,
+        let opt = git::ls::Options {
+            repo: BString::from(self.urn.encode_id()),
+            extra_params: Vec::default(),
+            ref_prefixes,
+        };
+        let (remote, adverts) = match &self.adverts {
+            None => return git::ls_refs(opt, recv, send).await,
+            Some(adverts) => adverts,
+        };
+
+        let key = (*remote, self.urn.encode_id(), opt.ref_prefixes.clone());
+        let refs = match adverts.get(&key) {
+            None => git::ls_refs(opt, recv, send).await?,
+            Some((have, known)) => match git::ls::ls_refs_unless(opt, have, recv, send).await? {
+                None => {
+                    debug!("advertised refs unchanged");
+                    return Ok(known);
+                },
+                Some(refs) => refs,
             },
-            recv,
-            send,
-        )
-        .await
+        };
+        adverts.insert(key, refs.clone());
+
+        Ok(refs)
     }
 
     #[tracing::instrument(level = "debug", skip(self), err)]
@@ -106,6 +217,18 @@ where
             // FIXME: make options work with slice
             let wants = wants.clone();
             let thick: B::Owned = self.db.as_ref().to_owned();
+            let on_progress = self.on_progress.clone().map(|f| {
+                let received = self.received.clone();
+                let base = received.load(Ordering::Relaxed);
+                move |transfer: Transfer| {
+                    let received_bytes = base + transfer.received_bytes;
+                    received.store(received_bytes, Ordering::Relaxed);
+                    f(Transfer {
+                        received_bytes,
+                        ..transfer
+                    })
+                }
+            });
             let (recv, send) = self.conn.open_stream().await.map_err(io_other)?;
             git::fetch(
                 git::fetch::Options {
@@ -116,7 +239,7 @@ where
                     want_refs: vec![],
                 },
                 move |stop| {
-                    git::packwriter::Standard::new(
+                    let writer = git::packwriter::Standard::new(
                         &self.git_dir,
                         git::packwriter::Options {
                             max_pack_bytes,
@@ -124,7 +247,11 @@ where
                         },
                         thick,
                         stop,
-                    )
+                    );
+                    match on_progress {
+                        Some(f) => writer.with_progress(f),
+                        None => writer,
+                    }
                 },
                 recv,
                 send,
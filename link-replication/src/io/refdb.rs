@@ -245,7 +245,7 @@ impl<D: Odb> Refdb<D> {
                     log: LogChange {
                         mode: RefLog::AndReference,
                         force_create_reflog,
-                        message: "replicate: create".into(),
+                        message: log_message("create", &name),
                     },
                     expected: PreviousValue::MustNotExist,
                     new: Target::Peeled(target),
@@ -280,7 +280,7 @@ impl<D: Odb> Refdb<D> {
                                 log: LogChange {
                                     mode: RefLog::AndReference,
                                     force_create_reflog,
-                                    message: "replicate: forced update".into(),
+                                    message: log_message("forced update", &name),
                                 },
                                 expected: PreviousValue::MustExistAndMatch(Target::Peeled(prev)),
                                 new: Target::Peeled(target),
@@ -295,7 +295,7 @@ impl<D: Odb> Refdb<D> {
                             log: LogChange {
                                 mode: RefLog::AndReference,
                                 force_create_reflog,
-                                message: "replicate: fast-forward".into(),
+                                message: log_message("fast-forward", &name),
                             },
                             expected: PreviousValue::MustExistAndMatch(Target::Peeled(prev)),
                             new: Target::Peeled(target),
@@ -637,6 +637,24 @@ impl<'a> Iterator for Scan<'a> {
     }
 }
 
+/// The reflog message for an update of `refname`, in the structured format
+/// of `librad::git::storage::reflog`, naming the peer if `refname` is a remote
+/// tracking branch.
+fn log_message(action: &str, refname: &Qualified) -> BString {
+    let mut components = refname.components().skip(1);
+    let peer = match (components.next(), components.next()) {
+        (Some(remotes), Some(peer)) if remotes.as_str() == "remotes" => {
+            peer.as_str().parse::<PeerId>().ok()
+        },
+        _ => None,
+    };
+    match peer {
+        Some(peer) => format!("replicate: {}; peer={}", action, peer),
+        None => format!("replicate: {}", action),
+    }
+    .into()
+}
+
 fn force_reflog(refname: &Qualified) -> bool {
     use git_ref_format::lit::{KnownLit::*, SomeLit};
 
@@ -21,6 +21,8 @@ pub mod error;
 pub use error::Error;
 
 pub mod fetch;
+pub mod hooks;
+pub use hooks::Hooks;
 pub mod internal;
 pub mod io;
 pub mod peek;
@@ -50,7 +52,7 @@ mod track;
 pub use track::{Rel as TrackingRel, Tracking};
 
 mod transmit;
-pub use transmit::{FilteredRef, LsRefs, Negotiation, Net, RefPrefix, WantsHaves};
+pub use transmit::{BuildWantsHaves, FilteredRef, LsRefs, Negotiation, Net, RefPrefix, WantsHaves};
 
 mod validation;
 pub use validation::validate;
@@ -89,6 +91,7 @@ pub fn pull<C>(
 ) -> Result<Success<<C as Identities>::Urn>, Error>
 where
     C: Identities
+        + Hooks
         + LocalPeer
         + Net
         + Refdb
@@ -122,6 +125,7 @@ pub fn clone<C>(
 ) -> Result<Success<<C as Identities>::Urn>, Error>
 where
     C: Identities
+        + Hooks
         + LocalPeer
         + Net
         + Refdb
@@ -3,6 +3,32 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+//! The ref layout conventions of `link`.
+//!
+//! Each identity has its own namespace in the storage, ie. all its refs are
+//! below `refs/namespaces/<urn>/`. Within a namespace, the refs of the local
+//! peer are stored as usual, while the refs replicated from other peers are
+//! stored as remote tracking branches below `refs/remotes/<peer>/`. Unlike in
+//! plain git, a remote tracking branch retains the category of the ref it
+//! tracks:
+//!
+//! ```text
+//! refs/heads/main                   ->  refs/remotes/<peer>/heads/main
+//! refs/rad/id                       ->  refs/remotes/<peer>/rad/id
+//! refs/rad/ids/<urn>                ->  refs/remotes/<peer>/rad/ids/<urn>
+//! ```
+//!
+//! The `refs/rad` category is reserved for the identity (`rad/id`), the
+//! identity of the owning peer (`rad/self`), the signed refs
+//! (`rad/signed_refs`) and the identities of delegates (`rad/ids/<urn>`).
+//!
+//! * [`parse`] and [`parse_namespaced`] parse ref names into [`Parsed`], which
+//!   [`Parsed::to_qualified`] renders back.
+//! * [`Owned`] and [`RemoteTracking`] are names of either kind, which can be
+//!   validated via [`TryFrom`], or obtained by rewriting a name via [`owned`],
+//!   [`remote_tracking`] and [`scoped`].
+//! * [`namespaced`] adds the namespace of an identity to a name.
+
 use std::convert::TryFrom;
 
 use bstr::BString;
@@ -18,9 +44,9 @@ mod lit;
 pub use lit::*;
 
 pub mod parsed;
-pub use parsed::{parse, Parsed};
+pub use parsed::{parse, parse_namespaced, Parsed};
 
-mod scoped;
+pub mod scoped;
 pub use scoped::{
     namespaced,
     owned,
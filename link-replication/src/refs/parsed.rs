@@ -5,6 +5,7 @@
 
 use std::{
     convert::{Infallible, TryFrom},
+    fmt::{self, Display},
     iter,
 };
 
@@ -26,6 +27,12 @@ pub enum Error {
     #[error("unexpected namespace: '{0}'")]
     Namespaced(String),
 
+    #[error("expected a namespaced ref name")]
+    NotNamespaced,
+
+    #[error("invalid namespace")]
+    Urn(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+
     #[error("invalid remote peer id")]
     PeerId(#[from] link_crypto::peer::conversion::Error),
 
@@ -39,6 +46,12 @@ pub enum Error {
     Other,
 }
 
+/// A ref name parsed according to the `link` conventions, relative to a
+/// namespace.
+///
+/// The name is either one of the special `refs/rad` refs, or any other ref
+/// which has a category (eg. `refs/heads/main`). If it is a remote tracking
+/// branch, ie. `refs/remotes/<peer>/...`, `remote` is the peer it is tracking.
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Parsed<'a, Urn> {
     pub remote: Option<PeerId>,
@@ -65,6 +78,23 @@ where
             |y| super::remote_tracking(id, y.clone().into_owned()),
         )
     }
+
+    /// The fully qualified ref name, ie. the inverse of [`parse`].
+    pub fn to_qualified<'b>(&self) -> Qualified<'b> {
+        match self.to_remote_tracking() {
+            Some(rt) => rt.into(),
+            None => self.to_owned().into(),
+        }
+    }
+}
+
+impl<Urn> Display for Parsed<'_, Urn>
+where
+    Urn: ids::Urn + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_qualified().fmt(f)
+    }
 }
 
 impl<'a, Urn> AsRef<Either<Rad<Urn>, Owned<'a>>> for Parsed<'a, Urn> {
@@ -210,6 +240,7 @@ impl AsRef<str> for Identity {
     }
 }
 
+/// Parse a ref name relative to a namespace, eg. `refs/remotes/<peer>/rad/id`.
 pub fn parse<Urn>(input: &BStr) -> Result<Parsed<Urn>, Error>
 where
     Urn: ids::Urn,
@@ -217,3 +248,24 @@ where
     let rs = RefString::try_from(input.to_str()?)?;
     Parsed::try_from(rs)
 }
+
+/// Parse a fully qualified ref name in the storage, eg.
+/// `refs/namespaces/<urn>/refs/remotes/<peer>/heads/main`, into the
+/// [`ids::Urn`] of its namespace and the name relative to it.
+///
+/// Only a single level of namespacing is permitted.
+pub fn parse_namespaced<'a, Urn>(input: &BStr) -> Result<(Urn, Parsed<'a, Urn>), Error>
+where
+    Urn: ids::Urn,
+{
+    let rs = RefString::try_from(input.to_str()?)?;
+    let ns = rs
+        .into_qualified()
+        .ok_or(Error::Unqualified)?
+        .namespaced()
+        .ok_or(Error::NotNamespaced)?
+        .to_owned();
+    let urn = Urn::try_from_id(ns.namespace().as_str()).map_err(|e| Error::Urn(Box::new(e)))?;
+    let parsed = Parsed::try_from(ns.strip_namespace())?;
+    Ok((urn, parsed))
+}
@@ -5,11 +5,12 @@
 
 //! Ref rewriting utilities.
 //!
-//! Note that this is an internal API, exported mainly for testing. In
-//! particular, ref name parameters are generally expected to be pre-validated
-//! in some way, and should never be empty.
+//! The functions in this module rewrite names which are expected to be
+//! pre-validated, ie. to have a category. The [`TryFrom`] impls of
+//! [`Owned`] and [`RemoteTracking`] validate arbitrary names instead.
 
 use std::{
+    convert::TryFrom,
     fmt::{self, Display},
     iter,
     ops::Deref,
@@ -21,12 +22,32 @@ use either::{
 };
 use git_ref_format::{name, Component, RefStr, RefString};
 use link_crypto::PeerId;
+use thiserror::Error;
 
 use super::{from_peer_id, lit, parsed};
 use crate::Urn;
 
 pub use git_ref_format::{Namespaced, Qualified};
 
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("`{0}` is namespaced")]
+    Namespaced(RefString),
+
+    #[error("`{0}` is not a remote tracking branch")]
+    NotRemoteTracking(RefString),
+
+    #[error("`{0}` is a remote tracking branch")]
+    RemoteTracking(RefString),
+
+    #[error("`{0}` has no category, expected eg. `refs/heads/<name>`")]
+    NoCategory(RefString),
+
+    #[error("invalid remote peer id")]
+    PeerId(#[from] link_crypto::peer::conversion::Error),
+}
+
 /// Add a (`link`) namespace (of type [`Urn`] to `name`.
 ///
 /// `name` should not already be namespaced, but this condition is not checked.
@@ -99,6 +120,41 @@ pub fn remote_tracking<'a>(
     }
 }
 
+impl<'a> RemoteTracking<'a> {
+    /// The peer this ref is tracking.
+    pub fn remote_id(&self) -> PeerId {
+        let (_refs, _remotes, remote_id, _) = self.0.non_empty_components();
+        remote_id
+            .as_str()
+            .parse()
+            .expect("remote tracking branch has a valid peer id")
+    }
+
+    /// The name of the ref as seen by the [`Self::remote_id`].
+    pub fn to_owned_ref<'b>(&self) -> Owned<'b> {
+        owned(self.0.to_owned()).expect("remote tracking branch has a category")
+    }
+}
+
+/// Validates that the name is `refs/remotes/<peer>/<category>/<name>`.
+impl<'a> TryFrom<Qualified<'a>> for RemoteTracking<'a> {
+    type Error = Error;
+
+    fn try_from(name: Qualified<'a>) -> Result<Self, Self::Error> {
+        use name::str::REMOTES;
+
+        let (_refs, remotes, remote_id, mut tail) = name.non_empty_components();
+        if REMOTES != remotes.as_str() {
+            return Err(Error::NotRemoteTracking(name.to_owned().into_refstring()));
+        }
+        remote_id.as_str().parse::<PeerId>()?;
+        if tail.next().and(tail.next()).is_none() {
+            return Err(Error::NoCategory(name.to_owned().into_refstring()));
+        }
+        Ok(Self(name))
+    }
+}
+
 impl<'a> Deref for RemoteTracking<'a> {
     type Target = Qualified<'a>;
 
@@ -192,6 +248,25 @@ pub fn owned(name: Qualified) -> Option<Owned> {
     }
 }
 
+/// Validates that the name is neither namespaced nor a remote tracking
+/// branch.
+impl<'a> TryFrom<Qualified<'a>> for Owned<'a> {
+    type Error = Error;
+
+    fn try_from(name: Qualified<'a>) -> Result<Self, Self::Error> {
+        use name::str::{NAMESPACES, REMOTES};
+
+        let cat = name.non_empty_components().1;
+        if NAMESPACES == cat.as_str() {
+            Err(Error::Namespaced(name.to_owned().into_refstring()))
+        } else if REMOTES == cat.as_str() {
+            Err(Error::RemoteTracking(name.to_owned().into_refstring()))
+        } else {
+            Ok(Self(name))
+        }
+    }
+}
+
 impl<'a> Deref for Owned<'a> {
     type Target = Qualified<'a>;
 
@@ -6,7 +6,6 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use git_ref_format::RefString;
-use itertools::Itertools as _;
 use link_crypto::PeerId;
 use link_git::protocol::{oid, ObjectId};
 
@@ -42,6 +41,34 @@ pub trait SignedRefs {
         cutoff: usize,
     ) -> Result<Option<Sigrefs<Self::Oid>>, Self::Error>;
 
+    /// Load the signed refs of several remote peers at once.
+    ///
+    /// For each peer, the sigrefs are loaded from the given treeish, or from
+    /// the peer's current `rad/signed_refs` if `None`. Peers for which the
+    /// sigrefs could not be found are absent from the result.
+    ///
+    /// Implementations are encouraged to verify the signatures in batch.
+    fn load_many<'a, I>(
+        &self,
+        of: I,
+        cutoff: usize,
+    ) -> Result<BTreeMap<PeerId, Sigrefs<Self::Oid>>, Self::Error>
+    where
+        I: IntoIterator<Item = (&'a PeerId, Option<ObjectId>)>,
+    {
+        let mut loaded = BTreeMap::new();
+        for (id, at) in of {
+            let sigrefs = match at {
+                None => self.load(id, cutoff)?,
+                Some(at) => self.load_at(at, id, cutoff)?,
+            };
+            if let Some(sigrefs) = sigrefs {
+                loaded.insert(*id, sigrefs);
+            }
+        }
+        Ok(loaded)
+    }
+
     /// Compute and update the sigrefs for the local peer.
     ///
     /// A `None` return value denotes a no-op (ie. the sigrefs were already
@@ -103,23 +130,12 @@ pub fn combined<S>(
 where
     S: SignedRefs,
 {
-    let must = must.iter().map(|id| {
-        SignedRefs::load(s, id, cutoff)
-            .map_err(error::Combine::from)
-            .and_then(|sr| match sr {
-                None => Err(error::Combine::NotFound(*id)),
-                Some(sr) => Ok((id, sr)),
-            })
-    });
-    let may = may
-        .iter()
-        .filter_map(|id| match SignedRefs::load(s, id, cutoff) {
-            Ok(None) => None,
-            Ok(Some(sr)) => Some(Ok((id, sr))),
-            Err(e) => Some(Err(e.into())),
-        });
-
-    must.chain(may).fold_ok(
+    let loaded = SignedRefs::load_many(s, must.union(may).map(|id| (id, None)), cutoff)?;
+    if let Some(missing) = must.iter().find(|id| !loaded.contains_key(id)) {
+        return Err(error::Combine::NotFound(*missing));
+    }
+
+    Ok(loaded.into_iter().fold(
         Combined::default(),
         |mut comb,
          (
@@ -130,9 +146,9 @@ where
                 mut remotes,
             },
         )| {
-            comb.refs.insert(*id, Refs { at, refs });
+            comb.refs.insert(id, Refs { at, refs });
             comb.remotes.append(&mut remotes);
             comb
         },
-    )
+    ))
 }
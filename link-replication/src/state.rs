@@ -252,6 +252,28 @@ where
         self.inner.load_at(treeish, of, cutoff)
     }
 
+    fn load_many<'a, I>(
+        &self,
+        of: I,
+        cutoff: usize,
+    ) -> Result<BTreeMap<PeerId, Sigrefs<Self::Oid>>, Self::Error>
+    where
+        I: IntoIterator<Item = (&'a PeerId, Option<ObjectId>)>,
+    {
+        if self.fetch.sigs.is_empty() {
+            self.inner.load_many(of, cutoff)
+        } else {
+            let tips = self.fetch.sigref_tips();
+            self.inner.load_many(
+                of.into_iter().filter_map(|(id, at)| {
+                    at.or_else(|| tips.get(id).copied())
+                        .map(|tip| (id, Some(tip)))
+                }),
+                cutoff,
+            )
+        }
+    }
+
     fn update(&self) -> Result<Option<Self::Oid>, Self::Error> {
         self.inner.update()
     }
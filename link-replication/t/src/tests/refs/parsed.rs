@@ -205,3 +205,33 @@ fn unknown_cat() {
         "refs/dogs/snoop",
     );
 }
+
+#[test]
+fn roundtrip() {
+    for input in [
+        "refs/rad/id",
+        "refs/heads/main",
+        "refs/remotes/hyn3aar1qghrnjrdi161oks1w3z9s173mxti88ci6qthps8brmp6yo/rad/ids/xyz",
+        "refs/remotes/hyn3aar1qghrnjrdi161oks1w3z9s173mxti88ci6qthps8brmp6yo/heads/a/b",
+    ] {
+        let parsed = parse::<Identity>(input.into()).unwrap();
+        assert_eq!(input, parsed.to_string())
+    }
+}
+
+#[test]
+fn namespaced() {
+    let (urn, parsed) = refs::parse_namespaced::<Identity>(
+        "refs/namespaces/xyz/refs/remotes/hyn3aar1qghrnjrdi161oks1w3z9s173mxti88ci6qthps8brmp6yo/heads/main"
+            .into(),
+    )
+    .unwrap();
+    assert_eq!("xyz", urn.as_ref());
+    assert_eq!(Some(*PEER), parsed.remote);
+    assert_eq!("refs/heads/main", parsed.to_owned().as_str());
+
+    assert!(matches!(
+        refs::parse_namespaced::<Identity>("refs/heads/main".into()),
+        Err(refs::parsed::Error::NotNamespaced)
+    ));
+}
@@ -93,3 +93,64 @@ fn scoped_wanted_is_not_remote() {
         .as_str()
     )
 }
+
+#[test]
+fn remote_tracking_validated() {
+    let rt = refs::RemoteTracking::try_from(
+        refname!("refs/remotes/hyn3aar1qghrnjrdi161oks1w3z9s173mxti88ci6qthps8brmp6yo/heads/main")
+            .into_qualified()
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(*PEER, rt.remote_id());
+    assert_eq!(REFS_HEADS_MAIN.as_str(), rt.to_owned_ref().as_str());
+
+    assert!(matches!(
+        refs::RemoteTracking::try_from(REFS_HEADS_MAIN.clone()),
+        Err(refs::scoped::Error::NotRemoteTracking(_))
+    ));
+    assert!(matches!(
+        refs::RemoteTracking::try_from(
+            refname!("refs/remotes/hyn3aar1qghrnjrdi161oks1w3z9s173mxti88ci6qthps8brmp6yo/main")
+                .into_qualified()
+                .unwrap()
+        ),
+        Err(refs::scoped::Error::NoCategory(_))
+    ));
+    assert!(matches!(
+        refs::RemoteTracking::try_from(
+            refname!("refs/remotes/origin/heads/main")
+                .into_qualified()
+                .unwrap()
+        ),
+        Err(refs::scoped::Error::PeerId(_))
+    ));
+}
+
+#[test]
+fn owned_validated() {
+    assert_eq!(
+        REFS_HEADS_MAIN.as_str(),
+        refs::Owned::try_from(REFS_HEADS_MAIN.clone())
+            .unwrap()
+            .as_str()
+    );
+    assert!(matches!(
+        refs::Owned::try_from(
+            refname!(
+                "refs/remotes/hyn3aar1qghrnjrdi161oks1w3z9s173mxti88ci6qthps8brmp6yo/heads/main"
+            )
+            .into_qualified()
+            .unwrap()
+        ),
+        Err(refs::scoped::Error::RemoteTracking(_))
+    ));
+    assert!(matches!(
+        refs::Owned::try_from(
+            refname!("refs/namespaces/xyz/refs/heads/main")
+                .into_qualified()
+                .unwrap()
+        ),
+        Err(refs::scoped::Error::Namespaced(_))
+    ));
+}
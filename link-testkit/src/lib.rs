@@ -0,0 +1,38 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Multi-peer test networks.
+//!
+//! [`testnet::run`] boots a number of [`librad::net::peer::Peer`]s, each with
+//! its own temporary storage, connects them according to a
+//! [`testnet::Bootstrap`] mode, and waits until enough of them joined the
+//! membership before handing out the running network. The peers are shut
+//! down, and their storage removed, when the [`testnet::Testnet`] is dropped.
+//!
+//! This allows applications built on top of `librad` to test against real
+//! protocol behaviour, for example:
+//!
+//! ```no_run
+//! use std::num::NonZeroUsize;
+//!
+//! use link_testkit::testnet;
+//!
+//! let net = testnet::run(testnet::Config {
+//!     num_peers: NonZeroUsize::new(3).unwrap(),
+//!     min_connected: 3,
+//!     bootstrap: testnet::Bootstrap::from_env(),
+//! })
+//! .unwrap();
+//! net.enter(async {
+//!     let peers = net.peers();
+//!     // ... exercise the peers
+//! });
+//! ```
+//!
+//! Functions waiting for specific protocol events can be found in
+//! [`librad::net::protocol::event::upstream`].
+
+pub mod testnet;
+pub use testnet::{Bootstrap, Config, RunningTestPeer, Testnet};
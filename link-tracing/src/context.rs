@@ -0,0 +1,101 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Task-aware tracing contexts.
+//!
+//! Spans are tied to the task which entered them: a task spawned from within
+//! a span does not inherit it, so its events lose track of which peer or URN
+//! they are about. A [`Context`] carries these identifying fields explicitly,
+//! and is cheap to clone into spawned tasks, which can then create their own
+//! span from it via [`Context::span`] or [`InContext::in_context`].
+
+use std::{fmt::Display, future::Future, sync::Arc};
+
+use tracing::{field, instrument::Instrumented, Instrument as _, Span};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Context {
+    peer: Option<Arc<str>>,
+    remote: Option<Arc<str>>,
+    urn: Option<Arc<str>>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the local peer, see [`crate::fields::PEER`].
+    pub fn with_peer(self, peer: impl Display) -> Self {
+        Self {
+            peer: Some(peer.to_string().into()),
+            ..self
+        }
+    }
+
+    /// Set the remote peer, see [`crate::fields::REMOTE`].
+    pub fn with_remote(self, remote: impl Display) -> Self {
+        Self {
+            remote: Some(remote.to_string().into()),
+            ..self
+        }
+    }
+
+    /// Set the URN, see [`crate::fields::URN`].
+    pub fn with_urn(self, urn: impl Display) -> Self {
+        Self {
+            urn: Some(urn.to_string().into()),
+            ..self
+        }
+    }
+
+    pub fn peer(&self) -> Option<&str> {
+        self.peer.as_deref()
+    }
+
+    pub fn remote(&self) -> Option<&str> {
+        self.remote.as_deref()
+    }
+
+    pub fn urn(&self) -> Option<&str> {
+        self.urn.as_deref()
+    }
+
+    /// Create a span for the task `task`, carrying the fields of this
+    /// context.
+    ///
+    /// All such spans are named "task", with the task name in the
+    /// [`crate::fields::TASK`] field, so they can be told apart from spans of
+    /// individual functions.
+    pub fn span(&self, task: &'static str) -> Span {
+        let span = tracing::info_span!(
+            "task",
+            task,
+            peer = field::Empty,
+            remote_peer = field::Empty,
+            urn = field::Empty
+        );
+        if let Some(peer) = self.peer() {
+            span.record(crate::fields::PEER, &peer);
+        }
+        if let Some(remote) = self.remote() {
+            span.record(crate::fields::REMOTE, &remote);
+        }
+        if let Some(urn) = self.urn() {
+            span.record(crate::fields::URN, &urn);
+        }
+        span
+    }
+}
+
+/// Instrument futures with the span of a [`Context`].
+pub trait InContext: Future + Sized {
+    /// Run `self` in the span created by [`Context::span`].
+    fn in_context(self, cx: &Context, task: &'static str) -> Instrumented<Self> {
+        self.instrument(cx.span(task))
+    }
+}
+
+impl<F: Future> InContext for F {}
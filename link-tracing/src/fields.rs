@@ -0,0 +1,19 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Names of the fields identifying what a span is about.
+//!
+//! Using the same names everywhere allows to filter and correlate traces
+//! across crates and processes, eg. `RUST_LOG='[{urn=rad:git:hnrk...}]'`.
+//! [`crate::Context::span`] records them under these names.
+
+/// The local peer.
+pub const PEER: &str = "peer";
+/// The remote peer a task talks to.
+pub const REMOTE: &str = "remote_peer";
+/// The URN a task operates on.
+pub const URN: &str = "urn";
+/// The name of the task, see [`crate::Context::span`].
+pub const TASK: &str = "task";
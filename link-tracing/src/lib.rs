@@ -0,0 +1,170 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Shared tracing setup.
+//!
+//! [`init`] installs a global subscriber which
+//!
+//! * filters according to `RUST_LOG` (or [`Config::filter`]),
+//! * formats to stderr according to [`Format`],
+//! * samples high-frequency spans via [`sampling::Sampler`], and
+//! * optionally (with the `otlp` feature) exports spans to an OpenTelemetry
+//!   collector, so traces spanning several peers can be correlated.
+//!
+//! Spans should identify what they are about using the names in [`fields`],
+//! and tasks which are spawned should carry a [`Context`].
+
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+use tracing_subscriber::{
+    fmt as tfmt,
+    layer::{Layered, SubscriberExt as _},
+    util::SubscriberInitExt as _,
+    EnvFilter,
+    Layer,
+    Registry,
+};
+
+pub mod context;
+pub use context::{Context, InContext};
+
+pub mod fields;
+pub mod sampling;
+
+#[cfg(feature = "otlp")]
+pub mod otlp;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("invalid filter directive")]
+    Filter(#[from] tracing_subscriber::filter::ParseError),
+
+    #[error("a global subscriber was already installed")]
+    AlreadyInitialised(#[from] tracing_subscriber::util::TryInitError),
+
+    #[cfg(feature = "otlp")]
+    #[error("failed to set up the OTLP exporter")]
+    Otlp(#[from] opentelemetry::trace::TraceError),
+}
+
+/// Output format of the log lines.
+///
+/// See the corresponding [`tracing_subscriber::fmt::format`] types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Full,
+    Pretty,
+    Compact,
+    Json,
+}
+
+impl Format {
+    /// The format set in the `TRACING_FMT` environment variable, if any.
+    pub fn from_env() -> Option<Self> {
+        std::env::var("TRACING_FMT").ok()?.parse().ok()
+    }
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::Full
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Full => "full",
+            Self::Pretty => "pretty",
+            Self::Compact => "compact",
+            Self::Json => "json",
+        })
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(Self::Full),
+            "pretty" => Ok(Self::Pretty),
+            "compact" => Ok(Self::Compact),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown tracing format `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub format: Format,
+    /// Filter directives, see [`EnvFilter`]. If `None`, `RUST_LOG` is used,
+    /// falling back to `info`.
+    pub filter: Option<String>,
+    /// Include thread ids in log lines.
+    pub thread_ids: bool,
+    pub sampling: Vec<sampling::Rule>,
+    #[cfg(feature = "otlp")]
+    pub otlp: Option<otlp::Config>,
+}
+
+/// Flushes pending spans when dropped. Must be held for as long as tracing is
+/// needed, typically until the process exits.
+#[must_use = "dropping the guard stops exporting spans"]
+pub struct Guard {
+    #[cfg(feature = "otlp")]
+    otlp: bool,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if self.otlp {
+            opentelemetry::global::shutdown_tracer_provider()
+        }
+    }
+}
+
+/// Install the global subscriber according to `config`.
+pub fn init(config: Config) -> Result<Guard, Error> {
+    let filter = match config.filter {
+        Some(directives) => EnvFilter::try_new(directives)?,
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    };
+    let sampler = sampling::Sampler::new(config.sampling);
+
+    let fmt = tfmt::layer()
+        .with_writer(std::io::stderr)
+        .with_thread_ids(config.thread_ids);
+    let fmt: Box<dyn Layer<Layered<EnvFilter, Registry>> + Send + Sync> = match config.format {
+        Format::Full => fmt.boxed(),
+        Format::Pretty => fmt.pretty().boxed(),
+        Format::Compact => fmt.compact().boxed(),
+        Format::Json => fmt.json().flatten_event(true).boxed(),
+    };
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt.with_filter(sampler.clone()));
+
+    #[cfg(feature = "otlp")]
+    {
+        let otlp = config
+            .otlp
+            .map(|cfg| otlp::layer(cfg).map(|l| l.with_filter(sampler)))
+            .transpose()?;
+        let enabled = otlp.is_some();
+        registry.with(otlp).try_init()?;
+        Ok(Guard { otlp: enabled })
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    {
+        registry.try_init()?;
+        Ok(Guard {})
+    }
+}
@@ -0,0 +1,51 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Export of spans to an OpenTelemetry collector via OTLP/gRPC.
+//!
+//! Requires a Tokio runtime to be running when [`crate::init`] is called.
+
+use opentelemetry::{
+    sdk::{trace, Resource},
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig as _;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The collector endpoint, eg. `http://localhost:4317`.
+    pub endpoint: String,
+    /// The `service.name` resource attribute, eg. `linkd`.
+    pub service_name: String,
+}
+
+pub(crate) fn layer<S>(
+    config: Config,
+) -> Result<
+    tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry::sdk::trace::Tracer>,
+    opentelemetry::trace::TraceError,
+>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(config.endpoint),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                config.service_name,
+            )])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)?;
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
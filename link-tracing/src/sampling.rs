@@ -0,0 +1,138 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Sampling of high-frequency spans.
+//!
+//! Some spans are created for every message received, eg. for gossip. Under
+//! load, recording all of them is expensive and drowns out everything else.
+//! The [`Sampler`] is a per-layer filter which only lets through every n-th
+//! span matching a [`Rule`]. Spans are counted per callsite, so that a
+//! frequent span does not crowd out a rare one matching the same rule.
+//!
+//! Note that only the spans themselves are sampled: events within a span
+//! which was not sampled are still subject to the other filters.
+
+use std::{
+    collections::HashMap,
+    num::NonZeroU64,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+        PoisonError,
+        RwLock,
+    },
+};
+
+use tracing::{callsite::Identifier, subscriber::Interest, Metadata};
+use tracing_subscriber::layer::{Context, Filter};
+
+/// Sample spans whose target starts with `target`, and whose name is `name`
+/// (if given).
+#[derive(Debug)]
+pub struct Rule {
+    target: String,
+    name: Option<String>,
+    every: NonZeroU64,
+    seen: RwLock<HashMap<Identifier, AtomicU64>>,
+}
+
+impl Rule {
+    /// Keep one in `every` spans with a target starting with `target`.
+    pub fn new(target: impl Into<String>, every: NonZeroU64) -> Self {
+        Self {
+            target: target.into(),
+            name: None,
+            every,
+            seen: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Only sample spans named `name`.
+    pub fn named(self, name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// The spans of the gossip protocol, which are created for every message
+    /// sent or received.
+    pub fn gossip(every: NonZeroU64) -> Vec<Self> {
+        vec![
+            Self::new("librad::net::protocol::io::recv::gossip", every),
+            Self::new("librad::net::protocol::broadcast", every),
+        ]
+    }
+
+    fn matches(&self, meta: &Metadata<'_>) -> bool {
+        meta.target().starts_with(self.target.as_str())
+            && self
+                .name
+                .as_deref()
+                .map_or(true, |name| meta.name() == name)
+    }
+
+    fn sample(&self, meta: &Metadata<'_>) -> bool {
+        let every = self.every.get();
+        let callsite = meta.callsite();
+        {
+            let seen = self.seen.read().unwrap_or_else(PoisonError::into_inner);
+            if let Some(n) = seen.get(&callsite) {
+                return n.fetch_add(1, Ordering::Relaxed) % every == 0;
+            }
+        }
+        let mut seen = self.seen.write().unwrap_or_else(PoisonError::into_inner);
+        seen.entry(callsite)
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed)
+            % every
+            == 0
+    }
+}
+
+/// Per-layer filter sampling spans according to a set of [`Rule`]s.
+///
+/// Spans matching no rule, and all events, are let through. If multiple rules
+/// match, the first one applies.
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    rules: Arc<[Rule]>,
+}
+
+impl Sampler {
+    pub fn new(rules: impl IntoIterator<Item = Rule>) -> Self {
+        Self {
+            rules: rules.into_iter().collect(),
+        }
+    }
+
+    fn rule(&self, meta: &Metadata<'_>) -> Option<&Rule> {
+        if meta.is_span() {
+            self.rules.iter().find(|rule| rule.matches(meta))
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl<S> Filter<S> for Sampler {
+    fn enabled(&self, meta: &Metadata<'_>, _: &Context<'_, S>) -> bool {
+        self.rule(meta).map_or(true, |rule| rule.sample(meta))
+    }
+
+    fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> Interest {
+        if self.rule(meta).is_some() {
+            Interest::sometimes()
+        } else {
+            Interest::always()
+        }
+    }
+}
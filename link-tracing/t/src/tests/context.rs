@@ -0,0 +1,74 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use link_tracing::{fields, Context};
+use tracing::{
+    field::{Field, Visit},
+    span,
+    Subscriber,
+};
+use tracing_subscriber::{layer::SubscriberExt as _, Layer};
+
+#[derive(Clone, Default)]
+struct Fields(Arc<Mutex<BTreeMap<String, String>>>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(field.name().to_owned(), format!("{:?}", value));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Fields {
+    fn on_new_span(
+        &self,
+        attrs: &span::Attributes<'_>,
+        _: &span::Id,
+        _: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        attrs.record(&mut self.clone())
+    }
+
+    fn on_record(
+        &self,
+        _: &span::Id,
+        values: &span::Record<'_>,
+        _: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        values.record(&mut self.clone())
+    }
+}
+
+#[test]
+fn span_carries_fields() {
+    let recorded = Fields::default();
+    let subscriber = tracing_subscriber::registry().with(recorded.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        let cx = Context::new().with_peer("alice").with_urn("rad:git:hnrk");
+        let _span = cx.span("replicate");
+    });
+
+    let recorded = recorded.0.lock().unwrap();
+    assert_eq!(recorded[fields::TASK], "\"replicate\"");
+    assert_eq!(recorded[fields::PEER], "\"alice\"");
+    assert_eq!(recorded[fields::URN], "\"rad:git:hnrk\"");
+    assert!(!recorded.contains_key(fields::REMOTE));
+}
+
+#[test]
+fn builder() {
+    let cx = Context::new().with_remote(42);
+    assert_eq!(cx.remote(), Some("42"));
+    assert_eq!(cx.peer(), None);
+    assert_eq!(cx.clone().with_peer("bob").remote(), Some("42"));
+}
@@ -0,0 +1,86 @@
+// Copyright © 2022 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    num::NonZeroU64,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use link_tracing::sampling::{Rule, Sampler};
+use tracing::{span, Subscriber};
+use tracing_subscriber::{layer::SubscriberExt as _, Layer};
+
+#[derive(Clone, Default)]
+struct Count {
+    spans: Arc<AtomicUsize>,
+    events: Arc<AtomicUsize>,
+}
+
+impl<S: Subscriber> Layer<S> for Count {
+    fn on_new_span(
+        &self,
+        _: &span::Attributes<'_>,
+        _: &span::Id,
+        _: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        self.spans.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_event(&self, _: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+        self.events.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn count_with(sampler: Sampler, f: impl FnOnce()) -> (usize, usize) {
+    let count = Count::default();
+    let subscriber = tracing_subscriber::registry().with(count.clone().with_filter(sampler));
+    tracing::subscriber::with_default(subscriber, f);
+    (
+        count.spans.load(Ordering::Relaxed),
+        count.events.load(Ordering::Relaxed),
+    )
+}
+
+#[test]
+fn samples_matching_spans() {
+    let every = NonZeroU64::new(3).unwrap();
+    let (spans, events) = count_with(Sampler::new(vec![Rule::new(module_path!(), every)]), || {
+        for _ in 0..9 {
+            let _span = tracing::info_span!("hot").entered();
+            tracing::info!("event");
+        }
+    });
+
+    assert_eq!(spans, 3);
+    assert_eq!(events, 9);
+}
+
+#[test]
+fn ignores_other_spans() {
+    let every = NonZeroU64::new(3).unwrap();
+    let sampler = Sampler::new(vec![Rule::new(module_path!(), every).named("hot")]);
+    let (spans, _) = count_with(sampler, || {
+        for _ in 0..9 {
+            let _span = tracing::info_span!("cold").entered();
+        }
+    });
+
+    assert_eq!(spans, 9);
+}
+
+#[test]
+fn samples_per_callsite() {
+    let every = NonZeroU64::new(2).unwrap();
+    let (spans, _) = count_with(Sampler::new(vec![Rule::new(module_path!(), every)]), || {
+        tracing::info_span!("hot").in_scope(|| {});
+        tracing::info_span!("rare").in_scope(|| {});
+    });
+
+    // The first span of each callsite is sampled
+    assert_eq!(spans, 2);
+}